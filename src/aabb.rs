@@ -0,0 +1,88 @@
+use cgmath::{InnerSpace, Matrix4, Point3, Transform, Vector3};
+
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    pub fn empty() -> Self {
+        Self {
+            min: Point3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Point3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn grow(&mut self, p: [f32; 3]) {
+        self.min.x = self.min.x.min(p[0]);
+        self.min.y = self.min.y.min(p[1]);
+        self.min.z = self.min.z.min(p[2]);
+        self.max.x = self.max.x.max(p[0]);
+        self.max.y = self.max.y.max(p[1]);
+        self.max.z = self.max.z.max(p[2]);
+    }
+
+    pub fn union(&mut self, other: &Aabb) {
+        if other.is_empty() {
+            return;
+        }
+        self.grow(other.min.into());
+        self.grow(other.max.into());
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        Point3::new(
+            (self.min.x + self.max.x) * 0.5,
+            (self.min.y + self.max.y) * 0.5,
+            (self.min.z + self.max.z) * 0.5,
+        )
+    }
+
+    pub fn extent(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+
+    /// Box that contains everything, for geometry whose extent is not tracked.
+    pub fn infinite() -> Self {
+        Self {
+            min: Point3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+            max: Point3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+        }
+    }
+
+    /// False for [`Aabb::infinite`] and empty boxes.
+    pub fn is_finite(&self) -> bool {
+        !self.is_empty() && self.min.x.is_finite() && self.max.x.is_finite()
+    }
+
+    pub fn intersects_sphere(&self, center: Point3<f32>, radius: f32) -> bool {
+        let closest = Point3::new(
+            center.x.clamp(self.min.x, self.max.x),
+            center.y.clamp(self.min.y, self.max.y),
+            center.z.clamp(self.min.z, self.max.z),
+        );
+        (closest - center).magnitude2() <= radius * radius
+    }
+
+    /// Box around the eight corners of this one moved by `m`.
+    pub fn transformed(&self, m: Matrix4<f32>) -> Self {
+        let mut bounds = Self::empty();
+        if self.is_empty() {
+            return bounds;
+        }
+        for corner in 0..8 {
+            let pick = |axis: usize| if corner & (1 << axis) == 0 { self.min[axis] } else { self.max[axis] };
+            bounds.grow(m.transform_point(Point3::new(pick(0), pick(1), pick(2))).into());
+        }
+        bounds
+    }
+
+    pub fn radius(&self) -> f32 {
+        (self.extent().magnitude() * 0.5).max(1.0)
+    }
+}
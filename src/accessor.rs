@@ -0,0 +1,217 @@
+use gltf::accessor::sparse::IndexType;
+use gltf::accessor::{Accessor, DataType};
+
+fn view_slice<'b>(view: &gltf::buffer::View, buffers: &'b [Vec<u8>]) -> Option<&'b [u8]> {
+    let data = buffers.get(view.buffer().index())?;
+    data.get(view.offset()..view.offset() + view.length())
+}
+
+fn component(data: &[u8], offset: usize, ty: DataType, normalized: bool) -> Option<f64> {
+    let bytes = data.get(offset..offset + ty.size())?;
+    let value = match ty {
+        DataType::I8 => {
+            let v = bytes[0] as i8 as f64;
+            if normalized { (v / 127.0).max(-1.0) } else { v }
+        }
+        DataType::U8 => {
+            let v = bytes[0] as f64;
+            if normalized { v / 255.0 } else { v }
+        }
+        DataType::I16 => {
+            let v = i16::from_le_bytes([bytes[0], bytes[1]]) as f64;
+            if normalized { (v / 32767.0).max(-1.0) } else { v }
+        }
+        DataType::U16 => {
+            let v = u16::from_le_bytes([bytes[0], bytes[1]]) as f64;
+            if normalized { v / 65535.0 } else { v }
+        }
+        DataType::U32 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+        DataType::F32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+    };
+    Some(value)
+}
+
+/// Reads every element of `accessor` as a flat list of components, applying the sparse
+/// substitutions on top of the base view (or on top of zeros when there is no view).
+/// Sparse indices do not have to be sorted; out-of-range ones are skipped.
+fn read_components(accessor: &Accessor, buffers: &[Vec<u8>], normalize: bool) -> Option<Vec<f64>> {
+    let ty = accessor.data_type();
+    let n = accessor.dimensions().multiplicity();
+    let count = accessor.count();
+    let normalized = normalize && accessor.normalized();
+    let mut out = vec![0.0; count * n];
+
+    if let Some(view) = accessor.view() {
+        let data = view_slice(&view, buffers)?;
+        let stride = view.stride().unwrap_or(ty.size() * n);
+        for i in 0..count {
+            for c in 0..n {
+                let offset = accessor.offset() + i * stride + c * ty.size();
+                out[i * n + c] = component(data, offset, ty, normalized)?;
+            }
+        }
+    }
+
+    if let Some(sparse) = accessor.sparse() {
+        let indices = sparse.indices();
+        let index_data = view_slice(&indices.view(), buffers)?;
+        let index_type = match indices.index_type() {
+            IndexType::U8 => DataType::U8,
+            IndexType::U16 => DataType::U16,
+            IndexType::U32 => DataType::U32,
+        };
+        let values = sparse.values();
+        let value_data = view_slice(&values.view(), buffers)?;
+
+        let mut skipped = 0;
+        for k in 0..sparse.count() {
+            let target = component(index_data, indices.offset() + k * index_type.size(), index_type, false)? as usize;
+            if target >= count {
+                skipped += 1;
+                continue;
+            }
+            for c in 0..n {
+                let offset = values.offset() + (k * n + c) * ty.size();
+                out[target * n + c] = component(value_data, offset, ty, normalized)?;
+            }
+        }
+        if skipped > 0 {
+            log::warn!(
+                "Accessor {}: {} sparse indices out of range (count {})",
+                accessor.index(),
+                skipped,
+                count
+            );
+        }
+    }
+
+    Some(out)
+}
+
+pub fn read_f32<const N: usize>(accessor: &Accessor, buffers: &[Vec<u8>]) -> Option<Vec<[f32; N]>> {
+    if accessor.dimensions().multiplicity() != N {
+        return None;
+    }
+    let flat = read_components(accessor, buffers, true)?;
+    Some(
+        flat.chunks_exact(N)
+            .map(|c| std::array::from_fn(|i| c[i] as f32))
+            .collect(),
+    )
+}
+
+pub fn read_u32<const N: usize>(accessor: &Accessor, buffers: &[Vec<u8>]) -> Option<Vec<[u32; N]>> {
+    if accessor.dimensions().multiplicity() != N || accessor.data_type() == DataType::F32 {
+        return None;
+    }
+    let flat = read_components(accessor, buffers, false)?;
+    Some(
+        flat.chunks_exact(N)
+            .map(|c| std::array::from_fn(|i| c[i] as u32))
+            .collect(),
+    )
+}
+
+pub fn read_mat4(accessor: &Accessor, buffers: &[Vec<u8>]) -> Option<Vec<[[f32; 4]; 4]>> {
+    let flat = read_f32::<16>(accessor, buffers)?;
+    Some(
+        flat.iter()
+            .map(|m| std::array::from_fn(|col| std::array::from_fn(|row| m[col * 4 + row])))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn f32_bytes(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    /// A document whose only buffer is `data`, split into `views` of (offset, length),
+    /// with `accessor` as its only accessor.
+    fn document(data: &[u8], views: &[(usize, usize)], accessor: serde_json::Value) -> gltf::Document {
+        let views: Vec<_> = views
+            .iter()
+            .map(|(offset, length)| serde_json::json!({ "buffer": 0, "byteOffset": offset, "byteLength": length }))
+            .collect();
+        let json = serde_json::json!({
+            "asset": { "version": "2.0" },
+            "buffers": [{ "byteLength": data.len() }],
+            "bufferViews": views,
+            "accessors": [accessor],
+        });
+        gltf::Gltf::from_slice(json.to_string().as_bytes()).expect("test document").document
+    }
+
+    /// Three base vectors, then sparse indices 2 and 0 (unsorted) as u16, then their values.
+    fn sparse_data() -> Vec<u8> {
+        let mut data = f32_bytes(&[1.0, 1.0, 1.0, 2.0, 2.0, 2.0, 3.0, 3.0, 3.0]);
+        data.extend([2u16, 0].iter().flat_map(|i| i.to_le_bytes()));
+        data.extend(f32_bytes(&[30.0, 31.0, 32.0, 10.0, 11.0, 12.0]));
+        data
+    }
+
+    fn sparse(count: usize, indices_view: usize, values_view: usize) -> serde_json::Value {
+        serde_json::json!({
+            "count": count,
+            "indices": { "bufferView": indices_view, "componentType": 5123 },
+            "values": { "bufferView": values_view },
+        })
+    }
+
+    #[test]
+    fn sparse_values_replace_the_base_view() {
+        let data = sparse_data();
+        let doc = document(
+            &data,
+            &[(0, 36), (36, 4), (40, 24)],
+            serde_json::json!({
+                "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                "sparse": sparse(2, 1, 2),
+            }),
+        );
+        let read = read_f32::<3>(&doc.accessors().next().unwrap(), &[data]).unwrap();
+        assert_eq!(read, [[10.0, 11.0, 12.0], [2.0, 2.0, 2.0], [30.0, 31.0, 32.0]]);
+    }
+
+    #[test]
+    fn sparse_without_a_view_starts_from_zeros() {
+        let data = sparse_data();
+        let doc = document(
+            &data,
+            &[(36, 4), (40, 24)],
+            serde_json::json!({ "componentType": 5126, "count": 3, "type": "VEC3", "sparse": sparse(2, 0, 1) }),
+        );
+        let read = read_f32::<3>(&doc.accessors().next().unwrap(), &[data]).unwrap();
+        assert_eq!(read, [[10.0, 11.0, 12.0], [0.0; 3], [30.0, 31.0, 32.0]]);
+    }
+
+    #[test]
+    fn out_of_range_sparse_indices_are_skipped() {
+        let data = sparse_data();
+        // Index 2 is past the end of a two-element accessor.
+        let doc = document(
+            &data,
+            &[(0, 24), (36, 4), (40, 24)],
+            serde_json::json!({
+                "bufferView": 0, "componentType": 5126, "count": 2, "type": "VEC3",
+                "sparse": sparse(2, 1, 2),
+            }),
+        );
+        let read = read_f32::<3>(&doc.accessors().next().unwrap(), &[data]).unwrap();
+        assert_eq!(read, [[10.0, 11.0, 12.0], [2.0, 2.0, 2.0]]);
+    }
+
+    #[test]
+    fn truncated_buffers_read_as_none() {
+        let data = sparse_data();
+        let doc = document(
+            &data,
+            &[(0, 36)],
+            serde_json::json!({ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }),
+        );
+        assert!(read_f32::<3>(&doc.accessors().next().unwrap(), &[data[..20].to_vec()]).is_none());
+    }
+}
@@ -0,0 +1,515 @@
+use cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, Quaternion, SquareMatrix, Vector3, Vector4, VectorSpace};
+
+use crate::model::Vertex;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    CubicSpline,
+}
+
+impl Interpolation {
+    /// Output values a sampler has per keyframe: cubic splines add in and out tangents.
+    pub fn values_per_key(self) -> usize {
+        match self {
+            Self::Step | Self::Linear => 1,
+            Self::CubicSpline => 3,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum ChannelValues {
+    Translation(Vec<Vector3<f32>>),
+    Rotation(Vec<Quaternion<f32>>),
+    Scale(Vec<Vector3<f32>>),
+}
+
+impl ChannelValues {
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Translation(v) | Self::Scale(v) => v.len(),
+            Self::Rotation(v) => v.len(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Channel {
+    pub node: usize,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    pub values: ChannelValues,
+}
+
+impl Channel {
+    /// Whether there are keyframes and exactly the values their interpolation needs, so
+    /// sampling stays in bounds.
+    pub fn is_complete(&self) -> bool {
+        !self.times.is_empty() && self.values.len() == self.times.len() * self.interpolation.values_per_key()
+    }
+}
+
+#[derive(Clone)]
+pub struct AnimationClip {
+    pub name: String,
+    pub duration: f32,
+    pub channels: Vec<Channel>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct NodeTransform {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl NodeTransform {
+    pub fn from_decomposed((t, r, s): ([f32; 3], [f32; 4], [f32; 3])) -> Self {
+        Self {
+            translation: Vector3::from(t),
+            rotation: Quaternion::new(r[3], r[0], r[1], r[2]),
+            scale: Vector3::from(s),
+        }
+    }
+
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+#[derive(Clone)]
+pub struct Skin {
+    pub joints: Vec<usize>,
+    pub inverse_bind_matrices: Vec<Matrix4<f32>>,
+}
+
+/// Per-model animation data: the node hierarchy in rest pose, the clips that drive it and
+/// the skins used by skinned primitives.
+#[derive(Clone)]
+pub struct AnimationSet {
+    pub parents: Vec<Option<usize>>,
+    pub rest: Vec<NodeTransform>,
+    pub clips: Vec<AnimationClip>,
+    pub skins: Vec<Skin>,
+}
+
+fn segment(times: &[f32], t: f32) -> (usize, usize, f32, f32) {
+    let last = times.len() - 1;
+    if t <= times[0] {
+        return (0, 0, 0.0, 0.0);
+    }
+    if t >= times[last] {
+        return (last, last, 0.0, 0.0);
+    }
+    let i = times.partition_point(|&k| k <= t) - 1;
+    let dt = times[i + 1] - times[i];
+    let u = if dt > 0.0 { (t - times[i]) / dt } else { 0.0 };
+    (i, i + 1, u, dt)
+}
+
+fn hermite<T>(p0: T, m0: T, p1: T, m1: T, u: f32, dt: f32) -> T
+where
+    T: Copy + std::ops::Mul<f32, Output = T> + std::ops::Add<Output = T>,
+{
+    let u2 = u * u;
+    let u3 = u2 * u;
+    p0 * (2.0 * u3 - 3.0 * u2 + 1.0)
+        + m0 * ((u3 - 2.0 * u2 + u) * dt)
+        + p1 * (-2.0 * u3 + 3.0 * u2)
+        + m1 * ((u3 - u2) * dt)
+}
+
+fn sample_vec3(values: &[Vector3<f32>], interpolation: Interpolation, times: &[f32], t: f32) -> Vector3<f32> {
+    let (a, b, u, dt) = segment(times, t);
+    match interpolation {
+        Interpolation::Step => values[a],
+        Interpolation::Linear => values[a].lerp(values[b], u),
+        Interpolation::CubicSpline => {
+            if a == b {
+                return values[a * 3 + 1];
+            }
+            hermite(values[a * 3 + 1], values[a * 3 + 2], values[b * 3 + 1], values[b * 3], u, dt)
+        }
+    }
+}
+
+fn sample_quat(values: &[Quaternion<f32>], interpolation: Interpolation, times: &[f32], t: f32) -> Quaternion<f32> {
+    let (a, b, u, dt) = segment(times, t);
+    match interpolation {
+        Interpolation::Step => values[a],
+        Interpolation::Linear => {
+            let q0 = values[a];
+            let mut q1 = values[b];
+            if q0.dot(q1) < 0.0 {
+                q1 = -q1;
+            }
+            q0.nlerp(q1, u)
+        }
+        Interpolation::CubicSpline => {
+            if a == b {
+                return values[a * 3 + 1].normalize();
+            }
+            hermite(values[a * 3 + 1], values[a * 3 + 2], values[b * 3 + 1], values[b * 3], u, dt).normalize()
+        }
+    }
+}
+
+impl AnimationClip {
+    pub fn sample(&self, t: f32, pose: &mut [NodeTransform]) {
+        for channel in &self.channels {
+            if channel.times.is_empty() {
+                continue;
+            }
+            let Some(node) = pose.get_mut(channel.node) else {
+                continue;
+            };
+            match &channel.values {
+                ChannelValues::Translation(v) => {
+                    node.translation = sample_vec3(v, channel.interpolation, &channel.times, t)
+                }
+                ChannelValues::Rotation(v) => {
+                    node.rotation = sample_quat(v, channel.interpolation, &channel.times, t)
+                }
+                ChannelValues::Scale(v) => node.scale = sample_vec3(v, channel.interpolation, &channel.times, t),
+            }
+        }
+    }
+}
+
+impl AnimationSet {
+    pub fn is_empty(&self) -> bool {
+        self.clips.is_empty()
+    }
+
+    pub fn world_matrices(&self, pose: &[NodeTransform], root: Matrix4<f32>) -> Vec<Matrix4<f32>> {
+        let mut world: Vec<Option<Matrix4<f32>>> = vec![None; pose.len()];
+        fn resolve(
+            i: usize,
+            parents: &[Option<usize>],
+            pose: &[NodeTransform],
+            root: Matrix4<f32>,
+            world: &mut [Option<Matrix4<f32>>],
+        ) -> Matrix4<f32> {
+            if let Some(m) = world[i] {
+                return m;
+            }
+            let parent = match parents[i] {
+                Some(p) => resolve(p, parents, pose, root, world),
+                None => root,
+            };
+            let m = parent * pose[i].matrix();
+            world[i] = Some(m);
+            m
+        }
+        for i in 0..pose.len() {
+            resolve(i, &self.parents, pose, root, &mut world);
+        }
+        world.into_iter().map(|m| m.unwrap_or(root)).collect()
+    }
+
+    /// Nodes whose world transform changes when any clip plays, including descendants of
+    /// animated nodes.
+    pub fn animated_nodes(&self) -> Vec<bool> {
+        let mut animated = vec![false; self.parents.len()];
+        for clip in &self.clips {
+            for channel in &clip.channels {
+                if let Some(a) = animated.get_mut(channel.node) {
+                    *a = true;
+                }
+            }
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for i in 0..self.parents.len() {
+                if !animated[i] && self.parents[i].is_some_and(|p| animated[p]) {
+                    animated[i] = true;
+                    changed = true;
+                }
+            }
+        }
+        animated
+    }
+}
+
+fn normal_matrix(m: Matrix4<f32>) -> Matrix3<f32> {
+    let a = Matrix3::new(
+        m.x.x, m.x.y, m.x.z,
+        m.y.x, m.y.y, m.y.z,
+        m.z.x, m.z.y, m.z.z,
+    );
+    a.invert().unwrap_or(Matrix3::from_scale(1.0)).transpose()
+}
+
+/// CPU-side animation state for one mesh whose vertices were baked into world space at
+/// load time.
+pub struct AnimatedMesh {
+    pub scene_mesh: usize,
+    pub node: usize,
+    pub skin: Option<usize>,
+    pub rest_vertices: Vec<Vertex>,
+    pub rest_tangents: Vec<[f32; 4]>,
+    pub joints: Vec<[u16; 4]>,
+    pub weights: Vec<[f32; 4]>,
+}
+
+pub struct PosedMesh {
+    pub scene_mesh: usize,
+    /// Empty for rigid meshes, which move with `transform` alone.
+    pub vertices: Vec<Vertex>,
+    pub tangents: Vec<[f32; 4]>,
+    /// Object transform, from the rest pose the vertices were baked in.
+    pub transform: Matrix4<f32>,
+}
+
+fn transform_dir(m: &Matrix4<f32>, v: Vector3<f32>) -> Vector3<f32> {
+    (m * v.extend(0.0)).truncate()
+}
+
+impl AnimatedMesh {
+    pub fn pose(&self, set: &AnimationSet, rest_world: &[Matrix4<f32>], world: &[Matrix4<f32>]) -> PosedMesh {
+        let to_local = rest_world[self.node].invert().unwrap_or(Matrix4::from_scale(1.0));
+
+        let skin = self.skin.and_then(|s| set.skins.get(s));
+        let joint_mats: Vec<Matrix4<f32>> = skin
+            .map(|skin| {
+                skin.joints
+                    .iter()
+                    .zip(skin.inverse_bind_matrices.iter())
+                    .map(|(&j, ibm)| world[j] * ibm)
+                    .collect()
+            })
+            .unwrap_or_default();
+        if joint_mats.is_empty() {
+            return PosedMesh {
+                scene_mesh: self.scene_mesh,
+                vertices: Vec::new(),
+                tangents: Vec::new(),
+                transform: world[self.node] * to_local,
+            };
+        }
+
+        let mut tangents = Vec::with_capacity(self.rest_tangents.len());
+        let vertices = self
+            .rest_vertices
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                let p = Vector4::new(v.position[0], v.position[1], v.position[2], 1.0);
+                let n = Vector3::from(v.normal);
+                let lp = to_local * p;
+                let ln = normal_matrix(to_local) * n;
+                let joints = self.joints.get(i).copied().unwrap_or([0; 4]);
+                let weights = self.weights.get(i).copied().unwrap_or([1.0, 0.0, 0.0, 0.0]);
+                let mut m = Matrix4::from_scale(0.0);
+                for k in 0..4 {
+                    if weights[k] > 0.0 {
+                        if let Some(jm) = joint_mats.get(joints[k] as usize) {
+                            m += jm * weights[k];
+                        }
+                    }
+                }
+                let (wp, wn) = (m * lp, normal_matrix(m) * ln);
+                let m = m * to_local;
+                if let Some(t) = self.rest_tangents.get(i) {
+                    let wt = transform_dir(&m, Vector3::new(t[0], t[1], t[2]));
+                    let wt = if wt.magnitude2() > 0.0 { wt.normalize() } else { wt };
+                    tangents.push([wt.x, wt.y, wt.z, t[3]]);
+                }
+                let wn = if wn.magnitude2() > 0.0 { wn.normalize() } else { Vector3::unit_y() };
+                Vertex {
+                    position: [wp.x, wp.y, wp.z],
+                    normal: [wn.x, wn.y, wn.z],
+                    tex_coords: v.tex_coords,
+                }
+            })
+            .collect();
+
+        PosedMesh {
+            scene_mesh: self.scene_mesh,
+            vertices,
+            tangents,
+            transform: Matrix4::from_scale(1.0),
+        }
+    }
+
+    pub fn bone_weights(&self, joint: usize) -> Vec<f32> {
+        (0..self.rest_vertices.len())
+            .map(|i| {
+                let joints = self.joints.get(i).copied().unwrap_or([0; 4]);
+                let weights = self.weights.get(i).copied().unwrap_or([0.0; 4]);
+                (0..4)
+                    .filter(|&k| joints[k] as usize == joint)
+                    .map(|k| weights[k])
+                    .sum()
+            })
+            .collect()
+    }
+}
+
+pub struct ModelAnimation {
+    pub set: AnimationSet,
+    pub root: Matrix4<f32>,
+    pub rest_world: Vec<Matrix4<f32>>,
+    pub meshes: Vec<AnimatedMesh>,
+}
+
+impl ModelAnimation {
+    pub fn new(set: AnimationSet, root: Matrix4<f32>, meshes: Vec<AnimatedMesh>) -> Self {
+        let rest_world = set.world_matrices(&set.rest, root);
+        Self {
+            set,
+            root,
+            rest_world,
+            meshes,
+        }
+    }
+
+    pub fn pose(&self, clip: usize, time: f32) -> Vec<PosedMesh> {
+        let mut pose = self.set.rest.clone();
+        if let Some(clip) = self.set.clips.get(clip) {
+            clip.sample(time, &mut pose);
+        }
+        let world = self.set.world_matrices(&pose, self.root);
+        self.meshes
+            .iter()
+            .map(|m| m.pose(&self.set, &self.rest_world, &world))
+            .collect()
+    }
+
+    pub fn joint_count(&self) -> usize {
+        self.set.skins.iter().map(|s| s.joints.len()).max().unwrap_or(0)
+    }
+}
+
+pub struct AnimationPlayer {
+    pub clips: Vec<(usize, usize)>,
+    pub current: usize,
+    pub time: f32,
+    pub speed: f32,
+    pub looping: bool,
+    pub playing: bool,
+    pub show_weights: bool,
+    pub weight_joint: usize,
+    pub dirty: bool,
+}
+
+impl AnimationPlayer {
+    pub fn new(models: &[ModelAnimation]) -> Self {
+        let clips = models
+            .iter()
+            .enumerate()
+            .flat_map(|(m, anim)| (0..anim.set.clips.len()).map(move |c| (m, c)))
+            .collect();
+        Self {
+            clips,
+            current: 0,
+            time: 0.0,
+            speed: 1.0,
+            looping: true,
+            playing: true,
+            show_weights: false,
+            weight_joint: 0,
+            dirty: true,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clips.is_empty()
+    }
+
+    pub fn duration(&self, models: &[ModelAnimation]) -> f32 {
+        self.clips
+            .get(self.current)
+            .and_then(|&(m, c)| models[m].set.clips.get(c))
+            .map(|c| c.duration)
+            .unwrap_or(0.0)
+    }
+
+    pub fn advance(&mut self, dt: f32, models: &[ModelAnimation]) {
+        if !self.playing || self.is_empty() {
+            return;
+        }
+        let duration = self.duration(models);
+        self.time += dt * self.speed;
+        if duration <= 0.0 {
+            self.time = 0.0;
+        } else if self.looping {
+            self.time = self.time.rem_euclid(duration);
+        } else if self.time >= duration || self.time <= 0.0 {
+            self.time = self.time.clamp(0.0, duration);
+            self.playing = false;
+        }
+        self.dirty = true;
+    }
+
+    pub fn select(&mut self, delta: i32) {
+        if self.is_empty() {
+            return;
+        }
+        let n = self.clips.len() as i32;
+        self.current = (self.current as i32 + delta).rem_euclid(n) as usize;
+        self.time = 0.0;
+        self.dirty = true;
+    }
+
+    pub fn scrub(&mut self, delta: f32, models: &[ModelAnimation]) {
+        let duration = self.duration(models);
+        self.playing = false;
+        self.time = if self.looping && duration > 0.0 {
+            (self.time + delta).rem_euclid(duration)
+        } else {
+            (self.time + delta).clamp(0.0, duration)
+        };
+        self.dirty = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(interpolation: Interpolation, times: &[f32], values: &[[f32; 3]]) -> Channel {
+        Channel {
+            node: 0,
+            interpolation,
+            times: times.to_vec(),
+            values: ChannelValues::Translation(values.iter().map(|&v| Vector3::from(v)).collect()),
+        }
+    }
+
+    #[test]
+    fn values_must_match_the_keyframes() {
+        let two = [[0.0; 3], [1.0; 3]];
+        assert!(channel(Interpolation::Linear, &[0.0, 1.0], &two).is_complete());
+        assert!(!channel(Interpolation::Linear, &[0.0, 1.0, 2.0], &two).is_complete());
+        assert!(!channel(Interpolation::Step, &[], &[]).is_complete());
+        // A cubic spline needs an in tangent, a value and an out tangent per key.
+        assert!(!channel(Interpolation::CubicSpline, &[0.0, 1.0], &two).is_complete());
+        assert!(channel(Interpolation::CubicSpline, &[0.0, 1.0], &[[0.0; 3]; 6]).is_complete());
+    }
+
+    #[test]
+    fn samples_between_and_past_the_keys() {
+        let times = [0.0, 2.0];
+        let values = [Vector3::new(0.0, 0.0, 0.0), Vector3::new(4.0, 2.0, 0.0)];
+        assert_eq!(sample_vec3(&values, Interpolation::Linear, &times, 1.0), Vector3::new(2.0, 1.0, 0.0));
+        assert_eq!(sample_vec3(&values, Interpolation::Step, &times, 1.9), values[0]);
+        assert_eq!(sample_vec3(&values, Interpolation::Linear, &times, -1.0), values[0]);
+        assert_eq!(sample_vec3(&values, Interpolation::Linear, &times, 5.0), values[1]);
+    }
+
+    #[test]
+    fn cubic_splines_pass_through_their_values() {
+        let times = [0.0, 1.0];
+        let zero = Vector3::new(0.0, 0.0, 0.0);
+        let values = [zero, Vector3::new(1.0, 0.0, 0.0), zero, zero, Vector3::new(3.0, 0.0, 0.0), zero];
+        assert_eq!(sample_vec3(&values, Interpolation::CubicSpline, &times, 0.0), values[1]);
+        assert_eq!(sample_vec3(&values, Interpolation::CubicSpline, &times, 1.0), values[4]);
+        assert_eq!(sample_vec3(&values, Interpolation::CubicSpline, &times, 0.5), Vector3::new(2.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,198 @@
+//! One place asset files register to be reloaded when they change on disk.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use notify::event::{AccessKind, AccessMode};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// How long a changed file has to stay unchanged before it reloads, so an editor that
+/// writes a file in several steps reloads it once.
+const SETTLE_TIME: Duration = Duration::from_millis(300);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AssetKind {
+    Config,
+    Shader,
+    Environment,
+    Texture,
+    Model,
+}
+
+impl AssetKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Config => "config",
+            Self::Shader => "shader",
+            Self::Environment => "environment map",
+            Self::Texture => "texture",
+            Self::Model => "model",
+        }
+    }
+}
+
+/// Reloads an asset of `T` from the file at the path.
+pub type ReloadHandler<T> = fn(&mut T, &Path);
+
+struct WatchedFile<T> {
+    kind: AssetKind,
+    path: PathBuf,
+    /// `path` made absolute, as change events name it.
+    absolute: PathBuf,
+    handler: ReloadHandler<T>,
+    /// When the file last changed, while the reload waits for it to settle.
+    changed_at: Option<Instant>,
+}
+
+/// Watches the directories of asset files for changes the OS reports and hands back the
+/// reload handler of each file that changed.
+pub struct AssetWatcher<T> {
+    files: Vec<WatchedFile<T>>,
+    /// None when the OS watcher could not start, and nothing reloads.
+    watcher: Option<RecommendedWatcher>,
+    events: Receiver<notify::Result<notify::Event>>,
+    /// Directories the watcher is told about. Editors often save by replacing the file,
+    /// which a watch on the file itself would lose.
+    dirs: HashSet<PathBuf>,
+}
+
+impl<T> AssetWatcher<T> {
+    pub fn new() -> Self {
+        let (sender, events) = mpsc::channel();
+        let watcher = notify::recommended_watcher(sender)
+            .map_err(|e| log::warn!("Asset files won't reload when they change: {}", e))
+            .ok();
+        Self {
+            files: Vec::new(),
+            watcher,
+            events,
+            dirs: HashSet::new(),
+        }
+    }
+
+    /// Calls `handler` when the file at `path` changes, replacing any handler it had as
+    /// the same kind of asset.
+    pub fn watch(&mut self, kind: AssetKind, path: &Path, handler: ReloadHandler<T>) {
+        self.files.retain(|f| f.kind != kind || f.path != path);
+        self.files.push(WatchedFile {
+            kind,
+            path: path.to_path_buf(),
+            absolute: std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+            handler,
+            changed_at: None,
+        });
+        self.update_dirs();
+    }
+
+    /// Stops watching every file of `kind`.
+    pub fn unwatch(&mut self, kind: AssetKind) {
+        self.files.retain(|f| f.kind != kind);
+        self.update_dirs();
+    }
+
+    /// Watches the directories holding the files, and no others.
+    fn update_dirs(&mut self) {
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+        let wanted: HashSet<PathBuf> =
+            self.files.iter().filter_map(|f| f.absolute.parent()).map(Path::to_path_buf).collect();
+        for dir in self.dirs.difference(&wanted) {
+            // Fails when the directory is already gone, which stops the watch anyway.
+            let _ = watcher.unwatch(dir);
+        }
+        self.dirs.retain(|dir| wanted.contains(dir));
+        for dir in wanted {
+            if self.dirs.contains(&dir) {
+                continue;
+            }
+            match watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    self.dirs.insert(dir);
+                }
+                Err(e) => log::warn!("Can't watch {} for changes: {}", dir.display(), e),
+            }
+        }
+    }
+
+    /// The handlers to call, with the path to give each, for files that changed and have
+    /// since settled. A file that went missing waits until it is back.
+    pub fn poll(&mut self) -> Vec<(ReloadHandler<T>, PathBuf)> {
+        let now = Instant::now();
+        for event in self.events.try_iter() {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    log::warn!("Watching asset files: {}", e);
+                    continue;
+                }
+            };
+            // Reads, including the reloads' own, don't count.
+            let written = match event.kind {
+                EventKind::Access(access) => access == AccessKind::Close(AccessMode::Write),
+                _ => true,
+            };
+            if !written {
+                continue;
+            }
+            for file in self.files.iter_mut().filter(|f| event.paths.contains(&f.absolute)) {
+                file.changed_at = Some(now);
+            }
+        }
+        let mut due = Vec::new();
+        for file in &mut self.files {
+            if file.changed_at.is_some_and(|t| now.duration_since(t) >= SETTLE_TIME) && file.path.exists() {
+                file.changed_at = None;
+                log::info!("Reloading {} {}", file.kind.name(), file.path.display());
+                due.push((file.handler, file.path.clone()));
+            }
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(reloads: &mut u32, _: &Path) {
+        *reloads += 1;
+    }
+
+    /// Polls like a frame loop would until a reload comes or `wait` runs out.
+    fn reloads_within(watcher: &mut AssetWatcher<u32>, wait: Duration) -> u32 {
+        let mut reloads = 0;
+        let deadline = Instant::now() + wait;
+        while reloads == 0 && Instant::now() < deadline {
+            for (handler, path) in watcher.poll() {
+                handler(&mut reloads, &path);
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        reloads
+    }
+
+    #[test]
+    fn writes_reload_once_they_settle() {
+        let dir = std::env::temp_dir().join(format!("dusk-watcher-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("texture.png");
+        std::fs::write(&path, "a").unwrap();
+        let mut watcher = AssetWatcher::new();
+        watcher.watch(AssetKind::Texture, &path, count);
+        assert_eq!(reloads_within(&mut watcher, SETTLE_TIME * 2), 0);
+
+        std::fs::write(&path, "b").unwrap();
+        std::fs::write(&path, "c").unwrap();
+        assert_eq!(reloads_within(&mut watcher, Duration::from_secs(5)), 1);
+        assert_eq!(reloads_within(&mut watcher, SETTLE_TIME * 2), 0);
+
+        // Written again under another name and moved over, as many editors save.
+        std::fs::write(dir.join("texture.png.tmp"), "d").unwrap();
+        std::fs::rename(dir.join("texture.png.tmp"), &path).unwrap();
+        assert_eq!(reloads_within(&mut watcher, Duration::from_secs(5)), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
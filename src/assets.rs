@@ -0,0 +1,188 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use half::f16;
+
+use crate::layout::SceneEntry;
+use crate::model::{ImportOptions, Model};
+use crate::profiler;
+
+/// Loads the files a scene is made of: models, with one set of import options, and
+/// environment maps.
+pub struct AssetServer {
+    pub import_options: ImportOptions,
+    /// The environment map last loaded, kept decoded so a new device gets it without
+    /// reading the file again.
+    environment: Option<EnvironmentImage>,
+}
+
+impl AssetServer {
+    pub fn new(import_options: ImportOptions) -> Self {
+        Self {
+            import_options,
+            environment: None,
+        }
+    }
+
+    /// Loads the model of `entry` with its material overrides applied; overrides that
+    /// don't apply are skipped with a warning.
+    pub fn load_model(&self, entry: &SceneEntry) -> Result<Model> {
+        let mut model = Model::load(&entry.path, &self.import_options)?;
+        for o in &entry.overrides {
+            if let Err(e) = model.apply_material_override(o) {
+                log::warn!("{}: {:#}", entry.path.display(), e);
+            }
+        }
+        Ok(model)
+    }
+
+    /// The HDR file next to the models, or one directory up, that looks most like a
+    /// skybox.
+    pub fn find_environment<'a>(&self, model_paths: impl IntoIterator<Item = &'a Path>) -> Option<PathBuf> {
+        pick_env_hdr_path(model_paths)
+    }
+
+    /// Uploads an equirectangular environment map as half floats, reading the file only
+    /// when it is not the one last loaded; a missing or unreadable file gives a black 1x1
+    /// texture.
+    pub fn load_environment(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, hdr_path: &Path) -> wgpu::Texture {
+        if self.environment.as_ref().is_none_or(|e| e.path != hdr_path) {
+            self.environment = decode_environment(hdr_path);
+        }
+        upload_env_texture(device, queue, self.environment.as_ref())
+    }
+
+    /// Like [`Self::load_environment`], but always reads the file, for one that changed.
+    pub fn reload_environment(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, hdr_path: &Path) -> wgpu::Texture {
+        self.environment = None;
+        self.load_environment(device, queue, hdr_path)
+    }
+}
+
+fn pick_env_hdr_path<'a>(model_paths: impl IntoIterator<Item = &'a Path>) -> Option<PathBuf> {
+    fn score(name: &str) -> i32 {
+        let n = name.to_ascii_lowercase();
+        if n.contains("skybox") {
+            300
+        } else if n.contains("reflection") && n.contains("interior") {
+            250
+        } else if n.contains("reflection") {
+            200
+        } else {
+            100
+        }
+    }
+
+    let mut best: Option<(i32, PathBuf)> = None;
+    for path in model_paths {
+        let dirs = [path.parent(), path.parent().and_then(|d| d.parent())];
+        for dir in dirs.into_iter().flatten() {
+            if let Ok(entries) = std::fs::read_dir(dir) {
+                for entry in entries.flatten() {
+                    let ep = entry.path();
+                    if ep.extension().and_then(|s| s.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("hdr")) {
+                        let name = ep.file_name().and_then(|s| s.to_str()).unwrap_or("");
+                        let s = score(name);
+                        if best.as_ref().is_none_or(|(bs, _)| s > *bs) {
+                            best = Some((s, ep));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    best.map(|(_, p)| p)
+}
+
+/// An environment map decoded to half floats, RGBA.
+struct EnvironmentImage {
+    path: PathBuf,
+    width: u32,
+    height: u32,
+    rgba16: Vec<u16>,
+}
+
+fn decode_environment(hdr_path: &Path) -> Option<EnvironmentImage> {
+    let _span = profiler::scope("load environment");
+    let bytes = std::fs::read(hdr_path).ok()?;
+    let img = image::load_from_memory(&bytes).ok()?;
+    let (width, height) = (img.width(), img.height());
+    let mut rgba16: Vec<u16> = Vec::with_capacity((width * height * 4) as usize);
+    match img {
+        image::DynamicImage::ImageRgb32F(buf) => {
+            for p in buf.pixels() {
+                rgba16.push(f16::from_f32(p.0[0]).to_bits());
+                rgba16.push(f16::from_f32(p.0[1]).to_bits());
+                rgba16.push(f16::from_f32(p.0[2]).to_bits());
+                rgba16.push(f16::from_f32(1.0).to_bits());
+            }
+        }
+        image::DynamicImage::ImageRgba32F(buf) => {
+            for p in buf.pixels() {
+                rgba16.push(f16::from_f32(p.0[0]).to_bits());
+                rgba16.push(f16::from_f32(p.0[1]).to_bits());
+                rgba16.push(f16::from_f32(p.0[2]).to_bits());
+                rgba16.push(f16::from_f32(p.0[3]).to_bits());
+            }
+        }
+        other => {
+            for p in other.to_rgba8().pixels() {
+                let r = (p.0[0] as f32) / 255.0;
+                let g = (p.0[1] as f32) / 255.0;
+                let b = (p.0[2] as f32) / 255.0;
+                let a = (p.0[3] as f32) / 255.0;
+                rgba16.push(f16::from_f32(r).to_bits());
+                rgba16.push(f16::from_f32(g).to_bits());
+                rgba16.push(f16::from_f32(b).to_bits());
+                rgba16.push(f16::from_f32(a).to_bits());
+            }
+        }
+    }
+    Some(EnvironmentImage {
+        path: hdr_path.to_path_buf(),
+        width,
+        height,
+        rgba16,
+    })
+}
+
+fn upload_env_texture(device: &wgpu::Device, queue: &wgpu::Queue, image: Option<&EnvironmentImage>) -> wgpu::Texture {
+    let black = [0.0, 0.0, 0.0, 1.0].map(|c| f16::from_f32(c).to_bits());
+    let (width, height, rgba16) = image.map_or((1, 1, &black[..]), |i| (i.width, i.height, &i.rgba16[..]));
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Env Texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba16Float,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        bytemuck::cast_slice(rgba16),
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(8 * width),
+            rows_per_image: Some(height),
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+
+    texture
+}
@@ -0,0 +1,284 @@
+use cgmath::{InnerSpace, Vector3};
+
+use crate::camera::DepthMode;
+use crate::pipelines::depth_shader;
+use crate::post::HDR_FORMAT;
+
+const TRANSMITTANCE_SIZE: (u32, u32) = (256, 64);
+const SKY_SIZE: (u32, u32) = (512, 256);
+const LUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// Scales the unit-illuminance scattering into roughly the range of the HDR environments.
+const SUN_ILLUMINANCE: f32 = 30.0;
+/// Height of the scene's origin above the ground, in kilometres.
+const OBSERVER_ALTITUDE: f32 = 0.2;
+/// One world unit is a metre by default.
+pub const DEFAULT_KM_PER_UNIT: f32 = 0.001;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct AtmosphereParams {
+    sun: [f32; 4],
+    view: [f32; 4],
+}
+
+fn lut_texture(device: &wgpu::Device, label: &str, (width, height): (u32, u32)) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: LUT_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
+
+fn fullscreen_pass<'e>(
+    encoder: &'e mut wgpu::CommandEncoder,
+    label: &str,
+    view: &wgpu::TextureView,
+    load: wgpu::LoadOp<wgpu::Color>,
+) -> wgpu::RenderPass<'e> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        occlusion_query_set: None,
+        timestamp_writes: None,
+    })
+}
+
+/// Earth-like atmosphere that replaces the environment map: the sky is raymarched into
+/// an equirectangular texture (re-baked when the sun moves) and scene fragments get
+/// aerial perspective from a fullscreen pass over the depth buffer.
+pub struct Atmosphere {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    km_per_unit: f32,
+    params_buffer: wgpu::Buffer,
+    lut_bind_group: wgpu::BindGroup,
+    sky_pipeline: wgpu::RenderPipeline,
+    aerial_pipeline: wgpu::RenderPipeline,
+    baked: Option<Vector3<f32>>,
+}
+
+impl Atmosphere {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        camera_layout: &wgpu::BindGroupLayout,
+        km_per_unit: f32,
+        depth_mode: DepthMode,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Atmosphere Shader"),
+            source: wgpu::ShaderSource::Wgsl(depth_shader(include_str!("atmosphere.wgsl"), depth_mode).into()),
+        });
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Atmosphere Params Buffer"),
+            size: std::mem::size_of::<AtmosphereParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let params_entry = wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        // The transmittance pass writes the LUT, so it binds only the parameters.
+        let params_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("atmosphere_params_bind_group_layout"),
+            entries: &[params_entry],
+        });
+        let lut_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("atmosphere_bind_group_layout"),
+            entries: &[
+                params_entry,
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline = |label: &str,
+                        layouts: &[&wgpu::BindGroupLayout],
+                        entry_point: &str,
+                        format: wgpu::TextureFormat,
+                        blend: Option<wgpu::BlendState>| {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: layouts,
+                push_constant_ranges: &[],
+            });
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                cache: None,
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_fullscreen",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        let transmittance_pipeline =
+            pipeline("Atmosphere Transmittance Pipeline", &[&params_layout], "fs_transmittance", LUT_FORMAT, None);
+        let sky_pipeline = pipeline("Atmosphere Sky Pipeline", &[&lut_layout], "fs_sky_bake", LUT_FORMAT, None);
+        let aerial_pipeline = pipeline(
+            "Atmosphere Aerial Perspective Pipeline",
+            &[&lut_layout, camera_layout],
+            "fs_aerial",
+            HDR_FORMAT,
+            Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+        );
+
+        let transmittance = lut_texture(device, "Atmosphere Transmittance LUT", TRANSMITTANCE_SIZE);
+        let transmittance_view = transmittance.create_view(&wgpu::TextureViewDescriptor::default());
+        let texture = lut_texture(device, "Atmosphere Sky", SKY_SIZE);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Atmosphere LUT Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let params_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("atmosphere_params_bind_group"),
+            layout: &params_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            }],
+        });
+        let lut_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("atmosphere_bind_group"),
+            layout: &lut_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&transmittance_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        // The transmittance LUT depends only on the atmosphere, so it is computed once here.
+        let params = AtmosphereParams {
+            sun: [0.0, 1.0, 0.0, SUN_ILLUMINANCE],
+            view: [OBSERVER_ALTITUDE, km_per_unit, TRANSMITTANCE_SIZE.0 as f32, TRANSMITTANCE_SIZE.1 as f32],
+        };
+        queue.write_buffer(&params_buffer, 0, bytemuck::cast_slice(&[params]));
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Atmosphere Transmittance Encoder"),
+        });
+        {
+            let clear = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+            let mut pass = fullscreen_pass(&mut encoder, "Atmosphere Transmittance", &transmittance_view, clear);
+            pass.set_pipeline(&transmittance_pipeline);
+            pass.set_bind_group(0, &params_bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+
+        Self {
+            texture,
+            view,
+            km_per_unit,
+            params_buffer,
+            lut_bind_group,
+            sky_pipeline,
+            aerial_pipeline,
+            baked: None,
+        }
+    }
+
+    /// Re-renders the sky if the sun (given as the direction its light travels) moved
+    /// since the last bake.
+    pub fn bake(&mut self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, light_dir: Vector3<f32>) {
+        let to_sun = -light_dir.normalize();
+        if self.baked.is_some_and(|sun| sun.dot(to_sun) > 0.99999) {
+            return;
+        }
+        self.baked = Some(to_sun);
+
+        let params = AtmosphereParams {
+            sun: [to_sun.x, to_sun.y, to_sun.z, SUN_ILLUMINANCE],
+            view: [OBSERVER_ALTITUDE, self.km_per_unit, SKY_SIZE.0 as f32, SKY_SIZE.1 as f32],
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+        let mut pass = fullscreen_pass(encoder, "Atmosphere Sky Bake", &self.view, clear);
+        pass.set_pipeline(&self.sky_pipeline);
+        pass.set_bind_group(0, &self.lut_bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    /// Blends in-scattering over the opaque scene; needs the scene depth copy that the
+    /// camera bind group exposes.
+    pub fn apply_aerial_perspective(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        hdr_view: &wgpu::TextureView,
+        camera_bind_group: &wgpu::BindGroup,
+    ) {
+        let mut pass = fullscreen_pass(encoder, "Aerial Perspective", hdr_view, wgpu::LoadOp::Load);
+        pass.set_pipeline(&self.aerial_pipeline);
+        pass.set_bind_group(0, &self.lut_bind_group, &[]);
+        pass.set_bind_group(1, camera_bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
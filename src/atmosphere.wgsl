@@ -0,0 +1,193 @@
+// Physically based atmosphere after Hillaire 2020: a transmittance LUT, a raymarched sky
+// baked into the equirectangular environment map, and aerial perspective over the scene.
+// Distances are in kilometres.
+
+struct AtmosphereParams {
+    // xyz: direction towards the sun, w: sun illuminance.
+    sun: vec4<f32>,
+    // x: observer altitude, y: kilometres per world unit, zw: target size in texels.
+    view: vec4<f32>,
+};
+
+// Prefix of CameraUniform in shader.wgsl.
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    view_inv: mat4x4<f32>,
+    proj_inv: mat4x4<f32>,
+    position: vec4<f32>,
+    light_view_proj: mat4x4<f32>,
+    light_dir: vec4<f32>,
+    env_intensity: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> params: AtmosphereParams;
+
+@group(0) @binding(1)
+var transmittance_lut: texture_2d<f32>;
+
+@group(0) @binding(2)
+var lut_sampler: sampler;
+
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(5)
+var scene_depth: texture_depth_2d;
+
+const PI: f32 = 3.14159265359;
+const BOTTOM_RADIUS: f32 = 6360.0;
+const TOP_RADIUS: f32 = 6460.0;
+const RAYLEIGH_SCATTERING: vec3<f32> = vec3<f32>(5.802e-3, 13.558e-3, 33.1e-3);
+const RAYLEIGH_HEIGHT: f32 = 8.0;
+const MIE_SCATTERING: f32 = 3.996e-3;
+const MIE_ABSORPTION: f32 = 4.4e-3;
+const MIE_HEIGHT: f32 = 1.2;
+const MIE_G: f32 = 0.8;
+const OZONE_ABSORPTION: vec3<f32> = vec3<f32>(0.650e-3, 1.881e-3, 0.085e-3);
+const TRANSMITTANCE_STEPS: i32 = 40;
+const SKY_STEPS: i32 = 32;
+const AERIAL_STEPS: i32 = 8;
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vid << 1u) & 2u), f32(vid & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+struct Medium {
+    rayleigh: vec3<f32>,
+    mie: f32,
+    extinction: vec3<f32>,
+};
+
+fn medium(altitude: f32) -> Medium {
+    let h = max(altitude, 0.0);
+    let rayleigh = RAYLEIGH_SCATTERING * exp(-h / RAYLEIGH_HEIGHT);
+    let mie_density = exp(-h / MIE_HEIGHT);
+    // Ozone as a tent centred at 25 km.
+    let ozone = max(0.0, 1.0 - abs(h - 25.0) / 15.0);
+    var m: Medium;
+    m.rayleigh = rayleigh;
+    m.mie = MIE_SCATTERING * mie_density;
+    m.extinction = rayleigh + vec3<f32>((MIE_SCATTERING + MIE_ABSORPTION) * mie_density) + OZONE_ABSORPTION * ozone;
+    return m;
+}
+
+// Distance along the ray to the far side of a planet-centred sphere, or -1 on a miss.
+fn ray_sphere_far(origin: vec3<f32>, dir: vec3<f32>, radius: f32) -> f32 {
+    let b = dot(origin, dir);
+    let disc = b * b - (dot(origin, origin) - radius * radius);
+    if disc < 0.0 {
+        return -1.0;
+    }
+    return -b + sqrt(disc);
+}
+
+// Distance to the ground, or -1 if the ray never reaches it.
+fn ray_ground(origin: vec3<f32>, dir: vec3<f32>) -> f32 {
+    let b = dot(origin, dir);
+    let disc = b * b - (dot(origin, origin) - BOTTOM_RADIUS * BOTTOM_RADIUS);
+    if disc < 0.0 {
+        return -1.0;
+    }
+    let t = -b - sqrt(disc);
+    return select(-1.0, t, t > 0.0);
+}
+
+fn sun_transmittance(position: vec3<f32>, sun: vec3<f32>) -> vec3<f32> {
+    let r = length(position);
+    let mu = dot(position / r, sun);
+    let uv = vec2<f32>(mu * 0.5 + 0.5, clamp((r - BOTTOM_RADIUS) / (TOP_RADIUS - BOTTOM_RADIUS), 0.0, 1.0));
+    return textureSampleLevel(transmittance_lut, lut_sampler, uv, 0.0).rgb;
+}
+
+@fragment
+fn fs_transmittance(@builtin(position) frag: vec4<f32>) -> @location(0) vec4<f32> {
+    let uv = frag.xy / params.view.zw;
+    let mu = uv.x * 2.0 - 1.0;
+    let origin = vec3<f32>(0.0, BOTTOM_RADIUS + uv.y * (TOP_RADIUS - BOTTOM_RADIUS), 0.0);
+    let dir = vec3<f32>(sqrt(max(1.0 - mu * mu, 0.0)), mu, 0.0);
+    if ray_ground(origin, dir) > 0.0 {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    let t_max = ray_sphere_far(origin, dir, TOP_RADIUS);
+    let dt = t_max / f32(TRANSMITTANCE_STEPS);
+    var depth = vec3<f32>(0.0);
+    for (var i = 0; i < TRANSMITTANCE_STEPS; i++) {
+        let p = origin + dir * ((f32(i) + 0.5) * dt);
+        depth += medium(length(p) - BOTTOM_RADIUS).extinction * dt;
+    }
+    return vec4<f32>(exp(-depth), 1.0);
+}
+
+struct Scattering {
+    inscatter: vec3<f32>,
+    transmittance: vec3<f32>,
+};
+
+// Single scattering along a ray, integrated per step as in Hillaire 2020.
+fn integrate(origin: vec3<f32>, dir: vec3<f32>, t_max: f32, sun: vec3<f32>, steps: i32) -> Scattering {
+    let cos_theta = dot(dir, sun);
+    let phase_rayleigh = 3.0 / (16.0 * PI) * (1.0 + cos_theta * cos_theta);
+    let g2 = MIE_G * MIE_G;
+    let phase_mie = (1.0 - g2) / (4.0 * PI * pow(1.0 + g2 - 2.0 * MIE_G * cos_theta, 1.5));
+
+    let dt = t_max / f32(steps);
+    var result: Scattering;
+    result.inscatter = vec3<f32>(0.0);
+    result.transmittance = vec3<f32>(1.0);
+    for (var i = 0; i < steps; i++) {
+        let p = origin + dir * ((f32(i) + 0.5) * dt);
+        let m = medium(length(p) - BOTTOM_RADIUS);
+        let scattering = (m.rayleigh * phase_rayleigh + vec3<f32>(m.mie * phase_mie)) * sun_transmittance(p, sun);
+        let step_transmittance = exp(-m.extinction * dt);
+        let extinction = max(m.extinction, vec3<f32>(1e-7));
+        result.inscatter += result.transmittance * (scattering - scattering * step_transmittance) / extinction;
+        result.transmittance *= step_transmittance;
+    }
+    return result;
+}
+
+// Inverse of dir_to_equirect_uv in shader.wgsl.
+fn equirect_dir(uv: vec2<f32>) -> vec3<f32> {
+    let phi = (uv.x - 0.5) * 2.0 * PI;
+    let theta = uv.y * PI;
+    return vec3<f32>(sin(theta) * cos(phi), cos(theta), sin(theta) * sin(phi));
+}
+
+@fragment
+fn fs_sky_bake(@builtin(position) frag: vec4<f32>) -> @location(0) vec4<f32> {
+    let dir = equirect_dir(frag.xy / params.view.zw);
+    let sun = normalize(params.sun.xyz);
+    let origin = vec3<f32>(0.0, BOTTOM_RADIUS + params.view.x, 0.0);
+    let ground = ray_ground(origin, dir);
+    let t_max = select(ray_sphere_far(origin, dir, TOP_RADIUS), ground, ground > 0.0);
+    let s = integrate(origin, dir, t_max, sun, SKY_STEPS);
+    return vec4<f32>(s.inscatter * params.sun.w, 1.0);
+}
+
+// Premultiplied: inscattered light in rgb, one minus the mean transmittance in alpha.
+@fragment
+fn fs_aerial(@builtin(position) frag: vec4<f32>) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(scene_depth));
+    let depth = textureLoad(scene_depth, vec2<i32>(frag.xy), 0);
+    if depth == FAR_DEPTH {
+        discard;
+    }
+    let uv = frag.xy / size;
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let view_h = camera.proj_inv * ndc;
+    let world = (camera.view_inv * vec4<f32>(view_h.xyz / view_h.w, 1.0)).xyz;
+    let offset = world - camera.position.xyz;
+    let distance = length(offset) * params.view.y;
+    if distance <= 0.0 {
+        discard;
+    }
+
+    let altitude = params.view.x + max(camera.position.y * params.view.y, 0.0);
+    let origin = vec3<f32>(0.0, BOTTOM_RADIUS + altitude, 0.0);
+    let s = integrate(origin, normalize(offset), distance, normalize(params.sun.xyz), AERIAL_STEPS);
+    let inscatter = s.inscatter * params.sun.w * camera.env_intensity.rgb;
+    return vec4<f32>(inscatter, 1.0 - dot(s.transmittance, vec3<f32>(1.0 / 3.0)));
+}
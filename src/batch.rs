@@ -0,0 +1,90 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use cgmath::Point3;
+
+use crate::layout::floats;
+use crate::snapshot::{CameraState, Snapshot};
+
+pub struct CameraBookmark {
+    pub name: String,
+    pub camera: CameraState,
+}
+
+/// One column of the matrix; unset fields keep the scene's own settings.
+pub struct Variant {
+    pub name: String,
+    pub hdr: Option<PathBuf>,
+    /// Environment luminance in nits.
+    pub env_intensity: Option<f32>,
+    pub ev100: Option<f32>,
+}
+
+/// Renders every camera bookmark under every variant to `output/<variant>/<camera>.png`.
+pub struct ShotMatrix {
+    pub output: PathBuf,
+    pub cameras: Vec<CameraBookmark>,
+    pub variants: Vec<Variant>,
+}
+
+/// Loads a batch description:
+///
+/// ```json
+/// { "output": "shots",
+///   "cameras": [{ "name": "entrance", "position": [0, 2, 10], "yaw": -90, "pitch": -5 },
+///               { "name": "atrium", "snapshot": "atrium.json" }],
+///   "variants": [{ "name": "day", "hdr": "day.hdr" },
+///                { "name": "night", "hdr": "night.hdr", "env_intensity": 50, "ev100": 6 }] }
+/// ```
+///
+/// Paths are relative to the batch file and angles in degrees. A camera can point at a
+/// snapshot file instead, taking only its camera. Without cameras the startup view is
+/// used, and without variants the scene is rendered once as `default`.
+pub fn load_shot_matrix(path: &Path) -> Result<ShotMatrix> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("reading batch file {}", path.display()))?;
+    let value: serde_json::Value =
+        serde_json::from_str(&text).with_context(|| format!("parsing batch file {}", path.display()))?;
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let list = |key: &str| value.get(key).and_then(|v| v.as_array()).into_iter().flatten().enumerate();
+
+    let mut cameras = Vec::new();
+    for (i, c) in list("cameras") {
+        let name = c.get("name").and_then(|v| v.as_str()).map_or_else(|| format!("camera{}", i), str::to_string);
+        let camera = if let Some(snapshot) = c.get("snapshot").and_then(|v| v.as_str()) {
+            Snapshot::load(&base_dir.join(snapshot))?.camera
+        } else {
+            let position = c
+                .get("position")
+                .and_then(floats::<3>)
+                .with_context(|| format!("batch camera '{}' has no position or snapshot", name))?;
+            let degrees = |key: &str| c.get(key).and_then(|v| v.as_f64()).map(|v| (v as f32).to_radians());
+            CameraState {
+                position: Point3::from(position),
+                yaw: degrees("yaw").unwrap_or(0.0),
+                pitch: degrees("pitch").unwrap_or(0.0),
+                fovy: c.get("fovy").and_then(|v| v.as_f64()).map_or(45.0, |v| v as f32),
+            }
+        };
+        cameras.push(CameraBookmark { name, camera });
+    }
+
+    let variants = list("variants")
+        .map(|(i, v)| Variant {
+            name: v.get("name").and_then(|v| v.as_str()).map_or_else(|| format!("variant{}", i), str::to_string),
+            hdr: v.get("hdr").and_then(|v| v.as_str()).map(|p| base_dir.join(p)),
+            env_intensity: v.get("env_intensity").and_then(|v| v.as_f64()).map(|v| v as f32),
+            ev100: v.get("ev100").and_then(|v| v.as_f64()).map(|v| v as f32),
+        })
+        .collect();
+
+    Ok(ShotMatrix {
+        output: base_dir.join(value.get("output").and_then(|v| v.as_str()).unwrap_or("shots")),
+        cameras,
+        variants,
+    })
+}
+
+/// Keeps bookmark and variant names usable as file and folder names.
+pub fn file_stem(name: &str) -> String {
+    name.chars().map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect()
+}
@@ -0,0 +1,543 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use winit::keyboard::KeyCode;
+
+/// Where bindings load from, and rebinding saves to, unless `--bindings=` says otherwise.
+pub const DEFAULT_BINDINGS_PATH: &str = "bindings.toml";
+
+/// What a key does. Movement actions apply while their key is held; the rest fire on
+/// press.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Sprint,
+    Zoom,
+    FrameScene,
+    FovDown,
+    FovUp,
+    ToggleUi,
+    Profiler,
+    MeshInspector,
+    SceneShapes,
+    MeshBounds,
+    InspectorMode,
+    InspectorPreviousMaterial,
+    InspectorNextMaterial,
+    InspectorSlot,
+    InspectorChannel,
+    MaterialOverride,
+    MaterialOverrideNext,
+    Lods,
+    PresentMode,
+    Rebind,
+    LogLuminance,
+    LowLatency,
+    Flashlight,
+    ShadowFilter,
+    ShadowQuality,
+    SunLeft,
+    SunRight,
+    SunUp,
+    SunDown,
+    TimeOfDay,
+    BakeProbes,
+    ReflectionProbe,
+    Ssao,
+    Ssgi,
+    DynamicGi,
+    VoxelGi,
+    VoxelDebug,
+    LightProbes,
+    Lightmaps,
+    RtShadows,
+    Fog,
+    LensFlare,
+    MotionBlur,
+    Taa,
+    Fxaa,
+    Screenshot,
+    DebugView,
+    AutoExposure,
+    ExposureDown,
+    ExposureUp,
+    SaveSnapshot,
+    LoadSnapshot,
+    PathKey,
+    PlayPath,
+    ClearPath,
+    PlayAnimation,
+    PreviousClip,
+    NextClip,
+    ScrubBack,
+    ScrubForward,
+    SlowerAnimation,
+    FasterAnimation,
+    LoopAnimation,
+    BoneWeights,
+    NextJoint,
+    PreviousJoint,
+    SettingsPanel,
+    Console,
+    Fullscreen,
+}
+
+/// Every action, in `Action` order, with its name in bindings files and its default keys.
+const ACTIONS: [(Action, &str, &[KeyCode]); 75] = [
+    (Action::MoveForward, "move_forward", &[KeyCode::KeyW]),
+    (Action::MoveBack, "move_back", &[KeyCode::KeyS]),
+    (Action::MoveLeft, "move_left", &[KeyCode::KeyA]),
+    (Action::MoveRight, "move_right", &[KeyCode::KeyD]),
+    (Action::MoveUp, "move_up", &[KeyCode::Space]),
+    (Action::MoveDown, "move_down", &[KeyCode::ControlLeft, KeyCode::ControlRight]),
+    (Action::Sprint, "sprint", &[KeyCode::ShiftLeft, KeyCode::ShiftRight]),
+    (Action::Zoom, "zoom", &[KeyCode::AltLeft]),
+    (Action::FrameScene, "frame_scene", &[KeyCode::KeyF]),
+    (Action::FovDown, "fov_down", &[KeyCode::PageDown]),
+    (Action::FovUp, "fov_up", &[KeyCode::PageUp]),
+    (Action::ToggleUi, "toggle_ui", &[KeyCode::Tab]),
+    (Action::Profiler, "profiler", &[KeyCode::Slash]),
+    (Action::MeshInspector, "mesh_inspector", &[KeyCode::KeyI]),
+    (Action::SceneShapes, "scene_shapes", &[KeyCode::Digit0]),
+    (Action::MeshBounds, "mesh_bounds", &[KeyCode::Backslash]),
+    (Action::InspectorMode, "inspector_mode", &[KeyCode::Digit1]),
+    (Action::InspectorPreviousMaterial, "inspector_previous_material", &[KeyCode::Digit2]),
+    (Action::InspectorNextMaterial, "inspector_next_material", &[KeyCode::Digit3]),
+    (Action::InspectorSlot, "inspector_slot", &[KeyCode::Digit4]),
+    (Action::InspectorChannel, "inspector_channel", &[KeyCode::Digit5]),
+    (Action::MaterialOverride, "material_override", &[KeyCode::Semicolon]),
+    (Action::MaterialOverrideNext, "material_override_next", &[KeyCode::Quote]),
+    (Action::Lods, "lods", &[KeyCode::Digit6]),
+    (Action::PresentMode, "present_mode", &[KeyCode::Digit7]),
+    (Action::Rebind, "rebind", &[KeyCode::Digit8]),
+    (Action::LogLuminance, "log_luminance", &[KeyCode::KeyH]),
+    (Action::LowLatency, "low_latency", &[KeyCode::KeyK]),
+    (Action::Flashlight, "flashlight", &[KeyCode::KeyG]),
+    (Action::ShadowFilter, "shadow_filter", &[KeyCode::KeyV]),
+    (Action::ShadowQuality, "shadow_quality", &[KeyCode::KeyO]),
+    (Action::SunLeft, "sun_left", &[KeyCode::ArrowLeft]),
+    (Action::SunRight, "sun_right", &[KeyCode::ArrowRight]),
+    (Action::SunUp, "sun_up", &[KeyCode::ArrowUp]),
+    (Action::SunDown, "sun_down", &[KeyCode::ArrowDown]),
+    (Action::TimeOfDay, "time_of_day", &[KeyCode::KeyT]),
+    (Action::BakeProbes, "bake_probes", &[KeyCode::KeyU]),
+    (Action::ReflectionProbe, "reflection_probe", &[KeyCode::KeyR]),
+    (Action::Ssao, "ssao", &[KeyCode::KeyX]),
+    (Action::Ssgi, "ssgi", &[KeyCode::KeyZ]),
+    (Action::DynamicGi, "dynamic_gi", &[KeyCode::KeyJ]),
+    (Action::VoxelGi, "voxel_gi", &[KeyCode::KeyC]),
+    (Action::VoxelDebug, "voxel_debug", &[KeyCode::KeyQ]),
+    (Action::LightProbes, "light_probes", &[KeyCode::KeyY]),
+    (Action::Lightmaps, "lightmaps", &[KeyCode::F6]),
+    (Action::RtShadows, "rt_shadows", &[KeyCode::F7]),
+    (Action::Fog, "fog", &[KeyCode::KeyE]),
+    (Action::LensFlare, "lens_flare", &[KeyCode::F8]),
+    (Action::MotionBlur, "motion_blur", &[KeyCode::F10]),
+    (Action::Taa, "taa", &[KeyCode::Delete]),
+    (Action::Fxaa, "fxaa", &[KeyCode::Backspace]),
+    (Action::Screenshot, "screenshot", &[KeyCode::F12, KeyCode::PrintScreen]),
+    (Action::DebugView, "debug_view", &[KeyCode::F1]),
+    (Action::AutoExposure, "auto_exposure", &[KeyCode::F2]),
+    (Action::ExposureDown, "exposure_down", &[KeyCode::F3]),
+    (Action::ExposureUp, "exposure_up", &[KeyCode::F4]),
+    (Action::SaveSnapshot, "save_snapshot", &[KeyCode::F5]),
+    (Action::LoadSnapshot, "load_snapshot", &[KeyCode::F9]),
+    (Action::PathKey, "path_key", &[KeyCode::Insert]),
+    (Action::PlayPath, "play_path", &[KeyCode::Home]),
+    (Action::ClearPath, "clear_path", &[KeyCode::End]),
+    (Action::PlayAnimation, "play_animation", &[KeyCode::KeyP]),
+    (Action::PreviousClip, "previous_clip", &[KeyCode::BracketLeft]),
+    (Action::NextClip, "next_clip", &[KeyCode::BracketRight]),
+    (Action::ScrubBack, "scrub_back", &[KeyCode::Comma]),
+    (Action::ScrubForward, "scrub_forward", &[KeyCode::Period]),
+    (Action::SlowerAnimation, "slower_animation", &[KeyCode::Minus]),
+    (Action::FasterAnimation, "faster_animation", &[KeyCode::Equal]),
+    (Action::LoopAnimation, "loop_animation", &[KeyCode::KeyL]),
+    (Action::BoneWeights, "bone_weights", &[KeyCode::KeyB]),
+    (Action::NextJoint, "next_joint", &[KeyCode::KeyN]),
+    (Action::PreviousJoint, "previous_joint", &[KeyCode::KeyM]),
+    (Action::SettingsPanel, "settings_panel", &[KeyCode::Digit9]),
+    (Action::Console, "console", &[KeyCode::Backquote]),
+    (Action::Fullscreen, "fullscreen", &[KeyCode::F11]),
+];
+
+/// Keys by their winit names; `parse_key` also takes single letters and digits.
+const KEY_NAMES: [(&str, KeyCode); 98] = [
+    ("KeyA", KeyCode::KeyA),
+    ("KeyB", KeyCode::KeyB),
+    ("KeyC", KeyCode::KeyC),
+    ("KeyD", KeyCode::KeyD),
+    ("KeyE", KeyCode::KeyE),
+    ("KeyF", KeyCode::KeyF),
+    ("KeyG", KeyCode::KeyG),
+    ("KeyH", KeyCode::KeyH),
+    ("KeyI", KeyCode::KeyI),
+    ("KeyJ", KeyCode::KeyJ),
+    ("KeyK", KeyCode::KeyK),
+    ("KeyL", KeyCode::KeyL),
+    ("KeyM", KeyCode::KeyM),
+    ("KeyN", KeyCode::KeyN),
+    ("KeyO", KeyCode::KeyO),
+    ("KeyP", KeyCode::KeyP),
+    ("KeyQ", KeyCode::KeyQ),
+    ("KeyR", KeyCode::KeyR),
+    ("KeyS", KeyCode::KeyS),
+    ("KeyT", KeyCode::KeyT),
+    ("KeyU", KeyCode::KeyU),
+    ("KeyV", KeyCode::KeyV),
+    ("KeyW", KeyCode::KeyW),
+    ("KeyX", KeyCode::KeyX),
+    ("KeyY", KeyCode::KeyY),
+    ("KeyZ", KeyCode::KeyZ),
+    ("Digit0", KeyCode::Digit0),
+    ("Digit1", KeyCode::Digit1),
+    ("Digit2", KeyCode::Digit2),
+    ("Digit3", KeyCode::Digit3),
+    ("Digit4", KeyCode::Digit4),
+    ("Digit5", KeyCode::Digit5),
+    ("Digit6", KeyCode::Digit6),
+    ("Digit7", KeyCode::Digit7),
+    ("Digit8", KeyCode::Digit8),
+    ("Digit9", KeyCode::Digit9),
+    ("F1", KeyCode::F1),
+    ("F2", KeyCode::F2),
+    ("F3", KeyCode::F3),
+    ("F4", KeyCode::F4),
+    ("F5", KeyCode::F5),
+    ("F6", KeyCode::F6),
+    ("F7", KeyCode::F7),
+    ("F8", KeyCode::F8),
+    ("F9", KeyCode::F9),
+    ("F10", KeyCode::F10),
+    ("F11", KeyCode::F11),
+    ("F12", KeyCode::F12),
+    ("PrintScreen", KeyCode::PrintScreen),
+    ("ArrowUp", KeyCode::ArrowUp),
+    ("ArrowDown", KeyCode::ArrowDown),
+    ("ArrowLeft", KeyCode::ArrowLeft),
+    ("ArrowRight", KeyCode::ArrowRight),
+    ("Space", KeyCode::Space),
+    ("Tab", KeyCode::Tab),
+    ("Enter", KeyCode::Enter),
+    ("Escape", KeyCode::Escape),
+    ("Backspace", KeyCode::Backspace),
+    ("Delete", KeyCode::Delete),
+    ("Insert", KeyCode::Insert),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("ShiftLeft", KeyCode::ShiftLeft),
+    ("ShiftRight", KeyCode::ShiftRight),
+    ("ControlLeft", KeyCode::ControlLeft),
+    ("ControlRight", KeyCode::ControlRight),
+    ("AltLeft", KeyCode::AltLeft),
+    ("AltRight", KeyCode::AltRight),
+    ("CapsLock", KeyCode::CapsLock),
+    ("Backquote", KeyCode::Backquote),
+    ("Backslash", KeyCode::Backslash),
+    ("BracketLeft", KeyCode::BracketLeft),
+    ("BracketRight", KeyCode::BracketRight),
+    ("Comma", KeyCode::Comma),
+    ("Period", KeyCode::Period),
+    ("Semicolon", KeyCode::Semicolon),
+    ("Quote", KeyCode::Quote),
+    ("Slash", KeyCode::Slash),
+    ("Minus", KeyCode::Minus),
+    ("Equal", KeyCode::Equal),
+    ("Numpad0", KeyCode::Numpad0),
+    ("Numpad1", KeyCode::Numpad1),
+    ("Numpad2", KeyCode::Numpad2),
+    ("Numpad3", KeyCode::Numpad3),
+    ("Numpad4", KeyCode::Numpad4),
+    ("Numpad5", KeyCode::Numpad5),
+    ("Numpad6", KeyCode::Numpad6),
+    ("Numpad7", KeyCode::Numpad7),
+    ("Numpad8", KeyCode::Numpad8),
+    ("Numpad9", KeyCode::Numpad9),
+    ("NumpadAdd", KeyCode::NumpadAdd),
+    ("NumpadSubtract", KeyCode::NumpadSubtract),
+    ("NumpadMultiply", KeyCode::NumpadMultiply),
+    ("NumpadDivide", KeyCode::NumpadDivide),
+    ("NumpadDecimal", KeyCode::NumpadDecimal),
+    ("NumpadEnter", KeyCode::NumpadEnter),
+];
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    if let Some((_, code)) = KEY_NAMES.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)) {
+        return Some(*code);
+    }
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphabetic() => parse_key(&format!("Key{}", c.to_ascii_uppercase())),
+        (Some(c), None) if c.is_ascii_digit() => parse_key(&format!("Digit{}", c)),
+        _ => None,
+    }
+}
+
+fn key_name(code: KeyCode) -> Option<&'static str> {
+    KEY_NAMES.iter().find(|(_, c)| *c == code).map(|(name, _)| *name)
+}
+
+impl Action {
+    pub fn name(self) -> &'static str {
+        ACTIONS[self as usize].1
+    }
+}
+
+/// Which keys trigger each action: the defaults, overridden per action by a TOML file of
+/// `action = ["Key", ...]` entries.
+pub struct Bindings {
+    /// Per action, in `ACTIONS` order.
+    keys: Vec<Vec<KeyCode>>,
+    path: PathBuf,
+}
+
+impl Bindings {
+    /// Reads `path` over the defaults and `base`, the `[bindings]` table of the engine
+    /// config; a missing file leaves them all.
+    pub fn load(path: &Path, base: Option<&toml::Table>) -> Self {
+        let mut bindings = Self {
+            keys: ACTIONS.iter().map(|(_, _, keys)| keys.to_vec()).collect(),
+            path: path.to_path_buf(),
+        };
+        if let Some(table) = base {
+            bindings.apply_table(table, "the engine config");
+        }
+        if path.exists() {
+            match bindings.apply_file() {
+                Ok(()) => log::info!("Loaded key bindings from {}", path.display()),
+                Err(e) => log::warn!("{:#}", e),
+            }
+        }
+        bindings
+    }
+
+    /// Loads the bindings again from the same file, over a new `base`.
+    pub fn reload(&mut self, base: Option<&toml::Table>) {
+        *self = Self::load(&self.path.clone(), base);
+    }
+
+    fn apply_file(&mut self) -> Result<()> {
+        let path = &self.path;
+        let text = std::fs::read_to_string(path).with_context(|| format!("reading bindings {}", path.display()))?;
+        let table: toml::Table = text.parse().with_context(|| format!("parsing bindings {}", path.display()))?;
+        let source = path.display().to_string();
+        self.apply_table(&table, &source);
+        Ok(())
+    }
+
+    /// Sets the keys of every action `table` lists; `source` names it in warnings.
+    fn apply_table(&mut self, table: &toml::Table, source: &str) {
+        let mut listed = vec![false; ACTIONS.len()];
+        for (name, value) in table {
+            let Some(index) = ACTIONS.iter().position(|(_, n, _)| n == name) else {
+                log::warn!("Ignoring unknown action '{}' in {}", name, source);
+                continue;
+            };
+            let names: Vec<&str> = match value {
+                toml::Value::String(key) => vec![key],
+                toml::Value::Array(keys) => keys.iter().filter_map(toml::Value::as_str).collect(),
+                _ => {
+                    log::warn!("Ignoring '{}' in {}: expected a key name or a list of them", name, source);
+                    continue;
+                }
+            };
+            self.keys[index] = names
+                .into_iter()
+                .filter_map(|key| {
+                    let code = parse_key(key);
+                    if code.is_none() {
+                        log::warn!("Ignoring unknown key '{}' for '{}'", key, name);
+                    }
+                    code
+                })
+                .collect();
+            listed[index] = true;
+        }
+        // Keys the table gives to an action are taken from the others.
+        let taken: Vec<KeyCode> =
+            self.keys.iter().zip(&listed).filter(|(_, &l)| l).flat_map(|(k, _)| k.clone()).collect();
+        for (keys, _) in self.keys.iter_mut().zip(&listed).filter(|(_, &l)| !l) {
+            keys.retain(|k| !taken.contains(k));
+        }
+    }
+
+    /// Writes every action's keys to the file the bindings were loaded from.
+    pub fn save(&self) -> Result<()> {
+        let mut text = String::from("# Key bindings: action = [\"Key\", ...], with winit key names.\n");
+        for ((_, name, _), keys) in ACTIONS.iter().zip(&self.keys) {
+            let names: Vec<String> = keys.iter().filter_map(|&k| key_name(k)).map(|k| format!("\"{}\"", k)).collect();
+            text += &format!("{} = [{}]\n", name, names.join(", "));
+        }
+        std::fs::write(&self.path, text).with_context(|| format!("writing bindings {}", self.path.display()))
+    }
+
+    pub fn action(&self, key: KeyCode) -> Option<Action> {
+        self.keys.iter().position(|keys| keys.contains(&key)).map(|i| ACTIONS[i].0)
+    }
+
+    /// Adds `key` to `action`, taking it from the action it triggered before, if any.
+    pub fn bind(&mut self, action: Action, key: KeyCode) -> Option<Action> {
+        let previous = self.action(key).filter(|&a| a != action);
+        for keys in &mut self.keys {
+            keys.retain(|&k| k != key);
+        }
+        self.keys[action as usize].push(key);
+        previous
+    }
+
+    pub fn clear(&mut self, action: Action) {
+        self.keys[action as usize].clear();
+    }
+
+    pub fn describe(&self, action: Action) -> String {
+        let names: Vec<&str> = self.keys[action as usize].iter().filter_map(|&k| key_name(k)).collect();
+        if names.is_empty() {
+            "unbound".to_string()
+        } else {
+            names.join(", ")
+        }
+    }
+}
+
+/// Runtime rebinding, driven from the keyboard while it is active: the arrows pick an
+/// action, Enter adds the next key pressed to it, Delete unbinds it and Escape saves
+/// and leaves.
+pub struct Rebinder {
+    selected: usize,
+    capturing: bool,
+}
+
+impl Rebinder {
+    pub fn new() -> Self {
+        Self {
+            selected: 0,
+            capturing: false,
+        }
+    }
+
+    /// Handles a key press; false once rebinding is over.
+    pub fn key(&mut self, key: KeyCode, bindings: &mut Bindings) -> bool {
+        let action = ACTIONS[self.selected].0;
+        if self.capturing {
+            self.capturing = false;
+            if key == KeyCode::Escape {
+                return true;
+            }
+            if key_name(key).is_none() {
+                log::warn!("{:?} can't be bound", key);
+                return true;
+            }
+            if let Some(previous) = bindings.bind(action, key) {
+                log::info!("{} no longer triggers {}", key_name(key).unwrap_or_default(), previous.name());
+            }
+            log::info!("{}: {}", action.name(), bindings.describe(action));
+            return true;
+        }
+        match key {
+            KeyCode::ArrowUp => self.selected = (self.selected + ACTIONS.len() - 1) % ACTIONS.len(),
+            KeyCode::ArrowDown => self.selected = (self.selected + 1) % ACTIONS.len(),
+            KeyCode::Enter => self.capturing = true,
+            KeyCode::Delete | KeyCode::Backspace => {
+                bindings.clear(action);
+                log::info!("{}: unbound", action.name());
+            }
+            KeyCode::Escape => {
+                match bindings.save() {
+                    Ok(()) => log::info!("Saved key bindings to {}", bindings.path.display()),
+                    Err(e) => log::warn!("{:#}", e),
+                }
+                return false;
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// What the rebinder is doing, for the window title.
+    pub fn prompt(&self, bindings: &Bindings) -> String {
+        let action = ACTIONS[self.selected].0;
+        if self.capturing {
+            format!("Press a key for {}", action.name())
+        } else {
+            format!(
+                "Rebind {} ({}): Up/Down choose, Enter add key, Delete unbind, Esc save",
+                action.name(),
+                bindings.describe(action)
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The defaults with `toml` as the engine config's `[bindings]` table, and no file.
+    fn with_table(toml: &str) -> Bindings {
+        let table: toml::Table = toml.parse().expect("test table");
+        Bindings::load(Path::new("missing-bindings-for-tests.toml"), Some(&table))
+    }
+
+    #[test]
+    fn defaults_without_a_file() {
+        let bindings = Bindings::load(Path::new("missing-bindings-for-tests.toml"), None);
+        assert_eq!(bindings.action(KeyCode::KeyW), Some(Action::MoveForward));
+        assert_eq!(bindings.action(KeyCode::ShiftRight), Some(Action::Sprint));
+        assert_eq!(bindings.describe(Action::MoveDown), "ControlLeft, ControlRight");
+    }
+
+    #[test]
+    fn keys_as_a_name_or_a_list() {
+        let bindings = with_table("move_forward = \"ArrowUp\"\nsprint = [\"q\", \"ShiftLeft\"]");
+        assert_eq!(bindings.action(KeyCode::ArrowUp), Some(Action::MoveForward));
+        assert_eq!(bindings.action(KeyCode::KeyW), None);
+        assert_eq!(bindings.action(KeyCode::KeyQ), Some(Action::Sprint));
+        assert_eq!(bindings.action(KeyCode::ShiftRight), None);
+    }
+
+    #[test]
+    fn listed_keys_are_taken_from_other_actions() {
+        // F frames the scene by default.
+        let bindings = with_table("move_forward = [\"F\"]");
+        assert_eq!(bindings.action(KeyCode::KeyF), Some(Action::MoveForward));
+        assert_eq!(bindings.describe(Action::FrameScene), "unbound");
+    }
+
+    #[test]
+    fn unknown_actions_keys_and_values_are_skipped() {
+        let bindings = with_table("jump = [\"F\"]\nmove_back = [\"NoSuchKey\", \"ArrowDown\"]\nmove_left = 3");
+        assert_eq!(bindings.action(KeyCode::KeyF), Some(Action::FrameScene));
+        assert_eq!(bindings.describe(Action::MoveBack), "ArrowDown");
+        assert_eq!(bindings.action(KeyCode::KeyA), Some(Action::MoveLeft));
+    }
+
+    #[test]
+    fn digits_and_single_letters() {
+        assert_eq!(parse_key("7"), Some(KeyCode::Digit7));
+        assert_eq!(parse_key("k"), Some(KeyCode::KeyK));
+        assert_eq!(parse_key("pageup"), Some(KeyCode::PageUp));
+        assert_eq!(parse_key("kk"), None);
+    }
+
+    #[test]
+    fn saved_bindings_load_back() {
+        let path = std::env::temp_dir().join(format!("dusk-bindings-{}.toml", std::process::id()));
+        let mut bindings = Bindings::load(&path, None);
+        assert_eq!(bindings.bind(Action::Zoom, KeyCode::KeyF), Some(Action::FrameScene));
+        bindings.clear(Action::Sprint);
+        bindings.save().unwrap();
+        let loaded = Bindings::load(&path, None);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.describe(Action::Zoom), "AltLeft, KeyF");
+        assert_eq!(loaded.describe(Action::FrameScene), "unbound");
+        assert_eq!(loaded.describe(Action::Sprint), "unbound");
+        assert_eq!(loaded.keys, bindings.keys);
+    }
+}
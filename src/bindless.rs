@@ -0,0 +1,178 @@
+use std::num::NonZeroU32;
+
+use crate::material::{Material, MaterialUniform};
+
+/// Textures per material in the array: base color, metallic-roughness and normal.
+const SLOTS: usize = 3;
+/// Sampled textures the scene shaders bind outside the material table.
+const RESERVED_TEXTURES: u32 = 32;
+
+/// Every material's uniforms and textures in one bind group, indexed in the shaders by the
+/// material id of the drawn object. Passes bind it once instead of once per draw, and
+/// GPU-driven draws can mix materials that share a pipeline.
+pub struct BindlessMaterials {
+    layout: wgpu::BindGroupLayout,
+    capacity: usize,
+    uniform_buffer: wgpu::Buffer,
+    replacement_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+impl BindlessMaterials {
+    /// Features the table needs; request them when the adapter has them.
+    pub const FEATURES: wgpu::Features = wgpu::Features::TEXTURE_BINDING_ARRAY
+        .union(wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING);
+
+    /// A table for `capacity` materials, or None when the device lacks the features or
+    /// cannot sample that many textures in one shader stage.
+    pub fn new(device: &wgpu::Device, capacity: usize) -> Option<Self> {
+        if !device.features().contains(Self::FEATURES) || capacity == 0 {
+            return None;
+        }
+        let textures = (capacity * SLOTS) as u32;
+        let limit = device.limits().max_sampled_textures_per_shader_stage;
+        if textures + RESERVED_TEXTURES > limit {
+            log::warn!(
+                "Bindless materials need {} sampled textures per stage, the device allows {}; using bind groups",
+                textures + RESERVED_TEXTURES,
+                limit
+            );
+            return None;
+        }
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("bindless_material_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: NonZeroU32::new(textures),
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Bindless Material Buffer"),
+            size: (capacity * std::mem::size_of::<MaterialUniform>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let replacement_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Bindless Material Replacement Buffer"),
+            size: std::mem::size_of::<[u32; 4]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // Matches the per-material samplers.
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        Some(Self {
+            layout,
+            capacity,
+            uniform_buffer,
+            replacement_buffer,
+            sampler,
+            bind_group: None,
+        })
+    }
+
+    /// Materials the table has room for, fixed by its layout.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Layout of material group 1 for the scene pipelines.
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.layout
+    }
+
+    /// Rebuilds the table from `materials`, in material id order. Call again whenever a
+    /// material's textures change. Ids past the end repeat the last material.
+    pub fn update<'a>(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        materials: impl Iterator<Item = &'a Material> + Clone,
+    ) {
+        let Some(last) = materials.clone().last() else {
+            return;
+        };
+        let materials: Vec<&Material> = materials.chain(std::iter::repeat(last)).take(self.capacity).collect();
+        let uniforms: Vec<MaterialUniform> = materials.iter().map(|m| m.uniform).collect();
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&uniforms));
+        let views: Vec<&wgpu::TextureView> =
+            materials.iter().flat_map(|m| m.textures.iter().map(|t| &t.view)).collect();
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bindless_material_bind_group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureViewArray(&views),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.replacement_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        }));
+    }
+
+    /// Makes every draw use the material with id `replacement`, or its own again.
+    pub fn set_replacement(&self, queue: &wgpu::Queue, replacement: Option<usize>) {
+        let id = replacement.map_or(0, |id| id as u32 + 1);
+        queue.write_buffer(&self.replacement_buffer, 0, bytemuck::cast_slice(&[id, 0, 0, 0]));
+    }
+
+    /// Binds the table at group 1.
+    pub fn bind(&self, pass: &mut wgpu::RenderPass<'_>) {
+        if let Some(bind_group) = &self.bind_group {
+            pass.set_bind_group(1, bind_group, &[]);
+        }
+    }
+}
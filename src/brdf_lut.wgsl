@@ -0,0 +1,70 @@
+const PI: f32 = 3.14159265359;
+const SAMPLE_COUNT: u32 = 512u;
+
+struct FullscreenOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> FullscreenOut {
+    var p = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -3.0),
+        vec2<f32>( 3.0,  1.0),
+        vec2<f32>(-1.0,  1.0),
+    );
+    var o: FullscreenOut;
+    o.pos = vec4<f32>(p[vid], 0.0, 1.0);
+    o.uv = vec2<f32>(p[vid].x * 0.5 + 0.5, 0.5 - p[vid].y * 0.5);
+    return o;
+}
+
+fn radical_inverse(bits_in: u32) -> f32 {
+    var bits = bits_in;
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return f32(bits) * 2.3283064365386963e-10;
+}
+
+fn importance_sample_ggx(xi: vec2<f32>, roughness: f32) -> vec3<f32> {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+}
+
+fn geometry_schlick_ggx_ibl(NdotV: f32, roughness: f32) -> f32 {
+    let k = roughness * roughness / 2.0;
+    return NdotV / (NdotV * (1.0 - k) + k);
+}
+
+// Split-sum DFG terms: x = scale, y = bias applied to F0 (Karis 2013).
+@fragment
+fn fs_integrate(in: FullscreenOut) -> @location(0) vec4<f32> {
+    let NdotV = max(in.uv.x, 1e-3);
+    let roughness = max(in.uv.y, 0.02);
+    let V = vec3<f32>(sqrt(1.0 - NdotV * NdotV), 0.0, NdotV);
+
+    var a = 0.0;
+    var b = 0.0;
+    for (var i = 0u; i < SAMPLE_COUNT; i = i + 1u) {
+        let xi = vec2<f32>(f32(i) / f32(SAMPLE_COUNT), radical_inverse(i));
+        let H = importance_sample_ggx(xi, roughness);
+        let L = normalize(2.0 * dot(V, H) * H - V);
+        let NdotL = max(L.z, 0.0);
+        let NdotH = max(H.z, 0.0);
+        let VdotH = max(dot(V, H), 0.0);
+        if NdotL > 0.0 {
+            let G = geometry_schlick_ggx_ibl(NdotV, roughness) * geometry_schlick_ggx_ibl(NdotL, roughness);
+            let G_vis = (G * VdotH) / (NdotH * NdotV);
+            let Fc = pow(1.0 - VdotH, 5.0);
+            a += (1.0 - Fc) * G_vis;
+            b += Fc * G_vis;
+        }
+    }
+    return vec4<f32>(a / f32(SAMPLE_COUNT), b / f32(SAMPLE_COUNT), 0.0, 1.0);
+}
@@ -0,0 +1,323 @@
+use cgmath::{InnerSpace, Vector3};
+
+use crate::aabb::Aabb;
+
+const BINS: usize = 12;
+const MAX_LEAF_TRIANGLES: usize = 4;
+/// Cost of visiting a node relative to intersecting one triangle.
+const TRAVERSAL_COST: f32 = 0.125;
+
+/// A triangle for GPU ray tracing, with the diffuse color hits on it are shaded with.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BvhTriangle {
+    pub a: [f32; 4],
+    pub b: [f32; 4],
+    pub c: [f32; 4],
+    pub albedo: [f32; 4],
+}
+
+impl BvhTriangle {
+    pub fn new(a: [f32; 3], b: [f32; 3], c: [f32; 3], albedo: [f32; 3]) -> Self {
+        Self {
+            a: [a[0], a[1], a[2], 0.0],
+            b: [b[0], b[1], b[2], 0.0],
+            c: [c[0], c[1], c[2], 0.0],
+            albedo: [albedo[0], albedo[1], albedo[2], 0.0],
+        }
+    }
+
+    fn bounds(&self) -> Aabb {
+        let mut bounds = Aabb::empty();
+        for p in [self.a, self.b, self.c] {
+            bounds.grow([p[0], p[1], p[2]]);
+        }
+        bounds
+    }
+
+    fn centroid(&self, axis: usize) -> f32 {
+        (self.a[axis] + self.b[axis] + self.c[axis]) / 3.0
+    }
+}
+
+/// Interior nodes have `count` 0 and their children at `first` and `first + 1`; leaves
+/// hold `count` triangles starting at `first`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BvhNode {
+    pub min: [f32; 3],
+    pub first: u32,
+    pub max: [f32; 3],
+    pub count: u32,
+}
+
+/// Bounding volume hierarchy over static triangles, built with binned SAH splits and
+/// flattened for traversal in shaders.
+pub struct Bvh {
+    pub nodes: Vec<BvhNode>,
+    pub triangles: Vec<BvhTriangle>,
+}
+
+fn area(bounds: &Aabb) -> f32 {
+    if bounds.is_empty() {
+        return 0.0;
+    }
+    let e = bounds.extent();
+    2.0 * (e.x * e.y + e.y * e.z + e.z * e.x)
+}
+
+fn axis_range(bounds: &Aabb, axis: usize) -> (f32, f32) {
+    match axis {
+        0 => (bounds.min.x, bounds.max.x),
+        1 => (bounds.min.y, bounds.max.y),
+        _ => (bounds.min.z, bounds.max.z),
+    }
+}
+
+fn storage_buffer<T: bytemuck::Pod + bytemuck::Zeroable>(device: &wgpu::Device, label: &str, items: &[T]) -> wgpu::Buffer {
+    use wgpu::util::DeviceExt;
+    // Bindings cannot be empty, so an empty scene gets one zeroed element.
+    let zeroed = [T::zeroed()];
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some(label),
+        contents: bytemuck::cast_slice(if items.is_empty() { &zeroed } else { items }),
+        usage: wgpu::BufferUsages::STORAGE,
+    })
+}
+
+impl Bvh {
+    /// Node and triangle storage buffers for traversal in compute shaders.
+    pub fn create_buffers(&self, device: &wgpu::Device, label: &str) -> (wgpu::Buffer, wgpu::Buffer) {
+        (
+            storage_buffer(device, &format!("{} BVH Nodes", label), &self.nodes),
+            storage_buffer(device, &format!("{} BVH Triangles", label), &self.triangles),
+        )
+    }
+
+    pub fn build(triangles: Vec<BvhTriangle>) -> Self {
+        let bounds: Vec<Aabb> = triangles.iter().map(BvhTriangle::bounds).collect();
+        let mut order: Vec<usize> = (0..triangles.len()).collect();
+        let mut nodes = vec![BvhNode {
+            min: [0.0; 3],
+            first: 0,
+            max: [0.0; 3],
+            count: triangles.len() as u32,
+        }];
+        let mut stack = vec![0usize];
+        while let Some(index) = stack.pop() {
+            let first = nodes[index].first as usize;
+            let count = nodes[index].count as usize;
+            let mut node_bounds = Aabb::empty();
+            let mut centroid_bounds = Aabb::empty();
+            for &t in &order[first..first + count] {
+                node_bounds.union(&bounds[t]);
+                let tri = &triangles[t];
+                centroid_bounds.grow([tri.centroid(0), tri.centroid(1), tri.centroid(2)]);
+            }
+            if !node_bounds.is_empty() {
+                nodes[index].min = node_bounds.min.into();
+                nodes[index].max = node_bounds.max.into();
+            }
+            if count <= MAX_LEAF_TRIANGLES {
+                continue;
+            }
+
+            let candidates = &order[first..first + count];
+            let Some((axis, split)) = Self::best_split(&triangles, &bounds, candidates, area(&node_bounds), &centroid_bounds)
+            else {
+                continue;
+            };
+            let (lo, hi) = axis_range(&centroid_bounds, axis);
+            let scale = BINS as f32 / (hi - lo);
+            let bin = |t: usize| (((triangles[t].centroid(axis) - lo) * scale) as usize).min(BINS - 1);
+            let slice = &mut order[first..first + count];
+            let mut left = 0;
+            for i in 0..slice.len() {
+                if bin(slice[i]) < split {
+                    slice.swap(i, left);
+                    left += 1;
+                }
+            }
+            if left == 0 || left == count {
+                continue;
+            }
+
+            let child = nodes.len();
+            nodes[index].first = child as u32;
+            nodes[index].count = 0;
+            for (offset, len) in [(first, left), (first + left, count - left)] {
+                nodes.push(BvhNode {
+                    min: [0.0; 3],
+                    first: offset as u32,
+                    max: [0.0; 3],
+                    count: len as u32,
+                });
+            }
+            stack.push(child);
+            stack.push(child + 1);
+        }
+
+        let triangles = order.iter().map(|&t| triangles[t]).collect();
+        Self { nodes, triangles }
+    }
+
+    /// Axis and first right-hand bin of the cheapest split, or None when keeping the
+    /// node as a leaf is cheaper.
+    fn best_split(
+        triangles: &[BvhTriangle],
+        bounds: &[Aabb],
+        order: &[usize],
+        node_area: f32,
+        centroid_bounds: &Aabb,
+    ) -> Option<(usize, usize)> {
+        if node_area <= 0.0 {
+            return None;
+        }
+        let mut best: Option<(usize, usize)> = None;
+        let mut best_cost = order.len() as f32;
+        for axis in 0..3 {
+            let (lo, hi) = axis_range(centroid_bounds, axis);
+            if hi - lo <= f32::EPSILON {
+                continue;
+            }
+            let scale = BINS as f32 / (hi - lo);
+            let mut bin_bounds = [Aabb::empty(); BINS];
+            let mut bin_counts = [0usize; BINS];
+            for &t in order {
+                let bin = (((triangles[t].centroid(axis) - lo) * scale) as usize).min(BINS - 1);
+                bin_bounds[bin].union(&bounds[t]);
+                bin_counts[bin] += 1;
+            }
+            let mut right_area = [0.0; BINS];
+            let mut right_count = [0usize; BINS];
+            let mut acc = Aabb::empty();
+            let mut n = 0;
+            for bin in (1..BINS).rev() {
+                acc.union(&bin_bounds[bin]);
+                n += bin_counts[bin];
+                right_area[bin] = area(&acc);
+                right_count[bin] = n;
+            }
+            let mut acc = Aabb::empty();
+            let mut n = 0;
+            for split in 1..BINS {
+                acc.union(&bin_bounds[split - 1]);
+                n += bin_counts[split - 1];
+                let cost = TRAVERSAL_COST
+                    + (area(&acc) * n as f32 + right_area[split] * right_count[split] as f32) / node_area;
+                if cost < best_cost {
+                    best_cost = cost;
+                    best = Some((axis, split));
+                }
+            }
+        }
+        best
+    }
+}
+
+/// Closest intersection found by [`Bvh::intersect`].
+#[derive(Copy, Clone, Debug)]
+pub struct Hit {
+    pub t: f32,
+    pub triangle: usize,
+    /// Geometric normal of the triangle, facing against the ray.
+    pub normal: Vector3<f32>,
+}
+
+fn vec3(p: [f32; 4]) -> Vector3<f32> {
+    Vector3::new(p[0], p[1], p[2])
+}
+
+/// Distance to the node's box along the ray, or None if it is missed before `max_t`.
+fn slab(node: &BvhNode, origin: Vector3<f32>, inv_dir: Vector3<f32>, max_t: f32) -> Option<f32> {
+    let mut near = 0.0f32;
+    let mut far = max_t;
+    for axis in 0..3 {
+        let t0 = (node.min[axis] - origin[axis]) * inv_dir[axis];
+        let t1 = (node.max[axis] - origin[axis]) * inv_dir[axis];
+        near = near.max(t0.min(t1));
+        far = far.min(t0.max(t1));
+    }
+    (near <= far).then_some(near)
+}
+
+/// Möller-Trumbore; hits from either side count.
+fn intersect_triangle(tri: &BvhTriangle, origin: Vector3<f32>, dir: Vector3<f32>) -> Option<f32> {
+    let (a, b, c) = (vec3(tri.a), vec3(tri.b), vec3(tri.c));
+    let e1 = b - a;
+    let e2 = c - a;
+    let p = dir.cross(e2);
+    let det = e1.dot(p);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let s = origin - a;
+    let u = s.dot(p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(e1);
+    let v = dir.dot(q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = e2.dot(q) * inv_det;
+    (t > 0.0).then_some(t)
+}
+
+impl Bvh {
+    /// Closest triangle along the ray within `max_t`, traversing on the CPU.
+    pub fn intersect(&self, origin: Vector3<f32>, dir: Vector3<f32>, max_t: f32) -> Option<Hit> {
+        self.traverse(origin, dir, max_t, false)
+    }
+
+    /// Whether anything lies along the ray within `max_t`.
+    pub fn occluded(&self, origin: Vector3<f32>, dir: Vector3<f32>, max_t: f32) -> bool {
+        self.traverse(origin, dir, max_t, true).is_some()
+    }
+
+    fn traverse(&self, origin: Vector3<f32>, dir: Vector3<f32>, max_t: f32, any: bool) -> Option<Hit> {
+        if self.triangles.is_empty() {
+            return None;
+        }
+        let inv_dir = Vector3::new(1.0 / dir.x, 1.0 / dir.y, 1.0 / dir.z);
+        let mut closest: Option<(f32, usize)> = None;
+        let mut limit = max_t;
+        let mut stack = vec![0usize];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if slab(node, origin, inv_dir, limit).is_none() {
+                continue;
+            }
+            if node.count == 0 {
+                stack.push(node.first as usize);
+                stack.push(node.first as usize + 1);
+                continue;
+            }
+            let first = node.first as usize;
+            for t_index in first..first + node.count as usize {
+                if let Some(t) = intersect_triangle(&self.triangles[t_index], origin, dir) {
+                    if t < limit {
+                        limit = t;
+                        closest = Some((t, t_index));
+                        if any {
+                            stack.clear();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        closest.map(|(t, triangle)| {
+            let tri = &self.triangles[triangle];
+            let normal = (vec3(tri.b) - vec3(tri.a)).cross(vec3(tri.c) - vec3(tri.a));
+            let normal = if normal.magnitude2() > 0.0 { normal.normalize() } else { -dir };
+            Hit {
+                t,
+                triangle,
+                normal: if normal.dot(dir) > 0.0 { -normal } else { normal },
+            }
+        })
+    }
+}
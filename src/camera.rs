@@ -1,6 +1,9 @@
-use cgmath::{InnerSpace, Matrix4, Point3, Vector3};
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3};
 
-fn opengl_to_wgpu_matrix() -> Matrix4<f32> {
+use crate::aabb::Aabb;
+use crate::controller::{DEFAULT_ACCELERATION, DEFAULT_DECELERATION};
+
+pub fn opengl_to_wgpu_matrix() -> Matrix4<f32> {
     Matrix4::new(
         1.0, 0.0, 0.0, 0.0,
         0.0, 1.0, 0.0, 0.0,
@@ -9,6 +12,166 @@ fn opengl_to_wgpu_matrix() -> Matrix4<f32> {
     )
 }
 
+/// How scene depth is laid out in the depth buffer.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DepthMode {
+    /// Depth 0 at the near plane and 1 at the far plane.
+    #[default]
+    Standard,
+    /// Depth 1 at the near plane and 0 at the far plane, which spreads float precision
+    /// evenly over distance instead of spending it all near the camera.
+    Reversed,
+    /// Reversed, with the far plane pushed out to infinity.
+    ReversedInfinite,
+}
+
+impl DepthMode {
+    pub fn is_reversed(self) -> bool {
+        self != DepthMode::Standard
+    }
+
+    /// Shader defines for this mode; see [`crate::pipelines::depth_shader`].
+    pub fn defines(self) -> &'static [&'static str] {
+        if self.is_reversed() { &["REVERSED_Z"] } else { &[] }
+    }
+
+    /// Depth of the far plane, which the depth buffer is cleared to.
+    pub fn far(self) -> f32 {
+        if self.is_reversed() { 0.0 } else { 1.0 }
+    }
+
+    /// The comparison that keeps what `compare` keeps with standard depth.
+    pub fn compare(self, compare: wgpu::CompareFunction) -> wgpu::CompareFunction {
+        use wgpu::CompareFunction::*;
+        if !self.is_reversed() {
+            return compare;
+        }
+        match compare {
+            Less => Greater,
+            LessEqual => GreaterEqual,
+            Greater => Less,
+            GreaterEqual => LessEqual,
+            other => other,
+        }
+    }
+
+    /// Perspective projection into wgpu clip space with this depth layout.
+    pub fn perspective(self, fovy: f32, aspect: f32, znear: f32, zfar: f32) -> Matrix4<f32> {
+        let standard = || opengl_to_wgpu_matrix() * cgmath::perspective(cgmath::Deg(fovy), aspect, znear, zfar);
+        match self {
+            DepthMode::Standard => standard(),
+            // Depth becomes one minus the standard depth.
+            DepthMode::Reversed => Matrix4::new(
+                1.0, 0.0, 0.0, 0.0,
+                0.0, 1.0, 0.0, 0.0,
+                0.0, 0.0, -1.0, 0.0,
+                0.0, 0.0, 1.0, 1.0,
+            ) * standard(),
+            // The limit of the reversed projection as zfar grows: depth is znear over the
+            // view distance.
+            DepthMode::ReversedInfinite => {
+                let f = 1.0 / (fovy.to_radians() * 0.5).tan();
+                Matrix4::new(
+                    f / aspect, 0.0, 0.0, 0.0,
+                    0.0, f, 0.0, 0.0,
+                    0.0, 0.0, 0.0, -1.0,
+                    0.0, 0.0, znear, 0.0,
+                )
+            }
+        }
+    }
+
+    /// Off-axis perspective projection from the tangents of the angles to the left, right,
+    /// top and bottom edges of the view, as XR runtimes give each eye's field of view.
+    /// Left and bottom are negative. Infinite far planes become reversed over `zfar`.
+    pub fn off_axis(self, [left, right, up, down]: [f32; 4], znear: f32, zfar: f32) -> Matrix4<f32> {
+        let frustum = cgmath::frustum(left * znear, right * znear, down * znear, up * znear, znear, zfar);
+        self.reverse(opengl_to_wgpu_matrix() * frustum)
+    }
+
+    /// Orthographic projection `height` world units tall into wgpu clip space with this
+    /// depth layout. It has no infinite form, so that is reversed over `zfar`.
+    pub fn orthographic(self, height: f32, aspect: f32, znear: f32, zfar: f32) -> Matrix4<f32> {
+        let (half_h, half_w) = (height * 0.5, height * 0.5 * aspect);
+        self.reverse(opengl_to_wgpu_matrix() * cgmath::ortho(-half_w, half_w, -half_h, half_h, znear, zfar))
+    }
+
+    /// `standard`, a projection with depth 0 at the near plane, with depth flipped for
+    /// the reversed layouts.
+    fn reverse(self, standard: Matrix4<f32>) -> Matrix4<f32> {
+        if !self.is_reversed() {
+            return standard;
+        }
+        Matrix4::new(
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, -1.0, 0.0,
+            0.0, 0.0, 1.0, 1.0,
+        ) * standard
+    }
+}
+
+/// Inward-facing frustum planes of a wgpu-style (0..1 depth) view-projection matrix.
+pub fn frustum_planes(view_proj: [[f32; 4]; 4]) -> [[f32; 4]; 6] {
+    let m = Matrix4::from(view_proj);
+    let row = |i: usize| [m.x[i], m.y[i], m.z[i], m.w[i]];
+    let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+    let add = |a: [f32; 4], b: [f32; 4]| std::array::from_fn(|i| a[i] + b[i]);
+    let sub = |a: [f32; 4], b: [f32; 4]| std::array::from_fn(|i| a[i] - b[i]);
+    let planes: [[f32; 4]; 6] = [add(r3, r0), sub(r3, r0), add(r3, r1), sub(r3, r1), r2, sub(r3, r2)];
+    planes.map(|p| {
+        let len = (p[0] * p[0] + p[1] * p[1] + p[2] * p[2]).sqrt().max(1e-6);
+        p.map(|c| c / len)
+    })
+}
+
+/// Exposure of a bright overcast day; with the default sun and environment it keeps
+/// shaded values around 1.
+pub const DEFAULT_EV100: f32 = 14.0;
+/// Luminance in nits that an environment map value of 1.0 stands for.
+pub const DEFAULT_ENV_NITS: f32 = 20_000.0;
+
+/// Scale from luminance in nits to the pre-exposed values the shaders work with, for a
+/// camera at `ev100` (saturation-based, Lagarde and de Rousiers 2014).
+pub fn exposure(ev100: f32) -> f32 {
+    1.0 / (1.2 * ev100.exp2())
+}
+
+/// Camera and fly controls, from the `[camera]` table of the engine config and the
+/// command line.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CameraSettings {
+    pub znear: f32,
+    pub zfar: f32,
+    /// Vertical field of view in degrees.
+    pub fovy: f32,
+    /// Fly speeds in metres per second.
+    pub move_speed: f32,
+    pub sprint_speed: f32,
+    /// Radians of turn per pixel of mouse motion.
+    pub mouse_sensitivity: f32,
+    pub acceleration: f32,
+    pub deceleration: f32,
+    pub look_smoothing: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            znear: 0.1,
+            zfar: 1000.0,
+            fovy: 45.0,
+            move_speed: 6.0,
+            sprint_speed: 18.0,
+            mouse_sensitivity: 0.002,
+            acceleration: DEFAULT_ACCELERATION,
+            deceleration: DEFAULT_DECELERATION,
+            look_smoothing: 0.0,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Camera {
     pub position: Point3<f32>,
     pub target: Point3<f32>,
@@ -19,10 +182,11 @@ pub struct Camera {
     pub aspect: f32,
     pub znear: f32,
     pub zfar: f32,
+    pub depth_mode: DepthMode,
 }
 
 impl Camera {
-    pub fn new(width: u32, height: u32) -> Self {
+    pub fn new(width: u32, height: u32, settings: &CameraSettings) -> Self {
         let position: Point3<f32> = Point3::new(0.0, 5.0, 10.0);
         let target: Point3<f32> = Point3::new(0.0, 0.0, 0.0);
         let forward = (target - position).normalize();
@@ -34,10 +198,11 @@ impl Camera {
             up: Vector3::new(0.0, 1.0, 0.0),
             yaw,
             pitch,
-            fovy: 45.0,
+            fovy: settings.fovy,
             aspect: width as f32 / height as f32,
-            znear: 0.1,
-            zfar: 1000.0,
+            znear: settings.znear,
+            zfar: settings.zfar,
+            depth_mode: DepthMode::Standard,
         }
     }
 
@@ -49,6 +214,43 @@ impl Camera {
         self.pitch = forward.y.asin();
     }
 
+    pub fn frame(&mut self, bounds: &Aabb) {
+        let (position, target) = Self::framing_for(bounds);
+        self.set_look_at(position, target);
+    }
+
+    pub fn framing_for(bounds: &Aabb) -> (Point3<f32>, Point3<f32>) {
+        let center = bounds.center();
+        let radius = bounds.radius();
+        (
+            center + Vector3::new(0.0, radius * 0.5 + 1.0, radius * 2.0 + 2.0),
+            center,
+        )
+    }
+
+    /// Where the camera has to be, looking the way it does now, for the bounding sphere of
+    /// `bounds` to just fit the narrower side of the view.
+    pub fn focus_position(&self, bounds: &Aabb) -> Point3<f32> {
+        let half_y = self.fovy.to_radians() * 0.5;
+        let half_x = (half_y.tan() * self.aspect).atan();
+        let distance = bounds.radius().max(0.01) / half_y.min(half_x).sin();
+        bounds.center() - self.forward() * distance
+    }
+
+    /// Fraction of the screen height the bounding sphere of `bounds` spans; infinite when
+    /// the camera is inside it or the bounds are unbounded.
+    pub fn screen_coverage(&self, bounds: &Aabb) -> f32 {
+        if !bounds.is_finite() {
+            return f32::INFINITY;
+        }
+        let radius = bounds.extent().magnitude() * 0.5;
+        let distance = (bounds.center() - self.position).magnitude();
+        if distance <= radius {
+            return f32::INFINITY;
+        }
+        radius / (distance * (self.fovy.to_radians() * 0.5).tan())
+    }
+
     pub fn forward(&self) -> Vector3<f32> {
         Vector3::new(
             self.pitch.cos() * self.yaw.cos(),
@@ -62,6 +264,16 @@ impl Camera {
         self.forward().cross(self.up).normalize()
     }
 
+    /// Direction from the eye through a point on screen, in normalized device coordinates
+    /// (x right, y up, both -1 to 1).
+    pub fn screen_ray(&self, x: f32, y: f32) -> Vector3<f32> {
+        let forward = self.forward();
+        let right = self.right();
+        let up = right.cross(forward);
+        let tan_y = (self.fovy.to_radians() * 0.5).tan();
+        (forward + right * (x * tan_y * self.aspect) + up * (y * tan_y)).normalize()
+    }
+
     pub fn apply_mouse_look(&mut self, dx: f32, dy: f32, sensitivity: f32) {
         self.yaw += dx * sensitivity;
         self.pitch -= dy * sensitivity;
@@ -89,9 +301,15 @@ impl Camera {
     pub fn view_matrix(&self) -> Matrix4<f32> {
         Matrix4::look_at_rh(self.position, self.target, self.up)
     }
+
+    /// Camera-to-world: the inverse of the view matrix, looking down its -Z axis.
+    pub fn world_matrix(&self) -> Matrix4<f32> {
+        self.view_matrix().invert().unwrap_or(Matrix4::from_translation(self.position.to_vec()))
+    }
     
-    pub fn projection_matrix(&self) -> Matrix4<f32> {
-        cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar)
+    /// The projection the scene is rendered with, in wgpu clip space.
+    pub fn clip_projection(&self) -> Matrix4<f32> {
+        self.depth_mode.perspective(self.fovy, self.aspect, self.znear, self.zfar)
     }
 }
 
@@ -104,11 +322,32 @@ pub struct CameraUniform {
     pub position: [f32; 4],
     pub light_view_proj: [[f32; 4]; 4],
     pub light_dir: [f32; 4],
+    /// rgb: pre-exposed environment intensity, w: 1 when the environment is the procedural sky.
     pub env_intensity: [f32; 4],
     pub cascade_splits: [f32; 4],
     pub light_view_proj_cascade1: [[f32; 4]; 4],
     pub light_view_proj_cascade2: [[f32; 4]; 4],
     pub light_view_proj_cascade3: [[f32; 4]; 4],
+    pub time: [f32; 4],
+    /// x: 1 for EVSM filtering, y: light bleeding reduction, zw: EVSM exponents.
+    pub shadow_params: [f32; 4],
+    /// x: cascade map resolution, y: cascade count, z: shadow normal offset.
+    pub shadow_map: [f32; 4],
+    /// x: 1 when the SSAO texture applies, y: 1 when the SSGI texture applies, z: 1 when
+    /// the ray-traced shadow texture applies.
+    pub screen_space: [f32; 4],
+    /// xyz: voxel GI grid minimum corner, w: 1 when voxel GI applies.
+    pub voxel_min: [f32; 4],
+    /// xyz: voxel GI grid size, w: voxel size.
+    pub voxel_extent: [f32; 4],
+    /// x: 1 when volumetric fog applies, y: first froxel slice distance, z: last slice distance.
+    pub fog: [f32; 4],
+    /// x: 1 when the baked lightmap applies, y: exposure to apply to it.
+    pub lightmap: [f32; 4],
+    /// rgb: pre-exposed sun color and intensity, w: angular radius of the sun disk.
+    pub sun: [f32; 4],
+    /// x: debug view index, 0 for regular shading.
+    pub debug: [f32; 4],
 }
 
 impl CameraUniform {
@@ -125,6 +364,16 @@ impl CameraUniform {
             light_view_proj_cascade1: Matrix4::from_scale(1.0).into(),
             light_view_proj_cascade2: Matrix4::from_scale(1.0).into(),
             light_view_proj_cascade3: Matrix4::from_scale(1.0).into(),
+            time: [0.0; 4],
+            shadow_params: [0.0; 4],
+            shadow_map: [4096.0, 4.0, 0.004, 0.0],
+            screen_space: [0.0; 4],
+            voxel_min: [0.0; 4],
+            voxel_extent: [0.0; 4],
+            fog: [0.0; 4],
+            lightmap: [0.0; 4],
+            sun: [0.0; 4],
+            debug: [0.0; 4],
         }
     }
 
@@ -132,9 +381,8 @@ impl CameraUniform {
         use cgmath::SquareMatrix;
         
         let view = camera.view_matrix();
-        let proj = camera.projection_matrix();
-        let view_proj = opengl_to_wgpu_matrix() * proj * view;
-        let proj_wgpu = opengl_to_wgpu_matrix() * proj;
+        let proj_wgpu = camera.clip_projection();
+        let view_proj = proj_wgpu * view;
         
         self.view_proj = view_proj.into();
         self.view_inv = view.invert().unwrap().into();
@@ -145,6 +393,16 @@ impl CameraUniform {
         self.env_intensity = [env_intensity, env_intensity, env_intensity, 0.0];
     }
 
+    /// Offsets the projection by `jitter` in NDC, keeping the inverse in step so depth
+    /// reconstruction matches what was rasterized.
+    pub fn apply_jitter(&mut self, jitter: [f32; 2]) {
+        use cgmath::SquareMatrix;
+
+        let offset = Matrix4::from_translation(Vector3::new(jitter[0], jitter[1], 0.0));
+        self.view_proj = (offset * Matrix4::from(self.view_proj)).into();
+        self.proj_inv = (Matrix4::from(self.proj_inv) * offset.invert().unwrap()).into();
+    }
+
     pub fn update_with_cascades(
         &mut self, 
         camera: &Camera, 
@@ -156,9 +414,8 @@ impl CameraUniform {
         use cgmath::SquareMatrix;
         
         let view = camera.view_matrix();
-        let proj = camera.projection_matrix();
-        let view_proj = opengl_to_wgpu_matrix() * proj * view;
-        let proj_wgpu = opengl_to_wgpu_matrix() * proj;
+        let proj_wgpu = camera.clip_projection();
+        let view_proj = proj_wgpu * view;
         
         self.view_proj = view_proj.into();
         self.view_inv = view.invert().unwrap().into();
@@ -0,0 +1,129 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
+use serde_json::{json, Value};
+
+use crate::snapshot::CameraState;
+
+pub const DEFAULT_CAMERA_PATH: &str = "dusk_camera_path.json";
+pub const DEFAULT_PATH_DURATION: f32 = 10.0;
+
+fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let (t2, t3) = (t * t, t * t * t);
+    0.5 * (2.0 * p1 + (p2 - p0) * t + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2 + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+/// Camera poses to fly through in order, over `duration` seconds. Keys are spaced in time
+/// by the distance between them, so the camera moves at an even pace, and poses between
+/// them follow a Catmull-Rom spline through position, angles and field of view.
+pub struct CameraPath {
+    pub keys: Vec<CameraState>,
+    pub duration: f32,
+}
+
+impl CameraPath {
+    pub fn new(duration: f32) -> Self {
+        Self {
+            keys: Vec::new(),
+            duration,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("reading camera path {}", path.display()))?;
+        let value: Value =
+            serde_json::from_str(&text).with_context(|| format!("parsing camera path {}", path.display()))?;
+        let float = |v: &Value, key: &str| v.get(key).and_then(Value::as_f64).map(|f| f as f32);
+        let keys = value
+            .get("keys")
+            .and_then(Value::as_array)
+            .context("camera path has no keys")?
+            .iter()
+            .filter_map(|k| {
+                let p = k.get("position")?.as_array()?;
+                let p: Vec<f32> = p.iter().filter_map(Value::as_f64).map(|f| f as f32).collect();
+                Some(CameraState {
+                    position: Point3::new(*p.first()?, *p.get(1)?, *p.get(2)?),
+                    yaw: float(k, "yaw")?,
+                    pitch: float(k, "pitch")?,
+                    fovy: float(k, "fovy").unwrap_or(45.0),
+                })
+            })
+            .collect();
+        Ok(Self {
+            keys,
+            duration: float(&value, "duration").unwrap_or(DEFAULT_PATH_DURATION),
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let value = json!({
+            "duration": self.duration,
+            "keys": self.keys.iter().map(|k| json!({
+                "position": [k.position.x, k.position.y, k.position.z],
+                "yaw": k.yaw,
+                "pitch": k.pitch,
+                "fovy": k.fovy,
+            })).collect::<Vec<_>>(),
+        });
+        let text = serde_json::to_string_pretty(&value)?;
+        std::fs::write(path, text).with_context(|| format!("writing camera path {}", path.display()))
+    }
+
+    /// Time of each key in seconds, from 0 to `duration`.
+    fn key_times(&self) -> Vec<f32> {
+        let mut lengths = vec![0.0];
+        for pair in self.keys.windows(2) {
+            let last = lengths[lengths.len() - 1];
+            // A radian of turning counts like a metre of travel, so turns on the spot
+            // still take time.
+            let turn = (pair[1].yaw - pair[0].yaw).abs() + (pair[1].pitch - pair[0].pitch).abs();
+            lengths.push(last + (pair[1].position - pair[0].position).magnitude() + turn);
+        }
+        let total = lengths[lengths.len() - 1];
+        let last_index = (self.keys.len() - 1).max(1) as f32;
+        lengths
+            .iter()
+            .enumerate()
+            .map(|(i, length)| {
+                // Keys that are all the same pose are spaced evenly.
+                let fraction = if total > 1e-4 { length / total } else { i as f32 / last_index };
+                fraction * self.duration
+            })
+            .collect()
+    }
+
+    /// The pose `time` seconds into the path, or None without keys.
+    pub fn sample(&self, time: f32) -> Option<CameraState> {
+        let last = self.keys.len().checked_sub(1)?;
+        if last == 0 {
+            return Some(self.keys[0]);
+        }
+        let times = self.key_times();
+        let time = time.clamp(0.0, self.duration);
+        let segment = times[1..].iter().position(|&t| time <= t).unwrap_or(last - 1);
+        let span = times[segment + 1] - times[segment];
+        let t = if span > 0.0 { (time - times[segment]) / span } else { 1.0 };
+
+        let key = |i: isize| self.keys[i.clamp(0, last as isize) as usize];
+        let i = segment as isize;
+        let (k0, k1, k2, k3) = (key(i - 1), key(i), key(i + 1), key(i + 2));
+        let curve = |f: fn(&CameraState) -> f32| catmull_rom(f(&k0), f(&k1), f(&k2), f(&k3), t);
+        let position = Vector3::new(curve(|k| k.position.x), curve(|k| k.position.y), curve(|k| k.position.z));
+        Some(CameraState {
+            position: Point3::from_vec(position),
+            yaw: curve(|k| k.yaw),
+            pitch: curve(|k| k.pitch),
+            fovy: curve(|k| k.fovy),
+        })
+    }
+}
+
+/// Progress through a playing camera path.
+#[derive(Default)]
+pub struct PathPlayback {
+    pub time: f32,
+    /// Frames captured so far.
+    pub frame: u32,
+}
@@ -0,0 +1,841 @@
+//! The command line of the `dusk_engine` binary.
+
+use std::ops::{Bound, RangeBounds};
+use std::path::PathBuf;
+
+use clap::builder::RangedU64ValueParser;
+use clap::{value_parser, Args, Parser, Subcommand, ValueEnum};
+
+use crate::model::NormalImport;
+use crate::options::{AutoExposure, CameraOverrides, FullscreenMode, ImportOptions, Layout, LatencySettings};
+use crate::options::{MaterialDebugMode, RendererOptions, SceneSource, ShadowBias, ShadowFilter, VertexPacking};
+use crate::{crowd, latency, probes, screenshot, shadows, viewport};
+
+#[derive(Parser, Debug)]
+#[command(version, about = "A wgpu renderer for glTF scenes")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Open models or scene files in a window (the default)
+    View(Box<ViewArgs>),
+    /// Bake probes and lightmaps without a window (reserved)
+    Bake,
+    /// Convert models and textures for faster loading (reserved)
+    Convert,
+}
+
+// A later occurrence of an option replaces an earlier one.
+#[derive(Args, Debug, Default)]
+#[command(args_override_self = true)]
+pub struct ViewArgs {
+    /// glTF models (.gltf or .glb) and .json or .toml scene files, each optionally
+    /// placed with @pos=x,y,z,rot=x,y,z,scale=s
+    pub models: Vec<String>,
+    /// Scene file to load after the models; may be repeated
+    #[arg(long, value_name = "PATH")]
+    pub scene: Vec<PathBuf>,
+    /// Environment HDR to light the scene with
+    #[arg(long, value_name = "PATH")]
+    pub hdr: Option<PathBuf>,
+    /// Engine config file [default: dusk.toml]
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Snapshot to start from
+    #[arg(long, value_name = "PATH")]
+    pub snapshot: Option<PathBuf>,
+    /// Window width in pixels
+    #[arg(long, value_parser = value_parser!(u32).range(1..))]
+    pub width: Option<u32>,
+    /// Window height in pixels
+    #[arg(long, value_parser = value_parser!(u32).range(1..))]
+    pub height: Option<u32>,
+    /// Start fullscreen
+    #[arg(long)]
+    pub fullscreen: bool,
+    /// How to go fullscreen: borderless or exclusive
+    #[arg(long, value_name = "MODE", value_parser = named(FullscreenMode::from_name, "borderless or exclusive"))]
+    pub fullscreen_mode: Option<FullscreenMode>,
+    /// Index of the monitor to go fullscreen on
+    #[arg(long)]
+    pub monitor: Option<usize>,
+    /// Wait for vertical blank; --vsync=false presents as soon as a frame is ready
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true", value_name = "BOOL")]
+    pub vsync: Option<bool>,
+    /// Graphics API to render with
+    #[arg(long, value_enum)]
+    pub backend: Option<Backend>,
+    /// Adapter to render on: its index in --list-gpus or part of its name
+    #[arg(long, value_name = "INDEX|NAME")]
+    pub gpu: Option<String>,
+    /// Print the adapters --gpu chooses from and exit
+    #[arg(long)]
+    pub list_gpus: bool,
+    /// Fraction of the window size to render at, upscaled to fit (0.25-1)
+    #[arg(long, visible_alias = "render-scale", value_parser = parse_scale)]
+    pub scale: Option<f32>,
+    #[command(flatten)]
+    pub import: ImportArgs,
+    #[command(flatten)]
+    pub frames: FrameArgs,
+    #[command(flatten)]
+    pub camera: CameraArgs,
+    #[command(flatten)]
+    pub shadows: ShadowArgs,
+    #[command(flatten)]
+    pub lighting: LightingArgs,
+    #[command(flatten)]
+    pub effects: EffectArgs,
+    #[command(flatten)]
+    pub pipeline: PipelineArgs,
+    #[command(flatten)]
+    pub capture: CaptureArgs,
+}
+
+#[derive(Args, Debug, Default)]
+#[command(next_help_heading = "Import")]
+pub struct ImportArgs {
+    /// Keep the file's normals, even broken ones
+    #[arg(long, conflicts_with = "smooth_normals")]
+    pub keep_normals: bool,
+    /// Rebuild every normal
+    #[arg(long)]
+    pub smooth_normals: bool,
+    /// Degrees below which rebuilt normals are smoothed across an edge [default: 60]
+    #[arg(long, value_name = "DEGREES")]
+    pub smoothing_angle: Option<f32>,
+    /// Don't generate tangents for files without them
+    #[arg(long)]
+    pub no_tangents: bool,
+    /// Draw every blend material dithered in the opaque pass
+    #[arg(long)]
+    pub dither_blend: bool,
+    /// Merge static meshes that share a material and shadow role
+    #[arg(long)]
+    pub batch_static: bool,
+    /// Simplify static meshes without MSFT_lod levels into LODs
+    #[arg(long)]
+    pub lods: bool,
+    /// Flip the green channel of every normal map
+    #[arg(long)]
+    pub flip_normal_y: bool,
+    /// Triangles above which a caster gets a decimated shadow proxy; 0 turns them off
+    /// [default: 100000]
+    #[arg(long, value_name = "TRIANGLES")]
+    pub shadow_proxies: Option<usize>,
+    /// Metres over which blend materials fade out in front of opaque ones [default: 0.1]
+    #[arg(long, value_name = "DISTANCE")]
+    pub soft_fade: Option<f32>,
+    /// How to place several models: row, grid, circle, origin or explicit
+    #[arg(long, value_parser = named(Layout::from_name, "row, grid, circle, origin or explicit"))]
+    pub layout: Option<Layout>,
+}
+
+#[derive(Args, Debug, Default)]
+#[command(next_help_heading = "Frame pacing")]
+pub struct FrameArgs {
+    /// Queue one frame, wait for it before presenting and prefer mailbox
+    #[arg(long)]
+    pub low_latency: bool,
+    /// Wait for the GPU to finish each frame before presenting it
+    #[arg(long)]
+    pub present_wait: bool,
+    /// vsync (fifo), fifo-relaxed, mailbox or immediate
+    #[arg(
+        long,
+        value_name = "MODE",
+        conflicts_with = "vsync",
+        value_parser = named(latency::parse_present_mode, "vsync, fifo, fifo-relaxed, mailbox or immediate")
+    )]
+    pub present_mode: Option<wgpu::PresentMode>,
+    /// Frames the swapchain may queue ahead of the display (1-3)
+    #[arg(long, value_name = "FRAMES", value_parser = value_parser!(u32).range(1..=3))]
+    pub frame_latency: Option<u32>,
+    /// Frames per second to draw at most
+    #[arg(long, value_name = "FPS", value_parser = number_in(1.0f32.., "a rate of at least 1"))]
+    pub fps_cap: Option<f32>,
+    /// Advance movement, animation and the sun in fixed steps at this rate
+    #[arg(long, value_name = "HZ", value_parser = number_in(1.0f32.., "a rate of at least 1"))]
+    pub fixed_timestep: Option<f32>,
+    /// Job threads [default: one per core]
+    #[arg(long, value_name = "COUNT", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub job_threads: Option<usize>,
+}
+
+#[derive(Args, Debug, Default)]
+#[command(next_help_heading = "Camera")]
+pub struct CameraArgs {
+    /// Near clip plane
+    #[arg(long, value_parser = positive)]
+    pub znear: Option<f32>,
+    /// Far clip plane
+    #[arg(long, value_parser = positive)]
+    pub zfar: Option<f32>,
+    /// Vertical field of view in degrees
+    #[arg(long, value_name = "DEGREES", value_parser = number_in(1.0f32..179.0, "degrees from 1 to 179"))]
+    pub fov: Option<f32>,
+    /// Fly speed in units per second
+    #[arg(long, value_parser = positive)]
+    pub move_speed: Option<f32>,
+    /// Fly speed while sprinting
+    #[arg(long, value_parser = positive)]
+    pub sprint_speed: Option<f32>,
+    /// Radians of turn per pixel of mouse movement
+    #[arg(long, value_parser = positive)]
+    pub mouse_sensitivity: Option<f32>,
+    /// How fast the camera speeds up
+    #[arg(long, value_parser = non_negative)]
+    pub acceleration: Option<f32>,
+    /// How fast the camera slows down
+    #[arg(long, value_parser = non_negative)]
+    pub deceleration: Option<f32>,
+    /// Seconds mouse look eases over
+    #[arg(long, value_name = "SECONDS", value_parser = non_negative)]
+    pub look_smoothing: Option<f32>,
+    /// Degrees the field of view widens while sprinting
+    #[arg(long, value_name = "DEGREES", value_parser = non_negative)]
+    pub sprint_fov: Option<f32>,
+    /// Field of view while zoomed, in degrees
+    #[arg(long, value_name = "DEGREES", value_parser = number_in(1.0f32..179.0, "degrees from 1 to 179"))]
+    pub zoom_fov: Option<f32>,
+    /// Reversed depth, for precision far from the camera
+    #[arg(long)]
+    pub reversed_z: bool,
+    /// Reversed depth with no far plane
+    #[arg(long)]
+    pub infinite_far: bool,
+    /// Add a top-down view in a corner
+    #[arg(long)]
+    pub top_view: bool,
+    /// Side-by-side stereo, with an eye distance in metres [default: 0.064]
+    #[arg(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        value_name = "METRES",
+        value_parser = number_in(0.0f32..1.0, "metres below 1")
+    )]
+    pub stereo: Option<Option<f32>>,
+    /// Render into an OpenXR headset; needs the openxr feature
+    #[arg(long)]
+    pub openxr: bool,
+    /// Key bindings file [default: bindings.toml]
+    #[arg(long, value_name = "PATH")]
+    pub bindings: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Default)]
+#[command(next_help_heading = "Shadows")]
+pub struct ShadowArgs {
+    /// pcf or evsm
+    #[arg(long, value_name = "FILTER", value_parser = named(ShadowFilter::from_name, "pcf or evsm"))]
+    pub shadow_filter: Option<ShadowFilter>,
+    /// Size of each shadow cascade map in texels
+    #[arg(long = "shadow-res", value_name = "TEXELS")]
+    pub shadow_resolution: Option<u32>,
+    /// Shadow cascades (1-4)
+    #[arg(long, value_name = "COUNT", value_parser = value_parser!(u32).range(1..=shadows::MAX_CASCADES as i64))]
+    pub shadow_cascades: Option<u32>,
+    /// Constant depth bias of the shadow maps [default: 1]
+    #[arg(long, value_name = "BIAS", allow_negative_numbers = true)]
+    pub shadow_depth_bias: Option<i32>,
+    /// Slope-scaled depth bias of the shadow maps [default: 1]
+    #[arg(long, value_name = "BIAS", allow_negative_numbers = true)]
+    pub shadow_slope_bias: Option<f32>,
+    /// Distance shadow lookups move along the normal [default: 0.004]
+    #[arg(long, value_name = "DISTANCE", allow_negative_numbers = true)]
+    pub shadow_normal_offset: Option<f32>,
+    /// Don't fit the cascades to the depth range on screen
+    #[arg(long)]
+    pub no_sdsm: bool,
+    /// Trace the sun's shadows against a BVH of the static scene
+    #[arg(long)]
+    pub rt_shadows: bool,
+}
+
+#[derive(Args, Debug, Default)]
+#[command(next_help_heading = "Lighting")]
+pub struct LightingArgs {
+    /// Hours from 0 to 24; starts the day cycle
+    #[arg(long, value_name = "HOURS")]
+    pub time_of_day: Option<f32>,
+    /// Seconds for a whole day to pass
+    #[arg(long, value_name = "SECONDS", value_parser = positive)]
+    pub day_length: Option<f32>,
+    /// Light the scene with a procedural sky instead of an environment map
+    #[arg(long)]
+    pub procedural_sky: bool,
+    /// Haze of the procedural sky (1.7-10)
+    #[arg(long, value_parser = number_in(1.7f32..=10.0, "a number from 1.7 to 10"))]
+    pub turbidity: Option<f32>,
+    /// Render the sky and aerial perspective from a physical atmosphere
+    #[arg(long)]
+    pub atmosphere: bool,
+    /// Kilometres per scene unit for the atmosphere
+    #[arg(long, value_name = "KM", value_parser = positive)]
+    pub atmosphere_scale: Option<f32>,
+    /// Bake irradiance probes after the first frame
+    #[arg(long)]
+    pub probes: bool,
+    /// Probes along each axis, such as 8x4x8
+    #[arg(
+        long,
+        value_name = "XxYxZ",
+        value_parser = named(probes::parse_counts, "counts such as 8x4x8, 2-16 per axis")
+    )]
+    pub probe_grid: Option<[u32; 3]>,
+    /// Traced probe GI
+    #[arg(long)]
+    pub ddgi: bool,
+    /// Voxel cone traced GI
+    #[arg(long)]
+    pub vct: bool,
+    /// Bake static lighting into lightmaps
+    #[arg(long)]
+    pub lightmaps: bool,
+    /// Lightmap atlas size in texels (64-8192)
+    #[arg(long, value_name = "TEXELS", value_parser = value_parser!(u32).range(64..=8192))]
+    pub lightmap_size: Option<u32>,
+    /// Rays per lightmap texel
+    #[arg(long, value_name = "COUNT", value_parser = value_parser!(u32).range(1..))]
+    pub lightmap_samples: Option<u32>,
+    /// Camera exposure, over the scene file's
+    #[arg(long, allow_negative_numbers = true, value_parser = finite)]
+    pub ev100: Option<f32>,
+    /// Environment luminance in nits, over the scene file's
+    #[arg(long, value_name = "NITS", value_parser = non_negative)]
+    pub env_nits: Option<f32>,
+    /// Meter the exposure from the frame
+    #[arg(long)]
+    pub auto_exposure: bool,
+    /// Lowest EV100 auto exposure goes to
+    #[arg(long, value_name = "EV100", allow_negative_numbers = true, value_parser = finite)]
+    pub auto_exposure_min: Option<f32>,
+    /// Highest EV100 auto exposure goes to
+    #[arg(long, value_name = "EV100", allow_negative_numbers = true, value_parser = finite)]
+    pub auto_exposure_max: Option<f32>,
+    /// Adaptation speed towards brighter scenes
+    #[arg(long, value_name = "SPEED", value_parser = positive)]
+    pub auto_exposure_speed_up: Option<f32>,
+    /// Adaptation speed towards darker scenes
+    #[arg(long, value_name = "SPEED", value_parser = positive)]
+    pub auto_exposure_speed_down: Option<f32>,
+}
+
+#[derive(Args, Debug, Default)]
+#[command(next_help_heading = "Effects")]
+pub struct EffectArgs {
+    /// Turn screen-space ambient occlusion off
+    #[arg(long)]
+    pub no_ssao: bool,
+    /// SSAO sample radius
+    #[arg(long, value_parser = positive)]
+    pub ssao_radius: Option<f32>,
+    /// SSAO strength
+    #[arg(long, value_parser = positive)]
+    pub ssao_intensity: Option<f32>,
+    /// Screen-space global illumination
+    #[arg(long)]
+    pub ssgi: bool,
+    /// SSGI sample radius
+    #[arg(long, value_parser = positive)]
+    pub ssgi_radius: Option<f32>,
+    /// SSGI strength
+    #[arg(long, value_parser = non_negative)]
+    pub ssgi_intensity: Option<f32>,
+    /// Camera and object motion blur
+    #[arg(long)]
+    pub motion_blur: bool,
+    /// Shutter angle of the motion blur in degrees (0-360)
+    #[arg(long, value_name = "DEGREES", value_parser = number_in(0.0f32..=360.0, "degrees from 0 to 360"))]
+    pub shutter_angle: Option<f32>,
+    /// Temporal anti-aliasing
+    #[arg(long)]
+    pub taa: bool,
+    /// FXAA after tonemapping
+    #[arg(long)]
+    pub fxaa: bool,
+    /// Volumetric fog lit by the sun
+    #[arg(long)]
+    pub fog: bool,
+    /// Fog extinction per unit
+    #[arg(long, value_parser = non_negative)]
+    pub fog_density: Option<f32>,
+    /// Forward scattering of the fog, above -1 and below 1
+    #[arg(
+        long,
+        value_name = "G",
+        allow_negative_numbers = true,
+        value_parser = number_in(-1.0f32..1.0, "a number between -1 and 1")
+    )]
+    pub fog_anisotropy: Option<f32>,
+    /// How fast the fog thins with height
+    #[arg(long, value_parser = non_negative)]
+    pub fog_height_falloff: Option<f32>,
+    /// RCAS sharpening of the upscaler in stops; 0 is sharpest
+    #[arg(long, value_name = "STOPS", value_parser = non_negative)]
+    pub sharpness: Option<f32>,
+    /// Post-processing stack file [default: dusk_post.json when it exists]
+    #[arg(long, value_name = "PATH")]
+    pub post: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Default)]
+#[command(next_help_heading = "Pipeline")]
+pub struct PipelineArgs {
+    /// Deferred shading through a G-buffer
+    #[arg(long)]
+    pub deferred: bool,
+    /// Draw depth before the opaque pass even when no effect needs it
+    #[arg(long)]
+    pub depth_prepass: bool,
+    /// Cull and draw static meshes on the GPU
+    #[arg(long)]
+    pub gpu_culling: bool,
+    /// Bind each material on its own instead of all in one bind group
+    #[arg(long)]
+    pub no_bindless: bool,
+    /// Pack vertices into fewer bytes
+    #[arg(long)]
+    pub compact_vertices: bool,
+    /// Stream texture mips within this many megabytes
+    #[arg(long, value_name = "MB")]
+    pub texture_budget: Option<u64>,
+    /// Draw every mesh with a debug material: checker, clay, off or a material index
+    #[arg(
+        long,
+        value_name = "MODE",
+        value_parser = named(MaterialDebugMode::parse, "checker, clay, off or a material index")
+    )]
+    pub material_debug: Option<MaterialDebugMode>,
+}
+
+#[derive(Args, Debug, Default)]
+#[command(next_help_heading = "Capture and benchmarks")]
+pub struct CaptureArgs {
+    /// Camera path file [default: dusk_camera_path.json]
+    #[arg(long, value_name = "PATH")]
+    pub camera_path: Option<PathBuf>,
+    /// Seconds to play the camera path over
+    #[arg(long, value_name = "SECONDS", value_parser = positive)]
+    pub path_duration: Option<f32>,
+    /// Directory to write every frame of the camera path to
+    #[arg(long, value_name = "DIR")]
+    pub path_capture: Option<PathBuf>,
+    /// Frame rate of captured camera paths [default: 30]
+    #[arg(long, value_name = "FPS", value_parser = number_in(1.0f32.., "a rate of at least 1"))]
+    pub path_fps: Option<f32>,
+    /// Play the camera path at startup
+    #[arg(long)]
+    pub play_path: bool,
+    /// Screenshot size in each axis as a multiple of the window's: 1, 2 or 4
+    #[arg(long, value_name = "FACTOR", value_parser = supersample_factor)]
+    pub screenshot_supersample: Option<u32>,
+    /// Write screenshots as EXR as well as PNG
+    #[arg(long)]
+    pub exr: bool,
+    /// Shot matrix to render offscreen instead of opening the window
+    #[arg(long, value_name = "PATH")]
+    pub batch: Option<PathBuf>,
+    /// Chrome trace file to write the profiler scopes to
+    #[arg(long, value_name = "PATH")]
+    pub profile_trace: Option<PathBuf>,
+    /// Add walking figures for the crowd benchmark [default: 10000]
+    #[arg(long, num_args = 0..=1, require_equals = true, value_name = "COUNT")]
+    pub crowd: Option<Option<u32>>,
+    /// Exit after this many crowd frames
+    #[arg(long, value_name = "FRAMES")]
+    pub crowd_frames: Option<u32>,
+    /// Write crash reports without showing a dialog
+    #[arg(long)]
+    pub no_crash_dialog: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Backend {
+    Vulkan,
+    Metal,
+    Dx12,
+    Gl,
+}
+
+impl Backend {
+    pub fn backends(self) -> wgpu::Backends {
+        match self {
+            Self::Vulkan => wgpu::Backends::VULKAN,
+            Self::Metal => wgpu::Backends::METAL,
+            Self::Dx12 => wgpu::Backends::DX12,
+            Self::Gl => wgpu::Backends::GL,
+        }
+    }
+}
+
+fn parse_scale(text: &str) -> Result<f32, String> {
+    number_in(0.25..=1.0, "a number from 0.25 to 1")(text)
+}
+
+/// A value parser for the numbers `range` holds, described as `expected` in its errors.
+fn number_in<T, R>(range: R, expected: &'static str) -> impl Fn(&str) -> Result<T, String> + Clone
+where
+    T: std::str::FromStr + PartialOrd,
+    R: RangeBounds<T> + Clone,
+{
+    move |text| text.parse().ok().filter(|value| range.contains(value)).ok_or_else(|| format!("expected {}", expected))
+}
+
+fn positive(text: &str) -> Result<f32, String> {
+    number_in((Bound::Excluded(0.0), Bound::Unbounded), "a number above 0")(text)
+}
+
+fn non_negative(text: &str) -> Result<f32, String> {
+    number_in(0.0.., "a number of at least 0")(text)
+}
+
+fn finite(text: &str) -> Result<f32, String> {
+    number_in(f32::MIN..=f32::MAX, "a finite number")(text)
+}
+
+fn supersample_factor(text: &str) -> Result<u32, String> {
+    let factor = text.parse().ok().filter(|factor| screenshot::SUPERSAMPLE_FACTORS.contains(factor));
+    factor.ok_or_else(|| "expected 1, 2 or 4".to_string())
+}
+
+/// A value parser for the names `parse` knows, listing the `expected` ones in its errors.
+fn named<T>(parse: fn(&str) -> Option<T>, expected: &'static str) -> impl Fn(&str) -> Result<T, String> + Clone {
+    move |text| parse(text).ok_or_else(|| format!("expected {}", expected))
+}
+
+impl Cli {
+    /// Parses the process arguments, exiting with the usage on errors.
+    pub fn parse_args() -> Self {
+        Self::parse_from_args(std::env::args().collect())
+    }
+
+    /// Parses `args`, the program name first, exiting with the usage on errors.
+    pub fn parse_from_args(args: Vec<String>) -> Self {
+        Self::try_parse_from_args(args).unwrap_or_else(|e| e.exit())
+    }
+
+    /// Parses `args`, the program name first. Without a subcommand they are taken as
+    /// `view`'s, as they were before subcommands existed.
+    pub fn try_parse_from_args(mut args: Vec<String>) -> Result<Self, clap::Error> {
+        let command = <Self as clap::CommandFactory>::command();
+        let first = args.get(1).map(String::as_str);
+        let is_subcommand = |arg: &str| arg == "help" || command.find_subcommand(arg).is_some();
+        let is_top_level = |arg: &str| ["-h", "--help", "-V", "--version"].contains(&arg);
+        if first.is_none_or(|arg| !is_subcommand(arg) && !is_top_level(arg)) {
+            args.insert(1.min(args.len()), "view".to_string());
+        }
+        Self::try_parse_from(args)
+    }
+}
+
+impl ViewArgs {
+    /// The options to build the renderer with; those not given keep their defaults.
+    pub fn renderer_options(&self) -> RendererOptions {
+        let defaults = RendererOptions::default();
+        let scene_files = self.scene.iter().cloned().map(SceneSource::File);
+        let mut options = RendererOptions {
+            scene: self.models.iter().map(|arg| SceneSource::from_arg(arg)).chain(scene_files).collect(),
+            hdr: self.hdr.clone(),
+            config: self.config.clone().unwrap_or(defaults.config),
+            snapshot: self.snapshot.clone(),
+            width: self.width,
+            height: self.height,
+            fullscreen: self.fullscreen,
+            fullscreen_mode: self.fullscreen_mode,
+            monitor: self.monitor,
+            vsync: self.vsync,
+            backends: self.backend.map_or(defaults.backends, Backend::backends),
+            gpu: self.gpu.clone(),
+            render_scale: self.scale,
+            ..defaults
+        };
+        self.import.apply(&mut options.import);
+        options.layout = self.import.layout;
+        self.frames.apply(&mut options);
+        self.camera.apply(&mut options);
+        self.shadows.apply(&mut options);
+        self.lighting.apply(&mut options);
+        self.effects.apply(&mut options);
+        self.pipeline.apply(&mut options);
+        self.capture.apply(&mut options);
+        options
+    }
+}
+
+impl ImportArgs {
+    fn apply(&self, import: &mut ImportOptions) {
+        if self.keep_normals {
+            import.normals = NormalImport::Keep;
+        } else if self.smooth_normals {
+            import.normals = NormalImport::Regenerate;
+        }
+        import.smoothing_angle = self.smoothing_angle.unwrap_or(import.smoothing_angle);
+        import.generate_tangents &= !self.no_tangents;
+        import.dither_blend |= self.dither_blend;
+        import.batch_by_material |= self.batch_static;
+        import.generate_lods |= self.lods;
+        import.flip_normal_y |= self.flip_normal_y;
+        import.shadow_proxy_triangles = self.shadow_proxies.unwrap_or(import.shadow_proxy_triangles);
+        import.soft_fade = self.soft_fade.unwrap_or(import.soft_fade);
+    }
+}
+
+impl FrameArgs {
+    fn apply(&self, options: &mut RendererOptions) {
+        if self.low_latency {
+            options.latency = LatencySettings::low_latency();
+        }
+        let latency = &mut options.latency;
+        latency.wait_before_present |= self.present_wait;
+        latency.present_mode = self.present_mode.or(latency.present_mode);
+        latency.max_frame_latency = self.frame_latency.unwrap_or(latency.max_frame_latency);
+        options.fps_cap = self.fps_cap;
+        options.fixed_timestep = self.fixed_timestep;
+        options.job_threads = self.job_threads.unwrap_or(options.job_threads);
+    }
+}
+
+impl CameraArgs {
+    fn apply(&self, options: &mut RendererOptions) {
+        options.camera = CameraOverrides {
+            znear: self.znear,
+            zfar: self.zfar,
+            fovy: self.fov,
+            move_speed: self.move_speed,
+            sprint_speed: self.sprint_speed,
+            mouse_sensitivity: self.mouse_sensitivity,
+            acceleration: self.acceleration,
+            deceleration: self.deceleration,
+            look_smoothing: self.look_smoothing,
+        };
+        options.sprint_fov = self.sprint_fov;
+        options.zoom_fov = self.zoom_fov;
+        options.reversed_z = self.reversed_z;
+        options.infinite_far = self.infinite_far;
+        options.top_view = self.top_view;
+        options.stereo = self.stereo.map(|ipd| ipd.unwrap_or(viewport::DEFAULT_IPD));
+        options.openxr = self.openxr;
+        if let Some(path) = &self.bindings {
+            options.bindings = path.clone();
+        }
+    }
+}
+
+impl ShadowArgs {
+    fn apply(&self, options: &mut RendererOptions) {
+        options.shadow_filter = self.shadow_filter;
+        options.shadow_resolution = self.shadow_resolution;
+        options.shadow_cascades = self.shadow_cascades;
+        let defaults = ShadowBias::default();
+        options.shadow_bias = ShadowBias {
+            constant: self.shadow_depth_bias.unwrap_or(defaults.constant),
+            slope_scale: self.shadow_slope_bias.unwrap_or(defaults.slope_scale),
+            normal_offset: self.shadow_normal_offset.unwrap_or(defaults.normal_offset),
+        };
+        options.sdsm = !self.no_sdsm;
+        options.rt_shadows = self.rt_shadows;
+    }
+}
+
+impl LightingArgs {
+    fn apply(&self, options: &mut RendererOptions) {
+        options.time_of_day = self.time_of_day;
+        options.day_length = self.day_length;
+        options.procedural_sky = self.procedural_sky;
+        options.turbidity = self.turbidity.unwrap_or(options.turbidity);
+        options.atmosphere = self.atmosphere;
+        options.atmosphere_scale = self.atmosphere_scale.unwrap_or(options.atmosphere_scale);
+        options.probes = self.probes;
+        options.probe_grid = self.probe_grid.unwrap_or(options.probe_grid);
+        options.ddgi = self.ddgi;
+        options.vct = self.vct;
+        options.lightmaps = self.lightmaps;
+        options.lightmap_size = self.lightmap_size.unwrap_or(options.lightmap_size);
+        options.lightmap_samples = self.lightmap_samples.unwrap_or(options.lightmap_samples);
+        options.ev100 = self.ev100;
+        options.env_nits = self.env_nits;
+        let defaults = AutoExposure::default();
+        options.auto_exposure = AutoExposure {
+            enabled: self.auto_exposure,
+            min_ev100: self.auto_exposure_min.unwrap_or(defaults.min_ev100),
+            max_ev100: self.auto_exposure_max.unwrap_or(defaults.max_ev100),
+            speed_up: self.auto_exposure_speed_up.unwrap_or(defaults.speed_up),
+            speed_down: self.auto_exposure_speed_down.unwrap_or(defaults.speed_down),
+            ..defaults
+        };
+    }
+}
+
+impl EffectArgs {
+    fn apply(&self, options: &mut RendererOptions) {
+        options.ssao = !self.no_ssao;
+        options.ssao_radius = self.ssao_radius.unwrap_or(options.ssao_radius);
+        options.ssao_intensity = self.ssao_intensity.unwrap_or(options.ssao_intensity);
+        options.ssgi = self.ssgi;
+        options.ssgi_radius = self.ssgi_radius.unwrap_or(options.ssgi_radius);
+        options.ssgi_intensity = self.ssgi_intensity.unwrap_or(options.ssgi_intensity);
+        options.motion_blur = self.motion_blur;
+        options.shutter_angle = self.shutter_angle.unwrap_or(options.shutter_angle);
+        options.taa = self.taa;
+        options.fxaa = self.fxaa;
+        options.fog = self.fog;
+        options.fog_density = self.fog_density.unwrap_or(options.fog_density);
+        options.fog_anisotropy = self.fog_anisotropy.unwrap_or(options.fog_anisotropy);
+        options.fog_height_falloff = self.fog_height_falloff.unwrap_or(options.fog_height_falloff);
+        options.sharpness = self.sharpness.unwrap_or(options.sharpness);
+        options.post = self.post.clone();
+    }
+}
+
+impl PipelineArgs {
+    fn apply(&self, options: &mut RendererOptions) {
+        options.deferred = self.deferred;
+        options.depth_prepass = self.depth_prepass;
+        options.gpu_culling = self.gpu_culling;
+        options.bindless = !self.no_bindless;
+        if self.compact_vertices {
+            options.vertex_packing = VertexPacking::Compact;
+        }
+        options.texture_budget = self.texture_budget.map(|megabytes| megabytes * 1024 * 1024);
+        options.material_debug = self.material_debug.unwrap_or(options.material_debug);
+    }
+}
+
+impl CaptureArgs {
+    fn apply(&self, options: &mut RendererOptions) {
+        if let Some(path) = &self.camera_path {
+            options.camera_path = path.clone();
+        }
+        options.path_duration = self.path_duration;
+        options.path_capture = self.path_capture.clone();
+        options.path_fps = self.path_fps.unwrap_or(options.path_fps);
+        options.play_path = self.play_path;
+        options.screenshot_supersample = self.screenshot_supersample.unwrap_or(options.screenshot_supersample);
+        options.exr = self.exr;
+        options.batch = self.batch.clone();
+        options.profile_trace = self.profile_trace.clone();
+        options.crowd = self.crowd.map(|count| count.unwrap_or(crowd::DEFAULT_CROWD_SIZE));
+        options.crowd_frames = self.crowd_frames;
+        options.crash_dialog = !self.no_crash_dialog;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        std::iter::once("dusk_engine").chain(args.iter().copied()).map(String::from).collect()
+    }
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::parse_from_args(self::args(args))
+    }
+
+    fn view(args: &[&str]) -> ViewArgs {
+        match parse(args).command {
+            Command::View(view) => *view,
+            command => panic!("expected view, got {:?}", command),
+        }
+    }
+
+    #[test]
+    fn arguments_without_a_command_are_views() {
+        let view = view(&["a.glb", "b.glb"]);
+        assert_eq!(view.models, ["a.glb", "b.glb"]);
+        assert!(matches!(parse(&[]).command, Command::View(_)));
+        assert!(matches!(parse(&["bake"]).command, Command::Bake));
+        assert!(matches!(parse(&["convert"]).command, Command::Convert));
+    }
+
+    fn options(args: &[&str]) -> RendererOptions {
+        view(args).renderer_options()
+    }
+
+    #[test]
+    fn declared_options_become_renderer_options() {
+        let options = options(&[
+            "view", "building.glb", "--hdr", "sky.hdr", "--width", "1920", "--height=1080", "--vsync=false",
+            "--backend", "dx12", "--gpu", "nvidia", "--scale", "0.75",
+        ]);
+        assert!(matches!(&options.scene[..], [SceneSource::Model(e)] if e.path == Path::new("building.glb")));
+        assert_eq!(options.hdr.as_deref(), Some(Path::new("sky.hdr")));
+        assert_eq!((options.width, options.height), (Some(1920), Some(1080)));
+        assert_eq!(options.vsync, Some(false));
+        assert_eq!(options.backends, wgpu::Backends::DX12);
+        assert_eq!(options.gpu.as_deref(), Some("nvidia"));
+        assert_eq!(options.render_scale, Some(0.75));
+        assert_eq!(view(&["--vsync"]).vsync, Some(true));
+    }
+
+    #[test]
+    fn renderer_flags_take_values_either_way() {
+        let options = options(&["--shadow-res", "2048", "--taa", "scene.toml", "--fog-density=0.2", "--fxaa"]);
+        assert!(matches!(&options.scene[..], [SceneSource::File(path)] if path == Path::new("scene.toml")));
+        assert!(options.taa && options.fxaa && !options.ssgi);
+        assert_eq!(options.shadow_resolution, Some(2048));
+        assert_eq!(options.fog_density, 0.2);
+
+        let options = self::options(&["--crowd", "--stereo", "--texture-budget", "64", "--scene=a.toml", "b.glb"]);
+        assert_eq!(options.crowd, Some(crowd::DEFAULT_CROWD_SIZE));
+        assert_eq!(options.stereo, Some(viewport::DEFAULT_IPD));
+        assert_eq!(options.texture_budget, Some(64 * 1024 * 1024));
+        assert!(matches!(&options.scene[..], [SceneSource::Model(_), SceneSource::File(_)]));
+        let options = self::options(&["--crowd=500", "--stereo=0.07", "--shadow-depth-bias", "-2", "--no-ssao"]);
+        assert_eq!((options.crowd, options.stereo), (Some(500), Some(0.07)));
+        assert_eq!(options.shadow_bias.constant, -2);
+        assert!(!options.ssao);
+    }
+
+    #[test]
+    fn later_flags_win() {
+        let options = options(&["--scale", "0.75", "--render-scale=0.5", "--fov=60", "--fov", "70"]);
+        assert_eq!(options.render_scale, Some(0.5));
+        assert_eq!(options.camera.fovy, Some(70.0));
+
+        let options = self::options(&["--present-mode=mailbox", "--low-latency"]);
+        assert_eq!((options.vsync, options.latency.present_mode), (None, Some(wgpu::PresentMode::Mailbox)));
+        assert_eq!(options.latency.max_frame_latency, LatencySettings::low_latency().max_frame_latency);
+    }
+
+    #[test]
+    fn bad_values_and_unknown_flags_are_errors() {
+        let fails = |args: &[&str]| Cli::try_parse_from_args(self::args(args)).is_err();
+        assert!(fails(&["--shadow-res=big"]));
+        assert!(fails(&["--fov=200"]));
+        assert!(fails(&["--shadow-cascades=5"]));
+        assert!(fails(&["--layout=spiral"]));
+        assert!(fails(&["--unknown"]));
+        assert!(fails(&["--shadow-rez=2048"]));
+        assert!(fails(&["--vsync", "--present-mode=mailbox"]));
+        assert!(!fails(&["--layout=grid", "--shadow-cascades=2", "--probe-grid=8x4x8"]));
+    }
+
+    #[test]
+    fn arguments_after_the_separator_are_models() {
+        let view = view(&["--taa", "--", "--odd-name.glb"]);
+        assert_eq!(view.models, ["--odd-name.glb"]);
+        assert!(view.effects.taa);
+    }
+
+    #[test]
+    fn list_gpus_is_a_view_option() {
+        assert!(view(&["--list-gpus"]).list_gpus);
+        assert!(!view(&["a.glb"]).list_gpus);
+    }
+
+    #[test]
+    fn scale_is_checked() {
+        assert_eq!(parse_scale("0.5"), Ok(0.5));
+        assert!(parse_scale("0.1").is_err());
+        assert!(parse_scale("1.5").is_err());
+        assert!(parse_scale("half").is_err());
+    }
+}
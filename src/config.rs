@@ -0,0 +1,167 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::camera::CameraSettings;
+use crate::evsm::ShadowFilter;
+use crate::options::RendererOptions;
+use crate::shadows;
+use crate::window_mode::FullscreenMode;
+
+/// Where the engine config loads from unless `--config=` says otherwise.
+pub const DEFAULT_CONFIG_PATH: &str = "dusk.toml";
+
+/// Settings read from the engine config file at startup. Command-line flags override
+/// them. Settings left out are None and keep the engine's defaults.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EngineConfig {
+    pub camera: CameraSettings,
+    /// Inner size of the window in pixels.
+    pub window_size: Option<(u32, u32)>,
+    pub vsync: Option<bool>,
+    /// Whether the window starts fullscreen.
+    pub fullscreen: Option<bool>,
+    pub fullscreen_mode: Option<FullscreenMode>,
+    /// Index of the monitor to go fullscreen on.
+    pub monitor: Option<usize>,
+    pub shadow_resolution: Option<u32>,
+    pub shadow_cascades: Option<u32>,
+    pub shadow_filter: Option<ShadowFilter>,
+    pub render_scale: Option<f32>,
+    /// The `[bindings]` table, laid out like the bindings file.
+    pub bindings: Option<toml::Table>,
+    /// Scene file to show when the command line names no models.
+    pub scene: Option<PathBuf>,
+}
+
+impl EngineConfig {
+    /// Reads `path` over the defaults; a missing file leaves them all.
+    pub fn load(path: &Path) -> Self {
+        let mut config = Self::default();
+        if path.exists() {
+            match config.apply_file(path) {
+                Ok(()) => log::info!("Loaded engine config from {}", path.display()),
+                Err(e) => log::warn!("{:#}", e),
+            }
+        }
+        config
+    }
+
+    /// The config with the settings `options` give in place of its own, as the renderer
+    /// applies them: command-line flags win over the file, and a `--present-mode=` turns
+    /// the file's vsync off.
+    pub fn under(mut self, options: &RendererOptions) -> Self {
+        if options.width.is_some() || options.height.is_some() {
+            let (width, height) = self.window_size.unwrap_or((1280, 720));
+            self.window_size = Some((options.width.unwrap_or(width), options.height.unwrap_or(height)));
+        }
+        self.vsync = options.vsync.or(self.vsync.filter(|_| options.latency.present_mode.is_none()));
+        if options.fullscreen {
+            self.fullscreen = Some(true);
+        }
+        self.fullscreen_mode = options.fullscreen_mode.or(self.fullscreen_mode);
+        self.monitor = options.monitor.or(self.monitor);
+        self.shadow_resolution = options.shadow_resolution.or(self.shadow_resolution);
+        self.shadow_cascades = options.shadow_cascades.or(self.shadow_cascades);
+        self.shadow_filter = options.shadow_filter.or(self.shadow_filter);
+        self.render_scale = options.render_scale.or(self.render_scale);
+        options.camera.apply(&mut self.camera);
+        self
+    }
+
+    fn apply_file(&mut self, path: &Path) -> Result<()> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("reading config {}", path.display()))?;
+        let table: toml::Table = text.parse().with_context(|| format!("parsing config {}", path.display()))?;
+        for (key, value) in &table {
+            match (key.as_str(), value) {
+                ("camera", toml::Value::Table(camera)) => self.camera.apply(camera, path),
+                ("window", toml::Value::Table(window)) => self.apply_window(window, path),
+                ("shadows", toml::Value::Table(shadows)) => self.apply_shadows(shadows, path),
+                ("bindings", toml::Value::Table(bindings)) => self.bindings = Some(bindings.clone()),
+                ("render_scale", value) => match number(value) {
+                    Some(scale) if (0.25..=1.0).contains(&scale) => self.render_scale = Some(scale),
+                    _ => log::warn!("Ignoring invalid render scale '{}' (0.25-1) in {}", value, path.display()),
+                },
+                ("scene", toml::Value::String(scene)) => self.scene = Some(PathBuf::from(scene)),
+                _ => log::warn!("Ignoring unknown or invalid setting '{}' in {}", key, path.display()),
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_window(&mut self, table: &toml::Table, path: &Path) {
+        let mut size = self.window_size.unwrap_or((1280, 720));
+        for (key, value) in table {
+            let pixels = value.as_integer().and_then(|n| u32::try_from(n).ok()).filter(|&n| n > 0);
+            match (key.as_str(), pixels, value.as_bool()) {
+                ("width", Some(width), _) => size.0 = width,
+                ("height", Some(height), _) => size.1 = height,
+                ("vsync", _, Some(vsync)) => self.vsync = Some(vsync),
+                ("fullscreen", _, Some(fullscreen)) => self.fullscreen = Some(fullscreen),
+                ("fullscreen_mode", ..) if value.as_str().and_then(FullscreenMode::from_name).is_some() => {
+                    self.fullscreen_mode = value.as_str().and_then(FullscreenMode::from_name);
+                }
+                ("monitor", ..) if value.as_integer().is_some_and(|n| n >= 0) => {
+                    self.monitor = value.as_integer().map(|n| n as usize);
+                }
+                _ => log::warn!("Ignoring unknown or invalid window setting '{}' in {}", key, path.display()),
+            }
+        }
+        if table.contains_key("width") || table.contains_key("height") {
+            self.window_size = Some(size);
+        }
+    }
+
+    fn apply_shadows(&mut self, table: &toml::Table, path: &Path) {
+        for (key, value) in table {
+            let count = value.as_integer().and_then(|n| u32::try_from(n).ok());
+            match (key.as_str(), count, value.as_str()) {
+                ("resolution", Some(resolution), _) => self.shadow_resolution = Some(resolution),
+                ("cascades", Some(n), _) if (1..=shadows::MAX_CASCADES).contains(&n) => self.shadow_cascades = Some(n),
+                ("filter", _, Some(name)) if ShadowFilter::from_name(name).is_some() => {
+                    self.shadow_filter = ShadowFilter::from_name(name);
+                }
+                _ => log::warn!("Ignoring unknown or invalid shadow setting '{}' in {}", key, path.display()),
+            }
+        }
+    }
+}
+
+/// A TOML number as f32. TOML keeps integers apart from floats, but `fov = 60` should
+/// work.
+fn number(value: &toml::Value) -> Option<f32> {
+    value.as_float().or_else(|| value.as_integer().map(|i| i as f64)).map(|f| f as f32)
+}
+
+/// A setting's name, where it goes and which values it accepts.
+type Field<'a> = (&'static str, &'a mut f32, fn(f32) -> bool);
+
+impl CameraSettings {
+    fn apply(&mut self, table: &toml::Table, path: &Path) {
+        let fields: [Field; 9] = [
+            ("znear", &mut self.znear, |v| v > 0.0),
+            ("zfar", &mut self.zfar, |v| v > 0.0),
+            ("fov", &mut self.fovy, |v| (1.0..179.0).contains(&v)),
+            ("move_speed", &mut self.move_speed, |v| v > 0.0),
+            ("sprint_speed", &mut self.sprint_speed, |v| v > 0.0),
+            ("mouse_sensitivity", &mut self.mouse_sensitivity, |v| v > 0.0),
+            ("acceleration", &mut self.acceleration, |v| v >= 0.0),
+            ("deceleration", &mut self.deceleration, |v| v >= 0.0),
+            ("look_smoothing", &mut self.look_smoothing, |v| v >= 0.0),
+        ];
+        for name in table.keys() {
+            if !fields.iter().any(|(known, ..)| known == name) {
+                log::warn!("Ignoring unknown camera setting '{}' in {}", name, path.display());
+            }
+        }
+        for (name, field, valid) in fields {
+            let Some(value) = table.get(name) else {
+                continue;
+            };
+            match number(value) {
+                Some(v) if valid(v) => *field = v,
+                _ => log::warn!("Ignoring invalid camera {} '{}' in {}", name, value, path.display()),
+            }
+        }
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use anyhow::Result;
+
+use crate::renderer::Renderer;
+
+/// Lines of output the console keeps.
+const MAX_LINES: usize = 200;
+const MAX_HISTORY: usize = 100;
+/// Height of the output above the input line, as a fraction of the window.
+const HEIGHT_FRACTION: f32 = 0.35;
+const INPUT_ID: &str = "console input";
+
+/// Runs a command with the words after its name, returning what to print.
+pub type CommandFn = dyn Fn(&mut Renderer, &[&str]) -> Result<String>;
+
+struct Command {
+    help: String,
+    /// Words offered when completing the first argument.
+    arguments: Vec<String>,
+    run: Rc<CommandFn>,
+}
+
+/// Console commands by name. The renderer registers its own; other code adds more
+/// through [`Renderer::commands_mut`].
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: BTreeMap<String, Command>,
+}
+
+impl CommandRegistry {
+    /// Adds `name`, replacing a command of that name. `help` is its line in `help`.
+    pub fn register(
+        &mut self,
+        name: &str,
+        help: &str,
+        run: impl Fn(&mut Renderer, &[&str]) -> Result<String> + 'static,
+    ) {
+        let command = Command {
+            help: help.to_string(),
+            arguments: Vec::new(),
+            run: Rc::new(run),
+        };
+        self.commands.insert(name.to_string(), command);
+    }
+
+    /// Sets the words Tab completes the first argument of `name` to.
+    pub fn set_arguments(&mut self, name: &str, arguments: &[&str]) {
+        if let Some(command) = self.commands.get_mut(name) {
+            command.arguments = arguments.iter().map(|a| a.to_string()).collect();
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Rc<CommandFn>> {
+        self.commands.get(name).map(|c| c.run.clone())
+    }
+
+    /// Every command with its help, one per line.
+    pub fn help(&self) -> String {
+        let width = self.commands.keys().map(String::len).max().unwrap_or(0);
+        let lines: Vec<_> = self.commands.iter().map(|(name, c)| format!("{:width$}  {}", name, c.help)).collect();
+        lines.join("\n")
+    }
+
+    /// What the last word of `line` could complete to: a command name for the first
+    /// word, one of the command's arguments for the second.
+    fn candidates(&self, line: &str) -> Vec<&str> {
+        let words: Vec<&str> = line.split(' ').collect();
+        let word = words[words.len() - 1];
+        let options: Vec<&str> = match words.len() {
+            1 => self.commands.keys().map(String::as_str).collect(),
+            2 => self.commands.get(words[0]).map_or(Vec::new(), |c| c.arguments.iter().map(String::as_str).collect()),
+            _ => Vec::new(),
+        };
+        options.into_iter().filter(|o| o.starts_with(word)).collect()
+    }
+}
+
+/// A command line's name and the words after it; None for a blank line.
+pub fn split_command(line: &str) -> Option<(&str, Vec<&str>)> {
+    let mut words = line.split_whitespace();
+    let name = words.next()?;
+    Some((name, words.collect()))
+}
+
+/// Parses exactly `N` numbers out of a command's arguments.
+pub fn parse_numbers<const N: usize>(args: &[&str]) -> Result<[f32; N]> {
+    if args.len() != N {
+        anyhow::bail!("Expected {} numbers, got {} arguments", N, args.len());
+    }
+    let mut numbers = [0.0; N];
+    for (number, arg) in numbers.iter_mut().zip(args) {
+        *number = arg.parse().map_err(|_| anyhow::anyhow!("'{}' is not a number", arg))?;
+    }
+    Ok(numbers)
+}
+
+/// A drop-down console along the top of the window: command output above a line to
+/// type into, with history on Up and Down and completion on Tab.
+#[derive(Default)]
+pub struct Console {
+    pub open: bool,
+    input: String,
+    lines: Vec<String>,
+    history: Vec<String>,
+    /// Which history entry the input shows while browsing; None for a new line.
+    browsing: Option<usize>,
+}
+
+impl Console {
+    pub fn print(&mut self, text: &str) {
+        self.lines.extend(text.lines().map(str::to_string));
+        let excess = self.lines.len().saturating_sub(MAX_LINES);
+        self.lines.drain(..excess);
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    /// Draws the console and handles its keys; returns a line entered this frame.
+    pub fn show(&mut self, ctx: &egui::Context, commands: &CommandRegistry) -> Option<String> {
+        let mut entered = None;
+        let id = egui::Id::new(INPUT_ID);
+        egui::TopBottomPanel::top("console").show(ctx, |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(ctx.screen_rect().height() * HEIGHT_FRACTION)
+                .auto_shrink([false, true])
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for line in &self.lines {
+                        ui.monospace(line);
+                    }
+                });
+            let edit = egui::TextEdit::singleline(&mut self.input)
+                .id(id)
+                .font(egui::TextStyle::Monospace)
+                .desired_width(f32::INFINITY)
+                .lock_focus(true);
+            let response = ui.add(edit);
+            let pressed = |key| ui.input(|i| i.key_pressed(key));
+            if pressed(egui::Key::Escape) {
+                self.open = false;
+            } else if response.lost_focus() && pressed(egui::Key::Enter) {
+                entered = self.enter();
+            } else if response.has_focus() {
+                let edited = if pressed(egui::Key::Tab) {
+                    self.complete(commands);
+                    true
+                } else if pressed(egui::Key::ArrowUp) || pressed(egui::Key::ArrowDown) {
+                    self.browse(pressed(egui::Key::ArrowUp));
+                    true
+                } else {
+                    false
+                };
+                if edited {
+                    move_cursor_to_end(ctx, id, self.input.chars().count());
+                }
+            }
+            response.request_focus();
+        });
+        entered
+    }
+
+    fn enter(&mut self) -> Option<String> {
+        self.browsing = None;
+        let line = std::mem::take(&mut self.input);
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+        if self.history.last().map(String::as_str) != Some(line) {
+            self.history.push(line.to_string());
+            let excess = self.history.len().saturating_sub(MAX_HISTORY);
+            self.history.drain(..excess);
+        }
+        Some(line.to_string())
+    }
+
+    /// Shows the previous or next history entry, ending on an empty line.
+    fn browse(&mut self, older: bool) {
+        if self.history.is_empty() {
+            return;
+        }
+        self.browsing = match (self.browsing, older) {
+            (None, true) => Some(self.history.len() - 1),
+            (None, false) => None,
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) => (i + 1 < self.history.len()).then_some(i + 1),
+        };
+        self.input = self.browsing.map_or_else(String::new, |i| self.history[i].clone());
+    }
+
+    /// Completes the last word as far as the candidates agree, and lists them when they
+    /// part ways.
+    fn complete(&mut self, commands: &CommandRegistry) {
+        let candidates = commands.candidates(&self.input);
+        let Some(first) = candidates.first() else {
+            return;
+        };
+        let common = candidates.iter().fold(first.len(), |len, c| {
+            first.bytes().zip(c.bytes()).take(len).take_while(|(a, b)| a == b).count()
+        });
+        let start = self.input.rfind(' ').map_or(0, |i| i + 1);
+        let mut input = format!("{}{}", &self.input[..start], &first[..common]);
+        if candidates.len() == 1 {
+            input.push(' ');
+        } else {
+            let list = candidates.join("  ");
+            self.print(&list);
+        }
+        self.input = input;
+    }
+}
+
+fn move_cursor_to_end(ctx: &egui::Context, id: egui::Id, len: usize) {
+    if let Some(mut state) = egui::TextEdit::load_state(ctx, id) {
+        let end = egui::text::CCursor::new(len);
+        state.cursor.set_char_range(Some(egui::text::CCursorRange::one(end)));
+        state.store(ctx, id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry(names: &[&str]) -> CommandRegistry {
+        let mut commands = CommandRegistry::default();
+        for name in names {
+            commands.register(name, &format!("help for {}", name), |_, _| Ok(String::new()));
+        }
+        commands
+    }
+
+    #[test]
+    fn splits_a_line_into_name_and_arguments() {
+        assert_eq!(split_command("  sun 45   30 "), Some(("sun", vec!["45", "30"])));
+        assert_eq!(split_command("help"), Some(("help", vec![])));
+        assert_eq!(split_command("   "), None);
+    }
+
+    #[test]
+    fn parses_exactly_n_numbers() {
+        assert_eq!(parse_numbers::<2>(&["45", "-30.5"]).unwrap(), [45.0, -30.5]);
+        assert!(parse_numbers::<2>(&["45"]).is_err());
+        assert!(parse_numbers::<2>(&["45", "30", "1"]).is_err());
+        let error = parse_numbers::<1>(&["up"]).unwrap_err();
+        assert_eq!(error.to_string(), "'up' is not a number");
+    }
+
+    #[test]
+    fn help_lists_commands_in_order() {
+        let commands = registry(&["sun", "exposure"]);
+        assert_eq!(commands.help(), "exposure  help for exposure\nsun       help for sun");
+    }
+
+    #[test]
+    fn completes_names_then_arguments() {
+        let mut commands = registry(&["screenshot", "set", "sun"]);
+        commands.set_arguments("set", &["taa", "fxaa", "tonemap"]);
+        assert_eq!(commands.candidates("s"), ["screenshot", "set", "sun"]);
+        assert_eq!(commands.candidates("su"), ["sun"]);
+        assert_eq!(commands.candidates("set t"), ["taa", "tonemap"]);
+        assert!(commands.candidates("sun 4").is_empty());
+        assert!(commands.candidates("set taa o").is_empty());
+
+        let mut console = Console {
+            input: "se".to_string(),
+            ..Default::default()
+        };
+        console.complete(&commands);
+        assert_eq!(console.input, "set ");
+        console.input = "set t".to_string();
+        console.complete(&commands);
+        assert_eq!(console.input, "set t");
+        assert_eq!(console.lines, ["taa  tonemap"]);
+        console.input = "set ta".to_string();
+        console.complete(&commands);
+        assert_eq!(console.input, "set taa ");
+    }
+
+    #[test]
+    fn history_skips_blanks_and_repeats() {
+        let mut console = Console::default();
+        for line in ["help", "  ", "sun 1 2", "sun 1 2", "clear"] {
+            console.input = line.to_string();
+            console.enter();
+        }
+        assert_eq!(console.history, ["help", "sun 1 2", "clear"]);
+
+        console.browse(true);
+        assert_eq!(console.input, "clear");
+        console.browse(true);
+        console.browse(true);
+        console.browse(true);
+        assert_eq!(console.input, "help");
+        console.browse(false);
+        assert_eq!(console.input, "sun 1 2");
+        console.browse(false);
+        console.browse(false);
+        assert_eq!(console.input, "");
+    }
+
+    #[test]
+    fn output_keeps_the_last_lines() {
+        let mut console = Console::default();
+        for i in 0..MAX_LINES + 5 {
+            console.print(&format!("line {}", i));
+        }
+        assert_eq!(console.lines.len(), MAX_LINES);
+        assert_eq!(console.lines[0], "line 5");
+    }
+}
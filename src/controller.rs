@@ -1,5 +1,9 @@
-use winit::event::{ElementState, MouseButton, WindowEvent};
-use winit::keyboard::{KeyCode, PhysicalKey};
+use cgmath::{InnerSpace, Point3, Vector3, Zero};
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::keyboard::PhysicalKey;
+
+use crate::bindings::{Action, Bindings};
+use crate::camera::CameraSettings;
 
 pub struct InputState {
     pub forward: bool,
@@ -9,10 +13,20 @@ pub struct InputState {
     pub up: bool,
     pub down: bool,
     pub sprint: bool,
+    pub zoom: bool,
     pub mouse_captured: bool,
     pub mouse_delta: (f32, f32),
+    /// Last cursor position in the window, in physical pixels.
+    pub cursor: Option<(f32, f32)>,
+    /// Wheel notches turned since the last `take_scroll`, positive away from the user.
+    pub scroll: f32,
 }
 
+/// Pixels of touchpad scrolling counted as one wheel notch.
+const PIXELS_PER_NOTCH: f32 = 50.0;
+/// Fly speed factor per wheel notch.
+const SPEED_STEP: f32 = 1.2;
+
 impl InputState {
     pub fn new() -> Self {
         Self {
@@ -23,23 +37,30 @@ impl InputState {
             up: false,
             down: false,
             sprint: false,
+            zoom: false,
             mouse_captured: false,
             mouse_delta: (0.0, 0.0),
+            cursor: None,
+            scroll: 0.0,
         }
     }
 
-    pub fn on_window_event(&mut self, event: &WindowEvent) -> bool {
+    pub fn on_window_event(&mut self, event: &WindowEvent, bindings: &Bindings) -> bool {
         match event {
             WindowEvent::KeyboardInput { event, .. } => {
                 let pressed = event.state == ElementState::Pressed;
-                match event.physical_key {
-                    PhysicalKey::Code(KeyCode::KeyW) => self.forward = pressed,
-                    PhysicalKey::Code(KeyCode::KeyS) => self.back = pressed,
-                    PhysicalKey::Code(KeyCode::KeyA) => self.left = pressed,
-                    PhysicalKey::Code(KeyCode::KeyD) => self.right = pressed,
-                    PhysicalKey::Code(KeyCode::Space) => self.up = pressed,
-                    PhysicalKey::Code(KeyCode::ControlLeft) | PhysicalKey::Code(KeyCode::ControlRight) => self.down = pressed,
-                    PhysicalKey::Code(KeyCode::ShiftLeft) | PhysicalKey::Code(KeyCode::ShiftRight) => self.sprint = pressed,
+                let PhysicalKey::Code(code) = event.physical_key else {
+                    return true;
+                };
+                match bindings.action(code) {
+                    Some(Action::MoveForward) => self.forward = pressed,
+                    Some(Action::MoveBack) => self.back = pressed,
+                    Some(Action::MoveLeft) => self.left = pressed,
+                    Some(Action::MoveRight) => self.right = pressed,
+                    Some(Action::MoveUp) => self.up = pressed,
+                    Some(Action::MoveDown) => self.down = pressed,
+                    Some(Action::Sprint) => self.sprint = pressed,
+                    Some(Action::Zoom) => self.zoom = pressed,
                     _ => {}
                 }
                 true
@@ -51,10 +72,33 @@ impl InputState {
                 }
                 false
             }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = Some((position.x as f32, position.y as f32));
+                false
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.scroll += match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(p) => p.y as f32 / PIXELS_PER_NOTCH,
+                };
+                true
+            }
             _ => false,
         }
     }
 
+    /// Lets go of every held action, for when key releases stop reaching `on_window_event`.
+    pub fn release_keys(&mut self) {
+        self.forward = false;
+        self.back = false;
+        self.left = false;
+        self.right = false;
+        self.up = false;
+        self.down = false;
+        self.sprint = false;
+        self.zoom = false;
+    }
+
     pub fn on_mouse_motion(&mut self, delta: (f64, f64)) {
         if self.mouse_captured {
             self.mouse_delta.0 += delta.0 as f32;
@@ -62,9 +106,174 @@ impl InputState {
         }
     }
 
+    pub fn take_scroll(&mut self) -> f32 {
+        std::mem::take(&mut self.scroll)
+    }
+
     pub fn take_mouse_delta(&mut self) -> (f32, f32) {
         let d = self.mouse_delta;
         self.mouse_delta = (0.0, 0.0);
         d
     }
 }
+
+/// Reaches walking speed in about a sixth of a second.
+pub const DEFAULT_ACCELERATION: f32 = 40.0;
+/// Stops from walking speed in about an eighth of a second.
+pub const DEFAULT_DECELERATION: f32 = 50.0;
+
+/// Eases the fly camera in and out of motion instead of starting and stopping it
+/// instantly, and optionally damps mouse look.
+pub struct CameraMotion {
+    /// Metres per second squared while speeding up; 0 reaches the target speed at once.
+    pub acceleration: f32,
+    /// Metres per second squared while slowing down or turning around; 0 stops at once.
+    pub deceleration: f32,
+    /// Time constant in seconds of the mouse look damping; 0 applies mouse motion raw.
+    pub look_smoothing: f32,
+    /// Factor on the fly speed, set with the mouse wheel. The acceleration scales with it
+    /// so speeding up takes as long at any scale.
+    pub speed_scale: f32,
+    /// Fly speeds in metres per second before `speed_scale`.
+    pub move_speed: f32,
+    pub sprint_speed: f32,
+    /// Radians of turn per pixel of mouse motion.
+    pub mouse_sensitivity: f32,
+    velocity: Vector3<f32>,
+    /// Mouse motion not yet applied to the camera.
+    look_pending: (f32, f32),
+}
+
+impl CameraMotion {
+    pub fn new(settings: &CameraSettings) -> Self {
+        Self {
+            acceleration: settings.acceleration,
+            deceleration: settings.deceleration,
+            look_smoothing: settings.look_smoothing,
+            speed_scale: 1.0,
+            move_speed: settings.move_speed,
+            sprint_speed: settings.sprint_speed,
+            mouse_sensitivity: settings.mouse_sensitivity,
+            velocity: Vector3::zero(),
+            look_pending: (0.0, 0.0),
+        }
+    }
+
+    /// Moves the velocity `dt` seconds towards `target` and returns it.
+    pub fn velocity(&mut self, target: Vector3<f32>, dt: f32) -> Vector3<f32> {
+        let slowing = target.magnitude2() < self.velocity.magnitude2() || target.dot(self.velocity) < 0.0;
+        let rate = if slowing { self.deceleration } else { self.acceleration } * self.speed_scale;
+        let change = target - self.velocity;
+        let max_change = rate * dt;
+        if rate <= 0.0 || change.magnitude() <= max_change {
+            self.velocity = target;
+        } else {
+            self.velocity += change.normalize_to(max_change);
+        }
+        self.velocity
+    }
+
+    /// Scales the fly speed by `notches` wheel steps, within 1/1000 and 1000 times the
+    /// default.
+    pub fn scale_speed(&mut self, notches: f32) {
+        self.speed_scale = (self.speed_scale * SPEED_STEP.powf(notches)).clamp(1e-3, 1e3);
+    }
+
+    /// Adds raw mouse motion and returns the share of the pending motion to apply after
+    /// `dt` seconds. The rest carries over, so the camera still turns as far as the mouse
+    /// moved, only later.
+    pub fn look(&mut self, delta: (f32, f32), dt: f32) -> (f32, f32) {
+        self.look_pending.0 += delta.0;
+        self.look_pending.1 += delta.1;
+        let share = if self.look_smoothing > 0.0 { 1.0 - (-dt / self.look_smoothing).exp() } else { 1.0 };
+        let applied = (self.look_pending.0 * share, self.look_pending.1 * share);
+        self.look_pending.0 -= applied.0;
+        self.look_pending.1 -= applied.1;
+        applied
+    }
+
+    /// Drops any motion in progress, for when the camera is placed directly.
+    pub fn stop(&mut self) {
+        self.velocity = Vector3::zero();
+        self.look_pending = (0.0, 0.0);
+    }
+}
+
+/// Narrowest and widest field of view the FOV keys allow, in degrees.
+const FOV_RANGE: (f32, f32) = (10.0, 120.0);
+/// Degrees per press of the FOV keys.
+const FOV_STEP: f32 = 5.0;
+/// Time constant in seconds of the sprint and zoom transitions.
+const FOV_SMOOTHING: f32 = 0.12;
+
+/// The camera's vertical field of view: a base angle set by the user, widened while
+/// sprinting and narrowed while zooming, easing between them.
+pub struct FovControl {
+    pub base: f32,
+    /// Degrees added while sprinting; 0 turns the kick off.
+    pub sprint_kick: f32,
+    /// Field of view while the zoom key is held.
+    pub zoom: f32,
+    current: f32,
+}
+
+impl FovControl {
+    pub fn new(base: f32) -> Self {
+        Self {
+            base,
+            sprint_kick: 0.0,
+            zoom: 15.0,
+            current: base,
+        }
+    }
+
+    /// Changes the base by `steps` key presses and returns the new base.
+    pub fn step(&mut self, steps: f32) -> f32 {
+        self.base = (self.base + steps * FOV_STEP).clamp(FOV_RANGE.0, FOV_RANGE.1);
+        self.base
+    }
+
+    /// Sets the base and jumps straight to it, for when the camera is placed directly.
+    pub fn reset(&mut self, base: f32) {
+        self.base = base;
+        self.current = base;
+    }
+
+    /// Eases `dt` seconds towards the field of view for the current input and returns it.
+    pub fn update(&mut self, zooming: bool, sprinting: bool, dt: f32) -> f32 {
+        let target = if zooming {
+            self.zoom
+        } else if sprinting {
+            self.base + self.sprint_kick
+        } else {
+            self.base
+        };
+        self.current += (target - self.current) * (1.0 - (-dt / FOV_SMOOTHING).exp());
+        self.current
+    }
+}
+
+/// Seconds a focus flight takes.
+const FLIGHT_TIME: f32 = 0.4;
+
+/// Moves the camera to a new position over a short, eased flight instead of cutting to
+/// it.
+pub struct CameraFlight {
+    from: Point3<f32>,
+    to: Point3<f32>,
+    elapsed: f32,
+}
+
+impl CameraFlight {
+    pub fn new(from: Point3<f32>, to: Point3<f32>) -> Self {
+        Self { from, to, elapsed: 0.0 }
+    }
+
+    /// Advances `dt` seconds; the position along the way, and whether the flight is over.
+    pub fn advance(&mut self, dt: f32) -> (Point3<f32>, bool) {
+        self.elapsed += dt;
+        let t = (self.elapsed / FLIGHT_TIME).min(1.0);
+        let eased = t * t * (3.0 - 2.0 * t);
+        (self.from + (self.to - self.from) * eased, t >= 1.0)
+    }
+}
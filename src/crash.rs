@@ -0,0 +1,143 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const REPORT_DIR: &str = "crash_reports";
+const LOG_TAIL_LINES: usize = 500;
+
+static LOG_TAIL: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+static CONTEXT: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+static REPORTED: AtomicBool = AtomicBool::new(false);
+static SHOW_DIALOG: AtomicBool = AtomicBool::new(true);
+
+/// env_logger that also keeps the last lines in memory for crash reports.
+struct TailLogger {
+    inner: env_logger::Logger,
+}
+
+impl log::Log for TailLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.inner.matches(record) {
+            return;
+        }
+        self.inner.log(record);
+        if let Ok(mut tail) = LOG_TAIL.lock() {
+            if tail.len() == LOG_TAIL_LINES {
+                tail.pop_front();
+            }
+            tail.push_back(format!("[{} {}] {}", record.level(), record.target(), record.args()));
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Installs logging and the panic hook. Reports stay on this machine; nothing is sent.
+pub fn init() {
+    let inner = env_logger::Builder::from_default_env().build();
+    log::set_max_level(inner.filter());
+    if log::set_boxed_logger(Box::new(TailLogger { inner })).is_err() {
+        eprintln!("crash reporter: a logger was already installed");
+    }
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        let backtrace = std::backtrace::Backtrace::force_capture();
+        report(&format!("panic: {}\n\n{}", info, backtrace));
+    }));
+}
+
+/// Adds or replaces a line of context (adapter, scene, settings) included in reports.
+pub fn set_context(key: &str, value: impl Into<String>) {
+    if let Ok(mut context) = CONTEXT.lock() {
+        context.insert(key.to_string(), value.into());
+    }
+}
+
+pub fn set_dialog_enabled(enabled: bool) {
+    SHOW_DIALOG.store(enabled, Ordering::Relaxed);
+}
+
+/// Writes `crash_reports/<time>/` with `report.txt` and `log.txt` and points the user at
+/// it. Only the first call per run writes anything, so a device loss that turns into a
+/// panic produces one report.
+pub fn report(reason: &str) -> Option<PathBuf> {
+    if REPORTED.swap(true, Ordering::SeqCst) {
+        return None;
+    }
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let dir = Path::new(REPORT_DIR).join(format!("crash-{}", stamp));
+    match write_bundle(&dir, reason) {
+        Ok(()) => {
+            eprintln!("Crash report written to {}", dir.display());
+            if SHOW_DIALOG.load(Ordering::Relaxed) {
+                show_dialog(&dir);
+            }
+            Some(dir)
+        }
+        Err(e) => {
+            eprintln!("Failed to write crash report to {}: {}", dir.display(), e);
+            None
+        }
+    }
+}
+
+fn write_bundle(dir: &Path, reason: &str) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+
+    let mut text = String::new();
+    let _ = writeln!(text, "Dusk Engine {} crash report", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(text, "os: {} {}", std::env::consts::OS, std::env::consts::ARCH);
+    // A poisoned lock still holds useful context; the panic may have happened mid-update.
+    let context = CONTEXT.lock().unwrap_or_else(|e| e.into_inner());
+    for (key, value) in context.iter() {
+        let _ = writeln!(text, "{}: {}", key, value);
+    }
+    drop(context);
+    let _ = writeln!(text, "\n{}", reason);
+    std::fs::write(dir.join("report.txt"), text)?;
+
+    let tail = LOG_TAIL.lock().unwrap_or_else(|e| e.into_inner());
+    let log: Vec<&str> = tail.iter().map(String::as_str).collect();
+    std::fs::write(dir.join("log.txt"), log.join("\n"))
+}
+
+fn show_dialog(dir: &Path) {
+    use std::process::Command;
+
+    let message = format!(
+        "Dusk Engine stopped unexpectedly.\n\nA report was saved to:\n{}\n\nPlease attach this folder to your bug report.",
+        std::fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf()).display()
+    );
+    let shown = if cfg!(target_os = "windows") {
+        let script = format!(
+            "Add-Type -AssemblyName PresentationFramework; [System.Windows.MessageBox]::Show('{}', 'Dusk Engine')",
+            message.replace('\'', "''")
+        );
+        Command::new("powershell").args(["-NoProfile", "-Command", &script]).status()
+    } else if cfg!(target_os = "macos") {
+        let script = format!(
+            "display alert \"Dusk Engine\" message \"{}\"",
+            message.replace('\\', "\\\\").replace('"', "\\\"")
+        );
+        Command::new("osascript").args(["-e", &script]).status()
+    } else {
+        Command::new("zenity")
+            .args(["--error", "--title=Dusk Engine", "--no-markup", &format!("--text={}", message)])
+            .status()
+            .or_else(|_| Command::new("kdialog").args(["--error", &message]).status())
+    };
+    if shown.is_err() {
+        eprintln!("{}", message);
+    }
+}
@@ -0,0 +1,483 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use cgmath::Point3;
+use wgpu::util::DeviceExt;
+
+use crate::aabb::Aabb;
+use crate::camera::{frustum_planes, DepthMode};
+
+pub const DEFAULT_CROWD_SIZE: u32 = 10_000;
+
+const SPACING: f32 = 2.5;
+const INSTANCE_SIZE: u64 = 4 * (16 + 4 + 16 * 5);
+const DRAW_ARGS_SIZE: u64 = 20;
+const STATS_INTERVAL: f32 = 2.0;
+
+const MAP_PENDING: u8 = 0;
+const MAP_DONE: u8 = 1;
+const MAP_FAILED: u8 = 2;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CrowdVertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+    bone: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CrowdParams {
+    planes: [[f32; 4]; 6],
+    time: f32,
+    count: u32,
+    _pad: [f32; 2],
+}
+
+/// Boxy walker: body and head on bone 0, legs on 1/2, arms on 3/4.
+fn figure_mesh() -> (Vec<CrowdVertex>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    let mut push_box = |min: [f32; 3], max: [f32; 3], bone: u32| {
+        let faces: [([f32; 3], [usize; 3]); 6] = [
+            ([1.0, 0.0, 0.0], [0, 1, 2]),
+            ([-1.0, 0.0, 0.0], [0, 1, 2]),
+            ([0.0, 1.0, 0.0], [1, 2, 0]),
+            ([0.0, -1.0, 0.0], [1, 2, 0]),
+            ([0.0, 0.0, 1.0], [2, 0, 1]),
+            ([0.0, 0.0, -1.0], [2, 0, 1]),
+        ];
+        for (normal, [axis, u, v]) in faces {
+            let side = if normal[axis] > 0.0 { max[axis] } else { min[axis] };
+            let base = vertices.len() as u32;
+            for (a, b) in [(0, 0), (1, 0), (1, 1), (0, 1)] {
+                let mut p = [0.0; 3];
+                p[axis] = side;
+                p[u] = if a == 0 { min[u] } else { max[u] };
+                p[v] = if b == 0 { min[v] } else { max[v] };
+                vertices.push(CrowdVertex { position: p, normal, bone });
+            }
+            // Keep counter-clockwise winding seen from outside.
+            if normal[axis] > 0.0 {
+                indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+            } else {
+                indices.extend_from_slice(&[base, base + 2, base + 1, base, base + 3, base + 2]);
+            }
+        }
+    };
+    push_box([-0.22, 0.9, -0.12], [0.22, 1.5, 0.12], 0);
+    push_box([-0.12, 1.55, -0.12], [0.12, 1.8, 0.12], 0);
+    push_box([-0.2, 0.0, -0.08], [-0.04, 0.9, 0.08], 1);
+    push_box([0.04, 0.0, -0.08], [0.2, 0.9, 0.08], 2);
+    push_box([-0.34, 0.85, -0.06], [-0.24, 1.48, 0.06], 3);
+    push_box([0.24, 0.85, -0.06], [0.34, 1.48, 0.06], 4);
+    (vertices, indices)
+}
+
+#[derive(Default)]
+struct FrameTimes {
+    count: u32,
+    total: f32,
+    min: f32,
+    max: f32,
+}
+
+impl FrameTimes {
+    fn push(&mut self, dt: f32) {
+        if self.count == 0 {
+            self.min = dt;
+            self.max = dt;
+        }
+        self.count += 1;
+        self.total += dt;
+        self.min = self.min.min(dt);
+        self.max = self.max.max(dt);
+    }
+
+    fn average(&self) -> f32 {
+        if self.count > 0 { self.total / self.count as f32 } else { 0.0 }
+    }
+}
+
+/// Built-in stress scene: thousands of walking figures animated, skinned and frustum
+/// culled on the GPU, drawn with a single indirect instanced draw.
+pub struct CrowdScene {
+    count: u32,
+    index_count: u32,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    params_buffer: wgpu::Buffer,
+    draw_args: wgpu::Buffer,
+    update_pipeline: wgpu::ComputePipeline,
+    update_bind_group: wgpu::BindGroup,
+    render_pipeline: wgpu::RenderPipeline,
+    render_bind_group: wgpu::BindGroup,
+    readback: wgpu::Buffer,
+    readback_busy: bool,
+    map_status: Arc<AtomicU8>,
+    visible: u32,
+    interval: FrameTimes,
+    run: FrameTimes,
+    last_frame: Instant,
+    last_report: Instant,
+    benchmark_frames: Option<u32>,
+}
+
+impl CrowdScene {
+    pub fn new(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        color_format: wgpu::TextureFormat,
+        depth_mode: DepthMode,
+        count: u32,
+        benchmark_frames: Option<u32>,
+    ) -> Self {
+        let count = count.max(1);
+        let (vertices, indices) = figure_mesh();
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Crowd Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Crowd Index Buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let side = (count as f32).sqrt().ceil() as u32;
+        let seeds: Vec<[f32; 4]> = (0..count)
+            .map(|i| {
+                let (gx, gz) = ((i % side) as f32, (i / side) as f32);
+                let h = (i.wrapping_mul(2654435761) >> 8) as f32 / (1u32 << 24) as f32;
+                [gx * SPACING, gz * SPACING, 0.8 + 0.8 * h, h * std::f32::consts::TAU]
+            })
+            .collect();
+        let seed_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Crowd Seed Buffer"),
+            contents: bytemuck::cast_slice(&seeds),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Crowd Instance Buffer"),
+            size: INSTANCE_SIZE * count as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let visible_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Crowd Visible Buffer"),
+            size: 4 * count as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let draw_args = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Crowd Draw Args"),
+            size: DRAW_ARGS_SIZE,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Crowd Params Buffer"),
+            size: std::mem::size_of::<CrowdParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Crowd Stats Readback"),
+            size: DRAW_ARGS_SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let storage = |binding: u32, read_only: bool, visibility: wgpu::ShaderStages| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let update_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("crowd_update_bind_group_layout"),
+            entries: &[
+                storage(0, true, wgpu::ShaderStages::COMPUTE),
+                storage(1, false, wgpu::ShaderStages::COMPUTE),
+                storage(2, false, wgpu::ShaderStages::COMPUTE),
+                storage(3, false, wgpu::ShaderStages::COMPUTE),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let update_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("crowd_update_bind_group"),
+            layout: &update_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: seed_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: instance_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: visible_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: draw_args.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        let update_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Crowd Update Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("crowd_update.wgsl").into()),
+        });
+        let update_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Crowd Update Pipeline Layout"),
+            bind_group_layouts: &[&update_layout],
+            push_constant_ranges: &[],
+        });
+        let update_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Crowd Update Pipeline"),
+            layout: Some(&update_pipeline_layout),
+            module: &update_shader,
+            entry_point: "cs_update",
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let render_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("crowd_render_bind_group_layout"),
+            entries: &[
+                storage(0, true, wgpu::ShaderStages::VERTEX),
+                storage(1, true, wgpu::ShaderStages::VERTEX),
+            ],
+        });
+        let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("crowd_render_bind_group"),
+            layout: &render_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: instance_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: visible_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Crowd Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("crowd.wgsl").into()),
+        });
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Crowd Render Pipeline Layout"),
+            bind_group_layouts: &[camera_layout, &render_layout],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Crowd Render Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            cache: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_crowd",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<CrowdVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Uint32],
+                }],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_crowd",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: depth_mode.compare(wgpu::CompareFunction::Less),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        log::info!(
+            "Crowd: {} instances, {} triangles each ({} total)",
+            count,
+            indices.len() / 3,
+            count as usize * indices.len() / 3
+        );
+
+        Self {
+            count,
+            index_count: indices.len() as u32,
+            vertex_buffer,
+            index_buffer,
+            params_buffer,
+            draw_args,
+            update_pipeline,
+            update_bind_group,
+            render_pipeline,
+            render_bind_group,
+            readback,
+            readback_busy: false,
+            map_status: Arc::new(AtomicU8::new(MAP_PENDING)),
+            visible: 0,
+            interval: FrameTimes::default(),
+            run: FrameTimes::default(),
+            last_frame: Instant::now(),
+            last_report: Instant::now(),
+            benchmark_frames,
+        }
+    }
+
+    /// Area the crowd of `count` walkers covers, for framing the camera and shadows.
+    pub fn bounds(count: u32) -> Aabb {
+        let side = (count.max(1) as f32).sqrt().ceil();
+        let mut bounds = Aabb::empty();
+        bounds.grow(Point3::new(-1.0, 0.0, -1.0).into());
+        bounds.grow(Point3::new(side * SPACING + 1.0, 2.0, side * SPACING + 1.0).into());
+        bounds
+    }
+
+    /// Records the animation/culling dispatch; must run before the pass that draws the crowd.
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view_proj: [[f32; 4]; 4],
+        time: f32,
+    ) {
+        let params = CrowdParams {
+            planes: frustum_planes(view_proj),
+            time,
+            count: self.count,
+            _pad: [0.0; 2],
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+        queue.write_buffer(&self.draw_args, 0, bytemuck::cast_slice(&[self.index_count, 0, 0, 0, 0]));
+
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Crowd Update Pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.update_pipeline);
+            pass.set_bind_group(0, &self.update_bind_group, &[]);
+            pass.dispatch_workgroups(self.count.div_ceil(64), 1, 1);
+        }
+        if !self.readback_busy {
+            encoder.copy_buffer_to_buffer(&self.draw_args, 0, &self.readback, 0, DRAW_ARGS_SIZE);
+        }
+    }
+
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+        pass.set_pipeline(&self.render_pipeline);
+        pass.set_bind_group(1, &self.render_bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        pass.draw_indexed_indirect(&self.draw_args, 0);
+    }
+
+    /// Call after submitting the frame: collects the visible count and frame timings.
+    pub fn end_frame(&mut self, device: &wgpu::Device) {
+        if !self.readback_busy {
+            let status = self.map_status.clone();
+            status.store(MAP_PENDING, Ordering::Release);
+            self.readback.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                status.store(if result.is_ok() { MAP_DONE } else { MAP_FAILED }, Ordering::Release);
+            });
+            self.readback_busy = true;
+        } else {
+            device.poll(wgpu::Maintain::Poll);
+            match self.map_status.load(Ordering::Acquire) {
+                MAP_DONE => {
+                    {
+                        let data = self.readback.slice(..).get_mapped_range();
+                        let args: &[u32] = bytemuck::cast_slice(&data);
+                        self.visible = args[1];
+                    }
+                    self.readback.unmap();
+                    self.readback_busy = false;
+                }
+                MAP_FAILED => self.readback_busy = false,
+                _ => {}
+            }
+        }
+
+        let now = Instant::now();
+        let dt = now.duration_since(self.last_frame).as_secs_f32();
+        self.last_frame = now;
+        self.interval.push(dt);
+        self.run.push(dt);
+        if self.last_report.elapsed().as_secs_f32() >= STATS_INTERVAL {
+            let avg = self.interval.average();
+            log::info!(
+                "Crowd: {}/{} visible, {:.2} ms/frame ({:.0} fps), worst {:.2} ms",
+                self.visible,
+                self.count,
+                avg * 1000.0,
+                1.0 / avg.max(1e-6),
+                self.interval.max * 1000.0
+            );
+            self.interval = FrameTimes::default();
+            self.last_report = now;
+        }
+    }
+
+    /// True once the `--crowd-frames` budget is used up; logs the run summary.
+    pub fn benchmark_finished(&self) -> bool {
+        let Some(frames) = self.benchmark_frames else {
+            return false;
+        };
+        if self.run.count < frames {
+            return false;
+        }
+        let avg = self.run.average();
+        log::info!(
+            "Crowd benchmark: {} instances, {} frames, avg {:.2} ms ({:.0} fps), best {:.2} ms, worst {:.2} ms",
+            self.count,
+            self.run.count,
+            avg * 1000.0,
+            1.0 / avg.max(1e-6),
+            self.run.min * 1000.0,
+            self.run.max * 1000.0
+        );
+        true
+    }
+}
@@ -0,0 +1,58 @@
+const BONE_COUNT: u32 = 5u;
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    view_inv: mat4x4<f32>,
+    proj_inv: mat4x4<f32>,
+    position: vec4<f32>,
+    light_view_proj: mat4x4<f32>,
+    light_dir: vec4<f32>,
+    env_intensity: vec4<f32>,
+};
+
+// Written by crowd_update.wgsl.
+struct Instance {
+    model: mat4x4<f32>,
+    color: vec4<f32>,
+    bones: array<mat4x4<f32>, 5>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var<storage, read> instances: array<Instance>;
+
+@group(1) @binding(1)
+var<storage, read> visible: array<u32>;
+
+struct CrowdVertex {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) bone: u32,
+};
+
+struct CrowdOut {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) color: vec3<f32>,
+};
+
+@vertex
+fn vs_crowd(v: CrowdVertex, @builtin(instance_index) slot: u32) -> CrowdOut {
+    let index = visible[slot];
+    let skin = instances[index].model * instances[index].bones[min(v.bone, BONE_COUNT - 1u)];
+    var out: CrowdOut;
+    out.clip_position = camera.view_proj * skin * vec4<f32>(v.position, 1.0);
+    out.normal = (skin * vec4<f32>(v.normal, 0.0)).xyz;
+    out.color = instances[index].color.rgb;
+    return out;
+}
+
+@fragment
+fn fs_crowd(in: CrowdOut) -> @location(0) vec4<f32> {
+    let n = normalize(in.normal);
+    let ndl = max(dot(n, normalize(-camera.light_dir.xyz)), 0.0);
+    let color = in.color * (ndl * 6.0 / 3.14159265 + camera.env_intensity.rgb * 0.3);
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,128 @@
+const CIRCLE_RADIUS: f32 = 0.8;
+const HIP_Y: f32 = 0.9;
+const SHOULDER_Y: f32 = 1.45;
+const BOUNDS_RADIUS: f32 = 1.1;
+
+// xy = home position on the ground, z = walking speed, w = phase.
+struct Seed {
+    home: vec4<f32>,
+};
+
+struct Instance {
+    model: mat4x4<f32>,
+    color: vec4<f32>,
+    bones: array<mat4x4<f32>, 5>,
+};
+
+struct DrawArgs {
+    index_count: u32,
+    instance_count: atomic<u32>,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+};
+
+struct Params {
+    // Frustum planes, normals pointing inwards.
+    planes: array<vec4<f32>, 6>,
+    time: f32,
+    count: u32,
+    _pad: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var<storage, read> seeds: array<Seed>;
+
+@group(0) @binding(1)
+var<storage, read_write> instances_out: array<Instance>;
+
+@group(0) @binding(2)
+var<storage, read_write> visible_out: array<u32>;
+
+@group(0) @binding(3)
+var<storage, read_write> draw_args: DrawArgs;
+
+@group(0) @binding(4)
+var<uniform> params: Params;
+
+fn translation(t: vec3<f32>) -> mat4x4<f32> {
+    return mat4x4<f32>(
+        vec4<f32>(1.0, 0.0, 0.0, 0.0),
+        vec4<f32>(0.0, 1.0, 0.0, 0.0),
+        vec4<f32>(0.0, 0.0, 1.0, 0.0),
+        vec4<f32>(t, 1.0),
+    );
+}
+
+fn rotation_y(a: f32) -> mat4x4<f32> {
+    let c = cos(a);
+    let s = sin(a);
+    return mat4x4<f32>(
+        vec4<f32>(c, 0.0, -s, 0.0),
+        vec4<f32>(0.0, 1.0, 0.0, 0.0),
+        vec4<f32>(s, 0.0, c, 0.0),
+        vec4<f32>(0.0, 0.0, 0.0, 1.0),
+    );
+}
+
+// Rotation about the X axis through a pivot at height `y`.
+fn swing_about(a: f32, y: f32) -> mat4x4<f32> {
+    let c = cos(a);
+    let s = sin(a);
+    let r = mat4x4<f32>(
+        vec4<f32>(1.0, 0.0, 0.0, 0.0),
+        vec4<f32>(0.0, c, s, 0.0),
+        vec4<f32>(0.0, -s, c, 0.0),
+        vec4<f32>(0.0, 0.0, 0.0, 1.0),
+    );
+    return translation(vec3<f32>(0.0, y, 0.0)) * r * translation(vec3<f32>(0.0, -y, 0.0));
+}
+
+fn hash(n: u32) -> u32 {
+    var x = n * 747796405u + 2891336453u;
+    x = ((x >> ((x >> 28u) + 4u)) ^ x) * 277803737u;
+    return (x >> 22u) ^ x;
+}
+
+@compute @workgroup_size(64)
+fn cs_update(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if i >= params.count {
+        return;
+    }
+    let seed = seeds[i].home;
+    let t = params.time;
+
+    // Walk a small circle around the home position, facing along the path.
+    let angle = seed.w + t * seed.z / CIRCLE_RADIUS;
+    let gait = t * seed.z * 4.0 + seed.w * 7.0;
+    let swing = sin(gait) * 0.6;
+    let bob = abs(cos(gait)) * 0.05;
+    let position = vec3<f32>(seed.x + cos(angle) * CIRCLE_RADIUS, bob, seed.y + sin(angle) * CIRCLE_RADIUS);
+
+    var inst: Instance;
+    inst.model = translation(position) * rotation_y(-angle);
+    let h = hash(i);
+    inst.color = vec4<f32>(
+        0.3 + 0.6 * f32(h & 255u) / 255.0,
+        0.3 + 0.6 * f32((h >> 8u) & 255u) / 255.0,
+        0.3 + 0.6 * f32((h >> 16u) & 255u) / 255.0,
+        1.0,
+    );
+    inst.bones[0] = translation(vec3<f32>(0.0));
+    inst.bones[1] = swing_about(swing, HIP_Y);
+    inst.bones[2] = swing_about(-swing, HIP_Y);
+    inst.bones[3] = swing_about(-swing * 0.7, SHOULDER_Y);
+    inst.bones[4] = swing_about(swing * 0.7, SHOULDER_Y);
+    instances_out[i] = inst;
+
+    let center = position + vec3<f32>(0.0, HIP_Y, 0.0);
+    for (var p = 0u; p < 6u; p = p + 1u) {
+        let plane = params.planes[p];
+        if dot(plane.xyz, center) + plane.w < -BOUNDS_RADIUS {
+            return;
+        }
+    }
+    let slot = atomicAdd(&draw_args.instance_count, 1u);
+    visible_out[slot] = i;
+}
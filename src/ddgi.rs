@@ -0,0 +1,398 @@
+use cgmath::{Matrix4, Quaternion, Rotation};
+
+use crate::bvh::Bvh;
+use crate::lights::DirectionalLight;
+use crate::model::{Material, Texture};
+use crate::probes::ProbeGrid;
+
+const ATLAS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// Matches RAYS_PER_PROBE and the trace workgroup size in ddgi.wgsl.
+const RAYS_PER_PROBE: u64 = 64;
+/// Interior texels per tile side, plus a one texel border.
+const IRRADIANCE_TILE: u32 = 6 + 2;
+const VISIBILITY_TILE: u32 = 14 + 2;
+/// Share of the previous result kept each update; higher is smoother but slower to react.
+const HYSTERESIS: f32 = 0.97;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct DdgiParams {
+    rotation: [[f32; 4]; 4],
+    grid_origin: [f32; 4],
+    grid_spacing: [f32; 4],
+    grid_counts: [u32; 4],
+    sun_direction: [f32; 4],
+    sun_radiance: [f32; 4],
+    env_intensity: [f32; 4],
+    /// x: hysteresis, y: max ray distance, z: 1 once the atlases hold results, w: BVH node count.
+    settings: [f32; 4],
+}
+
+/// Linear diffuse color of a material for ray hits: the base color factor times the
+/// average of its base color texture when that is stored as plain RGBA8.
+pub fn material_albedo(material: &Material, textures: &[Texture]) -> [f32; 3] {
+    let mut albedo = [material.base_color[0], material.base_color[1], material.base_color[2]];
+    let Some(texture) = material.base_color_image.and_then(|i| textures.get(i)) else {
+        return albedo;
+    };
+    let srgb = match texture.format {
+        wgpu::TextureFormat::Rgba8UnormSrgb => true,
+        wgpu::TextureFormat::Rgba8Unorm => false,
+        _ => return albedo,
+    };
+    let decode = |c: u8| {
+        let c = c as f32 / 255.0;
+        if srgb {
+            c.powf(2.2)
+        } else {
+            c
+        }
+    };
+    let mut sum = [0.0f64; 3];
+    let mut count = 0usize;
+    // Every texel would be slow for large textures and a few thousand are plenty.
+    let stride = (texture.data.len() / 4 / 4096).max(1) * 4;
+    for texel in texture.data.chunks_exact(4).step_by(stride / 4) {
+        for (s, c) in sum.iter_mut().zip(texel) {
+            *s += decode(*c) as f64;
+        }
+        count += 1;
+    }
+    if count > 0 {
+        for (a, s) in albedo.iter_mut().zip(sum) {
+            *a *= (s / count as f64) as f32;
+        }
+    }
+    albedo
+}
+
+/// A random rotation for frame `frame`, so successive updates sample different rays.
+fn ray_rotation(frame: u32) -> Matrix4<f32> {
+    let hash = |n: u32| {
+        let mut x = n.wrapping_mul(0x9E37_79B9) ^ frame.wrapping_mul(0x85EB_CA6B);
+        x ^= x >> 16;
+        x = x.wrapping_mul(0x7FEB_352D);
+        x ^= x >> 15;
+        x as f32 / u32::MAX as f32
+    };
+    // Uniform random quaternion (Shoemake).
+    let (u1, u2, u3) = (hash(1), hash(2) * std::f32::consts::TAU, hash(3) * std::f32::consts::TAU);
+    let (a, b) = ((1.0 - u1).sqrt(), u1.sqrt());
+    let q = Quaternion::new(b * u3.cos(), a * u2.sin(), a * u2.cos(), b * u3.sin());
+    Matrix4::from(q.invert())
+}
+
+fn atlas(device: &wgpu::Device, label: &str, grid: &ProbeGrid, tile: u32, usage: wgpu::TextureUsages) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: grid.counts[0] * grid.counts[1] * tile,
+            height: grid.counts[2] * tile,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: ATLAS_FORMAT,
+        usage,
+        view_formats: &[],
+    })
+}
+
+struct DdgiResources {
+    irradiance: wgpu::Texture,
+    visibility: wgpu::Texture,
+    irradiance_view: wgpu::TextureView,
+    visibility_view: wgpu::TextureView,
+    irradiance_next: wgpu::Texture,
+    visibility_next: wgpu::Texture,
+    irradiance_next_view: wgpu::TextureView,
+    visibility_next_view: wgpu::TextureView,
+    params_buffer: wgpu::Buffer,
+    node_buffer: wgpu::Buffer,
+    triangle_buffer: wgpu::Buffer,
+    ray_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+}
+
+impl DdgiResources {
+    fn bind_group(&self, device: &wgpu::Device, layout: &wgpu::BindGroupLayout, env_view: &wgpu::TextureView) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("ddgi_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.node_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.triangle_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.ray_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&self.irradiance_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(&self.visibility_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: wgpu::BindingResource::TextureView(env_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: wgpu::BindingResource::TextureView(&self.irradiance_next_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 9,
+                    resource: wgpu::BindingResource::TextureView(&self.visibility_next_view),
+                },
+            ],
+        })
+    }
+}
+
+/// Dynamic diffuse GI on the light probe grid: each frame every probe traces rays
+/// against a BVH of the static scene, lit by the sun, the sky and the previous update,
+/// and the results are blended into octahedral irradiance and distance atlases.
+pub struct Ddgi {
+    pub enabled: bool,
+    grid: ProbeGrid,
+    node_count: u32,
+    resources: DdgiResources,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    trace_pipeline: wgpu::ComputePipeline,
+    irradiance_pipeline: wgpu::ComputePipeline,
+    visibility_pipeline: wgpu::ComputePipeline,
+    frame: u32,
+    valid: bool,
+}
+
+impl Ddgi {
+    pub fn new(device: &wgpu::Device, grid: ProbeGrid, bvh: &Bvh, env_view: &wgpu::TextureView) -> Self {
+        let sampled = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+        let storage = wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC;
+        let irradiance = atlas(device, "DDGI Irradiance", &grid, IRRADIANCE_TILE, sampled);
+        let visibility = atlas(device, "DDGI Visibility", &grid, VISIBILITY_TILE, sampled);
+        let irradiance_next = atlas(device, "DDGI Irradiance Update", &grid, IRRADIANCE_TILE, storage);
+        let visibility_next = atlas(device, "DDGI Visibility Update", &grid, VISIBILITY_TILE, storage);
+        let view = |texture: &wgpu::Texture| texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("DDGI Params Buffer"),
+            size: std::mem::size_of::<DdgiParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let (node_buffer, triangle_buffer) = bvh.create_buffers(device, "DDGI");
+        let ray_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("DDGI Ray Buffer"),
+            size: grid.probe_count() as u64 * RAYS_PER_PROBE * 16,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("DDGI Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let buffer = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let texture = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let storage_texture = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: ATLAS_FORMAT,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        };
+        let read_only = wgpu::BufferBindingType::Storage { read_only: true };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("ddgi_bind_group_layout"),
+            entries: &[
+                buffer(0, wgpu::BufferBindingType::Uniform),
+                buffer(1, read_only),
+                buffer(2, read_only),
+                buffer(3, wgpu::BufferBindingType::Storage { read_only: false }),
+                texture(4),
+                texture(5),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                texture(7),
+                storage_texture(8),
+                storage_texture(9),
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("DDGI Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("ddgi.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("DDGI Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |label: &str, entry_point: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point,
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        let trace_pipeline = pipeline("DDGI Trace Pipeline", "cs_trace");
+        let irradiance_pipeline = pipeline("DDGI Irradiance Pipeline", "cs_irradiance");
+        let visibility_pipeline = pipeline("DDGI Visibility Pipeline", "cs_visibility");
+
+        let resources = DdgiResources {
+            irradiance_view: view(&irradiance),
+            visibility_view: view(&visibility),
+            irradiance_next_view: view(&irradiance_next),
+            visibility_next_view: view(&visibility_next),
+            irradiance,
+            visibility,
+            irradiance_next,
+            visibility_next,
+            params_buffer,
+            node_buffer,
+            triangle_buffer,
+            ray_buffer,
+            sampler,
+        };
+        let bind_group = resources.bind_group(device, &layout, env_view);
+
+        Self {
+            enabled: true,
+            grid,
+            node_count: bvh.nodes.len() as u32,
+            resources,
+            layout,
+            bind_group,
+            trace_pipeline,
+            irradiance_pipeline,
+            visibility_pipeline,
+            frame: 0,
+            valid: false,
+        }
+    }
+
+    pub fn irradiance_view(&self) -> &wgpu::TextureView {
+        &self.resources.irradiance_view
+    }
+
+    pub fn visibility_view(&self) -> &wgpu::TextureView {
+        &self.resources.visibility_view
+    }
+
+    /// Rebinds the sky that rays leaving the scene pick up.
+    pub fn set_environment(&mut self, device: &wgpu::Device, env_view: &wgpu::TextureView) {
+        self.bind_group = self.resources.bind_group(device, &self.layout, env_view);
+    }
+
+    /// Traces this frame's rays and blends them into the atlases.
+    pub fn update(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        sun: &DirectionalLight,
+        env_intensity: [f32; 4],
+    ) {
+        let g = &self.grid;
+        let radiance = sun.color.map(|c| c * sun.intensity);
+        let max_distance = (g.spacing.x.max(g.spacing.y).max(g.spacing.z) * g.counts.iter().copied().max().unwrap_or(1) as f32)
+            .max(1.0);
+        let params = DdgiParams {
+            rotation: ray_rotation(self.frame).into(),
+            grid_origin: [g.origin.x, g.origin.y, g.origin.z, 0.0],
+            grid_spacing: [g.spacing.x, g.spacing.y, g.spacing.z, 0.0],
+            grid_counts: [g.counts[0], g.counts[1], g.counts[2], 0],
+            sun_direction: [sun.direction.x, sun.direction.y, sun.direction.z, 0.0],
+            sun_radiance: [radiance[0], radiance[1], radiance[2], 0.0],
+            env_intensity,
+            settings: [
+                HYSTERESIS,
+                max_distance,
+                if self.valid { 1.0 } else { 0.0 },
+                self.node_count as f32,
+            ],
+        };
+        queue.write_buffer(&self.resources.params_buffer, 0, bytemuck::cast_slice(&[params]));
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("DDGI Update"),
+                timestamp_writes: None,
+            });
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            let probes = g.probe_count();
+            pass.set_pipeline(&self.trace_pipeline);
+            pass.dispatch_workgroups(probes, 1, 1);
+            pass.set_pipeline(&self.irradiance_pipeline);
+            pass.dispatch_workgroups(probes, 1, 1);
+            pass.set_pipeline(&self.visibility_pipeline);
+            pass.dispatch_workgroups(probes, 1, 1);
+        }
+        let r = &self.resources;
+        for (source, target) in [(&r.irradiance_next, &r.irradiance), (&r.visibility_next, &r.visibility)] {
+            encoder.copy_texture_to_texture(source.as_image_copy(), target.as_image_copy(), source.size());
+        }
+        self.frame = self.frame.wrapping_add(1);
+        self.valid = true;
+    }
+}
+
+/// Stand-in for the atlases in bind groups while DDGI is off; the shaders never sample it then.
+pub fn placeholder_view(device: &wgpu::Device) -> wgpu::TextureView {
+    let grid = ProbeGrid {
+        origin: cgmath::Point3::new(0.0, 0.0, 0.0),
+        spacing: cgmath::Vector3::new(1.0, 1.0, 1.0),
+        counts: [1, 1, 1],
+    };
+    atlas(device, "DDGI Placeholder", &grid, 1, wgpu::TextureUsages::TEXTURE_BINDING)
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
@@ -0,0 +1,355 @@
+// Dynamic diffuse GI: every probe of the grid traces a batch of rays against the scene
+// BVH each frame, and the results are blended into octahedral irradiance and distance
+// atlases that the main shader samples with a Chebyshev visibility test.
+
+struct DdgiParams {
+    // Random rotation applied to this frame's ray directions.
+    rotation: mat4x4<f32>,
+    grid_origin: vec4<f32>,
+    grid_spacing: vec4<f32>,
+    grid_counts: vec4<u32>,
+    // Direction the sun's light travels.
+    sun_direction: vec4<f32>,
+    sun_radiance: vec4<f32>,
+    env_intensity: vec4<f32>,
+    // x: hysteresis, y: max ray distance, z: 1 once the atlases hold results, w: BVH node count.
+    settings: vec4<f32>,
+};
+
+struct BvhNode {
+    min: vec3<f32>,
+    first: u32,
+    max: vec3<f32>,
+    count: u32,
+};
+
+struct Triangle {
+    a: vec4<f32>,
+    b: vec4<f32>,
+    c: vec4<f32>,
+    albedo: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> params: DdgiParams;
+
+@group(0) @binding(1)
+var<storage, read> nodes: array<BvhNode>;
+
+@group(0) @binding(2)
+var<storage, read> triangles: array<Triangle>;
+
+// Per ray: radiance in rgb, hit distance in w (negative for back faces).
+@group(0) @binding(3)
+var<storage, read_write> rays: array<vec4<f32>>;
+
+@group(0) @binding(4)
+var irradiance_atlas: texture_2d<f32>;
+
+@group(0) @binding(5)
+var visibility_atlas: texture_2d<f32>;
+
+@group(0) @binding(6)
+var linear_sampler: sampler;
+
+@group(0) @binding(7)
+var env_map: texture_2d<f32>;
+
+@group(0) @binding(8)
+var irradiance_out: texture_storage_2d<rgba16float, write>;
+
+@group(0) @binding(9)
+var visibility_out: texture_storage_2d<rgba16float, write>;
+
+const PI: f32 = 3.14159265359;
+const RAYS_PER_PROBE: u32 = 64u;
+// Interior texels per tile side; tiles add a one texel border for bilinear filtering.
+const IRRADIANCE_TEXELS: i32 = 6;
+const VISIBILITY_TEXELS: i32 = 14;
+const STACK_SIZE: u32 = 32u;
+const NO_HIT: f32 = 3.4e38;
+
+fn sign_not_zero(v: vec2<f32>) -> vec2<f32> {
+    return select(vec2<f32>(-1.0), vec2<f32>(1.0), v >= vec2<f32>(0.0));
+}
+
+fn oct_encode(n: vec3<f32>) -> vec2<f32> {
+    let p = n.xy / (abs(n.x) + abs(n.y) + abs(n.z));
+    if n.z < 0.0 {
+        return (1.0 - abs(p.yx)) * sign_not_zero(p);
+    }
+    return p;
+}
+
+fn oct_decode(e: vec2<f32>) -> vec3<f32> {
+    var n = vec3<f32>(e, 1.0 - abs(e.x) - abs(e.y));
+    if n.z < 0.0 {
+        n = vec3<f32>((1.0 - abs(n.yx)) * sign_not_zero(n.xy), n.z);
+    }
+    return normalize(n);
+}
+
+fn probe_tile(probe: u32) -> vec2<i32> {
+    let counts = params.grid_counts;
+    let x = probe % counts.x;
+    let y = probe / counts.x % counts.y;
+    let z = probe / (counts.x * counts.y);
+    return vec2<i32>(i32(x + y * counts.x), i32(z));
+}
+
+fn ray_direction(index: u32) -> vec3<f32> {
+    // Spherical Fibonacci point set.
+    let golden = 2.39996323;
+    let z = 1.0 - (2.0 * f32(index) + 1.0) / f32(RAYS_PER_PROBE);
+    let r = sqrt(max(1.0 - z * z, 0.0));
+    let phi = f32(index) * golden;
+    return normalize((params.rotation * vec4<f32>(r * cos(phi), r * sin(phi), z, 0.0)).xyz);
+}
+
+fn dir_to_equirect_uv(dir: vec3<f32>) -> vec2<f32> {
+    let d = normalize(dir);
+    let u = atan2(d.z, d.x) / (2.0 * PI) + 0.5;
+    let v = acos(clamp(d.y, -1.0, 1.0)) / PI;
+    return vec2<f32>(u, v);
+}
+
+fn intersect_box(origin: vec3<f32>, inv_dir: vec3<f32>, node: BvhNode, t_max: f32) -> bool {
+    let t0 = (node.min - origin) * inv_dir;
+    let t1 = (node.max - origin) * inv_dir;
+    let near = max(max(min(t0.x, t1.x), min(t0.y, t1.y)), min(t0.z, t1.z));
+    let far = min(min(max(t0.x, t1.x), max(t0.y, t1.y)), max(t0.z, t1.z));
+    return far >= max(near, 0.0) && near < t_max;
+}
+
+// Möller-Trumbore, two-sided; returns NO_HIT on a miss.
+fn intersect_triangle(origin: vec3<f32>, dir: vec3<f32>, tri: Triangle) -> f32 {
+    let e1 = tri.b.xyz - tri.a.xyz;
+    let e2 = tri.c.xyz - tri.a.xyz;
+    let p = cross(dir, e2);
+    let det = dot(e1, p);
+    if abs(det) < 1e-10 {
+        return NO_HIT;
+    }
+    let inv_det = 1.0 / det;
+    let s = origin - tri.a.xyz;
+    let u = dot(s, p) * inv_det;
+    if u < 0.0 || u > 1.0 {
+        return NO_HIT;
+    }
+    let q = cross(s, e1);
+    let v = dot(dir, q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return NO_HIT;
+    }
+    let t = dot(e2, q) * inv_det;
+    return select(NO_HIT, t, t > 1e-4);
+}
+
+struct Hit {
+    t: f32,
+    triangle: u32,
+};
+
+// Closest hit within t_max, or the first one found when `any_hit` is set.
+fn trace(origin: vec3<f32>, dir: vec3<f32>, t_max: f32, any_hit: bool) -> Hit {
+    var hit = Hit(t_max, 0xffffffffu);
+    if params.settings.w < 1.0 {
+        return hit;
+    }
+    let inv_dir = 1.0 / select(dir, vec3<f32>(1e-8), abs(dir) < vec3<f32>(1e-8));
+    var stack: array<u32, STACK_SIZE>;
+    var top = 1u;
+    stack[0] = 0u;
+    while top > 0u {
+        top -= 1u;
+        let node = nodes[stack[top]];
+        if !intersect_box(origin, inv_dir, node, hit.t) {
+            continue;
+        }
+        if node.count > 0u {
+            for (var i = node.first; i < node.first + node.count; i++) {
+                let t = intersect_triangle(origin, dir, triangles[i]);
+                if t < hit.t {
+                    hit = Hit(t, i);
+                    if any_hit {
+                        return hit;
+                    }
+                }
+            }
+        } else if top + 2u <= STACK_SIZE {
+            stack[top] = node.first;
+            stack[top + 1u] = node.first + 1u;
+            top += 2u;
+        }
+    }
+    return hit;
+}
+
+fn sample_uv(probe: u32, dir: vec3<f32>, texels: i32, atlas: vec2<f32>) -> vec2<f32> {
+    let tile = probe_tile(probe) * (texels + 2) + 1;
+    let coords = vec2<f32>(tile) + (oct_encode(dir) * 0.5 + 0.5) * f32(texels);
+    return coords / atlas;
+}
+
+// Irradiance over pi from the previous update, for multi-bounce lighting at ray hits.
+fn probe_irradiance(p: vec3<f32>, N: vec3<f32>) -> vec3<f32> {
+    let counts = vec3<i32>(params.grid_counts.xyz);
+    let spacing = params.grid_spacing.xyz;
+    let biased = p + N * (0.3 * min(spacing.x, min(spacing.y, spacing.z)));
+    let local = (biased - params.grid_origin.xyz) / spacing;
+    let base = clamp(vec3<i32>(floor(local)), vec3<i32>(0), counts - 2);
+    let t = clamp(local - vec3<f32>(base), vec3<f32>(0.0), vec3<f32>(1.0));
+    let irradiance_size = vec2<f32>(textureDimensions(irradiance_atlas));
+    let visibility_size = vec2<f32>(textureDimensions(visibility_atlas));
+
+    var sum = vec3<f32>(0.0);
+    var total = 0.0;
+    for (var i = 0u; i < 8u; i++) {
+        let offset = vec3<u32>(i & 1u, (i >> 1u) & 1u, (i >> 2u) & 1u);
+        let cell = vec3<u32>(base) + offset;
+        let probe = (cell.z * params.grid_counts.y + cell.y) * params.grid_counts.x + cell.x;
+        let probe_pos = params.grid_origin.xyz + vec3<f32>(cell) * spacing;
+        let to_probe = probe_pos - biased;
+        let distance = length(to_probe);
+        let dir = to_probe / max(distance, 1e-4);
+        let tri = mix(1.0 - t, t, vec3<f32>(offset));
+        let wrap = dot(dir, N) * 0.5 + 0.5;
+        var w = tri.x * tri.y * tri.z * (wrap * wrap + 0.2);
+        let moments = textureSampleLevel(
+            visibility_atlas, linear_sampler, sample_uv(probe, -dir, VISIBILITY_TEXELS, visibility_size), 0.0
+        ).xy;
+        if distance > moments.x {
+            let variance = abs(moments.x * moments.x - moments.y);
+            let d = distance - moments.x;
+            let chebyshev = variance / (variance + d * d);
+            w *= max(chebyshev * chebyshev * chebyshev, 0.05);
+        }
+        let irradiance = textureSampleLevel(
+            irradiance_atlas, linear_sampler, sample_uv(probe, N, IRRADIANCE_TEXELS, irradiance_size), 0.0
+        ).rgb;
+        sum += irradiance * w;
+        total += w;
+    }
+    return sum / max(total, 1e-6);
+}
+
+@compute @workgroup_size(64)
+fn cs_trace(@builtin(workgroup_id) group: vec3<u32>, @builtin(local_invocation_index) lid: u32) {
+    let probe = group.x;
+    let counts = params.grid_counts;
+    let cell = vec3<u32>(probe % counts.x, probe / counts.x % counts.y, probe / (counts.x * counts.y));
+    let origin = params.grid_origin.xyz + vec3<f32>(cell) * params.grid_spacing.xyz;
+    let dir = ray_direction(lid);
+    let max_distance = params.settings.y;
+
+    let hit = trace(origin, dir, max_distance, false);
+    var result: vec4<f32>;
+    if hit.triangle == 0xffffffffu {
+        let sky = textureSampleLevel(env_map, linear_sampler, dir_to_equirect_uv(dir), 0.0).rgb;
+        result = vec4<f32>(sky * params.env_intensity.rgb, max_distance);
+    } else {
+        let tri = triangles[hit.triangle];
+        var n = normalize(cross(tri.b.xyz - tri.a.xyz, tri.c.xyz - tri.a.xyz));
+        if dot(n, dir) > 0.0 {
+            // Probes that see back faces are inside geometry; a short distance keeps
+            // them from leaking light through walls.
+            result = vec4<f32>(0.0, 0.0, 0.0, -hit.t * 0.2);
+        } else {
+            let p = origin + dir * hit.t;
+            let L = -normalize(params.sun_direction.xyz);
+            var direct = vec3<f32>(0.0);
+            let n_dot_l = dot(n, L);
+            if n_dot_l > 0.0 && trace(p + n * 1e-3, L, 1e5, true).triangle == 0xffffffffu {
+                direct = params.sun_radiance.rgb * n_dot_l / PI;
+            }
+            var indirect = vec3<f32>(0.0);
+            if params.settings.z > 0.5 {
+                indirect = probe_irradiance(p, n);
+            }
+            result = vec4<f32>(tri.albedo.rgb * (direct + indirect), hit.t);
+        }
+    }
+    rays[probe * RAYS_PER_PROBE + lid] = result;
+}
+
+var<workgroup> probe_rays: array<vec4<f32>, 64>;
+
+// Interior texel whose value a tile texel holds; border texels mirror the octahedral
+// neighbour across the tile edge so bilinear filtering wraps correctly.
+fn interior_texel(t: vec2<i32>, n: i32) -> vec2<i32> {
+    var s = t;
+    if t.x == 0 || t.x == n + 1 {
+        s.y = n + 1 - t.y;
+    }
+    if t.y == 0 || t.y == n + 1 {
+        s.x = n + 1 - t.x;
+    }
+    return clamp(s, vec2<i32>(1), vec2<i32>(n));
+}
+
+fn texel_direction(t: vec2<i32>, n: i32) -> vec3<f32> {
+    let interior = interior_texel(t, n);
+    return oct_decode((vec2<f32>(interior - 1) + 0.5) / f32(n) * 2.0 - 1.0);
+}
+
+fn load_rays(probe: u32, lid: u32) {
+    if lid < RAYS_PER_PROBE {
+        probe_rays[lid] = rays[probe * RAYS_PER_PROBE + lid];
+    }
+    workgroupBarrier();
+}
+
+fn hysteresis() -> f32 {
+    return select(0.0, params.settings.x, params.settings.z > 0.5);
+}
+
+@compute @workgroup_size(8, 8)
+fn cs_irradiance(
+    @builtin(workgroup_id) group: vec3<u32>,
+    @builtin(local_invocation_id) local: vec3<u32>,
+    @builtin(local_invocation_index) lid: u32,
+) {
+    let probe = group.x;
+    load_rays(probe, lid);
+    let t = vec2<i32>(local.xy);
+    let texel_dir = texel_direction(t, IRRADIANCE_TEXELS);
+    var sum = vec3<f32>(0.0);
+    var total = 0.0;
+    for (var i = 0u; i < RAYS_PER_PROBE; i++) {
+        let ray = probe_rays[i];
+        if ray.w < 0.0 {
+            continue;
+        }
+        let w = max(dot(texel_dir, ray_direction(i)), 0.0);
+        sum += ray.rgb * w;
+        total += w;
+    }
+    let pixel = probe_tile(probe) * (IRRADIANCE_TEXELS + 2) + t;
+    let previous = textureLoad(irradiance_atlas, pixel, 0).rgb;
+    let current = select(previous, sum / max(total, 1e-6), total > 0.0);
+    textureStore(irradiance_out, pixel, vec4<f32>(mix(current, previous, hysteresis()), 1.0));
+}
+
+@compute @workgroup_size(16, 16)
+fn cs_visibility(
+    @builtin(workgroup_id) group: vec3<u32>,
+    @builtin(local_invocation_id) local: vec3<u32>,
+    @builtin(local_invocation_index) lid: u32,
+) {
+    let probe = group.x;
+    load_rays(probe, lid);
+    let t = vec2<i32>(local.xy);
+    let texel_dir = texel_direction(t, VISIBILITY_TEXELS);
+    var sum = vec2<f32>(0.0);
+    var total = 0.0;
+    for (var i = 0u; i < RAYS_PER_PROBE; i++) {
+        let d = min(abs(probe_rays[i].w), params.settings.y);
+        let w = pow(max(dot(texel_dir, ray_direction(i)), 0.0), 20.0);
+        sum += vec2<f32>(d, d * d) * w;
+        total += w;
+    }
+    let pixel = probe_tile(probe) * (VISIBILITY_TEXELS + 2) + t;
+    let previous = textureLoad(visibility_atlas, pixel, 0).xy;
+    let current = select(previous, sum / max(total, 1e-6), total > 1e-4);
+    textureStore(visibility_out, pixel, vec4<f32>(mix(current, previous, hysteresis()), 0.0, 1.0));
+}
@@ -0,0 +1,251 @@
+use cgmath::{InnerSpace, Matrix4, Point3, SquareMatrix, Vector3, Vector4};
+use wgpu::util::DeviceExt;
+
+use crate::aabb::Aabb;
+use crate::camera::DepthMode;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+}
+
+/// Segments per circle of a debug sphere.
+const SPHERE_SEGMENTS: usize = 24;
+
+/// Line overlay drawn over the scene with depth testing. Static lines stay until replaced;
+/// shapes queued with `line`, `aabb`, `sphere`, `frustum` and `arrow` are drawn by the next
+/// `upload` and `draw` and then dropped, so they have to be queued again every frame.
+pub struct DebugLines {
+    pipeline: wgpu::RenderPipeline,
+    camera_bind_group: wgpu::BindGroup,
+    static_lines: Option<(wgpu::Buffer, u32)>,
+    queued: Vec<LineVertex>,
+    /// Grown to fit the largest frame so far.
+    dynamic_buffer: Option<wgpu::Buffer>,
+    dynamic_count: u32,
+}
+
+impl DebugLines {
+    pub fn new(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        camera_buffer: &wgpu::Buffer,
+        color_format: wgpu::TextureFormat,
+        depth_mode: DepthMode,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Debug Draw Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("debug_draw.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug Lines Pipeline Layout"),
+            bind_group_layouts: &[camera_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Debug Lines Pipeline"),
+            layout: Some(&layout),
+            cache: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_line",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &[
+                        wgpu::VertexAttribute {
+                            offset: 0,
+                            shader_location: 0,
+                            format: wgpu::VertexFormat::Float32x3,
+                        },
+                        wgpu::VertexAttribute {
+                            offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                            shader_location: 1,
+                            format: wgpu::VertexFormat::Float32x3,
+                        },
+                    ],
+                }],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_line",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: depth_mode.compare(wgpu::CompareFunction::LessEqual),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("debug_lines_camera_bind_group"),
+            layout: camera_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            pipeline,
+            camera_bind_group,
+            static_lines: None,
+            queued: Vec::new(),
+            dynamic_buffer: None,
+            dynamic_count: 0,
+        }
+    }
+
+    pub fn set_static_lines(&mut self, device: &wgpu::Device, lines: &[LineVertex]) {
+        if lines.is_empty() {
+            self.static_lines = None;
+            return;
+        }
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Debug Static Lines"),
+            contents: bytemuck::cast_slice(lines),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        self.static_lines = Some((buffer, lines.len() as u32));
+    }
+
+    pub fn line(&mut self, a: Point3<f32>, b: Point3<f32>, color: [f32; 3]) {
+        self.queued.push(LineVertex { position: a.into(), color });
+        self.queued.push(LineVertex { position: b.into(), color });
+    }
+
+    pub fn aabb(&mut self, aabb: &Aabb, color: [f32; 3]) {
+        let (min, max) = (aabb.min, aabb.max);
+        let corners = [0, 1, 2, 3, 4, 5, 6, 7].map(|i| {
+            Point3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        });
+        self.box_edges(&corners, color);
+    }
+
+    /// Three great circles, one per axis plane.
+    pub fn sphere(&mut self, center: Point3<f32>, radius: f32, color: [f32; 3]) {
+        let axes = [
+            (Vector3::unit_x(), Vector3::unit_y()),
+            (Vector3::unit_y(), Vector3::unit_z()),
+            (Vector3::unit_z(), Vector3::unit_x()),
+        ];
+        for (u, v) in axes {
+            let point = |i: usize| {
+                let angle = i as f32 / SPHERE_SEGMENTS as f32 * std::f32::consts::TAU;
+                center + (u * angle.cos() + v * angle.sin()) * radius
+            };
+            for i in 0..SPHERE_SEGMENTS {
+                self.line(point(i), point(i + 1), color);
+            }
+        }
+    }
+
+    /// The volume a clip-from-world matrix sees, with wgpu's 0-1 depth range.
+    pub fn frustum(&mut self, view_proj: Matrix4<f32>, color: [f32; 3]) {
+        let Some(inverse) = view_proj.invert() else {
+            return;
+        };
+        let corners = [0, 1, 2, 3, 4, 5, 6, 7].map(|i| {
+            let ndc = Vector4::new(
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { 0.0 } else { 1.0 },
+                1.0,
+            );
+            let p = inverse * ndc;
+            Point3::new(p.x / p.w, p.y / p.w, p.z / p.w)
+        });
+        self.box_edges(&corners, color);
+    }
+
+    /// A line from `origin` along `direction` with a small head at the far end.
+    pub fn arrow(&mut self, origin: Point3<f32>, direction: Vector3<f32>, length: f32, color: [f32; 3]) {
+        let dir = direction.normalize();
+        let tip = origin + dir * length;
+        self.line(origin, tip, color);
+        let side = if dir.y.abs() > 0.95 { Vector3::unit_x() } else { Vector3::unit_y() };
+        let u = dir.cross(side).normalize();
+        let v = dir.cross(u);
+        let head = length * 0.1;
+        for offset in [u, -u, v, -v] {
+            self.line(tip, tip - dir * head + offset * head * 0.5, color);
+        }
+    }
+
+    /// Corners indexed by bits: x in bit 0, y in bit 1, z in bit 2.
+    fn box_edges(&mut self, corners: &[Point3<f32>; 8], color: [f32; 3]) {
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corners[i], corners[i | bit], color);
+                }
+            }
+        }
+    }
+
+    /// Moves this frame's queued shapes to the GPU, replacing the previous frame's.
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.dynamic_count = self.queued.len() as u32;
+        if self.queued.is_empty() {
+            return;
+        }
+        let bytes: &[u8] = bytemuck::cast_slice(&self.queued);
+        let size = bytes.len() as u64;
+        let buffer = match self.dynamic_buffer.take() {
+            Some(buffer) if buffer.size() >= size => buffer,
+            _ => device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Debug Dynamic Lines"),
+                size: size.next_power_of_two(),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+        };
+        queue.write_buffer(&buffer, 0, bytes);
+        self.dynamic_buffer = Some(buffer);
+        self.queued.clear();
+    }
+
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+        let dynamic = self.dynamic_buffer.as_ref().filter(|_| self.dynamic_count > 0);
+        let dynamic = dynamic.map(|b| (b, self.dynamic_count));
+        let static_lines = self.static_lines.as_ref().map(|(b, count)| (b, *count));
+        if dynamic.is_none() && static_lines.is_none() {
+            return;
+        }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        for (buffer, count) in static_lines.into_iter().chain(dynamic) {
+            pass.set_vertex_buffer(0, buffer.slice(..));
+            pass.draw(0..count, 0..1);
+        }
+    }
+}
@@ -0,0 +1,24 @@
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct LineOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+@vertex
+fn vs_line(@location(0) position: vec3<f32>, @location(1) color: vec3<f32>) -> LineOut {
+    var o: LineOut;
+    o.pos = camera.view_proj * vec4<f32>(position, 1.0);
+    o.color = color;
+    return o;
+}
+
+@fragment
+fn fs_line(in: LineOut) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
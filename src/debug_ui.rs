@@ -0,0 +1,296 @@
+use winit::{event::WindowEvent, window::Window};
+
+use crate::bindings::Action;
+use crate::debug_view::DebugView;
+use crate::evsm::ShadowFilter;
+use crate::model::MeshStats;
+use crate::shadows::ShadowSettings;
+
+/// What the settings panel edits, copied out of the renderer before it is drawn and
+/// written back after.
+pub struct Settings {
+    pub shadow_filter: ShadowFilter,
+    pub shadow_settings: ShadowSettings,
+    pub normal_offset: f32,
+    pub ev100: f32,
+    pub auto_exposure: bool,
+    pub compensation: f32,
+    pub env_intensity: f32,
+    /// Hours of the sun's day; changing it hands the scene's light to the sun.
+    pub time_of_day: f32,
+    pub animate_sun: bool,
+    pub debug_view: DebugView,
+    /// Effects switched through their key actions, with whether each is on.
+    pub effects: Vec<(&'static str, Action, bool)>,
+    /// None when the scene has no animation clips.
+    pub animation: Option<AnimationSettings>,
+}
+
+/// The animation player's timeline and the bone weight view.
+#[derive(Clone, PartialEq)]
+pub struct AnimationSettings {
+    /// Names of every loaded clip, in the player's order.
+    pub clips: Vec<String>,
+    pub current: usize,
+    pub time: f32,
+    pub duration: f32,
+    pub speed: f32,
+    pub looping: bool,
+    pub playing: bool,
+    pub show_weights: bool,
+    pub weight_joint: usize,
+    /// Joints in the largest skin; 0 without skins.
+    pub joints: usize,
+}
+
+/// A scene graph node in the mesh inspector, with the meshes hanging from it.
+pub struct InspectorNode {
+    pub name: String,
+    pub meshes: Vec<InspectorMesh>,
+    pub children: Vec<InspectorNode>,
+}
+
+pub struct InspectorMesh {
+    /// Scene mesh index, as picking selects it.
+    pub index: usize,
+    pub name: String,
+    pub stats: MeshStats,
+    /// How the mesh takes part in shadows, when not as a plain caster.
+    pub shadow: &'static str,
+}
+
+impl InspectorNode {
+    fn has_issues(&self) -> bool {
+        self.meshes.iter().any(|m| m.stats.has_issues()) || self.children.iter().any(Self::has_issues)
+    }
+}
+
+/// egui over the frame, drawn last so nothing covers it: the settings panel with the
+/// renderer's shadow, exposure and debug view settings, and the console.
+pub struct DebugUi {
+    /// Whether the settings panel shows.
+    pub visible: bool,
+    ctx: egui::Context,
+    state: egui_winit::State,
+    renderer: egui_wgpu::Renderer,
+}
+
+impl DebugUi {
+    pub fn new(device: &wgpu::Device, window: &Window, output_format: wgpu::TextureFormat) -> Self {
+        let ctx = egui::Context::default();
+        let max_texture_side = device.limits().max_texture_dimension_2d as usize;
+        let state = egui_winit::State::new(
+            ctx.clone(),
+            egui::ViewportId::ROOT,
+            window,
+            Some(window.scale_factor() as f32),
+            None,
+            Some(max_texture_side),
+        );
+        Self {
+            visible: false,
+            ctx,
+            state,
+            renderer: egui_wgpu::Renderer::new(device, output_format, None, 1, false),
+        }
+    }
+
+    /// Hands `event` to egui; true if it used it, such as a click over a window or a key
+    /// typed into the console.
+    pub fn on_window_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        self.state.on_window_event(window, event).consumed
+    }
+
+    /// Lays out what `build` adds to the context and records it onto `target`.
+    pub fn draw(
+        &mut self,
+        window: &Window,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        target: (&wgpu::TextureView, (u32, u32)),
+        build: impl FnMut(&egui::Context),
+    ) {
+        let input = self.state.take_egui_input(window);
+        let output = self.ctx.run(input, build);
+        self.state.handle_platform_output(window, output.platform_output);
+
+        let (view, (width, height)) = target;
+        let screen = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: [width, height],
+            pixels_per_point: output.pixels_per_point,
+        };
+        let primitives = self.ctx.tessellate(output.shapes, output.pixels_per_point);
+        for (id, delta) in &output.textures_delta.set {
+            self.renderer.update_texture(device, queue, *id, delta);
+        }
+        // The panel has no paint callbacks, so no command buffers of their own come back.
+        self.renderer.update_buffers(device, queue, encoder, &primitives, &screen);
+        let mut pass = encoder
+            .begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Debug UI Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            })
+            .forget_lifetime();
+        self.renderer.render(&mut pass, &primitives, &screen);
+        drop(pass);
+        for id in &output.textures_delta.free {
+            self.renderer.free_texture(id);
+        }
+    }
+}
+
+/// Lays the settings panel out over `s`, adding the actions its toggles and buttons
+/// fired, for the renderer to run like key presses.
+pub fn settings_panel(ctx: &egui::Context, s: &mut Settings, actions: &mut Vec<Action>) {
+    egui::Window::new("Settings")
+        .default_pos([12.0, 12.0])
+        .resizable(false)
+        .show(ctx, |ui| {
+            egui::CollapsingHeader::new("Shadows").default_open(true).show(ui, |ui| {
+                egui::ComboBox::from_label("Filter")
+                    .selected_text(format!("{:?}", s.shadow_filter))
+                    .show_ui(ui, |ui| {
+                        for filter in [ShadowFilter::Pcf, ShadowFilter::Evsm] {
+                            ui.selectable_value(&mut s.shadow_filter, filter, format!("{:?}", filter));
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    let quality = &s.shadow_settings;
+                    ui.label(format!("{} px, {} cascades", quality.resolution, quality.cascades));
+                    if ui.button("Next preset").clicked() {
+                        actions.push(Action::ShadowQuality);
+                    }
+                });
+                ui.add(egui::Slider::new(&mut s.normal_offset, 0.0..=0.05).text("Normal offset"));
+            });
+            egui::CollapsingHeader::new("Exposure").default_open(true).show(ui, |ui| {
+                ui.checkbox(&mut s.auto_exposure, "Auto exposure");
+                if s.auto_exposure {
+                    ui.add(egui::Slider::new(&mut s.compensation, -5.0..=5.0).text("Compensation (EV)"));
+                } else {
+                    ui.add(egui::Slider::new(&mut s.ev100, -6.0..=18.0).text("EV100"));
+                }
+                let env = egui::Slider::new(&mut s.env_intensity, 0.0..=100_000.0).logarithmic(true);
+                ui.add(env.text("Environment (nits)"));
+            });
+            egui::CollapsingHeader::new("Sun").default_open(true).show(ui, |ui| {
+                ui.add(egui::Slider::new(&mut s.time_of_day, 0.0..=24.0).text("Time of day"));
+                ui.checkbox(&mut s.animate_sun, "Animate");
+            });
+            egui::CollapsingHeader::new("Debug").default_open(true).show(ui, |ui| {
+                egui::ComboBox::from_label("View")
+                    .selected_text(format!("{:?}", s.debug_view))
+                    .show_ui(ui, |ui| {
+                        for view in DebugView::ALL {
+                            ui.selectable_value(&mut s.debug_view, view, format!("{:?}", view));
+                        }
+                    });
+            });
+            egui::CollapsingHeader::new("Effects").default_open(true).show(ui, |ui| {
+                for (label, action, on) in &mut s.effects {
+                    if ui.checkbox(on, *label).changed() {
+                        actions.push(*action);
+                    }
+                }
+            });
+            if let Some(a) = &mut s.animation {
+                egui::CollapsingHeader::new("Animation").default_open(true).show(ui, |ui| animation_section(ui, a));
+            }
+        });
+}
+
+fn animation_section(ui: &mut egui::Ui, a: &mut AnimationSettings) {
+    egui::ComboBox::from_label("Clip")
+        .selected_text(format!("{} ({}/{})", a.clips[a.current], a.current + 1, a.clips.len()))
+        .show_ui(ui, |ui| {
+            for (i, name) in a.clips.iter().enumerate() {
+                ui.selectable_value(&mut a.current, i, name);
+            }
+        });
+    ui.horizontal(|ui| {
+        if ui.button(if a.playing { "Pause" } else { "Play" }).clicked() {
+            a.playing = !a.playing;
+        }
+        ui.checkbox(&mut a.looping, "Loop");
+    });
+    let timeline = egui::Slider::new(&mut a.time, 0.0..=a.duration).suffix(" s").text("Time");
+    // Scrubbing holds the clip where it is let go.
+    if ui.add(timeline).changed() {
+        a.playing = false;
+    }
+    ui.add(egui::Slider::new(&mut a.speed, 0.125..=8.0).logarithmic(true).suffix("x").text("Speed"));
+    if a.joints > 0 {
+        ui.checkbox(&mut a.show_weights, "Bone weights");
+        ui.add_enabled(a.show_weights, egui::Slider::new(&mut a.weight_joint, 0..=a.joints - 1).text("Joint"));
+    }
+}
+
+/// Lists the scene graph with each mesh's statistics. Meshes with broken geometry are
+/// flagged, and so are the nodes above them, which start expanded; clicking a mesh
+/// selects it, as picking it would.
+pub fn mesh_panel(ctx: &egui::Context, nodes: &[InspectorNode], selected: &mut Option<usize>) {
+    egui::Window::new("Meshes")
+        .default_pos([ctx.screen_rect().width() - 380.0, 12.0])
+        .default_size([360.0, 480.0])
+        .show(ctx, |ui| {
+            ui.label("Normals are drawn blue and tangents red.");
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for (i, node) in nodes.iter().enumerate() {
+                    node_row(ui, node, i, selected);
+                }
+            });
+        });
+}
+
+fn node_row(ui: &mut egui::Ui, node: &InspectorNode, id: usize, selected: &mut Option<usize>) {
+    let flagged = node.has_issues();
+    let mut header = egui::RichText::new(&node.name);
+    if flagged {
+        header = egui::RichText::new(format!("⚠ {}", node.name)).color(ui.visuals().warn_fg_color);
+    }
+    egui::CollapsingHeader::new(header).id_salt(id).default_open(flagged).show(ui, |ui| {
+        for mesh in &node.meshes {
+            mesh_row(ui, mesh, selected);
+        }
+        for (i, child) in node.children.iter().enumerate() {
+            node_row(ui, child, i, selected);
+        }
+    });
+}
+
+fn mesh_row(ui: &mut egui::Ui, mesh: &InspectorMesh, selected: &mut Option<usize>) {
+    let s = &mesh.stats;
+    let mut label = format!("{}: {} tris, {} verts", mesh.name, s.triangles, s.vertices);
+    if !mesh.shadow.is_empty() {
+        label += &format!(", {}", mesh.shadow);
+    }
+    let mut text = egui::RichText::new(label);
+    if s.has_issues() {
+        text = text.color(ui.visuals().warn_fg_color);
+    }
+    if ui.selectable_label(*selected == Some(mesh.index), text).clicked() {
+        *selected = Some(mesh.index);
+    }
+    if s.has_issues() {
+        ui.indent(mesh.index, |ui| {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                format!(
+                    "{} degenerate triangles, {} NaN vertices, {} indices out of range",
+                    s.degenerate_triangles, s.invalid_vertices, s.out_of_range_indices
+                ),
+            );
+        });
+    }
+}
@@ -0,0 +1,69 @@
+/// Unlit views of a single shading input, cycled with `F1`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DebugView {
+    #[default]
+    Off,
+    BaseColor,
+    Normals,
+    Roughness,
+    Metallic,
+    AmbientOcclusion,
+    Depth,
+    CascadeIndex,
+    ShadowFactor,
+    Uvs,
+    /// Fragments rasterized per pixel, drawn by `Overdraw` rather than the mesh shaders.
+    Overdraw,
+}
+
+impl DebugView {
+    pub const ALL: [Self; 11] = [
+        Self::Off,
+        Self::BaseColor,
+        Self::Normals,
+        Self::Roughness,
+        Self::Metallic,
+        Self::AmbientOcclusion,
+        Self::Depth,
+        Self::CascadeIndex,
+        Self::ShadowFactor,
+        Self::Uvs,
+        Self::Overdraw,
+    ];
+
+    /// The view after this one, wrapping back to regular shading.
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+
+    /// Name in the console's `debug` command.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::BaseColor => "base_color",
+            Self::Normals => "normals",
+            Self::Roughness => "roughness",
+            Self::Metallic => "metallic",
+            Self::AmbientOcclusion => "ao",
+            Self::Depth => "depth",
+            Self::CascadeIndex => "cascades",
+            Self::ShadowFactor => "shadows",
+            Self::Uvs => "uvs",
+            Self::Overdraw => "overdraw",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        Self::ALL.into_iter().find(|v| v.name() == name)
+    }
+
+    /// Value of `camera.debug.x` in the shader.
+    pub fn shader_index(self) -> f32 {
+        match self {
+            // Shaded normally underneath; the heatmap replaces the frame afterwards.
+            Self::Overdraw => 0.0,
+            _ => self as usize as f32,
+        }
+    }
+}
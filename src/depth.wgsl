@@ -0,0 +1,10 @@
+// Depth constants shared by every shader that reads the depth buffer, appended to each
+// after preprocessing with the depth mode's defines.
+
+// Depth the buffer is cleared to, where the sky shows: 0 when reversed depth puts the far
+// plane at 0.
+#ifdef REVERSED_Z
+const FAR_DEPTH: f32 = 0.0;
+#else
+const FAR_DEPTH: f32 = 1.0;
+#endif
@@ -0,0 +1,227 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+use crate::camera::DepthMode;
+use crate::pipelines::depth_shader;
+
+/// Bits of the nearest and farthest distance.
+const BUFFER_SIZE: u64 = 8;
+/// Cleared bounds: any distance is nearer than `u32::MAX` and farther than zero.
+const CLEARED: [u32; 2] = [u32::MAX, 0];
+
+const MAP_PENDING: u8 = 0;
+const MAP_DONE: u8 = 1;
+const MAP_FAILED: u8 = 2;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum ReadbackState {
+    Idle,
+    Copied,
+    Mapping,
+}
+
+/// Reduces the depth buffer to the nearest and farthest camera distance of the opaque
+/// surfaces on screen, read back a frame or two later, so the shadow cascades can cover
+/// only the depth range that is actually visible (sample distribution shadow maps).
+pub struct DepthBounds {
+    pipeline: wgpu::ComputePipeline,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    bounds_buffer: wgpu::Buffer,
+    params_buffer: wgpu::Buffer,
+    readback: wgpu::Buffer,
+    readback_state: ReadbackState,
+    map_status: Arc<AtomicU8>,
+    size: (u32, u32),
+    latest: Option<(f32, f32)>,
+}
+
+impl DepthBounds {
+    pub fn new(
+        device: &wgpu::Device,
+        depth_view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+        depth_mode: DepthMode,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Depth Bounds Shader"),
+            source: wgpu::ShaderSource::Wgsl(depth_shader(include_str!("depth_bounds.wgsl"), depth_mode).into()),
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("depth_bounds_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Depth Bounds Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Depth Bounds Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "cs_reduce",
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let bounds_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Depth Bounds Buffer"),
+            size: BUFFER_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Depth Bounds Params Buffer"),
+            size: std::mem::size_of::<[[f32; 4]; 4]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Depth Bounds Readback Buffer"),
+            size: BUFFER_SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = Self::create_bind_group(device, &layout, depth_view, &bounds_buffer, &params_buffer);
+
+        Self {
+            pipeline,
+            layout,
+            bind_group,
+            bounds_buffer,
+            params_buffer,
+            readback,
+            readback_state: ReadbackState::Idle,
+            map_status: Arc::new(AtomicU8::new(MAP_PENDING)),
+            size: (width, height),
+            latest: None,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        depth_view: &wgpu::TextureView,
+        bounds_buffer: &wgpu::Buffer,
+        params_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("depth_bounds_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: bounds_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, depth_view: &wgpu::TextureView, width: u32, height: u32) {
+        self.bind_group =
+            Self::create_bind_group(device, &self.layout, depth_view, &self.bounds_buffer, &self.params_buffer);
+        self.size = (width, height);
+    }
+
+    /// Records the reduction of the frame's depth, rendered with the inverse projection
+    /// `proj_inv`. Skipped while the previous result is still being read back.
+    pub fn record(&mut self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, proj_inv: [[f32; 4]; 4]) {
+        if self.readback_state != ReadbackState::Idle {
+            return;
+        }
+        queue.write_buffer(&self.bounds_buffer, 0, bytemuck::cast_slice(&CLEARED));
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&proj_inv));
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Depth Bounds Pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.dispatch_workgroups(self.size.0.div_ceil(16), self.size.1.div_ceil(16), 1);
+        }
+        encoder.copy_buffer_to_buffer(&self.bounds_buffer, 0, &self.readback, 0, BUFFER_SIZE);
+        self.readback_state = ReadbackState::Copied;
+    }
+
+    /// Call after the frame's commands were submitted: starts the readback and collects a
+    /// finished one without blocking.
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        match self.readback_state {
+            ReadbackState::Idle => {}
+            ReadbackState::Copied => {
+                let status = self.map_status.clone();
+                status.store(MAP_PENDING, Ordering::Release);
+                self.readback.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                    status.store(if result.is_ok() { MAP_DONE } else { MAP_FAILED }, Ordering::Release);
+                });
+                self.readback_state = ReadbackState::Mapping;
+            }
+            ReadbackState::Mapping => {
+                device.poll(wgpu::Maintain::Poll);
+                match self.map_status.load(Ordering::Acquire) {
+                    MAP_DONE => {
+                        {
+                            let data = self.readback.slice(..).get_mapped_range();
+                            let words: &[u32] = bytemuck::cast_slice(&data);
+                            // Nothing but sky leaves the bounds cleared.
+                            self.latest = (words[0] <= words[1])
+                                .then(|| (f32::from_bits(words[0]), f32::from_bits(words[1])));
+                        }
+                        self.readback.unmap();
+                        self.readback_state = ReadbackState::Idle;
+                    }
+                    MAP_FAILED => self.readback_state = ReadbackState::Idle,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Nearest and farthest visible distance of the last reduced frame, if it showed any
+    /// surface.
+    pub fn latest(&self) -> Option<(f32, f32)> {
+        self.latest
+    }
+}
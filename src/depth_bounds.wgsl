@@ -0,0 +1,59 @@
+// Reduces the nearest and farthest camera distance of the opaque surfaces in the depth
+// buffer, which the shadow cascades are fitted to.
+
+struct Bounds {
+    // Non-negative floats order the same as their bit patterns.
+    min_bits: atomic<u32>,
+    max_bits: atomic<u32>,
+};
+
+struct Params {
+    proj_inv: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var depth_texture: texture_depth_2d;
+
+@group(0) @binding(1)
+var<storage, read_write> bounds: Bounds;
+
+@group(0) @binding(2)
+var<uniform> params: Params;
+
+var<workgroup> local_min: atomic<u32>;
+var<workgroup> local_max: atomic<u32>;
+
+@compute @workgroup_size(16, 16)
+fn cs_reduce(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(local_invocation_index) lid: u32,
+) {
+    if lid == 0u {
+        atomicStore(&local_min, 0xffffffffu);
+        atomicStore(&local_max, 0u);
+    }
+    workgroupBarrier();
+
+    let size = textureDimensions(depth_texture);
+    if gid.x < size.x && gid.y < size.y {
+        let depth = textureLoad(depth_texture, vec2<i32>(gid.xy), 0);
+        // Pixels left at the cleared depth show the sky.
+        if depth != FAR_DEPTH {
+            let uv = (vec2<f32>(gid.xy) + 0.5) / vec2<f32>(size);
+            let view = params.proj_inv * vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+            let distance = length(view.xyz / view.w);
+            atomicMin(&local_min, bitcast<u32>(distance));
+            atomicMax(&local_max, bitcast<u32>(distance));
+        }
+    }
+    workgroupBarrier();
+
+    if lid == 0u {
+        let nearest = atomicLoad(&local_min);
+        let farthest = atomicLoad(&local_max);
+        if nearest <= farthest {
+            atomicMin(&bounds.min_bits, nearest);
+            atomicMax(&bounds.max_bits, farthest);
+        }
+    }
+}
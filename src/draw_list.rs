@@ -0,0 +1,41 @@
+/// Where one mesh goes in the frame's draw order.
+pub struct DrawKey {
+    pub mesh: usize,
+    /// Shared by the materials drawn with the same pipeline.
+    pub pipeline: usize,
+    pub material: usize,
+    /// Distance from the camera.
+    pub depth: f32,
+    pub blended: bool,
+}
+
+/// The camera passes' mesh draws, sorted once per frame so each pass binds as few
+/// pipelines and materials as it can: opaque meshes by pipeline, then material, then
+/// front to back, and blended meshes back to front.
+#[derive(Default)]
+pub struct DrawList {
+    opaque: Vec<usize>,
+    transparent: Vec<usize>,
+}
+
+impl DrawList {
+    pub fn prepare(&mut self, keys: impl IntoIterator<Item = DrawKey>) {
+        let (mut opaque, mut transparent): (Vec<DrawKey>, Vec<DrawKey>) = keys.into_iter().partition(|k| !k.blended);
+        opaque.sort_unstable_by(|a, b| {
+            (a.pipeline, a.material).cmp(&(b.pipeline, b.material)).then(a.depth.total_cmp(&b.depth))
+        });
+        transparent.sort_unstable_by(|a, b| b.depth.total_cmp(&a.depth));
+        self.opaque = opaque.into_iter().map(|k| k.mesh).collect();
+        self.transparent = transparent.into_iter().map(|k| k.mesh).collect();
+    }
+
+    /// Scene mesh indices of the opaque and masked meshes, in draw order.
+    pub fn opaque(&self) -> &[usize] {
+        &self.opaque
+    }
+
+    /// Scene mesh indices of the blended meshes, in draw order.
+    pub fn transparent(&self) -> &[usize] {
+        &self.transparent
+    }
+}
@@ -0,0 +1,296 @@
+use std::time::Instant;
+
+use anyhow::Result;
+use winit::{
+    application::ApplicationHandler,
+    event::*,
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    keyboard::{KeyCode, PhysicalKey},
+    window::{Window, WindowAttributes, WindowId},
+};
+
+use crate::options::RendererOptions;
+use crate::plugin::Plugin;
+use crate::profiler;
+use crate::renderer::Renderer;
+
+/// How often a hidden window checks whether it shows again.
+const HIDDEN_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+/// Device losses in a row [`App`] recovers from before it gives up.
+const MAX_DEVICE_RECOVERIES: u32 = 3;
+/// Frames presented after a recovery before [`App`] counts device losses from zero again.
+const RECOVERY_RESET_FRAMES: u32 = 600;
+
+/// A [`Renderer`] with the [`Plugin`]s hooked into it. [`Engine::update`] and
+/// [`Engine::render`] advance and draw a frame, for programs that run their own loop;
+/// [`Engine::run`] and [`App`] run it on winit's event loop, feeding it input, resizing
+/// it with the window and pausing it while the window is hidden.
+pub struct Engine {
+    renderer: Renderer,
+    plugins: Vec<Box<dyn Plugin>>,
+}
+
+impl Engine {
+    /// Creates the renderer for `window` from `options`; see [`Renderer::new`].
+    pub async fn new(window: Window, options: RendererOptions) -> Result<Self> {
+        Ok(Self {
+            renderer: Renderer::new(window, options).await?,
+            plugins: Vec::new(),
+        })
+    }
+
+    /// Registers `plugin` after those already added and calls its `setup`.
+    pub fn with_plugin(mut self, mut plugin: impl Plugin + 'static) -> Self {
+        plugin.setup(&mut self.renderer);
+        self.plugins.push(Box::new(plugin));
+        self
+    }
+
+    pub fn renderer(&self) -> &Renderer {
+        &self.renderer
+    }
+
+    pub fn renderer_mut(&mut self) -> &mut Renderer {
+        &mut self.renderer
+    }
+
+    /// Hands a window event to the plugins, then to the engine's own controls (see
+    /// [`Renderer::on_window_event`]). True if one of them used it.
+    pub fn window_event(&mut self, event: &WindowEvent) -> bool {
+        let Engine { renderer, plugins } = self;
+        plugins.iter_mut().any(|p| p.on_event(renderer, event)) || renderer.on_window_event(event)
+    }
+
+    /// Builds the renderer again on a new GPU device after the old one was lost (see
+    /// [`Renderer::is_device_lost`]), then runs the plugins' `setup` again so they can
+    /// recreate their GPU resources.
+    pub fn recover(self) -> Result<Self> {
+        let Engine { renderer, mut plugins } = self;
+        let mut renderer = pollster::block_on(renderer.rebuild())?;
+        for plugin in &mut plugins {
+            plugin.setup(&mut renderer);
+        }
+        Ok(Self { renderer, plugins })
+    }
+
+    /// Advances the scene by `dt` seconds, then runs the plugins' updates.
+    pub fn update(&mut self, dt: f32) {
+        self.renderer.update_by(dt);
+        for plugin in &mut self.plugins {
+            plugin.on_update(&mut self.renderer, dt);
+        }
+        self.renderer.capture_path_frame();
+    }
+
+    /// Draws a frame with the plugins' passes over it and presents it.
+    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        let Engine { renderer, plugins } = self;
+        let rendered = renderer.render_with(|ctx| {
+            for plugin in plugins.iter_mut() {
+                plugin.on_render_pass(ctx);
+            }
+        });
+        profiler::end_frame();
+        rendered
+    }
+
+    /// Renders until the window closes or Escape is pressed, then finishes the profiler
+    /// trace. With a shot matrix (`--batch=`) it renders that offscreen and returns
+    /// without opening the event loop.
+    pub fn run(mut self, event_loop: EventLoop<()>) -> Result<()> {
+        if self.renderer.run_batch()? {
+            return profiler::finish_trace();
+        }
+        App::running(self).run(event_loop)
+    }
+
+    /// Advances and draws one frame as the window asks for it. True if it was presented.
+    fn redraw(&mut self, event_loop: &ActiveEventLoop, dt: f32) -> bool {
+        self.update(dt);
+        match self.render() {
+            Ok(_) => {
+                if self.renderer.finished() {
+                    event_loop.exit();
+                }
+                return true;
+            }
+            Err(wgpu::SurfaceError::Lost) => self.renderer.resize(self.renderer.size()),
+            Err(wgpu::SurfaceError::OutOfMemory) => event_loop.exit(),
+            Err(e) => log::warn!("Failed to render a frame: {}", e),
+        }
+        false
+    }
+}
+
+type Setup = Box<dyn FnOnce(Engine) -> Engine>;
+
+/// Runs an [`Engine`] on winit's event loop. [`App::new`] opens the window once the loop
+/// has started, as winit expects, and creates the engine in it.
+pub struct App {
+    attributes: WindowAttributes,
+    options: RendererOptions,
+    setup: Option<Setup>,
+    engine: Option<Engine>,
+    started: bool,
+    last_update: Instant,
+    recoveries: u32,
+    /// Frames presented since the last recovery.
+    recovered_frames: u32,
+    result: Result<()>,
+}
+
+impl App {
+    /// An app that opens a window with `attributes` and creates the engine in it from
+    /// `options`, as [`Engine::new`] does.
+    pub fn new(attributes: WindowAttributes, options: RendererOptions) -> Self {
+        Self {
+            attributes,
+            options,
+            setup: None,
+            engine: None,
+            started: false,
+            last_update: Instant::now(),
+            recoveries: 0,
+            recovered_frames: 0,
+            result: Ok(()),
+        }
+    }
+
+    fn running(engine: Engine) -> Self {
+        Self {
+            engine: Some(engine),
+            started: true,
+            ..Self::new(WindowAttributes::default(), RendererOptions::default())
+        }
+    }
+
+    /// Calls `setup` with the engine before its first frame, such as to add plugins.
+    pub fn with_setup(mut self, setup: impl FnOnce(Engine) -> Engine + 'static) -> Self {
+        self.setup = Some(Box::new(setup));
+        self
+    }
+
+    /// Runs until the window closes or Escape is pressed, then finishes the profiler
+    /// trace. Errors creating the engine end the loop and come back from here.
+    pub fn run(mut self, event_loop: EventLoop<()>) -> Result<()> {
+        event_loop.run_app(&mut self)?;
+        self.result?;
+        profiler::finish_trace()
+    }
+
+    fn start(&mut self, event_loop: &ActiveEventLoop) -> Result<()> {
+        let window = event_loop.create_window(self.attributes.clone())?;
+        let mut engine = pollster::block_on(Engine::new(window, std::mem::take(&mut self.options)))?;
+        if let Some(setup) = self.setup.take() {
+            engine = setup(engine);
+        }
+        if engine.renderer.run_batch()? {
+            event_loop.exit();
+            return Ok(());
+        }
+        self.engine = Some(engine);
+        self.last_update = Instant::now();
+        Ok(())
+    }
+
+    /// Replaces an engine whose GPU device was lost with one on a new device, or ends the
+    /// loop with a crash report when that fails.
+    fn recover(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(engine) = self.engine.take() else {
+            return;
+        };
+        self.recoveries += 1;
+        self.recovered_frames = 0;
+        let recovered = if self.recoveries > MAX_DEVICE_RECOVERIES {
+            Err(anyhow::anyhow!("the device was lost {} times", self.recoveries))
+        } else {
+            engine.recover()
+        };
+        match recovered {
+            Ok(engine) => {
+                self.engine = Some(engine);
+                self.last_update = Instant::now();
+            }
+            Err(e) => {
+                let e = e.context("The GPU device was lost and could not be recreated");
+                crate::crash::report(&format!("{:#}", e));
+                self.result = Err(e);
+                event_loop.exit();
+            }
+        }
+    }
+}
+
+impl ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if std::mem::replace(&mut self.started, true) {
+            return;
+        }
+        if let Err(e) = self.start(event_loop) {
+            self.result = Err(e);
+            event_loop.exit();
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, window_id: WindowId, event: WindowEvent) {
+        let Some(engine) = &mut self.engine else {
+            return;
+        };
+        if window_id != engine.renderer.window().id() || engine.window_event(&event) {
+            return;
+        }
+        let state = &mut engine.renderer;
+        match event {
+            WindowEvent::CloseRequested
+            | WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state: ElementState::Pressed,
+                        physical_key: PhysicalKey::Code(KeyCode::Escape),
+                        ..
+                    },
+                ..
+            } => event_loop.exit(),
+            WindowEvent::Resized(physical_size) => state.resize(physical_size),
+            WindowEvent::Occluded(occluded) => state.set_occluded(occluded),
+            // The platform may still ask for a frame while the window is hidden.
+            WindowEvent::RedrawRequested if state.is_paused() => {}
+            WindowEvent::RedrawRequested => {
+                state.wait_for_frame();
+                let now = Instant::now();
+                let dt = now.duration_since(self.last_update).as_secs_f32();
+                self.last_update = now;
+                if engine.redraw(event_loop, dt) && self.recoveries > 0 {
+                    self.recovered_frames += 1;
+                    if self.recovered_frames >= RECOVERY_RESET_FRAMES {
+                        self.recoveries = 0;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn device_event(&mut self, _: &ActiveEventLoop, _: DeviceId, event: DeviceEvent) {
+        if let (Some(engine), DeviceEvent::MouseMotion { delta }) = (&mut self.engine, event) {
+            engine.renderer.on_mouse_motion(delta);
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if self.engine.as_ref().is_some_and(|e| e.renderer.is_device_lost()) {
+            self.recover(event_loop);
+        }
+        let Some(engine) = &mut self.engine else {
+            return;
+        };
+        let state = &mut engine.renderer;
+        if state.update_paused() {
+            // Wake now and then in case a platform never reports the window visible again.
+            event_loop.set_control_flow(ControlFlow::WaitUntil(Instant::now() + HIDDEN_POLL_INTERVAL));
+        } else {
+            event_loop.set_control_flow(ControlFlow::Wait);
+            state.window().request_redraw();
+        }
+    }
+}
@@ -0,0 +1,381 @@
+use crate::pipelines::vertex_buffer_layout;
+use crate::shadows::ShadowSettings;
+use crate::vertex_packing::VertexPacking;
+
+/// Moments are filtered, so they need fewer texels than the depth cascades.
+const EVSM_MAX_SIZE: u32 = 2048;
+/// Warp exponents for the positive and negative moments; Rgba16Float overflows above ~5.5.
+pub const EVSM_EXPONENTS: [f32; 2] = [5.0, 5.0];
+const MOMENTS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShadowFilter {
+    /// Comparison-sampled depth with a Poisson-disk PCF kernel.
+    Pcf,
+    /// Exponential variance shadow maps: blurred moments, no comparison sampler.
+    Evsm,
+}
+
+impl ShadowFilter {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "pcf" => Some(Self::Pcf),
+            "evsm" => Some(Self::Evsm),
+            _ => None,
+        }
+    }
+}
+
+struct EvsmMaps {
+    settings: ShadowSettings,
+    textures: [wgpu::Texture; 3],
+    layer_views: Vec<wgpu::TextureView>,
+    temp_view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+    blur_h_bind_groups: Vec<wgpu::BindGroup>,
+    blur_v_bind_group: wgpu::BindGroup,
+}
+
+/// Cascade moments for [`ShadowFilter::Evsm`], bound as group 3 of the main pipeline.
+/// The maps (up to 2048², one layer per cascade) are only allocated once EVSM is first
+/// enabled; until then the group points at a 1×1 placeholder.
+pub struct EvsmShadows {
+    pub layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    sampler: wgpu::Sampler,
+    moments_pipeline: wgpu::RenderPipeline,
+    blur_layout: wgpu::BindGroupLayout,
+    blur_h_pipeline: wgpu::RenderPipeline,
+    blur_v_pipeline: wgpu::RenderPipeline,
+    maps: Option<EvsmMaps>,
+}
+
+fn moments_texture(device: &wgpu::Device, label: &str, size: u32, layers: u32) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: layers,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: MOMENTS_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
+
+fn array_view(texture: &wgpu::Texture) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("EVSM Moments View"),
+        dimension: Some(wgpu::TextureViewDimension::D2Array),
+        ..Default::default()
+    })
+}
+
+impl EvsmShadows {
+    pub fn new(device: &wgpu::Device, caster_layout: &wgpu::BindGroupLayout, packing: VertexPacking) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("evsm_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("EVSM Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let placeholder = moments_texture(device, "EVSM Placeholder", 1, 1);
+        let bind_group = Self::create_bind_group(device, &layout, &array_view(&placeholder), &sampler);
+
+        let moments_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("EVSM Moments Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("evsm.wgsl").into()),
+        });
+        let moments_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("EVSM Moments Pipeline Layout"),
+            bind_group_layouts: &[caster_layout],
+            push_constant_ranges: &[],
+        });
+        let moments_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("EVSM Moments Pipeline"),
+            layout: Some(&moments_layout),
+            cache: None,
+            vertex: wgpu::VertexState {
+                module: &moments_shader,
+                entry_point: "vs_moments",
+                buffers: &[vertex_buffer_layout(packing)],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &moments_shader,
+                entry_point: "fs_moments",
+                targets: &[Some(MOMENTS_FORMAT.into())],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let blur_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("EVSM Blur Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("evsm_blur.wgsl").into()),
+        });
+        let blur_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("evsm_blur_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                },
+                count: None,
+            }],
+        });
+        let blur_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("EVSM Blur Pipeline Layout"),
+            bind_group_layouts: &[&blur_layout],
+            push_constant_ranges: &[],
+        });
+        let blur_pipeline = |entry_point: &str| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("EVSM Blur Pipeline"),
+                layout: Some(&blur_pipeline_layout),
+                cache: None,
+                vertex: wgpu::VertexState {
+                    module: &blur_shader,
+                    entry_point: "vs_fullscreen",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &blur_shader,
+                    entry_point,
+                    targets: &[Some(MOMENTS_FORMAT.into())],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        let blur_h_pipeline = blur_pipeline("fs_blur_h");
+        let blur_v_pipeline = blur_pipeline("fs_blur_v");
+
+        Self {
+            layout,
+            bind_group,
+            sampler,
+            moments_pipeline,
+            blur_layout,
+            blur_h_pipeline,
+            blur_v_pipeline,
+            maps: None,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        view: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("evsm_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        })
+    }
+
+    pub fn is_allocated(&self) -> bool {
+        self.maps.is_some()
+    }
+
+    /// Allocates the moment maps the first time EVSM is used and reallocates them when the
+    /// shadow settings change. Returns the replaced textures so the caller can defer them.
+    pub fn ensure_maps(&mut self, device: &wgpu::Device, settings: &ShadowSettings) -> Vec<wgpu::Texture> {
+        if self.maps.as_ref().is_some_and(|maps| maps.settings == *settings) {
+            return Vec::new();
+        }
+        let size = settings.resolution.min(EVSM_MAX_SIZE);
+        let moments = moments_texture(device, "EVSM Moments", size, settings.cascades);
+        let temp = moments_texture(device, "EVSM Blur Temp", size, 1);
+        let depth = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("EVSM Depth"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let layer_views: Vec<wgpu::TextureView> = (0..settings.cascades)
+            .map(|layer| {
+                moments.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some(&format!("EVSM Moments Layer {}", layer)),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let temp_view = temp.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_view = depth.create_view(&wgpu::TextureViewDescriptor::default());
+        let blur_bind_group = |view: &wgpu::TextureView| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("evsm_blur_bind_group"),
+                layout: &self.blur_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                }],
+            })
+        };
+        let blur_h_bind_groups = layer_views.iter().map(blur_bind_group).collect();
+        let blur_v_bind_group = blur_bind_group(&temp_view);
+
+        self.bind_group = Self::create_bind_group(device, &self.layout, &array_view(&moments), &self.sampler);
+        let old = self.maps.replace(EvsmMaps {
+            settings: *settings,
+            textures: [moments, temp, depth],
+            layer_views,
+            temp_view,
+            depth_view,
+            blur_h_bind_groups,
+            blur_v_bind_group,
+        });
+        log::info!("Allocated {}² EVSM maps for {} cascades", size, settings.cascades);
+        old.map(|maps| maps.textures.into()).unwrap_or_default()
+    }
+
+    /// Renders and blurs the moments of every cascade. `draw_casters` issues the shadow
+    /// caster draws into a pass that already has the pipeline and camera bound.
+    pub fn record(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        cascade_cameras: &[wgpu::BindGroup],
+        mut draw_casters: impl FnMut(&mut wgpu::RenderPass<'_>, u32),
+    ) {
+        let Some(maps) = &self.maps else {
+            return;
+        };
+        // Moments of the far plane, i.e. fully lit.
+        let far = [EVSM_EXPONENTS[0].exp(), (2.0 * EVSM_EXPONENTS[0]).exp(), -(-EVSM_EXPONENTS[1]).exp()];
+        let clear = wgpu::Color {
+            r: far[0] as f64,
+            g: far[1] as f64,
+            b: far[2] as f64,
+            a: (far[2] * far[2]) as f64,
+        };
+        for (cascade, (layer_view, camera)) in maps.layer_views.iter().zip(cascade_cameras).enumerate() {
+            {
+                let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some(&format!("EVSM Moments Pass {}", cascade)),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: layer_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(clear),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: &maps.depth_view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: wgpu::StoreOp::Discard,
+                        }),
+                        stencil_ops: None,
+                    }),
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+                pass.set_pipeline(&self.moments_pipeline);
+                pass.set_bind_group(0, camera, &[]);
+                draw_casters(&mut pass, cascade as u32);
+            }
+            {
+                let mut pass = blur_pass(encoder, &maps.temp_view, "EVSM Blur H", clear);
+                pass.set_pipeline(&self.blur_h_pipeline);
+                pass.set_bind_group(0, &maps.blur_h_bind_groups[cascade], &[]);
+                pass.draw(0..3, 0..1);
+            }
+            {
+                let mut pass = blur_pass(encoder, layer_view, "EVSM Blur V", clear);
+                pass.set_pipeline(&self.blur_v_pipeline);
+                pass.set_bind_group(0, &maps.blur_v_bind_group, &[]);
+                pass.draw(0..3, 0..1);
+            }
+        }
+    }
+}
+
+fn blur_pass<'e>(
+    encoder: &'e mut wgpu::CommandEncoder,
+    view: &wgpu::TextureView,
+    label: &str,
+    clear: wgpu::Color,
+) -> wgpu::RenderPass<'e> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(clear),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        occlusion_query_set: None,
+        timestamp_writes: None,
+    })
+}
@@ -0,0 +1,51 @@
+// Exponential variance shadow maps: the cascades are rendered as warped depth moments,
+// blurred, and sampled with Chebyshev's inequality in shader.wgsl.
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    view_inv: mat4x4<f32>,
+    proj_inv: mat4x4<f32>,
+    position: vec4<f32>,
+    light_view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// Must match ObjectUniform in objects.rs.
+struct Object {
+    model: mat4x4<f32>,
+    normal: mat3x3<f32>,
+    material: vec4<u32>,
+};
+
+@group(0) @binding(19)
+var<storage, read> objects: array<Object>;
+
+// Must match EVSM_EXPONENTS in evsm.rs.
+const POSITIVE_EXPONENT: f32 = 5.0;
+const NEGATIVE_EXPONENT: f32 = 5.0;
+
+struct MomentsOut {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) depth: f32,
+};
+
+@vertex
+fn vs_moments(
+    @location(0) position: vec3<f32>,
+    @builtin(instance_index) instance: u32,
+) -> MomentsOut {
+    var out: MomentsOut;
+    out.clip_position = camera.light_view_proj * objects[instance].model * vec4<f32>(position, 1.0);
+    out.depth = out.clip_position.z / out.clip_position.w;
+    return out;
+}
+
+@fragment
+fn fs_moments(in: MomentsOut) -> @location(0) vec4<f32> {
+    let d = clamp(in.depth, 0.0, 1.0) * 2.0 - 1.0;
+    let pos = exp(POSITIVE_EXPONENT * d);
+    let neg = -exp(-NEGATIVE_EXPONENT * d);
+    return vec4<f32>(pos, pos * pos, neg, neg * neg);
+}
@@ -0,0 +1,45 @@
+// Separable blur of the EVSM moments, one cascade layer at a time.
+
+@group(0) @binding(0)
+var blur_source: texture_2d<f32>;
+
+struct FullscreenOut {
+    @builtin(position) pos: vec4<f32>,
+};
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> FullscreenOut {
+    var p = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -3.0),
+        vec2<f32>( 3.0,  1.0),
+        vec2<f32>(-1.0,  1.0),
+    );
+    var o: FullscreenOut;
+    o.pos = vec4<f32>(p[vid], 0.0, 1.0);
+    return o;
+}
+
+// 9-tap binomial kernel.
+const BLUR_WEIGHTS = array<f32, 5>(0.2734375, 0.21875, 0.109375, 0.03125, 0.00390625);
+
+fn blur(pixel: vec2<i32>, step: vec2<i32>) -> vec4<f32> {
+    var weights = BLUR_WEIGHTS;
+    let last = vec2<i32>(textureDimensions(blur_source)) - 1;
+    var sum = textureLoad(blur_source, pixel, 0) * weights[0];
+    for (var i = 1; i < 5; i = i + 1) {
+        let a = clamp(pixel + step * i, vec2<i32>(0), last);
+        let b = clamp(pixel - step * i, vec2<i32>(0), last);
+        sum += (textureLoad(blur_source, a, 0) + textureLoad(blur_source, b, 0)) * weights[i];
+    }
+    return sum;
+}
+
+@fragment
+fn fs_blur_h(in: FullscreenOut) -> @location(0) vec4<f32> {
+    return blur(vec2<i32>(in.pos.xy), vec2<i32>(1, 0));
+}
+
+@fragment
+fn fs_blur_v(in: FullscreenOut) -> @location(0) vec4<f32> {
+    return blur(vec2<i32>(in.pos.xy), vec2<i32>(0, 1));
+}
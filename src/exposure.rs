@@ -0,0 +1,65 @@
+use crate::luminance::LuminanceStats;
+
+pub const DEFAULT_MIN_EV100: f32 = -2.0;
+pub const DEFAULT_MAX_EV100: f32 = 16.0;
+/// How fast exposure follows a brighter and a darker scene, as the inverse of the time
+/// constant in seconds; eyes and cameras recover from glare faster than they open up in
+/// the dark.
+pub const DEFAULT_SPEED_UP: f32 = 3.0;
+pub const DEFAULT_SPEED_DOWN: f32 = 1.0;
+
+/// Pre-exposed value the metered average is brought to.
+const MIDDLE_GREY: f32 = 0.18;
+/// Percentiles of the histogram that are metered; the darkest pixels and highlights like
+/// the sun are left out.
+const METER_LOW: f32 = 0.1;
+const METER_HIGH: f32 = 0.95;
+
+/// Drives the camera EV100 from the luminance histogram of recent frames.
+#[derive(Copy, Clone, Debug)]
+pub struct AutoExposure {
+    pub enabled: bool,
+    pub min_ev100: f32,
+    pub max_ev100: f32,
+    pub speed_up: f32,
+    pub speed_down: f32,
+    /// Stops added to the metered EV100; positive values darken the image.
+    pub compensation: f32,
+}
+
+impl Default for AutoExposure {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_ev100: DEFAULT_MIN_EV100,
+            max_ev100: DEFAULT_MAX_EV100,
+            speed_up: DEFAULT_SPEED_UP,
+            speed_down: DEFAULT_SPEED_DOWN,
+            compensation: 0.0,
+        }
+    }
+}
+
+impl AutoExposure {
+    /// EV100 that exposes the metered part of `stats` at middle grey, within the clamps.
+    pub fn target_ev100(&self, stats: &LuminanceStats) -> Option<f32> {
+        let average = stats.trimmed_average(METER_LOW, METER_HIGH)?;
+        // Back to nits: the histogram was built from pre-exposed values.
+        let nits = average / stats.exposure;
+        if !(nits > 0.0 && nits.is_finite()) {
+            return None;
+        }
+        let ev100 = (nits / (1.2 * MIDDLE_GREY)).log2() + self.compensation;
+        Some(ev100.clamp(self.min_ev100, self.max_ev100))
+    }
+
+    /// Moves `ev100` towards the metered target, exponentially so the speed settings
+    /// behave the same at any frame rate.
+    pub fn adapt(&self, ev100: &mut f32, stats: &LuminanceStats, dt: f32) {
+        let Some(target) = self.target_ev100(stats) else {
+            return;
+        };
+        let speed = if target > *ev100 { self.speed_up } else { self.speed_down };
+        *ev100 += (target - *ev100) * (1.0 - (-speed * dt).exp());
+    }
+}
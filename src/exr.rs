@@ -0,0 +1,81 @@
+//! A minimal OpenEXR writer: uncompressed scanlines of half-float RGB, which any
+//! compositor or grading tool reads.
+
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use half::f16;
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+/// Version 2, single-part scanline file.
+const VERSION: [u8; 4] = [2, 0, 0, 0];
+const PIXEL_TYPE_HALF: i32 = 1;
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+fn values(numbers: &[i32]) -> Vec<u8> {
+    numbers.iter().flat_map(|n| n.to_le_bytes()).collect()
+}
+
+/// Writes linear RGB pixels, row by row from the top, to an .exr file.
+pub fn write_rgb(path: &Path, width: u32, height: u32, pixels: &[[f32; 3]]) -> Result<()> {
+    assert_eq!(pixels.len(), (width * height) as usize);
+    // Channels are stored in name order, so blue comes first.
+    let channels = [("B", 2), ("G", 1), ("R", 0)];
+    let mut channel_list = Vec::new();
+    for (name, _) in channels {
+        channel_list.extend_from_slice(name.as_bytes());
+        channel_list.push(0);
+        channel_list.extend(values(&[PIXEL_TYPE_HALF]));
+        // Perceptually linear flag and three reserved bytes.
+        channel_list.extend_from_slice(&[0; 4]);
+        channel_list.extend(values(&[1, 1]));
+    }
+    channel_list.push(0);
+
+    let window = values(&[0, 0, width as i32 - 1, height as i32 - 1]);
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC);
+    header.extend_from_slice(&VERSION);
+    attribute(&mut header, "channels", "chlist", &channel_list);
+    attribute(&mut header, "compression", "compression", &[0]);
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(&mut header, "pixelAspectRatio", "float", &1.0f32.to_le_bytes());
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(&mut header, "screenWindowWidth", "float", &1.0f32.to_le_bytes());
+    header.push(0);
+
+    // Each scanline is its own chunk: its y, its size, then every channel's row in turn.
+    let line_size = width as usize * channels.len() * 2;
+    let chunk_size = 8 + line_size;
+    let table_end = header.len() + height as usize * 8;
+    let mut file = header;
+    for y in 0..height as usize {
+        file.extend_from_slice(&((table_end + y * chunk_size) as u64).to_le_bytes());
+    }
+    for (y, row) in pixels.chunks_exact(width as usize).enumerate() {
+        file.extend(values(&[y as i32, line_size as i32]));
+        for (_, channel) in channels {
+            for pixel in row {
+                file.extend_from_slice(&f16::from_f32(pixel[channel]).to_le_bytes());
+            }
+        }
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::File::create(path)
+        .and_then(|mut f| f.write_all(&file))
+        .with_context(|| format!("writing {}", path.display()))
+}
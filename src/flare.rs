@@ -0,0 +1,194 @@
+use cgmath::{Matrix4, Vector3, Vector4};
+
+use crate::camera::DepthMode;
+use crate::pipelines::depth_shader;
+use crate::post::HDR_FORMAT;
+use crate::sun::smoothstep;
+
+/// Flare brightness relative to the pre-exposed sun radiance.
+const STRENGTH: f32 = 0.04;
+/// The starburst plus one instance per ghost in flare.wgsl.
+const INSTANCES: u32 = 7;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct FlareParams {
+    sun: [f32; 4],
+    color: [f32; 4],
+}
+
+/// Starburst and ghosts added over the scene while the sun is on screen, dimmed by how
+/// much of the depth buffer around the sun is sky.
+pub struct LensFlare {
+    pub enabled: bool,
+    /// Whether the sun was in front of the camera at the last update.
+    active: bool,
+    params_buffer: wgpu::Buffer,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl LensFlare {
+    pub fn new(device: &wgpu::Device, depth_view: &wgpu::TextureView, depth_mode: DepthMode) -> Self {
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Lens Flare Params Buffer"),
+            size: std::mem::size_of::<FlareParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("lens_flare_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Lens Flare Shader"),
+            source: wgpu::ShaderSource::Wgsl(depth_shader(include_str!("flare.wgsl"), depth_mode).into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Lens Flare Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Lens Flare Pipeline"),
+            layout: Some(&pipeline_layout),
+            cache: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_flare",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_flare",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: HDR_FORMAT,
+                    blend: Some(wgpu::BlendState {
+                        color: additive,
+                        alpha: additive,
+                    }),
+                    write_mask: wgpu::ColorWrites::COLOR,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        let bind_group = Self::create_bind_group(device, &layout, &params_buffer, depth_view);
+        Self {
+            enabled: true,
+            active: false,
+            params_buffer,
+            layout,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        params_buffer: &wgpu::Buffer,
+        depth_view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("lens_flare_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+            ],
+        })
+    }
+
+    /// Rebinds the scene depth after it was reallocated.
+    pub fn set_depth_view(&mut self, device: &wgpu::Device, depth_view: &wgpu::TextureView) {
+        self.bind_group = Self::create_bind_group(device, &self.layout, &self.params_buffer, depth_view);
+    }
+
+    /// Projects the sun for this frame. `to_sun` points at the sun and `radiance` is its
+    /// pre-exposed color and intensity.
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        view_proj: Matrix4<f32>,
+        to_sun: Vector3<f32>,
+        radiance: [f32; 3],
+        aspect: f32,
+    ) {
+        let clip = view_proj * Vector4::new(to_sun.x, to_sun.y, to_sun.z, 0.0);
+        let (x, y) = (clip.x / clip.w, clip.y / clip.w);
+        // Ghosts reach past the centre, so keep drawing a little beyond the screen edge.
+        self.active = clip.w > 0.0 && x.abs() < 1.5 && y.abs() < 1.5;
+        if !self.active {
+            return;
+        }
+        // Fade out with the sun disk as it sets.
+        let scale = STRENGTH * smoothstep(-0.02, 0.05, to_sun.y);
+        let params = FlareParams {
+            sun: [x, y, 0.0, aspect],
+            color: [radiance[0] * scale, radiance[1] * scale, radiance[2] * scale, 0.0],
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+    }
+
+    /// Adds the flare to `target`; the depth view must hold this frame's opaque depth.
+    pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        if !self.enabled || !self.active {
+            return;
+        }
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Lens Flare"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..6, 0..INSTANCES);
+    }
+}
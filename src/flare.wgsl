@@ -0,0 +1,110 @@
+// Sun lens flare: a starburst at the sun and a row of ghosts mirrored through the screen
+// centre, added to the HDR target. Each vertex counts how much of a small patch of the
+// scene depth around the sun is sky, so the flare fades as geometry covers the sun.
+
+struct FlareParams {
+    // xy: sun position in NDC, w: width over height.
+    sun: vec4<f32>,
+    // rgb: pre-exposed sun radiance times the flare strength.
+    color: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> params: FlareParams;
+
+@group(0) @binding(1)
+var depth_map: texture_depth_2d;
+
+struct FlareOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) color: vec3<f32>,
+    // 0 for the starburst, 1 for ghosts.
+    @location(2) @interpolate(flat) kind: u32,
+};
+
+const GHOSTS: u32 = 6u;
+// Ghost centres as multiples of the sun position; negative ones land past the centre.
+const GHOST_OFFSETS: array<f32, 6> = array<f32, 6>(0.55, 0.3, -0.15, -0.4, -0.7, -1.25);
+// Radii as a fraction of the screen height.
+const GHOST_SIZES: array<f32, 6> = array<f32, 6>(0.04, 0.08, 0.03, 0.12, 0.06, 0.2);
+const GHOST_TINTS: array<vec3<f32>, 6> = array<vec3<f32>, 6>(
+    vec3<f32>(0.3, 0.25, 0.1),
+    vec3<f32>(0.1, 0.2, 0.3),
+    vec3<f32>(0.35, 0.15, 0.3),
+    vec3<f32>(0.1, 0.3, 0.15),
+    vec3<f32>(0.3, 0.2, 0.1),
+    vec3<f32>(0.08, 0.12, 0.25),
+);
+const STARBURST_SIZE: f32 = 0.35;
+// Depth samples around the sun, in pixels.
+const SAMPLE_SPACING: f32 = 3.0;
+
+// Share of the depth samples around the sun that see the sky.
+fn sun_visibility() -> f32 {
+    let size = vec2<i32>(textureDimensions(depth_map));
+    let center = (params.sun.xy * vec2<f32>(0.5, -0.5) + 0.5) * vec2<f32>(size);
+    var visible = 0.0;
+    for (var y = -2; y <= 2; y++) {
+        for (var x = -2; x <= 2; x++) {
+            let p = vec2<i32>(center + vec2<f32>(f32(x), f32(y)) * SAMPLE_SPACING);
+            if all(p >= vec2<i32>(0)) && all(p < size) && textureLoad(depth_map, p, 0) == FAR_DEPTH {
+                visible += 1.0;
+            }
+        }
+    }
+    return visible / 25.0;
+}
+
+@vertex
+fn vs_flare(@builtin(vertex_index) vid: u32, @builtin(instance_index) instance: u32) -> FlareOut {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    var offsets = GHOST_OFFSETS;
+    var sizes = GHOST_SIZES;
+    var tints = GHOST_TINTS;
+
+    var center = params.sun.xy;
+    var size = STARBURST_SIZE;
+    var color = params.color.rgb;
+    var kind = 0u;
+    if instance > 0u {
+        let ghost = min(instance - 1u, GHOSTS - 1u);
+        center = params.sun.xy * offsets[ghost];
+        size = sizes[ghost];
+        color = params.color.rgb * tints[ghost];
+        kind = 1u;
+    }
+
+    let corner = corners[vid];
+    var o: FlareOut;
+    o.pos = vec4<f32>(center + corner * size * vec2<f32>(1.0 / params.sun.w, 1.0), 0.0, 1.0);
+    o.uv = corner;
+    o.color = color * sun_visibility();
+    o.kind = kind;
+    return o;
+}
+
+@fragment
+fn fs_flare(in: FlareOut) -> @location(0) vec4<f32> {
+    let r = length(in.uv);
+    if r >= 1.0 {
+        discard;
+    }
+    if in.kind == 0u {
+        let angle = atan2(in.uv.y, in.uv.x);
+        let falloff = (1.0 - r) * (1.0 - r);
+        let streaks = pow(abs(cos(angle * 4.0)), 80.0) + 0.3 * pow(abs(sin(angle * 9.0 + 0.3)), 40.0);
+        let glow = exp(-r * 12.0);
+        return vec4<f32>(in.color * (glow + streaks * falloff * 0.5), 1.0);
+    }
+    // Soft disk with a brighter rim, like an aperture reflection.
+    let ghost = smoothstep(1.0, 0.85, r) * (0.4 + 0.6 * smoothstep(0.5, 0.95, r));
+    return vec4<f32>(in.color * ghost, 1.0);
+}
@@ -0,0 +1,298 @@
+use crate::lights::DirectionalLight;
+
+const FROXEL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// Froxels across, down and in depth; matches 16:9 screens.
+const FROXELS: [u32; 3] = [160, 90, 64];
+pub const DEFAULT_DENSITY: f32 = 0.02;
+pub const DEFAULT_ANISOTROPY: f32 = 0.6;
+/// Share of the environment intensity the fog scatters as ambient light.
+const AMBIENT: [f32; 3] = [0.35, 0.4, 0.5];
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct FogParams {
+    sun_radiance: [f32; 4],
+    ambient: [f32; 4],
+    medium: [f32; 4],
+    range: [f32; 4],
+}
+
+fn froxel_texture(device: &wgpu::Device, label: &str) -> wgpu::TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: FROXELS[0],
+                height: FROXELS[1],
+                depth_or_array_layers: FROXELS[2],
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: FROXEL_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// Froxel-based volumetric fog lit by the sun through the cascaded shadow maps. The
+/// integrated volume holds in-scattered light and transmittance from the camera to each
+/// froxel, which the scene shaders apply to every surface and the sky.
+pub struct VolumetricFog {
+    pub enabled: bool,
+    pub density: f32,
+    pub anisotropy: f32,
+    /// Density falls off exponentially by this much per unit above `base_height`.
+    pub height_falloff: f32,
+    base_height: f32,
+    range: [f32; 2],
+    scatter_view: wgpu::TextureView,
+    integrated_view: wgpu::TextureView,
+    params_buffer: wgpu::Buffer,
+    scatter_layout: wgpu::BindGroupLayout,
+    scatter_bind_group: wgpu::BindGroup,
+    integrate_bind_group: wgpu::BindGroup,
+    scatter_pipeline: wgpu::ComputePipeline,
+    integrate_pipeline: wgpu::ComputePipeline,
+}
+
+impl VolumetricFog {
+    /// `shadow` is the cascade array and its comparison sampler; the fog reaches
+    /// `distance` from the camera and thins out above `base_height`.
+    pub fn new(
+        device: &wgpu::Device,
+        camera_buffer: &wgpu::Buffer,
+        shadow: (&wgpu::TextureView, &wgpu::Sampler),
+        distance: f32,
+        base_height: f32,
+    ) -> Self {
+        let scatter_view = froxel_texture(device, "Fog Scattering");
+        let integrated_view = froxel_texture(device, "Fog Integrated");
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Fog Params Buffer"),
+            size: std::mem::size_of::<FogParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let uniform = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let storage = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: FROXEL_FORMAT,
+                view_dimension: wgpu::TextureViewDimension::D3,
+            },
+            count: None,
+        };
+        let scatter_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("fog_scatter_bind_group_layout"),
+            entries: &[
+                uniform(0),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                },
+                uniform(3),
+                storage(4),
+            ],
+        });
+        let integrate_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("fog_integrate_bind_group_layout"),
+            entries: &[
+                uniform(3),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                storage(6),
+            ],
+        });
+        let scatter_bind_group =
+            Self::create_scatter_bind_group(device, &scatter_layout, camera_buffer, shadow, (&params_buffer, &scatter_view));
+        let integrate_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("fog_integrate_bind_group"),
+            layout: &integrate_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(&scatter_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::TextureView(&integrated_view),
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Fog Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("fog.wgsl").into()),
+        });
+        let pipeline = |label: &str, layout: &wgpu::BindGroupLayout, entry_point: &str| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Fog Pipeline Layout"),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point,
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        let scatter_pipeline = pipeline("Fog Scatter Pipeline", &scatter_layout, "cs_scatter");
+        let integrate_pipeline = pipeline("Fog Integrate Pipeline", &integrate_layout, "cs_integrate");
+
+        let distance = distance.max(1.0);
+        Self {
+            enabled: false,
+            density: DEFAULT_DENSITY,
+            anisotropy: DEFAULT_ANISOTROPY,
+            height_falloff: 0.0,
+            base_height,
+            range: [distance * 0.002, distance],
+            scatter_view,
+            integrated_view,
+            params_buffer,
+            scatter_layout,
+            scatter_bind_group,
+            integrate_bind_group,
+            scatter_pipeline,
+            integrate_pipeline,
+        }
+    }
+
+    fn create_scatter_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        camera_buffer: &wgpu::Buffer,
+        shadow: (&wgpu::TextureView, &wgpu::Sampler),
+        output: (&wgpu::Buffer, &wgpu::TextureView),
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("fog_scatter_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(shadow.0),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(shadow.1),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: output.0.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(output.1),
+                },
+            ],
+        })
+    }
+
+    /// Rebinds the cascade array after the shadow maps were reallocated.
+    pub fn set_shadow_map(
+        &mut self,
+        device: &wgpu::Device,
+        camera_buffer: &wgpu::Buffer,
+        shadow: (&wgpu::TextureView, &wgpu::Sampler),
+    ) {
+        self.scatter_bind_group = Self::create_scatter_bind_group(
+            device,
+            &self.scatter_layout,
+            camera_buffer,
+            shadow,
+            (&self.params_buffer, &self.scatter_view),
+        );
+    }
+
+    /// In-scattered light (rgb) and transmittance (a) from the camera to each froxel.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.integrated_view
+    }
+
+    /// x: 1 when fog applies, y: first slice distance, z: last slice distance.
+    pub fn camera_params(&self) -> [f32; 4] {
+        [if self.enabled { 1.0 } else { 0.0 }, self.range[0], self.range[1], 0.0]
+    }
+
+    /// Scatters and integrates the froxels; the camera buffer must already hold this
+    /// frame's camera and cascades, and the shadow maps must be drawn.
+    pub fn record(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        sun: &DirectionalLight,
+        env_intensity: [f32; 4],
+    ) {
+        let radiance = sun.color.map(|c| c * sun.intensity);
+        let params = FogParams {
+            sun_radiance: [radiance[0], radiance[1], radiance[2], 0.0],
+            ambient: [
+                AMBIENT[0] * env_intensity[0],
+                AMBIENT[1] * env_intensity[1],
+                AMBIENT[2] * env_intensity[2],
+                0.0,
+            ],
+            medium: [self.density, self.height_falloff, self.base_height, self.anisotropy],
+            range: [self.range[0], self.range[1], 0.0, 0.0],
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Volumetric Fog"),
+            timestamp_writes: None,
+        });
+        let [x, y, z] = FROXELS;
+        pass.set_pipeline(&self.scatter_pipeline);
+        pass.set_bind_group(0, &self.scatter_bind_group, &[]);
+        pass.dispatch_workgroups(x.div_ceil(8), y.div_ceil(8), z);
+        pass.set_pipeline(&self.integrate_pipeline);
+        pass.set_bind_group(0, &self.integrate_bind_group, &[]);
+        pass.dispatch_workgroups(x.div_ceil(8), y.div_ceil(8), 1);
+    }
+}
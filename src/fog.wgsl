@@ -0,0 +1,146 @@
+// Froxel volumetric fog: every froxel of a camera-aligned grid (screen x/y, exponential
+// distance slices) gets the sun's in-scattering through the cascaded shadow maps, then
+// each column is integrated front to back into in-scattered light and transmittance.
+
+// Prefix of CameraUniform in shader.wgsl.
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    view_inv: mat4x4<f32>,
+    proj_inv: mat4x4<f32>,
+    position: vec4<f32>,
+    light_view_proj: mat4x4<f32>,
+    light_dir: vec4<f32>,
+    env_intensity: vec4<f32>,
+    cascade_splits: vec4<f32>,
+    light_view_proj_cascade1: mat4x4<f32>,
+    light_view_proj_cascade2: mat4x4<f32>,
+    light_view_proj_cascade3: mat4x4<f32>,
+    time: vec4<f32>,
+    shadow_params: vec4<f32>,
+    shadow_map: vec4<f32>,
+};
+
+struct FogParams {
+    sun_radiance: vec4<f32>,
+    ambient: vec4<f32>,
+    // x: density, y: height falloff, z: height density starts falling off at, w: anisotropy.
+    medium: vec4<f32>,
+    // x: first slice distance, y: last slice distance.
+    range: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(0) @binding(1)
+var shadow_map: texture_depth_2d_array;
+
+@group(0) @binding(2)
+var shadow_sampler: sampler_comparison;
+
+@group(0) @binding(3)
+var<uniform> fog: FogParams;
+
+// rgb: in-scattered radiance per unit length, a: extinction.
+@group(0) @binding(4)
+var scatter_out: texture_storage_3d<rgba16float, write>;
+
+@group(0) @binding(5)
+var scatter_in: texture_3d<f32>;
+
+// rgb: in-scattered light up to the froxel, a: transmittance.
+@group(0) @binding(6)
+var integrated_out: texture_storage_3d<rgba16float, write>;
+
+const PI: f32 = 3.14159265359;
+
+// Distance of slice coordinate `z` in [0, 1]; the inverse of fog_slice in shader.wgsl.
+fn slice_distance(z: f32) -> f32 {
+    return fog.range.x * pow(fog.range.y / fog.range.x, z);
+}
+
+fn light_view_proj(cascade: i32) -> mat4x4<f32> {
+    if cascade == 0 {
+        return camera.light_view_proj;
+    } else if cascade == 1 {
+        return camera.light_view_proj_cascade1;
+    } else if cascade == 2 {
+        return camera.light_view_proj_cascade2;
+    }
+    return camera.light_view_proj_cascade3;
+}
+
+fn sun_shadow(world_pos: vec3<f32>, distance: f32) -> f32 {
+    var cascade = 3;
+    if distance < camera.cascade_splits.x {
+        cascade = 0;
+    } else if distance < camera.cascade_splits.y {
+        cascade = 1;
+    } else if distance < camera.cascade_splits.z {
+        cascade = 2;
+    }
+    cascade = min(cascade, i32(camera.shadow_map.y) - 1);
+    let clip = light_view_proj(cascade) * vec4<f32>(world_pos, 1.0);
+    if clip.w <= 0.0 {
+        return 1.0;
+    }
+    let ndc = clip.xyz / clip.w;
+    let uv = ndc.xy * 0.5 + vec2<f32>(0.5, 0.5);
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) {
+        return 1.0;
+    }
+    return textureSampleCompareLevel(shadow_map, shadow_sampler, uv, cascade, ndc.z);
+}
+
+fn henyey_greenstein(cos_theta: f32, g: f32) -> f32 {
+    let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+    return (1.0 - g * g) / (4.0 * PI * denom * sqrt(denom));
+}
+
+fn view_ray(uv: vec2<f32>) -> vec3<f32> {
+    let view = camera.proj_inv * vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 1.0, 1.0);
+    return normalize((camera.view_inv * vec4<f32>(normalize(view.xyz / view.w), 0.0)).xyz);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn cs_scatter(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(scatter_out);
+    if any(id >= size) {
+        return;
+    }
+    let cell = (vec3<f32>(id) + 0.5) / vec3<f32>(size);
+    let dir = view_ray(cell.xy);
+    let distance = slice_distance(cell.z);
+    let world_pos = camera.position.xyz + dir * distance;
+
+    let height = max(world_pos.y - fog.medium.z, 0.0);
+    let density = fog.medium.x * exp(-height * fog.medium.y);
+    let phase = henyey_greenstein(dot(normalize(camera.light_dir.xyz), -dir), fog.medium.w);
+    let sun = fog.sun_radiance.rgb * phase * sun_shadow(world_pos, distance);
+    // The medium only scatters, so its scattering coefficient equals its extinction.
+    let radiance = (sun + fog.ambient.rgb / (4.0 * PI)) * density;
+    textureStore(scatter_out, id, vec4<f32>(radiance, density));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn cs_integrate(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(scatter_in);
+    if any(id.xy >= size.xy) {
+        return;
+    }
+    var light = vec3<f32>(0.0);
+    var transmittance = 1.0;
+    var previous = 0.0;
+    for (var z = 0u; z < size.z; z++) {
+        let far = slice_distance((f32(z) + 1.0) / f32(size.z));
+        let thickness = far - previous;
+        previous = far;
+        let s = textureLoad(scatter_in, vec3<u32>(id.xy, z), 0);
+        let extinction = max(s.a, 1e-6);
+        let slice_transmittance = exp(-extinction * thickness);
+        // Analytic integral of the scattering over the slice (Hillaire 2015).
+        light += transmittance * (s.rgb - s.rgb * slice_transmittance) / extinction;
+        transmittance *= slice_transmittance;
+        textureStore(integrated_out, vec3<u32>(id.xy, z), vec4<f32>(light, transmittance));
+    }
+}
@@ -0,0 +1,72 @@
+use std::time::{Duration, Instant};
+
+/// The OS may wake a sleeping thread this late, so the last stretch before a deadline is
+/// spun instead.
+const SPIN_MARGIN: Duration = Duration::from_millis(2);
+/// Most fixed steps one frame runs, so a long stall doesn't snowball into ever longer
+/// frames; the time beyond that is dropped.
+const MAX_STEPS: u32 = 8;
+
+/// Holds frames to a maximum rate, for when presentation doesn't.
+pub struct FrameLimiter {
+    interval: Duration,
+    deadline: Instant,
+}
+
+impl FrameLimiter {
+    pub fn new(fps: f32) -> Self {
+        Self {
+            interval: Duration::from_secs_f32(1.0 / fps),
+            deadline: Instant::now(),
+        }
+    }
+
+    /// Blocks until the next frame is due: sleeps while the OS can be trusted to wake on
+    /// time, then spins.
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        if let Some(remaining) = self.deadline.checked_duration_since(now) {
+            if remaining > SPIN_MARGIN {
+                std::thread::sleep(remaining - SPIN_MARGIN);
+            }
+            while Instant::now() < self.deadline {
+                std::hint::spin_loop();
+            }
+            self.deadline += self.interval;
+        } else {
+            // Running behind: pace from now instead of rushing to catch up.
+            self.deadline = now + self.interval;
+        }
+    }
+}
+
+/// Splits frame time into simulation steps of a fixed length, so movement and animation
+/// advance the same whatever the frame rate.
+pub struct FixedTimestep {
+    step: f32,
+    accumulator: f32,
+}
+
+impl FixedTimestep {
+    pub fn new(hz: f32) -> Self {
+        Self {
+            step: 1.0 / hz,
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn step(&self) -> f32 {
+        self.step
+    }
+
+    /// Steps due after `frame_time` more seconds; the remainder carries over.
+    pub fn advance(&mut self, frame_time: f32) -> u32 {
+        self.accumulator += frame_time;
+        let steps = (self.accumulator / self.step) as u32;
+        self.accumulator -= steps as f32 * self.step;
+        if steps > MAX_STEPS {
+            self.accumulator = 0.0;
+        }
+        steps.min(MAX_STEPS)
+    }
+}
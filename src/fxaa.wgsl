@@ -0,0 +1,146 @@
+// FXAA 3.11 (Lottes 2011), quality preset: finds the edge through each pixel from local
+// luma contrast, walks along it to both ends and blends across it by how far the pixel
+// sits from the nearer end. Runs on the tonemapped image.
+
+@group(0) @binding(0)
+var ldr_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var linear_sampler: sampler;
+
+// Contrast below which a pixel is left alone, relative to the brightest neighbour.
+const EDGE_THRESHOLD: f32 = 0.125;
+// Absolute floor so dark regions are not processed.
+const EDGE_THRESHOLD_MIN: f32 = 0.0312;
+const SUBPIXEL_QUALITY: f32 = 0.75;
+const SEARCH_STEPS: i32 = 10;
+
+struct FullscreenOut {
+    @builtin(position) pos: vec4<f32>,
+};
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> FullscreenOut {
+    var p = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -3.0),
+        vec2<f32>( 3.0,  1.0),
+        vec2<f32>(-1.0,  1.0),
+    );
+    var o: FullscreenOut;
+    o.pos = vec4<f32>(p[vid], 0.0, 1.0);
+    return o;
+}
+
+// Roughly perceptual luma; the texture may decode sRGB to linear on sampling.
+fn luma(c: vec3<f32>) -> f32 {
+    return sqrt(dot(c, vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+fn luma_at(uv: vec2<f32>) -> f32 {
+    return luma(textureSampleLevel(ldr_texture, linear_sampler, uv, 0.0).rgb);
+}
+
+fn search_step(i: i32) -> f32 {
+    // Short steps near the pixel, longer ones further out.
+    var steps = array<f32, 10>(1.0, 1.0, 1.0, 1.0, 1.0, 1.5, 2.0, 2.0, 4.0, 8.0);
+    return steps[i];
+}
+
+@fragment
+fn fs_fxaa(in: FullscreenOut) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(ldr_texture));
+    let uv = in.pos.xy * texel;
+    let center = textureSampleLevel(ldr_texture, linear_sampler, uv, 0.0);
+
+    let l = luma(center.rgb);
+    let n = luma_at(uv + vec2<f32>(0.0, -texel.y));
+    let s = luma_at(uv + vec2<f32>(0.0, texel.y));
+    let e = luma_at(uv + vec2<f32>(texel.x, 0.0));
+    let w = luma_at(uv + vec2<f32>(-texel.x, 0.0));
+    let lo = min(l, min(min(n, s), min(e, w)));
+    let hi = max(l, max(max(n, s), max(e, w)));
+    let range = hi - lo;
+    if range < max(EDGE_THRESHOLD_MIN, hi * EDGE_THRESHOLD) {
+        return center;
+    }
+
+    let nw = luma_at(uv + vec2<f32>(-texel.x, -texel.y));
+    let ne = luma_at(uv + vec2<f32>(texel.x, -texel.y));
+    let sw = luma_at(uv + vec2<f32>(-texel.x, texel.y));
+    let se = luma_at(uv + vec2<f32>(texel.x, texel.y));
+
+    let horizontal_edge = abs(nw + sw - 2.0 * w) + 2.0 * abs(n + s - 2.0 * l) + abs(ne + se - 2.0 * e);
+    let vertical_edge = abs(nw + ne - 2.0 * n) + 2.0 * abs(w + e - 2.0 * l) + abs(sw + se - 2.0 * s);
+    let is_horizontal = horizontal_edge >= vertical_edge;
+
+    // Which side of the pixel the edge lies on.
+    let luma1 = select(w, n, is_horizontal);
+    let luma2 = select(e, s, is_horizontal);
+    let gradient1 = abs(luma1 - l);
+    let gradient2 = abs(luma2 - l);
+    let steepest_is_1 = gradient1 >= gradient2;
+    let gradient_scaled = 0.25 * max(gradient1, gradient2);
+    var step_length = select(texel.x, texel.y, is_horizontal);
+    var local_average = 0.5 * (luma2 + l);
+    if steepest_is_1 {
+        step_length = -step_length;
+        local_average = 0.5 * (luma1 + l);
+    }
+
+    // Start half a pixel over, on the edge itself.
+    var edge_uv = uv;
+    if is_horizontal {
+        edge_uv.y += step_length * 0.5;
+    } else {
+        edge_uv.x += step_length * 0.5;
+    }
+    let offset = select(vec2<f32>(0.0, texel.y), vec2<f32>(texel.x, 0.0), is_horizontal);
+
+    var uv1 = edge_uv - offset;
+    var uv2 = edge_uv + offset;
+    var end1 = luma_at(uv1) - local_average;
+    var end2 = luma_at(uv2) - local_average;
+    var done1 = abs(end1) >= gradient_scaled;
+    var done2 = abs(end2) >= gradient_scaled;
+    for (var i = 1; i < SEARCH_STEPS && !(done1 && done2); i++) {
+        if !done1 {
+            uv1 -= offset * search_step(i);
+            end1 = luma_at(uv1) - local_average;
+            done1 = abs(end1) >= gradient_scaled;
+        }
+        if !done2 {
+            uv2 += offset * search_step(i);
+            end2 = luma_at(uv2) - local_average;
+            done2 = abs(end2) >= gradient_scaled;
+        }
+    }
+
+    let distance1 = select(uv.y - uv1.y, uv.x - uv1.x, is_horizontal);
+    let distance2 = select(uv2.y - uv.y, uv2.x - uv.x, is_horizontal);
+    let nearer_is_1 = distance1 < distance2;
+    let nearest = min(distance1, distance2);
+    let edge_length = distance1 + distance2;
+    var pixel_offset = 0.5 - nearest / edge_length;
+    // Only blend when the walk ended where the edge's luma turned the expected way.
+    let center_smaller = l < local_average;
+    let correct_variation = select((end2 < 0.0) != center_smaller, (end1 < 0.0) != center_smaller, nearer_is_1);
+    if !correct_variation {
+        pixel_offset = 0.0;
+    }
+
+    // Sub-pixel aliasing: single-pixel features get blended by their contrast to the
+    // 3×3 average instead.
+    let average = (2.0 * (n + s + e + w) + nw + ne + sw + se) / 12.0;
+    let subpixel1 = clamp(abs(average - l) / range, 0.0, 1.0);
+    let subpixel2 = (-2.0 * subpixel1 + 3.0) * subpixel1 * subpixel1;
+    let subpixel_offset = subpixel2 * subpixel2 * SUBPIXEL_QUALITY;
+    let final_offset = max(pixel_offset, subpixel_offset);
+
+    var final_uv = uv;
+    if is_horizontal {
+        final_uv.y += final_offset * step_length;
+    } else {
+        final_uv.x += final_offset * step_length;
+    }
+    return vec4<f32>(textureSampleLevel(ldr_texture, linear_sampler, final_uv, 0.0).rgb, center.a);
+}
@@ -0,0 +1,78 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// A GPU resource waiting for the frames that used it to finish.
+pub enum Garbage {
+    Buffer(wgpu::Buffer),
+    Texture(wgpu::Texture),
+}
+
+impl From<wgpu::Buffer> for Garbage {
+    fn from(buffer: wgpu::Buffer) -> Self {
+        Self::Buffer(buffer)
+    }
+}
+
+impl From<wgpu::Texture> for Garbage {
+    fn from(texture: wgpu::Texture) -> Self {
+        Self::Texture(texture)
+    }
+}
+
+impl Garbage {
+    fn destroy(self) {
+        match self {
+            Self::Buffer(buffer) => buffer.destroy(),
+            Self::Texture(texture) => texture.destroy(),
+        }
+    }
+}
+
+/// Deferred destruction keyed by frame: resources replaced while frames are in flight
+/// (resizes, environment swaps, unloads) are destroyed once the GPU has finished every
+/// frame submitted before the replacement, so their memory is released promptly without
+/// pulling it out from under a command buffer that still reads it.
+#[derive(Default)]
+pub struct GpuGarbage {
+    /// Frame currently being recorded; resources deferred now may be used by it.
+    frame: u64,
+    /// Highest frame whose submission the GPU has completed, plus one.
+    completed: Arc<AtomicU64>,
+    pending: VecDeque<(u64, Vec<Garbage>)>,
+}
+
+impl GpuGarbage {
+    pub fn defer(&mut self, resource: impl Into<Garbage>) {
+        match self.pending.back_mut() {
+            Some((frame, list)) if *frame == self.frame => list.push(resource.into()),
+            _ => self.pending.push_back((self.frame, vec![resource.into()])),
+        }
+    }
+
+    /// Call after the frame's command buffers were submitted.
+    pub fn submitted(&mut self, queue: &wgpu::Queue) {
+        let frame = self.frame;
+        let completed = self.completed.clone();
+        queue.on_submitted_work_done(move || {
+            completed.fetch_max(frame + 1, Ordering::AcqRel);
+        });
+        self.frame += 1;
+    }
+
+    /// Destroys everything whose frames have completed; returns how many resources went.
+    pub fn collect(&mut self, device: &wgpu::Device) -> usize {
+        if self.pending.is_empty() {
+            return 0;
+        }
+        device.poll(wgpu::Maintain::Poll);
+        let completed = self.completed.load(Ordering::Acquire);
+        let mut destroyed = 0;
+        while self.pending.front().is_some_and(|(frame, _)| *frame < completed) {
+            let (_, list) = self.pending.pop_front().unwrap();
+            destroyed += list.len();
+            list.into_iter().for_each(Garbage::destroy);
+        }
+        destroyed
+    }
+}
@@ -0,0 +1,212 @@
+/// Albedo, world normal, ORM (unused, roughness, metallic) and emissive, in the order of
+/// `fs_gbuffer`'s outputs.
+pub const GBUFFER_FORMATS: [wgpu::TextureFormat; 4] = [
+    wgpu::TextureFormat::Rgba8UnormSrgb,
+    wgpu::TextureFormat::Rgba16Float,
+    wgpu::TextureFormat::Rgba8Unorm,
+    wgpu::TextureFormat::Rgba16Float,
+];
+
+const LABELS: [&str; 4] = ["G-Buffer Albedo", "G-Buffer Normal", "G-Buffer ORM", "G-Buffer Emissive"];
+
+struct GBufferTargets {
+    textures: [wgpu::Texture; 4],
+    views: [wgpu::TextureView; 4],
+}
+
+impl GBufferTargets {
+    fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let textures = std::array::from_fn(|i| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(LABELS[i]),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: GBUFFER_FORMATS[i],
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+        });
+        let views = std::array::from_fn(|i: usize| textures[i].create_view(&wgpu::TextureViewDescriptor::default()));
+        Self { textures, views }
+    }
+}
+
+/// G-buffer for the deferred path: opaque meshes write their surfaces with the `GBUFFER`
+/// pipeline variants and `resolve` lights every pixel once into the HDR target.
+pub struct GBuffer {
+    targets: GBufferTargets,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl GBuffer {
+    /// `layouts` are the camera, light and EVSM layouts of the main pipelines, bound at
+    /// groups 0, 2 and 3 around the G-buffer's own group 1.
+    pub fn new(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        layouts: [&wgpu::BindGroupLayout; 3],
+        depth_view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let targets = GBufferTargets::new(device, width, height);
+        let texture = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type,
+            },
+            count: None,
+        };
+        let float = wgpu::TextureSampleType::Float { filterable: false };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("gbuffer_bind_group_layout"),
+            entries: &[
+                texture(5, float),
+                texture(6, float),
+                texture(7, float),
+                texture(8, float),
+                texture(9, wgpu::TextureSampleType::Depth),
+            ],
+        });
+        let bind_group = Self::create_bind_group(device, &layout, &targets, depth_view);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Deferred Resolve Pipeline Layout"),
+            bind_group_layouts: &[layouts[0], &layout, layouts[1], layouts[2]],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Deferred Resolve Pipeline"),
+            layout: Some(&pipeline_layout),
+            cache: None,
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_deferred",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_deferred",
+                targets: &[Some(crate::post::HDR_FORMAT.into())],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            targets,
+            layout,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        targets: &GBufferTargets,
+        depth_view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        let [albedo, normal, orm, emissive] = &targets.views;
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("gbuffer_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(albedo),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::TextureView(normal),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: wgpu::BindingResource::TextureView(orm),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: wgpu::BindingResource::TextureView(emissive),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 9,
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+            ],
+        })
+    }
+
+    /// Color attachments for the G-buffer pass, cleared.
+    pub fn color_attachments(&self) -> [Option<wgpu::RenderPassColorAttachment<'_>>; 4] {
+        self.targets.views.each_ref().map(|view| {
+            Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })
+        })
+    }
+
+    /// Reallocates the targets for a new size and rebinds the resized depth buffer,
+    /// returning the old textures so they can be destroyed once the GPU is done with them.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        depth_view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+    ) -> [wgpu::Texture; 4] {
+        let old = std::mem::replace(&mut self.targets, GBufferTargets::new(device, width, height));
+        self.bind_group = Self::create_bind_group(device, &self.layout, &self.targets, depth_view);
+        old.textures
+    }
+
+    /// Lights the G-buffer into `hdr_view`, clearing it first. `bind_groups` are the
+    /// camera, light and EVSM groups of the forward pass. Background pixels are left for
+    /// the sky.
+    pub fn resolve(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        hdr_view: &wgpu::TextureView,
+        bind_groups: [&wgpu::BindGroup; 3],
+    ) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Deferred Resolve"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: hdr_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, bind_groups[0], &[]);
+        pass.set_bind_group(1, &self.bind_group, &[]);
+        pass.set_bind_group(2, bind_groups[1], &[]);
+        pass.set_bind_group(3, bind_groups[2], &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,308 @@
+use cgmath::{InnerSpace, Vector3};
+use std::collections::HashMap;
+
+use crate::model::Vertex;
+
+pub fn normals_are_broken(vertices: &[Vertex]) -> bool {
+    vertices.iter().any(|v| {
+        let n = Vector3::from(v.normal);
+        !(n.x.is_finite() && n.y.is_finite() && n.z.is_finite()) || n.magnitude2() < 1e-8
+    })
+}
+
+pub fn tangents_are_broken(tangents: &[[f32; 4]], vertex_count: usize) -> bool {
+    tangents.len() != vertex_count
+        || tangents.iter().any(|t| {
+            let v = Vector3::new(t[0], t[1], t[2]);
+            !t.iter().all(|c| c.is_finite()) || v.magnitude2() < 1e-8
+        })
+}
+
+fn face_normal(vertices: &[Vertex], tri: &[u32]) -> Vector3<f32> {
+    let p0 = Vector3::from(vertices[tri[0] as usize].position);
+    let p1 = Vector3::from(vertices[tri[1] as usize].position);
+    let p2 = Vector3::from(vertices[tri[2] as usize].position);
+    (p1 - p0).cross(p2 - p0)
+}
+
+pub fn position_key(p: [f32; 3]) -> [u32; 3] {
+    [p[0].to_bits(), p[1].to_bits(), p[2].to_bits()]
+}
+
+/// Recomputes area-weighted vertex normals. Faces meeting at a shared position are only
+/// averaged together when the angle between them is below `angle_deg`, so hard edges stay
+/// hard. Vertices are split where needed; the returned vector maps every output vertex to
+/// the input vertex it was copied from.
+pub fn smooth_normals(vertices: &mut Vec<Vertex>, indices: &mut [u32], angle_deg: f32) -> Vec<u32> {
+    if indices.iter().any(|&i| i as usize >= vertices.len()) {
+        return (0..vertices.len() as u32).collect();
+    }
+    let cos_limit = angle_deg.to_radians().cos();
+    let tri_count = indices.len() / 3;
+
+    let face_normals: Vec<Vector3<f32>> = indices
+        .chunks_exact(3)
+        .map(|tri| face_normal(vertices, tri))
+        .collect();
+
+    let mut faces_at: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+    for (f, tri) in indices.chunks_exact(3).enumerate() {
+        for &i in tri {
+            faces_at.entry(position_key(vertices[i as usize].position)).or_default().push(f);
+        }
+    }
+
+    let mut out: Vec<Vertex> = Vec::with_capacity(vertices.len());
+    let mut remap: Vec<u32> = Vec::with_capacity(vertices.len());
+    let mut dedup: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+
+    for f in 0..tri_count {
+        let n_face = face_normals[f];
+        let n_face_dir = if n_face.magnitude2() > 0.0 { n_face.normalize() } else { n_face };
+        for corner in 0..3 {
+            let src = indices[f * 3 + corner];
+            let v = vertices[src as usize];
+            let mut sum = Vector3::new(0.0f32, 0.0, 0.0);
+            if let Some(neighbours) = faces_at.get(&position_key(v.position)) {
+                for &g in neighbours {
+                    let n = face_normals[g];
+                    if n.magnitude2() <= 0.0 {
+                        continue;
+                    }
+                    if g == f || n.normalize().dot(n_face_dir) >= cos_limit {
+                        sum += n;
+                    }
+                }
+            }
+            let normal = if sum.magnitude2() > 1e-20 {
+                sum.normalize()
+            } else if n_face.magnitude2() > 0.0 {
+                n_face_dir
+            } else {
+                Vector3::unit_y()
+            };
+
+            let key = (src, position_key(normal.into()));
+            let index = *dedup.entry(key).or_insert_with(|| {
+                out.push(Vertex {
+                    normal: normal.into(),
+                    ..v
+                });
+                remap.push(src);
+                (out.len() - 1) as u32
+            });
+            indices[f * 3 + corner] = index;
+        }
+    }
+
+    *vertices = out;
+    remap
+}
+
+struct MikkGeometry<'a> {
+    vertices: &'a [Vertex],
+    indices: &'a [u32],
+    tangents: Vec<[f32; 4]>,
+}
+
+impl bevy_mikktspace::Geometry for MikkGeometry<'_> {
+    fn num_faces(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertices[self.indices[face * 3 + vert] as usize].position
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertices[self.indices[face * 3 + vert] as usize].normal
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.vertices[self.indices[face * 3 + vert] as usize].tex_coords
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        let i = self.indices[face * 3 + vert] as usize;
+        self.tangents[i] = tangent;
+    }
+}
+
+/// MikkTSpace tangents (xyz) with the bitangent sign in w. Falls back to per-triangle UV
+/// gradients when MikkTSpace rejects the mesh.
+pub fn generate_tangents(vertices: &[Vertex], indices: &[u32]) -> Vec<[f32; 4]> {
+    if indices.iter().all(|&i| (i as usize) < vertices.len()) && !indices.is_empty() {
+        let mut geometry = MikkGeometry {
+            vertices,
+            indices,
+            tangents: vec![[0.0; 4]; vertices.len()],
+        };
+        if bevy_mikktspace::generate_tangents(&mut geometry) && !tangents_are_broken(&geometry.tangents, vertices.len()) {
+            return geometry.tangents;
+        }
+    }
+    gradient_tangents(vertices, indices)
+}
+
+fn gradient_tangents(vertices: &[Vertex], indices: &[u32]) -> Vec<[f32; 4]> {
+    let mut tan = vec![Vector3::new(0.0f32, 0.0, 0.0); vertices.len()];
+    let mut bitan = vec![Vector3::new(0.0f32, 0.0, 0.0); vertices.len()];
+
+    for tri in indices.chunks_exact(3) {
+        let [i0, i1, i2] = [tri[0] as usize, tri[1] as usize, tri[2] as usize];
+        if i0 >= vertices.len() || i1 >= vertices.len() || i2 >= vertices.len() {
+            continue;
+        }
+        let (v0, v1, v2) = (&vertices[i0], &vertices[i1], &vertices[i2]);
+        let e1 = Vector3::from(v1.position) - Vector3::from(v0.position);
+        let e2 = Vector3::from(v2.position) - Vector3::from(v0.position);
+        let du1 = v1.tex_coords[0] - v0.tex_coords[0];
+        let dv1 = v1.tex_coords[1] - v0.tex_coords[1];
+        let du2 = v2.tex_coords[0] - v0.tex_coords[0];
+        let dv2 = v2.tex_coords[1] - v0.tex_coords[1];
+        let det = du1 * dv2 - du2 * dv1;
+        if det.abs() < 1e-12 || !det.is_finite() {
+            continue;
+        }
+        let r = 1.0 / det;
+        let t = (e1 * dv2 - e2 * dv1) * r;
+        let b = (e2 * du1 - e1 * du2) * r;
+        for i in [i0, i1, i2] {
+            tan[i] += t;
+            bitan[i] += b;
+        }
+    }
+
+    vertices
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let n = Vector3::from(v.normal);
+            let t = tan[i] - n * n.dot(tan[i]);
+            let t = if t.magnitude2() > 1e-12 {
+                t.normalize()
+            } else {
+                let helper = if n.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+                n.cross(helper).normalize()
+            };
+            let w = if n.cross(t).dot(bitan[i]) < 0.0 { -1.0 } else { 1.0 };
+            [t.x, t.y, t.z, w]
+        })
+        .collect()
+}
+
+/// Vertex-clustering decimation for shadow proxies: vertices are snapped to a uniform grid
+/// sized so that the result has roughly `target_triangles`, triangles that collapse are
+/// dropped. Only positions are meaningful in the output.
+pub fn simplify_clustered(vertices: &[Vertex], indices: &[u32], target_triangles: usize) -> (Vec<Vertex>, Vec<u32>) {
+    cluster_grid(vertices, indices, target_triangles, false)
+}
+
+/// Like `simplify_clustered`, but for meshes that are shaded: vertices facing different
+/// ways stay in separate clusters so hard edges survive, and normals and UVs are averaged
+/// per cluster.
+pub fn simplify_lod(vertices: &[Vertex], indices: &[u32], target_triangles: usize) -> (Vec<Vertex>, Vec<u32>) {
+    cluster_grid(vertices, indices, target_triangles, true)
+}
+
+/// The axis and sign a normal points along most, 0-5.
+fn normal_bucket(n: [f32; 3]) -> i32 {
+    let axis = (0..3).max_by(|&a, &b| n[a].abs().total_cmp(&n[b].abs())).unwrap_or(0);
+    axis as i32 * 2 + (n[axis] < 0.0) as i32
+}
+
+fn cluster_grid(
+    vertices: &[Vertex],
+    indices: &[u32],
+    target_triangles: usize,
+    split_by_normal: bool,
+) -> (Vec<Vertex>, Vec<u32>) {
+    let mut min = Vector3::new(f32::MAX, f32::MAX, f32::MAX);
+    let mut max = Vector3::new(f32::MIN, f32::MIN, f32::MIN);
+    for v in vertices {
+        for c in 0..3 {
+            min[c] = min[c].min(v.position[c]);
+            max[c] = max[c].max(v.position[c]);
+        }
+    }
+    let extent = (max - min).x.max((max - min).y).max((max - min).z);
+    if vertices.is_empty() || !extent.is_finite() || extent <= 0.0 {
+        return (vertices.to_vec(), indices.to_vec());
+    }
+    let resolution = (target_triangles.max(2) as f32 / 2.0).sqrt().max(1.0);
+    let cell = extent / resolution;
+
+    struct Sum {
+        position: Vector3<f32>,
+        normal: Vector3<f32>,
+        tex_coords: [f32; 2],
+        count: f32,
+    }
+    let mut clusters: HashMap<[i32; 4], u32> = HashMap::new();
+    let mut sums: Vec<Sum> = Vec::new();
+    let cluster_of: Vec<u32> = vertices
+        .iter()
+        .map(|v| {
+            let p = Vector3::from(v.position);
+            let key = [
+                ((p.x - min.x) / cell).floor() as i32,
+                ((p.y - min.y) / cell).floor() as i32,
+                ((p.z - min.z) / cell).floor() as i32,
+                if split_by_normal { normal_bucket(v.normal) } else { 0 },
+            ];
+            let id = *clusters.entry(key).or_insert_with(|| {
+                sums.push(Sum {
+                    position: Vector3::new(0.0, 0.0, 0.0),
+                    normal: Vector3::new(0.0, 0.0, 0.0),
+                    tex_coords: [0.0, 0.0],
+                    count: 0.0,
+                });
+                (sums.len() - 1) as u32
+            });
+            let sum = &mut sums[id as usize];
+            sum.position += p;
+            sum.normal += Vector3::from(v.normal);
+            sum.tex_coords[0] += v.tex_coords[0];
+            sum.tex_coords[1] += v.tex_coords[1];
+            sum.count += 1.0;
+            id
+        })
+        .collect();
+
+    let mut seen: std::collections::HashSet<[u32; 3]> = std::collections::HashSet::new();
+    let mut out_indices = Vec::new();
+    for tri in indices.chunks_exact(3) {
+        let Some(t) = tri
+            .iter()
+            .map(|&i| cluster_of.get(i as usize).copied())
+            .collect::<Option<Vec<u32>>>()
+        else {
+            continue;
+        };
+        if t[0] == t[1] || t[1] == t[2] || t[0] == t[2] {
+            continue;
+        }
+        let mut key = [t[0], t[1], t[2]];
+        key.sort_unstable();
+        if seen.insert(key) {
+            out_indices.extend_from_slice(&t);
+        }
+    }
+
+    let out_vertices = sums
+        .iter()
+        .map(|sum| {
+            let normal = if sum.normal.magnitude2() > 1e-12 { sum.normal.normalize() } else { Vector3::unit_y() };
+            Vertex {
+                position: (sum.position / sum.count).into(),
+                normal: normal.into(),
+                tex_coords: sum.tex_coords.map(|t| t / sum.count),
+            }
+        })
+        .collect();
+    (out_vertices, out_indices)
+}
@@ -0,0 +1,294 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use wgpu::util::DeviceExt;
+
+use crate::model::Vertex;
+use crate::vertex_packing::VertexPacking;
+
+/// Vertices per pool block, 32 MiB of them.
+const BLOCK_VERTICES: usize = 1 << 20;
+/// Indices per pool block, 32 MiB of them.
+const BLOCK_INDICES: usize = 1 << 23;
+
+/// Buffers of a mesh kept out of the pool.
+pub struct OwnBuffers {
+    pub vertices: wgpu::Buffer,
+    pub tangents: Option<wgpu::Buffer>,
+    indices: wgpu::Buffer,
+    index_count: u32,
+}
+
+/// Where a mesh's vertices and indices live. Clones draw the same buffers, for meshes
+/// that differ only in their object.
+#[derive(Clone)]
+pub enum MeshGeometry {
+    /// Buffers of its own, for meshes rewritten every frame or with per-vertex streams the
+    /// pool does not carry.
+    Own(Arc<OwnBuffers>),
+    /// A range of a `GeometryPool` block, drawn with a base vertex.
+    Pooled {
+        block: usize,
+        base_vertex: i32,
+        indices: Range<u32>,
+    },
+}
+
+impl MeshGeometry {
+    /// Uploads the mesh into buffers of its own; `usage` is added to the vertex and
+    /// tangent buffers.
+    pub fn own(
+        device: &wgpu::Device,
+        packing: VertexPacking,
+        (vertices, indices): (&[Vertex], &[u32]),
+        tangents: Option<&[[f32; 4]]>,
+        usage: wgpu::BufferUsages,
+    ) -> Self {
+        let vertices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Vertex Buffer"),
+            contents: &packing.encode(vertices),
+            usage: wgpu::BufferUsages::VERTEX | usage,
+        });
+        let tangents = tangents.map(|tangents| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Tangent Buffer"),
+                contents: bytemuck::cast_slice(tangents),
+                usage: wgpu::BufferUsages::VERTEX | usage,
+            })
+        });
+        let index_count = indices.len() as u32;
+        let indices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Index Buffer"),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        Self::Own(Arc::new(OwnBuffers {
+            vertices,
+            tangents,
+            indices,
+            index_count,
+        }))
+    }
+
+    /// GPU memory of the mesh's own buffers; pooled geometry counts towards the pool.
+    pub fn buffer_bytes(&self) -> u64 {
+        match self {
+            Self::Own(own) => {
+                own.vertices.size() + own.indices.size() + own.tangents.as_ref().map_or(0, wgpu::Buffer::size)
+            }
+            Self::Pooled { .. } => 0,
+        }
+    }
+}
+
+#[derive(Default)]
+struct BlockData {
+    vertices: Vec<Vertex>,
+    /// One per vertex; zero for meshes without normal maps.
+    tangents: Vec<[f32; 4]>,
+    indices: Vec<u32>,
+}
+
+/// Gathers static geometry into blocks while the scene loads.
+pub struct GeometryPoolBuilder {
+    packing: VertexPacking,
+    blocks: Vec<BlockData>,
+}
+
+impl GeometryPoolBuilder {
+    pub fn new(packing: VertexPacking) -> Self {
+        Self {
+            packing,
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Appends the mesh to the last block, or a new one when it would overflow, and
+    /// returns where it will be once built.
+    pub fn add(&mut self, (vertices, indices): (&[Vertex], &[u32]), tangents: Option<&[[f32; 4]]>) -> MeshGeometry {
+        let fits = self.blocks.last().is_some_and(|b| {
+            b.vertices.len() + vertices.len() <= BLOCK_VERTICES && b.indices.len() + indices.len() <= BLOCK_INDICES
+        });
+        if !fits {
+            self.blocks.push(BlockData::default());
+        }
+        let block = self.blocks.last_mut().unwrap();
+        let base_vertex = block.vertices.len() as i32;
+        let first_index = block.indices.len() as u32;
+        block.vertices.extend_from_slice(vertices);
+        match tangents {
+            Some(tangents) => block.tangents.extend_from_slice(tangents),
+            None => block.tangents.resize(block.vertices.len(), [0.0; 4]),
+        }
+        block.indices.extend_from_slice(indices);
+        MeshGeometry::Pooled {
+            block: self.blocks.len() - 1,
+            base_vertex,
+            indices: first_index..first_index + indices.len() as u32,
+        }
+    }
+
+    pub fn build(self, device: &wgpu::Device) -> GeometryPool {
+        let blocks = self
+            .blocks
+            .iter()
+            .map(|data| PoolBlock {
+                vertices: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Geometry Pool Vertex Buffer"),
+                    contents: &self.packing.encode(&data.vertices),
+                    usage: wgpu::BufferUsages::VERTEX,
+                }),
+                tangents: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Geometry Pool Tangent Buffer"),
+                    contents: bytemuck::cast_slice(&data.tangents),
+                    usage: wgpu::BufferUsages::VERTEX,
+                }),
+                indices: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Geometry Pool Index Buffer"),
+                    contents: bytemuck::cast_slice(&data.indices),
+                    usage: wgpu::BufferUsages::INDEX,
+                }),
+            })
+            .collect();
+        GeometryPool { blocks }
+    }
+}
+
+struct PoolBlock {
+    vertices: wgpu::Buffer,
+    tangents: wgpu::Buffer,
+    indices: wgpu::Buffer,
+}
+
+/// Static geometry of the whole scene in a few large vertex, tangent and index buffers,
+/// so consecutive draws only rebind when they cross into another block.
+pub struct GeometryPool {
+    blocks: Vec<PoolBlock>,
+}
+
+impl GeometryPool {
+    pub fn block_count(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn buffer_bytes(&self) -> u64 {
+        self.blocks
+            .iter()
+            .map(|b| b.vertices.size() + b.tangents.size() + b.indices.size())
+            .sum()
+    }
+}
+
+/// Binds mesh geometry for the draws of one pass, skipping the pool buffers already
+/// bound. Anything else bound to vertex slots 0 and 1 must go through `reset`.
+pub struct GeometryBinder<'a> {
+    pool: &'a GeometryPool,
+    vertices: Option<usize>,
+    tangents: Option<usize>,
+    indices: Option<usize>,
+}
+
+impl<'a> GeometryBinder<'a> {
+    pub fn new(pool: &'a GeometryPool) -> Self {
+        Self {
+            pool,
+            vertices: None,
+            tangents: None,
+            indices: None,
+        }
+    }
+
+    /// Forgets what is bound, after other buffers were set on the pass.
+    pub fn reset(&mut self) {
+        (self.vertices, self.tangents, self.indices) = (None, None, None);
+    }
+
+    /// Binds `geometry`'s vertices at slot 0, its tangents at slot 1 when `tangents`, and
+    /// its indices. Returns the index range and base vertex to draw it with.
+    pub fn bind(
+        &mut self,
+        pass: &mut wgpu::RenderPass<'_>,
+        geometry: &MeshGeometry,
+        tangents: bool,
+    ) -> (Range<u32>, i32) {
+        self.bind_to(pass, geometry, tangents)
+    }
+
+    /// [`GeometryBinder::bind`] for a render bundle, which holds on to the buffers.
+    pub fn bind_bundle(
+        &mut self,
+        bundle: &mut wgpu::RenderBundleEncoder<'a>,
+        geometry: &'a MeshGeometry,
+        tangents: bool,
+    ) -> (Range<u32>, i32) {
+        self.bind_to(bundle, geometry, tangents)
+    }
+
+    fn bind_to<'g>(
+        &mut self,
+        pass: &mut impl BufferTarget<'g>,
+        geometry: &'g MeshGeometry,
+        tangents: bool,
+    ) -> (Range<u32>, i32)
+    where
+        'a: 'g,
+    {
+        match geometry {
+            MeshGeometry::Own(own) => {
+                self.reset();
+                pass.vertex_buffer(0, own.vertices.slice(..));
+                if let Some(buffer) = own.tangents.as_ref().filter(|_| tangents) {
+                    pass.vertex_buffer(1, buffer.slice(..));
+                }
+                pass.index_buffer(own.indices.slice(..), wgpu::IndexFormat::Uint32);
+                (0..own.index_count, 0)
+            }
+            MeshGeometry::Pooled {
+                block,
+                base_vertex,
+                indices,
+            } => {
+                let buffers = &self.pool.blocks[*block];
+                if self.vertices != Some(*block) {
+                    pass.vertex_buffer(0, buffers.vertices.slice(..));
+                    self.vertices = Some(*block);
+                }
+                if tangents && self.tangents != Some(*block) {
+                    pass.vertex_buffer(1, buffers.tangents.slice(..));
+                    self.tangents = Some(*block);
+                }
+                if self.indices != Some(*block) {
+                    pass.index_buffer(buffers.indices.slice(..), wgpu::IndexFormat::Uint32);
+                    self.indices = Some(*block);
+                }
+                (indices.clone(), *base_vertex)
+            }
+        }
+    }
+}
+
+/// Somewhere to bind vertex and index buffers: a render pass, or a bundle recorded for one.
+trait BufferTarget<'a> {
+    fn vertex_buffer(&mut self, slot: u32, slice: wgpu::BufferSlice<'a>);
+    fn index_buffer(&mut self, slice: wgpu::BufferSlice<'a>, format: wgpu::IndexFormat);
+}
+
+impl<'a> BufferTarget<'a> for wgpu::RenderPass<'_> {
+    fn vertex_buffer(&mut self, slot: u32, slice: wgpu::BufferSlice<'a>) {
+        self.set_vertex_buffer(slot, slice);
+    }
+
+    fn index_buffer(&mut self, slice: wgpu::BufferSlice<'a>, format: wgpu::IndexFormat) {
+        self.set_index_buffer(slice, format);
+    }
+}
+
+impl<'a> BufferTarget<'a> for wgpu::RenderBundleEncoder<'a> {
+    fn vertex_buffer(&mut self, slot: u32, slice: wgpu::BufferSlice<'a>) {
+        self.set_vertex_buffer(slot, slice);
+    }
+
+    fn index_buffer(&mut self, slice: wgpu::BufferSlice<'a>, format: wgpu::IndexFormat) {
+        self.set_index_buffer(slice, format);
+    }
+}
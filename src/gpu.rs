@@ -0,0 +1,146 @@
+//! Opening a GPU device, with fallbacks when the preferred adapter is missing or refuses,
+//! and noticing when the device is lost.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::bindless::BindlessMaterials;
+use crate::gpu_culling::GpuCulling;
+use crate::gpu_timer::GpuTimer;
+
+/// Adapters to try in turn: power preference, whether to force the fallback (software)
+/// adapter, and a name for the log.
+const ADAPTER_CHOICES: [(wgpu::PowerPreference, bool, &str); 3] = [
+    (wgpu::PowerPreference::HighPerformance, false, "high-performance"),
+    (wgpu::PowerPreference::LowPower, false, "low-power"),
+    (wgpu::PowerPreference::None, true, "fallback"),
+];
+
+/// An instance with a device opened on one of its adapters.
+pub(crate) type Gpu = (wgpu::Instance, wgpu::Adapter, wgpu::Device, wgpu::Queue);
+
+pub(crate) fn device_descriptor(adapter: &wgpu::Adapter) -> wgpu::DeviceDescriptor<'static> {
+    let features = adapter.features();
+    let limits = adapter.limits();
+    wgpu::DeviceDescriptor {
+        // Timestamps only feed the statistics overlay; run without them where missing.
+        required_features: features & (GpuTimer::FEATURES | GpuCulling::FEATURES | BindlessMaterials::FEATURES),
+        // The scene shaders bind more textures than the downlevel default of 16,
+        // and bindless materials as many as the adapter allows.
+        required_limits: wgpu::Limits {
+            max_sampled_textures_per_shader_stage: if features.contains(BindlessMaterials::FEATURES) {
+                limits.max_sampled_textures_per_shader_stage
+            } else {
+                limits.max_sampled_textures_per_shader_stage.min(32)
+            },
+            ..wgpu::Limits::default()
+        },
+        memory_hints: Default::default(),
+        label: None,
+    }
+}
+
+/// Every adapter on `backends`, in the order `--gpu=<index>` counts them.
+pub fn list_adapters(backends: wgpu::Backends) -> Vec<wgpu::AdapterInfo> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends,
+        ..Default::default()
+    });
+    instance.enumerate_adapters(backends).iter().map(wgpu::Adapter::get_info).collect()
+}
+
+/// The adapter `choice` names: an index into [`list_adapters`], or else a case-insensitive
+/// part of the adapter's name.
+fn pick_adapter(instance: &wgpu::Instance, backends: wgpu::Backends, choice: &str) -> Option<wgpu::Adapter> {
+    let adapters = instance.enumerate_adapters(backends);
+    match choice.parse::<usize>() {
+        Ok(index) => adapters.into_iter().nth(index),
+        Err(_) => {
+            let choice = choice.to_lowercase();
+            adapters.into_iter().find(|a| a.get_info().name.to_lowercase().contains(&choice))
+        }
+    }
+}
+
+/// A device on the adapter `gpu` picks, when given, or else on the first adapter from
+/// [`ADAPTER_CHOICES`] that can present to `surface` and opens one.
+pub async fn request_device(
+    instance: &wgpu::Instance,
+    backends: wgpu::Backends,
+    surface: &wgpu::Surface<'_>,
+    gpu: Option<&str>,
+) -> Result<(wgpu::Adapter, wgpu::Device, wgpu::Queue)> {
+    let mut failures = Vec::new();
+    let mut tried: Vec<wgpu::AdapterInfo> = Vec::new();
+    if let Some(choice) = gpu {
+        match pick_adapter(instance, backends, choice) {
+            None => failures.push(format!("no adapter matches --gpu={}", choice)),
+            Some(adapter) if !adapter.is_surface_supported(surface) => {
+                failures.push(format!("{} cannot present to the window", adapter.get_info().name))
+            }
+            Some(adapter) => {
+                let info = adapter.get_info();
+                match adapter.request_device(&device_descriptor(&adapter), None).await {
+                    Ok((device, queue)) => {
+                        log::info!("Using {} ({:?}) as --gpu={} asks", info.name, info.backend, choice);
+                        return Ok((adapter, device, queue));
+                    }
+                    Err(e) => failures.push(format!("{} refused a device: {}", info.name, e)),
+                }
+                tried.push(info);
+            }
+        }
+        log::warn!("Ignoring --gpu={} ({}); `--list-gpus` lists the adapters", choice, failures.join("; "));
+    }
+    for (power_preference, force_fallback_adapter, name) in ADAPTER_CHOICES {
+        let options = wgpu::RequestAdapterOptions {
+            power_preference,
+            compatible_surface: Some(surface),
+            force_fallback_adapter,
+        };
+        let Some(adapter) = instance.request_adapter(&options).await else {
+            failures.push(format!("no {} adapter", name));
+            continue;
+        };
+        let info = adapter.get_info();
+        if tried.contains(&info) {
+            continue;
+        }
+        match adapter.request_device(&device_descriptor(&adapter), None).await {
+            Ok((device, queue)) => {
+                if !failures.is_empty() {
+                    log::warn!("Using the {} adapter {} ({})", name, info.name, failures.join("; "));
+                }
+                if info.device_type == wgpu::DeviceType::Cpu {
+                    log::warn!("{} renders on the CPU; expect low frame rates", info.name);
+                }
+                return Ok((adapter, device, queue));
+            }
+            Err(e) => failures.push(format!("{} refused a device: {}", info.name, e)),
+        }
+        tried.push(info);
+    }
+    anyhow::bail!("No usable GPU adapter: {}", failures.join("; "))
+}
+
+/// Sets `lost` when the driver loses the device. Errors raised by work on a lost device
+/// are only logged; any other uncaptured error panics, as by default.
+pub fn watch_device(device: &wgpu::Device, lost: Arc<AtomicBool>) {
+    let flag = lost.clone();
+    device.set_device_lost_callback(move |reason, message| {
+        // Dropping or destroying the device on purpose reports a loss too.
+        if matches!(reason, wgpu::DeviceLostReason::Unknown | wgpu::DeviceLostReason::DeviceInvalid) {
+            log::error!("GPU device lost ({:?}): {}", reason, message);
+            flag.store(true, Ordering::Relaxed);
+        }
+    });
+    device.on_uncaptured_error(Box::new(move |error| {
+        if lost.load(Ordering::Relaxed) {
+            log::debug!("GPU error after the device was lost: {}", error);
+            return;
+        }
+        panic!("wgpu error: {}", error);
+    }));
+}
@@ -0,0 +1,323 @@
+use wgpu::util::DeviceExt;
+
+use crate::aabb::Aabb;
+use crate::camera::frustum_planes;
+use crate::model::Vertex;
+use crate::vertex_packing::VertexPacking;
+
+/// Size of one `DrawIndexedIndirectArgs`.
+const COMMAND_SIZE: u64 = 20;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CullMesh {
+    min: [f32; 4],
+    max: [f32; 4],
+    /// Index count, first index, base vertex and group.
+    draw: [u32; 4],
+    /// x: first command of the group, y: object index.
+    group: [u32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CullParams {
+    planes: [[f32; 4]; 6],
+    /// x: mesh count.
+    counts: [u32; 4],
+}
+
+struct PendingMesh {
+    scene_mesh: usize,
+    material_index: usize,
+    first_index: u32,
+    index_count: u32,
+    base_vertex: u32,
+    bounds: Aabb,
+}
+
+/// Gathers static meshes into shared vertex and index buffers while the scene loads.
+pub struct GpuSceneBuilder {
+    packing: VertexPacking,
+    vertices: Vec<Vertex>,
+    /// One per vertex; zero for meshes without normal maps.
+    tangents: Vec<[f32; 4]>,
+    indices: Vec<u32>,
+    meshes: Vec<PendingMesh>,
+}
+
+impl GpuSceneBuilder {
+    pub fn new(packing: VertexPacking) -> Self {
+        Self {
+            packing,
+            vertices: Vec::new(),
+            tangents: Vec::new(),
+            indices: Vec::new(),
+            meshes: Vec::new(),
+        }
+    }
+
+    /// Adds the geometry of `meshes[scene_mesh]`. `tangents`, if any, has one per vertex.
+    pub fn add(
+        &mut self,
+        scene_mesh: usize,
+        material_index: usize,
+        (vertices, indices): (&[Vertex], &[u32]),
+        tangents: Option<&[[f32; 4]]>,
+        bounds: Aabb,
+    ) {
+        self.meshes.push(PendingMesh {
+            scene_mesh,
+            material_index,
+            first_index: self.indices.len() as u32,
+            index_count: indices.len() as u32,
+            base_vertex: self.vertices.len() as u32,
+            bounds,
+        });
+        self.vertices.extend_from_slice(vertices);
+        match tangents {
+            Some(tangents) => self.tangents.extend_from_slice(tangents),
+            None => self.tangents.resize(self.vertices.len(), [0.0; 4]),
+        }
+        self.indices.extend_from_slice(indices);
+    }
+
+    /// Uploads the meshes `keep` accepts, grouped by material. None when no mesh is left.
+    /// `group_of` may map several materials to one group, drawn with the pipeline of the
+    /// material it returns, when the shaders index materials by object.
+    pub fn build(
+        mut self,
+        device: &wgpu::Device,
+        keep: impl Fn(usize) -> bool,
+        group_of: impl Fn(usize) -> usize,
+    ) -> Option<GpuCulling> {
+        self.meshes.retain(|m| keep(m.scene_mesh));
+        if self.meshes.is_empty() {
+            return None;
+        }
+        self.meshes.sort_by_key(|m| (group_of(m.material_index), m.material_index));
+
+        let mut groups: Vec<DrawGroup> = Vec::new();
+        let mut cull_meshes = Vec::with_capacity(self.meshes.len());
+        for (i, mesh) in self.meshes.iter().enumerate() {
+            let group_material = group_of(mesh.material_index);
+            if groups.last().is_none_or(|g| g.material_index != group_material) {
+                groups.push(DrawGroup {
+                    material_index: group_material,
+                    index: groups.len() as u32,
+                    first_command: i as u32,
+                    capacity: 0,
+                });
+            }
+            let group = groups.last_mut().unwrap();
+            group.capacity += 1;
+            let (min, max) = (mesh.bounds.min, mesh.bounds.max);
+            cull_meshes.push(CullMesh {
+                min: [min.x, min.y, min.z, 0.0],
+                max: [max.x, max.y, max.z, 0.0],
+                draw: [mesh.index_count, mesh.first_index, mesh.base_vertex, group.index],
+                group: [group.first_command, mesh.scene_mesh as u32, 0, 0],
+            });
+        }
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("GPU Culling Vertex Buffer"),
+            contents: &self.packing.encode(&self.vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let tangent_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("GPU Culling Tangent Buffer"),
+            contents: bytemuck::cast_slice(&self.tangents),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("GPU Culling Index Buffer"),
+            contents: bytemuck::cast_slice(&self.indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        let mesh_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("GPU Culling Mesh Buffer"),
+            contents: bytemuck::cast_slice(&cull_meshes),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let command_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU Culling Commands"),
+            size: COMMAND_SIZE * cull_meshes.len() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT,
+            mapped_at_creation: false,
+        });
+        let count_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU Culling Counts"),
+            size: 4 * groups.len() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU Culling Params Buffer"),
+            size: std::mem::size_of::<CullParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("gpu_culling_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, false),
+                storage(3, false),
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("gpu_culling_bind_group"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: mesh_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: command_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: count_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("GPU Culling Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("gpu_culling.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("GPU Culling Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("GPU Culling Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "cs_cull",
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Some(GpuCulling {
+            vertex_buffer,
+            tangent_buffer,
+            index_buffer,
+            command_buffer,
+            count_buffer,
+            params_buffer,
+            bind_group,
+            pipeline,
+            groups,
+            scene_meshes: self.meshes.iter().map(|m| m.scene_mesh).collect(),
+        })
+    }
+}
+
+/// Meshes sharing a material, or with bindless materials a pipeline, drawn with one
+/// multi-draw.
+pub struct DrawGroup {
+    /// The material, or one of those sharing the group's pipeline.
+    pub material_index: usize,
+    /// Slot in the count buffer.
+    index: u32,
+    first_command: u32,
+    capacity: u32,
+}
+
+/// Frustum culling of the static meshes on the GPU. A compute pass writes compacted
+/// indirect draws per material group, which the camera passes submit with
+/// `multi_draw_indexed_indirect_count`, so the CPU cost no longer grows with the mesh
+/// count.
+pub struct GpuCulling {
+    vertex_buffer: wgpu::Buffer,
+    tangent_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    command_buffer: wgpu::Buffer,
+    count_buffer: wgpu::Buffer,
+    params_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::ComputePipeline,
+    groups: Vec<DrawGroup>,
+    scene_meshes: Vec<usize>,
+}
+
+impl GpuCulling {
+    /// Features the device needs for the culling path; request them when the adapter has
+    /// them. Draws pass their object as the first instance.
+    pub const FEATURES: wgpu::Features = wgpu::Features::MULTI_DRAW_INDIRECT
+        .union(wgpu::Features::MULTI_DRAW_INDIRECT_COUNT)
+        .union(wgpu::Features::INDIRECT_FIRST_INSTANCE);
+
+    /// Indices into the scene's meshes of those drawn here rather than one by one.
+    pub fn scene_meshes(&self) -> &[usize] {
+        &self.scene_meshes
+    }
+
+    pub fn groups(&self) -> &[DrawGroup] {
+        &self.groups
+    }
+
+    /// Records the culling dispatch; must run before the passes that draw the groups.
+    pub fn cull(&self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, view_proj: [[f32; 4]; 4]) {
+        let mesh_count = self.scene_meshes.len() as u32;
+        let params = CullParams {
+            planes: frustum_planes(view_proj),
+            counts: [mesh_count, 0, 0, 0],
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+        encoder.clear_buffer(&self.count_buffer, 0, None);
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("GPU Culling Pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.dispatch_workgroups(mesh_count.div_ceil(64), 1, 1);
+    }
+
+    /// Draws the visible meshes of `group` with the pipeline and material already bound.
+    /// `tangents` binds the tangent stream at slot 1 for normal-mapped pipelines.
+    pub fn draw_group(&self, pass: &mut wgpu::RenderPass<'_>, group: &DrawGroup, tangents: bool) {
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        if tangents {
+            pass.set_vertex_buffer(1, self.tangent_buffer.slice(..));
+        }
+        pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        pass.multi_draw_indexed_indirect_count(
+            &self.command_buffer,
+            u64::from(group.first_command) * COMMAND_SIZE,
+            &self.count_buffer,
+            4 * u64::from(group.index),
+            group.capacity,
+        );
+    }
+}
@@ -0,0 +1,65 @@
+// GPU-driven culling: one thread per static mesh tests its bounds against the camera
+// frustum and appends an indexed draw for it to its group's slice of the command
+// buffer. Each group is then drawn with one multi-draw whose count is read from
+// `counts`.
+
+struct CullParams {
+    // Frustum planes, normals pointing inwards.
+    planes: array<vec4<f32>, 6>,
+    // x: mesh count.
+    counts: vec4<u32>,
+};
+
+struct CullMesh {
+    min: vec4<f32>,
+    max: vec4<f32>,
+    // x: index count, y: first index, z: base vertex (bits of an i32), w: group.
+    draw: vec4<u32>,
+    // x: first command of the group, y: object index, passed as the first instance.
+    group: vec4<u32>,
+};
+
+struct DrawIndexedCommand {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: CullParams;
+
+@group(0) @binding(1)
+var<storage, read> meshes: array<CullMesh>;
+
+@group(0) @binding(2)
+var<storage, read_write> commands: array<DrawIndexedCommand>;
+
+// Visible meshes per group; cleared before every dispatch.
+@group(0) @binding(3)
+var<storage, read_write> counts: array<atomic<u32>>;
+
+@compute @workgroup_size(64)
+fn cs_cull(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.counts.x {
+        return;
+    }
+    let mesh = meshes[id.x];
+    for (var p = 0u; p < 6u; p++) {
+        let plane = params.planes[p];
+        // The box corner furthest along the plane normal.
+        let corner = select(mesh.min.xyz, mesh.max.xyz, plane.xyz >= vec3<f32>(0.0));
+        if dot(plane.xyz, corner) + plane.w < 0.0 {
+            return;
+        }
+    }
+    let slot = atomicAdd(&counts[mesh.draw.w], 1u);
+    commands[mesh.group.x + slot] = DrawIndexedCommand(
+        mesh.draw.x,
+        1u,
+        mesh.draw.y,
+        bitcast<i32>(mesh.draw.z),
+        mesh.group.y,
+    );
+}
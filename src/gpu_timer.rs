@@ -0,0 +1,165 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+const MAP_PENDING: u8 = 0;
+const MAP_DONE: u8 = 1;
+const MAP_FAILED: u8 = 2;
+
+/// Parts of the frame timed on the GPU, in recording order. Each runs from the previous
+/// part's end to its own.
+pub const SECTIONS: [&str; 5] = ["SKY", "SHADOWS", "OPAQUE", "TRANSPARENT", "POST"];
+
+/// A timestamp where the frame starts and one at the end of each section.
+const QUERY_COUNT: u32 = SECTIONS.len() as u32 + 1;
+const BUFFER_SIZE: u64 = QUERY_COUNT as u64 * std::mem::size_of::<u64>() as u64;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum ReadbackState {
+    Idle,
+    /// Timestamps are being written by the frame being recorded.
+    Recording,
+    Copied,
+    Mapping,
+}
+
+struct Queries {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick.
+    period: f32,
+}
+
+impl Queries {
+    fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("GPU Timer Queries"),
+            ty: wgpu::QueryType::Timestamp,
+            count: QUERY_COUNT,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU Timer Resolve Buffer"),
+            size: BUFFER_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU Timer Readback Buffer"),
+            size: BUFFER_SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            query_set,
+            resolve_buffer,
+            readback,
+            period: queue.get_timestamp_period(),
+        }
+    }
+}
+
+/// GPU time per frame section from timestamps written between passes. Frames are timed
+/// while no earlier readback is in flight, so results lag a frame or two. Does nothing on
+/// devices without timestamp queries.
+pub struct GpuTimer {
+    /// None without [`GpuTimer::FEATURES`].
+    queries: Option<Queries>,
+    readback_state: ReadbackState,
+    map_status: Arc<AtomicU8>,
+    /// Timestamps written so far in the frame being recorded.
+    next_query: u32,
+    latest: Option<[f32; SECTIONS.len()]>,
+}
+
+impl GpuTimer {
+    /// Features the device needs for the timer; request them when the adapter has them.
+    pub const FEATURES: wgpu::Features =
+        wgpu::Features::TIMESTAMP_QUERY.union(wgpu::Features::TIMESTAMP_QUERY_INSIDE_ENCODERS);
+
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let queries = device.features().contains(Self::FEATURES).then(|| Queries::new(device, queue));
+        Self {
+            queries,
+            readback_state: ReadbackState::Idle,
+            map_status: Arc::new(AtomicU8::new(MAP_PENDING)),
+            next_query: 0,
+            latest: None,
+        }
+    }
+
+    pub fn is_supported(&self) -> bool {
+        self.queries.is_some()
+    }
+
+    /// Starts timing the frame being recorded, unless the last timed frame is still being
+    /// read back.
+    pub fn begin(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let Some(queries) = self.queries.as_ref().filter(|_| self.readback_state == ReadbackState::Idle) else {
+            return;
+        };
+        encoder.write_timestamp(&queries.query_set, 0);
+        self.next_query = 1;
+        self.readback_state = ReadbackState::Recording;
+    }
+
+    /// Ends the next section of [`SECTIONS`].
+    pub fn end_section(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let Some(queries) = &self.queries else {
+            return;
+        };
+        if self.readback_state != ReadbackState::Recording || self.next_query >= QUERY_COUNT {
+            return;
+        }
+        encoder.write_timestamp(&queries.query_set, self.next_query);
+        self.next_query += 1;
+        if self.next_query == QUERY_COUNT {
+            encoder.resolve_query_set(&queries.query_set, 0..QUERY_COUNT, &queries.resolve_buffer, 0);
+            encoder.copy_buffer_to_buffer(&queries.resolve_buffer, 0, &queries.readback, 0, BUFFER_SIZE);
+            self.readback_state = ReadbackState::Copied;
+        }
+    }
+
+    /// Call after the frame's commands were submitted: starts the readback and collects a
+    /// finished one without blocking.
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        let Some(queries) = &self.queries else {
+            return;
+        };
+        match self.readback_state {
+            // A frame that did not end every section has nothing to read.
+            ReadbackState::Recording => self.readback_state = ReadbackState::Idle,
+            ReadbackState::Idle => {}
+            ReadbackState::Copied => {
+                let status = self.map_status.clone();
+                status.store(MAP_PENDING, Ordering::Release);
+                queries.readback.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                    status.store(if result.is_ok() { MAP_DONE } else { MAP_FAILED }, Ordering::Release);
+                });
+                self.readback_state = ReadbackState::Mapping;
+            }
+            ReadbackState::Mapping => {
+                device.poll(wgpu::Maintain::Poll);
+                match self.map_status.load(Ordering::Acquire) {
+                    MAP_DONE => {
+                        {
+                            let data = queries.readback.slice(..).get_mapped_range();
+                            let ticks: &[u64] = bytemuck::cast_slice(&data);
+                            self.latest = Some(std::array::from_fn(|i| {
+                                ticks[i + 1].saturating_sub(ticks[i]) as f32 * queries.period / 1.0e6
+                            }));
+                        }
+                        queries.readback.unmap();
+                        self.readback_state = ReadbackState::Idle;
+                    }
+                    MAP_FAILED => self.readback_state = ReadbackState::Idle,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Milliseconds per section of the last frame read back.
+    pub fn latest(&self) -> Option<&[f32; SECTIONS.len()]> {
+        self.latest.as_ref()
+    }
+}
@@ -0,0 +1,517 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use wgpu::util::DeviceExt;
+
+use crate::gpu_timer::{self, GpuTimer};
+use crate::profiler;
+
+/// Frames shown in the frame time graph.
+const GRAPH_FRAMES: usize = 120;
+/// Frame time at the top of the graph, in milliseconds.
+const GRAPH_MS: f32 = 1000.0 / 30.0;
+const GRAPH_HEIGHT: f32 = 60.0;
+const BAR_WIDTH: f32 = 2.0;
+/// Text, bars and spans per frame never come close; anything past it is dropped.
+const MAX_QUADS: usize = 4096;
+/// Screen pixels per font pixel.
+const SCALE: f32 = 2.0;
+const MARGIN: f32 = 8.0;
+const LINE_HEIGHT: f32 = 9.0 * SCALE;
+const ADVANCE: f32 = 6.0 * SCALE;
+const SOLID: u32 = u32::MAX;
+/// Nesting levels shown by the CPU flame view; deeper spans are left out.
+const FLAME_ROWS: u32 = 8;
+const FLAME_ROW_HEIGHT: f32 = LINE_HEIGHT + 2.0;
+/// How long a notice stays up; it fades out over the last third.
+const NOTICE_TIME: Duration = Duration::from_millis(1500);
+
+/// 5×7 glyphs, one byte per row with the leftmost pixel in bit 4. Text is upper-cased
+/// before lookup and characters without a glyph draw as spaces.
+const GLYPHS: [(char, [u8; 7]); 45] = [
+    (' ', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('0', [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110]),
+    ('1', [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('2', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111]),
+    ('3', [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110]),
+    ('4', [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010]),
+    ('5', [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110]),
+    ('6', [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110]),
+    ('7', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000]),
+    ('8', [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110]),
+    ('9', [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100]),
+    ('A', [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('B', [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110]),
+    ('C', [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110]),
+    ('D', [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100]),
+    ('E', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111]),
+    ('F', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('G', [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111]),
+    ('H', [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('I', [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('J', [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100]),
+    ('K', [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001]),
+    ('L', [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111]),
+    ('M', [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001]),
+    ('N', [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001]),
+    ('O', [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('P', [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('Q', [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101]),
+    ('R', [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001]),
+    ('S', [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110]),
+    ('T', [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100]),
+    ('U', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('V', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100]),
+    ('W', [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010]),
+    ('X', [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001]),
+    ('Y', [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100]),
+    ('Z', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111]),
+    ('.', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100]),
+    (',', [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000]),
+    (':', [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000]),
+    ('/', [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000]),
+    ('%', [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011]),
+    ('-', [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000]),
+    ('(', [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010]),
+    (')', [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000]),
+];
+
+/// Mesh draws issued into one group of passes.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DrawCount {
+    pub draws: u32,
+    pub triangles: u64,
+    /// Pipelines and materials bound between draws.
+    pub switches: u32,
+}
+
+impl DrawCount {
+    /// Counts one indexed triangle-list draw.
+    pub fn add(&mut self, index_count: u32) {
+        self.draws += 1;
+        self.triangles += u64::from(index_count / 3);
+    }
+}
+
+impl std::ops::AddAssign for DrawCount {
+    fn add_assign(&mut self, other: Self) {
+        self.draws += other.draws;
+        self.triangles += other.triangles;
+        self.switches += other.switches;
+    }
+}
+
+/// What one frame submitted; shadow maps of every light are counted apart from the rest.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FrameStats {
+    pub main: DrawCount,
+    pub shadow: DrawCount,
+}
+
+/// Scene contents behind the overlay's count and memory lines.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SceneTotals {
+    pub meshes: usize,
+    pub materials: usize,
+    pub buffer_bytes: u64,
+    pub texture_bytes: u64,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct HudQuad {
+    /// Top-left corner and size, in pixels.
+    rect: [f32; 4],
+    color: [f32; 4],
+    /// Index into `GLYPHS`, or `SOLID`.
+    glyph: u32,
+    _pad: [u32; 3],
+}
+
+fn glyph_index(c: char) -> u32 {
+    let c = c.to_ascii_uppercase();
+    GLYPHS.iter().position(|(g, _)| *g == c).unwrap_or(0) as u32
+}
+
+fn format_count(n: u64) -> String {
+    match n {
+        0..=9_999 => n.to_string(),
+        10_000..=999_999 => format!("{:.1}K", n as f64 / 1e3),
+        _ => format!("{:.2}M", n as f64 / 1e6),
+    }
+}
+
+/// `value` to three significant digits, for values that span orders of magnitude.
+pub fn format_significant(value: f32) -> String {
+    let decimals = (2.0 - value.abs().max(1e-6).log10().floor()).clamp(0.0, 6.0) as usize;
+    format!("{:.*}", decimals, value)
+}
+
+fn format_megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+/// A stable, muted color per span name so a scope keeps its color from frame to frame.
+fn span_color(name: &str) -> [f32; 4] {
+    let hash = name.bytes().fold(0x811c9dc5u32, |h, b| (h ^ u32::from(b)).wrapping_mul(0x01000193));
+    let channel = |shift: u32| 0.3 + ((hash >> shift) & 0xff) as f32 / 255.0 * 0.5;
+    [channel(0), channel(8), channel(16), 1.0]
+}
+
+/// On-screen statistics: frame rate and a frame time graph, mesh draws and triangles for
+/// the scene and its shadows, what the scene keeps on the GPU and GPU time per section of
+/// the frame.
+pub struct Hud {
+    pub visible: bool,
+    /// The CPU flame view along the bottom of the window.
+    pub show_profiler: bool,
+    /// Seconds, oldest first.
+    frame_times: VecDeque<f32>,
+    /// A short message shown at the top of the window even with the panels hidden.
+    notice: Option<(String, Instant)>,
+    quads: Vec<HudQuad>,
+    quad_buffer: wgpu::Buffer,
+    params_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Hud {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, output_format: wgpu::TextureFormat) -> Self {
+        let width = 5 * GLYPHS.len() as u32;
+        let mut pixels = vec![0u8; (width * 7) as usize];
+        for (i, (_, rows)) in GLYPHS.iter().enumerate() {
+            for (y, row) in rows.iter().enumerate() {
+                for x in 0..5 {
+                    if row & (0b10000 >> x) != 0 {
+                        pixels[y * width as usize + i * 5 + x] = 255;
+                    }
+                }
+            }
+        }
+        let font = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("HUD Font"),
+                size: wgpu::Extent3d {
+                    width,
+                    height: 7,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            &pixels,
+        );
+        let font_view = font.create_view(&wgpu::TextureViewDescriptor::default());
+        let quad_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("HUD Quads"),
+            size: (MAX_QUADS * std::mem::size_of::<HudQuad>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("HUD Params Buffer"),
+            size: std::mem::size_of::<[f32; 4]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("hud_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("hud_bind_group"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&font_view),
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("HUD Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("hud.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("HUD Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("HUD Pipeline"),
+            layout: Some(&pipeline_layout),
+            cache: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_quad",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<HudQuad>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4, 2 => Uint32],
+                }],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_quad",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: output_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            visible: false,
+            show_profiler: false,
+            frame_times: VecDeque::with_capacity(GRAPH_FRAMES),
+            notice: None,
+            quads: Vec::new(),
+            quad_buffer,
+            params_buffer,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    pub fn push_frame_time(&mut self, seconds: f32) {
+        if self.frame_times.len() == GRAPH_FRAMES {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(seconds);
+    }
+
+    /// Shows `text` briefly, replacing any notice still up.
+    pub fn notify(&mut self, text: String) {
+        self.notice = Some((text, Instant::now()));
+    }
+
+    /// Whether `draw` has anything to show.
+    pub fn is_active(&mut self) -> bool {
+        self.notice = self.notice.take().filter(|(_, shown)| shown.elapsed() < NOTICE_TIME);
+        self.visible || self.show_profiler || self.notice.is_some()
+    }
+
+    fn rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: [f32; 4]) {
+        self.quads.push(HudQuad {
+            rect: [x, y, w, h],
+            color,
+            glyph: SOLID,
+            _pad: [0; 3],
+        });
+    }
+
+    fn text(&mut self, x: f32, y: f32, text: &str, color: [f32; 4]) {
+        for (i, c) in text.chars().enumerate() {
+            let glyph = glyph_index(c);
+            if glyph != 0 {
+                self.quads.push(HudQuad {
+                    rect: [x + i as f32 * ADVANCE, y, 5.0 * SCALE, 7.0 * SCALE],
+                    color,
+                    glyph,
+                    _pad: [0; 3],
+                });
+            }
+        }
+    }
+
+    /// Draws the visible panels over `target`, which is `size` pixels, keeping what is
+    /// there.
+    pub fn draw(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        (target, size): (&wgpu::TextureView, (u32, u32)),
+        stats: &FrameStats,
+        totals: &SceneTotals,
+        gpu_timer: &GpuTimer,
+    ) {
+        self.quads.clear();
+        if self.visible {
+            self.stats_panel(stats, totals, gpu_timer);
+        }
+        if self.show_profiler {
+            self.flame_panel(size);
+        }
+        self.notice_panel(size.0);
+        self.quads.truncate(MAX_QUADS);
+        if self.quads.is_empty() {
+            return;
+        }
+
+        let params = [size.0 as f32, size.1 as f32, 0.0, 0.0];
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&params));
+        queue.write_buffer(&self.quad_buffer, 0, bytemuck::cast_slice(&self.quads));
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("HUD Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.quad_buffer.slice(..));
+        pass.draw(0..6, 0..self.quads.len() as u32);
+    }
+
+    fn stats_panel(&mut self, stats: &FrameStats, totals: &SceneTotals, gpu_timer: &GpuTimer) {
+        let frames = self.frame_times.len().max(1) as f32;
+        let average_ms = self.frame_times.iter().sum::<f32>() / frames * 1000.0;
+        let worst_ms = self.frame_times.iter().copied().fold(0.0, f32::max) * 1000.0;
+        let mut lines = vec![
+            format!("{:.0} FPS  {:.2} MS  (MAX {:.1})", 1000.0 / average_ms.max(1e-3), average_ms, worst_ms),
+            format!(
+                "DRAWS {}  SWITCHES {}  TRIS {}",
+                stats.main.draws,
+                stats.main.switches,
+                format_count(stats.main.triangles)
+            ),
+            format!("SHADOW DRAWS {}  TRIS {}", stats.shadow.draws, format_count(stats.shadow.triangles)),
+            format!("MESHES {}  MATERIALS {}", totals.meshes, totals.materials),
+            format!(
+                "BUFFERS {}  TEXTURES {}",
+                format_megabytes(totals.buffer_bytes),
+                format_megabytes(totals.texture_bytes)
+            ),
+        ];
+        if let Some(times) = gpu_timer.latest() {
+            lines.push(format!("GPU {:.2} MS", times.iter().sum::<f32>()));
+            for (name, ms) in gpu_timer::SECTIONS.iter().zip(times) {
+                lines.push(format!("  {:<12}{:>6.2}", name, ms));
+            }
+        } else if gpu_timer.is_supported() {
+            lines.push("GPU TIMES PENDING".to_string());
+        } else {
+            lines.push("GPU TIMES UNSUPPORTED".to_string());
+        }
+        let text_width = lines.iter().map(|l| l.len()).max().unwrap_or(0) as f32 * ADVANCE;
+        let graph_width = GRAPH_FRAMES as f32 * BAR_WIDTH;
+        let graph_top = MARGIN * 2.0 + lines.len() as f32 * LINE_HEIGHT;
+
+        let panel = [0.0, 0.0, 0.0, 0.6];
+        self.rect(MARGIN, MARGIN, text_width.max(graph_width) + MARGIN * 2.0, graph_top + GRAPH_HEIGHT, panel);
+        let white = [1.0, 1.0, 1.0, 1.0];
+        for (i, line) in lines.iter().enumerate() {
+            self.text(MARGIN * 2.0, MARGIN * 2.0 + i as f32 * LINE_HEIGHT, line, white);
+        }
+        let graph_bottom = graph_top + GRAPH_HEIGHT;
+        let times: Vec<f32> = self.frame_times.iter().copied().collect();
+        let first_bar = GRAPH_FRAMES - times.len();
+        for (i, seconds) in times.into_iter().enumerate() {
+            let ms = seconds * 1000.0;
+            let height = (ms / GRAPH_MS).min(1.0) * GRAPH_HEIGHT;
+            let color = if ms <= 1000.0 / 60.0 + 0.5 {
+                [0.2, 0.9, 0.3, 1.0]
+            } else if ms <= GRAPH_MS + 0.5 {
+                [1.0, 0.85, 0.2, 1.0]
+            } else {
+                [1.0, 0.25, 0.2, 1.0]
+            };
+            let x = MARGIN * 2.0 + (first_bar + i) as f32 * BAR_WIDTH;
+            self.rect(x, graph_bottom - height, BAR_WIDTH, height, color);
+        }
+        // 60 Hz budget.
+        let budget_y = graph_bottom - GRAPH_HEIGHT * (1000.0 / 60.0) / GRAPH_MS;
+        self.rect(MARGIN * 2.0, budget_y, graph_width, 1.0, [1.0, 1.0, 1.0, 0.5]);
+    }
+
+    fn notice_panel(&mut self, width: u32) {
+        let Some((text, shown)) = self.notice.clone() else {
+            return;
+        };
+        let remaining = NOTICE_TIME.saturating_sub(shown.elapsed()).as_secs_f32();
+        let alpha = (remaining * 3.0 / NOTICE_TIME.as_secs_f32()).min(1.0);
+        let text_width = text.len() as f32 * ADVANCE;
+        let x = ((width as f32 - text_width) * 0.5).max(MARGIN * 2.0);
+        let panel = [0.0, 0.0, 0.0, 0.6 * alpha];
+        self.rect(x - MARGIN, MARGIN, text_width + MARGIN * 2.0, LINE_HEIGHT + MARGIN * 2.0, panel);
+        self.text(x, MARGIN * 2.0, &text, [1.0, 1.0, 1.0, alpha]);
+    }
+
+    /// The main thread's spans of the last profiled frame, one row per nesting level,
+    /// scaled to the window width.
+    fn flame_panel(&mut self, (width, height): (u32, u32)) {
+        let Some((header, spans, frame_start, frame_us)) = profiler::with_last_frame(|frame| {
+            let header = format!("CPU {:.2} MS  {} SPANS", frame.duration as f32 / 1000.0, frame.spans.len());
+            (header, frame.spans.clone(), frame.start, frame.duration.max(1))
+        }) else {
+            let panel_y = height as f32 - MARGIN * 3.0 - LINE_HEIGHT;
+            self.rect(MARGIN, panel_y, width as f32 - MARGIN * 2.0, LINE_HEIGHT + MARGIN * 2.0, [0.0, 0.0, 0.0, 0.6]);
+            self.text(MARGIN * 2.0, panel_y + MARGIN, "CPU PROFILE PENDING", [1.0, 1.0, 1.0, 1.0]);
+            return;
+        };
+        let rows = spans.iter().map(|s| s.depth + 1).max().unwrap_or(0).min(FLAME_ROWS);
+        let panel_height = MARGIN * 3.0 + LINE_HEIGHT + rows as f32 * FLAME_ROW_HEIGHT;
+        let panel_y = height as f32 - MARGIN - panel_height;
+        let panel_width = width as f32 - MARGIN * 2.0;
+        self.rect(MARGIN, panel_y, panel_width, panel_height, [0.0, 0.0, 0.0, 0.6]);
+        self.text(MARGIN * 2.0, panel_y + MARGIN, &header, [1.0, 1.0, 1.0, 1.0]);
+
+        let left = MARGIN * 2.0;
+        let track_width = panel_width - MARGIN * 2.0;
+        let rows_top = panel_y + MARGIN * 2.0 + LINE_HEIGHT;
+        let to_x = |us: u64| left + us.saturating_sub(frame_start).min(frame_us) as f32 / frame_us as f32 * track_width;
+        for span in spans.iter().filter(|s| s.depth < FLAME_ROWS) {
+            let x = to_x(span.start);
+            let w = (to_x(span.start + span.duration) - x).max(1.0);
+            let y = rows_top + span.depth as f32 * FLAME_ROW_HEIGHT;
+            self.rect(x, y, w, FLAME_ROW_HEIGHT - 2.0, span_color(span.name));
+            let label = format!("{} {:.2}", span.name, span.duration as f32 / 1000.0);
+            let label = if (label.len() as f32 * ADVANCE) < w - 4.0 { label } else { span.name.to_string() };
+            if (label.len() as f32 * ADVANCE) < w - 4.0 {
+                self.text(x + 2.0, y + 1.0, &label, [0.0, 0.0, 0.0, 1.0]);
+            }
+        }
+        // 60 Hz budget, when the frame runs past it.
+        if frame_us > 16_667 {
+            self.rect(to_x(frame_start + 16_667), rows_top, 1.0, rows as f32 * FLAME_ROW_HEIGHT, [1.0, 0.25, 0.2, 1.0]);
+        }
+    }
+}
@@ -0,0 +1,63 @@
+// Statistics overlay: screen-space quads that are either solid or one glyph of the
+// bitmap font, positioned in pixels.
+
+struct HudParams {
+    // xy: target size in pixels.
+    screen: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> params: HudParams;
+
+// One row of 5×7 glyphs side by side; red is 1 where a glyph pixel is set.
+@group(0) @binding(1)
+var font: texture_2d<f32>;
+
+const GLYPH_SIZE: vec2<f32> = vec2<f32>(5.0, 7.0);
+const SOLID: u32 = 0xffffffffu;
+
+struct QuadOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    // Position inside the glyph cell, in font pixels.
+    @location(1) cell: vec2<f32>,
+    @location(2) @interpolate(flat) glyph: u32,
+};
+
+@vertex
+fn vs_quad(
+    @builtin(vertex_index) vid: u32,
+    @location(0) rect: vec4<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) glyph: u32,
+) -> QuadOut {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let c = corners[vid];
+    let pixel = rect.xy + c * rect.zw;
+    var o: QuadOut;
+    o.pos = vec4<f32>(pixel / params.screen.xy * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    o.color = color;
+    o.cell = c * GLYPH_SIZE;
+    o.glyph = glyph;
+    return o;
+}
+
+@fragment
+fn fs_quad(in: QuadOut) -> @location(0) vec4<f32> {
+    if in.glyph == SOLID {
+        return in.color;
+    }
+    let cell = vec2<i32>(min(in.cell, GLYPH_SIZE - 0.001));
+    let texel = cell + vec2<i32>(i32(in.glyph) * 5, 0);
+    if textureLoad(font, texel, 0).r < 0.5 {
+        discard;
+    }
+    return in.color;
+}
@@ -0,0 +1,53 @@
+//! One pool of worker threads shared by the engine work that splits into independent
+//! jobs, so each system parallelizes without starting threads of its own.
+
+use std::sync::OnceLock;
+
+use rayon::prelude::*;
+
+use crate::profiler;
+
+static POOL: OnceLock<rayon::ThreadPool> = OnceLock::new();
+
+fn build(threads: usize) -> rayon::ThreadPool {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|i| format!("dusk-job-{}", i))
+        .build()
+        .expect("creating the job threads")
+}
+
+fn pool() -> &'static rayon::ThreadPool {
+    POOL.get_or_init(|| build(0))
+}
+
+/// Starts the workers, `threads` of them or one per core for 0. Only the first call, or
+/// the first job, starts them.
+pub fn init(threads: usize) {
+    let mut started = false;
+    POOL.get_or_init(|| {
+        started = true;
+        build(threads)
+    });
+    if started {
+        log::info!("Job system: {} worker threads", self::threads());
+    } else if threads != 0 && threads != self::threads() {
+        log::warn!("The job threads are already running; keeping {} of them", self::threads());
+    }
+}
+
+pub fn threads() -> usize {
+    pool().current_num_threads()
+}
+
+/// Runs `f` with a scope to spawn jobs into, timed as `name`, and returns once every job
+/// has finished. Jobs may borrow whatever outlives the call, such as the frame's state.
+pub fn scope<'scope, R: Send>(name: &'static str, f: impl FnOnce(&rayon::Scope<'scope>) -> R + Send) -> R {
+    let _span = profiler::scope(name);
+    pool().scope(f)
+}
+
+/// `f` applied to every item on the workers, with the results in the items' order.
+pub fn map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync + Send) -> Vec<R> {
+    pool().install(|| items.par_iter().map(f).collect())
+}
@@ -0,0 +1,174 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const REPORT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Present modes by the names `--present-mode=` and snapshots use, in the order the
+/// runtime toggle cycles through them.
+const PRESENT_MODES: [(&str, wgpu::PresentMode); 4] = [
+    ("fifo", wgpu::PresentMode::Fifo),
+    ("mailbox", wgpu::PresentMode::Mailbox),
+    ("immediate", wgpu::PresentMode::Immediate),
+    ("fifo-relaxed", wgpu::PresentMode::FifoRelaxed),
+];
+
+/// Accepts `vsync` for `fifo`.
+pub fn parse_present_mode(name: &str) -> Option<wgpu::PresentMode> {
+    let name = if name == "vsync" { "fifo" } else { name };
+    PRESENT_MODES.iter().find(|(n, _)| *n == name).map(|(_, mode)| *mode)
+}
+
+pub fn present_mode_name(mode: wgpu::PresentMode) -> Option<&'static str> {
+    PRESENT_MODES.iter().find(|(_, m)| *m == mode).map(|(name, _)| *name)
+}
+
+/// Fifo with vsync; without, the first of immediate and mailbox the surface supports.
+pub fn vsync_present_mode(vsync: bool, caps: &wgpu::SurfaceCapabilities) -> wgpu::PresentMode {
+    let modes: &[wgpu::PresentMode] =
+        if vsync { &[wgpu::PresentMode::Fifo] } else { &[wgpu::PresentMode::Immediate, wgpu::PresentMode::Mailbox] };
+    modes.iter().copied().find(|m| caps.present_modes.contains(m)).unwrap_or(wgpu::PresentMode::Fifo)
+}
+
+/// The supported mode after `current`, wrapping around.
+pub fn next_present_mode(current: wgpu::PresentMode, caps: &wgpu::SurfaceCapabilities) -> wgpu::PresentMode {
+    let supported: Vec<wgpu::PresentMode> = PRESENT_MODES
+        .iter()
+        .map(|(_, mode)| *mode)
+        .filter(|mode| caps.present_modes.contains(mode))
+        .collect();
+    let next = supported.iter().position(|&m| m == current).map_or(0, |i| (i + 1) % supported.len());
+    supported.get(next).copied().unwrap_or(wgpu::PresentMode::Fifo)
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct LatencySettings {
+    /// Frames the swapchain may queue ahead of the display.
+    pub max_frame_latency: u32,
+    /// Block after submitting until the GPU has finished the frame, so the next frame
+    /// samples input as late as possible instead of queueing behind this one.
+    pub wait_before_present: bool,
+    /// Prefer mailbox presentation when the surface supports it.
+    pub prefer_mailbox: bool,
+    /// Present mode asked for explicitly, overriding `prefer_mailbox`.
+    pub present_mode: Option<wgpu::PresentMode>,
+}
+
+impl Default for LatencySettings {
+    fn default() -> Self {
+        Self {
+            max_frame_latency: 2,
+            wait_before_present: false,
+            prefer_mailbox: false,
+            present_mode: None,
+        }
+    }
+}
+
+impl LatencySettings {
+    pub fn low_latency() -> Self {
+        Self {
+            max_frame_latency: 1,
+            wait_before_present: true,
+            prefer_mailbox: true,
+            present_mode: None,
+        }
+    }
+
+    pub fn is_low_latency(&self) -> bool {
+        self.max_frame_latency <= 1 && self.wait_before_present
+    }
+
+    pub fn apply(&self, config: &mut wgpu::SurfaceConfiguration, caps: &wgpu::SurfaceCapabilities) {
+        config.desired_maximum_frame_latency = self.max_frame_latency.clamp(1, 3);
+        let mailbox = self.prefer_mailbox && caps.present_modes.contains(&wgpu::PresentMode::Mailbox);
+        config.present_mode = match self.present_mode {
+            Some(mode) if caps.present_modes.contains(&mode) => mode,
+            Some(mode) => {
+                // Every surface supports FIFO.
+                log::warn!("Present mode {:?} is not supported by the surface; using Fifo", mode);
+                wgpu::PresentMode::Fifo
+            }
+            None if mailbox => wgpu::PresentMode::Mailbox,
+            None => caps.present_modes[0],
+        };
+    }
+}
+
+/// Estimates input-to-photon latency: time from sampling input at the start of a frame
+/// until the GPU finished it, plus the expected wait for scanout on the display.
+pub struct LatencyMonitor {
+    completed: Arc<Mutex<Vec<Duration>>>,
+    frame_start: Instant,
+    refresh_interval: Duration,
+    gpu_sum: Duration,
+    gpu_samples: u32,
+    last_report: Instant,
+    estimate: Option<Duration>,
+}
+
+impl LatencyMonitor {
+    pub fn new(refresh_millihertz: Option<u32>) -> Self {
+        let refresh_hz = refresh_millihertz.filter(|&mhz| mhz > 0).map(|mhz| mhz as f64 / 1000.0).unwrap_or(60.0);
+        Self {
+            completed: Arc::new(Mutex::new(Vec::new())),
+            frame_start: Instant::now(),
+            refresh_interval: Duration::from_secs_f64(1.0 / refresh_hz),
+            gpu_sum: Duration::ZERO,
+            gpu_samples: 0,
+            last_report: Instant::now(),
+            estimate: None,
+        }
+    }
+
+    /// Call right before input is applied for a new frame.
+    pub fn begin_frame(&mut self) {
+        self.frame_start = Instant::now();
+    }
+
+    /// Call after the frame's command buffers were submitted.
+    pub fn submitted(&mut self, queue: &wgpu::Queue) {
+        let start = self.frame_start;
+        let completed = self.completed.clone();
+        queue.on_submitted_work_done(move || {
+            if let Ok(mut completed) = completed.lock() {
+                completed.push(start.elapsed());
+            }
+        });
+    }
+
+    /// Collects finished frames; returns true when a new estimate is ready for display.
+    pub fn end_frame(&mut self, device: &wgpu::Device, present_mode: wgpu::PresentMode) -> bool {
+        device.poll(wgpu::Maintain::Poll);
+        if let Ok(mut completed) = self.completed.lock() {
+            for d in completed.drain(..) {
+                self.gpu_sum += d;
+                self.gpu_samples += 1;
+            }
+        }
+        if self.last_report.elapsed() < REPORT_INTERVAL || self.gpu_samples == 0 {
+            return false;
+        }
+        // FIFO waits for the next vblank (half a refresh on average) before the half-refresh
+        // average scanout; mailbox/immediate only pay the scanout.
+        let display = match present_mode {
+            wgpu::PresentMode::Fifo | wgpu::PresentMode::FifoRelaxed | wgpu::PresentMode::AutoVsync => {
+                self.refresh_interval
+            }
+            _ => self.refresh_interval / 2,
+        };
+        self.estimate = Some(self.gpu_sum / self.gpu_samples + display);
+        self.gpu_sum = Duration::ZERO;
+        self.gpu_samples = 0;
+        self.last_report = Instant::now();
+        true
+    }
+
+    pub fn label(&self, settings: &LatencySettings) -> Option<String> {
+        let estimate = self.estimate?;
+        Some(format!(
+            "~{:.1} ms input-to-photon{}",
+            estimate.as_secs_f64() * 1000.0,
+            if settings.is_low_latency() { " (low latency)" } else { "" }
+        ))
+    }
+}
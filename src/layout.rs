@@ -0,0 +1,451 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use cgmath::{Deg, Euler, InnerSpace, Matrix4, Point3, Quaternion, SquareMatrix, Vector3};
+
+use crate::aabb::Aabb;
+use crate::animation::NodeTransform;
+use crate::lights::{DirectionalLight, LightSet, PointLight, SpotLight, DEFAULT_SUN_LUX};
+use crate::model::MaterialOverride;
+use crate::reflections::ReflectionProbe;
+
+const PADDING: f32 = 2.0;
+
+/// How several loaded models are placed relative to each other.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// Side by side along +X, each shifted by the previous widths plus padding.
+    Row,
+    /// Square grid on the XZ plane, models centred in equally sized cells.
+    Grid,
+    /// Evenly spaced on a circle around the origin.
+    Circle,
+    /// Untouched: every model keeps its own origin (multi-part exports).
+    Origin,
+    /// Transforms from the scene file; models without one stay at their origin.
+    Explicit,
+}
+
+impl Layout {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "row" => Some(Self::Row),
+            "grid" => Some(Self::Grid),
+            "circle" => Some(Self::Circle),
+            "origin" => Some(Self::Origin),
+            "explicit" => Some(Self::Explicit),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SceneEntry {
+    pub path: PathBuf,
+    pub transform: Option<Matrix4<f32>>,
+    pub overrides: Vec<MaterialOverride>,
+}
+
+impl SceneEntry {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            transform: None,
+            overrides: Vec::new(),
+        }
+    }
+
+    /// Reads a model argument: a path, optionally followed by a placement as in
+    /// `props.glb@pos=4,0,-2,rot=0,90,0,scale=0.5`. `pos` is the translation, `rot` Euler
+    /// angles about X, Y and Z in degrees and `scale` one factor or three.
+    pub fn from_arg(arg: &str) -> Self {
+        let Some((path, placement)) = arg.rsplit_once('@').filter(|(_, p)| p.contains('=')) else {
+            return Self::new(arg);
+        };
+        let mut entry = Self::new(path);
+        // Commas split the values as well as the keys, so values run until the next `=`.
+        let mut fields: Vec<(&str, Vec<&str>)> = Vec::new();
+        for token in placement.split(',') {
+            match (token.split_once('='), fields.last_mut()) {
+                (Some((key, value)), _) => fields.push((key, vec![value])),
+                (None, Some((_, values))) => values.push(token),
+                (None, None) => log::warn!("Ignoring placement value '{}' without a key for {}", token, path),
+            }
+        }
+
+        let (mut translation, mut rotation, mut scale) = ([0.0; 3], Quaternion::new(1.0, 0.0, 0.0, 0.0), [1.0; 3]);
+        let mut placed = false;
+        for (key, values) in fields {
+            let numbers: Option<Vec<f32>> = values.iter().map(|v| v.trim().parse().ok()).collect();
+            match (key, numbers.as_deref()) {
+                ("pos", Some(&[x, y, z])) => translation = [x, y, z],
+                ("rot", Some(&[x, y, z])) => rotation = Quaternion::from(Euler::new(Deg(x), Deg(y), Deg(z))),
+                ("scale", Some(&[s])) => scale = [s; 3],
+                ("scale", Some(&[x, y, z])) => scale = [x, y, z],
+                _ => {
+                    log::warn!("Ignoring invalid placement '{}={}' for {}", key, values.join(","), path);
+                    continue;
+                }
+            }
+            placed = true;
+        }
+        if placed {
+            entry.transform = Some(
+                Matrix4::from_translation(translation.into())
+                    * Matrix4::from(rotation)
+                    * Matrix4::from_nonuniform_scale(scale[0], scale[1], scale[2]),
+            );
+        }
+        entry
+    }
+}
+
+pub struct SceneFile {
+    pub layout: Option<Layout>,
+    pub entries: Vec<SceneEntry>,
+    pub lights: LightSet,
+    pub reflection_probes: Vec<ReflectionProbe>,
+    pub ev100: Option<f32>,
+    /// Luminance in nits of an environment map value of 1.0.
+    pub env_nits: Option<f32>,
+    /// Equirectangular HDR for the environment, instead of one found next to the models.
+    pub environment: Option<PathBuf>,
+    pub sun: Option<SceneSun>,
+    pub camera: Option<SceneCamera>,
+}
+
+/// The time of day a scene file starts at.
+#[derive(Copy, Clone, Debug)]
+pub struct SceneSun {
+    pub time_of_day: Option<f32>,
+    pub day_length: Option<f32>,
+    pub animate: bool,
+}
+
+/// Where a scene file starts the camera; angles in radians as `Camera` keeps them.
+#[derive(Copy, Clone, Debug)]
+pub struct SceneCamera {
+    pub position: Point3<f32>,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub fovy: Option<f32>,
+}
+
+/// Whether a model argument names a scene file instead of a glTF model.
+pub fn is_scene_file(path: &str) -> bool {
+    let extension = Path::new(path).extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+    !path.starts_with("--") && matches!(extension.as_deref(), Some("json" | "toml"))
+}
+
+/// Loads a JSON scene description, or the same keys in TOML for a `.toml` file:
+///
+/// ```json
+/// { "layout": "explicit",
+///   "models": [{ "path": "a.glb", "translation": [0, 0, 5], "rotation": [0, 0, 0, 1], "scale": 2 }] }
+/// ```
+///
+/// Paths are relative to the scene file; `rotation` is an `[x, y, z, w]` quaternion and
+/// `scale` either a number or `[x, y, z]`. Without a `layout`, the explicit transforms are
+/// used when any model has one.
+///
+/// A model can also carry `"material_overrides"`: one object or a list of them with an
+/// optional `material` name and any of `tint` (`[r, g, b, a]`), `roughness_scale`,
+/// `metallic_scale`, `base_color_texture` (relative to the scene file) and
+/// `shadow_normal_offset`.
+///
+/// `"lights"` lists point lights in world space: `position`, `color`, `intensity`
+/// (candela) or `lumens`, optional `range` and `"shadows": false` to skip the shadow cube.
+/// `"type": "spot"` makes a spot light that also takes a `direction` and
+/// `inner_angle`/`outer_angle` half-angles in degrees. `"type": "directional"` takes only
+/// `direction`, `color` and `intensity` (lux); the first directional light casts the cascaded
+/// shadows. Spot and directional lights take an optional `cookie` image (relative to the
+/// scene file) that masks their light; a directional cookie repeats every `cookie_size`
+/// world units.
+///
+/// `"ev100"` sets the camera exposure and `"env_nits"` the luminance an environment map
+/// value of 1.0 stands for.
+///
+/// `"environment"` names the environment HDR (relative to the scene file). `"sun"` takes
+/// a `time_of_day` in hours, a `day_length` in seconds and `animate` to run the cycle.
+/// `"camera"` takes a `position` and either a `look_at` point or `yaw` and `pitch` in
+/// degrees, plus an optional `fov`.
+///
+/// `"reflection_probes"` lists boxes with `min` and `max` corners, an optional capture
+/// `position` (the box centre by default) and `blend_distance` over which the probe fades
+/// in from the box edges.
+pub fn load_scene_file(path: &Path) -> Result<SceneFile> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("reading scene file {}", path.display()))?;
+    let value: serde_json::Value = if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("toml")) {
+        let table: toml::Table = text.parse().with_context(|| format!("parsing scene file {}", path.display()))?;
+        serde_json::to_value(table)?
+    } else {
+        serde_json::from_str(&text).with_context(|| format!("parsing scene file {}", path.display()))?
+    };
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+    let layout = match value.get("layout").and_then(|v| v.as_str()) {
+        Some(name) => {
+            let layout = Layout::from_name(name);
+            if layout.is_none() {
+                log::warn!("Scene file {}: unknown layout '{}'", path.display(), name);
+            }
+            layout
+        }
+        None => None,
+    };
+
+    let mut entries = Vec::new();
+    for (i, model) in value.get("models").and_then(|v| v.as_array()).into_iter().flatten().enumerate() {
+        let Some(model_path) = model.as_str().or_else(|| model.get("path").and_then(|v| v.as_str())) else {
+            log::warn!("Scene file {}: model {} has no path", path.display(), i);
+            continue;
+        };
+        entries.push(SceneEntry {
+            path: base_dir.join(model_path),
+            transform: entry_transform(model),
+            overrides: entry_overrides(model, base_dir),
+        });
+    }
+    let mut lights = LightSet::default();
+    for l in value.get("lights").and_then(|v| v.as_array()).into_iter().flatten() {
+        let kind = l.get("type").and_then(|v| v.as_str()).unwrap_or("point");
+        let color = l.get("color").and_then(floats::<3>).unwrap_or([1.0; 3]);
+        let cookie = match l.get("cookie").and_then(|v| v.as_str()) {
+            Some(cookie) if kind != "point" => match lights.add_cookie(&base_dir.join(cookie)) {
+                Ok(index) => Some(index),
+                Err(e) => {
+                    log::warn!("Scene file {}: {:#}", path.display(), e);
+                    None
+                }
+            },
+            Some(_) => {
+                log::warn!("Scene file {}: point lights do not support cookies", path.display());
+                None
+            }
+            None => None,
+        };
+        if kind == "directional" {
+            let direction = Vector3::from(l.get("direction").and_then(floats::<3>).unwrap_or([0.0, -1.0, 0.0]));
+            if direction.magnitude2() == 0.0 {
+                log::warn!("Scene file {}: directional light with a zero direction", path.display());
+                continue;
+            }
+            let intensity = l.get("intensity").and_then(|v| v.as_f64()).map_or(DEFAULT_SUN_LUX, |v| v as f32);
+            let mut light = DirectionalLight::new(direction.normalize(), color, intensity);
+            light.cookie = cookie;
+            light.cookie_size = l.get("cookie_size").and_then(|v| v.as_f64()).map_or(light.cookie_size, |v| v as f32);
+            lights.directional.push(light);
+            continue;
+        }
+        let Some(position) = l.get("position").and_then(floats::<3>) else {
+            log::warn!("Scene file {}: light without a position", path.display());
+            continue;
+        };
+        let intensity = match l.get("lumens").and_then(|v| v.as_f64()) {
+            Some(lumens) if kind == "spot" => SpotLight::candela_from_lumens(lumens as f32),
+            Some(lumens) => PointLight::candela_from_lumens(lumens as f32),
+            None => l.get("intensity").and_then(|v| v.as_f64()).unwrap_or(10.0) as f32,
+        };
+        let range = l.get("range").and_then(|v| v.as_f64()).map(|v| v as f32);
+        let cast_shadows = l.get("shadows").and_then(|v| v.as_bool()).unwrap_or(true);
+        match kind {
+            "spot" => {
+                let direction = Vector3::from(l.get("direction").and_then(floats::<3>).unwrap_or([0.0, -1.0, 0.0]));
+                if direction.magnitude2() == 0.0 {
+                    log::warn!("Scene file {}: spot light with a zero direction", path.display());
+                    continue;
+                }
+                let degrees = |key: &str, default: f32| {
+                    l.get(key).and_then(|v| v.as_f64()).map(|v| v as f32).unwrap_or(default).to_radians()
+                };
+                let mut light = SpotLight::new(Point3::from(position), direction.normalize(), color, intensity, range);
+                light.inner_angle = degrees("inner_angle", 0.0);
+                light.outer_angle = degrees("outer_angle", 45.0);
+                light.cast_shadows = cast_shadows;
+                light.cookie = cookie;
+                lights.spots.push(light);
+            }
+            "point" => {
+                let mut light = PointLight::new(Point3::from(position), color, intensity, range);
+                light.cast_shadows = cast_shadows;
+                lights.points.push(light);
+            }
+            other => log::warn!("Scene file {}: unknown light type '{}'", path.display(), other),
+        }
+    }
+    let mut reflection_probes = Vec::new();
+    for p in value.get("reflection_probes").and_then(|v| v.as_array()).into_iter().flatten() {
+        let (Some(min), Some(max)) = (p.get("min").and_then(floats::<3>), p.get("max").and_then(floats::<3>)) else {
+            log::warn!("Scene file {}: reflection probe without min and max", path.display());
+            continue;
+        };
+        let mut bounds = Aabb::empty();
+        bounds.grow(min);
+        bounds.grow(max);
+        let position = p.get("position").and_then(floats::<3>).map(Point3::from).unwrap_or_else(|| bounds.center());
+        let mut probe = ReflectionProbe::new(position, bounds);
+        if let Some(distance) = p.get("blend_distance").and_then(|v| v.as_f64()) {
+            probe.blend_distance = distance as f32;
+        }
+        reflection_probes.push(probe);
+    }
+    if entries.is_empty() {
+        anyhow::bail!("Scene file {} lists no models", path.display());
+    }
+
+    let layout = layout.or_else(|| entries.iter().any(|e| e.transform.is_some()).then_some(Layout::Explicit));
+    Ok(SceneFile {
+        layout,
+        entries,
+        lights,
+        reflection_probes,
+        ev100: value.get("ev100").and_then(|v| v.as_f64()).map(|v| v as f32),
+        env_nits: value.get("env_nits").and_then(|v| v.as_f64()).map(|v| v as f32),
+        environment: value.get("environment").and_then(|v| v.as_str()).map(|p| base_dir.join(p)),
+        sun: value.get("sun").map(|sun| {
+            let number = |key: &str| sun.get(key).and_then(|v| v.as_f64()).map(|v| v as f32);
+            SceneSun {
+                time_of_day: number("time_of_day"),
+                day_length: number("day_length").filter(|&s| s > 0.0),
+                animate: sun.get("animate").and_then(|v| v.as_bool()).unwrap_or(false),
+            }
+        }),
+        camera: value.get("camera").and_then(|c| scene_camera(c, path)),
+    })
+}
+
+fn scene_camera(camera: &serde_json::Value, path: &Path) -> Option<SceneCamera> {
+    let Some(position) = camera.get("position").and_then(floats::<3>).map(Point3::from) else {
+        log::warn!("Scene file {}: camera without a position", path.display());
+        return None;
+    };
+    let degrees = |key: &str| camera.get(key).and_then(|v| v.as_f64()).map(|v| (v as f32).to_radians());
+    let (yaw, pitch) = match camera.get("look_at").and_then(floats::<3>) {
+        Some(target) => {
+            let dir = Point3::from(target) - position;
+            if dir.magnitude2() == 0.0 {
+                log::warn!("Scene file {}: camera looks at its own position", path.display());
+                return None;
+            }
+            let dir = dir.normalize();
+            (dir.z.atan2(dir.x), dir.y.clamp(-1.0, 1.0).asin())
+        }
+        None => (degrees("yaw").unwrap_or(0.0), degrees("pitch").unwrap_or(0.0)),
+    };
+    Some(SceneCamera {
+        position,
+        yaw,
+        pitch: pitch.clamp(-1.55, 1.55),
+        fovy: camera.get("fov").and_then(|v| v.as_f64()).map(|v| v as f32).filter(|f| (1.0..179.0).contains(f)),
+    })
+}
+
+pub(crate) fn floats<const N: usize>(value: &serde_json::Value) -> Option<[f32; N]> {
+    let array = value.as_array()?;
+    if array.len() != N {
+        return None;
+    }
+    let mut out = [0.0; N];
+    for (o, v) in out.iter_mut().zip(array) {
+        *o = v.as_f64()? as f32;
+    }
+    Some(out)
+}
+
+fn entry_transform(model: &serde_json::Value) -> Option<Matrix4<f32>> {
+    let translation = model.get("translation");
+    let rotation = model.get("rotation");
+    let scale = model.get("scale");
+    if translation.is_none() && rotation.is_none() && scale.is_none() {
+        return None;
+    }
+    let t = translation.and_then(floats::<3>).unwrap_or([0.0; 3]);
+    let r = rotation.and_then(floats::<4>).unwrap_or([0.0, 0.0, 0.0, 1.0]);
+    let s = match scale {
+        Some(v) => v.as_f64().map(|s| [s as f32; 3]).or_else(|| floats::<3>(v)).unwrap_or([1.0; 3]),
+        None => [1.0; 3],
+    };
+    Some(NodeTransform::from_decomposed((t, r, s)).matrix())
+}
+
+fn entry_overrides(model: &serde_json::Value, base_dir: &Path) -> Vec<MaterialOverride> {
+    let Some(value) = model.get("material_overrides") else {
+        return Vec::new();
+    };
+    let list = match value.as_array() {
+        Some(list) => list.iter().collect(),
+        None => vec![value],
+    };
+    list.into_iter()
+        .map(|o| MaterialOverride {
+            material: o.get("material").and_then(|v| v.as_str()).map(str::to_string),
+            tint: o.get("tint").and_then(floats::<4>),
+            roughness_scale: o.get("roughness_scale").and_then(|v| v.as_f64()).map(|v| v as f32),
+            metallic_scale: o.get("metallic_scale").and_then(|v| v.as_f64()).map(|v| v as f32),
+            base_color_texture: o
+                .get("base_color_texture")
+                .and_then(|v| v.as_str())
+                .map(|p| base_dir.join(p)),
+            shadow_normal_offset: o.get("shadow_normal_offset").and_then(|v| v.as_f64()).map(|v| v as f32),
+        })
+        .collect()
+}
+
+/// Placement transform for each model, given its bounds in its own space.
+pub fn arrange(layout: Layout, bounds: &[Aabb], explicit: &[Option<Matrix4<f32>>]) -> Vec<Matrix4<f32>> {
+    let footprint = bounds
+        .iter()
+        .filter(|b| !b.is_empty())
+        .map(|b| b.extent().x.max(b.extent().z))
+        .fold(1.0f32, f32::max);
+    let cell = footprint + PADDING;
+    // Moves a model so its XZ centre lands on `(x, z)`, keeping its height.
+    let centred = |b: &Aabb, x: f32, z: f32| {
+        let c = if b.is_empty() { cgmath::Point3::new(0.0, 0.0, 0.0) } else { b.center() };
+        Matrix4::from_translation(Vector3::new(x - c.x, 0.0, z - c.z))
+    };
+
+    match layout {
+        Layout::Row => {
+            let mut offset_x = 0.0f32;
+            bounds
+                .iter()
+                .map(|b| {
+                    let m = Matrix4::from_translation(Vector3::new(offset_x, 0.0, 0.0));
+                    let width = if b.is_empty() { 1.0 } else { b.extent().x.max(1.0) };
+                    offset_x += width + PADDING;
+                    m
+                })
+                .collect()
+        }
+        Layout::Grid => {
+            let columns = (bounds.len() as f32).sqrt().ceil().max(1.0) as usize;
+            bounds
+                .iter()
+                .enumerate()
+                .map(|(i, b)| centred(b, (i % columns) as f32 * cell, (i / columns) as f32 * cell))
+                .collect()
+        }
+        Layout::Circle => {
+            let n = bounds.len();
+            let radius = if n > 1 {
+                // Neighbours one cell apart along the chord.
+                cell / (2.0 * (std::f32::consts::PI / n as f32).sin())
+            } else {
+                0.0
+            };
+            bounds
+                .iter()
+                .enumerate()
+                .map(|(i, b)| {
+                    let angle = std::f32::consts::TAU * i as f32 / n as f32;
+                    centred(b, radius * angle.cos(), radius * angle.sin())
+                })
+                .collect()
+        }
+        Layout::Origin => vec![Matrix4::identity(); bounds.len()],
+        Layout::Explicit => (0..bounds.len())
+            .map(|i| explicit.get(i).copied().flatten().unwrap_or_else(Matrix4::identity))
+            .collect(),
+    }
+}
@@ -0,0 +1,103 @@
+//! Dusk, a wgpu renderer for glTF scenes.
+//!
+//! [`App`] runs an [`Engine`], a [`Renderer`] with its plugins, in a winit window; the
+//! `dusk_engine` binary is little more than that. The renderer is built from
+//! [`RendererOptions`], which the binary parses from its command line (see the README for
+//! every option), and `dusk.toml`; it loads a [`Scene`] through an
+//! [`AssetServer`] and then draws a frame per [`Renderer::update`] and
+//! [`Renderer::render`]. Engine code can hook into picking with [`Renderer::on_pick`] and
+//! grab frames with [`Renderer::capture`].
+
+pub mod aabb;
+mod accessor;
+mod animation;
+mod asset_watcher;
+mod assets;
+mod atmosphere;
+mod batch;
+mod bindings;
+mod bindless;
+mod brdf_lut;
+mod bvh;
+mod camera;
+mod camera_path;
+pub mod cli;
+mod config;
+pub mod console;
+mod controller;
+pub mod crash;
+mod crowd;
+mod ddgi;
+mod debug_draw;
+mod debug_ui;
+mod debug_view;
+mod depth_bounds;
+mod draw_list;
+mod engine;
+mod evsm;
+mod exposure;
+mod exr;
+mod flare;
+mod fog;
+mod frame_pacing;
+mod fxaa;
+mod garbage;
+mod gbuffer;
+mod geometry;
+mod geometry_pool;
+mod gpu;
+mod gpu_culling;
+mod gpu_timer;
+mod hud;
+mod jobs;
+mod latency;
+pub mod layout;
+mod lightmap;
+pub mod lights;
+mod luminance;
+mod material;
+mod material_debug;
+pub mod model;
+mod motion_blur;
+mod objects;
+pub mod options;
+mod overdraw;
+pub mod picking;
+mod plugin;
+mod pipelines;
+mod post;
+mod post_stack;
+mod probes;
+mod profiler;
+mod reflections;
+mod renderer;
+mod rt_shadows;
+mod scene;
+mod screenshot;
+mod shadows;
+mod sky;
+pub mod snapshot;
+mod ssao;
+mod ssgi;
+mod sun;
+mod taa;
+mod texture_inspector;
+mod texture_streaming;
+mod upscale;
+mod vct;
+mod velocity;
+mod vertex_packing;
+pub mod viewport;
+mod window_mode;
+pub mod world;
+#[cfg(feature = "openxr")]
+mod xr;
+
+pub use assets::AssetServer;
+pub use camera::Camera;
+pub use engine::{App, Engine};
+pub use gpu::list_adapters;
+pub use options::RendererOptions;
+pub use plugin::{Plugin, RenderContext};
+pub use renderer::Renderer;
+pub use scene::{LoadedScene, Scene, SceneSettings, SceneSource};
@@ -0,0 +1,668 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use cgmath::{ElementWise, InnerSpace, Vector2, Vector3, Zero};
+use half::f16;
+
+use crate::bvh::Bvh;
+use crate::geometry::position_key;
+use crate::lights::DirectionalLight;
+use crate::model::{Mesh, Vertex};
+use crate::profiler;
+
+pub const DEFAULT_RESOLUTION: u32 = 1024;
+pub const DEFAULT_SAMPLES: u32 = 64;
+pub const DEFAULT_CACHE_PATH: &str = "dusk_lightmaps.bin";
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const BOUNCES: u32 = 3;
+/// Empty texels kept around every chart so filtering never reads a neighbour.
+const GUTTER: u32 = 2;
+/// Charts only grow over faces within about 41 degrees of the face they started from.
+const CHART_COS: f32 = 0.75;
+/// The environment map only lives on the GPU, so bakes see a uniform sky of this radiance,
+/// scaled by the environment luminance.
+const SKY_RADIANCE: [f32; 3] = [0.5, 0.6, 0.75];
+const MAGIC: &[u8; 4] = b"DLM1";
+
+/// A group of connected, roughly coplanar triangles flattened onto a common plane.
+struct Chart {
+    first_triangle: usize,
+    triangle_count: usize,
+    min: Vector2<f32>,
+    size: Vector2<f32>,
+}
+
+/// A mesh split along chart seams, with every vertex's position on its chart's plane.
+pub struct Unwrapped {
+    pub mesh: Mesh,
+    planar: Vec<Vector2<f32>>,
+    charts: Vec<Chart>,
+}
+
+/// Splits a mesh into planar charts for a lightmap UV set. Vertices shared by two charts
+/// are duplicated, so the returned mesh has its own vertex order.
+pub fn unwrap(mesh: &Mesh) -> Unwrapped {
+    let vertex_count = mesh.vertices.len();
+    let triangles: Vec<[u32; 3]> = mesh
+        .indices
+        .chunks_exact(3)
+        .map(|t| [t[0], t[1], t[2]])
+        .filter(|t| t.iter().all(|&i| (i as usize) < vertex_count))
+        .collect();
+    let position = |i: u32| Vector3::from(mesh.vertices[i as usize].position);
+    let unit = |n: Vector3<f32>| if n.magnitude2() > 0.0 { n.normalize() } else { Vector3::unit_y() };
+    // Unnormalized, so summing them weights by area.
+    let face_normals: Vec<Vector3<f32>> = triangles
+        .iter()
+        .map(|t| (position(t[1]) - position(t[0])).cross(position(t[2]) - position(t[0])))
+        .collect();
+
+    let edge_key = |tri: &[u32; 3], k: usize| {
+        let a = position_key(mesh.vertices[tri[k] as usize].position);
+        let b = position_key(mesh.vertices[tri[(k + 1) % 3] as usize].position);
+        if a < b {
+            (a, b)
+        } else {
+            (b, a)
+        }
+    };
+    let mut edges: HashMap<_, Vec<usize>> = HashMap::new();
+    for (t, tri) in triangles.iter().enumerate() {
+        for k in 0..3 {
+            edges.entry(edge_key(tri, k)).or_default().push(t);
+        }
+    }
+
+    let mut assigned = vec![false; triangles.len()];
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for seed in 0..triangles.len() {
+        if assigned[seed] {
+            continue;
+        }
+        let seed_normal = unit(face_normals[seed]);
+        assigned[seed] = true;
+        let mut members = vec![seed];
+        let mut next = 0;
+        while next < members.len() {
+            let tri = &triangles[members[next]];
+            next += 1;
+            for k in 0..3 {
+                for &other in &edges[&edge_key(tri, k)] {
+                    if !assigned[other] && unit(face_normals[other]).dot(seed_normal) > CHART_COS {
+                        assigned[other] = true;
+                        members.push(other);
+                    }
+                }
+            }
+        }
+        groups.push(members);
+    }
+
+    let mut out = Mesh {
+        name: mesh.name.clone(),
+        vertices: Vec::new(),
+        indices: Vec::with_capacity(triangles.len() * 3),
+        material_index: mesh.material_index,
+        node: mesh.node,
+        skin: mesh.skin,
+        joints: Vec::new(),
+        weights: Vec::new(),
+        tangents: Vec::new(),
+        shadow: mesh.shadow,
+        lods: Vec::new(),
+        transform: mesh.transform,
+        source: None,
+    };
+    let mut planar = Vec::new();
+    let mut charts = Vec::with_capacity(groups.len());
+    for members in groups {
+        let normal = unit(members.iter().fold(Vector3::zero(), |sum, &t| sum + face_normals[t]));
+        let axis = if normal.y.abs() < 0.9 { Vector3::unit_y() } else { Vector3::unit_x() };
+        let u = axis.cross(normal).normalize();
+        let v = normal.cross(u);
+
+        let first_vertex = out.vertices.len();
+        let first_triangle = out.indices.len() / 3;
+        let mut remap: HashMap<u32, u32> = HashMap::new();
+        for &t in &members {
+            for &i in &triangles[t] {
+                let index = *remap.entry(i).or_insert_with(|| {
+                    let source = i as usize;
+                    out.vertices.push(mesh.vertices[source]);
+                    if mesh.tangents.len() == vertex_count {
+                        out.tangents.push(mesh.tangents[source]);
+                    }
+                    if mesh.joints.len() == vertex_count {
+                        out.joints.push(mesh.joints[source]);
+                    }
+                    if mesh.weights.len() == vertex_count {
+                        out.weights.push(mesh.weights[source]);
+                    }
+                    let p = position(i);
+                    planar.push(Vector2::new(p.dot(u), p.dot(v)));
+                    (out.vertices.len() - 1) as u32
+                });
+                out.indices.push(index);
+            }
+        }
+
+        let mut min = Vector2::new(f32::MAX, f32::MAX);
+        let mut max = Vector2::new(f32::MIN, f32::MIN);
+        for p in &planar[first_vertex..] {
+            min = Vector2::new(min.x.min(p.x), min.y.min(p.y));
+            max = Vector2::new(max.x.max(p.x), max.y.max(p.y));
+        }
+        charts.push(Chart {
+            first_triangle,
+            triangle_count: members.len(),
+            min,
+            size: max - min,
+        });
+    }
+
+    Unwrapped { mesh: out, planar, charts }
+}
+
+struct LayoutMesh {
+    scene_mesh: usize,
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    planar: Vec<Vector2<f32>>,
+    charts: Vec<Chart>,
+}
+
+/// Collects the unwrapped meshes sharing one lightmap atlas.
+#[derive(Default)]
+pub struct LightmapLayout {
+    meshes: Vec<LayoutMesh>,
+}
+
+/// A lightmap texel to bake, at the centre of the texel on its surface.
+struct Texel {
+    index: usize,
+    position: Vector3<f32>,
+    normal: Vector3<f32>,
+}
+
+/// Atlas coordinates for every lightmapped mesh, and the texels their triangles cover.
+pub struct PackedLightmap {
+    pub resolution: u32,
+    /// Scene mesh index and one UV per vertex of its unwrapped mesh.
+    pub uvs: Vec<(usize, Vec<[f32; 2]>)>,
+    texels: Vec<Texel>,
+    /// World size of a texel.
+    texel_size: f32,
+}
+
+fn edge(a: Vector2<f32>, b: Vector2<f32>, p: Vector2<f32>) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+impl LightmapLayout {
+    pub fn add(&mut self, scene_mesh: usize, unwrapped: Unwrapped) {
+        self.meshes.push(LayoutMesh {
+            scene_mesh,
+            vertices: unwrapped.mesh.vertices,
+            indices: unwrapped.mesh.indices,
+            planar: unwrapped.planar,
+            charts: unwrapped.charts,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.meshes.is_empty()
+    }
+
+    /// Texel corner each chart's interior starts at, or None if they do not fit.
+    fn shelf_pack(&self, resolution: u32, texel_size: f32) -> Option<Vec<Vec<[u32; 2]>>> {
+        let extent = |chart: &Chart| {
+            let texels = |size: f32| (size / texel_size).ceil() as u32 + 1 + GUTTER * 2;
+            [texels(chart.size.x), texels(chart.size.y)]
+        };
+        let mut order: Vec<(usize, usize)> = self
+            .meshes
+            .iter()
+            .enumerate()
+            .flat_map(|(m, mesh)| (0..mesh.charts.len()).map(move |c| (m, c)))
+            .collect();
+        order.sort_by_key(|&(m, c)| std::cmp::Reverse(extent(&self.meshes[m].charts[c])[1]));
+
+        let mut origins: Vec<Vec<[u32; 2]>> = self.meshes.iter().map(|m| vec![[0, 0]; m.charts.len()]).collect();
+        let (mut x, mut y, mut shelf) = (0, 0, 0);
+        for (m, c) in order {
+            let [w, h] = extent(&self.meshes[m].charts[c]);
+            if w > resolution {
+                return None;
+            }
+            if x + w > resolution {
+                x = 0;
+                y += shelf;
+                shelf = 0;
+            }
+            if y + h > resolution {
+                return None;
+            }
+            origins[m][c] = [x + GUTTER, y + GUTTER];
+            x += w;
+            shelf = shelf.max(h);
+        }
+        Some(origins)
+    }
+
+    /// Packs every chart into a `resolution` square atlas at the finest texel size that
+    /// fits, and finds the texels to bake.
+    pub fn pack(self, resolution: u32) -> PackedLightmap {
+        let area: f32 = self
+            .meshes
+            .iter()
+            .flat_map(|m| &m.charts)
+            .map(|c| c.size.x * c.size.y)
+            .sum();
+        let mut texel_size = (area / (resolution * resolution) as f32 * 2.0).sqrt().max(1e-4);
+        let origins = loop {
+            if let Some(origins) = self.shelf_pack(resolution, texel_size) {
+                break origins;
+            }
+            texel_size *= 1.1;
+        };
+
+        let size = resolution as usize;
+        let mut covered = vec![false; size * size];
+        let mut uvs = Vec::with_capacity(self.meshes.len());
+        let mut texels = Vec::new();
+        for (mesh, origins) in self.meshes.iter().zip(origins) {
+            let mut pixels = vec![Vector2::zero(); mesh.vertices.len()];
+            for (chart, origin) in mesh.charts.iter().zip(origins) {
+                let origin = Vector2::new(origin[0] as f32, origin[1] as f32) + Vector2::new(0.5, 0.5);
+                let indices = &mesh.indices[chart.first_triangle * 3..(chart.first_triangle + chart.triangle_count) * 3];
+                for &i in indices {
+                    pixels[i as usize] = origin + (mesh.planar[i as usize] - chart.min) / texel_size;
+                }
+                for tri in indices.chunks_exact(3) {
+                    let [a, b, c] = [0, 1, 2].map(|k| pixels[tri[k] as usize]);
+                    let area = edge(a, b, c);
+                    if area.abs() < 1e-8 {
+                        continue;
+                    }
+                    let vertex = |k: usize| &mesh.vertices[tri[k] as usize];
+                    let min_x = a.x.min(b.x).min(c.x).floor().max(0.0) as usize;
+                    let min_y = a.y.min(b.y).min(c.y).floor().max(0.0) as usize;
+                    let max_x = (a.x.max(b.x).max(c.x).ceil() as usize).min(size);
+                    let max_y = (a.y.max(b.y).max(c.y).ceil() as usize).min(size);
+                    for ty in min_y..max_y {
+                        for tx in min_x..max_x {
+                            let index = ty * size + tx;
+                            let p = Vector2::new(tx as f32 + 0.5, ty as f32 + 0.5);
+                            let w = [edge(b, c, p) / area, edge(c, a, p) / area, edge(a, b, p) / area];
+                            if covered[index] || w.iter().any(|&w| w < -1e-4) {
+                                continue;
+                            }
+                            covered[index] = true;
+                            let mut position = Vector3::zero();
+                            let mut normal = Vector3::zero();
+                            for (k, w) in w.iter().enumerate() {
+                                position += Vector3::from(vertex(k).position) * *w;
+                                normal += Vector3::from(vertex(k).normal) * *w;
+                            }
+                            if normal.magnitude2() == 0.0 {
+                                continue;
+                            }
+                            texels.push(Texel {
+                                index,
+                                position,
+                                normal: normal.normalize(),
+                            });
+                        }
+                    }
+                }
+            }
+            let scale = 1.0 / resolution as f32;
+            uvs.push((mesh.scene_mesh, pixels.iter().map(|p| [p.x * scale, p.y * scale]).collect()));
+        }
+
+        PackedLightmap {
+            resolution,
+            uvs,
+            texels,
+            texel_size,
+        }
+    }
+}
+
+/// Light the bake sees; it stays fixed once baked.
+#[derive(Copy, Clone)]
+struct BakeLight {
+    /// Towards the sun.
+    to_sun: Vector3<f32>,
+    sun_radiance: Vector3<f32>,
+    sky_radiance: Vector3<f32>,
+}
+
+struct Rng(u32);
+
+impl Rng {
+    fn next(&mut self) -> f32 {
+        // xorshift32
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as f32 / (1u32 << 24) as f32
+    }
+}
+
+fn cosine_sample(n: Vector3<f32>, rng: &mut Rng) -> Vector3<f32> {
+    let (u1, u2) = (rng.next(), rng.next());
+    let r = u1.sqrt();
+    let phi = std::f32::consts::TAU * u2;
+    let axis = if n.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+    let t = axis.cross(n).normalize();
+    let b = n.cross(t);
+    (t * (r * phi.cos()) + b * (r * phi.sin()) + n * (1.0 - u1).max(0.0).sqrt()).normalize()
+}
+
+/// Diffuse light reaching the texel, as the radiance of a uniform sky giving the same
+/// irradiance, so it can stand in for the environment lookup in shader.wgsl.
+fn trace_texel(bvh: &Bvh, texel: &Texel, samples: u32, light: BakeLight, epsilon: f32) -> Vector3<f32> {
+    let sky = light.sky_radiance;
+    let mut rng = Rng((texel.index as u32).wrapping_mul(0x9E37_79B9) | 1);
+    let mut sum = Vector3::zero();
+    for _ in 0..samples {
+        let mut origin = texel.position + texel.normal * epsilon;
+        let mut normal = texel.normal;
+        let mut throughput = Vector3::new(1.0, 1.0, 1.0);
+        for _ in 0..BOUNCES {
+            let dir = cosine_sample(normal, &mut rng);
+            let Some(hit) = bvh.intersect(origin, dir, f32::INFINITY) else {
+                sum += throughput.mul_element_wise(sky);
+                break;
+            };
+            let albedo = bvh.triangles[hit.triangle].albedo;
+            throughput = throughput.mul_element_wise(Vector3::new(albedo[0], albedo[1], albedo[2]));
+            origin += dir * hit.t + hit.normal * epsilon;
+            normal = hit.normal;
+            let n_dot_l = normal.dot(light.to_sun);
+            if n_dot_l > 0.0 && !bvh.occluded(origin, light.to_sun, f32::INFINITY) {
+                sum += throughput.mul_element_wise(light.sun_radiance) * (n_dot_l / std::f32::consts::PI);
+            }
+        }
+    }
+    sum / samples.max(1) as f32
+}
+
+/// Path traces every texel across all cores, then bleeds the results into the gutters.
+/// Returns half float RGBA bits.
+fn bake(bvh: &Bvh, packed: &PackedLightmap, samples: u32, light: BakeLight) -> Vec<u16> {
+    let root = &bvh.nodes[0];
+    let diagonal = Vector3::from(root.max) - Vector3::from(root.min);
+    let epsilon = (diagonal.magnitude() * 1e-5).max(packed.texel_size * 0.05);
+
+    let threads = std::thread::available_parallelism().map_or(4, |n| n.get());
+    let chunk = packed.texels.len().div_ceil(threads).max(1);
+    let radiance: Vec<Vector3<f32>> = std::thread::scope(|scope| {
+        let jobs: Vec<_> = packed
+            .texels
+            .chunks(chunk)
+            .map(|texels| {
+                scope.spawn(move || {
+                    texels
+                        .iter()
+                        .map(|t| trace_texel(bvh, t, samples, light, epsilon))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        jobs.into_iter().flat_map(|job| job.join().unwrap_or_default()).collect()
+    });
+
+    let size = packed.resolution as usize;
+    let mut pixels = vec![[0.0f32; 4]; size * size];
+    for (texel, radiance) in packed.texels.iter().zip(radiance) {
+        pixels[texel.index] = [radiance.x, radiance.y, radiance.z, 1.0];
+    }
+    for _ in 0..=GUTTER {
+        let source = pixels.clone();
+        for y in 0..size {
+            for x in 0..size {
+                if source[y * size + x][3] > 0.0 {
+                    continue;
+                }
+                let mut sum = [0.0f32; 4];
+                for (dx, dy) in [(-1i32, 0i32), (1, 0), (0, -1), (0, 1)] {
+                    let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                    if nx < 0 || ny < 0 || nx >= size as i32 || ny >= size as i32 {
+                        continue;
+                    }
+                    let n = source[ny as usize * size + nx as usize];
+                    if n[3] > 0.0 {
+                        for (s, n) in sum.iter_mut().zip(n) {
+                            *s += n;
+                        }
+                    }
+                }
+                if sum[3] > 0.0 {
+                    pixels[y * size + x] = [sum[0] / sum[3], sum[1] / sum[3], sum[2] / sum[3], 1.0];
+                }
+            }
+        }
+    }
+    pixels.iter().flatten().map(|&c| f16::from_f32(c).to_bits()).collect()
+}
+
+/// FNV-1a over everything the bake depends on.
+fn cache_key(bvh: &Bvh, packed: &PackedLightmap, samples: u32, light: BakeLight) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    let mut feed = |bytes: &[u8]| {
+        for &b in bytes {
+            hash = (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    };
+    feed(&packed.resolution.to_le_bytes());
+    feed(&samples.to_le_bytes());
+    feed(&BOUNCES.to_le_bytes());
+    feed(bytemuck::cast_slice(&bvh.triangles));
+    for texel in &packed.texels {
+        feed(&(texel.index as u64).to_le_bytes());
+        let position: [f32; 3] = texel.position.into();
+        feed(bytemuck::cast_slice(&position));
+    }
+    let light: [f32; 9] = [
+        light.to_sun.x,
+        light.to_sun.y,
+        light.to_sun.z,
+        light.sun_radiance.x,
+        light.sun_radiance.y,
+        light.sun_radiance.z,
+        light.sky_radiance.x,
+        light.sky_radiance.y,
+        light.sky_radiance.z,
+    ];
+    feed(bytemuck::cast_slice(&light));
+    hash
+}
+
+fn read_cache(path: &Path, resolution: u32, key: u64) -> Option<Vec<u16>> {
+    let bytes = std::fs::read(path).ok()?;
+    if bytes.len() < 16 {
+        return None;
+    }
+    let (header, data) = bytes.split_at(16);
+    let size = u32::from_le_bytes(header[4..8].try_into().ok()?);
+    let stored = u64::from_le_bytes(header[8..16].try_into().ok()?);
+    if &header[..4] != MAGIC || size != resolution || stored != key {
+        return None;
+    }
+    let texels = (size * size * 4) as usize;
+    (data.len() == texels * 2).then(|| {
+        data.chunks_exact(2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .collect()
+    })
+}
+
+fn write_cache(path: &Path, resolution: u32, key: u64, data: &[u16]) -> Result<()> {
+    let mut bytes = Vec::with_capacity(16 + data.len() * 2);
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&resolution.to_le_bytes());
+    bytes.extend_from_slice(&key.to_le_bytes());
+    for c in data {
+        bytes.extend_from_slice(&c.to_le_bytes());
+    }
+    std::fs::write(path, bytes).with_context(|| format!("writing lightmaps {}", path.display()))
+}
+
+/// Baked sky and bounce light for the static scene, sampled through each mesh's
+/// lightmap UVs in place of the probes and environment. Bakes run on a background
+/// thread and are cached on disk, keyed by the scene, atlas and sun.
+pub struct Lightmaps {
+    pub enabled: bool,
+    resolution: u32,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    pending: Option<JoinHandle<Vec<u16>>>,
+    baked: bool,
+}
+
+impl Lightmaps {
+    /// Loads the cached bake for this layout if it is current, otherwise starts baking
+    /// with `samples` paths per texel. The bake is in physical units, with the sky at
+    /// `env_nits`, and is exposed when sampled.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        packed: PackedLightmap,
+        bvh: Bvh,
+        samples: u32,
+        sun: Option<&DirectionalLight>,
+        env_nits: f32,
+    ) -> Self {
+        let resolution = packed.resolution;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Lightmap"),
+            size: wgpu::Extent3d {
+                width: resolution,
+                height: resolution,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sky_radiance = Vector3::from(SKY_RADIANCE) * env_nits;
+        let light = match sun {
+            Some(sun) => BakeLight {
+                to_sun: -sun.direction.normalize(),
+                sun_radiance: Vector3::from(sun.color) * sun.intensity,
+                sky_radiance,
+            },
+            None => BakeLight {
+                to_sun: Vector3::unit_y(),
+                sun_radiance: Vector3::zero(),
+                sky_radiance,
+            },
+        };
+        let mut lightmaps = Self {
+            enabled: true,
+            resolution,
+            texture,
+            view,
+            pending: None,
+            baked: false,
+        };
+
+        let path = Path::new(DEFAULT_CACHE_PATH);
+        let key = cache_key(&bvh, &packed, samples, light);
+        if let Some(data) = read_cache(path, resolution, key) {
+            log::info!("Loaded lightmaps from {}", path.display());
+            lightmaps.upload(queue, &data);
+            return lightmaps;
+        }
+        if bvh.triangles.is_empty() {
+            return lightmaps;
+        }
+
+        log::info!(
+            "Baking {} lightmap texels ({}x{}, {} samples) in the background",
+            packed.texels.len(),
+            resolution,
+            resolution,
+            samples
+        );
+        lightmaps.pending = Some(std::thread::spawn(move || {
+            let _span = profiler::scope("bake lightmaps");
+            let start = Instant::now();
+            let data = bake(&bvh, &packed, samples, light);
+            log::info!("Baked lightmaps in {:.1}s", start.elapsed().as_secs_f32());
+            if let Err(e) = write_cache(Path::new(DEFAULT_CACHE_PATH), resolution, key, &data) {
+                log::warn!("Could not cache lightmaps: {:#}", e);
+            }
+            data
+        }));
+        lightmaps
+    }
+
+    fn upload(&mut self, queue: &wgpu::Queue, data: &[u16]) {
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(data),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(self.resolution * 8),
+                rows_per_image: None,
+            },
+            self.texture.size(),
+        );
+        self.baked = true;
+    }
+
+    /// Uploads the bake once the background thread has finished it.
+    pub fn poll(&mut self, queue: &wgpu::Queue) {
+        if !self.pending.as_ref().is_some_and(|job| job.is_finished()) {
+            return;
+        }
+        match self.pending.take().map(JoinHandle::join) {
+            Some(Ok(data)) => self.upload(queue, &data),
+            Some(Err(_)) => log::warn!("Lightmap bake failed"),
+            None => {}
+        }
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// x: 1 when the lightmaps are baked and enabled, y: `exposure`.
+    pub fn camera_params(&self, exposure: f32) -> [f32; 4] {
+        [if self.enabled && self.baked { 1.0 } else { 0.0 }, exposure, 0.0, 0.0]
+    }
+}
+
+pub fn placeholder_view(device: &wgpu::Device) -> wgpu::TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some("Lightmap Placeholder"),
+            size: wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
@@ -0,0 +1,878 @@
+use anyhow::Context;
+use cgmath::{InnerSpace, Matrix4, Point3, Transform, Vector3};
+use wgpu::util::DeviceExt;
+
+use crate::camera::{opengl_to_wgpu_matrix, CameraUniform};
+use crate::objects::SceneObjects;
+
+pub const MAX_SHADOWED_POINT_LIGHTS: usize = 4;
+pub const MAX_SPOT_LIGHTS: usize = 64;
+pub const MAX_DIRECTIONAL_LIGHTS: usize = 8;
+const POINT_SHADOW_SIZE: u32 = 1024;
+const POINT_SHADOW_NEAR: f32 = 0.05;
+/// The spot shadow atlas is a grid of `SPOT_ATLAS_TILES`² square tiles.
+const SPOT_ATLAS_TILES: u32 = 4;
+const SPOT_TILE_SIZE: u32 = 1024;
+const MAX_SHADOWED_SPOT_LIGHTS: usize = (SPOT_ATLAS_TILES * SPOT_ATLAS_TILES) as usize;
+/// Illuminance in lux below which a light without an authored range stops contributing.
+const RANGE_CUTOFF: f32 = 0.05;
+/// Cookies are resampled to this square size so they fit one texture array.
+pub const COOKIE_SIZE: u32 = 512;
+/// World units one repeat of a directional light's cookie covers by default.
+pub const DEFAULT_COOKIE_SIZE: f32 = 10.0;
+/// Illuminance of the default sun in lux, a clear midday.
+pub const DEFAULT_SUN_LUX: f32 = 120_000.0;
+
+/// Sun-like light; the first one is the primary light and gets the shadow cascades.
+#[derive(Copy, Clone, Debug)]
+pub struct DirectionalLight {
+    /// Direction the light travels in.
+    pub direction: Vector3<f32>,
+    pub color: [f32; 3],
+    /// Illuminance in lux on a surface facing the light.
+    pub intensity: f32,
+    /// Index into [`LightSet::cookies`], tiled across the world perpendicular to the light.
+    pub cookie: Option<usize>,
+    /// World units one repeat of the cookie covers.
+    pub cookie_size: f32,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vector3<f32>, color: [f32; 3], intensity: f32) -> Self {
+        Self {
+            direction,
+            color,
+            intensity,
+            cookie: None,
+            cookie_size: DEFAULT_COOKIE_SIZE,
+        }
+    }
+
+    /// The light used when a scene defines none.
+    pub fn sun() -> Self {
+        Self::new(-Vector3::unit_y(), [1.0; 3], DEFAULT_SUN_LUX)
+    }
+}
+
+/// Omnidirectional light; `intensity` is in candela like glTF's KHR_lights_punctual.
+#[derive(Copy, Clone, Debug)]
+pub struct PointLight {
+    pub position: Point3<f32>,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub range: f32,
+    pub cast_shadows: bool,
+}
+
+impl PointLight {
+    pub fn new(position: Point3<f32>, color: [f32; 3], intensity: f32, range: Option<f32>) -> Self {
+        let range = range
+            .filter(|r| *r > 0.0)
+            .unwrap_or_else(|| (intensity.max(0.0) / RANGE_CUTOFF).sqrt())
+            .max(0.1);
+        Self {
+            position,
+            color,
+            intensity,
+            range,
+            cast_shadows: true,
+        }
+    }
+
+    /// Candela of a bulb emitting `lumens` evenly in every direction.
+    pub fn candela_from_lumens(lumens: f32) -> f32 {
+        lumens / (4.0 * std::f32::consts::PI)
+    }
+}
+
+/// Cone light; `inner_angle`/`outer_angle` are half-angles in radians from `direction`
+/// and `intensity` is in candela.
+#[derive(Copy, Clone, Debug)]
+pub struct SpotLight {
+    pub position: Point3<f32>,
+    pub direction: Vector3<f32>,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub range: f32,
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+    pub cast_shadows: bool,
+    /// Index into [`LightSet::cookies`], projected across the light's cone.
+    pub cookie: Option<usize>,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Point3<f32>,
+        direction: Vector3<f32>,
+        color: [f32; 3],
+        intensity: f32,
+        range: Option<f32>,
+    ) -> Self {
+        let point = PointLight::new(position, color, intensity, range);
+        Self {
+            position,
+            direction,
+            color,
+            intensity,
+            range: point.range,
+            inner_angle: 0.0,
+            outer_angle: std::f32::consts::FRAC_PI_4,
+            cast_shadows: true,
+            cookie: None,
+        }
+    }
+
+    /// Candela of a spot emitting `lumens`; like Filament this ignores the cone angle, so
+    /// narrowing the cone does not brighten the light.
+    pub fn candela_from_lumens(lumens: f32) -> f32 {
+        lumens / std::f32::consts::PI
+    }
+
+    fn view_proj(&self) -> Matrix4<f32> {
+        let dir = if self.direction.magnitude2() > 0.0 { self.direction.normalize() } else { -Vector3::unit_y() };
+        let up = if dir.y.abs() > 0.99 { Vector3::unit_z() } else { Vector3::unit_y() };
+        let fov = (self.outer_angle * 2.0).clamp(0.02, 2.97);
+        opengl_to_wgpu_matrix()
+            * cgmath::perspective(cgmath::Rad(fov), 1.0, POINT_SHADOW_NEAR, self.range.max(POINT_SHADOW_NEAR * 2.0))
+            * Matrix4::look_at_rh(self.position, self.position + dir, up)
+    }
+}
+
+/// Lights gathered from models and scene files before the GPU side exists.
+#[derive(Clone, Debug, Default)]
+pub struct LightSet {
+    pub directional: Vec<DirectionalLight>,
+    pub points: Vec<PointLight>,
+    pub spots: Vec<SpotLight>,
+    /// Cookie images referenced by the lights, all `COOKIE_SIZE` square.
+    pub cookies: Vec<image::RgbaImage>,
+}
+
+impl LightSet {
+    pub fn extend(&mut self, other: &LightSet) {
+        let offset = self.cookies.len();
+        self.directional.extend(other.directional.iter().map(|l| DirectionalLight {
+            cookie: l.cookie.map(|c| c + offset),
+            ..*l
+        }));
+        self.points.extend_from_slice(&other.points);
+        self.spots.extend(other.spots.iter().map(|l| SpotLight {
+            cookie: l.cookie.map(|c| c + offset),
+            ..*l
+        }));
+        self.cookies.extend_from_slice(&other.cookies);
+    }
+
+    /// Loads a cookie image, resampled to `COOKIE_SIZE`, and returns its index.
+    pub fn add_cookie(&mut self, path: &std::path::Path) -> anyhow::Result<usize> {
+        let image = image::open(path)
+            .with_context(|| format!("loading light cookie {}", path.display()))?
+            .resize_exact(COOKIE_SIZE, COOKIE_SIZE, image::imageops::FilterType::Triangle)
+            .to_rgba8();
+        self.cookies.push(image);
+        Ok(self.cookies.len() - 1)
+    }
+
+    pub fn transform(&mut self, m: Matrix4<f32>) {
+        for light in &mut self.directional {
+            light.direction = m.transform_vector(light.direction).normalize();
+        }
+        for light in &mut self.points {
+            light.position = m.transform_point(light.position);
+        }
+        for light in &mut self.spots {
+            light.position = m.transform_point(light.position);
+            light.direction = m.transform_vector(light.direction).normalize();
+        }
+    }
+}
+
+/// Handle returned by [`Lights::add_spot`]; stays valid until the light is removed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SpotLightId(usize);
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct PointLightGpu {
+    position: [f32; 3],
+    range: f32,
+    color: [f32; 3],
+    intensity: f32,
+    shadow_layer: i32,
+    _pad: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct DirectionalLightGpu {
+    direction: [f32; 3],
+    intensity: f32,
+    color: [f32; 3],
+    cookie: i32,
+    cookie_size: f32,
+    _pad: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct SpotLightGpu {
+    position: [f32; 3],
+    range: f32,
+    direction: [f32; 3],
+    intensity: f32,
+    color: [f32; 3],
+    cos_outer: f32,
+    cos_inner: f32,
+    shadow_tile: i32,
+    cookie: i32,
+    _pad: u32,
+    view_proj: [[f32; 4]; 4],
+}
+
+/// One face of a point light's shadow cube, rendered like a shadow cascade.
+pub struct ShadowFace {
+    pub view: wgpu::TextureView,
+    buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+    pub light_position: Point3<f32>,
+    pub range: f32,
+}
+
+/// A spot light's tile in the shadow atlas, valid until the next [`Lights::upload`].
+pub struct SpotShadowTile<'a> {
+    /// Viewport in atlas pixels: x, y, size.
+    pub viewport: (f32, f32, f32),
+    pub bind_group: &'a wgpu::BindGroup,
+    pub light_position: Point3<f32>,
+    pub range: f32,
+}
+
+struct SpotTileCamera {
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+/// A shadow camera's bind group: its uniform and the scene object table.
+fn caster_bind_group(
+    device: &wgpu::Device,
+    (caster_layout, label): (&wgpu::BindGroupLayout, &str),
+    buffer: &wgpu::Buffer,
+    objects: &SceneObjects,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: caster_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            },
+            objects.entry(),
+        ],
+        label: Some(label),
+    })
+}
+
+/// View-projections of the six cube faces in +X, -X, +Y, -Y, +Z, -Z order.
+fn face_view_projs(light: &PointLight) -> [Matrix4<f32>; 6] {
+    let proj = opengl_to_wgpu_matrix()
+        * cgmath::perspective(cgmath::Deg(90.0), 1.0, POINT_SHADOW_NEAR, light.range.max(POINT_SHADOW_NEAR * 2.0));
+    let faces = [
+        (Vector3::unit_x(), Vector3::unit_y()),
+        (-Vector3::unit_x(), Vector3::unit_y()),
+        (Vector3::unit_y(), Vector3::unit_z()),
+        (-Vector3::unit_y(), Vector3::unit_z()),
+        (Vector3::unit_z(), Vector3::unit_y()),
+        (-Vector3::unit_z(), Vector3::unit_y()),
+    ];
+    faces.map(|(dir, up)| proj * Matrix4::look_at_rh(light.position, light.position + dir, up))
+}
+
+/// Directional lights, point lights (fixed after load) and spot lights (editable at
+/// runtime) with their shadow maps, bound together as one bind group. The primary
+/// directional light's cascades live with the camera.
+pub struct Lights {
+    pub layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    directional: Vec<DirectionalLight>,
+    directional_dirty: bool,
+    directional_buffer: wgpu::Buffer,
+    pub point_shadow_faces: Vec<ShadowFace>,
+    points: Vec<PointLightGpu>,
+    points_dirty: bool,
+    point_buffer: wgpu::Buffer,
+    spots: Vec<Option<SpotLight>>,
+    spots_dirty: bool,
+    spot_buffer: wgpu::Buffer,
+    spot_tile_cameras: Vec<SpotTileCamera>,
+    /// Slot of the spot light rendered into each atlas tile.
+    spot_tiles: Vec<usize>,
+    pub spot_atlas_view: wgpu::TextureView,
+    cookie_count: usize,
+    /// Multiplies every intensity on upload; see [`crate::camera::exposure`].
+    exposure: f32,
+}
+
+impl Lights {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        (caster_layout, objects): (&wgpu::BindGroupLayout, &SceneObjects),
+        set: &LightSet,
+    ) -> Self {
+        let lights = &set.points;
+        let mut gpu_lights = Vec::with_capacity(lights.len());
+        let mut matrices: Vec<[[f32; 4]; 4]> = vec![Matrix4::from_scale(1.0).into(); MAX_SHADOWED_POINT_LIGHTS * 6];
+        let mut shadowed: Vec<&PointLight> = Vec::new();
+        for light in lights {
+            let mut shadow_layer = -1;
+            if light.cast_shadows && shadowed.len() < MAX_SHADOWED_POINT_LIGHTS {
+                shadow_layer = (shadowed.len() * 6) as i32;
+                for (face, m) in face_view_projs(light).into_iter().enumerate() {
+                    matrices[shadow_layer as usize + face] = m.into();
+                }
+                shadowed.push(light);
+            }
+            gpu_lights.push(PointLightGpu {
+                position: light.position.into(),
+                range: light.range,
+                color: light.color,
+                intensity: light.intensity,
+                shadow_layer,
+                _pad: [0; 3],
+            });
+        }
+        let shadowed_count = lights.iter().filter(|l| l.cast_shadows).count();
+        if shadowed_count > MAX_SHADOWED_POINT_LIGHTS {
+            log::warn!(
+                "{} point lights want shadows; only the first {} get shadow maps",
+                shadowed_count,
+                MAX_SHADOWED_POINT_LIGHTS
+            );
+        }
+
+        // Filled by `upload`, which scales the intensities by the exposure.
+        let point_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Point Light Buffer"),
+            size: (16 + gpu_lights.len().max(1) * std::mem::size_of::<PointLightGpu>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let matrix_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Point Shadow Matrix Buffer"),
+            contents: bytemuck::cast_slice(&matrices),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let shadow_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Point Shadow Maps"),
+            size: wgpu::Extent3d {
+                width: POINT_SHADOW_SIZE,
+                height: POINT_SHADOW_SIZE,
+                depth_or_array_layers: (MAX_SHADOWED_POINT_LIGHTS * 6) as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let shadow_view = shadow_texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Point Shadow Maps View"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let shadow_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Point Shadow Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let directional_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Directional Light Buffer"),
+            size: (16 + MAX_DIRECTIONAL_LIGHTS * std::mem::size_of::<DirectionalLightGpu>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let spot_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Spot Light Buffer"),
+            size: (16 + MAX_SPOT_LIGHTS * std::mem::size_of::<SpotLightGpu>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let spot_atlas = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Spot Shadow Atlas"),
+            size: wgpu::Extent3d {
+                width: SPOT_ATLAS_TILES * SPOT_TILE_SIZE,
+                height: SPOT_ATLAS_TILES * SPOT_TILE_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let spot_atlas_view = spot_atlas.create_view(&wgpu::TextureViewDescriptor::default());
+        let cookie_view = create_cookie_array(device, queue, &set.cookies);
+        let cookie_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Light Cookie Sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let spot_tile_cameras = (0..MAX_SHADOWED_SPOT_LIGHTS)
+            .map(|tile| {
+                let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("Spot Shadow Camera Buffer {}", tile)),
+                    contents: bytemuck::cast_slice(&[CameraUniform::new()]),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                });
+                let label = format!("spot_shadow_camera_bind_group {}", tile);
+                let bind_group = caster_bind_group(device, (caster_layout, &label), &buffer, objects);
+                SpotTileCamera { buffer, bind_group }
+            })
+            .collect();
+
+        let mut shadow_faces = Vec::new();
+        for (slot, light) in shadowed.iter().enumerate() {
+            for face in 0..6 {
+                let layer = slot * 6 + face;
+                let mut uniform = CameraUniform::new();
+                uniform.light_view_proj = matrices[layer];
+                let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("Point Shadow Camera Buffer {}", layer)),
+                    contents: bytemuck::cast_slice(&[uniform]),
+                    usage: wgpu::BufferUsages::UNIFORM,
+                });
+                let label = format!("point_shadow_camera_bind_group {}", layer);
+                let bind_group = caster_bind_group(device, (caster_layout, &label), &buffer, objects);
+                let view = shadow_texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some(&format!("Point Shadow Layer {}", layer)),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer as u32,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                });
+                shadow_faces.push(ShadowFace {
+                    view,
+                    buffer,
+                    bind_group,
+                    light_position: light.position,
+                    range: light.range,
+                });
+            }
+        }
+
+        let storage = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("light_bind_group_layout"),
+            entries: &[
+                storage(0),
+                storage(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                },
+                storage(4),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                storage(6),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 7,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 8,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("light_bind_group"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: point_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: matrix_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&shadow_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&shadow_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: spot_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(&spot_atlas_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: directional_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: wgpu::BindingResource::TextureView(&cookie_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: wgpu::BindingResource::Sampler(&cookie_sampler),
+                },
+            ],
+        });
+
+        let mut result = Self {
+            layout,
+            bind_group,
+            directional: Vec::new(),
+            directional_dirty: true,
+            directional_buffer,
+            point_shadow_faces: shadow_faces,
+            points: gpu_lights,
+            points_dirty: true,
+            point_buffer,
+            spots: Vec::new(),
+            spots_dirty: true,
+            spot_buffer,
+            spot_tile_cameras,
+            spot_tiles: Vec::new(),
+            spot_atlas_view,
+            cookie_count: set.cookies.len(),
+            exposure: 1.0,
+        };
+        result.set_directional(&set.directional);
+        for spot in &set.spots {
+            result.add_spot(*spot);
+        }
+        result
+    }
+
+    /// Binds the shadow cameras to `objects`, a new object table for the scene meshes.
+    pub fn bind_objects(
+        &mut self,
+        device: &wgpu::Device,
+        caster_layout: &wgpu::BindGroupLayout,
+        objects: &SceneObjects,
+    ) {
+        for (tile, camera) in self.spot_tile_cameras.iter_mut().enumerate() {
+            let label = format!("spot_shadow_camera_bind_group {}", tile);
+            camera.bind_group = caster_bind_group(device, (caster_layout, &label), &camera.buffer, objects);
+        }
+        for (layer, face) in self.point_shadow_faces.iter_mut().enumerate() {
+            let label = format!("point_shadow_camera_bind_group {}", layer);
+            face.bind_group = caster_bind_group(device, (caster_layout, &label), &face.buffer, objects);
+        }
+    }
+
+    pub fn directional(&self) -> &[DirectionalLight] {
+        &self.directional
+    }
+
+    /// Replaces the directional lights. Lights past the limit are dropped, and an empty
+    /// list falls back to [`DirectionalLight::sun`] so the cascades always have a light.
+    pub fn set_directional(&mut self, lights: &[DirectionalLight]) {
+        if lights.len() > MAX_DIRECTIONAL_LIGHTS {
+            log::warn!("{} directional lights; only the first {} are used", lights.len(), MAX_DIRECTIONAL_LIGHTS);
+        }
+        self.directional = lights.iter().take(MAX_DIRECTIONAL_LIGHTS).copied().collect();
+        if self.directional.is_empty() {
+            self.directional.push(DirectionalLight::sun());
+        }
+        self.directional_dirty = true;
+    }
+
+    /// The primary light with the exposure applied, for passes that light the scene
+    /// themselves.
+    pub fn exposed_primary(&self) -> DirectionalLight {
+        let light = self.directional[0];
+        DirectionalLight {
+            intensity: light.intensity * self.exposure,
+            ..light
+        }
+    }
+
+    /// Direction of the primary light, which drives the shadow cascades.
+    pub fn primary_direction(&self) -> Vector3<f32> {
+        self.directional[0].direction.normalize()
+    }
+
+    /// Position, range and color of each point light.
+    pub fn point_ranges(&self) -> impl Iterator<Item = (Point3<f32>, f32, [f32; 3])> + '_ {
+        self.points.iter().map(|p| (Point3::from(p.position), p.range, p.color))
+    }
+
+    pub fn point_count(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn spots(&self) -> impl Iterator<Item = &SpotLight> {
+        self.spots.iter().flatten()
+    }
+
+    pub fn spot_count(&self) -> usize {
+        self.spots.iter().flatten().count()
+    }
+
+    pub fn add_spot(&mut self, light: SpotLight) -> Option<SpotLightId> {
+        let slot = match self.spots.iter().position(Option::is_none) {
+            Some(slot) => slot,
+            None if self.spots.len() < MAX_SPOT_LIGHTS => {
+                self.spots.push(None);
+                self.spots.len() - 1
+            }
+            None => {
+                log::warn!("Spot light limit ({}) reached", MAX_SPOT_LIGHTS);
+                return None;
+            }
+        };
+        self.spots[slot] = Some(light);
+        self.spots_dirty = true;
+        Some(SpotLightId(slot))
+    }
+
+    pub fn remove_spot(&mut self, id: SpotLightId) -> Option<SpotLight> {
+        let light = self.spots.get_mut(id.0)?.take();
+        self.spots_dirty |= light.is_some();
+        light
+    }
+
+    /// Mutable access for animating a light; the change is uploaded on the next frame.
+    pub fn spot_mut(&mut self, id: SpotLightId) -> Option<&mut SpotLight> {
+        let light = self.spots.get_mut(id.0)?.as_mut()?;
+        self.spots_dirty = true;
+        Some(light)
+    }
+
+    pub fn spot_lights(&self) -> impl Iterator<Item = (SpotLightId, &SpotLight)> {
+        self.spots.iter().enumerate().filter_map(|(slot, l)| Some((SpotLightId(slot), l.as_ref()?)))
+    }
+
+    pub fn clear_spots(&mut self) {
+        self.spots.clear();
+        self.spots_dirty = true;
+    }
+
+    /// Sets the exposure the uploaded intensities are scaled by, re-uploading every light
+    /// when it changes.
+    pub fn set_exposure(&mut self, exposure: f32) {
+        if exposure != self.exposure {
+            self.exposure = exposure;
+            self.directional_dirty = true;
+            self.points_dirty = true;
+            self.spots_dirty = true;
+        }
+    }
+
+    /// Cookie layer for the GPU, or -1 when the light has none or it was never loaded.
+    fn cookie_layer(&self, cookie: Option<usize>) -> i32 {
+        cookie.filter(|&c| c < self.cookie_count).map_or(-1, |c| c as i32)
+    }
+
+    /// Writes changed lights and spot shadow cameras; call once per frame before rendering.
+    pub fn upload(&mut self, queue: &wgpu::Queue) {
+        if self.directional_dirty {
+            self.directional_dirty = false;
+            let gpu: Vec<DirectionalLightGpu> = self
+                .directional
+                .iter()
+                .map(|l| DirectionalLightGpu {
+                    direction: l.direction.normalize().into(),
+                    intensity: l.intensity * self.exposure,
+                    color: l.color,
+                    cookie: self.cookie_layer(l.cookie),
+                    cookie_size: l.cookie_size.max(1e-3),
+                    _pad: [0; 3],
+                })
+                .collect();
+            queue.write_buffer(&self.directional_buffer, 0, bytemuck::cast_slice(&[gpu.len() as u32, 0, 0, 0]));
+            queue.write_buffer(&self.directional_buffer, 16, bytemuck::cast_slice(&gpu));
+        }
+        if self.points_dirty {
+            self.points_dirty = false;
+            let gpu: Vec<PointLightGpu> = self
+                .points
+                .iter()
+                .map(|l| PointLightGpu {
+                    intensity: l.intensity * self.exposure,
+                    ..*l
+                })
+                .collect();
+            queue.write_buffer(&self.point_buffer, 0, bytemuck::cast_slice(&[gpu.len() as u32, 0, 0, 0]));
+            if !gpu.is_empty() {
+                queue.write_buffer(&self.point_buffer, 16, bytemuck::cast_slice(&gpu));
+            }
+        }
+        if !self.spots_dirty {
+            return;
+        }
+        self.spots_dirty = false;
+        self.spot_tiles.clear();
+
+        let mut gpu = Vec::new();
+        for (slot, light) in self.spots.iter().enumerate() {
+            let Some(light) = light else {
+                continue;
+            };
+            let view_proj = light.view_proj();
+            let mut shadow_tile = -1;
+            if light.cast_shadows && self.spot_tiles.len() < MAX_SHADOWED_SPOT_LIGHTS {
+                shadow_tile = self.spot_tiles.len() as i32;
+                let mut uniform = CameraUniform::new();
+                uniform.light_view_proj = view_proj.into();
+                queue.write_buffer(
+                    &self.spot_tile_cameras[self.spot_tiles.len()].buffer,
+                    0,
+                    bytemuck::cast_slice(&[uniform]),
+                );
+                self.spot_tiles.push(slot);
+            }
+            let outer = light.outer_angle.clamp(0.01, 1.48);
+            gpu.push(SpotLightGpu {
+                position: light.position.into(),
+                range: light.range,
+                direction: light.direction.normalize().into(),
+                intensity: light.intensity * self.exposure,
+                color: light.color,
+                cos_outer: outer.cos(),
+                cos_inner: light.inner_angle.clamp(0.0, outer - 0.005).cos(),
+                shadow_tile,
+                cookie: self.cookie_layer(light.cookie),
+                _pad: 0,
+                view_proj: view_proj.into(),
+            });
+        }
+        queue.write_buffer(&self.spot_buffer, 0, bytemuck::cast_slice(&[gpu.len() as u32, 0, 0, 0]));
+        if !gpu.is_empty() {
+            queue.write_buffer(&self.spot_buffer, 16, bytemuck::cast_slice(&gpu));
+        }
+    }
+
+    pub fn spot_shadow_tiles(&self) -> impl Iterator<Item = SpotShadowTile<'_>> {
+        self.spot_tiles.iter().enumerate().filter_map(|(tile, &slot)| {
+            let light = self.spots[slot].as_ref()?;
+            let size = SPOT_TILE_SIZE as f32;
+            Some(SpotShadowTile {
+                viewport: (
+                    (tile as u32 % SPOT_ATLAS_TILES) as f32 * size,
+                    (tile as u32 / SPOT_ATLAS_TILES) as f32 * size,
+                    size,
+                ),
+                bind_group: &self.spot_tile_cameras[tile].bind_group,
+                light_position: light.position,
+                range: light.range,
+            })
+        })
+    }
+}
+
+/// Uploads the cookies as one sRGB array; a single white layer stands in when there are none.
+fn create_cookie_array(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    cookies: &[image::RgbaImage],
+) -> wgpu::TextureView {
+    let (size, layers) = if cookies.is_empty() { (1, 1) } else { (COOKIE_SIZE, cookies.len() as u32) };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Light Cookies"),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: layers,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    let white = [255u8; 4];
+    let layers: Vec<&[u8]> =
+        if cookies.is_empty() { vec![&white] } else { cookies.iter().map(|c| c.as_raw().as_slice()).collect() };
+    for (layer, pixels) in layers.into_iter().enumerate() {
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: 0,
+                    z: layer as u32,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * size),
+                rows_per_image: Some(size),
+            },
+            wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("Light Cookies View"),
+        dimension: Some(wgpu::TextureViewDimension::D2Array),
+        ..Default::default()
+    })
+}
@@ -0,0 +1,303 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+use wgpu::util::DeviceExt;
+
+use crate::post::HdrTarget;
+
+pub const HISTOGRAM_BINS: usize = 64;
+pub const MIN_LOG_LUMINANCE: f32 = -12.0;
+pub const LOG_LUMINANCE_RANGE: f32 = 20.0;
+
+const BUFFER_WORDS: usize = HISTOGRAM_BINS + 3;
+const BUFFER_SIZE: u64 = (BUFFER_WORDS * 4) as u64;
+
+const MAP_PENDING: u8 = 0;
+const MAP_DONE: u8 = 1;
+const MAP_FAILED: u8 = 2;
+
+/// Scene luminance of the last analysed frame (one or two frames behind the one on screen).
+#[derive(Clone, Debug)]
+pub struct LuminanceStats {
+    pub min: f32,
+    pub max: f32,
+    /// Geometric mean, the usual key value for exposure.
+    pub average: f32,
+    pub histogram: [u32; HISTOGRAM_BINS],
+    pub pixel_count: u32,
+    /// Pre-exposure the frame was rendered with; dividing by it gives luminance in nits.
+    pub exposure: f32,
+}
+
+impl LuminanceStats {
+    pub fn bin_luminance(bin: usize) -> f32 {
+        let t = bin as f32 / (HISTOGRAM_BINS - 1) as f32;
+        (MIN_LOG_LUMINANCE + t * LOG_LUMINANCE_RANGE).exp2()
+    }
+
+    fn from_words(words: &[u32], exposure: f32) -> Self {
+        let mut histogram = [0u32; HISTOGRAM_BINS];
+        histogram.copy_from_slice(&words[..HISTOGRAM_BINS]);
+        let pixel_count = words[HISTOGRAM_BINS + 2];
+        let log_sum: f64 = histogram
+            .iter()
+            .enumerate()
+            .map(|(bin, &count)| count as f64 * Self::bin_luminance(bin).log2() as f64)
+            .sum();
+        let average = if pixel_count > 0 {
+            (log_sum / pixel_count as f64).exp2() as f32
+        } else {
+            0.0
+        };
+        let (min, max) = if pixel_count > 0 {
+            (f32::from_bits(words[HISTOGRAM_BINS]), f32::from_bits(words[HISTOGRAM_BINS + 1]))
+        } else {
+            (0.0, 0.0)
+        };
+        Self {
+            min,
+            max,
+            average,
+            histogram,
+            pixel_count,
+            exposure,
+        }
+    }
+
+    /// Luminance below which `fraction` of the pixels fall.
+    pub fn percentile(&self, fraction: f32) -> f32 {
+        let target = (self.pixel_count as f32 * fraction.clamp(0.0, 1.0)) as u32;
+        let mut seen = 0;
+        for (bin, &count) in self.histogram.iter().enumerate() {
+            seen += count;
+            if seen >= target && seen > 0 {
+                return Self::bin_luminance(bin);
+            }
+        }
+        self.max
+    }
+
+    /// Geometric mean of the pixels between the `low` and `high` percentiles, so a few very
+    /// dark or very bright pixels do not swing the result.
+    pub fn trimmed_average(&self, low: f32, high: f32) -> Option<f32> {
+        let first = (self.pixel_count as f64 * low.clamp(0.0, 1.0) as f64) as u64;
+        let last = (self.pixel_count as f64 * high.clamp(0.0, 1.0) as f64) as u64;
+        let (mut seen, mut count, mut log_sum) = (0u64, 0u64, 0.0f64);
+        for (bin, &n) in self.histogram.iter().enumerate() {
+            // Part of this bin that falls inside [first, last).
+            let start = seen.max(first);
+            let end = (seen + n as u64).min(last);
+            if end > start {
+                count += end - start;
+                log_sum += (end - start) as f64 * Self::bin_luminance(bin).log2() as f64;
+            }
+            seen += n as u64;
+        }
+        (count > 0).then(|| (log_sum / count as f64).exp2() as f32)
+    }
+}
+
+pub struct LuminanceAnalyzer {
+    pipeline: wgpu::ComputePipeline,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    histogram_buffer: wgpu::Buffer,
+    params_buffer: wgpu::Buffer,
+    readback: wgpu::Buffer,
+    readback_state: ReadbackState,
+    map_status: Arc<AtomicU8>,
+    size: (u32, u32),
+    /// Exposure of the frame whose histogram is being read back.
+    recorded_exposure: f32,
+    latest: Option<LuminanceStats>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum ReadbackState {
+    Idle,
+    Copied,
+    Mapping,
+}
+
+impl LuminanceAnalyzer {
+    pub fn new(device: &wgpu::Device, hdr: &HdrTarget) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Luminance Histogram Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("luminance.wgsl").into()),
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("luminance_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Luminance Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Luminance Histogram Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "cs_histogram",
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let histogram_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Luminance Histogram Buffer"),
+            size: BUFFER_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Luminance Params Buffer"),
+            contents: bytemuck::cast_slice(&[MIN_LOG_LUMINANCE, LOG_LUMINANCE_RANGE, 0.0, 0.0]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Luminance Readback Buffer"),
+            size: BUFFER_SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = Self::create_bind_group(device, &layout, hdr, &histogram_buffer, &params_buffer);
+
+        Self {
+            pipeline,
+            layout,
+            bind_group,
+            histogram_buffer,
+            params_buffer,
+            readback,
+            readback_state: ReadbackState::Idle,
+            map_status: Arc::new(AtomicU8::new(MAP_PENDING)),
+            size: (hdr.texture.width(), hdr.texture.height()),
+            recorded_exposure: 1.0,
+            latest: None,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        hdr: &HdrTarget,
+        histogram_buffer: &wgpu::Buffer,
+        params_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("luminance_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&hdr.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: histogram_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, hdr: &HdrTarget) {
+        self.bind_group =
+            Self::create_bind_group(device, &self.layout, hdr, &self.histogram_buffer, &self.params_buffer);
+        self.size = (hdr.texture.width(), hdr.texture.height());
+    }
+
+    /// Records the histogram pass for the current HDR frame, rendered with `exposure`.
+    /// Skipped while the previous result is still being read back, so the analysis runs
+    /// at most once per readback.
+    pub fn record(&mut self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, exposure: f32) {
+        if self.readback_state != ReadbackState::Idle {
+            return;
+        }
+        let mut init = [0u32; BUFFER_WORDS];
+        init[HISTOGRAM_BINS] = u32::MAX;
+        queue.write_buffer(&self.histogram_buffer, 0, bytemuck::cast_slice(&init));
+
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Luminance Histogram Pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.dispatch_workgroups(self.size.0.div_ceil(16), self.size.1.div_ceil(16), 1);
+        }
+        encoder.copy_buffer_to_buffer(&self.histogram_buffer, 0, &self.readback, 0, BUFFER_SIZE);
+        self.recorded_exposure = exposure;
+        self.readback_state = ReadbackState::Copied;
+    }
+
+    /// Call after the frame's commands were submitted: starts the readback and collects a
+    /// finished one without blocking.
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        match self.readback_state {
+            ReadbackState::Idle => {}
+            ReadbackState::Copied => {
+                let status = self.map_status.clone();
+                status.store(MAP_PENDING, Ordering::Release);
+                self.readback.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                    status.store(if result.is_ok() { MAP_DONE } else { MAP_FAILED }, Ordering::Release);
+                });
+                self.readback_state = ReadbackState::Mapping;
+            }
+            ReadbackState::Mapping => {
+                device.poll(wgpu::Maintain::Poll);
+                match self.map_status.load(Ordering::Acquire) {
+                    MAP_DONE => {
+                        {
+                            let data = self.readback.slice(..).get_mapped_range();
+                            self.latest = Some(LuminanceStats::from_words(bytemuck::cast_slice(&data), self.recorded_exposure));
+                        }
+                        self.readback.unmap();
+                        self.readback_state = ReadbackState::Idle;
+                    }
+                    MAP_FAILED => self.readback_state = ReadbackState::Idle,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    pub fn latest(&self) -> Option<&LuminanceStats> {
+        self.latest.as_ref()
+    }
+}
@@ -0,0 +1,58 @@
+const BIN_COUNT: u32 = 64u;
+
+struct Histogram {
+    bins: array<atomic<u32>, 64>,
+    min_bits: atomic<u32>,
+    max_bits: atomic<u32>,
+    pixel_count: atomic<u32>,
+};
+
+struct Params {
+    // x = min log2 luminance, y = log2 range covered by the bins
+    log_range: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var hdr_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var<storage, read_write> histogram: Histogram;
+
+@group(0) @binding(2)
+var<uniform> params: Params;
+
+var<workgroup> local_bins: array<atomic<u32>, 64>;
+
+@compute @workgroup_size(16, 16)
+fn cs_histogram(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(local_invocation_index) lid: u32,
+) {
+    if lid < BIN_COUNT {
+        atomicStore(&local_bins[lid], 0u);
+    }
+    workgroupBarrier();
+
+    let size = textureDimensions(hdr_texture);
+    if gid.x < size.x && gid.y < size.y {
+        let rgb = textureLoad(hdr_texture, vec2<i32>(gid.xy), 0).rgb;
+        let lum = max(dot(rgb, vec3<f32>(0.2126, 0.7152, 0.0722)), 0.0);
+        if lum == lum && lum < 65504.0 {
+            let t = (log2(max(lum, 1e-8)) - params.log_range.x) / params.log_range.y;
+            let bin = u32(clamp(t, 0.0, 1.0) * f32(BIN_COUNT - 1u));
+            atomicAdd(&local_bins[bin], 1u);
+            // Non-negative floats order the same as their bit patterns.
+            atomicMin(&histogram.min_bits, bitcast<u32>(lum));
+            atomicMax(&histogram.max_bits, bitcast<u32>(lum));
+        }
+    }
+    workgroupBarrier();
+
+    if lid < BIN_COUNT {
+        let count = atomicLoad(&local_bins[lid]);
+        if count > 0u {
+            atomicAdd(&histogram.bins[lid], count);
+            atomicAdd(&histogram.pixel_count, count);
+        }
+    }
+}
@@ -1,4 +1,6 @@
+
 use wgpu::util::DeviceExt;
+use crate::bindless::BindlessMaterials;
 use crate::model::{Material as ModelMaterial, Texture as ModelTexture};
 
 #[repr(C)]
@@ -7,11 +9,202 @@ pub struct MaterialUniform {
     pub base_color: [f32; 4],
     pub metallic_roughness: [f32; 4],
     pub alpha_cutoff_flags: [f32; 4],
+    /// x: shadow normal offset, negative to use the global setting.
+    pub shadow_bias: [f32; 4],
 }
 
 pub struct Material {
     pub uniform: MaterialUniform,
     pub bind_group: wgpu::BindGroup,
+    /// Size of the textures uploaded for this material; shared defaults are not counted.
+    pub texture_bytes: u64,
+    /// Base color, metallic-roughness and normal textures, for the texture inspector.
+    pub textures: [MaterialTexture; 3],
+    uniform_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+}
+
+/// Labels of the textures in [`Material::textures`], in order.
+pub const TEXTURE_LABELS: [&str; 3] = ["Base Color Texture", "Metallic Roughness Texture", "Normal Texture"];
+
+/// One texture a material samples.
+pub struct MaterialTexture {
+    pub view: wgpu::TextureView,
+    /// Size and format as uploaded, or None for the shared default.
+    pub source: Option<(u32, u32, wgpu::TextureFormat)>,
+}
+
+impl MaterialTexture {
+    /// Uploads `model_texture` as a single mip level.
+    pub fn upload(device: &wgpu::Device, queue: &wgpu::Queue, label: &str, model_texture: &ModelTexture) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: model_texture.width,
+                height: model_texture.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: model_texture.format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &model_texture.data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * model_texture.width),
+                rows_per_image: Some(model_texture.height),
+            },
+            wgpu::Extent3d {
+                width: model_texture.width,
+                height: model_texture.height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        Self {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            source: Some((model_texture.width, model_texture.height, model_texture.format)),
+        }
+    }
+}
+
+/// The materials a pass binds per mesh, optionally all replaced by one. With bindless
+/// materials, the replacement is its id in the table.
+#[derive(Copy, Clone)]
+pub struct DrawMaterials<'a> {
+    materials: &'a [Material],
+    replacement: Option<(usize, &'a Material)>,
+    bindless: Option<&'a BindlessMaterials>,
+}
+
+impl<'a> DrawMaterials<'a> {
+    pub fn new(
+        materials: &'a [Material],
+        replacement: Option<(usize, &'a Material)>,
+        bindless: Option<&'a BindlessMaterials>,
+    ) -> Self {
+        Self {
+            materials,
+            replacement,
+            bindless,
+        }
+    }
+
+    /// What to bind for the material at `index`; indices past the end use the last one.
+    pub fn get(&self, index: usize) -> &'a Material {
+        self.replacement
+            .map(|(_, material)| material)
+            .unwrap_or_else(|| &self.materials[index.min(self.materials.len().saturating_sub(1))])
+    }
+
+    /// Id of the material replacing all others, if any.
+    pub fn replacement(&self) -> Option<usize> {
+        self.replacement.map(|(id, _)| id)
+    }
+
+    /// Binds the bindless table, if any; call once per pass before `bind`.
+    pub fn begin(&self, pass: &mut wgpu::RenderPass<'_>) {
+        if let Some(bindless) = self.bindless {
+            bindless.bind(pass);
+        }
+    }
+
+    /// Selects the material at `index` for the next draw. Bindless draws find theirs
+    /// through their object, so there is nothing to bind.
+    pub fn bind(&self, pass: &mut wgpu::RenderPass<'_>, index: usize) {
+        if self.bindless.is_none() {
+            pass.set_bind_group(1, &self.get(index).bind_group, &[]);
+        }
+    }
+}
+
+pub struct DefaultTextures {
+    pub base_color: wgpu::Texture,
+    pub metallic_roughness: wgpu::Texture,
+    pub normal: wgpu::Texture,
+}
+
+impl DefaultTextures {
+    /// White base color, fully rough dielectric and a flat normal.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let pixel = |rgba, format| create_default_texture_pixel(device, queue, rgba, format);
+        Self {
+            base_color: pixel([255, 255, 255, 255], wgpu::TextureFormat::Rgba8UnormSrgb),
+            metallic_roughness: pixel([0, 255, 0, 255], wgpu::TextureFormat::Rgba8Unorm),
+            normal: pixel([128, 128, 255, 255], wgpu::TextureFormat::Rgba8Unorm),
+        }
+    }
+
+    /// The default for a slot of [`Material::textures`].
+    pub fn for_slot(&self, slot: usize) -> MaterialTexture {
+        let texture = [&self.base_color, &self.metallic_roughness, &self.normal][slot];
+        MaterialTexture {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            source: None,
+        }
+    }
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    uniform_buffer: &wgpu::Buffer,
+    sampler: &wgpu::Sampler,
+    [base_color, metallic_roughness, normal]: &[MaterialTexture; 3],
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Material Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&base_color.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&metallic_roughness.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::TextureView(&normal.view),
+            },
+        ],
+    })
+}
+
+fn upload_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    label: &str,
+    image: Option<usize>,
+    textures: &[ModelTexture],
+    default: &wgpu::Texture,
+) -> (MaterialTexture, u64) {
+    let Some(model_texture) = image.and_then(|i| textures.get(i)) else {
+        let view = default.create_view(&wgpu::TextureViewDescriptor::default());
+        return (MaterialTexture { view, source: None }, 0);
+    };
+    let material_texture = MaterialTexture::upload(device, queue, label, model_texture);
+    (material_texture, model_texture.data.len() as u64)
 }
 
 impl Material {
@@ -21,20 +214,58 @@ impl Material {
         layout: &wgpu::BindGroupLayout,
         material: &ModelMaterial,
         textures: &[ModelTexture],
-        default_base_color_texture: &wgpu::Texture,
-        default_metallic_roughness_texture: &wgpu::Texture,
+        defaults: &DefaultTextures,
+    ) -> Self {
+        let (base_color, base_color_bytes) = upload_texture(
+            device,
+            queue,
+            TEXTURE_LABELS[0],
+            material.base_color_image,
+            textures,
+            &defaults.base_color,
+        );
+        let (metallic_roughness, metallic_roughness_bytes) = upload_texture(
+            device,
+            queue,
+            TEXTURE_LABELS[1],
+            material.metallic_roughness_image,
+            textures,
+            &defaults.metallic_roughness,
+        );
+        let (normal, normal_bytes) = upload_texture(
+            device,
+            queue,
+            TEXTURE_LABELS[2],
+            material.normal_image,
+            textures,
+            &defaults.normal,
+        );
+        let texture_bytes = base_color_bytes + metallic_roughness_bytes + normal_bytes;
+        Self::new(device, layout, material, [base_color, metallic_roughness, normal], texture_bytes)
+    }
+
+    /// A material sampling `textures`, which were uploaded elsewhere and take up `texture_bytes`.
+    pub fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        material: &ModelMaterial,
+        textures: [MaterialTexture; 3],
+        texture_bytes: u64,
     ) -> Self {
         let alpha_mode = match material.alpha_mode {
             crate::model::AlphaMode::Opaque => 0.0,
             crate::model::AlphaMode::Mask => 1.0,
             crate::model::AlphaMode::Blend => 2.0,
+            crate::model::AlphaMode::Dither => 3.0,
         };
         let double_sided = if material.double_sided { 1.0 } else { 0.0 };
+        let flip_normal_y = if material.flip_normal_y { 1.0 } else { 0.0 };
 
         let uniform = MaterialUniform {
             base_color: material.base_color,
-            metallic_roughness: [material.metallic, material.roughness, 0.0, 0.0],
-            alpha_cutoff_flags: [material.alpha_cutoff, alpha_mode, double_sided, 0.0],
+            metallic_roughness: [material.metallic, material.roughness, material.normal_scale, flip_normal_y],
+            alpha_cutoff_flags: [material.alpha_cutoff, alpha_mode, double_sided, material.soft_fade],
+            shadow_bias: [material.shadow_normal_offset.unwrap_or(-1.0), 0.0, 0.0, 0.0],
         };
         
         let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -49,128 +280,33 @@ impl Material {
             address_mode_w: wgpu::AddressMode::Repeat,
             mag_filter: wgpu::FilterMode::Linear,
             min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Nearest,
+            // Streamed textures carry mip chains; the others have a single level.
+            mipmap_filter: wgpu::FilterMode::Linear,
             ..Default::default()
         });
 
-        let base_color_view = if let Some(img_idx) = material.base_color_image {
-            if let Some(model_texture) = textures.get(img_idx) {
-                let texture = device.create_texture(&wgpu::TextureDescriptor {
-                    label: Some("Base Color Texture"),
-                    size: wgpu::Extent3d {
-                        width: model_texture.width,
-                        height: model_texture.height,
-                        depth_or_array_layers: 1,
-                    },
-                    mip_level_count: 1,
-                    sample_count: 1,
-                    dimension: wgpu::TextureDimension::D2,
-                    format: model_texture.format,
-                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                    view_formats: &[],
-                });
-                
-                queue.write_texture(
-                    wgpu::ImageCopyTexture {
-                        texture: &texture,
-                        mip_level: 0,
-                        origin: wgpu::Origin3d::ZERO,
-                        aspect: wgpu::TextureAspect::All,
-                    },
-                    &model_texture.data,
-                    wgpu::ImageDataLayout {
-                        offset: 0,
-                        bytes_per_row: Some(4 * model_texture.width),
-                        rows_per_image: Some(model_texture.height),
-                    },
-                    wgpu::Extent3d {
-                        width: model_texture.width,
-                        height: model_texture.height,
-                        depth_or_array_layers: 1,
-                    },
-                );
-                
-                texture.create_view(&wgpu::TextureViewDescriptor::default())
-            } else {
-                default_base_color_texture.create_view(&wgpu::TextureViewDescriptor::default())
-            }
-        } else {
-            default_base_color_texture.create_view(&wgpu::TextureViewDescriptor::default())
-        };
-
-        let metallic_roughness_view = if let Some(img_idx) = material.metallic_roughness_image {
-            if let Some(model_texture) = textures.get(img_idx) {
-                let texture = device.create_texture(&wgpu::TextureDescriptor {
-                    label: Some("Metallic Roughness Texture"),
-                    size: wgpu::Extent3d {
-                        width: model_texture.width,
-                        height: model_texture.height,
-                        depth_or_array_layers: 1,
-                    },
-                    mip_level_count: 1,
-                    sample_count: 1,
-                    dimension: wgpu::TextureDimension::D2,
-                    format: model_texture.format,
-                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                    view_formats: &[],
-                });
-
-                queue.write_texture(
-                    wgpu::ImageCopyTexture {
-                        texture: &texture,
-                        mip_level: 0,
-                        origin: wgpu::Origin3d::ZERO,
-                        aspect: wgpu::TextureAspect::All,
-                    },
-                    &model_texture.data,
-                    wgpu::ImageDataLayout {
-                        offset: 0,
-                        bytes_per_row: Some(4 * model_texture.width),
-                        rows_per_image: Some(model_texture.height),
-                    },
-                    wgpu::Extent3d {
-                        width: model_texture.width,
-                        height: model_texture.height,
-                        depth_or_array_layers: 1,
-                    },
-                );
-
-                texture.create_view(&wgpu::TextureViewDescriptor::default())
-            } else {
-                default_metallic_roughness_texture.create_view(&wgpu::TextureViewDescriptor::default())
-            }
-        } else {
-            default_metallic_roughness_texture.create_view(&wgpu::TextureViewDescriptor::default())
-        };
-        
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Material Bind Group"),
-            layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: uniform_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::TextureView(&base_color_view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: wgpu::BindingResource::TextureView(&metallic_roughness_view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 3,
-                    resource: wgpu::BindingResource::Sampler(&sampler),
-                },
-            ],
-        });
-        
+        let bind_group = create_bind_group(device, layout, &uniform_buffer, &sampler, &textures);
         Self {
             uniform,
             bind_group,
+            texture_bytes,
+            textures,
+            uniform_buffer,
+            sampler,
         }
     }
+
+    /// Swaps the texture in `slot` and rebuilds the bind group around it.
+    pub fn set_texture(
+        &mut self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        slot: usize,
+        texture: MaterialTexture,
+    ) {
+        self.textures[slot] = texture;
+        self.bind_group = create_bind_group(device, layout, &self.uniform_buffer, &self.sampler, &self.textures);
+    }
 }
 
 pub fn create_default_texture(device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::Texture {
@@ -0,0 +1,144 @@
+use crate::bindless::BindlessMaterials;
+use crate::material::{DefaultTextures, DrawMaterials, Material};
+use crate::model::{self, Material as ModelMaterial, Texture as ModelTexture};
+
+const CHECKER_SIZE: u32 = 512;
+/// Checker cells along each side of the texture.
+const CHECKER_CELLS: u32 = 16;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MaterialDebugMode {
+    #[default]
+    Off,
+    /// A checker grid over the UVs, tinted red along U and green along V.
+    UvChecker,
+    /// Flat grey, rough and dielectric.
+    Clay,
+    /// One of the scene's materials, by index.
+    Single(usize),
+}
+
+impl MaterialDebugMode {
+    /// Parses `checker`, `clay` or a material index.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "checker" => Some(Self::UvChecker),
+            "clay" => Some(Self::Clay),
+            "off" => Some(Self::Off),
+            _ => s.parse().ok().map(Self::Single),
+        }
+    }
+}
+
+fn plain_material(name: &str, base_color: [f32; 4], roughness: f32) -> ModelMaterial {
+    ModelMaterial {
+        name: name.to_string(),
+        base_color,
+        metallic: 0.0,
+        roughness,
+        base_color_image: None,
+        metallic_roughness_image: None,
+        normal_image: None,
+        alpha_mode: model::AlphaMode::Opaque,
+        alpha_cutoff: 0.5,
+        double_sided: false,
+        base_color_texcoord_set: 0,
+        metallic_roughness_texcoord_set: 0,
+        surface_hook: None,
+        soft_fade: 0.0,
+        normal_scale: 1.0,
+        flip_normal_y: false,
+        shadow_normal_offset: None,
+    }
+}
+
+fn checker_texture() -> ModelTexture {
+    let cell = CHECKER_SIZE / CHECKER_CELLS;
+    let mut data = Vec::with_capacity((CHECKER_SIZE * CHECKER_SIZE * 4) as usize);
+    for y in 0..CHECKER_SIZE {
+        for x in 0..CHECKER_SIZE {
+            let (cx, cy) = (x / cell, y / cell);
+            let light = (cx + cy) % 2 == 0;
+            // Cell borders make stretching easy to spot even where the tint is subtle.
+            let border = x % cell == 0 || y % cell == 0;
+            let value = if border { 20 } else if light { 220 } else { 90 };
+            let r = value as f32 * (0.6 + 0.4 * cx as f32 / (CHECKER_CELLS - 1) as f32);
+            let g = value as f32 * (0.6 + 0.4 * cy as f32 / (CHECKER_CELLS - 1) as f32);
+            data.extend_from_slice(&[r as u8, g as u8, (value as f32 * 0.6) as u8, 255]);
+        }
+    }
+    ModelTexture {
+        data,
+        width: CHECKER_SIZE,
+        height: CHECKER_SIZE,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        has_alpha: false,
+    }
+}
+
+/// Replaces every material in the camera passes, to look at UV density or lighting
+/// without the scene's textures in the way. Probe and reflection captures keep the
+/// scene's materials.
+pub struct MaterialDebug {
+    pub mode: MaterialDebugMode,
+    checker: Material,
+    clay: Material,
+}
+
+impl MaterialDebug {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        defaults: &DefaultTextures,
+    ) -> Self {
+        let mut checker = plain_material("uv checker", [1.0; 4], 0.6);
+        checker.base_color_image = Some(0);
+        let checker = Material::from_model_material(device, queue, layout, &checker, &[checker_texture()], defaults);
+        let clay = plain_material("clay", [0.5, 0.5, 0.5, 1.0], 0.7);
+        let clay = Material::from_model_material(device, queue, layout, &clay, &[], defaults);
+        Self {
+            mode: MaterialDebugMode::Off,
+            checker,
+            clay,
+        }
+    }
+
+    /// Off, checker, clay, the first material and back.
+    pub fn cycle_mode(&mut self) {
+        self.mode = match self.mode {
+            MaterialDebugMode::Off => MaterialDebugMode::UvChecker,
+            MaterialDebugMode::UvChecker => MaterialDebugMode::Clay,
+            MaterialDebugMode::Clay => MaterialDebugMode::Single(0),
+            MaterialDebugMode::Single(_) => MaterialDebugMode::Off,
+        };
+    }
+
+    /// Switches to the material after the one shown, wrapping around `count`.
+    pub fn next_material(&mut self, count: usize) {
+        self.mode = match self.mode {
+            MaterialDebugMode::Single(index) => MaterialDebugMode::Single((index + 1) % count.max(1)),
+            _ => MaterialDebugMode::Single(0),
+        };
+    }
+
+    /// The checker and clay materials, which follow the scene's in the bindless table.
+    pub fn materials(&self) -> [&Material; 2] {
+        [&self.checker, &self.clay]
+    }
+
+    /// The scene's materials as the camera passes should draw them.
+    pub fn apply<'a>(
+        &'a self,
+        materials: &'a [Material],
+        bindless: Option<&'a BindlessMaterials>,
+    ) -> DrawMaterials<'a> {
+        let replacement = match self.mode {
+            MaterialDebugMode::Off => None,
+            MaterialDebugMode::UvChecker => Some((materials.len(), &self.checker)),
+            MaterialDebugMode::Clay => Some((materials.len() + 1, &self.clay)),
+            MaterialDebugMode::Single(index) => materials.get(index).map(|material| (index, material)),
+        };
+        DrawMaterials::new(materials, replacement, bindless)
+    }
+}
@@ -1,13 +1,30 @@
 use anyhow::{Context, Result};
-use cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, SquareMatrix, Vector3, Vector4};
+use cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, Point3, SquareMatrix, Vector3, Vector4};
+use std::borrow::Cow;
 use std::io::Cursor;
-use std::{fs, path::Path};
+use std::{fs, path::{Path, PathBuf}};
+
+use crate::aabb::Aabb;
+use crate::accessor;
+use crate::geometry;
+use crate::jobs;
+use crate::profiler;
+use crate::lights::{DirectionalLight, LightSet, PointLight, SpotLight};
+use crate::animation::{AnimationClip, AnimationSet, Channel, ChannelValues, Interpolation, NodeTransform, Skin};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum AlphaMode {
     Opaque,
     Mask,
     Blend,
+    Dither,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShadowRole {
+    Caster,
+    NonCaster,
+    Proxy,
 }
 
 #[repr(C)]
@@ -19,6 +36,7 @@ pub struct Vertex {
 }
 
 pub struct Material {
+    pub name: String,
     pub base_color: [f32; 4],
     pub metallic: f32,
     pub roughness: f32,
@@ -30,14 +48,264 @@ pub struct Material {
     pub double_sided: bool,
     pub base_color_texcoord_set: u32,
     pub metallic_roughness_texcoord_set: u32,
+    pub surface_hook: Option<String>,
+    pub soft_fade: f32,
+    pub normal_scale: f32,
+    pub flip_normal_y: bool,
+    /// Overrides the global shadow normal offset for surfaces with this material.
+    pub shadow_normal_offset: Option<f32>,
 }
 
+#[derive(Clone)]
 pub struct Mesh {
+    pub name: String,
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
     pub material_index: usize,
+    pub node: usize,
+    pub skin: Option<usize>,
+    pub joints: Vec<[u16; 4]>,
+    pub weights: Vec<[f32; 4]>,
+    pub tangents: Vec<[f32; 4]>,
+    pub shadow: ShadowRole,
+    /// Lower-detail versions, coarsest last.
+    pub lods: Vec<MeshLod>,
+    /// Object to world; the vertices stay in object space until `bake`.
+    pub transform: Matrix4<f32>,
+    /// glTF mesh and primitive the vertices came from, the same for every node that
+    /// instances it; None once baked or merged.
+    pub source: Option<(usize, usize)>,
+}
+
+/// A simplified copy of a mesh, drawn while the mesh covers less than `coverage` of the
+/// screen height.
+#[derive(Clone)]
+pub struct MeshLod {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub coverage: f32,
+}
+
+/// Screen coverage below which the first generated or unannotated LOD is used; each
+/// further level halves it.
+pub const LOD_COVERAGE: f32 = 0.25;
+/// Levels generated with `ImportOptions::generate_lods`, each with about a quarter of the
+/// triangles of the one before.
+const GENERATED_LODS: usize = 3;
+/// Meshes with fewer triangles get no generated LODs, nor do levels below it.
+const MIN_LOD_TRIANGLES: usize = 256;
+
+fn default_lod_coverage(level: usize) -> f32 {
+    LOD_COVERAGE * 0.5f32.powi(level as i32)
+}
+
+/// Generates LODs by clustering for a static mesh that has none. Stops early once a level
+/// no longer gets meaningfully smaller.
+fn generate_lods(mesh: &mut Mesh) {
+    let mut triangles = mesh.indices.len() / 3;
+    for level in 0..GENERATED_LODS {
+        let target = triangles / 4;
+        if target < MIN_LOD_TRIANGLES {
+            break;
+        }
+        let (vertices, indices) = geometry::simplify_lod(&mesh.vertices, &mesh.indices, target);
+        if indices.len() / 3 > triangles * 3 / 4 {
+            break;
+        }
+        triangles = indices.len() / 3;
+        mesh.lods.push(MeshLod {
+            vertices,
+            indices,
+            coverage: default_lod_coverage(level),
+        });
+    }
+}
+
+/// The nodes named by a node's `MSFT_lod` extension and the screen coverages of its
+/// `MSFT_screencoverage` extra, one per level starting with the node itself.
+fn msft_lod(node: &gltf::Node) -> Option<(Vec<usize>, Vec<f32>)> {
+    let ids: Vec<usize> = node
+        .extension_value("MSFT_lod")?
+        .get("ids")?
+        .as_array()?
+        .iter()
+        .filter_map(|id| id.as_u64().map(|id| id as usize))
+        .collect();
+    let coverage = extras_value(node.extras())
+        .and_then(|e| e.get("MSFT_screencoverage").and_then(|v| v.as_array()).cloned())
+        .map(|values| values.iter().filter_map(|v| v.as_f64().map(|c| c as f32)).collect())
+        .unwrap_or_default();
+    Some((ids, coverage))
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MeshStats {
+    pub vertices: usize,
+    pub triangles: usize,
+    pub degenerate_triangles: usize,
+    pub invalid_vertices: usize,
+    pub out_of_range_indices: usize,
+}
+
+impl MeshStats {
+    pub fn has_issues(&self) -> bool {
+        self.degenerate_triangles > 0 || self.invalid_vertices > 0 || self.out_of_range_indices > 0
+    }
 }
 
+fn upper_3x3(m: Matrix4<f32>) -> Matrix3<f32> {
+    Matrix3::new(
+        m.x.x, m.x.y, m.x.z,
+        m.y.x, m.y.y, m.y.z,
+        m.z.x, m.z.y, m.z.z,
+    )
+}
+
+/// Applies `m` to positions and normals.
+fn transform_vertices<'a>(m: Matrix4<f32>, vertices: impl Iterator<Item = &'a mut Vertex>) {
+    let nmat = upper_3x3(m).invert().unwrap_or(Matrix3::from_scale(1.0)).transpose();
+    for v in vertices {
+        let wp = m * Vector4::new(v.position[0], v.position[1], v.position[2], 1.0);
+        let nn = (nmat * Vector3::from(v.normal)).normalize();
+        v.position = [wp.x, wp.y, wp.z];
+        v.normal = [nn.x, nn.y, nn.z];
+    }
+}
+
+/// Vertices per merged mesh; a batch spanning the whole scene would never be culled.
+const MAX_BATCH_VERTICES: usize = 1 << 18;
+
+/// Merges static meshes with the same material and shadow role, in load order, into
+/// combined meshes of up to `MAX_BATCH_VERTICES`. Skinned meshes, meshes on animated
+/// nodes, meshes with LODs and meshes with out-of-range indices are kept as they are.
+fn batch_by_material(meshes: Vec<Mesh>, animated_nodes: &[bool]) -> Vec<Mesh> {
+    let mut out: Vec<Mesh> = Vec::with_capacity(meshes.len());
+    // Index in `out` of the open batch per material and shadow role.
+    let mut open: Vec<((usize, ShadowRole), usize)> = Vec::new();
+    // Meshes merged into each entry of `out`.
+    let mut merged: Vec<usize> = Vec::new();
+    for mut mesh in meshes {
+        let animated = mesh.skin.is_some() || animated_nodes.get(mesh.node).copied().unwrap_or(false);
+        // Merging a mesh with LODs would need the batch's LODs merged alike.
+        if animated || !mesh.lods.is_empty() || mesh.indices.iter().any(|&i| i as usize >= mesh.vertices.len()) {
+            out.push(mesh);
+            merged.push(1);
+            continue;
+        }
+        let key = (mesh.material_index, mesh.shadow);
+        let target = open
+            .iter()
+            .find(|(k, i)| *k == key && out[*i].vertices.len() + mesh.vertices.len() <= MAX_BATCH_VERTICES)
+            .map(|(_, i)| *i);
+        let Some(target) = target else {
+            open.retain(|(k, _)| *k != key);
+            open.push((key, out.len()));
+            out.push(mesh);
+            merged.push(1);
+            continue;
+        };
+        let batch = &mut out[target];
+        batch.bake();
+        mesh.bake();
+        let base = batch.vertices.len() as u32;
+        // Tangents survive only if every mesh in the batch has them; the rest are
+        // regenerated for normal-mapped materials at upload.
+        if batch.tangents.len() == batch.vertices.len() && mesh.tangents.len() == mesh.vertices.len() {
+            batch.tangents.extend_from_slice(&mesh.tangents);
+        } else {
+            batch.tangents.clear();
+        }
+        batch.vertices.extend_from_slice(&mesh.vertices);
+        batch.indices.extend(mesh.indices.iter().map(|i| i + base));
+        merged[target] += 1;
+    }
+    for (mesh, count) in out.iter_mut().zip(merged) {
+        if count > 1 {
+            mesh.name = format!("{} (+{} batched)", mesh.name, count - 1);
+        }
+    }
+    out
+}
+
+impl Mesh {
+    /// Applies `m` to positions, normals and tangents.
+    fn apply(&mut self, m: Matrix4<f32>) {
+        let tmat = upper_3x3(m);
+        let lod_vertices = self.lods.iter_mut().flat_map(|lod| lod.vertices.iter_mut());
+        transform_vertices(m, self.vertices.iter_mut().chain(lod_vertices));
+        for t in &mut self.tangents {
+            let wt = tmat * Vector3::new(t[0], t[1], t[2]);
+            let wt = if wt.magnitude2() > 0.0 { wt.normalize() } else { wt };
+            *t = [wt.x, wt.y, wt.z, t[3]];
+        }
+    }
+
+    /// Moves `transform` into the vertices, for consumers that need them in world space.
+    pub fn bake(&mut self) {
+        if self.transform != Matrix4::from_scale(1.0) {
+            self.apply(self.transform);
+            self.transform = Matrix4::from_scale(1.0);
+        }
+        self.source = None;
+    }
+
+    /// The vertices in world space, copied only when `transform` moves them.
+    pub fn world_vertices(&self) -> Cow<'_, [Vertex]> {
+        if self.transform == Matrix4::from_scale(1.0) {
+            return Cow::Borrowed(&self.vertices);
+        }
+        let mut vertices = self.vertices.clone();
+        transform_vertices(self.transform, vertices.iter_mut());
+        Cow::Owned(vertices)
+    }
+
+    pub fn world_bounds(&self) -> Aabb {
+        let mut bounds = Aabb::empty();
+        for v in &self.vertices {
+            let p = self.transform * Vector4::new(v.position[0], v.position[1], v.position[2], 1.0);
+            bounds.grow([p.x, p.y, p.z]);
+        }
+        bounds
+    }
+
+    pub fn stats(&self) -> MeshStats {
+        let mut stats = MeshStats {
+            vertices: self.vertices.len(),
+            triangles: self.indices.len() / 3,
+            ..Default::default()
+        };
+
+        stats.invalid_vertices = self
+            .vertices
+            .iter()
+            .filter(|v| {
+                v.position.iter().chain(v.normal.iter()).chain(v.tex_coords.iter()).any(|c| !c.is_finite())
+            })
+            .count();
+
+        for tri in self.indices.chunks_exact(3) {
+            let (Some(a), Some(b), Some(c)) = (
+                self.vertices.get(tri[0] as usize),
+                self.vertices.get(tri[1] as usize),
+                self.vertices.get(tri[2] as usize),
+            ) else {
+                stats.out_of_range_indices += 1;
+                continue;
+            };
+            let pa = Vector3::from(a.position);
+            let e1 = Vector3::from(b.position) - pa;
+            let e2 = Vector3::from(c.position) - pa;
+            let area2 = e1.cross(e2).magnitude2();
+            if tri[0] == tri[1] || tri[1] == tri[2] || tri[0] == tri[2] || area2.is_nan() || area2 <= 1e-14 {
+                stats.degenerate_triangles += 1;
+            }
+        }
+
+        stats
+    }
+}
+
+#[derive(Clone)]
 pub struct Texture {
     pub data: Vec<u8>,
     pub width: u32,
@@ -46,14 +314,207 @@ pub struct Texture {
     pub has_alpha: bool,
 }
 
+impl Texture {
+    fn from_rgba8(data: Vec<u8>, width: u32, height: u32) -> Self {
+        let has_alpha = data.len() >= 4 && data.iter().skip(3).step_by(4).any(|a| *a != 255);
+        Self {
+            data,
+            width,
+            height,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            has_alpha,
+        }
+    }
+
+    /// Reads and decodes an image file, as a model's external textures are.
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path).with_context(|| format!("read texture: {}", path.display()))?;
+        let (data, width, height) = decode_image(&bytes).with_context(|| format!("decode {}", path.display()))?;
+        Ok(Self::from_rgba8(data, width, height))
+    }
+}
+
+/// Decodes DDS (mip 0) or any format the `image` crate knows into RGBA8.
+fn decode_image(bytes: &[u8]) -> Option<(Vec<u8>, u32, u32)> {
+    let _span = profiler::scope("decode image");
+    let is_dds = bytes.len() >= 4 && &bytes[0..4] == b"DDS ";
+
+    if is_dds {
+        let mut cur = Cursor::new(bytes);
+        if let Ok(dds) = image_dds::ddsfile::Dds::read(&mut cur) {
+            if let Ok(img) = image_dds::image_from_dds(&dds, 0) {
+                let (w, h) = img.dimensions();
+                Some((img.into_raw(), w, h))
+            } else {
+                log::warn!("Failed to decode DDS image (mip0). Using fallback 1x1 white.");
+                None
+            }
+        } else {
+            log::warn!("Failed to parse DDS header. Using fallback 1x1 white.");
+            None
+        }
+    } else if let Ok(img) = image::load_from_memory(bytes) {
+        let rgba = img.to_rgba8();
+        let (w, h) = rgba.dimensions();
+        Some((rgba.into_raw(), w, h))
+    } else {
+        log::warn!("Failed to decode image bytes (non-DDS). Using fallback 1x1 white.");
+        None
+    }
+}
+
+/// Changes applied to the materials of one model instance before upload. `material`
+/// selects materials by name; `None` applies to all of them.
+#[derive(Clone, Debug, Default)]
+pub struct MaterialOverride {
+    pub material: Option<String>,
+    pub tint: Option<[f32; 4]>,
+    pub roughness_scale: Option<f32>,
+    pub metallic_scale: Option<f32>,
+    pub base_color_texture: Option<PathBuf>,
+    pub shadow_normal_offset: Option<f32>,
+}
+
 pub struct Model {
     pub meshes: Vec<Mesh>,
+    pub lights: LightSet,
     pub materials: Vec<Material>,
     pub textures: Vec<Texture>,
+    /// The file each texture was read from, None for those inside the glTF or GLB.
+    pub texture_paths: Vec<Option<PathBuf>>,
+    pub animation: AnimationSet,
+    /// Name of each glTF node by index, empty for unnamed ones. The node hierarchy
+    /// itself is `animation.parents` and `animation.rest`.
+    pub node_names: Vec<String>,
+}
+
+/// Reads a custom surface snippet from material extras, either inline
+/// (`"dusk_surface_wgsl": "fn surface(...) ..."`) or from a file relative to the model
+/// (`"dusk_surface": "shaders/toon.wgsl"`).
+fn extras_value(extras: &gltf::json::Extras) -> Option<serde_json::Value> {
+    serde_json::from_str(extras.as_ref()?.get()).ok()
+}
+
+fn material_surface_hook(value: &serde_json::Value, base_dir: &Path) -> Option<String> {
+    if let Some(code) = value.get("dusk_surface_wgsl").and_then(|v| v.as_str()) {
+        return Some(code.to_string());
+    }
+    let rel = value.get("dusk_surface").and_then(|v| v.as_str())?;
+    let path = base_dir.join(rel);
+    match fs::read_to_string(&path) {
+        Ok(code) => Some(code),
+        Err(e) => {
+            log::warn!("Failed to read surface hook {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NormalImport {
+    Keep,
+    RepairBroken,
+    Regenerate,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ImportOptions {
+    pub normals: NormalImport,
+    pub smoothing_angle: f32,
+    pub generate_tangents: bool,
+    pub dither_blend: bool,
+    pub shadow_proxy_triangles: usize,
+    pub soft_fade: f32,
+    pub flip_normal_y: bool,
+    /// Merge static meshes that share a material and shadow role.
+    pub batch_by_material: bool,
+    /// Simplify static meshes without `MSFT_lod` levels into LODs.
+    pub generate_lods: bool,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            normals: NormalImport::RepairBroken,
+            smoothing_angle: 60.0,
+            generate_tangents: true,
+            dither_blend: false,
+            shadow_proxy_triangles: 100_000,
+            soft_fade: 0.1,
+            flip_normal_y: false,
+            batch_by_material: false,
+            generate_lods: false,
+        }
+    }
 }
 
 impl Model {
-    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+    pub fn apply_material_override(&mut self, o: &MaterialOverride) -> Result<()> {
+        let texture = match &o.base_color_texture {
+            Some(path) => {
+                let bytes = fs::read(path).with_context(|| format!("reading override texture {}", path.display()))?;
+                let (data, width, height) = decode_image(&bytes)
+                    .with_context(|| format!("decoding override texture {}", path.display()))?;
+                self.textures.push(Texture::from_rgba8(data, width, height));
+                Some(self.textures.len() - 1)
+            }
+            None => None,
+        };
+
+        let mut matched = 0;
+        for mat in &mut self.materials {
+            if o.material.as_ref().is_some_and(|name| *name != mat.name) {
+                continue;
+            }
+            matched += 1;
+            if let Some(tint) = o.tint {
+                for (c, t) in mat.base_color.iter_mut().zip(tint) {
+                    *c *= t;
+                }
+            }
+            if let Some(scale) = o.roughness_scale {
+                mat.roughness = (mat.roughness * scale).clamp(0.0, 1.0);
+            }
+            if let Some(scale) = o.metallic_scale {
+                mat.metallic = (mat.metallic * scale).clamp(0.0, 1.0);
+            }
+            if let Some(offset) = o.shadow_normal_offset {
+                mat.shadow_normal_offset = Some(offset);
+            }
+            if let Some(index) = texture {
+                mat.base_color_image = Some(index);
+                if mat.alpha_mode == AlphaMode::Opaque && self.textures[index].has_alpha {
+                    log::warn!("Material '{}': override texture has alpha but the material is opaque", mat.name);
+                }
+            }
+        }
+        if matched == 0 {
+            log::warn!(
+                "Material override for '{}' matched no material",
+                o.material.as_deref().unwrap_or("*")
+            );
+        }
+        Ok(())
+    }
+
+    /// Applies a placement transform to every mesh and light.
+    pub fn transform(&mut self, m: Matrix4<f32>) {
+        for mesh in &mut self.meshes {
+            mesh.transform = m * mesh.transform;
+        }
+        self.lights.transform(m);
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        let mut bounds = Aabb::empty();
+        for mesh in &self.meshes {
+            bounds.union(&mesh.world_bounds());
+        }
+        bounds
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P, options: &ImportOptions) -> Result<Self> {
+        let _span = profiler::scope("load model");
         let path = path.as_ref();
 
         if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
@@ -93,7 +554,7 @@ impl Model {
             }
         }
 
-        fn try_read_uri(base_dir: &Path, uri: &str) -> Option<Vec<u8>> {
+        fn try_read_uri(base_dir: &Path, uri: &str) -> Option<(PathBuf, Vec<u8>)> {
             let mut candidate = uri.replace('\\', "/");
             if candidate.contains("%20") {
                 candidate = candidate.replace("%20", " ");
@@ -108,7 +569,7 @@ impl Model {
             for root in &roots {
                 let direct = root.join(&candidate);
                 if let Ok(bytes) = fs::read(&direct) {
-                    return Some(bytes);
+                    return Some((direct, bytes));
                 }
             }
 
@@ -116,7 +577,7 @@ impl Model {
                 for folder in ["textures", "Textures"] {
                     let direct = root.join(folder).join(&candidate);
                     if let Ok(bytes) = fs::read(&direct) {
-                        return Some(bytes);
+                        return Some((direct, bytes));
                     }
                 }
             }
@@ -126,7 +587,7 @@ impl Model {
                 for folder in ["textures", "Textures"] {
                     let direct = root.join(folder).join(&file_name);
                     if let Ok(bytes) = fs::read(&direct) {
-                        return Some(bytes);
+                        return Some((direct, bytes));
                     }
                 }
             }
@@ -150,7 +611,7 @@ impl Model {
                             };
                             if name.to_ascii_lowercase() == file_name_lower {
                                 if let Ok(bytes) = fs::read(&path) {
-                                    return Some(bytes);
+                                    return Some((path, bytes));
                                 }
                             }
                         }
@@ -161,67 +622,33 @@ impl Model {
             None
         }
 
-        let mut textures: Vec<Texture> = Vec::new();
+        let mut image_bytes: Vec<Option<Vec<u8>>> = Vec::new();
+        let mut texture_paths: Vec<Option<PathBuf>> = Vec::new();
         for image in document.images() {
             let bytes_opt: Option<Vec<u8>> = match image.source() {
                 gltf::image::Source::Uri { uri, .. } => {
-                    try_read_uri(base_dir, uri)
+                    let read = try_read_uri(base_dir, uri);
+                    texture_paths.push(read.as_ref().map(|(path, _)| path.clone()));
+                    read.map(|(_, bytes)| bytes)
                 }
                 gltf::image::Source::View { view, .. } => {
+                    texture_paths.push(None);
                     let buffer_data = &buffers[view.buffer().index()];
                     let start = view.offset();
                     let end = start + view.length();
                     Some(buffer_data[start..end].to_vec())
                 }
             };
-
-            let (data, width, height) = if let Some(bytes) = bytes_opt {
-                let is_dds = bytes.len() >= 4 && &bytes[0..4] == b"DDS ";
-
-                if is_dds {
-                    let mut cur = Cursor::new(&bytes);
-                    if let Ok(dds) = image_dds::ddsfile::Dds::read(&mut cur) {
-                        if let Ok(img) = image_dds::image_from_dds(&dds, 0) {
-                            let (w, h) = img.dimensions();
-                            (img.into_raw(), w, h)
-                        } else {
-                            log::warn!("Failed to decode DDS image (mip0). Using fallback 1x1 white.");
-                            (vec![255u8, 255, 255, 255], 1, 1)
-                        }
-                    } else {
-                        log::warn!("Failed to parse DDS header. Using fallback 1x1 white.");
-                        (vec![255u8, 255, 255, 255], 1, 1)
-                    }
-                } else if let Ok(img) = image::load_from_memory(&bytes) {
-                    let rgba = img.to_rgba8();
-                    let (w, h) = rgba.dimensions();
-                    (rgba.into_raw(), w, h)
-                } else {
-                    log::warn!("Failed to decode image bytes (non-DDS). Using fallback 1x1 white.");
-                    (vec![255u8, 255, 255, 255], 1, 1)
-                }
-            } else {
-                (vec![255u8, 255, 255, 255], 1, 1)
-            };
-
-            let mut has_alpha = false;
-            if data.len() >= 4 {
-                for a in data.iter().skip(3).step_by(4) {
-                    if *a != 255 {
-                        has_alpha = true;
-                        break;
-                    }
-                }
-            }
-
-            textures.push(Texture {
-                data,
-                width,
-                height,
-                format: wgpu::TextureFormat::Rgba8Unorm,
-                has_alpha,
-            });
+            image_bytes.push(bytes_opt);
         }
+        // Decoding takes most of the load time of a textured model, so each image is a job.
+        let mut textures: Vec<Texture> = jobs::map(&image_bytes, |bytes_opt| {
+            let (data, width, height) = match bytes_opt {
+                Some(bytes) => decode_image(bytes).unwrap_or_else(|| (vec![255u8, 255, 255, 255], 1, 1)),
+                None => (vec![255u8, 255, 255, 255], 1, 1),
+            };
+            Texture::from_rgba8(data, width, height)
+        });
 
         let mut materials = Vec::new();
         for material in document.materials() {
@@ -240,6 +667,7 @@ impl Model {
             let normal_image = material
                 .normal_texture()
                 .map(|t| t.texture().source().index());
+            let normal_scale = material.normal_texture().map(|t| t.scale()).unwrap_or(1.0);
 
             let mut alpha_mode = match material.alpha_mode() {
                 gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
@@ -266,7 +694,39 @@ impl Model {
                 roughness = roughness.max(0.85);
             }
 
+            let extras = extras_value(material.extras());
+            let surface_hook = extras.as_ref().and_then(|e| material_surface_hook(e, base_dir));
+
+            let dither = extras
+                .as_ref()
+                .and_then(|e| e.get("dusk_dither"))
+                .and_then(|v| v.as_bool())
+                .unwrap_or(options.dither_blend);
+            if dither && alpha_mode == AlphaMode::Blend {
+                alpha_mode = AlphaMode::Dither;
+            }
+            let soft_fade = extras
+                .as_ref()
+                .and_then(|e| e.get("dusk_soft_fade"))
+                .and_then(|v| v.as_f64())
+                .map(|v| v as f32)
+                .unwrap_or(options.soft_fade);
+            let flip_normal_y = extras
+                .as_ref()
+                .and_then(|e| e.get("dusk_flip_normal_y"))
+                .and_then(|v| v.as_bool())
+                .unwrap_or(options.flip_normal_y);
+            let shadow_normal_offset = extras
+                .as_ref()
+                .and_then(|e| e.get("dusk_shadow_normal_offset"))
+                .and_then(|v| v.as_f64())
+                .map(|v| v as f32);
+
             materials.push(Material {
+                name: material
+                    .name()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("material{}", materials.len())),
                 base_color: pbr.base_color_factor(),
                 metallic,
                 roughness,
@@ -278,11 +738,17 @@ impl Model {
                 double_sided,
                 base_color_texcoord_set,
                 metallic_roughness_texcoord_set,
+                surface_hook,
+                soft_fade,
+                normal_scale,
+                flip_normal_y,
+                shadow_normal_offset,
             });
         }
 
         if materials.is_empty() {
             materials.push(Material {
+                name: "default".to_string(),
                 base_color: [1.0, 1.0, 1.0, 1.0],
                 metallic: 0.0,
                 roughness: 0.5,
@@ -294,6 +760,11 @@ impl Model {
                 double_sided: false,
                 base_color_texcoord_set: 0,
                 metallic_roughness_texcoord_set: 0,
+                surface_hook: None,
+                soft_fade: 0.0,
+                normal_scale: 1.0,
+                flip_normal_y: false,
+                shadow_normal_offset: None,
             });
         }
 
@@ -315,29 +786,72 @@ impl Model {
             Matrix4::from(cols)
         }
 
-        fn normal_matrix(m: Matrix4<f32>) -> Matrix3<f32> {
-            let a = Matrix3::new(
-                m.x.x, m.x.y, m.x.z,
-                m.y.x, m.y.y, m.y.z,
-                m.z.x, m.z.y, m.z.z,
-            );
-            a.invert().unwrap_or(Matrix3::from_scale(1.0)).transpose()
-        }
-
         fn traverse<'a>(
             node: gltf::scene::Node<'a>,
             parent: Matrix4<f32>,
             buffers: &'a [Vec<u8>],
             materials: &'a [Material],
+            options: &ImportOptions,
             meshes_out: &mut Vec<Mesh>,
+            lights_out: &mut LightSet,
         ) {
+            let skin = node.skin().map(|s| s.index());
             let local = mat4_from_cols(node.transform().matrix());
             let world = parent * local;
-            let nmat = normal_matrix(world);
+
+            if let Some(light) = node.light() {
+                match light.kind() {
+                    gltf::khr_lights_punctual::Kind::Point => {
+                        let p = world * Vector4::new(0.0, 0.0, 0.0, 1.0);
+                        lights_out.points.push(PointLight::new(
+                            Point3::new(p.x, p.y, p.z),
+                            light.color(),
+                            light.intensity(),
+                            light.range(),
+                        ));
+                    }
+                    gltf::khr_lights_punctual::Kind::Spot {
+                        inner_cone_angle,
+                        outer_cone_angle,
+                    } => {
+                        let p = world * Vector4::new(0.0, 0.0, 0.0, 1.0);
+                        let d = world * Vector4::new(0.0, 0.0, -1.0, 0.0);
+                        let mut spot = SpotLight::new(
+                            Point3::new(p.x, p.y, p.z),
+                            d.truncate().normalize(),
+                            light.color(),
+                            light.intensity(),
+                            light.range(),
+                        );
+                        spot.inner_angle = inner_cone_angle;
+                        spot.outer_angle = outer_cone_angle;
+                        lights_out.spots.push(spot);
+                    }
+                    gltf::khr_lights_punctual::Kind::Directional => {
+                        let d = world * Vector4::new(0.0, 0.0, -1.0, 0.0);
+                        lights_out.directional.push(DirectionalLight::new(
+                            d.truncate().normalize(),
+                            light.color(),
+                            light.intensity(),
+                        ));
+                    }
+                }
+            }
 
             if let Some(mesh) = node.mesh() {
+                let mesh_name = mesh
+                    .name()
+                    .or(node.name())
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("mesh{}", mesh.index()));
+                let is_proxy = [node.extras(), mesh.extras()].into_iter().any(|extras| {
+                    extras_value(extras)
+                        .and_then(|e| e.get("dusk_shadow_proxy").and_then(|v| v.as_bool()))
+                        .unwrap_or(false)
+                });
+                let shadow = if is_proxy { ShadowRole::Proxy } else { ShadowRole::Caster };
                 for primitive in mesh.primitives() {
-                    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                    let attribute = |semantic: gltf::Semantic| primitive.get(&semantic);
 
                     let material_index = primitive.material().index().unwrap_or(0);
                     let uv_set = materials
@@ -351,66 +865,286 @@ impl Model {
                         })
                         .unwrap_or(0);
 
-                    let positions: Vec<[f32; 3]> = reader
-                        .read_positions()
-                        .map(|iter| iter.collect())
+                    let positions: Vec<[f32; 3]> = attribute(gltf::Semantic::Positions)
+                        .and_then(|a| accessor::read_f32(&a, buffers))
                         .unwrap_or_default();
 
-                    let normals: Vec<[f32; 3]> = reader
-                        .read_normals()
-                        .map(|iter| iter.collect())
-                        .unwrap_or_else(|| vec![[0.0, 1.0, 0.0]; positions.len()]);
+                    let normals: Option<Vec<[f32; 3]>> = attribute(gltf::Semantic::Normals)
+                        .and_then(|a| accessor::read_f32(&a, buffers))
+                        .filter(|n: &Vec<[f32; 3]>| n.len() == positions.len());
+                    let normals_missing = normals.is_none();
 
-                    let tex_coords: Vec<[f32; 2]> = reader
-                        .read_tex_coords(uv_set)
-                        .or_else(|| reader.read_tex_coords(0))
-                        .map(|iter| iter.into_f32().collect())
+                    let tex_coords: Vec<[f32; 2]> = attribute(gltf::Semantic::TexCoords(uv_set))
+                        .or_else(|| attribute(gltf::Semantic::TexCoords(0)))
+                        .and_then(|a| accessor::read_f32(&a, buffers))
                         .unwrap_or_else(|| vec![[0.0, 0.0]; positions.len()]);
 
-                    let mut vertices: Vec<Vertex> = Vec::with_capacity(positions.len());
-                    for ((pos, norm), uv) in positions.iter().zip(normals.iter()).zip(tex_coords.iter()) {
-                        let wp = world * Vector4::new(pos[0], pos[1], pos[2], 1.0);
-                        let nn = nmat * Vector3::new(norm[0], norm[1], norm[2]);
-                        let nn = nn.normalize();
-                        vertices.push(Vertex {
-                            position: [wp.x, wp.y, wp.z],
-                            normal: [nn.x, nn.y, nn.z],
-                            tex_coords: *uv,
-                        });
-                    }
+                    let mut vertices: Vec<Vertex> = positions
+                        .iter()
+                        .enumerate()
+                        .map(|(i, pos)| Vertex {
+                            position: *pos,
+                            normal: normals.as_ref().map(|n| n[i]).unwrap_or([0.0, 1.0, 0.0]),
+                            tex_coords: tex_coords.get(i).copied().unwrap_or([0.0, 0.0]),
+                        })
+                        .collect();
+
+                    let mut indices: Vec<u32> = primitive
+                        .indices()
+                        .and_then(|a| accessor::read_u32::<1>(&a, buffers))
+                        .map(|i| i.into_iter().map(|[i]| i).collect())
+                        .unwrap_or_else(|| (0..vertices.len() as u32).collect());
 
-                    let indices: Vec<u32> = reader
-                        .read_indices()
-                        .map(|iter| iter.into_u32().collect())
+                    let (mut joints, mut weights): (Vec<[u16; 4]>, Vec<[f32; 4]>) = if skin.is_some() {
+                        (
+                            attribute(gltf::Semantic::Joints(0))
+                                .and_then(|a| accessor::read_u32::<4>(&a, buffers))
+                                .map(|j| j.into_iter().map(|j| j.map(|i| i as u16)).collect())
+                                .unwrap_or_default(),
+                            attribute(gltf::Semantic::Weights(0))
+                                .and_then(|a| accessor::read_f32(&a, buffers))
+                                .unwrap_or_default(),
+                        )
+                    } else {
+                        (Vec::new(), Vec::new())
+                    };
+                    let mut tangents: Vec<[f32; 4]> = attribute(gltf::Semantic::Tangents)
+                        .and_then(|a| accessor::read_f32(&a, buffers))
                         .unwrap_or_default();
 
+                    let regenerate = match options.normals {
+                        NormalImport::Keep => false,
+                        NormalImport::Regenerate => true,
+                        NormalImport::RepairBroken => normals_missing || geometry::normals_are_broken(&vertices),
+                    };
+                    if regenerate {
+                        let remap = geometry::smooth_normals(&mut vertices, &mut indices, options.smoothing_angle);
+                        fn remap_attr<T: Copy>(attr: &mut Vec<T>, remap: &[u32]) {
+                            if !attr.is_empty() {
+                                *attr = remap.iter().filter_map(|&i| attr.get(i as usize).copied()).collect();
+                            }
+                        }
+                        remap_attr(&mut joints, &remap);
+                        remap_attr(&mut weights, &remap);
+                        tangents.clear();
+                    }
+                    if options.generate_tangents && geometry::tangents_are_broken(&tangents, vertices.len()) {
+                        tangents = geometry::generate_tangents(&vertices, &indices);
+                    }
+
                     meshes_out.push(Mesh {
+                        name: format!("{}/{}", mesh_name, primitive.index()),
                         vertices,
                         indices,
                         material_index,
+                        node: node.index(),
+                        skin,
+                        joints,
+                        weights,
+                        tangents,
+                        shadow,
+                        lods: Vec::new(),
+                        transform: world,
+                        source: Some((mesh.index(), primitive.index())),
                     });
                 }
             }
 
             for child in node.children() {
-                traverse(child, world, buffers, materials, meshes_out);
+                traverse(child, world, buffers, materials, options, meshes_out, lights_out);
             }
         }
 
+        let mut lights = LightSet::default();
         for node in scene.nodes() {
             traverse(
                 node,
                 Matrix4::from_scale(1.0),
                 &buffers,
                 &materials,
+                options,
                 &mut meshes,
+                &mut lights,
             );
         }
 
+        if meshes.iter().any(|m| m.shadow == ShadowRole::Proxy) {
+            for mesh in &mut meshes {
+                if mesh.shadow == ShadowRole::Caster {
+                    mesh.shadow = ShadowRole::NonCaster;
+                }
+            }
+        }
+
+        let node_count = document.nodes().len();
+        let mut parents = vec![None; node_count];
+        for node in document.nodes() {
+            for child in node.children() {
+                parents[child.index()] = Some(node.index());
+            }
+        }
+
+        // MSFT_lod: the lower levels are nodes outside the scene, loaded here with the
+        // parent transform of the node that names them and matched to its meshes by material.
+        for node in document.nodes() {
+            let Some((ids, coverage)) = msft_lod(&node) else {
+                continue;
+            };
+            let mut parent_world = Matrix4::from_scale(1.0);
+            let mut ancestor = parents[node.index()];
+            while let Some(n) = ancestor.and_then(|i| document.nodes().nth(i)) {
+                parent_world = mat4_from_cols(n.transform().matrix()) * parent_world;
+                ancestor = parents[n.index()];
+            }
+            for (level, id) in ids.into_iter().enumerate() {
+                let Some(lod_node) = document.nodes().nth(id) else {
+                    log::warn!("{}: MSFT_lod names missing node {}", path.display(), id);
+                    continue;
+                };
+                let mut lod_meshes = Vec::new();
+                let mut lod_lights = LightSet::default();
+                traverse(lod_node, parent_world, &buffers, &materials, options, &mut lod_meshes, &mut lod_lights);
+                let coverage = coverage.get(level).copied().unwrap_or_else(|| default_lod_coverage(level));
+                for host in meshes.iter_mut().filter(|m| m.node == node.index() && m.skin.is_none()) {
+                    let Some(i) = lod_meshes.iter().position(|m| m.material_index == host.material_index) else {
+                        continue;
+                    };
+                    // Into the host's object space, which the LOD is drawn with.
+                    let mut lod = lod_meshes.remove(i);
+                    lod.apply(host.transform.invert().unwrap_or(Matrix4::from_scale(1.0)) * lod.transform);
+                    host.lods.push(MeshLod {
+                        vertices: lod.vertices,
+                        indices: lod.indices,
+                        coverage,
+                    });
+                }
+            }
+        }
+        let rest = document
+            .nodes()
+            .map(|n| NodeTransform::from_decomposed(n.transform().decomposed()))
+            .collect();
+
+        let skins = document
+            .skins()
+            .map(|skin| {
+                let joints: Vec<usize> = skin.joints().map(|j| j.index()).collect();
+                let inverse_bind_matrices = skin
+                    .inverse_bind_matrices()
+                    .and_then(|a| accessor::read_mat4(&a, &buffers))
+                    .map(|m| m.into_iter().map(Matrix4::from).collect())
+                    .unwrap_or_else(|| vec![Matrix4::from_scale(1.0); joints.len()]);
+                Skin {
+                    joints,
+                    inverse_bind_matrices,
+                }
+            })
+            .collect();
+
+        let mut clips = Vec::new();
+        for (i, animation) in document.animations().enumerate() {
+            let mut channels = Vec::new();
+            let mut duration = 0.0f32;
+            for channel in animation.channels() {
+                let sampler = channel.sampler();
+                let Some(times) = accessor::read_f32::<1>(&sampler.input(), &buffers) else {
+                    continue;
+                };
+                let times: Vec<f32> = times.into_iter().map(|[t]| t).collect();
+                let output = sampler.output();
+                let values = match channel.target().property() {
+                    gltf::animation::Property::Translation => match accessor::read_f32::<3>(&output, &buffers) {
+                        Some(v) => ChannelValues::Translation(v.into_iter().map(Vector3::from).collect()),
+                        None => continue,
+                    },
+                    gltf::animation::Property::Rotation => match accessor::read_f32::<4>(&output, &buffers) {
+                        Some(v) => ChannelValues::Rotation(
+                            v.into_iter()
+                                .map(|r| cgmath::Quaternion::new(r[3], r[0], r[1], r[2]))
+                                .collect(),
+                        ),
+                        None => continue,
+                    },
+                    gltf::animation::Property::Scale => match accessor::read_f32::<3>(&output, &buffers) {
+                        Some(v) => ChannelValues::Scale(v.into_iter().map(Vector3::from).collect()),
+                        None => continue,
+                    },
+                    gltf::animation::Property::MorphTargetWeights => continue,
+                };
+                let interpolation = match channel.sampler().interpolation() {
+                    gltf::animation::Interpolation::Step => Interpolation::Step,
+                    gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                    gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+                };
+                let channel = Channel {
+                    node: channel.target().node().index(),
+                    interpolation,
+                    times,
+                    values,
+                };
+                if !channel.is_complete() {
+                    log::warn!(
+                        "Animation {}: dropping a channel of node {} with {} values for {} {:?} keyframes",
+                        animation.name().unwrap_or(&i.to_string()),
+                        channel.node,
+                        channel.values.len(),
+                        channel.times.len(),
+                        channel.interpolation,
+                    );
+                    continue;
+                }
+                if let Some(&last) = channel.times.last() {
+                    duration = duration.max(last);
+                }
+                channels.push(channel);
+            }
+            clips.push(AnimationClip {
+                name: animation
+                    .name()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("Animation {}", i)),
+                duration,
+                channels,
+            });
+        }
+
+        let animation = AnimationSet {
+            parents,
+            rest,
+            clips,
+            skins,
+        };
+        let animated_nodes = animation.animated_nodes();
+        let mut meshes = if options.batch_by_material {
+            let before = meshes.len();
+            let meshes = batch_by_material(meshes, &animated_nodes);
+            log::info!("{}: batched {} meshes into {}", path.display(), before, meshes.len());
+            meshes
+        } else {
+            meshes
+        };
+        if options.generate_lods {
+            let _span = profiler::scope("generate lods");
+            let mut generated = 0;
+            for mesh in &mut meshes {
+                let animated = mesh.skin.is_some() || animated_nodes.get(mesh.node).copied().unwrap_or(false);
+                if mesh.lods.is_empty() && !animated && mesh.shadow != ShadowRole::Proxy {
+                    generate_lods(mesh);
+                    generated += !mesh.lods.is_empty() as usize;
+                }
+            }
+            log::info!("{}: generated LODs for {} meshes", path.display(), generated);
+        }
+
+        let node_names = document.nodes().map(|n| n.name().unwrap_or_default().to_string()).collect();
         Ok(Model {
             meshes,
+            lights,
             materials,
             textures,
+            texture_paths,
+            animation,
+            node_names,
         })
     }
 }
@@ -0,0 +1,217 @@
+use crate::post::{HdrTarget, HDR_FORMAT};
+
+pub const DEFAULT_SHUTTER_ANGLE: f32 = 180.0;
+/// Longest blur in pixels, so a frame hitch does not smear the whole frame.
+const MAX_BLUR: f32 = 48.0;
+
+fn create_color_copy(device: &wgpu::Device, width: u32, height: u32) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Motion Blur Color"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: HDR_FORMAT,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    (texture, view)
+}
+
+/// Reconstruction-filter blur of the HDR frame along the velocity buffer's motion.
+pub struct MotionBlur {
+    pub enabled: bool,
+    /// Degrees of the frame the shutter is open for; 360 blurs over the whole frame time.
+    pub shutter_angle: f32,
+    color: wgpu::Texture,
+    color_view: wgpu::TextureView,
+    params_buffer: wgpu::Buffer,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    frame: u32,
+}
+
+impl MotionBlur {
+    pub fn new(
+        device: &wgpu::Device,
+        camera_buffer: &wgpu::Buffer,
+        (depth_view, velocity_view): (&wgpu::TextureView, &wgpu::TextureView),
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let (color, color_view) = create_color_copy(device, width, height);
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Motion Blur Params Buffer"),
+            size: std::mem::size_of::<[f32; 4]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let uniform = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let texture = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type,
+            },
+            count: None,
+        };
+        let float = wgpu::TextureSampleType::Float { filterable: false };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("motion_blur_bind_group_layout"),
+            entries: &[
+                uniform(0),
+                texture(1, wgpu::TextureSampleType::Depth),
+                uniform(2),
+                texture(3, float),
+                texture(4, float),
+            ],
+        });
+        let bind_group = Self::create_bind_group(
+            device,
+            &layout,
+            camera_buffer,
+            (depth_view, velocity_view),
+            &params_buffer,
+            &color_view,
+        );
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Motion Blur Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("motion_blur.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Motion Blur Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Motion Blur Pipeline"),
+            layout: Some(&pipeline_layout),
+            cache: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_fullscreen",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_blur",
+                targets: &[Some(HDR_FORMAT.into())],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            enabled: false,
+            shutter_angle: DEFAULT_SHUTTER_ANGLE,
+            color,
+            color_view,
+            params_buffer,
+            layout,
+            bind_group,
+            pipeline,
+            frame: 0,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        camera_buffer: &wgpu::Buffer,
+        (depth_view, velocity_view): (&wgpu::TextureView, &wgpu::TextureView),
+        params_buffer: &wgpu::Buffer,
+        color_view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("motion_blur_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(color_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(velocity_view),
+                },
+            ],
+        })
+    }
+
+    /// Reallocates the color copy for a new size and rebinds the resized depth and velocity
+    /// buffers, returning the old texture so it can be destroyed once the GPU is done with it.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        camera_buffer: &wgpu::Buffer,
+        views: (&wgpu::TextureView, &wgpu::TextureView),
+        width: u32,
+        height: u32,
+    ) -> wgpu::Texture {
+        let (color, color_view) = create_color_copy(device, width, height);
+        self.color_view = color_view;
+        self.bind_group =
+            Self::create_bind_group(device, &self.layout, camera_buffer, views, &self.params_buffer, &self.color_view);
+        std::mem::replace(&mut self.color, color)
+    }
+
+    /// Blurs `hdr` along the velocity buffer, which must already hold this frame's motion.
+    pub fn record(&mut self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, hdr: &HdrTarget) {
+        let settings = [self.shutter_angle / 360.0, MAX_BLUR, self.frame as f32, 0.0];
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&settings));
+        self.frame = (self.frame + 1) % 1024;
+
+        encoder.copy_texture_to_texture(hdr.texture.as_image_copy(), self.color.as_image_copy(), hdr.texture.size());
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Motion Blur"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &hdr.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,116 @@
+// Camera motion blur: gathers along each pixel's motion from the velocity buffer with the
+// McGuire et al. 2012 reconstruction weights, so foreground edges stay sharp over a moving
+// background.
+
+// Prefix of CameraUniform in shader.wgsl.
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    view_inv: mat4x4<f32>,
+    proj_inv: mat4x4<f32>,
+};
+
+struct BlurParams {
+    // x: shutter open fraction of the frame (shutter angle / 360), y: longest blur in
+    // pixels, z: frame index.
+    settings: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(0) @binding(1)
+var depth_map: texture_depth_2d;
+
+@group(0) @binding(2)
+var<uniform> params: BlurParams;
+
+// Copy of the HDR frame before the blur.
+@group(0) @binding(3)
+var color_map: texture_2d<f32>;
+
+// Screen-space motion since last frame in pixels, from the velocity buffer.
+@group(0) @binding(4)
+var velocity_map: texture_2d<f32>;
+
+const SAMPLES: i32 = 12;
+// View-space distance over which the depth comparison goes from front to back.
+const DEPTH_SOFTNESS: f32 = 0.1;
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vid << 1u) & 2u), f32(vid & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+fn ndc_at(pixel: vec2<i32>, depth: f32) -> vec4<f32> {
+    let uv = (vec2<f32>(pixel) + 0.5) / vec2<f32>(textureDimensions(depth_map));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+}
+
+fn view_depth(pixel: vec2<i32>) -> f32 {
+    let view = camera.proj_inv * ndc_at(pixel, textureLoad(depth_map, pixel, 0));
+    // The sky of an infinite far plane has w = 0.
+    return -view.z / max(view.w, 1e-20);
+}
+
+fn blur_vector(pixel: vec2<i32>) -> vec2<f32> {
+    let v = textureLoad(velocity_map, pixel, 0).xy * params.settings.x;
+    let len = length(v);
+    if len > params.settings.y {
+        return v * (params.settings.y / len);
+    }
+    return v;
+}
+
+// 1 when `a` is in front of `b`, fading over DEPTH_SOFTNESS.
+fn soft_depth_compare(a: f32, b: f32) -> f32 {
+    return clamp(1.0 - (a - b) / DEPTH_SOFTNESS, 0.0, 1.0);
+}
+
+// Whether a pixel blurred over `len` pixels covers a point `dist` pixels away.
+fn cone(dist: f32, len: f32) -> f32 {
+    return clamp(1.0 - dist / max(len, 1e-3), 0.0, 1.0);
+}
+
+fn cylinder(dist: f32, len: f32) -> f32 {
+    return 1.0 - smoothstep(0.95 * len, 1.05 * len, dist);
+}
+
+fn interleaved_gradient_noise(p: vec2<f32>) -> f32 {
+    return fract(52.9829189 * fract(dot(p, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+@fragment
+fn fs_blur(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(pos.xy);
+    let size = vec2<i32>(textureDimensions(color_map));
+    let center = textureLoad(color_map, pixel, 0);
+    let v = blur_vector(pixel);
+    let len = length(v);
+    if len < 0.5 {
+        return center;
+    }
+
+    let depth = view_depth(pixel);
+    var weight = 1.0 / len;
+    var sum = center.rgb * weight;
+    let jitter = interleaved_gradient_noise(pos.xy + params.settings.z * 5.588238) - 0.5;
+    for (var i = 0; i < SAMPLES; i++) {
+        // Symmetric taps from -v/2 to v/2, jittered to trade banding for noise.
+        let t = mix(-0.5, 0.5, (f32(i) + jitter + 0.5) / f32(SAMPLES));
+        let p = clamp(vec2<i32>(pos.xy + v * t), vec2<i32>(0), size - 1);
+        let dist = len * abs(t);
+        let sample_len = length(blur_vector(p));
+        let sample_depth = view_depth(p);
+        let front = soft_depth_compare(sample_depth, depth);
+        let back = soft_depth_compare(depth, sample_depth);
+        // Sample in front blurring over us, us blurring over the sample behind, or both
+        // moving together.
+        let w = front * cone(dist, sample_len)
+            + back * cone(dist, len)
+            + cylinder(dist, sample_len) * cylinder(dist, len) * 2.0;
+        sum += textureLoad(color_map, p, 0).rgb * w;
+        weight += w;
+    }
+    return vec4<f32>(sum / weight, center.a);
+}
@@ -0,0 +1,107 @@
+use std::ops::Range;
+
+use cgmath::{Matrix, Matrix4, SquareMatrix};
+
+/// Binding of the object table in the camera and shadow caster bind groups.
+pub const OBJECTS_BINDING: u32 = 19;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ObjectUniform {
+    model: [[f32; 4]; 4],
+    /// Inverse transpose of the upper 3x3 for normals, columns padded to four floats.
+    normal: [[f32; 4]; 3],
+    /// x: material id for bindless materials.
+    material: [u32; 4],
+}
+
+impl ObjectUniform {
+    fn new(transform: Matrix4<f32>, material: usize) -> Self {
+        let normal = transform.invert().unwrap_or(Matrix4::from_scale(1.0)).transpose();
+        Self {
+            model: transform.into(),
+            normal: [normal.x.into(), normal.y.into(), normal.z.into()],
+            material: [material as u32, 0, 0, 0],
+        }
+    }
+}
+
+/// The object-to-world transform and material of every scene mesh in one storage
+/// buffer, read by the vertex shaders with the instance index: mesh `i` is drawn as
+/// instance `i`. Meshes keep their vertices in object space, so they can move at runtime
+/// and nodes instancing one glTF mesh share its geometry.
+pub struct SceneObjects {
+    buffer: wgpu::Buffer,
+    objects: Vec<ObjectUniform>,
+    dirty: bool,
+}
+
+impl SceneObjects {
+    /// Room for `capacity` objects, pushed once the meshes are loaded.
+    pub fn new(device: &wgpu::Device, capacity: usize) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Scene Object Buffer"),
+            size: (capacity.max(1) * std::mem::size_of::<ObjectUniform>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            buffer,
+            objects: Vec::with_capacity(capacity),
+            dirty: false,
+        }
+    }
+
+    pub fn layout_entry() -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding: OBJECTS_BINDING,
+            visibility: wgpu::ShaderStages::VERTEX,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    }
+
+    pub fn entry(&self) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: OBJECTS_BINDING,
+            resource: self.buffer.as_entire_binding(),
+        }
+    }
+
+    /// Adds the object of the next scene mesh.
+    pub fn push(&mut self, transform: Matrix4<f32>, material: usize) {
+        self.objects.push(ObjectUniform::new(transform, material));
+        self.dirty = true;
+    }
+
+    pub fn set_transform(&mut self, index: usize, transform: Matrix4<f32>) {
+        if let Some(object) = self.objects.get_mut(index) {
+            *object = ObjectUniform::new(transform, object.material[0] as usize);
+            self.dirty = true;
+        }
+    }
+
+    pub fn set_material(&mut self, index: usize, material: usize) {
+        if let Some(object) = self.objects.get_mut(index) {
+            object.material[0] = material as u32;
+            self.dirty = true;
+        }
+    }
+
+    /// Writes the objects to the GPU if any changed since the last call.
+    pub fn upload(&mut self, queue: &wgpu::Queue) {
+        if self.dirty {
+            queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&self.objects));
+            self.dirty = false;
+        }
+    }
+}
+
+/// Instance range that draws scene mesh `index` with its object.
+pub fn instance(index: usize) -> Range<u32> {
+    index as u32..index as u32 + 1
+}
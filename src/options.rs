@@ -0,0 +1,267 @@
+//! What a [`Renderer`](crate::Renderer) is built with. The binary fills these in from its
+//! command line (see [`crate::cli`]); a program embedding the engine sets the ones it
+//! wants over [`RendererOptions::default`]. Settings left as None fall back to `dusk.toml`
+//! and then the engine's defaults.
+
+use std::path::PathBuf;
+
+pub use crate::camera::CameraSettings;
+pub use crate::evsm::ShadowFilter;
+pub use crate::exposure::AutoExposure;
+pub use crate::latency::LatencySettings;
+pub use crate::layout::{Layout, SceneEntry};
+pub use crate::material_debug::MaterialDebugMode;
+pub use crate::model::ImportOptions;
+pub use crate::scene::SceneSource;
+pub use crate::shadows::ShadowBias;
+pub use crate::vertex_packing::VertexPacking;
+pub use crate::window_mode::FullscreenMode;
+use crate::{atmosphere, camera_path, config, fog, lightmap, motion_blur, probes, sky, ssao, ssgi, upscale};
+
+/// Every setting a renderer is built from, as the command-line flags in the README give
+/// them.
+#[derive(Clone, Debug)]
+pub struct RendererOptions {
+    /// Models and scene files, in the order they are placed. Empty shows the scene of
+    /// `dusk.toml`, or Sponza.
+    pub scene: Vec<SceneSource>,
+    /// Environment HDR, over the scene's and one found next to the models.
+    pub hdr: Option<PathBuf>,
+    /// The engine config file.
+    pub config: PathBuf,
+    pub backends: wgpu::Backends,
+    /// Adapter to render on: its index among the adapters or part of its name.
+    pub gpu: Option<String>,
+    pub import: ImportOptions,
+    pub layout: Option<Layout>,
+    /// Animated characters for the crowd benchmark, and the frames it runs for.
+    pub crowd: Option<u32>,
+    pub crowd_frames: Option<u32>,
+    pub latency: LatencySettings,
+    /// Over the present mode in `latency` when set.
+    pub vsync: Option<bool>,
+    pub fps_cap: Option<f32>,
+    /// Rate in hertz of fixed simulation steps.
+    pub fixed_timestep: Option<f32>,
+    /// Job threads; 0 uses one per core.
+    pub job_threads: usize,
+    pub crash_dialog: bool,
+    pub camera: CameraOverrides,
+    /// Degrees the field of view widens while sprinting, and narrows to while zoomed.
+    pub sprint_fov: Option<f32>,
+    pub zoom_fov: Option<f32>,
+    /// Inner window size in pixels.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fullscreen: bool,
+    pub fullscreen_mode: Option<FullscreenMode>,
+    pub monitor: Option<usize>,
+    pub top_view: bool,
+    /// Side-by-side stereo with this eye distance in metres.
+    pub stereo: Option<f32>,
+    /// Render into an OpenXR headset; needs the `openxr` feature.
+    pub openxr: bool,
+    pub camera_path: PathBuf,
+    /// Seconds to play the camera path over, instead of its own timing.
+    pub path_duration: Option<f32>,
+    /// Directory to write every frame of the camera path to.
+    pub path_capture: Option<PathBuf>,
+    pub path_fps: f32,
+    pub play_path: bool,
+    pub screenshot_supersample: u32,
+    /// Write screenshots as EXR as well as PNG.
+    pub exr: bool,
+    pub bindings: PathBuf,
+    /// Snapshot to start from.
+    pub snapshot: Option<PathBuf>,
+    /// Shot matrix to render offscreen instead of opening the window.
+    pub batch: Option<PathBuf>,
+    /// Post-processing stack file.
+    pub post: Option<PathBuf>,
+    /// Chrome trace file to write the profiler scopes to.
+    pub profile_trace: Option<PathBuf>,
+    pub shadow_filter: Option<ShadowFilter>,
+    pub shadow_resolution: Option<u32>,
+    pub shadow_cascades: Option<u32>,
+    pub shadow_bias: ShadowBias,
+    /// Fit the shadow cascades to the depth range on screen.
+    pub sdsm: bool,
+    /// Hours, 0-24.
+    pub time_of_day: Option<f32>,
+    /// Seconds for a whole day to pass.
+    pub day_length: Option<f32>,
+    pub procedural_sky: bool,
+    pub turbidity: f32,
+    pub atmosphere: bool,
+    /// Kilometres per scene unit for the atmosphere.
+    pub atmosphere_scale: f32,
+    /// Bake irradiance probes, `probe_grid` of them along each axis.
+    pub probes: bool,
+    pub probe_grid: [u32; 3],
+    pub ssao: bool,
+    pub ssao_radius: f32,
+    pub ssao_intensity: f32,
+    pub ssgi: bool,
+    pub ssgi_radius: f32,
+    pub ssgi_intensity: f32,
+    pub motion_blur: bool,
+    /// Degrees, 0-360.
+    pub shutter_angle: f32,
+    pub taa: bool,
+    pub fxaa: bool,
+    pub deferred: bool,
+    pub reversed_z: bool,
+    pub infinite_far: bool,
+    pub depth_prepass: bool,
+    /// Fraction of the window size to render at, 0.25-1.
+    pub render_scale: Option<f32>,
+    /// RCAS sharpening in stops; 0 is sharpest.
+    pub sharpness: f32,
+    pub ddgi: bool,
+    pub vct: bool,
+    pub rt_shadows: bool,
+    pub gpu_culling: bool,
+    pub fog: bool,
+    pub fog_density: f32,
+    pub fog_anisotropy: f32,
+    pub fog_height_falloff: f32,
+    pub lightmaps: bool,
+    pub lightmap_size: u32,
+    pub lightmap_samples: u32,
+    /// Over the scene file's exposure.
+    pub ev100: Option<f32>,
+    pub env_nits: Option<f32>,
+    pub auto_exposure: AutoExposure,
+    pub material_debug: MaterialDebugMode,
+    /// Bytes of texture memory for texture streaming; None uploads every texture whole.
+    pub texture_budget: Option<u64>,
+    pub bindless: bool,
+    pub vertex_packing: VertexPacking,
+}
+
+impl Default for RendererOptions {
+    fn default() -> Self {
+        Self {
+            scene: Vec::new(),
+            hdr: None,
+            config: PathBuf::from(config::DEFAULT_CONFIG_PATH),
+            backends: wgpu::Backends::all(),
+            gpu: None,
+            import: ImportOptions::default(),
+            layout: None,
+            crowd: None,
+            crowd_frames: None,
+            latency: LatencySettings::default(),
+            vsync: None,
+            fps_cap: None,
+            fixed_timestep: None,
+            job_threads: 0,
+            crash_dialog: true,
+            camera: CameraOverrides::default(),
+            sprint_fov: None,
+            zoom_fov: None,
+            width: None,
+            height: None,
+            fullscreen: false,
+            fullscreen_mode: None,
+            monitor: None,
+            top_view: false,
+            stereo: None,
+            openxr: false,
+            camera_path: PathBuf::from(camera_path::DEFAULT_CAMERA_PATH),
+            path_duration: None,
+            path_capture: None,
+            path_fps: 30.0,
+            play_path: false,
+            screenshot_supersample: 1,
+            exr: false,
+            bindings: PathBuf::from(crate::bindings::DEFAULT_BINDINGS_PATH),
+            snapshot: None,
+            batch: None,
+            post: None,
+            profile_trace: None,
+            shadow_filter: None,
+            shadow_resolution: None,
+            shadow_cascades: None,
+            shadow_bias: ShadowBias::default(),
+            sdsm: true,
+            time_of_day: None,
+            day_length: None,
+            procedural_sky: false,
+            turbidity: sky::DEFAULT_TURBIDITY,
+            atmosphere: false,
+            atmosphere_scale: atmosphere::DEFAULT_KM_PER_UNIT,
+            probes: false,
+            probe_grid: probes::DEFAULT_PROBE_COUNTS,
+            ssao: true,
+            ssao_radius: ssao::DEFAULT_RADIUS,
+            ssao_intensity: ssao::DEFAULT_INTENSITY,
+            ssgi: false,
+            ssgi_radius: ssgi::DEFAULT_RADIUS,
+            ssgi_intensity: ssgi::DEFAULT_INTENSITY,
+            motion_blur: false,
+            shutter_angle: motion_blur::DEFAULT_SHUTTER_ANGLE,
+            taa: false,
+            fxaa: false,
+            deferred: false,
+            reversed_z: false,
+            infinite_far: false,
+            depth_prepass: false,
+            render_scale: None,
+            sharpness: upscale::DEFAULT_SHARPNESS,
+            ddgi: false,
+            vct: false,
+            rt_shadows: false,
+            gpu_culling: false,
+            fog: false,
+            fog_density: fog::DEFAULT_DENSITY,
+            fog_anisotropy: fog::DEFAULT_ANISOTROPY,
+            fog_height_falloff: 0.0,
+            lightmaps: false,
+            lightmap_size: lightmap::DEFAULT_RESOLUTION,
+            lightmap_samples: lightmap::DEFAULT_SAMPLES,
+            ev100: None,
+            env_nits: None,
+            auto_exposure: AutoExposure::default(),
+            material_debug: MaterialDebugMode::Off,
+            texture_budget: None,
+            bindless: true,
+            vertex_packing: VertexPacking::Full,
+        }
+    }
+}
+
+/// Camera settings over those of `dusk.toml`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct CameraOverrides {
+    pub znear: Option<f32>,
+    pub zfar: Option<f32>,
+    pub fovy: Option<f32>,
+    pub move_speed: Option<f32>,
+    pub sprint_speed: Option<f32>,
+    pub mouse_sensitivity: Option<f32>,
+    pub acceleration: Option<f32>,
+    pub deceleration: Option<f32>,
+    pub look_smoothing: Option<f32>,
+}
+
+impl CameraOverrides {
+    pub fn apply(&self, settings: &mut CameraSettings) {
+        let fields = [
+            (self.znear, &mut settings.znear),
+            (self.zfar, &mut settings.zfar),
+            (self.fovy, &mut settings.fovy),
+            (self.move_speed, &mut settings.move_speed),
+            (self.sprint_speed, &mut settings.sprint_speed),
+            (self.mouse_sensitivity, &mut settings.mouse_sensitivity),
+            (self.acceleration, &mut settings.acceleration),
+            (self.deceleration, &mut settings.deceleration),
+            (self.look_smoothing, &mut settings.look_smoothing),
+        ];
+        for (value, setting) in fields {
+            if let Some(value) = value {
+                *setting = value;
+            }
+        }
+    }
+}
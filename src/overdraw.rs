@@ -0,0 +1,248 @@
+use crate::objects::SceneObjects;
+use crate::pipelines::vertex_buffer_layout;
+use crate::vertex_packing::VertexPacking;
+
+/// Half floats count exactly up to 2048 layers and can be blended.
+const COUNT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R16Float;
+
+fn create_counts(device: &wgpu::Device, width: u32, height: u32) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Overdraw Counts"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: COUNT_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    (texture, view)
+}
+
+/// Overdraw heatmap: counts the fragments every mesh rasterizes per pixel, regardless of
+/// depth, and replaces the frame with a color ramp of the counts.
+pub struct Overdraw {
+    counts: wgpu::Texture,
+    counts_view: wgpu::TextureView,
+    camera_bind_group: wgpu::BindGroup,
+    heatmap_layout: wgpu::BindGroupLayout,
+    heatmap_bind_group: wgpu::BindGroup,
+    /// Back-face culled and double-sided.
+    count_pipelines: [wgpu::RenderPipeline; 2],
+    heatmap_pipeline: wgpu::RenderPipeline,
+}
+
+impl Overdraw {
+    pub fn new(
+        device: &wgpu::Device,
+        caster_layout: &wgpu::BindGroupLayout,
+        packing: VertexPacking,
+        (camera_buffer, objects): (&wgpu::Buffer, &SceneObjects),
+        output_format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let (counts, counts_view) = create_counts(device, width, height);
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Overdraw Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("overdraw.wgsl").into()),
+        });
+        let camera_bind_group = Self::create_camera_bind_group(device, caster_layout, (camera_buffer, objects));
+        let count_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Overdraw Count Pipeline Layout"),
+            bind_group_layouts: &[caster_layout],
+            push_constant_ranges: &[],
+        });
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let count_pipelines = [Some(wgpu::Face::Back), None].map(|cull_mode| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Overdraw Count Pipeline"),
+                layout: Some(&count_layout),
+                cache: None,
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_count",
+                    buffers: &[vertex_buffer_layout(packing)],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_count",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: COUNT_FORMAT,
+                        blend: Some(wgpu::BlendState {
+                            color: additive,
+                            alpha: additive,
+                        }),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    cull_mode,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        });
+
+        let heatmap_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("overdraw_heatmap_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                },
+                count: None,
+            }],
+        });
+        let heatmap_bind_group = Self::create_heatmap_bind_group(device, &heatmap_layout, &counts_view);
+        let heatmap_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Overdraw Heatmap Pipeline Layout"),
+            bind_group_layouts: &[&heatmap_layout],
+            push_constant_ranges: &[],
+        });
+        let heatmap_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Overdraw Heatmap Pipeline"),
+            layout: Some(&heatmap_pipeline_layout),
+            cache: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_fullscreen",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_heatmap",
+                targets: &[Some(output_format.into())],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            counts,
+            counts_view,
+            camera_bind_group,
+            heatmap_layout,
+            heatmap_bind_group,
+            count_pipelines,
+            heatmap_pipeline,
+        }
+    }
+
+    fn create_heatmap_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        counts_view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("overdraw_heatmap_bind_group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(counts_view),
+            }],
+        })
+    }
+
+    /// Reallocates the counter for a new size, returning the old texture so it can be
+    /// destroyed once the GPU is done with it.
+    /// Binds the camera to `objects`, a new object table for the scene meshes.
+    pub fn bind_objects(
+        &mut self,
+        device: &wgpu::Device,
+        caster_layout: &wgpu::BindGroupLayout,
+        (camera_buffer, objects): (&wgpu::Buffer, &SceneObjects),
+    ) {
+        self.camera_bind_group = Self::create_camera_bind_group(device, caster_layout, (camera_buffer, objects));
+    }
+
+    fn create_camera_bind_group(
+        device: &wgpu::Device,
+        caster_layout: &wgpu::BindGroupLayout,
+        (camera_buffer, objects): (&wgpu::Buffer, &SceneObjects),
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("overdraw_camera_bind_group"),
+            layout: caster_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                objects.entry(),
+            ],
+        })
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) -> wgpu::Texture {
+        let (counts, counts_view) = create_counts(device, width, height);
+        self.counts_view = counts_view;
+        self.heatmap_bind_group = Self::create_heatmap_bind_group(device, &self.heatmap_layout, &self.counts_view);
+        std::mem::replace(&mut self.counts, counts)
+    }
+
+    /// Counts the fragments of whatever `draw_meshes` draws, given the back-face culled
+    /// and the double-sided counting pipelines, then writes the heatmap to `target`.
+    pub fn record(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        draw_meshes: impl FnOnce(&mut wgpu::RenderPass<'_>, &[wgpu::RenderPipeline; 2]),
+    ) {
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Overdraw Count Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.counts_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            draw_meshes(&mut pass, &self.count_pipelines);
+        }
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Overdraw Heatmap Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.heatmap_pipeline);
+        pass.set_bind_group(0, &self.heatmap_bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
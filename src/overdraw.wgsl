@@ -0,0 +1,68 @@
+// Overdraw heatmap: every rasterized fragment adds one to a counter target, with no depth
+// test, and a fullscreen pass maps the counts to colors.
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// Must match ObjectUniform in objects.rs.
+struct Object {
+    model: mat4x4<f32>,
+    normal: mat3x3<f32>,
+    material: vec4<u32>,
+};
+
+@group(0) @binding(19)
+var<storage, read> objects: array<Object>;
+
+// Bound on its own by the heatmap pass.
+@group(0) @binding(1)
+var counts: texture_2d<f32>;
+
+@vertex
+fn vs_count(
+    @location(0) position: vec3<f32>,
+    @builtin(instance_index) instance: u32,
+) -> @builtin(position) vec4<f32> {
+    return camera.view_proj * objects[instance].model * vec4<f32>(position, 1.0);
+}
+
+@fragment
+fn fs_count() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 0.0, 0.0, 0.0);
+}
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+    var p = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -3.0),
+        vec2<f32>( 3.0,  1.0),
+        vec2<f32>(-1.0,  1.0),
+    );
+    return vec4<f32>(p[vid], 0.0, 1.0);
+}
+
+// Black for nothing, then blue, cyan, green, yellow and red at 1, 2, 4, 8 and 16 layers,
+// white from 32.
+@fragment
+fn fs_heatmap(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    let count = textureLoad(counts, vec2<i32>(pos.xy), 0).r;
+    if count < 0.5 {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    var ramp = array<vec3<f32>, 7>(
+        vec3<f32>(0.0, 0.0, 1.0),
+        vec3<f32>(0.0, 1.0, 1.0),
+        vec3<f32>(0.0, 1.0, 0.0),
+        vec3<f32>(1.0, 1.0, 0.0),
+        vec3<f32>(1.0, 0.0, 0.0),
+        vec3<f32>(1.0, 1.0, 1.0),
+        vec3<f32>(1.0, 1.0, 1.0),
+    );
+    let t = clamp(log2(count), 0.0, 5.0);
+    let i = u32(t);
+    return vec4<f32>(mix(ramp[i], ramp[i + 1u], fract(t)), 1.0);
+}
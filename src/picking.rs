@@ -0,0 +1,123 @@
+use cgmath::{Point3, Vector3};
+
+use crate::aabb::Aabb;
+use crate::bvh::{Bvh, BvhTriangle};
+use crate::world::Entity;
+
+/// What a click in the viewport hit.
+#[derive(Clone, Debug)]
+pub struct PickHit {
+    /// The entity holding the mesh.
+    pub entity: Entity,
+    /// Scene mesh index.
+    pub mesh: usize,
+    pub mesh_name: String,
+    pub material: usize,
+    /// Distance from the camera along the ray.
+    pub distance: f32,
+    pub position: Point3<f32>,
+}
+
+/// Called with every pick that hit something.
+pub type PickListener = Box<dyn FnMut(&PickHit)>;
+
+enum PickGeometry {
+    /// World-space positions and indices, until the mesh is first tested.
+    Pending(Vec<[f32; 3]>, Vec<u32>),
+    Built(Bvh),
+}
+
+struct PickMesh {
+    mesh: usize,
+    bounds: Aabb,
+    geometry: PickGeometry,
+}
+
+impl PickMesh {
+    fn bvh(&mut self) -> &Bvh {
+        if let PickGeometry::Pending(positions, indices) = &self.geometry {
+            let position = |i: u32| positions.get(i as usize).copied();
+            let triangles = indices
+                .chunks_exact(3)
+                .filter_map(|tri| match [position(tri[0]), position(tri[1]), position(tri[2])] {
+                    [Some(a), Some(b), Some(c)] => Some(BvhTriangle::new(a, b, c, [0.0; 3])),
+                    _ => None,
+                })
+                .collect();
+            self.geometry = PickGeometry::Built(Bvh::build(triangles));
+        }
+        match &self.geometry {
+            PickGeometry::Built(bvh) => bvh,
+            PickGeometry::Pending(..) => unreachable!(),
+        }
+    }
+}
+
+/// Distance along the ray to `bounds`, zero from inside, or None if it is missed.
+fn ray_box(bounds: &Aabb, origin: Point3<f32>, dir: Vector3<f32>) -> Option<f32> {
+    let mut near = 0.0f32;
+    let mut far = f32::INFINITY;
+    for axis in 0..3 {
+        let inv = 1.0 / dir[axis];
+        let t0 = (bounds.min[axis] - origin[axis]) * inv;
+        let t1 = (bounds.max[axis] - origin[axis]) * inv;
+        near = near.max(t0.min(t1));
+        far = far.min(t0.max(t1));
+    }
+    (near <= far).then_some(near)
+}
+
+/// Finds the static mesh under a screen ray on the CPU: rays are tested against mesh
+/// bounds nearest first, and against the triangles of the meshes whose bounds they hit,
+/// through a BVH per mesh built on its first test.
+#[derive(Default)]
+pub struct Picker {
+    meshes: Vec<PickMesh>,
+}
+
+impl Picker {
+    /// Makes scene mesh `mesh` pickable, with its world-space triangles.
+    pub fn add(&mut self, mesh: usize, bounds: Aabb, positions: Vec<[f32; 3]>, indices: Vec<u32>) {
+        if bounds.is_finite() && !bounds.is_empty() {
+            self.meshes.push(PickMesh {
+                mesh,
+                bounds,
+                geometry: PickGeometry::Pending(positions, indices),
+            });
+        }
+    }
+
+    /// Nearest mesh along the ray for which `include` holds, and the distance to it; `dir`
+    /// must be normalized.
+    pub fn pick(
+        &mut self,
+        origin: Point3<f32>,
+        dir: Vector3<f32>,
+        include: impl Fn(usize) -> bool,
+    ) -> Option<(usize, f32)> {
+        let mut candidates: Vec<(f32, usize)> = self
+            .meshes
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| include(m.mesh))
+            .filter_map(|(i, m)| ray_box(&m.bounds, origin, dir).map(|t| (t, i)))
+            .collect();
+        candidates.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+        let origin = Vector3::new(origin.x, origin.y, origin.z);
+        let mut closest: Option<(usize, f32)> = None;
+        for (box_t, i) in candidates {
+            let limit = closest.map_or(f32::INFINITY, |(_, t)| t);
+            // Every later box starts farther away than the hit so far.
+            if box_t > limit {
+                break;
+            }
+            let mesh = &mut self.meshes[i];
+            let index = mesh.mesh;
+            if let Some(hit) = mesh.bvh().intersect(origin, dir, limit) {
+                closest = Some((index, hit.t));
+            }
+        }
+        closest
+    }
+}
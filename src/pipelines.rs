@@ -0,0 +1,555 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::camera::DepthMode;
+use crate::gbuffer::GBUFFER_FORMATS;
+use crate::model::{AlphaMode, Vertex};
+use crate::vertex_packing::{CompactVertex, VertexPacking};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ShaderFeatures(u32);
+
+impl ShaderFeatures {
+    pub const ALPHA_MASK: Self = Self(1 << 0);
+    pub const ALPHA_BLEND: Self = Self(1 << 1);
+    pub const BONE_WEIGHTS: Self = Self(1 << 2);
+    pub const ALPHA_DITHER: Self = Self(1 << 3);
+    pub const NORMAL_MAP: Self = Self(1 << 4);
+    pub const LIGHTMAP: Self = Self(1 << 5);
+    /// Writes the surface to the G-buffer instead of lighting it.
+    pub const GBUFFER: Self = Self(1 << 6);
+
+    const NAMES: [(Self, &'static str); 7] = [
+        (Self::ALPHA_MASK, "ALPHA_MASK"),
+        (Self::ALPHA_BLEND, "ALPHA_BLEND"),
+        (Self::BONE_WEIGHTS, "BONE_WEIGHTS"),
+        (Self::ALPHA_DITHER, "ALPHA_DITHER"),
+        (Self::NORMAL_MAP, "NORMAL_MAP"),
+        (Self::LIGHTMAP, "LIGHTMAP"),
+        (Self::GBUFFER, "GBUFFER"),
+    ];
+
+    pub fn empty() -> Self {
+        Self(0)
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub fn for_alpha_mode(alpha_mode: AlphaMode) -> Self {
+        match alpha_mode {
+            AlphaMode::Opaque => Self::empty(),
+            AlphaMode::Mask => Self::ALPHA_MASK,
+            AlphaMode::Blend => Self::ALPHA_BLEND,
+            AlphaMode::Dither => Self::ALPHA_DITHER,
+        }
+    }
+
+    pub fn defines(self) -> Vec<&'static str> {
+        Self::NAMES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+/// Resolves `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif` lines against `defines`.
+/// Directives may be nested; lines in inactive branches are replaced by blanks so that
+/// naga error locations still match the original file.
+pub fn preprocess(source: &str, defines: &[&str]) -> String {
+    let mut out = String::with_capacity(source.len());
+    // Whether each open branch is taken; a line is kept when all of them are.
+    let mut stack: Vec<bool> = Vec::new();
+
+    for line in source.lines() {
+        let trimmed = line.trim_start();
+        let active = stack.iter().all(|taken| *taken);
+
+        if let Some(name) = trimmed.strip_prefix("#ifdef ") {
+            stack.push(defines.contains(&name.trim()));
+        } else if let Some(name) = trimmed.strip_prefix("#ifndef ") {
+            stack.push(!defines.contains(&name.trim()));
+        } else if trimmed.starts_with("#else") {
+            if let Some(top) = stack.last_mut() {
+                *top = !*top;
+            }
+        } else if trimmed.starts_with("#endif") {
+            stack.pop();
+        } else if active {
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+/// `FAR_DEPTH` for every shader that tests the depth buffer, so only the defines say
+/// which way depth runs.
+const DEPTH_CONSTANTS: &str = include_str!("depth.wgsl");
+
+/// `source` preprocessed for depth `mode`, with the shared depth constants after it so
+/// error locations still match the file.
+pub fn depth_shader(source: &str, mode: DepthMode) -> String {
+    let mut code = preprocess(source, mode.defines());
+    code.push_str(&preprocess(DEPTH_CONSTANTS, mode.defines()));
+    code
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineKey {
+    pub features: ShaderFeatures,
+    pub double_sided: bool,
+    pub surface_hook: Option<usize>,
+    /// Tests depth for equality without writing it, for surfaces already in the depth
+    /// prepass.
+    pub depth_equal: bool,
+}
+
+impl PipelineKey {
+    pub fn new(alpha_mode: AlphaMode, double_sided: bool) -> Self {
+        Self {
+            features: ShaderFeatures::for_alpha_mode(alpha_mode),
+            double_sided,
+            surface_hook: None,
+            depth_equal: false,
+        }
+    }
+
+    pub fn with_features(features: ShaderFeatures, double_sided: bool) -> Self {
+        Self {
+            features,
+            double_sided,
+            surface_hook: None,
+            depth_equal: false,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct ModuleKey {
+    features: ShaderFeatures,
+    surface_hook: Option<usize>,
+}
+
+pub fn vertex_buffer_layout(packing: VertexPacking) -> wgpu::VertexBufferLayout<'static> {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] = [
+        wgpu::VertexAttribute {
+            offset: 0,
+            shader_location: 0,
+            format: wgpu::VertexFormat::Float32x3,
+        },
+        wgpu::VertexAttribute {
+            offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+            shader_location: 1,
+            format: wgpu::VertexFormat::Float32x3,
+        },
+        wgpu::VertexAttribute {
+            offset: std::mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+            shader_location: 2,
+            format: wgpu::VertexFormat::Float32x2,
+        },
+    ];
+    const COMPACT_ATTRIBUTES: [wgpu::VertexAttribute; 3] = [
+        wgpu::VertexAttribute {
+            offset: 0,
+            shader_location: 0,
+            format: wgpu::VertexFormat::Float32x3,
+        },
+        wgpu::VertexAttribute {
+            offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+            shader_location: 1,
+            format: wgpu::VertexFormat::Snorm16x2,
+        },
+        wgpu::VertexAttribute {
+            offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+            shader_location: 2,
+            format: wgpu::VertexFormat::Float16x2,
+        },
+    ];
+    match packing {
+        VertexPacking::Full => wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        },
+        VertexPacking::Compact => wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<CompactVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &COMPACT_ATTRIBUTES,
+        },
+    }
+}
+
+pub fn tangent_buffer_layout() -> wgpu::VertexBufferLayout<'static> {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 1] = [wgpu::VertexAttribute {
+        offset: 0,
+        shader_location: 5,
+        format: wgpu::VertexFormat::Float32x4,
+    }];
+    wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &ATTRIBUTES,
+    }
+}
+
+pub fn lightmap_uv_buffer_layout() -> wgpu::VertexBufferLayout<'static> {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 1] = [wgpu::VertexAttribute {
+        offset: 0,
+        shader_location: 6,
+        format: wgpu::VertexFormat::Float32x2,
+    }];
+    wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &ATTRIBUTES,
+    }
+}
+
+pub fn bone_weight_buffer_layout() -> wgpu::VertexBufferLayout<'static> {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 1] = [wgpu::VertexAttribute {
+        offset: 0,
+        shader_location: 3,
+        format: wgpu::VertexFormat::Float32,
+    }];
+    wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<f32>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &ATTRIBUTES,
+    }
+}
+
+pub struct PipelineCache {
+    source: String,
+    layout: wgpu::PipelineLayout,
+    color_format: wgpu::TextureFormat,
+    vertex_packing: VertexPacking,
+    depth_mode: DepthMode,
+    /// Defined in every variant.
+    defines: Vec<&'static str>,
+    surface_hooks: Vec<String>,
+    /// Hooks that failed to build; their pipelines use the built-in surface.
+    broken_hooks: HashSet<usize>,
+    modules: HashMap<ModuleKey, wgpu::ShaderModule>,
+    pipelines: HashMap<PipelineKey, wgpu::RenderPipeline>,
+}
+
+impl PipelineCache {
+    pub fn new(source: &str, layout: wgpu::PipelineLayout, color_format: wgpu::TextureFormat) -> Self {
+        Self {
+            source: source.to_string(),
+            layout,
+            color_format,
+            vertex_packing: VertexPacking::Full,
+            depth_mode: DepthMode::Standard,
+            defines: Vec::new(),
+            surface_hooks: Vec::new(),
+            broken_hooks: HashSet::new(),
+            modules: HashMap::new(),
+            pipelines: HashMap::new(),
+        }
+    }
+
+    /// Compiles every variant with `name` defined; call before any module is built.
+    pub fn define(&mut self, name: &'static str) {
+        debug_assert!(self.modules.is_empty());
+        self.defines.push(name);
+    }
+
+    /// Reads vertices packed as `packing`; call before any module is built.
+    pub fn set_vertex_packing(&mut self, packing: VertexPacking) {
+        if packing == VertexPacking::Compact {
+            self.define("COMPACT_VERTICES");
+        }
+        self.vertex_packing = packing;
+    }
+
+    /// Renders with `mode`'s depth layout; call before any module is built.
+    pub fn set_depth_mode(&mut self, mode: DepthMode) {
+        for define in mode.defines() {
+            self.define(define);
+        }
+        self.depth_mode = mode;
+    }
+
+    /// Registers a WGSL snippet defining `fn surface(in: SurfaceInput) -> Surface`, which
+    /// replaces the built-in material evaluation for pipelines keyed with the returned id.
+    pub fn register_surface_hook(&mut self, source: &str) -> usize {
+        if let Some(id) = self.surface_hooks.iter().position(|s| s == source) {
+            return id;
+        }
+        self.surface_hooks.push(source.to_string());
+        self.surface_hooks.len() - 1
+    }
+
+    pub fn module(&mut self, device: &wgpu::Device, features: ShaderFeatures) -> &wgpu::ShaderModule {
+        self.module_for(device, ModuleKey {
+            features,
+            surface_hook: None,
+        })
+    }
+
+    fn module_for(&mut self, device: &wgpu::Device, key: ModuleKey) -> &wgpu::ShaderModule {
+        let source = &self.source;
+        let hooks = &self.surface_hooks;
+        let global = &self.defines;
+        self.modules.entry(key).or_insert_with(|| {
+            let mut defines = key.features.defines();
+            defines.extend(global);
+            let hook = key.surface_hook.and_then(|id| hooks.get(id));
+            if hook.is_some() {
+                defines.push("CUSTOM_SURFACE");
+            }
+            log::debug!("Compiling shader variant {:?}", defines);
+            let mut code = preprocess(source, &defines);
+            code.push_str(&preprocess(DEPTH_CONSTANTS, &defines));
+            if let Some(hook) = hook {
+                code.push_str(&preprocess(hook, &defines));
+            }
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(&format!("Shader {:?}", defines)),
+                source: wgpu::ShaderSource::Wgsl(code.into()),
+            })
+        })
+    }
+
+    /// Builds the pipeline for `key` unless it exists. A surface hook that fails to
+    /// compile is logged and left out, so its materials shade with the built-in surface.
+    pub fn prepare(&mut self, device: &wgpu::Device, key: PipelineKey) {
+        if self.pipelines.contains_key(&key) {
+            return;
+        }
+        let mut module_key = ModuleKey {
+            features: key.features,
+            surface_hook: key.surface_hook.filter(|id| !self.broken_hooks.contains(id)),
+        };
+        if let Some(id) = module_key.surface_hook {
+            device.push_error_scope(wgpu::ErrorFilter::Validation);
+            let pipeline = self.create_pipeline(device, key, module_key);
+            let Some(error) = pollster::block_on(device.pop_error_scope()) else {
+                self.pipelines.insert(key, pipeline);
+                return;
+            };
+            log::warn!("Material surface shader {} failed to build; using the built-in surface: {}", id, error);
+            self.broken_hooks.insert(id);
+            self.modules.remove(&module_key);
+            module_key.surface_hook = None;
+        }
+        let pipeline = self.create_pipeline(device, key, module_key);
+        self.pipelines.insert(key, pipeline);
+    }
+
+    fn create_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        key: PipelineKey,
+        module_key: ModuleKey,
+    ) -> wgpu::RenderPipeline {
+        self.module_for(device, module_key);
+        let module = &self.modules[&module_key];
+
+        let blending = key.features.contains(ShaderFeatures::ALPHA_BLEND);
+        let (blend, depth_write, depth_compare) = if blending {
+            (wgpu::BlendState::ALPHA_BLENDING, false, wgpu::CompareFunction::LessEqual)
+        } else if key.depth_equal {
+            (wgpu::BlendState::REPLACE, false, wgpu::CompareFunction::Equal)
+        } else {
+            (wgpu::BlendState::REPLACE, true, wgpu::CompareFunction::LessEqual)
+        };
+        let depth_compare = self.depth_mode.compare(depth_compare);
+        let cull = if key.double_sided { None } else { Some(wgpu::Face::Back) };
+        let mut buffers = vec![vertex_buffer_layout(self.vertex_packing)];
+        if key.features.contains(ShaderFeatures::NORMAL_MAP) {
+            buffers.push(tangent_buffer_layout());
+        }
+        if key.features.contains(ShaderFeatures::LIGHTMAP) {
+            buffers.push(lightmap_uv_buffer_layout());
+        }
+        if key.features.contains(ShaderFeatures::BONE_WEIGHTS) {
+            buffers.push(bone_weight_buffer_layout());
+        }
+        let (entry_point, targets) = if key.features.contains(ShaderFeatures::GBUFFER) {
+            ("fs_gbuffer", GBUFFER_FORMATS.map(|format| Some(format.into())).to_vec())
+        } else {
+            (
+                "fs_main",
+                vec![Some(wgpu::ColorTargetState {
+                    format: self.color_format,
+                    blend: Some(blend),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            )
+        };
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&format!("Render Pipeline {:?}", key)),
+            layout: Some(&self.layout),
+            cache: None,
+            vertex: wgpu::VertexState {
+                module,
+                entry_point: "vs_main",
+                buffers: &buffers,
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module,
+                entry_point,
+                targets: &targets,
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: cull,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: depth_write,
+                depth_compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+    }
+
+    /// Prepares the equal-depth variant of every fully opaque pipeline prepared so far,
+    /// for frames with a depth prepass.
+    pub fn prepare_depth_equal_variants(&mut self, device: &wgpu::Device) {
+        let keys: Vec<PipelineKey> = self
+            .pipelines
+            .keys()
+            .filter(|key| {
+                // Only fully opaque surfaces are in the prepass.
+                let excluded = [
+                    ShaderFeatures::ALPHA_MASK,
+                    ShaderFeatures::ALPHA_BLEND,
+                    ShaderFeatures::ALPHA_DITHER,
+                    ShaderFeatures::BONE_WEIGHTS,
+                ];
+                !key.depth_equal && !excluded.iter().any(|&flag| key.features.contains(flag))
+            })
+            .copied()
+            .collect();
+        for mut key in keys {
+            key.depth_equal = true;
+            self.prepare(device, key);
+        }
+    }
+
+    /// Prepares the G-buffer variant of every opaque pipeline prepared so far, for the
+    /// deferred path.
+    pub fn prepare_gbuffer_variants(&mut self, device: &wgpu::Device) {
+        let keys: Vec<PipelineKey> = self
+            .pipelines
+            .keys()
+            .filter(|key| {
+                !key.features.contains(ShaderFeatures::ALPHA_BLEND)
+                    && !key.features.contains(ShaderFeatures::BONE_WEIGHTS)
+                    && !key.features.contains(ShaderFeatures::GBUFFER)
+            })
+            .copied()
+            .collect();
+        for mut key in keys {
+            key.features = key.features.union(ShaderFeatures::GBUFFER);
+            self.prepare(device, key);
+        }
+    }
+
+    /// Recompiles every prepared pipeline from new shader `source`. If any variant fails
+    /// to compile, the previous source is rebuilt and the error returned.
+    pub fn set_source(&mut self, device: &wgpu::Device, source: &str) -> Result<(), wgpu::Error> {
+        let previous = std::mem::replace(&mut self.source, source.to_string());
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        self.rebuild(device);
+        match pollster::block_on(device.pop_error_scope()) {
+            Some(error) => {
+                self.source = previous;
+                self.rebuild(device);
+                Err(error)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn rebuild(&mut self, device: &wgpu::Device) {
+        let keys: Vec<PipelineKey> = self.pipelines.keys().copied().collect();
+        self.modules.clear();
+        self.pipelines.clear();
+        // A hook that broke under the old source may build under the new one.
+        self.broken_hooks.clear();
+        for key in keys {
+            self.prepare(device, key);
+        }
+    }
+
+    pub fn get(&self, key: &PipelineKey) -> Option<&wgpu::RenderPipeline> {
+        self.pipelines.get(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pipelines.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::preprocess;
+
+    /// The lines `preprocess` keeps, without the blanks left for the rest.
+    fn kept(source: &str, defines: &[&str]) -> Vec<String> {
+        preprocess(source, defines).lines().filter(|l| !l.is_empty()).map(str::to_string).collect()
+    }
+
+    #[test]
+    fn keeps_line_numbers() {
+        let source = "a\n#ifdef X\nb\n#endif\nc";
+        assert_eq!(preprocess(source, &[]), "a\n\n\n\nc\n");
+        assert_eq!(preprocess(source, &["X"]), "a\n\nb\n\nc\n");
+    }
+
+    #[test]
+    fn ifndef_and_else() {
+        let source = "#ifndef X\nwithout\n#else\nwith\n#endif";
+        assert_eq!(kept(source, &[]), ["without"]);
+        assert_eq!(kept(source, &["X"]), ["with"]);
+    }
+
+    #[test]
+    fn nested_branches() {
+        let source = "#ifdef A\na\n  #ifdef B\n  ab\n  #endif\na2\n#endif\nend";
+        assert_eq!(kept(source, &[]), ["end"]);
+        assert_eq!(kept(source, &["A"]), ["a", "a2", "end"]);
+        assert_eq!(kept(source, &["A", "B"]), ["a", "  ab", "a2", "end"]);
+        assert_eq!(kept(source, &["B"]), ["end"]);
+    }
+
+    #[test]
+    fn else_inside_a_false_branch_stays_inactive() {
+        let source = "#ifdef A\n#ifdef B\nb\n#else\nnot_b\n#endif\n#endif\nend";
+        assert_eq!(kept(source, &[]), ["end"]);
+        assert_eq!(kept(source, &["A"]), ["not_b", "end"]);
+    }
+
+    #[test]
+    fn unbalanced_endif_is_ignored() {
+        let source = "a\n#endif\nb\n#ifdef X\nx\n#endif\n#endif\nc";
+        assert_eq!(kept(source, &[]), ["a", "b", "c"]);
+        assert_eq!(kept(source, &["X"]), ["a", "b", "x", "c"]);
+    }
+}
@@ -0,0 +1,36 @@
+use winit::event::WindowEvent;
+
+use crate::renderer::Renderer;
+
+/// What a plugin gets to draw with: the frame's encoder and the window's surface view,
+/// after the scene, post effects and overlays and before the statistics panel.
+pub struct RenderContext<'a> {
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    pub encoder: &'a mut wgpu::CommandEncoder,
+    pub view: &'a wgpu::TextureView,
+    pub format: wgpu::TextureFormat,
+    /// Width and height of `view` in pixels.
+    pub size: (u32, u32),
+}
+
+/// Engine code that runs alongside the renderer, registered with
+/// [`Engine::with_plugin`](crate::Engine::with_plugin). Every hook does nothing by
+/// default; plugins are called in the order they were registered.
+pub trait Plugin {
+    /// Called on registration, with the scene loaded; create GPU resources here. Called
+    /// again with the rebuilt renderer when the engine recovers from a lost device.
+    fn setup(&mut self, _renderer: &mut Renderer) {}
+
+    /// Called every frame after the renderer's update, `dt` seconds after the last.
+    fn on_update(&mut self, _renderer: &mut Renderer, _dt: f32) {}
+
+    /// Records passes into the frame; see [`RenderContext`].
+    fn on_render_pass(&mut self, _ctx: &mut RenderContext<'_>) {}
+
+    /// Sees window events before the renderer; returning true keeps them from it and
+    /// from the plugins after this one.
+    fn on_event(&mut self, _renderer: &mut Renderer, _event: &WindowEvent) -> bool {
+        false
+    }
+}
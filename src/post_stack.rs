@@ -0,0 +1,310 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+/// Loaded from the working directory when no `--post=` file is given.
+pub const DEFAULT_POST_CONFIG_PATH: &str = "dusk_post.json";
+
+/// One entry of the post-effect stack, run on the tonemapped image.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PostEffect {
+    /// Darkens towards the corners; `smoothness` is how far in from them the falloff starts.
+    Vignette { intensity: f32, smoothness: f32 },
+    /// Animated film grain, strongest in the mid-tones.
+    Grain { intensity: f32, size: f32 },
+    /// Red and blue fringes growing towards the edges, `strength` pixels at the corners.
+    ChromaticAberration { strength: f32 },
+    /// Unsharp mask over the four direct neighbours.
+    Sharpen { amount: f32 },
+}
+
+impl PostEffect {
+    fn entry_point(&self) -> &'static str {
+        match self {
+            Self::Vignette { .. } => "fs_vignette",
+            Self::Grain { .. } => "fs_grain",
+            Self::ChromaticAberration { .. } => "fs_chromatic_aberration",
+            Self::Sharpen { .. } => "fs_sharpen",
+        }
+    }
+
+    fn params(&self, time: f32) -> [f32; 4] {
+        match *self {
+            Self::Vignette { intensity, smoothness } => [intensity, smoothness, 0.0, 0.0],
+            Self::Grain { intensity, size } => [intensity, size, time, 0.0],
+            Self::ChromaticAberration { strength } => [strength, 0.0, 0.0, 0.0],
+            Self::Sharpen { amount } => [amount, 0.0, 0.0, 0.0],
+        }
+    }
+
+    fn from_json(value: &serde_json::Value) -> Result<Self> {
+        let number = |key: &str, default: f32| value.get(key).and_then(|v| v.as_f64()).map_or(default, |v| v as f32);
+        match value.get("effect").and_then(|v| v.as_str()) {
+            Some("vignette") => Ok(Self::Vignette {
+                intensity: number("intensity", 0.3),
+                smoothness: number("smoothness", 0.6).clamp(0.0, 1.0),
+            }),
+            Some("grain") => Ok(Self::Grain {
+                intensity: number("intensity", 0.05),
+                size: number("size", 1.0).max(1.0),
+            }),
+            Some("chromatic_aberration") => Ok(Self::ChromaticAberration {
+                strength: number("strength", 2.0),
+            }),
+            Some("sharpen") => Ok(Self::Sharpen {
+                amount: number("amount", 0.5),
+            }),
+            Some(other) => anyhow::bail!(
+                "unknown post effect '{}' (vignette, grain, chromatic_aberration, sharpen)",
+                other
+            ),
+            None => anyhow::bail!("post effect without an \"effect\" name"),
+        }
+    }
+}
+
+/// Reads the post-effect stack from a JSON file:
+///
+/// ```json
+/// { "post": [{ "effect": "sharpen", "amount": 0.4 },
+///            { "effect": "vignette", "intensity": 0.3, "smoothness": 0.6 }] }
+/// ```
+///
+/// Effects run in the listed order; unknown ones are skipped with a warning.
+pub fn load_post_config(path: &Path) -> Result<Vec<PostEffect>> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("reading post config {}", path.display()))?;
+    let value: serde_json::Value =
+        serde_json::from_str(&text).with_context(|| format!("parsing post config {}", path.display()))?;
+    let Some(list) = value.get("post").and_then(|v| v.as_array()) else {
+        anyhow::bail!("Post config {} has no \"post\" list", path.display());
+    };
+    let mut effects = Vec::new();
+    for (i, entry) in list.iter().enumerate() {
+        match PostEffect::from_json(entry) {
+            Ok(effect) => effects.push(effect),
+            Err(e) => log::warn!("Post config {}: effect {}: {:#}", path.display(), i, e),
+        }
+    }
+    Ok(effects)
+}
+
+struct StackPass {
+    effect: PostEffect,
+    pipeline: wgpu::RenderPipeline,
+    params_buffer: wgpu::Buffer,
+}
+
+struct PingPong {
+    textures: [wgpu::Texture; 2],
+    views: [wgpu::TextureView; 2],
+    /// Bind groups per pass: pass `i` reads texture `i % 2` and writes the other one, or
+    /// the final target for the last pass.
+    bind_groups: Vec<wgpu::BindGroup>,
+}
+
+/// Ordered post effects applied between the tonemapper (or FXAA) and the surface. Empty
+/// stacks cost nothing: the previous pass writes straight to the surface.
+pub struct PostStack {
+    format: wgpu::TextureFormat,
+    passes: Vec<StackPass>,
+    sampler: wgpu::Sampler,
+    layout: wgpu::BindGroupLayout,
+    /// None while the stack is empty.
+    targets: Option<PingPong>,
+}
+
+impl PostStack {
+    pub fn new(
+        device: &wgpu::Device,
+        output_format: wgpu::TextureFormat,
+        effects: &[PostEffect],
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post Stack Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("post_stack_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Post Stack Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("post_stack.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post Stack Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let passes = effects
+            .iter()
+            .map(|&effect| StackPass {
+                effect,
+                pipeline: device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(effect.entry_point()),
+                    layout: Some(&pipeline_layout),
+                    cache: None,
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: "vs_fullscreen",
+                        buffers: &[],
+                        compilation_options: Default::default(),
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: effect.entry_point(),
+                        targets: &[Some(output_format.into())],
+                        compilation_options: Default::default(),
+                    }),
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                }),
+                params_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Post Effect Params Buffer"),
+                    size: std::mem::size_of::<[f32; 4]>() as u64,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+            })
+            .collect();
+        let mut stack = Self {
+            format: output_format,
+            passes,
+            sampler,
+            layout,
+            targets: None,
+        };
+        stack.targets = stack.create_targets(device, width, height);
+        stack
+    }
+
+    fn create_targets(&self, device: &wgpu::Device, width: u32, height: u32) -> Option<PingPong> {
+        if self.passes.is_empty() {
+            return None;
+        }
+        let texture = || {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Post Stack Target"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: self.format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+        };
+        let textures = [texture(), texture()];
+        let views = textures.each_ref().map(|t| t.create_view(&wgpu::TextureViewDescriptor::default()));
+        let bind_groups = self
+            .passes
+            .iter()
+            .enumerate()
+            .map(|(i, pass)| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("post_stack_bind_group"),
+                    layout: &self.layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&views[i % 2]),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&self.sampler),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: pass.params_buffer.as_entire_binding(),
+                        },
+                    ],
+                })
+            })
+            .collect();
+        Some(PingPong {
+            textures,
+            views,
+            bind_groups,
+        })
+    }
+
+    /// Where the previous pass should render instead of the surface; None when the stack
+    /// is empty.
+    pub fn input_view(&self) -> Option<&wgpu::TextureView> {
+        self.targets.as_ref().map(|targets| &targets.views[0])
+    }
+
+    /// Reallocates the ping-pong targets for a new size, returning the old textures so they
+    /// can be destroyed once the GPU is done with them.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) -> Vec<wgpu::Texture> {
+        let targets = self.create_targets(device, width, height);
+        std::mem::replace(&mut self.targets, targets).map_or_else(Vec::new, |old| old.textures.into())
+    }
+
+    /// Runs the effects in order, the last one writing to `surface`.
+    pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, surface: &wgpu::TextureView, time: f32) {
+        let Some(targets) = &self.targets else {
+            return;
+        };
+        for (i, (pass, bind_group)) in self.passes.iter().zip(&targets.bind_groups).enumerate() {
+            queue.write_buffer(&pass.params_buffer, 0, bytemuck::cast_slice(&pass.effect.params(time)));
+            let target = if i + 1 == self.passes.len() { surface } else { &targets.views[(i + 1) % 2] };
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Post Effect"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(&pass.pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+// Post-effect stack: one fragment entry point per effect, each reading the previous
+// effect's output. All run on the tonemapped image.
+
+struct EffectParams {
+    // Meaning depends on the effect; see each entry point.
+    values: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var input_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var linear_sampler: sampler;
+
+@group(0) @binding(2)
+var<uniform> params: EffectParams;
+
+struct FullscreenOut {
+    @builtin(position) pos: vec4<f32>,
+};
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> FullscreenOut {
+    var p = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -3.0),
+        vec2<f32>( 3.0,  1.0),
+        vec2<f32>(-1.0,  1.0),
+    );
+    var o: FullscreenOut;
+    o.pos = vec4<f32>(p[vid], 0.0, 1.0);
+    return o;
+}
+
+fn screen_uv(pos: vec4<f32>) -> vec2<f32> {
+    return pos.xy / vec2<f32>(textureDimensions(input_texture));
+}
+
+// x: darkening at the corners, y: how far in from the corners the falloff starts (0-1).
+@fragment
+fn fs_vignette(in: FullscreenOut) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(input_texture));
+    let color = textureLoad(input_texture, vec2<i32>(in.pos.xy), 0);
+    // Distance from the centre with the aspect ratio kept, 1 at the corners.
+    let d = (in.pos.xy / size - 0.5) * vec2<f32>(size.x / size.y, 1.0);
+    let r = length(d) / length(vec2<f32>(0.5 * size.x / size.y, 0.5));
+    let falloff = smoothstep(1.0 - params.values.y, 1.0, r);
+    return vec4<f32>(color.rgb * (1.0 - params.values.x * falloff), color.a);
+}
+
+fn hash(p: vec3<f32>) -> f32 {
+    var q = fract(p * 0.1031);
+    q += dot(q, q.zyx + 31.32);
+    return fract((q.x + q.y) * q.z);
+}
+
+// x: grain strength, y: grain size in pixels, z: time, so the grain changes every frame.
+@fragment
+fn fs_grain(in: FullscreenOut) -> @location(0) vec4<f32> {
+    let color = textureLoad(input_texture, vec2<i32>(in.pos.xy), 0);
+    let cell = floor(in.pos.xy / max(params.values.y, 1.0));
+    let noise = hash(vec3<f32>(cell, floor(params.values.z * 60.0))) - 0.5;
+    // Film grain shows most in the mid-tones.
+    let luma = dot(color.rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+    let weight = 1.0 - abs(luma * 2.0 - 1.0);
+    return vec4<f32>(max(color.rgb + noise * params.values.x * (0.25 + 0.75 * weight), vec3<f32>(0.0)), color.a);
+}
+
+// x: red and blue fringe offset at the screen corners, in pixels.
+@fragment
+fn fs_chromatic_aberration(in: FullscreenOut) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(input_texture));
+    let uv = screen_uv(in.pos);
+    // Grows towards the edges like lateral aberration in a lens.
+    let offset = (uv - 0.5) * 2.0 * params.values.x / size;
+    let r = textureSampleLevel(input_texture, linear_sampler, uv + offset, 0.0).r;
+    let center = textureSampleLevel(input_texture, linear_sampler, uv, 0.0);
+    let b = textureSampleLevel(input_texture, linear_sampler, uv - offset, 0.0).b;
+    return vec4<f32>(r, center.g, b, center.a);
+}
+
+// x: sharpening amount; an unsharp mask over the four direct neighbours.
+@fragment
+fn fs_sharpen(in: FullscreenOut) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(input_texture));
+    let p = vec2<i32>(in.pos.xy);
+    let color = textureLoad(input_texture, p, 0);
+    let n = textureLoad(input_texture, clamp(p + vec2<i32>(0, -1), vec2<i32>(0), size - 1), 0).rgb;
+    let s = textureLoad(input_texture, clamp(p + vec2<i32>(0, 1), vec2<i32>(0), size - 1), 0).rgb;
+    let e = textureLoad(input_texture, clamp(p + vec2<i32>(1, 0), vec2<i32>(0), size - 1), 0).rgb;
+    let w = textureLoad(input_texture, clamp(p + vec2<i32>(-1, 0), vec2<i32>(0), size - 1), 0).rgb;
+    let blurred = (n + s + e + w) * 0.25;
+    let sharpened = color.rgb + (color.rgb - blurred) * params.values.x;
+    return vec4<f32>(clamp(sharpened, vec3<f32>(0.0), vec3<f32>(1.0)), color.a);
+}
@@ -0,0 +1,364 @@
+use cgmath::{Matrix4, Point3, SquareMatrix, Vector3};
+
+use crate::aabb::Aabb;
+use crate::camera::{CameraUniform, DepthMode};
+use crate::post::HDR_FORMAT;
+
+/// Edge of each cube face rendered around a probe.
+const FACE_SIZE: u32 = 32;
+const SH_COEFFICIENTS: usize = 9;
+pub const MAX_PROBES_PER_AXIS: u32 = 16;
+const MAX_PROBES: u32 = MAX_PROBES_PER_AXIS * MAX_PROBES_PER_AXIS * MAX_PROBES_PER_AXIS;
+pub const DEFAULT_PROBE_COUNTS: [u32; 3] = [8, 4, 8];
+
+/// Forward and up vectors of the capture faces; `face_dir` in probes.wgsl and
+/// `capture_uv` in reflections.wgsl invert these.
+const FACES: [([f32; 3], [f32; 3]); 6] = [
+    ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ([-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ([0.0, 1.0, 0.0], [0.0, 0.0, -1.0]),
+    ([0.0, -1.0, 0.0], [0.0, 0.0, 1.0]),
+    ([0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+    ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+];
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ProbeHeader {
+    /// xyz: first probe, w: 1 when the probes are baked and enabled, or dynamic.
+    origin: [f32; 4],
+    /// w: 1 when the DDGI atlases replace the SH coefficients.
+    spacing: [f32; 4],
+    counts: [u32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ProjectParams {
+    /// x: probe index, y: face size.
+    probe: [u32; 4],
+}
+
+/// Six square views of the scene around a point, rendered with the material pipelines
+/// through camera bind groups built from `face_buffers`.
+pub struct CubeCapture {
+    pub face_buffers: [wgpu::Buffer; 6],
+    pub face_views: [wgpu::TextureView; 6],
+    /// All faces as a 2D array, for passes that read the capture.
+    pub faces_view: wgpu::TextureView,
+    pub depth_view: wgpu::TextureView,
+    /// Must match the depth compares of the pipelines the faces are drawn with.
+    pub depth_mode: DepthMode,
+}
+
+impl CubeCapture {
+    pub fn new(device: &wgpu::Device, size: u32, label: &str) -> Self {
+        let face_buffers = std::array::from_fn(|i| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(&format!("{} Face Camera Buffer {}", label, i)),
+                size: std::mem::size_of::<CameraUniform>() as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        });
+        let faces = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&format!("{} Capture Faces", label)),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let face_views = std::array::from_fn(|i| {
+            faces.create_view(&wgpu::TextureViewDescriptor {
+                label: Some(&format!("{} Capture Face {}", label, i)),
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_array_layer: i as u32,
+                array_layer_count: Some(1),
+                ..Default::default()
+            })
+        });
+        let faces_view = faces.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let depth = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&format!("{} Capture Depth", label)),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let depth_view = depth.create_view(&wgpu::TextureViewDescriptor::default());
+        Self {
+            face_buffers,
+            face_views,
+            faces_view,
+            depth_view,
+            depth_mode: DepthMode::Standard,
+        }
+    }
+
+    /// Writes the face cameras at `position`. They share everything else with `base`,
+    /// and always use the widest shadow cascade since the cascades follow the main camera.
+    pub fn write_cameras(
+        &self,
+        queue: &wgpu::Queue,
+        base: &CameraUniform,
+        position: Point3<f32>,
+        znear: f32,
+        zfar: f32,
+    ) {
+        let proj = self.depth_mode.perspective(90.0, 1.0, znear, zfar);
+        for ((forward, up), buffer) in FACES.iter().zip(&self.face_buffers) {
+            let view = Matrix4::look_to_rh(position, Vector3::from(*forward), Vector3::from(*up));
+            let mut uniform = *base;
+            uniform.view_proj = (proj * view).into();
+            uniform.view_inv = view.invert().unwrap().into();
+            uniform.proj_inv = proj.invert().unwrap().into();
+            uniform.position = [position.x, position.y, position.z, 1.0];
+            uniform.cascade_splits = [-1.0; 4];
+            uniform.screen_space = [0.0; 4];
+            uniform.fog = [0.0; 4];
+            uniform.debug = [0.0; 4];
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&[uniform]));
+        }
+    }
+}
+
+/// Regular grid of probes at the centres of equal cells spanning the scene bounds, so
+/// none sits on the outer walls or floor.
+#[derive(Copy, Clone, Debug)]
+pub struct ProbeGrid {
+    pub origin: Point3<f32>,
+    pub spacing: Vector3<f32>,
+    pub counts: [u32; 3],
+}
+
+impl ProbeGrid {
+    pub fn fit(bounds: &Aabb, counts: [u32; 3]) -> Self {
+        let counts = counts.map(|n| n.clamp(2, MAX_PROBES_PER_AXIS));
+        let extent = bounds.max - bounds.min;
+        let spacing = Vector3::new(
+            (extent.x / counts[0] as f32).max(1e-3),
+            (extent.y / counts[1] as f32).max(1e-3),
+            (extent.z / counts[2] as f32).max(1e-3),
+        );
+        Self {
+            origin: bounds.min + spacing * 0.5,
+            spacing,
+            counts,
+        }
+    }
+
+    pub fn probe_count(&self) -> u32 {
+        self.counts.iter().product()
+    }
+
+    /// Probes are stored x-fastest, then y, then z.
+    pub fn position(&self, index: u32) -> Point3<f32> {
+        let [nx, ny, _] = self.counts;
+        let (x, y, z) = (index % nx, index / nx % ny, index / (nx * ny));
+        self.origin + Vector3::new(
+            x as f32 * self.spacing.x,
+            y as f32 * self.spacing.y,
+            z as f32 * self.spacing.z,
+        )
+    }
+}
+
+/// Parses `8x4x8` style probe counts.
+pub fn parse_counts(s: &str) -> Option<[u32; 3]> {
+    let mut parts = s.split('x').map(|n| n.trim().parse::<u32>().ok());
+    let counts = [parts.next()??, parts.next()??, parts.next()??];
+    (parts.next().is_none() && counts.iter().all(|n| (2..=MAX_PROBES_PER_AXIS).contains(n))).then_some(counts)
+}
+
+/// Irradiance probe volume: each probe renders a small cube map of the lit scene, which
+/// a compute pass projects onto L2 spherical harmonics. The shader interpolates the
+/// eight probes around a fragment for its diffuse ambient light.
+pub struct ProbeVolume {
+    pub grid: ProbeGrid,
+    /// Probe header and SH coefficients, bound at binding 7 of the camera group.
+    pub buffer: wgpu::Buffer,
+    pub capture: CubeCapture,
+    params_buffer: wgpu::Buffer,
+    project_pipeline: wgpu::ComputePipeline,
+    project_bind_group: wgpu::BindGroup,
+    baked: bool,
+    enabled: bool,
+    dynamic: bool,
+}
+
+impl ProbeVolume {
+    pub fn new(device: &wgpu::Device, grid: ProbeGrid) -> Self {
+        let header_size = std::mem::size_of::<ProbeHeader>() as u64;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Probe Volume Buffer"),
+            size: header_size + MAX_PROBES as u64 * SH_COEFFICIENTS as u64 * 16,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let capture = CubeCapture::new(device, FACE_SIZE, "Probe");
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Probe Project Params Buffer"),
+            size: std::mem::size_of::<ProjectParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("probe_project_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let project_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("probe_project_bind_group"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&capture.faces_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Probe Projection Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("probes.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Probe Projection Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let project_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Probe Projection Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "cs_project",
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Self {
+            grid,
+            buffer,
+            capture,
+            params_buffer,
+            project_pipeline,
+            project_bind_group,
+            baked: false,
+            enabled: true,
+            dynamic: false,
+        }
+    }
+
+    pub fn is_baked(&self) -> bool {
+        self.baked
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn upload_header(&self, queue: &wgpu::Queue) {
+        let g = &self.grid;
+        let active = (self.baked && self.enabled) || self.dynamic;
+        let header = ProbeHeader {
+            origin: [g.origin.x, g.origin.y, g.origin.z, if active { 1.0 } else { 0.0 }],
+            spacing: [g.spacing.x, g.spacing.y, g.spacing.z, if self.dynamic { 1.0 } else { 0.0 }],
+            counts: [g.counts[0], g.counts[1], g.counts[2], 0],
+        };
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[header]));
+    }
+
+    pub fn set_enabled(&mut self, queue: &wgpu::Queue, enabled: bool) {
+        self.enabled = enabled;
+        self.upload_header(queue);
+    }
+
+    /// Switches the shader between the baked SH coefficients and the DDGI atlases, which
+    /// share this grid.
+    pub fn set_dynamic(&mut self, queue: &wgpu::Queue, dynamic: bool) {
+        self.dynamic = dynamic;
+        self.upload_header(queue);
+    }
+
+    /// Points the capture cameras at a probe.
+    pub fn write_face_cameras(&self, queue: &wgpu::Queue, base: &CameraUniform, probe: u32, zfar: f32) {
+        let znear = (self.grid.spacing.x.min(self.grid.spacing.y).min(self.grid.spacing.z) * 0.01).max(0.01);
+        self.capture.write_cameras(queue, base, self.grid.position(probe), znear, zfar);
+    }
+
+    /// Projects the captured faces of `probe` onto its SH coefficients.
+    pub fn project(&self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, probe: u32) {
+        let params = ProjectParams {
+            probe: [probe, FACE_SIZE, 0, 0],
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Probe Projection"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.project_pipeline);
+        pass.set_bind_group(0, &self.project_bind_group, &[]);
+        pass.dispatch_workgroups(1, 1, 1);
+    }
+
+    /// Marks the grid as baked so the shader starts using it.
+    pub fn finish_bake(&mut self, queue: &wgpu::Queue) {
+        self.baked = true;
+        self.upload_header(queue);
+    }
+}
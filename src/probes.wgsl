@@ -0,0 +1,97 @@
+// Projects the six captured cube faces of one probe onto L2 spherical harmonics.
+
+struct ProjectParams {
+    // x: probe index, y: face size.
+    probe: vec4<u32>,
+};
+
+// Same layout as ProbeVolume in shader.wgsl.
+struct ProbeVolume {
+    origin: vec4<f32>,
+    spacing: vec4<f32>,
+    counts: vec4<u32>,
+    coefficients: array<vec4<f32>>,
+};
+
+@group(0) @binding(0)
+var<uniform> params: ProjectParams;
+
+@group(0) @binding(1)
+var faces: texture_2d_array<f32>;
+
+@group(0) @binding(2)
+var<storage, read_write> volume: ProbeVolume;
+
+const PI: f32 = 3.14159265359;
+const WORKGROUP_SIZE: u32 = 64u;
+
+// Each invocation's sums; the weight total rides in the w of the first coefficient.
+var<workgroup> partial: array<array<vec4<f32>, 9>, 64>;
+
+// Direction through a texel of a face, for the FACES forward/up pairs in probes.rs.
+fn face_dir(face: u32, x: f32, y: f32) -> vec3<f32> {
+    switch face {
+        case 0u: { return vec3<f32>(1.0, y, x); }
+        case 1u: { return vec3<f32>(-1.0, y, -x); }
+        case 2u: { return vec3<f32>(-x, 1.0, -y); }
+        case 3u: { return vec3<f32>(-x, -1.0, y); }
+        case 4u: { return vec3<f32>(-x, y, 1.0); }
+        default: { return vec3<f32>(x, y, -1.0); }
+    }
+}
+
+fn sh_basis(d: vec3<f32>) -> array<f32, 9> {
+    return array<f32, 9>(
+        0.282095,
+        0.488603 * d.y,
+        0.488603 * d.z,
+        0.488603 * d.x,
+        1.092548 * d.x * d.y,
+        1.092548 * d.y * d.z,
+        0.315392 * (3.0 * d.z * d.z - 1.0),
+        1.092548 * d.x * d.z,
+        0.546274 * (d.x * d.x - d.y * d.y),
+    );
+}
+
+@compute @workgroup_size(64)
+fn cs_project(@builtin(local_invocation_index) lid: u32) {
+    let size = params.probe.y;
+    let texels = size * size * 6u;
+    var sums: array<vec4<f32>, 9>;
+    for (var k = 0u; k < 9u; k++) {
+        sums[k] = vec4<f32>(0.0);
+    }
+
+    for (var i = lid; i < texels; i += WORKGROUP_SIZE) {
+        let face = i / (size * size);
+        let texel = vec2<u32>(i % size, i / size % size);
+        let x = (f32(texel.x) + 0.5) / f32(size) * 2.0 - 1.0;
+        let y = 1.0 - (f32(texel.y) + 0.5) / f32(size) * 2.0;
+        // Solid angle of the texel, up to a constant the normalisation removes.
+        let d2 = 1.0 + x * x + y * y;
+        let weight = 1.0 / (d2 * sqrt(d2));
+        let radiance = textureLoad(faces, texel, face, 0).rgb * weight;
+        var basis = sh_basis(normalize(face_dir(face, x, y)));
+        for (var k = 0u; k < 9u; k++) {
+            sums[k] += vec4<f32>(radiance * basis[k], 0.0);
+        }
+        sums[0].w += weight;
+    }
+    partial[lid] = sums;
+    workgroupBarrier();
+
+    for (var stride = WORKGROUP_SIZE / 2u; stride > 0u; stride /= 2u) {
+        if lid < stride {
+            for (var k = 0u; k < 9u; k++) {
+                partial[lid][k] += partial[lid + stride][k];
+            }
+        }
+        workgroupBarrier();
+    }
+
+    if lid < 9u {
+        let scale = 4.0 * PI / max(partial[0][0].w, 1e-6);
+        volume.coefficients[params.probe.x * 9u + lid] = vec4<f32>(partial[0][lid].xyz * scale, 1.0);
+    }
+}
@@ -0,0 +1,202 @@
+use std::cell::Cell;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+
+/// Spans kept for the trace file; recording stops there so a long session cannot run out
+/// of memory.
+const MAX_TRACE_SPANS: usize = 4_000_000;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static NEXT_THREAD: AtomicU32 = AtomicU32::new(0);
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+static RECORDER: Mutex<Recorder> = Mutex::new(Recorder {
+    current: Vec::new(),
+    frame_start: 0,
+    last_frame: None,
+    trace: None,
+});
+
+thread_local! {
+    static THREAD: u32 = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+    static DEPTH: Cell<u32> = const { Cell::new(0) };
+}
+
+/// A timed CPU scope.
+#[derive(Copy, Clone)]
+pub struct Span {
+    pub name: &'static str,
+    /// Microseconds since the profiler started.
+    pub start: u64,
+    pub duration: u64,
+    /// Nesting depth on its thread, 0 for the outermost scopes.
+    pub depth: u32,
+    thread: u32,
+}
+
+/// The spans the main thread recorded in one frame.
+pub struct Frame {
+    pub start: u64,
+    pub duration: u64,
+    pub spans: Vec<Span>,
+}
+
+struct Trace {
+    path: PathBuf,
+    spans: Vec<Span>,
+    full: bool,
+}
+
+struct Recorder {
+    /// Spans finished since the last [`end_frame`].
+    current: Vec<Span>,
+    frame_start: u64,
+    last_frame: Option<Frame>,
+    trace: Option<Trace>,
+}
+
+fn now_us() -> u64 {
+    EPOCH.get_or_init(Instant::now).elapsed().as_micros() as u64
+}
+
+fn recorder() -> std::sync::MutexGuard<'static, Recorder> {
+    RECORDER.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Starts or stops recording scopes. Stopping drops the frame shown by the viewer.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+    if !enabled {
+        let mut recorder = recorder();
+        recorder.current.clear();
+        recorder.last_frame = None;
+    }
+}
+
+/// Whether a trace file will be written on exit.
+pub fn is_tracing() -> bool {
+    recorder().trace.is_some()
+}
+
+/// Enables recording and keeps every span for [`finish_trace`] to write to `path`.
+pub fn start_trace(path: &Path) {
+    recorder().trace = Some(Trace {
+        path: path.to_path_buf(),
+        spans: Vec::new(),
+        full: false,
+    });
+    set_enabled(true);
+}
+
+/// Times the rest of the enclosing block as `name`, if recording is enabled.
+#[must_use]
+pub fn scope(name: &'static str) -> Scope {
+    if !is_enabled() {
+        return Scope { open: None };
+    }
+    let depth = DEPTH.with(|d| {
+        let depth = d.get();
+        d.set(depth + 1);
+        depth
+    });
+    Scope {
+        open: Some((name, now_us(), depth)),
+    }
+}
+
+pub struct Scope {
+    open: Option<(&'static str, u64, u32)>,
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let Some((name, start, depth)) = self.open else {
+            return;
+        };
+        DEPTH.with(|d| d.set(depth));
+        let span = Span {
+            name,
+            start,
+            duration: now_us().saturating_sub(start),
+            depth,
+            thread: THREAD.with(|t| *t),
+        };
+        recorder().current.push(span);
+    }
+}
+
+/// Call once per frame from the main thread: the frame's spans become the one
+/// [`last_frame`] returns and move to the trace.
+pub fn end_frame() {
+    if !is_enabled() {
+        return;
+    }
+    let now = now_us();
+    let main_thread = THREAD.with(|t| *t);
+    let mut recorder = recorder();
+    let spans = std::mem::take(&mut recorder.current);
+    if let Some(trace) = &mut recorder.trace {
+        if !trace.full {
+            if trace.spans.len() + spans.len() > MAX_TRACE_SPANS {
+                log::warn!("CPU trace reached {} spans; later frames are not recorded", MAX_TRACE_SPANS);
+                trace.full = true;
+            } else {
+                trace.spans.extend_from_slice(&spans);
+            }
+        }
+    }
+    recorder.last_frame = Some(Frame {
+        start: recorder.frame_start,
+        duration: now.saturating_sub(recorder.frame_start),
+        spans: spans.into_iter().filter(|s| s.thread == main_thread).collect(),
+    });
+    recorder.frame_start = now;
+}
+
+/// Runs `f` with the last complete frame, if one was recorded.
+pub fn with_last_frame<R>(f: impl FnOnce(&Frame) -> R) -> Option<R> {
+    recorder().last_frame.as_ref().map(f)
+}
+
+/// Writes the spans recorded since [`start_trace`] as Chrome trace events, which
+/// chrome://tracing and Perfetto open. Does nothing without a trace.
+pub fn finish_trace() -> Result<()> {
+    let trace = {
+        let mut recorder = recorder();
+        let mut trace = match recorder.trace.take() {
+            Some(trace) => trace,
+            None => return Ok(()),
+        };
+        trace.spans.append(&mut recorder.current);
+        trace
+    };
+    let file = File::create(&trace.path).with_context(|| format!("creating {}", trace.path.display()))?;
+    let mut out = BufWriter::new(file);
+    let events: Vec<_> = trace
+        .spans
+        .iter()
+        .map(|s| {
+            serde_json::json!({
+                "name": s.name,
+                "ph": "X",
+                "ts": s.start,
+                "dur": s.duration,
+                "pid": 1,
+                "tid": s.thread,
+            })
+        })
+        .collect();
+    serde_json::to_writer(&mut out, &serde_json::json!({ "traceEvents": events }))?;
+    out.flush()?;
+    log::info!("Wrote {} CPU spans to {}", trace.spans.len(), trace.path.display());
+    Ok(())
+}
@@ -0,0 +1,289 @@
+use cgmath::Point3;
+
+use crate::aabb::Aabb;
+use crate::post::HDR_FORMAT;
+use crate::probes::CubeCapture;
+
+pub const MAX_REFLECTION_PROBES: usize = 8;
+const CUBE_SIZE: u32 = 128;
+/// 128² down to 4², roughness 0 to 1.
+const CUBE_MIPS: u32 = 6;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ProbeGpu {
+    position: [f32; 4],
+    box_min: [f32; 4],
+    /// w: distance inside the box over which the probe fades in.
+    box_max: [f32; 4],
+}
+
+/// A cube map captured at `position`, used for reflections of surfaces inside `bounds`
+/// with lookups corrected for the box's parallax.
+#[derive(Copy, Clone, Debug)]
+pub struct ReflectionProbe {
+    pub position: Point3<f32>,
+    pub bounds: Aabb,
+    pub blend_distance: f32,
+}
+
+impl ReflectionProbe {
+    /// A probe fading in over 5% of the box's smallest side.
+    pub fn new(position: Point3<f32>, bounds: Aabb) -> Self {
+        let extent = bounds.extent();
+        Self {
+            position,
+            bounds,
+            blend_distance: extent.x.min(extent.y).min(extent.z) * 0.05,
+        }
+    }
+}
+
+/// Up to `MAX_REFLECTION_PROBES` local reflection probes sharing one cube array, whose
+/// mips hold progressively blurrier copies for rough surfaces.
+pub struct ReflectionProbes {
+    pub capture: CubeCapture,
+    pub view: wgpu::TextureView,
+    pub buffer: wgpu::Buffer,
+    pub sampler: wgpu::Sampler,
+    texture: wgpu::Texture,
+    probes: Vec<ReflectionProbe>,
+    resolve_pipeline: wgpu::RenderPipeline,
+    resolve_bind_group: wgpu::BindGroup,
+    downsample_pipeline: wgpu::RenderPipeline,
+    downsample_layout: wgpu::BindGroupLayout,
+}
+
+impl ReflectionProbes {
+    pub fn new(device: &wgpu::Device, mut probes: Vec<ReflectionProbe>) -> Self {
+        if probes.len() > MAX_REFLECTION_PROBES {
+            log::warn!(
+                "Only the first {} of {} reflection probes are used",
+                MAX_REFLECTION_PROBES,
+                probes.len()
+            );
+            probes.truncate(MAX_REFLECTION_PROBES);
+        }
+        let capture = CubeCapture::new(device, CUBE_SIZE, "Reflection");
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Reflection Probe Cubes"),
+            size: wgpu::Extent3d {
+                width: CUBE_SIZE,
+                height: CUBE_SIZE,
+                depth_or_array_layers: 6 * MAX_REFLECTION_PROBES as u32,
+            },
+            mip_level_count: CUBE_MIPS,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Reflection Probe Cube Array"),
+            dimension: Some(wgpu::TextureViewDimension::CubeArray),
+            ..Default::default()
+        });
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Reflection Probe Buffer"),
+            size: 16 + (MAX_REFLECTION_PROBES * std::mem::size_of::<ProbeGpu>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Reflection Probe Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let sampler_entry = wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        };
+        let texture_entry = |binding, view_dimension| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let resolve_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("reflection_resolve_bind_group_layout"),
+            entries: &[sampler_entry, texture_entry(1, wgpu::TextureViewDimension::D2Array)],
+        });
+        let downsample_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("reflection_downsample_bind_group_layout"),
+            entries: &[sampler_entry, texture_entry(2, wgpu::TextureViewDimension::Cube)],
+        });
+        let resolve_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("reflection_resolve_bind_group"),
+            layout: &resolve_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&capture.faces_view),
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Reflection Probe Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("reflections.wgsl").into()),
+        });
+        let pipeline = |label: &str, layout: &wgpu::BindGroupLayout, entry_point: &str| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                cache: None,
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_face",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point,
+                    targets: &[Some(HDR_FORMAT.into())],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        let resolve_pipeline = pipeline("Reflection Resolve Pipeline", &resolve_layout, "fs_resolve");
+        let downsample_pipeline = pipeline("Reflection Downsample Pipeline", &downsample_layout, "fs_downsample");
+
+        Self {
+            capture,
+            view,
+            buffer,
+            sampler,
+            texture,
+            probes,
+            resolve_pipeline,
+            resolve_bind_group,
+            downsample_pipeline,
+            downsample_layout,
+        }
+    }
+
+    pub fn probes(&self) -> &[ReflectionProbe] {
+        &self.probes
+    }
+
+    /// Adds a probe, which takes effect at the next capture. Returns false when full.
+    pub fn add(&mut self, probe: ReflectionProbe) -> bool {
+        if self.probes.len() >= MAX_REFLECTION_PROBES {
+            return false;
+        }
+        self.probes.push(probe);
+        true
+    }
+
+    /// Writes the probe list; call once every probe has been captured.
+    pub fn upload(&self, queue: &wgpu::Queue) {
+        let probes: Vec<ProbeGpu> = self
+            .probes
+            .iter()
+            .map(|p| ProbeGpu {
+                position: [p.position.x, p.position.y, p.position.z, 0.0],
+                box_min: [p.bounds.min.x, p.bounds.min.y, p.bounds.min.z, 0.0],
+                box_max: [p.bounds.max.x, p.bounds.max.y, p.bounds.max.z, p.blend_distance],
+            })
+            .collect();
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.probes.len() as u32, 0, 0, 0]));
+        if !probes.is_empty() {
+            queue.write_buffer(&self.buffer, 16, bytemuck::cast_slice(&probes));
+        }
+    }
+
+    /// Copies the capture into cube `index` and rebuilds that cube's mips.
+    pub fn store_capture(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, index: usize) {
+        let first_layer = 6 * index as u32;
+        for mip in 0..CUBE_MIPS {
+            let source_bind_group = (mip > 0).then(|| {
+                let source = self.texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Reflection Mip Source"),
+                    dimension: Some(wgpu::TextureViewDimension::Cube),
+                    base_mip_level: mip - 1,
+                    mip_level_count: Some(1),
+                    base_array_layer: first_layer,
+                    array_layer_count: Some(6),
+                    ..Default::default()
+                });
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("reflection_downsample_bind_group"),
+                    layout: &self.downsample_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::Sampler(&self.sampler),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: wgpu::BindingResource::TextureView(&source),
+                        },
+                    ],
+                })
+            });
+            for face in 0..6 {
+                let target = self.texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Reflection Face Target"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_mip_level: mip,
+                    mip_level_count: Some(1),
+                    base_array_layer: first_layer + face,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                });
+                let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Reflection Probe Face"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &target,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+                match &source_bind_group {
+                    Some(bind_group) => {
+                        pass.set_pipeline(&self.downsample_pipeline);
+                        pass.set_bind_group(0, bind_group, &[]);
+                    }
+                    None => {
+                        pass.set_pipeline(&self.resolve_pipeline);
+                        pass.set_bind_group(0, &self.resolve_bind_group, &[]);
+                    }
+                }
+                pass.draw(0..3, face..face + 1);
+            }
+        }
+    }
+}
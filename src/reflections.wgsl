@@ -0,0 +1,76 @@
+// Turns a probe capture into one cube of the reflection array, then fills its mips by
+// repeated 2x2 filtering for rougher lookups.
+
+struct FaceOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) @interpolate(flat) face: u32,
+};
+
+@group(0) @binding(0)
+var linear_sampler: sampler;
+
+@group(0) @binding(1)
+var capture: texture_2d_array<f32>;
+
+// The previous mip of the cube being filtered.
+@group(0) @binding(2)
+var source: texture_cube<f32>;
+
+// The cube face comes in as the instance index.
+@vertex
+fn vs_face(@builtin(vertex_index) vid: u32, @builtin(instance_index) face: u32) -> FaceOut {
+    let uv = vec2<f32>(f32((vid << 1u) & 2u), f32(vid & 2u));
+    return FaceOut(vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0), face);
+}
+
+// Direction through a cube map texel, with u right and v down on each face.
+fn cube_dir(face: u32, uv: vec2<f32>) -> vec3<f32> {
+    let u = uv.x * 2.0 - 1.0;
+    let v = uv.y * 2.0 - 1.0;
+    switch face {
+        case 0u: { return vec3<f32>(1.0, -v, -u); }
+        case 1u: { return vec3<f32>(-1.0, -v, u); }
+        case 2u: { return vec3<f32>(u, 1.0, v); }
+        case 3u: { return vec3<f32>(u, -1.0, -v); }
+        case 4u: { return vec3<f32>(u, -v, 1.0); }
+        default: { return vec3<f32>(-u, -v, -1.0); }
+    }
+}
+
+struct CaptureTexel {
+    uv: vec2<f32>,
+    layer: u32,
+};
+
+// Inverse of the capture faces (FACES in probes.rs), which are rendered upright rather
+// than in cube map orientation.
+fn capture_uv(d: vec3<f32>) -> CaptureTexel {
+    let a = abs(d);
+    var xy: vec2<f32>;
+    var layer: u32;
+    if a.x >= a.y && a.x >= a.z {
+        layer = select(1u, 0u, d.x > 0.0);
+        xy = vec2<f32>(select(-d.z, d.z, d.x > 0.0), d.y) / a.x;
+    } else if a.y >= a.z {
+        layer = select(3u, 2u, d.y > 0.0);
+        xy = vec2<f32>(-d.x, select(d.z, -d.z, d.y > 0.0)) / a.y;
+    } else {
+        layer = select(5u, 4u, d.z > 0.0);
+        xy = vec2<f32>(select(d.x, -d.x, d.z > 0.0), d.y) / a.z;
+    }
+    return CaptureTexel(vec2<f32>(xy.x * 0.5 + 0.5, 0.5 - xy.y * 0.5), layer);
+}
+
+@fragment
+fn fs_resolve(in: FaceOut) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(capture));
+    let texel = capture_uv(cube_dir(in.face, in.pos.xy / size));
+    return vec4<f32>(textureSampleLevel(capture, linear_sampler, texel.uv, texel.layer, 0.0).rgb, 1.0);
+}
+
+@fragment
+fn fs_downsample(in: FaceOut) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(source)) * 0.5;
+    let dir = cube_dir(in.face, in.pos.xy / size);
+    return vec4<f32>(textureSampleLevel(source, linear_sampler, dir, 0.0).rgb, 1.0);
+}
@@ -0,0 +1,5705 @@
+use anyhow::{Context, Result};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use wgpu::util::DeviceExt;
+use winit::event::{ElementState, KeyEvent, MouseButton, WindowEvent};
+use winit::keyboard::PhysicalKey;
+use winit::window::{CursorGrabMode, Window};
+use cgmath::{Point3, Vector3, Vector4};
+
+use crate::{
+    batch, brdf_lut, camera, camera_path, crash, ddgi, geometry, gpu, hud, jobs, latency, lightmap, model,
+    objects, post_stack, profiler, reflections, rt_shadows, shadows, snapshot, sun, upscale, vct,
+};
+use crate::aabb::Aabb;
+use crate::assets::AssetServer;
+use crate::animation::{AnimatedMesh, AnimationPlayer, ModelAnimation};
+use crate::atmosphere::Atmosphere;
+use crate::batch::ShotMatrix;
+use crate::bindings::{Action, Bindings, Rebinder};
+use crate::bindless::BindlessMaterials;
+use crate::bvh::{Bvh, BvhTriangle};
+use crate::camera_path::{CameraPath, PathPlayback};
+use crate::camera::{Camera, CameraUniform, DepthMode, DEFAULT_ENV_NITS, DEFAULT_EV100};
+use crate::asset_watcher::{AssetKind, AssetWatcher};
+use crate::config::EngineConfig;
+use crate::console::{self, CommandRegistry, Console};
+use crate::controller::{CameraFlight, CameraMotion, FovControl, InputState};
+use crate::crowd::CrowdScene;
+use crate::ddgi::Ddgi;
+use crate::debug_draw::{DebugLines, LineVertex};
+use crate::debug_ui::{self, AnimationSettings, DebugUi, InspectorMesh, InspectorNode, Settings};
+use crate::debug_view::DebugView;
+use crate::depth_bounds::DepthBounds;
+use crate::draw_list::{DrawKey, DrawList};
+use crate::evsm::{EvsmShadows, ShadowFilter, EVSM_EXPONENTS};
+use crate::exposure::AutoExposure;
+use crate::flare::LensFlare;
+use crate::fog::VolumetricFog;
+use crate::frame_pacing::{FixedTimestep, FrameLimiter};
+use crate::fxaa::Fxaa;
+use crate::garbage::GpuGarbage;
+use crate::gbuffer::GBuffer;
+use crate::geometry_pool::{GeometryBinder, GeometryPool, GeometryPoolBuilder, MeshGeometry};
+use crate::gpu_culling::{GpuCulling, GpuSceneBuilder};
+use crate::gpu_timer::GpuTimer;
+use crate::hud::{DrawCount, FrameStats, Hud, SceneTotals};
+use crate::latency::{LatencyMonitor, LatencySettings};
+use crate::layout::{SceneEntry, SceneSun};
+use crate::lightmap::{LightmapLayout, Lightmaps};
+use crate::lights::{DirectionalLight, Lights, SpotLight};
+use crate::luminance::{LuminanceAnalyzer, LuminanceStats};
+use crate::material::{DefaultTextures, DrawMaterials, Material, MaterialTexture, TEXTURE_LABELS};
+use crate::material_debug::MaterialDebug;
+use crate::model::{MeshStats, Model, ShadowRole, Texture as ModelTexture};
+use crate::motion_blur::MotionBlur;
+use crate::objects::SceneObjects;
+use crate::options::RendererOptions;
+use crate::picking::{PickHit, PickListener, Picker};
+use crate::plugin::RenderContext;
+use crate::overdraw::Overdraw;
+use crate::texture_inspector::{InspectorMode, TextureInspector};
+use crate::texture_streaming::TextureStreamer;
+use crate::pipelines::{vertex_buffer_layout, PipelineCache, PipelineKey, ShaderFeatures};
+use crate::post::{HdrTarget, Tonemapper, HDR_FORMAT};
+use crate::post_stack::{PostEffect, PostStack};
+use crate::probes::{CubeCapture, ProbeGrid, ProbeVolume};
+use crate::reflections::{ReflectionProbe, ReflectionProbes};
+use crate::rt_shadows::RtShadows;
+use crate::shadows::{ShadowBias, ShadowSettings};
+use crate::scene::{self, LoadedScene, Scene, SceneSettings, SceneSource};
+use crate::screenshot::{self, Frame, Readback};
+use crate::sky::ProceduralSky;
+use crate::snapshot::{AnimationState, CameraState, InstanceState, Snapshot};
+use crate::ssao::Ssao;
+use crate::ssgi::Ssgi;
+use crate::taa::Taa;
+use crate::sun::Sun;
+use crate::upscale::Upscaler;
+use crate::vct::{VoxelGi, VoxelGrid};
+use crate::velocity::VelocityBuffer;
+use crate::vertex_packing::VertexPacking;
+use crate::viewport::{TrackedEye, Viewport};
+use crate::window_mode::{FullscreenMode, WindowMode};
+use crate::world::{Entity, Light, LocalTransform, MaterialHandle, MeshHandle, Name, Parent, Transform, World};
+#[cfg(feature = "openxr")]
+use crate::xr::XrSession;
+use std::time::Instant;
+use cgmath::InnerSpace;
+
+fn opengl_to_wgpu_matrix() -> cgmath::Matrix4<f32> {
+    cgmath::Matrix4::new(
+        1.0, 0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, 0.5, 0.0,
+        0.0, 0.0, 0.5, 1.0,
+    )
+}
+
+fn compute_light_view_proj(
+    light_dir: Vector3<f32>,
+    scene_min: Point3<f32>,
+    scene_max: Point3<f32>,
+    shadow_res: u32,
+) -> cgmath::Matrix4<f32> {
+    let center = Point3::new(
+        (scene_min.x + scene_max.x) * 0.5,
+        (scene_min.y + scene_max.y) * 0.5,
+        (scene_min.z + scene_max.z) * 0.5,
+    );
+    let extent = Vector3::new(
+        scene_max.x - scene_min.x,
+        scene_max.y - scene_min.y,
+        scene_max.z - scene_min.z,
+    );
+    let radius = (extent.magnitude() * 0.5).max(1.0);
+
+    let up_l = if light_dir.y.abs() > 0.95 {
+        Vector3::new(0.0, 0.0, 1.0)
+    } else {
+        Vector3::new(0.0, 1.0, 0.0)
+    };
+    let light_pos = center - light_dir * (radius * 3.0 + 10.0);
+    let light_view = cgmath::Matrix4::look_at_rh(light_pos, center, up_l);
+
+    let corners = [
+        Point3::new(scene_min.x, scene_min.y, scene_min.z),
+        Point3::new(scene_min.x, scene_min.y, scene_max.z),
+        Point3::new(scene_min.x, scene_max.y, scene_min.z),
+        Point3::new(scene_min.x, scene_max.y, scene_max.z),
+        Point3::new(scene_max.x, scene_min.y, scene_min.z),
+        Point3::new(scene_max.x, scene_min.y, scene_max.z),
+        Point3::new(scene_max.x, scene_max.y, scene_min.z),
+        Point3::new(scene_max.x, scene_max.y, scene_max.z),
+    ];
+
+    let mut min_ls = cgmath::Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+    let mut max_ls = cgmath::Vector3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+    for p in corners {
+        let lp = light_view * cgmath::Vector4::new(p.x, p.y, p.z, 1.0);
+        min_ls.x = min_ls.x.min(lp.x);
+        min_ls.y = min_ls.y.min(lp.y);
+        min_ls.z = min_ls.z.min(lp.z);
+        max_ls.x = max_ls.x.max(lp.x);
+        max_ls.y = max_ls.y.max(lp.y);
+        max_ls.z = max_ls.z.max(lp.z);
+    }
+
+    let half_x = ((max_ls.x - min_ls.x) * 0.5).max(0.01);
+    let half_y = ((max_ls.y - min_ls.y) * 0.5).max(0.01);
+    let half_size = half_x.max(half_y) * 1.05;
+
+    let center_x = (min_ls.x + max_ls.x) * 0.5;
+    let center_y = (min_ls.y + max_ls.y) * 0.5;
+
+    let texel = (2.0 * half_size) / shadow_res as f32;
+    let snapped_x = (center_x / texel).floor() * texel;
+    let snapped_y = (center_y / texel).floor() * texel;
+
+    let left = snapped_x - half_size;
+    let right_o = snapped_x + half_size;
+    let bottom = snapped_y - half_size;
+    let top = snapped_y + half_size;
+
+    let z_margin = radius * 0.6 + 10.0;
+    let near_z = (-max_ls.z - z_margin).max(0.1);
+    let far_z = (-min_ls.z + z_margin).max(near_z + 0.1);
+
+    let light_proj = cgmath::ortho(left, right_o, bottom, top, near_z, far_z);
+    opengl_to_wgpu_matrix() * light_proj * light_view
+}
+
+/// Light view-projection of one directional cascade: an orthographic box around the
+/// sphere bounding the camera frustum between the `near` and `far` distances, deep enough
+/// for every caster of the scene. The box is snapped to whole shadow map texels and its
+/// size to steps of an eighth of an octave, so it doesn't shimmer as the camera moves.
+fn compute_cascade_view_proj(
+    light_dir: Vector3<f32>,
+    camera: &Camera,
+    (near, far): (f32, f32),
+    scene: &Aabb,
+    resolution: u32,
+) -> cgmath::Matrix4<f32> {
+    use cgmath::{EuclideanSpace, Matrix4, SquareMatrix};
+
+    let tan_y = (camera.fovy.to_radians() * 0.5).tan();
+    let tan_x = tan_y * camera.aspect;
+    // Splits are distances from the eye, which reach less far along the view axis towards
+    // the frustum corners.
+    let corner_scale = (1.0 + tan_x * tan_x + tan_y * tan_y).sqrt();
+    let view_inv = camera.view_matrix().invert().unwrap_or(Matrix4::from_scale(1.0));
+    let mut corners = Vec::with_capacity(8);
+    for z in [near / corner_scale, far] {
+        for (sx, sy) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+            corners.push(view_inv * Vector4::new(sx * tan_x * z, sy * tan_y * z, -z, 1.0));
+        }
+    }
+    let center = corners.iter().fold(Vector4::new(0.0, 0.0, 0.0, 0.0), |sum, &c| sum + c) / 8.0;
+    let radius = corners.iter().map(|&c| (c - center).truncate().magnitude()).fold(0.01, f32::max);
+    // Room for the snapping to shift the box by up to a texel.
+    let radius = radius * resolution as f32 / (resolution as f32 - 2.0);
+    let radius = ((radius.log2() * 8.0).ceil() / 8.0).exp2();
+
+    let up_l = if light_dir.y.abs() > 0.95 {
+        Vector3::new(0.0, 0.0, 1.0)
+    } else {
+        Vector3::new(0.0, 1.0, 0.0)
+    };
+    // Rotation only, so texel snapping happens in a fixed frame.
+    let light_view = Matrix4::look_at_rh(Point3::origin(), Point3::from_vec(light_dir), up_l);
+    let center_ls = light_view * center;
+    let texel = 2.0 * radius / resolution as f32;
+    let (x, y) = ((center_ls.x / texel).floor() * texel, (center_ls.y / texel).floor() * texel);
+
+    let mut min_z = center_ls.z - radius;
+    let mut max_z = center_ls.z + radius;
+    if scene.is_finite() {
+        for i in 0..8 {
+            let corner = Point3::new(
+                if i & 1 == 0 { scene.min.x } else { scene.max.x },
+                if i & 2 == 0 { scene.min.y } else { scene.max.y },
+                if i & 4 == 0 { scene.min.z } else { scene.max.z },
+            );
+            let z = (light_view * corner.to_homogeneous()).z;
+            min_z = min_z.min(z);
+            max_z = max_z.max(z);
+        }
+    }
+
+    let margin = 1.0;
+    let light_proj = cgmath::ortho(x - radius, x + radius, y - radius, y + radius, -max_z - margin, -min_z + margin);
+    opengl_to_wgpu_matrix() * light_proj * light_view
+}
+
+fn create_depth_texture(device: &wgpu::Device, width: u32, height: u32, label: &str, usage: wgpu::TextureUsages) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Depth32Float,
+        usage,
+        view_formats: &[],
+    })
+}
+
+/// The generated sky when there is one, otherwise the loaded environment map.
+fn environment_view<'a>(
+    env: &'a wgpu::TextureView,
+    sky: Option<&'a ProceduralSky>,
+    atmosphere: Option<&'a Atmosphere>,
+) -> &'a wgpu::TextureView {
+    atmosphere.map(|a| &a.view).or(sky.map(|s| &s.view)).unwrap_or(env)
+}
+
+/// The casters' camera bind group of each shadow cascade.
+fn create_shadow_camera_bind_groups(
+    device: &wgpu::Device,
+    caster_layout: &wgpu::BindGroupLayout,
+    buffers: &[wgpu::Buffer; 4],
+    objects: &SceneObjects,
+) -> [wgpu::BindGroup; 4] {
+    std::array::from_fn(|i| {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: caster_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffers[i].as_entire_binding(),
+                },
+                objects.entry(),
+            ],
+            label: Some(&format!("shadow_camera_bind_group {}", i)),
+        })
+    })
+}
+
+/// The sun driving the first directional light, placed in the day by the scene and then
+/// the options. Logs the lights the scene has.
+fn create_sun(lights: &Lights, scene_sun: Option<SceneSun>, options: &RendererOptions) -> Sun {
+    let mut sun = Sun::from_light(&lights.directional()[0]);
+    if let Some(scene_sun) = scene_sun {
+        if let Some(seconds) = scene_sun.day_length {
+            sun.day_length = seconds;
+        }
+        if let Some(hours) = scene_sun.time_of_day {
+            sun.set_time_of_day(hours);
+        }
+        sun.animate = scene_sun.animate;
+    }
+    if let Some(seconds) = options.day_length {
+        sun.day_length = seconds;
+    }
+    if let Some(hours) = options.time_of_day {
+        sun.set_time_of_day(hours);
+        sun.animate = true;
+    }
+    if lights.directional().len() > 1 {
+        log::info!("{} directional lights", lights.directional().len());
+    }
+    if lights.point_count() > 0 {
+        log::info!(
+            "{} point lights, {} with shadow maps",
+            lights.point_count(),
+            lights.point_shadow_faces.len() / 6
+        );
+    }
+    if lights.spot_count() > 0 {
+        log::info!("{} spot lights", lights.spot_count());
+    }
+    sun
+}
+
+/// The camera path saved at `file`, or an empty one when there is none yet.
+fn load_camera_path(file: &Path, duration: Option<f32>) -> CameraPath {
+    let mut camera_path = match CameraPath::load(file) {
+        Ok(path) => {
+            log::info!("Loaded camera path {} with {} keys", file.display(), path.keys.len());
+            path
+        }
+        Err(e) => {
+            if file.exists() {
+                log::warn!("{:#}", e);
+            }
+            CameraPath::new(camera_path::DEFAULT_PATH_DURATION)
+        }
+    };
+    if let Some(duration) = duration {
+        camera_path.duration = duration;
+    }
+    camera_path
+}
+
+/// The scene's equirectangular environment map, or the one next to its models, or None
+/// when a generated sky replaces it: with `--procedural-sky` or `--atmosphere`, or when
+/// there is no map to show.
+fn find_environment(assets: &AssetServer, scene: &Scene, options: &RendererOptions) -> Option<PathBuf> {
+    let fallback_hdr = PathBuf::from("assets/models/environment/IntelSponza/textures/kloppenheim_05_4k.hdr");
+    let model_paths = scene.entries.iter().map(|e| e.path.as_path());
+    let hdr_path = match &scene.environment {
+        Some(path) => {
+            if !path.exists() {
+                log::warn!("Scene environment {} not found", path.display());
+            }
+            path.clone()
+        }
+        None => assets.find_environment(model_paths).unwrap_or(fallback_hdr),
+    };
+    if options.procedural_sky || options.atmosphere {
+        return None;
+    }
+    if !hdr_path.exists() {
+        log::info!("No environment map found; using the procedural sky");
+        return None;
+    }
+    Some(hdr_path)
+}
+
+/// Uploads the environment map at `environment`, or the black placeholder a generated sky
+/// replaces without one, with its sampler.
+fn create_environment(
+    (device, queue): (&wgpu::Device, &wgpu::Queue),
+    assets: &mut AssetServer,
+    environment: Option<&Path>,
+) -> (wgpu::Texture, wgpu::TextureView, wgpu::Sampler) {
+    // With a generated sky this is only the black placeholder set_environment replaces.
+    let texture = assets.load_environment(device, queue, environment.unwrap_or(Path::new("")));
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        address_mode_u: wgpu::AddressMode::Repeat,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        ..Default::default()
+    });
+    (texture, view, sampler)
+}
+
+/// Group 0 of the scene passes: the camera uniform, the shadow cascades with their
+/// comparison sampler, the environment (or the generated sky) with its sampler, the scene
+/// depth copy, the BRDF LUT, the irradiance and reflection probes, the SSAO, SSGI and fog
+/// results, the DDGI, voxel GI, lightmap and traced shadow textures (placeholders while
+/// those are off) and the scene object table.
+fn create_camera_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    (camera_buffer, objects): (&wgpu::Buffer, &SceneObjects),
+    shadow: (&wgpu::TextureView, &wgpu::Sampler),
+    env: (&wgpu::TextureView, &wgpu::Sampler),
+    screen: (&wgpu::TextureView, &wgpu::TextureView, &wgpu::TextureView, &wgpu::TextureView, &wgpu::TextureView),
+    indirect: (&wgpu::TextureView, &ProbeVolume, &ReflectionProbes, [&wgpu::TextureView; 4]),
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(shadow.0),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Sampler(shadow.1),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(env.0),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::Sampler(env.1),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: wgpu::BindingResource::TextureView(screen.0),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: wgpu::BindingResource::TextureView(indirect.0),
+            },
+            wgpu::BindGroupEntry {
+                binding: 7,
+                resource: indirect.1.buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 8,
+                resource: wgpu::BindingResource::TextureView(&indirect.2.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 9,
+                resource: indirect.2.buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 10,
+                resource: wgpu::BindingResource::Sampler(&indirect.2.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 11,
+                resource: wgpu::BindingResource::TextureView(screen.1),
+            },
+            wgpu::BindGroupEntry {
+                binding: 12,
+                resource: wgpu::BindingResource::TextureView(screen.2),
+            },
+            wgpu::BindGroupEntry {
+                binding: 13,
+                resource: wgpu::BindingResource::TextureView(indirect.3[0]),
+            },
+            wgpu::BindGroupEntry {
+                binding: 14,
+                resource: wgpu::BindingResource::TextureView(indirect.3[1]),
+            },
+            wgpu::BindGroupEntry {
+                binding: 15,
+                resource: wgpu::BindingResource::TextureView(indirect.3[2]),
+            },
+            wgpu::BindGroupEntry {
+                binding: 16,
+                resource: wgpu::BindingResource::TextureView(screen.3),
+            },
+            wgpu::BindGroupEntry {
+                binding: 17,
+                resource: wgpu::BindingResource::TextureView(indirect.3[3]),
+            },
+            wgpu::BindGroupEntry {
+                binding: 18,
+                resource: wgpu::BindingResource::TextureView(screen.4),
+            },
+            objects.entry(),
+        ],
+        label: Some("camera_bind_group"),
+    })
+}
+
+const DEPTH_USAGE: wgpu::TextureUsages = wgpu::TextureUsages::RENDER_ATTACHMENT
+    .union(wgpu::TextureUsages::TEXTURE_BINDING)
+    .union(wgpu::TextureUsages::COPY_SRC);
+const SCENE_DEPTH_USAGE: wgpu::TextureUsages =
+    wgpu::TextureUsages::TEXTURE_BINDING.union(wgpu::TextureUsages::COPY_DST);
+
+/// Where `--top-view` puts its viewport: the lower-right quarter of the window.
+const TOP_VIEW_RECT: [f32; 4] = [0.7, 0.6, 0.28, 0.37];
+/// Where `--stereo` draws each eye, left eye first: the left and right halves of the
+/// window.
+const EYES: [(bool, [f32; 4]); 2] = [(true, [0.0, 0.0, 0.5, 1.0]), (false, [0.5, 0.0, 0.5, 1.0])];
+/// The material shader in the source tree, watched for changes when the engine runs from
+/// a checkout.
+const SHADER_SOURCE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shader.wgsl");
+
+/// A viewport with the uniform buffer and bind group its camera is drawn with.
+struct SceneViewport {
+    viewport: Viewport,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+struct SceneMesh {
+    /// Pooled unless animated or lightmapped.
+    geometry: MeshGeometry,
+    material_index: usize,
+    bone_weight_buffer: Option<wgpu::Buffer>,
+    lightmap_uv_buffer: Option<wgpu::Buffer>,
+    name: String,
+    stats: MeshStats,
+    shadow: ShadowRole,
+    shadow_proxy: Option<MeshGeometry>,
+    /// Conservative world-space bounds for culling; infinite for animated meshes.
+    bounds: Aabb,
+    /// Bounds of the vertices as loaded, i.e. the bind pose of animated meshes.
+    rest_bounds: Aabb,
+    /// Bounds of the vertices in object space, refit to `bounds` when the mesh moves.
+    local_bounds: Aabb,
+    /// Whether an entity has this mesh; despawned meshes are skipped by every pass.
+    drawn: bool,
+    /// Drawn by `GpuCulling` in the camera passes instead of one by one.
+    gpu_culled: bool,
+    /// Lower-detail versions for the camera passes, coarsest last.
+    lods: Vec<SceneLod>,
+    /// Selected LOD: 0 for the mesh itself, else one past the index into `lods`.
+    lod: usize,
+}
+
+#[derive(Clone)]
+struct SceneLod {
+    geometry: MeshGeometry,
+    /// Screen coverage below which this LOD is drawn.
+    coverage: f32,
+}
+
+impl SceneMesh {
+    /// Geometry of the selected LOD.
+    fn lod_geometry(&self) -> &MeshGeometry {
+        match self.lod.checked_sub(1).and_then(|i| self.lods.get(i)) {
+            Some(lod) => &lod.geometry,
+            None => &self.geometry,
+        }
+    }
+
+    /// The LOD for a mesh spanning `coverage` of the screen height.
+    fn select_lod(&self, coverage: f32) -> usize {
+        self.lods.iter().take_while(|lod| coverage < lod.coverage).count()
+    }
+}
+
+const SHADOW_PROXY_FIRST_CASCADE: u32 = 1;
+
+/// Cascade colors of the shapes overlay, matching the cascade index debug view.
+const CASCADE_COLORS: [[f32; 3]; 4] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0, 1.0, 0.0]];
+
+/// Queues the scene shapes overlay: the frozen `camera`'s frustum cut at the cascade splits,
+/// each cascade's light volume, arrows for the directional and spot lights, point light ranges
+/// and the scene bounds.
+fn queue_scene_shapes(
+    shapes: &mut DebugLines,
+    camera: &Camera,
+    cascade_splits: &[f32],
+    light_view_projs: &[cgmath::Matrix4<f32>],
+    lights: &Lights,
+    bounds: &Aabb,
+) {
+    let view = camera.view_matrix();
+    let mut near = camera.znear;
+    for (i, &far) in cascade_splits.iter().enumerate() {
+        let proj = cgmath::perspective(cgmath::Deg(camera.fovy), camera.aspect, near, far);
+        shapes.frustum(opengl_to_wgpu_matrix() * proj * view, CASCADE_COLORS[i]);
+        near = far;
+    }
+    for (i, light_view_proj) in light_view_projs.iter().enumerate() {
+        // Dimmer than the camera slices, which they usually enclose.
+        shapes.frustum(*light_view_proj, CASCADE_COLORS[i].map(|c| c * 0.5));
+    }
+    let center = bounds.center();
+    let radius = bounds.radius();
+    for light in lights.directional() {
+        let dir = light.direction.normalize();
+        let peak = light.color.iter().copied().fold(f32::EPSILON, f32::max);
+        shapes.arrow(center - dir * radius * 1.5, dir, radius * 0.5, light.color.map(|c| c / peak));
+    }
+    for (position, range, color) in lights.point_ranges() {
+        let peak = color.iter().copied().fold(f32::EPSILON, f32::max);
+        shapes.sphere(position, range, color.map(|c| c / peak));
+    }
+    for spot in lights.spots() {
+        let peak = spot.color.iter().copied().fold(f32::EPSILON, f32::max);
+        shapes.arrow(spot.position, spot.direction, spot.range.min(radius) * 0.5, spot.color.map(|c| c / peak));
+    }
+    if !bounds.is_empty() {
+        shapes.aabb(bounds, [1.0, 1.0, 1.0]);
+    }
+}
+
+/// Queues every drawn mesh's bounds: cyan for static meshes, orange for the bind pose of
+/// animated ones, whose culling bounds are unbounded.
+fn queue_mesh_bounds(shapes: &mut DebugLines, meshes: &[SceneMesh]) {
+    for mesh in meshes.iter().filter(|m| m.shadow != ShadowRole::Proxy && m.drawn && !m.rest_bounds.is_empty()) {
+        let color = if mesh.bounds.is_finite() { [0.0, 1.0, 1.0] } else { [1.0, 0.5, 0.0] };
+        shapes.aabb(&mesh.rest_bounds, color);
+    }
+}
+
+/// Which meshes a shadow view draws.
+#[derive(Copy, Clone)]
+enum ShadowCasters {
+    /// Those of a directional cascade; far cascades use shadow proxies when present.
+    Cascade(u32),
+    /// Those reaching into a point or spot light's range.
+    Sphere(Point3<f32>, f32),
+}
+
+impl ShadowCasters {
+    /// The geometry `mesh` casts with, or None when it is culled.
+    fn geometry(self, mesh: &SceneMesh) -> Option<&MeshGeometry> {
+        if mesh.shadow == ShadowRole::NonCaster || !mesh.drawn {
+            return None;
+        }
+        match (self, &mesh.shadow_proxy) {
+            (Self::Cascade(cascade), Some(proxy)) if cascade >= SHADOW_PROXY_FIRST_CASCADE => Some(proxy),
+            (Self::Sphere(center, radius), _) if !mesh.bounds.intersects_sphere(center, radius) => None,
+            _ => Some(&mesh.geometry),
+        }
+    }
+}
+
+/// A frame's shadow casters, recorded per shadow view.
+struct ShadowBundles {
+    /// One per cascade, unless EVSM draws them.
+    cascades: Vec<wgpu::RenderBundle>,
+    point_faces: Vec<wgpu::RenderBundle>,
+    spot_tiles: Vec<wgpu::RenderBundle>,
+    count: DrawCount,
+}
+
+/// Records the meshes `casters` picks into a bundle for a depth-only shadow pass.
+fn record_caster_bundle<'a>(
+    device: &wgpu::Device,
+    pipeline: &'a wgpu::RenderPipeline,
+    bind_group: &'a wgpu::BindGroup,
+    (meshes, pool): (&'a [SceneMesh], &'a GeometryPool),
+    casters: ShadowCasters,
+) -> (wgpu::RenderBundle, DrawCount) {
+    let mut bundle = device.create_render_bundle_encoder(&wgpu::RenderBundleEncoderDescriptor {
+        label: Some("Shadow Casters"),
+        color_formats: &[],
+        depth_stencil: Some(wgpu::RenderBundleDepthStencil {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_read_only: false,
+            stencil_read_only: true,
+        }),
+        sample_count: 1,
+        multiview: None,
+    });
+    bundle.set_pipeline(pipeline);
+    bundle.set_bind_group(0, bind_group, &[]);
+    let mut count = DrawCount::default();
+    let mut binder = GeometryBinder::new(pool);
+    for (i, mesh) in meshes.iter().enumerate() {
+        let Some(geometry) = casters.geometry(mesh) else {
+            continue;
+        };
+        let (indices, base_vertex) = binder.bind_bundle(&mut bundle, geometry, false);
+        count.add(indices.len() as u32);
+        bundle.draw_indexed(indices, base_vertex, objects::instance(i));
+    }
+    let bundle = bundle.finish(&wgpu::RenderBundleDescriptor {
+        label: Some("Shadow Casters"),
+    });
+    (bundle, count)
+}
+
+/// Shadow casters of one directional cascade, drawn straight into `pass`.
+fn draw_cascade_casters(
+    pass: &mut wgpu::RenderPass<'_>,
+    meshes: &[SceneMesh],
+    pool: &GeometryPool,
+    cascade: u32,
+) -> DrawCount {
+    let mut count = DrawCount::default();
+    let mut binder = GeometryBinder::new(pool);
+    for (i, mesh) in meshes.iter().enumerate() {
+        let Some(geometry) = ShadowCasters::Cascade(cascade).geometry(mesh) else {
+            continue;
+        };
+        let (indices, base_vertex) = binder.bind(pass, geometry, false);
+        count.add(indices.len() as u32);
+        pass.draw_indexed(indices, base_vertex, objects::instance(i));
+    }
+    count
+}
+
+/// Opaque and masked meshes of the scene; with `weights_pipeline`, skinned meshes draw
+/// their bone weights instead. `features` selects the pipeline variants; with
+/// `depth_equal`, fully opaque meshes test against the depth prepass instead of writing.
+/// With `gpu_culled`, meshes that `GpuCulling` draws are left to `draw_gpu_culled`.
+/// `order` is the frame's `DrawList::opaque`; pipelines and materials are only bound when
+/// they change from the previous draw.
+fn draw_opaque_meshes(
+    pass: &mut wgpu::RenderPass<'_>,
+    (meshes, order, pool): (&[SceneMesh], &[usize], &GeometryPool),
+    materials: DrawMaterials<'_>,
+    material_meta: &[MaterialMeta],
+    pipeline_cache: &PipelineCache,
+    weights_pipeline: Option<&wgpu::RenderPipeline>,
+    (features, depth_equal, gpu_culled): (ShaderFeatures, bool, bool),
+) -> DrawCount {
+    let mut count = DrawCount::default();
+    materials.begin(pass);
+    if let Some(pipeline) = weights_pipeline {
+        count += draw_weight_meshes(pass, (meshes, pool), materials, pipeline);
+    }
+    let mut binder = GeometryBinder::new(pool);
+    let (mut bound_key, mut bound_material) = (None, None);
+    for &i in order {
+        let mesh = &meshes[i];
+        if (gpu_culled && mesh.gpu_culled) || (weights_pipeline.is_some() && mesh.bone_weight_buffer.is_some()) {
+            continue;
+        }
+        let material_index = mesh.material_index.min(material_meta.len().saturating_sub(1));
+        let meta = material_meta.get(material_index).copied().unwrap_or(MaterialMeta {
+            alpha_mode: model::AlphaMode::Opaque,
+            pipeline_key: PipelineKey::new(model::AlphaMode::Opaque, false),
+        });
+        if meta.alpha_mode == model::AlphaMode::Blend {
+            continue;
+        }
+        let mut key = meta.pipeline_key;
+        key.features = key.features.union(features);
+        key.depth_equal = depth_equal && meta.alpha_mode == model::AlphaMode::Opaque;
+        if mesh.lightmap_uv_buffer.is_some() {
+            key.features = key.features.union(ShaderFeatures::LIGHTMAP);
+        }
+        let Some(pipeline) = pipeline_cache.get(&key) else {
+            continue;
+        };
+        if bound_key != Some(key) {
+            pass.set_pipeline(pipeline);
+            bound_key = Some(key);
+            count.switches += 1;
+        }
+        if bound_material != Some(material_index) {
+            materials.bind(pass, material_index);
+            bound_material = Some(material_index);
+            count.switches += 1;
+        }
+        let normal_mapped = key.features.contains(ShaderFeatures::NORMAL_MAP);
+        let (indices, base_vertex) = binder.bind(pass, mesh.lod_geometry(), normal_mapped);
+        if let Some(uvs) = &mesh.lightmap_uv_buffer {
+            // The stream after the tangents; lightmapped meshes have their own buffers.
+            pass.set_vertex_buffer(1 + normal_mapped as u32, uvs.slice(..));
+            binder.reset();
+        }
+        count.add(indices.len() as u32);
+        pass.draw_indexed(indices, base_vertex, objects::instance(i));
+    }
+    count
+}
+
+/// The material groups of `culling`, as `draw_opaque_meshes` would draw their meshes. Each
+/// group counts as one draw, since only the GPU knows how many meshes survived.
+fn draw_gpu_culled(
+    pass: &mut wgpu::RenderPass<'_>,
+    culling: &GpuCulling,
+    materials: DrawMaterials<'_>,
+    material_meta: &[MaterialMeta],
+    pipeline_cache: &PipelineCache,
+    (features, depth_equal): (ShaderFeatures, bool),
+) -> DrawCount {
+    let mut count = DrawCount::default();
+    materials.begin(pass);
+    for group in culling.groups() {
+        let Some(meta) = material_meta.get(group.material_index) else {
+            continue;
+        };
+        let mut key = meta.pipeline_key;
+        key.features = key.features.union(features);
+        key.depth_equal = depth_equal && meta.alpha_mode == model::AlphaMode::Opaque;
+        let Some(pipeline) = pipeline_cache.get(&key) else {
+            continue;
+        };
+        pass.set_pipeline(pipeline);
+        materials.bind(pass, group.material_index);
+        culling.draw_group(pass, group, key.features.contains(ShaderFeatures::NORMAL_MAP));
+        count.draws += 1;
+    }
+    count
+}
+
+/// Draws the skinned meshes with the bone weight view.
+fn draw_weight_meshes(
+    pass: &mut wgpu::RenderPass<'_>,
+    (meshes, pool): (&[SceneMesh], &GeometryPool),
+    materials: DrawMaterials<'_>,
+    pipeline: &wgpu::RenderPipeline,
+) -> DrawCount {
+    let mut count = DrawCount::default();
+    let mut binder = GeometryBinder::new(pool);
+    for (i, mesh) in meshes.iter().enumerate().filter(|(_, m)| m.shadow != ShadowRole::Proxy && m.drawn) {
+        let Some(weights) = &mesh.bone_weight_buffer else {
+            continue;
+        };
+        pass.set_pipeline(pipeline);
+        materials.bind(pass, mesh.material_index);
+        let (indices, base_vertex) = binder.bind(pass, &mesh.geometry, false);
+        pass.set_vertex_buffer(1, weights.slice(..));
+        binder.reset();
+        count.add(indices.len() as u32);
+        pass.draw_indexed(indices, base_vertex, objects::instance(i));
+    }
+    count
+}
+
+/// The console commands every renderer starts with.
+fn builtin_commands() -> CommandRegistry {
+    let mut commands = CommandRegistry::default();
+    commands.register("help", "list the commands", |r, _| Ok(r.commands.help()));
+    commands.register("clear", "clear the console", |r, _| {
+        r.console.clear();
+        Ok(String::new())
+    });
+    commands.register("load", "load <path>: restore a snapshot", |r, args| {
+        let [path] = args else {
+            anyhow::bail!("Usage: load <path>");
+        };
+        let snapshot = Snapshot::load(Path::new(path))?;
+        r.restore(&snapshot);
+        Ok(format!("Restored {}", path))
+    });
+    commands.register("save", "save [path]: write a snapshot", |r, args| {
+        let path = Path::new(args.first().copied().unwrap_or(snapshot::DEFAULT_SNAPSHOT_PATH));
+        r.snapshot().save(path)?;
+        Ok(format!("Saved {}", path.display()))
+    });
+    commands.register("screenshot", "screenshot [1|2|4]: save the frame, supersampled by a factor", |r, args| {
+        let factor = match args {
+            [] => r.screenshot_supersample,
+            [n] => n.parse().ok().filter(|f| screenshot::SUPERSAMPLE_FACTORS.contains(f)).ok_or_else(|| {
+                anyhow::anyhow!("Supersampling must be 1, 2 or 4, not '{}'", n)
+            })?,
+            _ => anyhow::bail!("Usage: screenshot [1|2|4]"),
+        };
+        let path = r.screenshot(factor)?;
+        Ok(format!("Saved {}", path.display()))
+    });
+    commands.set_arguments("screenshot", &["1", "2", "4"]);
+    commands.register("reset-gpu", "rebuild every GPU resource on a new device, as after a device loss", |r, _| {
+        r.device_lost.store(true, Ordering::Relaxed);
+        Ok("Recreating the GPU device".to_string())
+    });
+    commands.register("viewport", "viewport top | clear: add a top-down view, or remove the extra views", |r, args| {
+        match args {
+            ["top"] => {
+                r.add_viewport(Viewport::top_down(&r.scene_bounds, TOP_VIEW_RECT));
+                Ok("Added a top-down viewport".to_string())
+            }
+            ["clear"] => {
+                r.clear_viewports();
+                Ok("Removed the viewports".to_string())
+            }
+            _ => anyhow::bail!("Usage: viewport top | clear"),
+        }
+    });
+    commands.set_arguments("viewport", &["top", "clear"]);
+    commands.register("sun", "sun <azimuth> <elevation>: place the sun, in degrees", |r, args| {
+        let [azimuth, elevation] = console::parse_numbers(args)?;
+        r.sun.set_angles(azimuth, elevation);
+        Ok(format!("Sun at {:.0}° azimuth, {:.0}° elevation", r.sun.azimuth, r.sun.elevation))
+    });
+    commands.register("time", "time <hours>: set the time of day", |r, args| {
+        let [hours] = console::parse_numbers(args)?;
+        r.sun.set_time_of_day(hours);
+        Ok(format!("Time of day {:.1} h", r.sun.time_of_day))
+    });
+    commands.register("exposure", "exposure <ev100> | auto: set the camera exposure", |r, args| {
+        if args == ["auto"] {
+            r.auto_exposure.enabled = true;
+            return Ok("Auto exposure on".to_string());
+        }
+        let [ev100] = console::parse_numbers(args)?;
+        r.auto_exposure.enabled = false;
+        r.ev100 = ev100;
+        Ok(format!("EV100 {:.1}", ev100))
+    });
+    commands.set_arguments("exposure", &["auto"]);
+    commands.register("debug", "debug <view>: show a debug view, or off", |r, args| {
+        let names = DebugView::ALL.map(DebugView::name).join(", ");
+        let [name] = args else {
+            anyhow::bail!("Usage: debug <view>, one of {}", names);
+        };
+        let view = DebugView::from_name(name).with_context(|| format!("No debug view '{}'; one of {}", name, names))?;
+        r.debug_view = view;
+        Ok(format!("Debug view: {:?}", view))
+    });
+    commands.set_arguments("debug", &DebugView::ALL.map(DebugView::name));
+    commands
+}
+
+#[derive(Copy, Clone)]
+struct MaterialMeta {
+    alpha_mode: model::AlphaMode,
+    pipeline_key: PipelineKey,
+}
+
+/// The GPU device and the window's surface it presents to.
+struct Display {
+    surface: wgpu::Surface<'static>,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    surface_caps: wgpu::SurfaceCapabilities,
+    /// Sized to the window, with the first present mode until the latency settings pick one.
+    config: wgpu::SurfaceConfiguration,
+    /// Set from the device-lost callback.
+    device_lost: Arc<AtomicBool>,
+    #[cfg(feature = "openxr")]
+    xr: Option<XrSession>,
+}
+
+impl Display {
+    /// Opens a device for `window` on the adapter `options` pick, or on the headset
+    /// runtime's with `--openxr`. GPU errors from here on are caught in error scopes that
+    /// [`Renderer::build`] pops once the scene is built.
+    async fn open(window: &Arc<Window>, options: &RendererOptions) -> Result<Self> {
+        let size = window.inner_size();
+        let backends = options.backends;
+        // With `--openxr` the runtime opens the device, and the window mirrors the headset.
+        #[cfg(feature = "openxr")]
+        let (xr, opened) = match options.openxr {
+            true => XrSession::new().map(|(xr, gpu)| (Some(xr), Some(gpu))).context("starting OpenXR")?,
+            false => (None, None),
+        };
+        #[cfg(not(feature = "openxr"))]
+        let opened: Option<gpu::Gpu> = {
+            if options.openxr {
+                log::warn!("Ignoring --openxr in a build without the openxr feature");
+            }
+            None
+        };
+        let (surface, adapter, device, queue) = match opened {
+            Some((instance, adapter, device, queue)) => {
+                let surface = instance.create_surface(window.clone())?;
+                (surface, adapter, device, queue)
+            }
+            None => {
+                let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+                    backends,
+                    ..Default::default()
+                });
+                let surface = instance.create_surface(window.clone())?;
+                let (adapter, device, queue) =
+                    gpu::request_device(&instance, backends, &surface, options.gpu.as_deref()).await?;
+                (surface, adapter, device, queue)
+            }
+        };
+        let info = adapter.get_info();
+        crash::set_context(
+            "adapter",
+            format!("{} ({:?}, {:?}, driver {} {})", info.name, info.device_type, info.backend, info.driver, info.driver_info),
+        );
+        let device_lost = Arc::new(AtomicBool::new(false));
+        gpu::watch_device(&device, device_lost.clone());
+        // Running out of memory or a bad resource while building the scene fails startup
+        // with an error rather than a panic.
+        device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        
+        let surface_caps = surface.get_capabilities(&adapter);
+        let surface_format = surface_caps
+            .formats
+            .iter()
+            .copied()
+            .find(|f| f.is_srgb())
+            .unwrap_or(surface_caps.formats[0]);
+        #[cfg(feature = "openxr")]
+        let headset = xr.is_some();
+        #[cfg(not(feature = "openxr"))]
+        let headset = false;
+        // The frame is copied from the window into the headset's swapchain.
+        anyhow::ensure!(
+            !headset || surface_caps.usages.contains(wgpu::TextureUsages::COPY_SRC),
+            "The window's surface cannot be copied to the headset"
+        );
+        let copy = if headset { wgpu::TextureUsages::COPY_SRC } else { wgpu::TextureUsages::empty() };
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | copy,
+            format: surface_format,
+            width: size.width,
+            height: size.height,
+            present_mode: surface_caps.present_modes[0],
+            alpha_mode: surface_caps.alpha_modes[0],
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        Ok(Self {
+            surface,
+            device,
+            queue,
+            surface_caps,
+            config,
+            device_lost,
+            #[cfg(feature = "openxr")]
+            xr,
+        })
+    }
+}
+
+/// The bind group layouts of the scene passes.
+struct SceneLayouts {
+    /// See [`create_camera_bind_group`].
+    camera: wgpu::BindGroupLayout,
+    /// A light's camera and the object table, for passes drawing scene meshes from a light.
+    caster: wgpu::BindGroupLayout,
+    /// A camera alone, for the debug lines.
+    shadow_camera: wgpu::BindGroupLayout,
+    /// A material's uniform and textures.
+    material: wgpu::BindGroupLayout,
+}
+
+impl SceneLayouts {
+    fn new(device: &wgpu::Device) -> Self {
+        let shadow_camera = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("shadow_camera_bind_group_layout"),
+        });
+        let caster = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                SceneObjects::layout_entry(),
+            ],
+            label: Some("caster_bind_group_layout"),
+        });
+        let camera = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 7,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 8,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::CubeArray,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 9,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 10,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 11,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 12,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 13,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 14,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 15,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 16,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 17,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 18,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                SceneObjects::layout_entry(),
+            ],
+            label: Some("camera_bind_group_layout"),
+        });
+        let material = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("material_bind_group_layout"),
+        });
+        Self {
+            camera,
+            caster,
+            shadow_camera,
+            material,
+        }
+    }
+}
+
+/// The pipelines of the scene passes besides the material ones in the [`PipelineCache`].
+struct ScenePipelines {
+    shadow: wgpu::RenderPipeline,
+    sky: wgpu::RenderPipeline,
+    depth_prepass: wgpu::RenderPipeline,
+}
+
+impl ScenePipelines {
+    fn new(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        (caster_layout, camera_layout): (&wgpu::BindGroupLayout, &wgpu::BindGroupLayout),
+        vertex_packing: VertexPacking,
+        (shadow_bias, depth_mode): (wgpu::DepthBiasState, DepthMode),
+    ) -> Self {
+        let shadow_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Pipeline Layout"),
+            bind_group_layouts: &[caster_layout],
+            push_constant_ranges: &[],
+        });
+
+        let sky_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sky Pipeline Layout"),
+            bind_group_layouts: &[camera_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shadow = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Pipeline"),
+            layout: Some(&shadow_pipeline_layout),
+            cache: None,
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_shadow",
+                buffers: &[vertex_buffer_layout(vertex_packing)],
+                compilation_options: Default::default(),
+            },
+            fragment: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: shadow_bias,
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        let sky = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sky Pipeline"),
+            layout: Some(&sky_pipeline_layout),
+            cache: None,
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_sky",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_sky",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: HDR_FORMAT,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: depth_mode.compare(wgpu::CompareFunction::LessEqual),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        let depth_prepass = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Depth Prepass Pipeline"),
+            layout: Some(&sky_pipeline_layout),
+            cache: None,
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[vertex_buffer_layout(vertex_packing)],
+                compilation_options: Default::default(),
+            },
+            fragment: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: depth_mode.compare(wgpu::CompareFunction::Less),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        Self {
+            shadow,
+            sky,
+            depth_prepass,
+        }
+    }
+}
+
+/// The scene's models as GPU meshes and materials, spawned into the world, with what the
+/// traced GI and GPU culling are built from.
+struct SceneMeshes {
+    meshes: Vec<SceneMesh>,
+    mesh_entities: Vec<Entity>,
+    /// The root entity of each model's node hierarchy.
+    model_roots: Vec<Entity>,
+    materials: Vec<Material>,
+    material_meta: Vec<MaterialMeta>,
+    /// The materials and texture slots sampling each texture file.
+    texture_files: HashMap<PathBuf, Vec<(usize, usize)>>,
+    geometry_pool: GeometryPool,
+    picker: Picker,
+    animations: Vec<ModelAnimation>,
+    /// Triangles of the static meshes, for the traced GI and the lightmaps.
+    static_triangles: Vec<BvhTriangle>,
+    lightmap_layout: LightmapLayout,
+    /// Present with `--gpu-culling` on a device that supports it.
+    gpu_scene: Option<GpuSceneBuilder>,
+    /// The normals and tangents the mesh inspector draws.
+    inspect_lines: Vec<LineVertex>,
+}
+
+impl SceneMeshes {
+    /// Uploads the meshes and materials of `models`, spawns their node hierarchies and
+    /// meshes into `world` and pushes each mesh's object to `objects`, prepares the
+    /// pipelines they draw with and registers their textures with the streamer.
+    fn load(
+        (device, queue): (&wgpu::Device, &wgpu::Queue),
+        (material_layout, default_textures): (&wgpu::BindGroupLayout, &DefaultTextures),
+        (pipeline_cache, texture_streamer): (&mut PipelineCache, &mut Option<TextureStreamer>),
+        (world, objects): (&mut World, &mut SceneObjects),
+        models: &[(Model, cgmath::Matrix4<f32>)],
+        (instances, scene_bounds): (&[InstanceState], &Aabb),
+        options: &RendererOptions,
+    ) -> Self {
+        let vertex_packing = options.vertex_packing;
+        let traced_gi = options.ddgi || options.vct || options.rt_shadows || options.lightmaps;
+        let mut meshes: Vec<SceneMesh> = Vec::new();
+        let mut mesh_entities = Vec::new();
+        let mut model_roots = Vec::new();
+        let mut materials: Vec<Material> = Vec::new();
+        let mut material_meta: Vec<MaterialMeta> = Vec::new();
+        let mut material_albedo: Vec<[f32; 3]> = Vec::new();
+        let mut static_triangles: Vec<BvhTriangle> = Vec::new();
+        let mut picker = Picker::default();
+        let mut lightmap_layout = LightmapLayout::default();
+        let mut geometry_pool = GeometryPoolBuilder::new(vertex_packing);
+        let mut gpu_scene = (options.gpu_culling && device.features().contains(GpuCulling::FEATURES))
+            .then(|| GpuSceneBuilder::new(vertex_packing));
+
+        let weights_key = PipelineKey::with_features(ShaderFeatures::BONE_WEIGHTS, true);
+        let mut animations: Vec<ModelAnimation> = Vec::new();
+
+        let total_vertices: usize = models
+            .iter()
+            .flat_map(|(m, _)| m.meshes.iter())
+            .map(|m| m.vertices.len())
+            .sum();
+        let inspect_stride = total_vertices.div_ceil(50_000).max(1);
+        let inspect_length = scene_bounds.radius() * 0.004;
+        let mut inspect_lines: Vec<LineVertex> = Vec::new();
+
+        // Pooled geometry, shadow proxy and LODs of each glTF primitive, shared by every node
+        // instancing it.
+        let mut shared_geometry: HashMap<_, (MeshGeometry, Option<MeshGeometry>, Vec<SceneLod>)> = HashMap::new();
+        let mut shared_meshes = 0;
+        let mut texture_files: HashMap<PathBuf, Vec<(usize, usize)>> = HashMap::new();
+        for (model_index, (model, placement)) in models.iter().enumerate() {
+            let material_offset = materials.len();
+            for mat in &model.materials {
+                // Streamed textures are rebuilt mip by mip, so only whole uploads reload.
+                if texture_streamer.is_none() {
+                    let images = [mat.base_color_image, mat.metallic_roughness_image, mat.normal_image];
+                    for (slot, image) in images.into_iter().enumerate() {
+                        if let Some(path) = image.and_then(|i| model.texture_paths.get(i)).and_then(Option::as_ref) {
+                            texture_files.entry(path.clone()).or_default().push((materials.len(), slot));
+                        }
+                    }
+                }
+                let material = match texture_streamer.as_mut() {
+                    Some(streamer) => streamer.load_material(
+                        device,
+                        queue,
+                        material_layout,
+                        (materials.len(), mat),
+                        &model.textures,
+                        default_textures,
+                    ),
+                    None => Material::from_model_material(
+                        device,
+                        queue,
+                        material_layout,
+                        mat,
+                        &model.textures,
+                        default_textures,
+                    ),
+                };
+                materials.push(material);
+                let mut pipeline_key = PipelineKey::new(mat.alpha_mode, mat.double_sided);
+                if mat.normal_image.is_some_and(|i| i < model.textures.len()) {
+                    pipeline_key.features = pipeline_key.features.union(ShaderFeatures::NORMAL_MAP);
+                }
+                if let Some(hook) = &mat.surface_hook {
+                    pipeline_key.surface_hook = Some(pipeline_cache.register_surface_hook(hook));
+                }
+                pipeline_cache.prepare(device, pipeline_key);
+                material_meta.push(MaterialMeta {
+                    alpha_mode: mat.alpha_mode,
+                    pipeline_key,
+                });
+                material_albedo.push(ddgi::material_albedo(mat, &model.textures));
+            }
+
+            // The glTF node hierarchy, under a root for the model's placement.
+            let root = world.spawn();
+            model_roots.push(root);
+            world.insert(root, LocalTransform(*placement));
+            if let Some(name) = instances.get(model_index).and_then(|i| i.path.file_name()) {
+                world.insert(root, Name(name.to_string_lossy().into_owned()));
+            }
+            let node_entities: Vec<Entity> = model.animation.parents.iter().map(|_| world.spawn()).collect();
+            for (i, &node) in node_entities.iter().enumerate() {
+                let parent = model.animation.parents[i].map_or(root, |p| node_entities[p]);
+                world.insert(node, Parent(parent));
+                world.insert(node, LocalTransform(model.animation.rest[i].matrix()));
+                if let Some(name) = model.node_names.get(i).filter(|n| !n.is_empty()) {
+                    world.insert(node, Name(name.clone()));
+                }
+            }
+
+            let animated_nodes = model.animation.animated_nodes();
+            let mut animated_meshes: Vec<AnimatedMesh> = Vec::new();
+
+            for mesh in &model.meshes {
+                let animated = !model.animation.is_empty()
+                    && (mesh.skin.is_some() || animated_nodes.get(mesh.node).copied().unwrap_or(false));
+                let skinned = animated && mesh.skin.is_some();
+                let material_index = material_offset + mesh.material_index;
+                let traced = material_meta
+                    .get(material_index)
+                    .is_some_and(|m| m.alpha_mode != model::AlphaMode::Blend);
+                // Posing and lightmap charts work on world-space vertices.
+                let baked = (animated || (options.lightmaps && traced)).then(|| {
+                    let mut mesh = mesh.clone();
+                    mesh.bake();
+                    mesh
+                });
+                let mesh = baked.as_ref().unwrap_or(mesh);
+                // Lightmapped meshes are drawn with their chart-split copy.
+                let unwrapped = (options.lightmaps && !animated && traced).then(|| lightmap::unwrap(mesh));
+                let mesh = unwrapped.as_ref().map_or(mesh, |u| &u.mesh);
+                let normal_mapped = material_meta
+                    .get(material_offset + mesh.material_index)
+                    .is_some_and(|m| m.pipeline_key.features.contains(ShaderFeatures::NORMAL_MAP));
+                let tangents = normal_mapped.then(|| {
+                    if mesh.tangents.len() == mesh.vertices.len() {
+                        Cow::Borrowed(&mesh.tangents[..])
+                    } else {
+                        Cow::Owned(geometry::generate_tangents(&mesh.vertices, &mesh.indices))
+                    }
+                });
+                let shared = mesh
+                    .source
+                    .filter(|_| !animated)
+                    .and_then(|source| shared_geometry.get(&(model_index, source)));
+                shared_meshes += shared.is_some() as usize;
+                // Skinned meshes are rewritten every frame, and lightmap UVs are a stream per mesh.
+                let geometry = if skinned || unwrapped.is_some() {
+                    let usage = if skinned { wgpu::BufferUsages::COPY_DST } else { wgpu::BufferUsages::empty() };
+                    let mesh_data = (mesh.vertices.as_slice(), mesh.indices.as_slice());
+                    MeshGeometry::own(device, vertex_packing, mesh_data, tangents.as_deref(), usage)
+                } else if let Some((geometry, _, _)) = shared {
+                    geometry.clone()
+                } else {
+                    geometry_pool.add((&mesh.vertices, &mesh.indices), tangents.as_deref())
+                };
+                let bone_weight_buffer = skinned.then(|| {
+                    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("Bone Weight Buffer"),
+                        contents: bytemuck::cast_slice(&vec![0.0f32; mesh.vertices.len()]),
+                        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                    })
+                });
+
+                let world_vertices = mesh.world_vertices();
+                for (i, v) in world_vertices.iter().enumerate().step_by(inspect_stride) {
+                    let p = Vector3::from(v.position);
+                    let mut lines = vec![(Vector3::from(v.normal), [0.2, 0.4, 1.0])];
+                    if let Some(t) = mesh.tangents.get(i) {
+                        let t = (mesh.transform * Vector4::new(t[0], t[1], t[2], 0.0)).truncate();
+                        lines.push((t / t.magnitude().max(f32::EPSILON), [1.0, 0.25, 0.2]));
+                    }
+                    for (dir, color) in lines {
+                        let end = p + dir * inspect_length;
+                        inspect_lines.push(LineVertex { position: v.position, color });
+                        inspect_lines.push(LineVertex { position: end.into(), color });
+                    }
+                }
+
+                let stats = mesh.stats();
+                let rest_bounds = mesh.world_bounds();
+                let mut local_bounds = Aabb::empty();
+                for v in &mesh.vertices {
+                    local_bounds.grow(v.position);
+                }
+                let bounds = if animated { Aabb::infinite() } else { rest_bounds };
+
+                let shadow_proxy = match shared {
+                    Some((_, proxy, _)) => proxy.clone(),
+                    None => (options.import.shadow_proxy_triangles > 0
+                    && mesh.shadow == ShadowRole::Caster
+                    && !animated
+                    && stats.triangles > options.import.shadow_proxy_triangles)
+                    .then(|| {
+                        let (vertices, indices) = geometry::simplify_clustered(
+                            &mesh.vertices,
+                            &mesh.indices,
+                            options.import.shadow_proxy_triangles / 4,
+                        );
+                        log::info!(
+                            "Shadow proxy for '{}': {} -> {} triangles",
+                            mesh.name,
+                            stats.triangles,
+                            indices.len() / 3
+                        );
+                        geometry_pool.add((&vertices, &indices), None)
+                    }),
+                };
+
+                // Lightmapped meshes have no LODs to match their lightmap UVs.
+                let lods: Vec<SceneLod> = match shared {
+                    Some((_, _, lods)) => lods.clone(),
+                    None if animated || unwrapped.is_some() => Vec::new(),
+                    None => mesh
+                        .lods
+                        .iter()
+                        .map(|lod| {
+                            let tangents =
+                                normal_mapped.then(|| geometry::generate_tangents(&lod.vertices, &lod.indices));
+                            SceneLod {
+                                geometry: geometry_pool.add((&lod.vertices, &lod.indices), tangents.as_deref()),
+                                coverage: lod.coverage,
+                            }
+                        })
+                        .collect(),
+                };
+                if let (Some(source), None, false) = (mesh.source, shared, animated || unwrapped.is_some()) {
+                    let shared = (geometry.clone(), shadow_proxy.clone(), lods.clone());
+                    shared_geometry.insert((model_index, source), shared);
+                }
+
+                if stats.has_issues() {
+                    log::warn!(
+                        "Mesh '{}': {} degenerate triangles, {} NaN/inf vertices, {} out-of-range indices",
+                        mesh.name,
+                        stats.degenerate_triangles,
+                        stats.invalid_vertices,
+                        stats.out_of_range_indices,
+                    );
+                }
+
+                if traced_gi && !animated && traced {
+                    let albedo = material_albedo[material_index];
+                    let position = |i: &u32| world_vertices.get(*i as usize).map(|v| v.position);
+                    for tri in mesh.indices.chunks_exact(3) {
+                        if let [Some(a), Some(b), Some(c)] = [position(&tri[0]), position(&tri[1]), position(&tri[2])] {
+                            static_triangles.push(BvhTriangle::new(a, b, c, albedo));
+                        }
+                    }
+                }
+
+                if !animated && mesh.shadow != ShadowRole::Proxy {
+                    let positions = world_vertices.iter().map(|v| v.position).collect();
+                    picker.add(meshes.len(), bounds, positions, mesh.indices.clone());
+                }
+
+                if let Some(builder) = gpu_scene.as_mut().filter(|_| !animated && traced) {
+                    if mesh.shadow != ShadowRole::Proxy {
+                        let geometry = (&mesh.vertices[..], &mesh.indices[..]);
+                        builder.add(meshes.len(), material_index, geometry, tangents.as_deref(), bounds);
+                    }
+                }
+
+                if animated {
+                    // Rigid meshes move with their object alone.
+                    animated_meshes.push(AnimatedMesh {
+                        scene_mesh: meshes.len(),
+                        node: mesh.node,
+                        skin: mesh.skin,
+                        rest_vertices: if skinned { mesh.vertices.clone() } else { Vec::new() },
+                        rest_tangents: if skinned && tangents.is_some() {
+                            mesh.tangents.clone()
+                        } else {
+                            Vec::new()
+                        },
+                        joints: mesh.joints.clone(),
+                        weights: mesh.weights.clone(),
+                    });
+                }
+
+                objects.push(mesh.transform, material_index);
+                let entity = world.spawn();
+                world.insert(entity, Transform(mesh.transform));
+                world.insert(entity, MeshHandle(meshes.len()));
+                world.insert(entity, MaterialHandle(material_index));
+                // Baked meshes are in world space and stay put unless animated.
+                if let Some(&node) = node_entities.get(mesh.node).filter(|_| mesh.source.is_some()) {
+                    world.insert(entity, Parent(node));
+                    world.insert(entity, LocalTransform(cgmath::Matrix4::from_scale(1.0)));
+                }
+                mesh_entities.push(entity);
+                meshes.push(SceneMesh {
+                    geometry,
+                    material_index,
+                    bone_weight_buffer,
+                    lightmap_uv_buffer: None,
+                    name: mesh.name.clone(),
+                    stats,
+                    shadow: mesh.shadow,
+                    shadow_proxy,
+                    bounds,
+                    rest_bounds,
+                    local_bounds,
+                    drawn: true,
+                    gpu_culled: false,
+                    lods,
+                    lod: 0,
+                });
+                if let Some(unwrapped) = unwrapped {
+                    lightmap_layout.add(meshes.len() - 1, unwrapped);
+                }
+            }
+
+            if !model.animation.is_empty() {
+                if animated_meshes.iter().any(|m| m.skin.is_some()) {
+                    pipeline_cache.prepare(device, weights_key);
+                }
+                animations.push(ModelAnimation::new(
+                    model.animation.clone(),
+                    *placement,
+                    animated_meshes,
+                ));
+            }
+        }
+
+        objects.upload(queue);
+        world.propagate_transforms();
+        // The objects already hold what the entities start with.
+        world.take_changed::<Transform>();
+        world.take_changed::<MeshHandle>();
+        world.take_changed::<MaterialHandle>();
+        let geometry_pool = geometry_pool.build(device);
+        let pooled = meshes.iter().filter(|m| matches!(m.geometry, MeshGeometry::Pooled { .. })).count();
+        log::info!(
+            "Geometry pool: {} of {} meshes, {} sharing another's, in {} blocks ({:.1} MB)",
+            pooled,
+            meshes.len(),
+            shared_meshes,
+            geometry_pool.block_count(),
+            geometry_pool.buffer_bytes() as f64 / (1024.0 * 1024.0)
+        );
+        log::info!(
+            "Scene: {} meshes, {} triangles, {} vertices",
+            meshes.len(),
+            meshes.iter().map(|m| m.stats.triangles).sum::<usize>(),
+            total_vertices,
+        );
+        Self {
+            meshes,
+            mesh_entities,
+            model_roots,
+            materials,
+            material_meta,
+            texture_files,
+            geometry_pool,
+            picker,
+            animations,
+            static_triangles,
+            lightmap_layout,
+            gpu_scene,
+            inspect_lines,
+        }
+    }
+}
+
+/// Despawns the node hierarchies under `model_roots` and the meshes in `mesh_entities`,
+/// which include the baked meshes outside those hierarchies.
+fn despawn_scene_meshes(world: &mut World, model_roots: &[Entity], mesh_entities: &[Entity]) {
+    for &root in model_roots {
+        world.despawn_recursive(root);
+    }
+    for &entity in mesh_entities {
+        world.despawn(entity);
+    }
+}
+
+/// The traced lighting the options turn on, built from the static triangles of the scene.
+struct TracedGi {
+    vct: Option<VoxelGi>,
+    ddgi: Option<Ddgi>,
+    rt_shadows: Option<RtShadows>,
+    lightmaps: Option<Lightmaps>,
+}
+
+impl SceneMeshes {
+    /// Builds the voxel GI, DDGI, traced shadows and lightmaps the options turn on from
+    /// the static triangles, which are used up. Lightmapped meshes get their lightmap UVs
+    /// and pipelines.
+    fn trace_gi(
+        &mut self,
+        (device, queue): (&wgpu::Device, &wgpu::Queue),
+        pipeline_cache: &mut PipelineCache,
+        (camera_buffer, scene_depth_view, render_size, depth_mode): (
+            &wgpu::Buffer,
+            &wgpu::TextureView,
+            (u32, u32),
+            DepthMode,
+        ),
+        (probe_grid, environment, sun, env_nits): (ProbeGrid, &wgpu::TextureView, Option<&DirectionalLight>, f32),
+        scene_bounds: &Aabb,
+        options: &RendererOptions,
+    ) -> TracedGi {
+        let static_triangles = std::mem::take(&mut self.static_triangles);
+        let vct = options.vct.then(|| {
+            let grid = VoxelGrid::fit(scene_bounds, vct::DEFAULT_RESOLUTION);
+            log::info!("Voxel GI: {}x{}x{} voxels", grid.counts[0], grid.counts[1], grid.counts[2]);
+            VoxelGi::new(device, queue, camera_buffer, grid, &static_triangles)
+        });
+        let bvh = (options.ddgi || options.rt_shadows || options.lightmaps).then(|| Bvh::build(static_triangles));
+        let ddgi = bvh.as_ref().filter(|_| options.ddgi).map(|bvh| {
+            log::info!("DDGI: {} triangles, {} BVH nodes", bvh.triangles.len(), bvh.nodes.len());
+            Ddgi::new(device, probe_grid, bvh, environment)
+        });
+        let rt_shadows = bvh.as_ref().filter(|_| options.rt_shadows).map(|bvh| {
+            RtShadows::new(device, camera_buffer, scene_depth_view, bvh, render_size, depth_mode)
+        });
+        let lightmap_layout = std::mem::take(&mut self.lightmap_layout);
+        let lightmaps = bvh.filter(|_| !lightmap_layout.is_empty()).map(|bvh| {
+            let packed = lightmap_layout.pack(options.lightmap_size);
+            for (index, uvs) in &packed.uvs {
+                let mesh = &mut self.meshes[*index];
+                mesh.lightmap_uv_buffer = Some(device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Lightmap UV Buffer"),
+                    contents: bytemuck::cast_slice(uvs),
+                    usage: wgpu::BufferUsages::VERTEX,
+                }));
+                let mut key = self.material_meta[mesh.material_index].pipeline_key;
+                key.features = key.features.union(ShaderFeatures::LIGHTMAP);
+                pipeline_cache.prepare(device, key);
+            }
+            Lightmaps::new(device, queue, packed, bvh, options.lightmap_samples, sun, env_nits)
+        });
+        TracedGi {
+            vct,
+            ddgi,
+            rt_shadows,
+            lightmaps,
+        }
+    }
+
+    /// Moves the static meshes into the GPU culling's buffers, grouped by material, or by
+    /// pipeline with bindless materials. None without `--gpu-culling` or its features.
+    fn build_gpu_culling(&mut self, device: &wgpu::Device, bindless: bool) -> Option<GpuCulling> {
+        let meshes = &mut self.meshes;
+        let material_meta = &self.material_meta;
+        // Lightmapped meshes keep their own draws for the extra UV stream.
+        // Bindless draws share a multi-draw whenever their pipelines match.
+        let group_of = |material: usize| match bindless {
+            true => material_meta
+                .iter()
+                .position(|meta| {
+                    meta.pipeline_key == material_meta[material].pipeline_key
+                        && meta.alpha_mode == material_meta[material].alpha_mode
+                })
+                .unwrap_or(material),
+            false => material,
+        };
+        let culling = self.gpu_scene.take()?.build(device, |i| meshes[i].lightmap_uv_buffer.is_none(), group_of)?;
+        for &i in culling.scene_meshes() {
+            meshes[i].gpu_culled = true;
+            // The culling buffers hold the full-detail geometry.
+            meshes[i].lods.clear();
+        }
+        log::info!(
+            "GPU culling: {} meshes in {} material groups",
+            culling.scene_meshes().len(),
+            culling.groups().len()
+        );
+        Some(culling)
+    }
+}
+
+/// The scene as read from disk: its entries and settings, the models loaded and placed,
+/// the environment map and the post stack.
+struct SceneData {
+    scene: Scene,
+    settings: SceneSettings,
+    loaded: LoadedScene,
+    /// The environment map shown, None while a generated sky is.
+    environment: Option<PathBuf>,
+    post_effects: Vec<PostEffect>,
+}
+
+/// Everything a renderer is built from besides its device and options. Read from disk
+/// once at startup; a rebuild after a device loss takes it back from the running renderer.
+struct Sources {
+    engine_config: EngineConfig,
+    assets: AssetServer,
+    scene: SceneData,
+    camera_path: CameraPath,
+    bindings: Bindings,
+    batch: Option<ShotMatrix>,
+    /// The `--snapshot` restored once the renderer is built.
+    snapshot: Option<Snapshot>,
+}
+
+impl Sources {
+    /// Reads the config file, the scene with its models and environment map, the post
+    /// stack, the camera path, the bindings and the shot matrix and snapshot `options` name.
+    fn load(options: &RendererOptions) -> Result<Self> {
+        let engine_config = EngineConfig::load(&options.config);
+        let snapshot = options.snapshot.as_deref().map(Snapshot::load).transpose()?;
+        let batch = options.batch.as_deref().map(batch::load_shot_matrix).transpose()?;
+
+        // The config's scene stands in when the options name nothing to show.
+        let names_scene = !options.scene.is_empty() || options.snapshot.is_some() || options.crowd.is_some();
+        let default_scene = engine_config.scene.clone().filter(|_| !names_scene).map(SceneSource::File);
+        let scene_sources: Vec<SceneSource> = default_scene.into_iter().chain(options.scene.iter().cloned()).collect();
+        let (mut scene, settings) = Scene::from_sources(&scene_sources)?;
+        if let Some(snapshot) = snapshot.as_ref().filter(|_| scene.entries.is_empty()) {
+            scene.add_snapshot_models(snapshot);
+        }
+        if scene.entries.is_empty() && options.crowd.is_none() {
+            scene.entries.push(SceneEntry::new(scene::DEFAULT_MODEL));
+        }
+        scene.layout = options.layout.unwrap_or(scene.layout);
+        scene.environment = options.hdr.clone().or(scene.environment);
+
+        crash::set_context("import", format!("{:?}", options.import));
+        let scene_paths: Vec<String> = scene.entries.iter().map(|e| e.path.display().to_string()).collect();
+        crash::set_context("scene", scene_paths.join(", "));
+        let assets = AssetServer::new(options.import);
+        let loaded = scene.load(&assets)?;
+        let environment = find_environment(&assets, &scene, options);
+
+        let post_effects = match &options.post {
+            Some(path) => post_stack::load_post_config(path)?,
+            None => {
+                let path = Path::new(post_stack::DEFAULT_POST_CONFIG_PATH);
+                if path.exists() { post_stack::load_post_config(path)? } else { Vec::new() }
+            }
+        };
+        if !post_effects.is_empty() {
+            log::info!("Post effects: {:?}", post_effects);
+        }
+        Ok(Self {
+            bindings: Bindings::load(&options.bindings, engine_config.bindings.as_ref()),
+            engine_config,
+            assets,
+            scene: SceneData {
+                scene,
+                settings,
+                loaded,
+                environment,
+                post_effects,
+            },
+            camera_path: load_camera_path(&options.camera_path, options.path_duration),
+            batch,
+            snapshot,
+        })
+    }
+}
+
+/// The whole renderer: the GPU device and surface, the loaded scene, the camera and its
+/// controls, and every pass that turns them into a frame. It is configured from its
+/// [`RendererOptions`] and the engine config file when created.
+pub struct Renderer {
+    surface: wgpu::Surface<'static>,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    config: wgpu::SurfaceConfiguration,
+    size: winit::dpi::PhysicalSize<u32>,
+    window: Arc<Window>,
+    /// What the renderer was built from, to build it again after a device loss.
+    options: RendererOptions,
+    /// Set from the device-lost callback.
+    device_lost: Arc<AtomicBool>,
+    assets: AssetServer,
+    pipeline_cache: PipelineCache,
+    sky_pipeline: wgpu::RenderPipeline,
+    shadow_pipeline: wgpu::RenderPipeline,
+    camera: Camera,
+    camera_uniform: CameraUniform,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group_layout: wgpu::BindGroupLayout,
+    /// Group 0 of the shadow and overdraw passes: a camera and the scene object table.
+    caster_layout: wgpu::BindGroupLayout,
+    camera_bind_group: wgpu::BindGroup,
+    shadow_camera_buffers: [wgpu::Buffer; 4],
+    shadow_camera_bind_groups: [wgpu::BindGroup; 4],
+    input: InputState,
+    camera_motion: CameraMotion,
+    fov: FovControl,
+    picker: Picker,
+    /// Scene mesh of the last pick that hit something; focused by `F`.
+    selected: Option<usize>,
+    camera_flight: Option<CameraFlight>,
+    camera_path: CameraPath,
+    /// Where path keys are saved to.
+    camera_path_file: PathBuf,
+    path_playback: Option<PathPlayback>,
+    /// From `--path-capture`: frames of each playback are written here.
+    path_capture: Option<PathBuf>,
+    /// Frame rate of captured playback, which then advances by exactly one frame at a time.
+    path_fps: f32,
+    /// From `--screenshot-supersample`: how many times the window size the screenshot key
+    /// renders at in each axis.
+    screenshot_supersample: u32,
+    /// From `--exr`: captures also write the HDR target, before tonemapping, to an .exr.
+    write_exr: bool,
+    pick_listeners: Vec<PickListener>,
+    last_frame: Instant,
+    start_time: Instant,
+    /// Scene meshes by `MeshHandle`.
+    meshes: Vec<SceneMesh>,
+    world: World,
+    /// The entity each scene mesh was loaded as, moved by the animation.
+    mesh_entities: Vec<Entity>,
+    /// The root entity of each model's node hierarchy, despawned when the model reloads.
+    model_roots: Vec<Entity>,
+    /// Mirrors the fly camera: its transform is camera-to-world.
+    camera_entity: Entity,
+    geometry_pool: GeometryPool,
+    vertex_packing: VertexPacking,
+    objects: SceneObjects,
+    materials: Vec<Material>,
+    material_meta: Vec<MaterialMeta>,
+    /// Per material, the first material sharing its pipeline; draws sort by it.
+    pipeline_ranks: Vec<usize>,
+    draw_list: DrawList,
+    light_view_proj: cgmath::Matrix4<f32>,
+    depth_texture: wgpu::Texture,
+    depth_texture_view: wgpu::TextureView,
+    scene_depth_texture: wgpu::Texture,
+    scene_depth_view: wgpu::TextureView,
+    brdf_lut_view: wgpu::TextureView,
+    shadow_texture: wgpu::Texture,
+    shadow_texture_view: wgpu::TextureView,
+    shadow_sampler: wgpu::Sampler,
+    env_texture: wgpu::Texture,
+    env_texture_view: wgpu::TextureView,
+    env_sampler: wgpu::Sampler,
+    scene_bounds: Aabb,
+    /// Extra cameras drawn over regions of the main view.
+    viewports: Vec<SceneViewport>,
+    /// Depth for the viewport passes, at the render size; made with the first viewport.
+    viewport_depth: Option<(wgpu::Texture, wgpu::TextureView)>,
+    /// From `--stereo`: the eye distance and the viewports of the left and right eyes,
+    /// which follow the camera.
+    stereo: Option<(f32, [usize; 2])>,
+    /// From `--openxr`: the headset session whose tracked eyes place the stereo viewports
+    /// and which is shown each frame.
+    #[cfg(feature = "openxr")]
+    xr: Option<XrSession>,
+    animations: Vec<ModelAnimation>,
+    animation_player: AnimationPlayer,
+    mesh_inspector: DebugLines,
+    show_mesh_inspector: bool,
+    scene_shapes: DebugLines,
+    /// The camera as it was when the scene shapes were turned on; None while they are off.
+    shapes_camera: Option<Camera>,
+    show_mesh_bounds: bool,
+    hud: Hud,
+    debug_ui: DebugUi,
+    console: Console,
+    commands: CommandRegistry,
+    texture_inspector: TextureInspector,
+    material_debug: MaterialDebug,
+    /// All materials in one bind group, when the device supports texture binding arrays.
+    bindless: Option<BindlessMaterials>,
+    material_layout: wgpu::BindGroupLayout,
+    /// With `--texture-budget`, streams the material textures' mips.
+    texture_streamer: Option<TextureStreamer>,
+    /// Pick mesh LODs by screen coverage; off draws every mesh at full detail.
+    lods_enabled: bool,
+    gpu_timer: GpuTimer,
+    /// Mesh draws of the last recorded frame, for the HUD.
+    frame_stats: FrameStats,
+    hdr_target: HdrTarget,
+    tonemapper: Tonemapper,
+    fxaa: Fxaa,
+    post_stack: PostStack,
+    /// Present when the deferred path was selected at startup.
+    gbuffer: Option<GBuffer>,
+    /// Fraction of the window size the scene renders at.
+    render_scale: f32,
+    debug_view: DebugView,
+    overdraw: Overdraw,
+    /// Present when rendering below the window size.
+    upscaler: Option<Upscaler>,
+    luminance: LuminanceAnalyzer,
+    /// Visible depth range the shadow cascades are fitted to; None with `--no-sdsm`.
+    depth_bounds: Option<DepthBounds>,
+    crowd: Option<CrowdScene>,
+    gpu_culling: Option<GpuCulling>,
+    lights: Lights,
+    flashlight: Option<Entity>,
+    batch: Option<ShotMatrix>,
+    garbage: GpuGarbage,
+    evsm: EvsmShadows,
+    shadow_filter: ShadowFilter,
+    shadow_settings: ShadowSettings,
+    shadow_bias: ShadowBias,
+    sun: Sun,
+    sun_clock: Option<String>,
+    /// Environment luminance in nits before the sun's sky tint.
+    env_intensity: f32,
+    /// Camera exposure; light intensities are scaled by it before they reach the GPU.
+    ev100: f32,
+    auto_exposure: AutoExposure,
+    /// Replaces the environment map when set.
+    sky: Option<ProceduralSky>,
+    /// Replaces the environment map and adds aerial perspective when set.
+    atmosphere: Option<Atmosphere>,
+    probes: ProbeVolume,
+    /// Bake the probes after the next frame, once its shadow maps are drawn.
+    bake_probes: bool,
+    ssao: Ssao,
+    ssgi: Ssgi,
+    fog: VolumetricFog,
+    /// Traced probe GI, present when started with --ddgi.
+    ddgi: Option<Ddgi>,
+    ddgi_placeholder: wgpu::TextureView,
+    /// Voxel cone traced GI, present when started with --vct.
+    vct: Option<VoxelGi>,
+    vct_placeholder: wgpu::TextureView,
+    /// Baked static lighting, present when started with --lightmaps.
+    lightmaps: Option<Lightmaps>,
+    lightmap_placeholder: wgpu::TextureView,
+    /// Traced sun shadows, present when started with --rt-shadows.
+    rt_shadows: Option<RtShadows>,
+    rt_shadow_placeholder: wgpu::TextureView,
+    lens_flare: LensFlare,
+    velocity: VelocityBuffer,
+    taa: Taa,
+    motion_blur: MotionBlur,
+    depth_prepass_pipeline: wgpu::RenderPipeline,
+    /// Run the depth prepass even when no screen-space effect needs it.
+    depth_prepass: bool,
+    reflections: ReflectionProbes,
+    /// Recapture the reflection probes after the next frame.
+    reflections_dirty: bool,
+    surface_caps: wgpu::SurfaceCapabilities,
+    latency_settings: LatencySettings,
+    latency: LatencyMonitor,
+    /// From `--fps-cap`; waited on before each redraw.
+    frame_limiter: Option<FrameLimiter>,
+    bindings: Bindings,
+    /// The engine config as last read, to tell what a reload changed.
+    engine_config: EngineConfig,
+    window_mode: WindowMode,
+    /// Config, shader, environment, texture and model files reloaded when they change.
+    asset_watcher: AssetWatcher<Renderer>,
+    /// The materials and texture slots sampling each texture file, to reload it into.
+    texture_files: HashMap<PathBuf, Vec<(usize, usize)>>,
+    /// The scene as loaded, to build it again on a new device and to load a model again
+    /// when its file changes.
+    scene_data: SceneData,
+    /// Set while keys are being rebound; takes all keyboard input until closed.
+    rebinder: Option<Rebinder>,
+    /// From `--fixed-timestep`; movement, animation and the sun advance in its steps.
+    fixed_timestep: Option<FixedTimestep>,
+    /// The window is fully covered by others, as reported by the platform.
+    occluded: bool,
+    /// The last resize was to zero, which is how some platforms minimize.
+    minimized: bool,
+    /// No frames are drawn while the window can't be seen.
+    paused: bool,
+}
+
+impl Renderer {
+    /// Sets up the device and surface for `window`, loads the scene `options` name and
+    /// builds every pass.
+    pub async fn new(window: Window, options: RendererOptions) -> Result<Self> {
+        let window = Arc::new(window);
+        let display = Display::open(&window, &options).await?;
+        if !options.crash_dialog {
+            crash::set_dialog_enabled(false);
+        }
+        if let Some(path) = &options.profile_trace {
+            profiler::start_trace(path);
+        }
+        jobs::init(options.job_threads);
+        let sources = Sources::load(&options)?;
+        Self::create(window, display, sources, options).await
+    }
+
+    /// Builds every pass on `display` for the scene, config and files in `sources`,
+    /// reading nothing from disk.
+    async fn create(window: Arc<Window>, display: Display, sources: Sources, options: RendererOptions) -> Result<Self> {
+        let size = window.inner_size();
+        let Display {
+            surface,
+            device,
+            queue,
+            surface_caps,
+            mut config,
+            device_lost,
+            #[cfg(feature = "openxr")]
+            xr,
+        } = display;
+        let Sources {
+            engine_config,
+            mut assets,
+            scene: scene_data,
+            camera_path,
+            bindings,
+            batch: shot_matrix,
+            snapshot: startup_snapshot,
+        } = sources;
+
+        // Options left unset fall back to the config file.
+        let settings = engine_config.clone().under(&options);
+        let mut camera_settings = settings.camera;
+        let mut fov = FovControl::new(camera_settings.fovy);
+        fov.sprint_kick = options.sprint_fov.unwrap_or(fov.sprint_kick);
+        fov.zoom = options.zoom_fov.unwrap_or(fov.zoom);
+        let mut latency_settings = options.latency;
+        if let Some(vsync) = settings.vsync {
+            latency_settings.present_mode = Some(latency::vsync_present_mode(vsync, &surface_caps));
+        }
+        let window_size = settings.window_size;
+        // A headset's eyes get the resolution it recommends, side by side.
+        #[cfg(feature = "openxr")]
+        let window_size = match &xr {
+            Some(xr) if options.width.is_none() && options.height.is_none() => Some(xr.recommended_size()?),
+            _ => window_size,
+        };
+        let fullscreen = settings.fullscreen.unwrap_or(false);
+        let window_mode = WindowMode::new(settings.fullscreen_mode.unwrap_or_default(), settings.monitor);
+        let shadow_filter = settings.shadow_filter.unwrap_or(ShadowFilter::Pcf);
+        let mut shadow_settings = ShadowSettings::default();
+        shadow_settings.resolution = settings.shadow_resolution.unwrap_or(shadow_settings.resolution);
+        shadow_settings.cascades = settings.shadow_cascades.unwrap_or(shadow_settings.cascades);
+        let upscale_settings = (settings.render_scale.unwrap_or(1.0), options.sharpness);
+
+        #[cfg(feature = "openxr")]
+        let stereo = options.stereo.or(xr.as_ref().map(|_| crate::viewport::DEFAULT_IPD));
+        #[cfg(not(feature = "openxr"))]
+        let stereo = options.stereo;
+        let vertex_packing = options.vertex_packing;
+
+        let SceneSettings {
+            sun: scene_sun,
+            camera: scene_camera,
+            ev100,
+            env_nits,
+            ..
+        } = scene_data.settings;
+        // EV100, environment nits.
+        let exposure_settings = (
+            options.ev100.or(ev100).unwrap_or(DEFAULT_EV100),
+            options.env_nits.or(env_nits).unwrap_or(DEFAULT_ENV_NITS),
+        );
+
+        if camera_settings.zfar <= camera_settings.znear {
+            log::warn!(
+                "Ignoring far plane {} at or before the near plane {}",
+                camera_settings.zfar,
+                camera_settings.znear
+            );
+            camera_settings.zfar = camera_settings.znear * 10_000.0;
+        }
+        fov.reset(camera_settings.fovy);
+        let camera_motion = CameraMotion::new(&camera_settings);
+        latency_settings.apply(&mut config, &surface_caps);
+        surface.configure(&device, &config);
+        crash::set_context("latency", format!("{:?}", latency_settings));
+        let shadow_settings = shadow_settings.sanitized(&device.limits());
+        crash::set_context("shadows", format!("{:?}", shadow_settings));
+        crash::set_context("shadow_bias", format!("{:?}", options.shadow_bias));
+        let latency = LatencyMonitor::new(window.current_monitor().and_then(|m| m.refresh_rate_millihertz()));
+
+        let scene_lights = &scene_data.loaded.lights;
+        let mut scene_bounds = scene_data.loaded.bounds;
+        if let Some(count) = options.crowd {
+            scene_bounds.union(&CrowdScene::bounds(count));
+        }
+
+        let mut camera = Camera::new(size.width, size.height, &camera_settings);
+        camera.frame(&scene_bounds);
+        camera.depth_mode = if options.infinite_far {
+            DepthMode::ReversedInfinite
+        } else if options.reversed_z {
+            DepthMode::Reversed
+        } else {
+            DepthMode::Standard
+        };
+        
+        let mut camera_uniform = CameraUniform::new();
+
+        let light_dir = scene_lights.directional.first().copied().unwrap_or_else(DirectionalLight::sun).direction.normalize();
+        let light_view_proj =
+            compute_light_view_proj(light_dir, scene_bounds.min, scene_bounds.max, shadow_settings.resolution);
+
+        camera_uniform.update(&camera, light_view_proj, light_dir, 1.0);
+        
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Camera Buffer"),
+            contents: bytemuck::cast_slice(&[camera_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        
+        let SceneLayouts {
+            camera: camera_bind_group_layout,
+            caster: caster_bind_group_layout,
+            shadow_camera: shadow_camera_bind_group_layout,
+            material: material_bind_group_layout,
+        } = SceneLayouts::new(&device);
+        let models = &scene_data.loaded.models;
+        let mut objects = SceneObjects::new(&device, models.iter().map(|(m, _)| m.meshes.len()).sum());
+
+        let shadow_texture = shadow_settings.create_texture(&device);
+        let shadow_texture_view = shadows::array_view(&shadow_texture);
+        let shadow_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let (env_texture, env_texture_view, env_sampler) =
+            create_environment((&device, &queue), &mut assets, scene_data.environment.as_deref());
+
+        let shadow_camera_buffers: [wgpu::Buffer; 4] = std::array::from_fn(|i| {
+            let mut u = camera_uniform;
+            u.light_view_proj = light_view_proj.into();
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("Shadow Camera Buffer {}", i)),
+                contents: bytemuck::cast_slice(&[u]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            })
+        });
+
+        let shadow_camera_bind_groups =
+            create_shadow_camera_bind_groups(&device, &caster_bind_group_layout, &shadow_camera_buffers, &objects);
+
+        // Everything up to the tonemap renders at the scaled size.
+        let (render_width, render_height) = upscale::scaled_size(config.width, config.height, upscale_settings.0);
+        let depth_texture = create_depth_texture(&device, render_width, render_height, "Depth Texture", DEPTH_USAGE);
+        let depth_texture_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let scene_depth_texture =
+            create_depth_texture(&device, render_width, render_height, "Scene Depth Copy", SCENE_DEPTH_USAGE);
+        let scene_depth_view = scene_depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let depth_mode = camera.depth_mode;
+        let lens_flare = LensFlare::new(&device, &scene_depth_view, depth_mode);
+        let brdf_lut_view = brdf_lut::create_brdf_lut(&device, &queue);
+        let generated_sky = scene_data.environment.is_none();
+        let atmosphere = (options.atmosphere && generated_sky).then(|| {
+            Atmosphere::new(&device, &queue, &camera_bind_group_layout, options.atmosphere_scale, depth_mode)
+        });
+        let sky = (generated_sky && atmosphere.is_none()).then(|| ProceduralSky::new(&device, options.turbidity));
+        let mut probes = ProbeVolume::new(&device, ProbeGrid::fit(&scene_bounds, options.probe_grid));
+        probes.capture.depth_mode = camera.depth_mode;
+        let mut reflections = ReflectionProbes::new(&device, scene_data.settings.reflection_probes.clone());
+        reflections.capture.depth_mode = camera.depth_mode;
+        let reflections_dirty = !reflections.probes().is_empty();
+        let mut ssao = Ssao::new(&device, &camera_buffer, &scene_depth_view, render_width, render_height, depth_mode);
+        ssao.enabled = options.ssao;
+        ssao.radius = options.ssao_radius;
+        ssao.intensity = options.ssao_intensity;
+        let mut ssgi = Ssgi::new(&device, &camera_buffer, &scene_depth_view, render_width, render_height, depth_mode);
+        ssgi.enabled = options.ssgi;
+        ssgi.radius = options.ssgi_radius;
+        ssgi.intensity = options.ssgi_intensity;
+        let velocity = VelocityBuffer::new(&device, &camera_buffer, &scene_depth_view, render_width, render_height);
+        let mut taa = Taa::new(&device, &scene_depth_view, velocity.view(), render_width, render_height, depth_mode);
+        taa.enabled = options.taa;
+        let mut motion_blur = MotionBlur::new(
+            &device,
+            &camera_buffer,
+            (&scene_depth_view, velocity.view()),
+            render_width,
+            render_height,
+        );
+        motion_blur.enabled = options.motion_blur;
+        motion_blur.shutter_angle = options.shutter_angle;
+        let mut fog = VolumetricFog::new(
+            &device,
+            &camera_buffer,
+            (&shadow_texture_view, &shadow_sampler),
+            scene_bounds.radius() * 2.0,
+            scene_bounds.min.y,
+        );
+        fog.enabled = options.fog;
+        fog.density = options.fog_density;
+        fog.anisotropy = options.fog_anisotropy;
+        fog.height_falloff = options.fog_height_falloff;
+        let ddgi_placeholder = ddgi::placeholder_view(&device);
+        let vct_placeholder = vct::placeholder_view(&device);
+        let lightmap_placeholder = lightmap::placeholder_view(&device);
+        let rt_shadow_placeholder = rt_shadows::placeholder_view(&device);
+
+        let camera_bind_group = create_camera_bind_group(
+            &device,
+            &camera_bind_group_layout,
+            (&camera_buffer, &objects),
+            (&shadow_texture_view, &shadow_sampler),
+            (environment_view(&env_texture_view, sky.as_ref(), atmosphere.as_ref()), &env_sampler),
+            (&scene_depth_view, &ssao.view, ssgi.view(), fog.view(), &rt_shadow_placeholder),
+            (&brdf_lut_view, &probes, &reflections, [&ddgi_placeholder, &ddgi_placeholder, &vct_placeholder, &lightmap_placeholder]),
+        );
+
+        let mut evsm = EvsmShadows::new(&device, &caster_bind_group_layout, vertex_packing);
+        if shadow_filter == ShadowFilter::Evsm {
+            evsm.ensure_maps(&device, &shadow_settings);
+        }
+
+        let lights = Lights::new(&device, &queue, (&caster_bind_group_layout, &objects), scene_lights);
+        let sun = create_sun(&lights, scene_sun, &options);
+
+        // Scene materials, then the material debug view's checker and clay.
+        let material_capacity = models.iter().map(|(m, _)| m.materials.len()).sum::<usize>() + 2;
+        let mut bindless = options.bindless.then(|| BindlessMaterials::new(&device, material_capacity)).flatten();
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Render Pipeline Layout"),
+                bind_group_layouts: &[
+                    &camera_bind_group_layout,
+                    bindless.as_ref().map_or(&material_bind_group_layout, |b| b.layout()),
+                    &lights.layout,
+                    &evsm.layout,
+                ],
+                push_constant_ranges: &[],
+            });
+
+        let mut pipeline_cache = PipelineCache::new(
+            include_str!("shader.wgsl"),
+            render_pipeline_layout,
+            HDR_FORMAT,
+        );
+        if bindless.is_some() {
+            pipeline_cache.define("BINDLESS");
+        }
+        pipeline_cache.set_vertex_packing(vertex_packing);
+        pipeline_cache.set_depth_mode(camera.depth_mode);
+        let ScenePipelines {
+            shadow: shadow_pipeline,
+            sky: sky_pipeline,
+            depth_prepass: depth_prepass_pipeline,
+        } = ScenePipelines::new(
+            &device,
+            pipeline_cache.module(&device, ShaderFeatures::empty()),
+            (&caster_bind_group_layout, &camera_bind_group_layout),
+            vertex_packing,
+            (options.shadow_bias.depth_bias_state(), camera.depth_mode),
+        );
+
+        let default_textures = DefaultTextures::new(&device, &queue);
+
+        let fallback_key = PipelineKey::new(model::AlphaMode::Opaque, false);
+        pipeline_cache.prepare(&device, fallback_key);
+
+        let mut texture_streamer = options.texture_budget.map(TextureStreamer::new);
+        if options.gpu_culling && !device.features().contains(GpuCulling::FEATURES) {
+            log::warn!("GPU culling needs multi-draw indirect with a count buffer; drawing meshes one by one");
+        }
+        let mut world = World::default();
+        let mut scene_meshes = SceneMeshes::load(
+            (&device, &queue),
+            (&material_bind_group_layout, &default_textures),
+            (&mut pipeline_cache, &mut texture_streamer),
+            (&mut world, &mut objects),
+            models,
+            (&scene_data.loaded.instances, &scene_bounds),
+            &options,
+        );
+        let camera_entity = world.spawn();
+        world.insert(camera_entity, Transform(camera.world_matrix()));
+        world.insert(camera_entity, camera.clone());
+        let animation_player = AnimationPlayer::new(&scene_meshes.animations);
+        if !animation_player.is_empty() {
+            log::info!("Loaded {} animation clips", animation_player.clips.len());
+        }
+
+        log::info!("Prepared {} material pipeline variants", pipeline_cache.len());
+        if let (Some(streamer), Some(budget)) = (&texture_streamer, options.texture_budget) {
+            log::info!(
+                "Texture streaming: {:.1} MB resident at load, {} MB budget",
+                streamer.resident_bytes() as f64 / (1024.0 * 1024.0),
+                budget / (1024 * 1024)
+            );
+        }
+
+        let TracedGi {
+            vct,
+            ddgi,
+            rt_shadows,
+            lightmaps,
+        } = scene_meshes.trace_gi(
+            (&device, &queue),
+            &mut pipeline_cache,
+            (&camera_buffer, &scene_depth_view, (render_width, render_height), depth_mode),
+            (
+                probes.grid,
+                environment_view(&env_texture_view, sky.as_ref(), atmosphere.as_ref()),
+                lights.directional().first(),
+                exposure_settings.1,
+            ),
+            &scene_bounds,
+            &options,
+        );
+        let gpu_culling = scene_meshes.build_gpu_culling(&device, bindless.is_some());
+        let SceneMeshes {
+            meshes,
+            mesh_entities,
+            model_roots,
+            materials,
+            material_meta,
+            texture_files,
+            geometry_pool,
+            picker,
+            animations,
+            inspect_lines,
+            ..
+        } = scene_meshes;
+        let lod_meshes = meshes.iter().filter(|m| !m.lods.is_empty()).count();
+        if lod_meshes > 0 {
+            log::info!(
+                "LODs: {} meshes, {} levels",
+                lod_meshes,
+                meshes.iter().map(|m| m.lods.len()).sum::<usize>()
+            );
+        }
+
+        pipeline_cache.prepare_depth_equal_variants(&device);
+        let gbuffer = options.deferred.then(|| {
+            pipeline_cache.prepare_gbuffer_variants(&device);
+            let shader = pipeline_cache.module(&device, ShaderFeatures::empty());
+            let layouts = [&camera_bind_group_layout, &lights.layout, &evsm.layout];
+            log::info!("Deferred rendering");
+            GBuffer::new(&device, shader, layouts, &depth_texture_view, render_width, render_height)
+        });
+
+        let mut mesh_inspector = DebugLines::new(
+            &device,
+            &shadow_camera_bind_group_layout,
+            &camera_buffer,
+            config.format,
+            camera.depth_mode,
+        );
+        mesh_inspector.set_static_lines(&device, &inspect_lines);
+        let scene_shapes = DebugLines::new(
+            &device,
+            &shadow_camera_bind_group_layout,
+            &camera_buffer,
+            config.format,
+            camera.depth_mode,
+        );
+        let hud = Hud::new(&device, &queue, config.format);
+        let debug_ui = DebugUi::new(&device, &window, config.format);
+        let texture_inspector = TextureInspector::new(&device, config.format);
+        let mut material_debug = MaterialDebug::new(&device, &queue, &material_bind_group_layout, &default_textures);
+        material_debug.mode = options.material_debug;
+        if let Some(bindless) = &mut bindless {
+            bindless.update(&device, &queue, materials.iter().chain(material_debug.materials()));
+            log::info!("Bindless materials: {} materials in one bind group", material_capacity);
+        }
+        let gpu_timer = GpuTimer::new(&device, &queue);
+        if !gpu_timer.is_supported() {
+            log::info!("Timestamp queries unavailable; the statistics overlay shows no GPU times");
+        }
+        let overdraw = Overdraw::new(
+            &device,
+            &caster_bind_group_layout,
+            vertex_packing,
+            (&camera_buffer, &objects),
+            config.format,
+            render_width,
+            render_height,
+        );
+
+        let hdr_target = HdrTarget::new(&device, render_width, render_height);
+        let tonemapper = Tonemapper::new(&device, &hdr_target, config.format);
+        let mut fxaa = Fxaa::new(&device, config.format, render_width, render_height);
+        let display_size = (config.width, config.height);
+        let upscaler = (upscale_settings.0 < 1.0).then(|| {
+            log::info!("Rendering at {}x{}, upscaled to {}x{}", render_width, render_height, config.width, config.height);
+            let mut upscaler = Upscaler::new(&device, config.format, (render_width, render_height), display_size);
+            upscaler.sharpness = upscale_settings.1;
+            upscaler
+        });
+        fxaa.enabled = options.fxaa;
+        let post_effects = &scene_data.post_effects;
+        let post_stack = PostStack::new(&device, config.format, post_effects, config.width, config.height);
+        let luminance = LuminanceAnalyzer::new(&device, &hdr_target);
+        let depth_bounds =
+            options.sdsm.then(|| DepthBounds::new(&device, &scene_depth_view, render_width, render_height, depth_mode));
+        let crowd = options.crowd.map(|count| {
+            let frames = options.crowd_frames;
+            CrowdScene::new(&device, &camera_bind_group_layout, HDR_FORMAT, camera.depth_mode, count, frames)
+        });
+
+        let mut state = Self {
+            surface,
+            device,
+            queue,
+            config,
+            size,
+            window,
+            device_lost,
+            assets,
+            pipeline_cache,
+            sky_pipeline,
+            shadow_pipeline,
+            camera,
+            camera_uniform,
+            camera_buffer,
+            camera_bind_group_layout,
+            caster_layout: caster_bind_group_layout,
+            camera_bind_group,
+            shadow_camera_buffers,
+            shadow_camera_bind_groups,
+            input: InputState::new(),
+            camera_motion,
+            fov,
+            picker,
+            selected: None,
+            camera_flight: None,
+            camera_path,
+            camera_path_file: options.camera_path.clone(),
+            path_playback: None,
+            path_capture: options.path_capture.clone(),
+            path_fps: options.path_fps,
+            screenshot_supersample: options.screenshot_supersample,
+            write_exr: options.exr,
+            pick_listeners: Vec::new(),
+            last_frame: Instant::now(),
+            start_time: Instant::now(),
+            meshes,
+            world,
+            mesh_entities,
+            model_roots,
+            camera_entity,
+            geometry_pool,
+            vertex_packing,
+            objects,
+            materials,
+            pipeline_ranks: material_meta
+                .iter()
+                .map(|meta| material_meta.iter().position(|m| m.pipeline_key == meta.pipeline_key).unwrap_or(0))
+                .collect(),
+            material_meta,
+            draw_list: DrawList::default(),
+            light_view_proj,
+            depth_texture,
+            depth_texture_view,
+            scene_depth_texture,
+            scene_depth_view,
+            brdf_lut_view,
+            shadow_texture,
+            shadow_texture_view,
+            shadow_sampler,
+            env_texture,
+            env_texture_view,
+            env_sampler,
+            scene_bounds,
+            animations,
+            animation_player,
+            mesh_inspector,
+            show_mesh_inspector: false,
+            scene_shapes,
+            shapes_camera: None,
+            show_mesh_bounds: false,
+            hud,
+            debug_ui,
+            console: Console::default(),
+            commands: builtin_commands(),
+            texture_inspector,
+            material_debug,
+            bindless,
+            material_layout: material_bind_group_layout,
+            texture_streamer,
+            lods_enabled: true,
+            gpu_timer,
+            frame_stats: FrameStats::default(),
+            hdr_target,
+            tonemapper,
+            fxaa,
+            post_stack,
+            gbuffer,
+            render_scale: upscale_settings.0,
+            debug_view: DebugView::Off,
+            overdraw,
+            upscaler,
+            luminance,
+            depth_bounds,
+            crowd,
+            gpu_culling,
+            lights,
+            flashlight: None,
+            batch: shot_matrix,
+            garbage: GpuGarbage::default(),
+            evsm,
+            shadow_filter,
+            shadow_settings,
+            shadow_bias: options.shadow_bias,
+            sun,
+            sun_clock: None,
+            env_intensity: exposure_settings.1,
+            ev100: exposure_settings.0,
+            auto_exposure: options.auto_exposure,
+            sky,
+            atmosphere,
+            probes,
+            bake_probes: options.probes,
+            reflections,
+            reflections_dirty,
+            ssao,
+            ssgi,
+            fog,
+            ddgi,
+            ddgi_placeholder,
+            vct,
+            vct_placeholder,
+            lightmaps,
+            lightmap_placeholder,
+            rt_shadows,
+            rt_shadow_placeholder,
+            lens_flare,
+            velocity,
+            taa,
+            motion_blur,
+            depth_prepass_pipeline,
+            depth_prepass: options.depth_prepass,
+            surface_caps,
+            latency_settings,
+            latency,
+            frame_limiter: options.fps_cap.map(FrameLimiter::new),
+            bindings,
+            asset_watcher: AssetWatcher::new(),
+            texture_files,
+            scene_data,
+            engine_config,
+            window_mode,
+            viewports: Vec::new(),
+            viewport_depth: None,
+            stereo: None,
+            #[cfg(feature = "openxr")]
+            xr,
+            rebinder: None,
+            fixed_timestep: options.fixed_timestep.map(FixedTimestep::new),
+            occluded: false,
+            minimized: false,
+            paused: false,
+            options,
+        };
+        if state.ddgi.is_some() {
+            state.probes.set_dynamic(&state.queue, true);
+        }
+        if state.ddgi.is_some() || state.vct.is_some() || state.lightmaps.is_some() || state.rt_shadows.is_some() {
+            state.rebuild_camera_bind_group();
+        }
+        if let Some(c) = scene_camera {
+            let fovy = c.fovy.unwrap_or(state.camera.fovy);
+            state.apply_camera(&CameraState {
+                position: c.position,
+                yaw: c.yaw,
+                pitch: c.pitch,
+                fovy,
+            });
+        }
+        if let Some(snapshot) = startup_snapshot {
+            state.restore(&snapshot);
+        }
+        if let Some(size) = window_size {
+            state.set_window_size(size);
+        }
+        if fullscreen {
+            state.window_mode.toggle(&state.window);
+        }
+        state.watch_assets();
+        if let Some(ipd) = stereo {
+            let eyes = EYES.map(|(left, rect)| state.add_viewport(Viewport::eye(&state.camera, ipd, left, rect)));
+            state.stereo = Some((ipd, eyes));
+        }
+        if state.options.top_view {
+            state.add_viewport(Viewport::top_down(&state.scene_bounds, TOP_VIEW_RECT));
+        }
+        if state.options.play_path {
+            state.toggle_path_playback();
+        }
+        state.on_pick(|hit| {
+            log::info!(
+                "Picked '{}' (mesh {}, material {}) at {:.2} m, ({:.2}, {:.2}, {:.2})",
+                hit.mesh_name,
+                hit.mesh,
+                hit.material,
+                hit.distance,
+                hit.position.x,
+                hit.position.y,
+                hit.position.z
+            )
+        });
+        for filter in ["Validation", "Out of memory"] {
+            if let Some(error) = state.device.pop_error_scope().await {
+                anyhow::bail!("Creating GPU resources failed ({}): {}", filter.to_lowercase(), error);
+            }
+        }
+        Ok(state)
+    }
+
+    /// Whether the GPU device was lost; [`Engine::recover`](crate::Engine::recover) gets a new one.
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Relaxed)
+    }
+
+    /// Builds the renderer again on a new device from the same options and the scene,
+    /// config and files it holds in memory, then restores the session as a snapshot holds
+    /// it. Used after a device loss.
+    pub async fn rebuild(mut self) -> Result<Self> {
+        let _span = profiler::scope("rebuild renderer");
+        let (window, options, snapshot) = (self.window.clone(), self.options.clone(), self.snapshot());
+        let window_mode = std::mem::replace(&mut self.window_mode, WindowMode::new(FullscreenMode::default(), None));
+        // The old surface goes first, since a window can only have one at a time.
+        let sources = self.into_sources();
+        let display = Display::open(&window, &options).await?;
+        let mut renderer = Self::create(window, display, sources, options).await?;
+        renderer.restore(&snapshot);
+        renderer.window_mode = window_mode;
+        log::info!("Recreated every GPU resource");
+        Ok(renderer)
+    }
+
+    /// What [`Renderer::create`] needs to build this renderer again; the GPU resources
+    /// are dropped.
+    fn into_sources(self) -> Sources {
+        Sources {
+            engine_config: self.engine_config,
+            assets: self.assets,
+            scene: self.scene_data,
+            camera_path: self.camera_path,
+            bindings: self.bindings,
+            batch: self.batch,
+            snapshot: None,
+        }
+    }
+
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+
+    pub fn size(&self) -> winit::dpi::PhysicalSize<u32> {
+        self.size
+    }
+
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    /// Format of the window's surface, which plugin passes draw to.
+    pub fn surface_format(&self) -> wgpu::TextureFormat {
+        self.config.format
+    }
+
+    /// Resizes the surface and every screen-sized target; a zero size pauses rendering.
+    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        self.minimized = new_size.width == 0 || new_size.height == 0;
+        if new_size.width > 0 && new_size.height > 0 {
+            self.size = new_size;
+            self.config.width = new_size.width;
+            self.config.height = new_size.height;
+            self.surface.configure(&self.device, &self.config);
+            
+            self.camera.update_aspect(new_size.width, new_size.height);
+            
+            let (width, height) = self.render_size();
+            let depth_texture = create_depth_texture(&self.device, width, height, "Depth Texture", DEPTH_USAGE);
+            self.garbage.defer(std::mem::replace(&mut self.depth_texture, depth_texture));
+            self.depth_texture_view = self.depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let scene_depth_texture =
+                create_depth_texture(&self.device, width, height, "Scene Depth Copy", SCENE_DEPTH_USAGE);
+            self.garbage.defer(std::mem::replace(&mut self.scene_depth_texture, scene_depth_texture));
+            self.scene_depth_view = self.scene_depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+            self.lens_flare.set_depth_view(&self.device, &self.scene_depth_view);
+            if let Some(bounds) = &mut self.depth_bounds {
+                bounds.resize(&self.device, &self.scene_depth_view, width, height);
+            }
+            let old_ao = self.ssao.resize(&self.device, &self.camera_buffer, &self.scene_depth_view, width, height);
+            let old_gi = self.ssgi.resize(&self.device, &self.camera_buffer, &self.scene_depth_view, width, height);
+            let old_rt = self.rt_shadows.as_mut().map(|rt| {
+                rt.resize(&self.device, &self.camera_buffer, &self.scene_depth_view, width, height)
+            });
+            let old_velocity =
+                self.velocity.resize(&self.device, &self.camera_buffer, &self.scene_depth_view, width, height);
+            let views = (&self.scene_depth_view, self.velocity.view());
+            let old_taa = self.taa.resize(&self.device, views, width, height);
+            let old_blur = self.motion_blur.resize(&self.device, &self.camera_buffer, views, width, height);
+            let old_post = old_taa.into_iter().chain([old_velocity, old_blur]);
+            for texture in old_ao.into_iter().chain(old_gi).chain(old_rt.into_iter().flatten()).chain(old_post) {
+                self.garbage.defer(texture);
+            }
+            if self.viewport_depth.is_some() {
+                let depth = self.create_viewport_depth();
+                if let Some((texture, _)) = self.viewport_depth.replace(depth) {
+                    self.garbage.defer(texture);
+                }
+            }
+            self.rebuild_camera_bind_group();
+            let hdr_target = HdrTarget::new(&self.device, width, height);
+            self.garbage.defer(std::mem::replace(&mut self.hdr_target, hdr_target).texture);
+            self.tonemapper.resize(&self.device, &self.hdr_target);
+            self.luminance.resize(&self.device, &self.hdr_target);
+            let old_fxaa = self.fxaa.resize(&self.device, width, height);
+            let display_size = (self.config.width, self.config.height);
+            let old_post = self.post_stack.resize(&self.device, display_size.0, display_size.1);
+            let old_upscale = self.upscaler.as_mut().map(|u| u.resize(&self.device, (width, height), display_size));
+            let old_overdraw = self.overdraw.resize(&self.device, width, height);
+            let old_ldr = [old_fxaa, old_overdraw];
+            for texture in old_post.into_iter().chain(old_ldr).chain(old_upscale.into_iter().flatten()) {
+                self.garbage.defer(texture);
+            }
+            if let Some(gbuffer) = &mut self.gbuffer {
+                for texture in gbuffer.resize(&self.device, &self.depth_texture_view, width, height) {
+                    self.garbage.defer(texture);
+                }
+            }
+        }
+    }
+    
+    /// Hands a window event to the engine's own controls: the egui windows, key
+    /// rebinding, the key bindings, picking and fly-camera input. True if one of them used
+    /// it.
+    pub fn on_window_event(&mut self, event: &WindowEvent) -> bool {
+        // The console key reaches the bindings even while the console takes the keyboard.
+        let console_key = matches!(
+            event,
+            WindowEvent::KeyboardInput { event: KeyEvent { physical_key: PhysicalKey::Code(code), .. }, .. }
+                if self.bindings.action(*code) == Some(Action::Console)
+        );
+        let egui_shown = self.debug_ui.visible || self.console.open || self.show_mesh_inspector;
+        if egui_shown && !console_key && self.debug_ui.on_window_event(&self.window, event) {
+            return true;
+        }
+        if let WindowEvent::KeyboardInput {
+            event:
+                KeyEvent {
+                    state: ElementState::Pressed,
+                    physical_key: PhysicalKey::Code(code),
+                    repeat: false,
+                    ..
+                },
+            ..
+        } = event
+        {
+            if let Some(rebinder) = &mut self.rebinder {
+                if !rebinder.key(*code, &mut self.bindings) {
+                    self.rebinder = None;
+                }
+                self.refresh_title();
+                return true;
+            }
+            if let Some(action) = self.bindings.action(*code) {
+                self.on_action(action);
+            }
+        }
+        if let WindowEvent::MouseInput {
+            state: ElementState::Pressed,
+            button: MouseButton::Right,
+            ..
+        } = event
+        {
+            self.pick();
+            return true;
+        }
+        if self.rebinder.is_some() && matches!(event, WindowEvent::KeyboardInput { .. }) {
+            return true;
+        }
+
+        let used = self.input.on_window_event(event, &self.bindings);
+        if self.input.mouse_captured {
+            let _ = self.window.set_cursor_grab(CursorGrabMode::Locked);
+            self.window.set_cursor_visible(false);
+        }
+        used
+    }
+
+    /// Turns the camera by raw mouse motion while mouse look has the cursor.
+    pub fn on_mouse_motion(&mut self, delta: (f64, f64)) {
+        self.input.on_mouse_motion(delta);
+    }
+
+    /// Shows the cursor again and stops mouse look, for clicking into the debug UI.
+    fn release_cursor(&mut self) {
+        self.input.mouse_captured = false;
+        let _ = self.window.set_cursor_grab(CursorGrabMode::None);
+        self.window.set_cursor_visible(true);
+    }
+
+    /// The console commands, for registering more.
+    pub fn commands_mut(&mut self) -> &mut CommandRegistry {
+        &mut self.commands
+    }
+
+    /// Runs a console command line and prints it and its output to the console.
+    pub fn run_command(&mut self, line: &str) {
+        self.console.print(&format!("> {}", line));
+        let Some((name, args)) = console::split_command(line) else {
+            return;
+        };
+        let output = match self.commands.get(name) {
+            Some(run) => run(self, &args),
+            None => Err(anyhow::anyhow!("Unknown command '{}'; 'help' lists them", name)),
+        };
+        match output {
+            Ok(text) => self.console.print(&text),
+            Err(e) => self.console.print(&format!("{:#}", e)),
+        }
+    }
+
+    /// Calls `listener` with every pick that hits a mesh.
+    pub fn on_pick(&mut self, listener: impl FnMut(&PickHit) + 'static) {
+        self.pick_listeners.push(Box::new(listener));
+    }
+
+    /// Picks the mesh under the cursor, or under the screen center while mouse look has
+    /// the cursor, and tells the pick listeners.
+    fn pick(&mut self) {
+        let (width, height) = (self.size.width as f32, self.size.height as f32);
+        let (x, y) = match self.input.cursor.filter(|_| !self.input.mouse_captured) {
+            Some((x, y)) => (x / width * 2.0 - 1.0, 1.0 - y / height * 2.0),
+            None => (0.0, 0.0),
+        };
+        let dir = self.camera.screen_ray(x, y);
+        let meshes = &self.meshes;
+        let Some((mesh, distance)) = self.picker.pick(self.camera.position, dir, |i| meshes[i].drawn) else {
+            self.selected = None;
+            self.hud.notify("NOTHING PICKED".to_string());
+            return;
+        };
+        self.selected = Some(mesh);
+        let hit = PickHit {
+            entity: self.mesh_entities[mesh],
+            mesh,
+            mesh_name: self.meshes[mesh].name.clone(),
+            material: self.meshes[mesh].material_index,
+            distance,
+            position: self.camera.position + dir * distance,
+        };
+        self.hud.notify(format!("{}  MATERIAL {}", hit.mesh_name, hit.material));
+        for listener in &mut self.pick_listeners {
+            listener(&hit);
+        }
+    }
+
+    fn on_action(&mut self, action: Action) {
+        let player = &mut self.animation_player;
+        match action {
+            Action::FovDown | Action::FovUp => {
+                let base = self.fov.step(if action == Action::FovUp { 1.0 } else { -1.0 });
+                self.hud.notify(format!("FOV {:.0}", base));
+            }
+            Action::FrameScene => {
+                let bounds = match self.selected {
+                    Some(mesh) => self.meshes[mesh].bounds,
+                    None => self.scene_bounds,
+                };
+                let to = self.camera.focus_position(&bounds);
+                self.camera_flight = Some(CameraFlight::new(self.camera.position, to));
+                self.camera_motion.stop();
+            }
+            Action::MeshInspector => {
+                self.show_mesh_inspector = !self.show_mesh_inspector;
+                if self.show_mesh_inspector {
+                    self.release_cursor();
+                }
+            }
+            Action::SceneShapes => {
+                self.shapes_camera = match self.shapes_camera {
+                    Some(_) => None,
+                    None => Some(self.camera.clone()),
+                };
+                log::info!("Scene shapes: {}", if self.shapes_camera.is_some() { "on" } else { "off" });
+            }
+            Action::MeshBounds => {
+                self.show_mesh_bounds = !self.show_mesh_bounds;
+                log::info!("Mesh bounds: {}", if self.show_mesh_bounds { "on" } else { "off" });
+            }
+            Action::ToggleUi => self.hud.visible = !self.hud.visible,
+            Action::SettingsPanel => {
+                self.debug_ui.visible = !self.debug_ui.visible;
+                if self.debug_ui.visible {
+                    self.release_cursor();
+                }
+            }
+            Action::Console => {
+                self.console.open = !self.console.open;
+                if self.console.open {
+                    self.release_cursor();
+                }
+            }
+            Action::InspectorMode
+            | Action::InspectorPreviousMaterial
+            | Action::InspectorNextMaterial
+            | Action::InspectorSlot
+            | Action::InspectorChannel => {
+                let inspector = &mut self.texture_inspector;
+                match action {
+                    Action::InspectorMode => inspector.cycle_mode(),
+                    Action::InspectorPreviousMaterial => inspector.step_material(-1, self.materials.len()),
+                    Action::InspectorNextMaterial => inspector.step_material(1, self.materials.len()),
+                    Action::InspectorSlot => inspector.next_slot(),
+                    _ => inspector.next_channel(),
+                }
+                if inspector.mode == InspectorMode::Off {
+                    log::info!("Texture inspector: off");
+                } else {
+                    log::info!("Texture inspector {}", inspector.describe(&self.materials));
+                }
+            }
+            Action::MaterialOverride | Action::MaterialOverrideNext => {
+                if action == Action::MaterialOverride {
+                    self.material_debug.cycle_mode();
+                } else {
+                    self.material_debug.next_material(self.materials.len());
+                }
+                log::info!("Material override: {:?}", self.material_debug.mode);
+            }
+            Action::Lods => {
+                self.lods_enabled = !self.lods_enabled;
+                log::info!("Mesh LODs: {}", if self.lods_enabled { "on" } else { "off" });
+            }
+            Action::Profiler => {
+                self.hud.show_profiler = !self.hud.show_profiler;
+                // A trace from --profile-trace keeps recording with the view closed.
+                profiler::set_enabled(self.hud.show_profiler || profiler::is_tracing());
+            }
+            Action::LogLuminance => self.log_luminance(),
+            Action::LowLatency => self.toggle_low_latency(),
+            Action::PresentMode => self.cycle_present_mode(),
+            Action::Rebind => {
+                self.input.release_keys();
+                self.rebinder = Some(Rebinder::new());
+                self.refresh_title();
+            }
+            Action::Flashlight => self.toggle_flashlight(),
+            Action::ShadowFilter => {
+                self.shadow_filter = match self.shadow_filter {
+                    ShadowFilter::Pcf => ShadowFilter::Evsm,
+                    ShadowFilter::Evsm => ShadowFilter::Pcf,
+                };
+                if self.shadow_filter == ShadowFilter::Evsm {
+                    for texture in self.evsm.ensure_maps(&self.device, &self.shadow_settings) {
+                        self.garbage.defer(texture);
+                    }
+                }
+                log::info!("Shadow filter: {:?}", self.shadow_filter);
+            }
+            Action::ShadowQuality => self.set_shadow_settings(self.shadow_settings.next_preset()),
+            Action::SunLeft => self.sun.nudge(-5.0, 0.0),
+            Action::SunRight => self.sun.nudge(5.0, 0.0),
+            Action::SunUp => self.sun.nudge(0.0, 5.0),
+            Action::SunDown => self.sun.nudge(0.0, -5.0),
+            Action::TimeOfDay => {
+                self.sun.toggle_animation();
+                log::info!("Time of day {}", if self.sun.animate { "running" } else { "paused" });
+            }
+            Action::BakeProbes => self.bake_probes = true,
+            Action::ReflectionProbe => {
+                if self.reflections.add(ReflectionProbe::new(self.camera.position, self.scene_bounds)) {
+                    log::info!("Placed reflection probe {} at the camera", self.reflections.probes().len());
+                    self.reflections_dirty = true;
+                } else {
+                    log::warn!("All {} reflection probes are in use", reflections::MAX_REFLECTION_PROBES);
+                }
+            }
+            Action::Ssao => {
+                self.ssao.enabled = !self.ssao.enabled;
+                log::info!("Ambient occlusion {}", if self.ssao.enabled { "on" } else { "off" });
+            }
+            Action::Ssgi => {
+                self.ssgi.set_enabled(!self.ssgi.enabled);
+                log::info!("Screen-space GI {}", if self.ssgi.enabled { "on" } else { "off" });
+            }
+            Action::DynamicGi => match &mut self.ddgi {
+                Some(ddgi) => {
+                    ddgi.enabled = !ddgi.enabled;
+                    self.probes.set_dynamic(&self.queue, ddgi.enabled);
+                    log::info!("Dynamic GI {}", if ddgi.enabled { "on" } else { "off" });
+                }
+                None => log::warn!("Dynamic GI needs the scene BVH; start with --ddgi"),
+            },
+            Action::RtShadows => match &mut self.rt_shadows {
+                Some(rt) => {
+                    rt.enabled = !rt.enabled;
+                    log::info!("Ray-traced shadows {}", if rt.enabled { "on" } else { "off" });
+                }
+                None => log::warn!("Ray-traced shadows need the scene BVH; start with --rt-shadows"),
+            },
+            Action::MotionBlur => {
+                self.motion_blur.enabled = !self.motion_blur.enabled;
+                self.velocity.reset();
+                log::info!(
+                    "Motion blur {} ({:.0}° shutter)",
+                    if self.motion_blur.enabled { "on" } else { "off" },
+                    self.motion_blur.shutter_angle
+                );
+            }
+            Action::Fullscreen => self.window_mode.toggle(&self.window),
+            Action::Taa => {
+                self.taa.enabled = !self.taa.enabled;
+                self.taa.reset();
+                self.velocity.reset();
+                log::info!("TAA {}", if self.taa.enabled { "on" } else { "off" });
+            }
+            Action::DebugView => {
+                self.debug_view = self.debug_view.next();
+                log::info!("Debug view: {:?}", self.debug_view);
+            }
+            Action::Fxaa => {
+                self.fxaa.enabled = !self.fxaa.enabled;
+                log::info!("FXAA {}", if self.fxaa.enabled { "on" } else { "off" });
+            }
+            Action::LensFlare => {
+                self.lens_flare.enabled = !self.lens_flare.enabled;
+                log::info!("Lens flare {}", if self.lens_flare.enabled { "on" } else { "off" });
+            }
+            Action::Lightmaps => match &mut self.lightmaps {
+                Some(lightmaps) => {
+                    lightmaps.enabled = !lightmaps.enabled;
+                    log::info!("Lightmaps {}", if lightmaps.enabled { "on" } else { "off" });
+                }
+                None => log::warn!("No lightmaps; start with --lightmaps"),
+            },
+            Action::VoxelGi => match &mut self.vct {
+                Some(vct) => {
+                    vct.enabled = !vct.enabled;
+                    log::info!("Voxel GI {}", if vct.enabled { "on" } else { "off" });
+                }
+                None => log::warn!("Voxel GI needs the voxelized scene; start with --vct"),
+            },
+            Action::Fog => {
+                self.fog.enabled = !self.fog.enabled;
+                log::info!("Volumetric fog {}", if self.fog.enabled { "on" } else { "off" });
+            }
+            Action::VoxelDebug => {
+                if let Some(vct) = &mut self.vct {
+                    vct.debug_view = !vct.debug_view;
+                    log::info!("Voxel debug view {}", if vct.debug_view { "on" } else { "off" });
+                }
+            }
+            Action::LightProbes if self.probes.is_baked() => {
+                self.probes.set_enabled(&self.queue, !self.probes.is_enabled());
+                log::info!("Light probes {}", if self.probes.is_enabled() { "on" } else { "off" });
+            }
+            Action::ExposureDown | Action::ExposureUp => {
+                let step = if action == Action::ExposureDown { -0.5 } else { 0.5 };
+                if self.auto_exposure.enabled {
+                    self.auto_exposure.compensation += step;
+                    log::info!("Exposure compensation {:+.1} EV", self.auto_exposure.compensation);
+                } else {
+                    self.ev100 += step;
+                    log::info!("EV100 {:.1}", self.ev100);
+                }
+            }
+            Action::AutoExposure => {
+                self.auto_exposure.enabled = !self.auto_exposure.enabled;
+                log::info!(
+                    "Auto exposure {} (EV100 {:.1})",
+                    if self.auto_exposure.enabled { "on" } else { "off" },
+                    self.ev100
+                );
+            }
+            Action::Screenshot => match self.screenshot(self.screenshot_supersample) {
+                Ok(path) => {
+                    log::info!("Saved screenshot to {}", path.display());
+                    self.hud.notify("SCREENSHOT SAVED".to_string());
+                }
+                Err(e) => log::warn!("{:#}", e),
+            },
+            Action::SaveSnapshot => {
+                let path = Path::new(snapshot::DEFAULT_SNAPSHOT_PATH);
+                match self.snapshot().save(path) {
+                    Ok(()) => log::info!("Saved snapshot to {}", path.display()),
+                    Err(e) => log::warn!("{:#}", e),
+                }
+            }
+            Action::LoadSnapshot => match Snapshot::load(Path::new(snapshot::DEFAULT_SNAPSHOT_PATH)) {
+                Ok(snapshot) => self.restore(&snapshot),
+                Err(e) => log::warn!("{:#}", e),
+            },
+            Action::PathKey => {
+                self.camera_path.keys.push(self.snapshot().camera);
+                self.save_camera_path();
+                self.hud.notify(format!("PATH KEY {}", self.camera_path.keys.len()));
+            }
+            Action::PlayPath => self.toggle_path_playback(),
+            Action::ClearPath => {
+                self.path_playback = None;
+                self.camera_path.keys.clear();
+                self.save_camera_path();
+                self.hud.notify("PATH CLEARED".to_string());
+            }
+            Action::PlayAnimation => {
+                player.playing = !player.playing;
+                player.dirty = true;
+            }
+            Action::PreviousClip => player.select(-1),
+            Action::NextClip => player.select(1),
+            Action::ScrubBack => player.scrub(-1.0 / 30.0, &self.animations),
+            Action::ScrubForward => player.scrub(1.0 / 30.0, &self.animations),
+            Action::SlowerAnimation => {
+                player.speed = (player.speed * 0.5).max(0.125);
+                player.dirty = true;
+            }
+            Action::FasterAnimation => {
+                player.speed = (player.speed * 2.0).min(8.0);
+                player.dirty = true;
+            }
+            Action::LoopAnimation => {
+                player.looping = !player.looping;
+                player.dirty = true;
+            }
+            Action::BoneWeights => {
+                player.show_weights = !player.show_weights;
+                self.upload_bone_weights();
+            }
+            Action::NextJoint | Action::PreviousJoint => {
+                let joints = self.animations.iter().map(|a| a.joint_count()).max().unwrap_or(0);
+                if joints > 0 {
+                    let step = if action == Action::NextJoint { 1 } else { joints - 1 };
+                    player.weight_joint = (player.weight_joint + step) % joints;
+                    self.upload_bone_weights();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn scene_luminance(&self) -> Option<&LuminanceStats> {
+        self.luminance.latest()
+    }
+
+    fn log_luminance(&self) {
+        let Some(stats) = self.scene_luminance() else {
+            log::info!("Scene luminance not available yet");
+            return;
+        };
+        log::info!(
+            "Scene luminance: min {:.4} avg {:.4} max {:.2} (median {:.4}, 95% {:.2})",
+            stats.min,
+            stats.average,
+            stats.max,
+            stats.percentile(0.5),
+            stats.percentile(0.95),
+        );
+        if let Some(ev100) = self.auto_exposure.target_ev100(stats) {
+            log::info!("Metered EV100 {:.1} (camera at {:.1})", ev100, self.ev100);
+        }
+        let peak = stats.histogram.iter().copied().max().unwrap_or(0).max(1);
+        for (bin, &count) in stats.histogram.iter().enumerate() {
+            if count > 0 {
+                log::info!(
+                    "  {:>10.4} {}",
+                    LuminanceStats::bin_luminance(bin),
+                    "#".repeat((count as u64 * 40 / peak as u64).max(1) as usize)
+                );
+            }
+        }
+    }
+
+    /// The scene graph from each model's root, with the meshes hanging from each node,
+    /// and the meshes baked into world space under a node of their own.
+    fn inspector_nodes(&self) -> Vec<InspectorNode> {
+        let children = self.world.children();
+        let mut nodes: Vec<InspectorNode> = self
+            .world
+            .query::<LocalTransform>()
+            .filter(|(entity, _)| self.world.get::<Parent>(*entity).is_none())
+            .map(|(entity, _)| self.inspector_node(entity, &children))
+            .collect();
+        let baked: Vec<InspectorMesh> = self
+            .world
+            .query::<MeshHandle>()
+            .filter(|(entity, _)| self.world.get::<Parent>(*entity).is_none())
+            .map(|(_, &MeshHandle(mesh))| self.inspector_mesh(mesh))
+            .collect();
+        if !baked.is_empty() {
+            nodes.push(InspectorNode {
+                name: "Baked into world space".to_string(),
+                meshes: baked,
+                children: Vec::new(),
+            });
+        }
+        nodes
+    }
+
+    fn inspector_node(&self, entity: Entity, children: &HashMap<Entity, Vec<Entity>>) -> InspectorNode {
+        let mut node = InspectorNode {
+            name: self.world.get::<Name>(entity).map_or_else(|| "(unnamed node)".to_string(), |n| n.0.clone()),
+            meshes: Vec::new(),
+            children: Vec::new(),
+        };
+        for &child in children.get(&entity).into_iter().flatten() {
+            match self.world.get::<MeshHandle>(child) {
+                Some(&MeshHandle(mesh)) => node.meshes.push(self.inspector_mesh(mesh)),
+                None => node.children.push(self.inspector_node(child, children)),
+            }
+        }
+        node
+    }
+
+    fn inspector_mesh(&self, index: usize) -> InspectorMesh {
+        let mesh = &self.meshes[index];
+        InspectorMesh {
+            index,
+            name: mesh.name.clone(),
+            stats: mesh.stats,
+            shadow: match (mesh.shadow, &mesh.shadow_proxy) {
+                (ShadowRole::Proxy, _) => "shadow proxy",
+                (ShadowRole::NonCaster, _) => "no shadow",
+                (ShadowRole::Caster, Some(_)) => "auto proxy",
+                (ShadowRole::Caster, None) => "",
+            },
+        }
+    }
+
+    fn upload_bone_weights(&mut self) {
+        let joint = self.animation_player.weight_joint;
+        for anim in &self.animations {
+            for animated in &anim.meshes {
+                if let Some(buffer) = &self.meshes[animated.scene_mesh].bone_weight_buffer {
+                    let weights = animated.bone_weights(joint);
+                    self.queue.write_buffer(buffer, 0, bytemuck::cast_slice(&weights));
+                }
+            }
+        }
+        self.animation_player.dirty = true;
+    }
+
+    fn update_animation(&mut self, dt: f32) {
+        if self.animation_player.is_empty() {
+            return;
+        }
+        let _span = profiler::scope("animation");
+        self.animation_player.advance(dt, &self.animations);
+        if !self.animation_player.dirty {
+            return;
+        }
+        self.animation_player.dirty = false;
+
+        if let Some(&(model, clip)) = self.animation_player.clips.get(self.animation_player.current) {
+            for posed in self.animations[model].pose(clip, self.animation_player.time) {
+                if posed.vertices.is_empty() {
+                    self.world.insert(self.mesh_entities[posed.scene_mesh], Transform(posed.transform));
+                    continue;
+                }
+                // Skinned meshes always have buffers of their own.
+                let MeshGeometry::Own(buffers) = &self.meshes[posed.scene_mesh].geometry else {
+                    continue;
+                };
+                self.queue.write_buffer(&buffers.vertices, 0, &self.vertex_packing.encode(&posed.vertices));
+                if let (Some(buffer), false) = (&buffers.tangents, posed.tangents.is_empty()) {
+                    self.queue.write_buffer(buffer, 0, bytemuck::cast_slice(&posed.tangents));
+                }
+            }
+        }
+    }
+
+    fn refresh_title(&self) {
+        let mut title = String::from("Dusk Engine");
+        if let Some(clock) = &self.sun_clock {
+            title += &format!(" | {}", clock);
+        }
+        if let Some(latency) = self.latency.label(&self.latency_settings) {
+            title += &format!(" | {}", latency);
+        }
+        if let Some(rebinder) = &self.rebinder {
+            title += &format!(" | {}", rebinder.prompt(&self.bindings));
+        }
+        self.window.set_title(&title);
+    }
+
+    fn toggle_low_latency(&mut self) {
+        let present_mode = self.latency_settings.present_mode;
+        self.latency_settings = if self.latency_settings.is_low_latency() {
+            LatencySettings::default()
+        } else {
+            LatencySettings::low_latency()
+        };
+        self.latency_settings.present_mode = present_mode;
+        self.latency_settings.apply(&mut self.config, &self.surface_caps);
+        self.surface.configure(&self.device, &self.config);
+        crash::set_context("latency", format!("{:?}", self.latency_settings));
+        log::info!(
+            "Low latency {} (frame latency {}, {:?})",
+            if self.latency_settings.is_low_latency() { "on" } else { "off" },
+            self.config.desired_maximum_frame_latency,
+            self.config.present_mode
+        );
+    }
+
+    fn cycle_present_mode(&mut self) {
+        let mode = latency::next_present_mode(self.config.present_mode, &self.surface_caps);
+        self.latency_settings.present_mode = Some(mode);
+        self.latency_settings.apply(&mut self.config, &self.surface_caps);
+        self.surface.configure(&self.device, &self.config);
+        crash::set_context("latency", format!("{:?}", self.latency_settings));
+        log::info!("Present mode: {:?}", self.config.present_mode);
+    }
+
+    /// Asks for a new inner window size; some platforms apply it at once, without a
+    /// resize event. While fullscreen, the size waits for the window to leave it.
+    fn set_window_size(&mut self, (width, height): (u32, u32)) {
+        let size = winit::dpi::PhysicalSize::new(width, height);
+        if self.window_mode.defer_windowed_size(size) {
+            return;
+        }
+        if let Some(size) = self.window.request_inner_size(size) {
+            self.resize(size);
+        }
+    }
+
+    /// Applies what changed in the engine config file since it was last read: the window
+    /// size and fullscreen mode, vsync, shadows, key bindings and fly controls change
+    /// live, while the render scale, default scene and camera clip planes and field of
+    /// view wait for a restart. Settings given on the command line keep winning over the
+    /// file, as they do at startup.
+    fn reload_config(&mut self, config: EngineConfig) {
+        let old = std::mem::replace(&mut self.engine_config, config.clone());
+        if config == old {
+            return;
+        }
+        log::info!("Reloading the engine config");
+        let (old, config) = (old.under(&self.options), config.under(&self.options));
+        if let Some(size) = config.window_size.filter(|_| config.window_size != old.window_size) {
+            self.set_window_size(size);
+        }
+        if (config.fullscreen_mode, config.monitor) != (old.fullscreen_mode, old.monitor) {
+            self.window_mode.set(&self.window, config.fullscreen_mode.unwrap_or_default(), config.monitor);
+        }
+        let fullscreen = config.fullscreen.unwrap_or(false);
+        if config.fullscreen != old.fullscreen && fullscreen != self.window_mode.is_fullscreen() {
+            self.window_mode.toggle(&self.window);
+        }
+        if config.vsync != old.vsync {
+            let caps = &self.surface_caps;
+            self.latency_settings.present_mode = match config.vsync {
+                Some(vsync) => Some(latency::vsync_present_mode(vsync, caps)),
+                None => self.options.latency.present_mode,
+            };
+            self.latency_settings.apply(&mut self.config, &self.surface_caps);
+            self.surface.configure(&self.device, &self.config);
+            log::info!("Present mode: {:?}", self.config.present_mode);
+        }
+        if (config.shadow_resolution, config.shadow_cascades) != (old.shadow_resolution, old.shadow_cascades) {
+            let defaults = ShadowSettings::default();
+            self.set_shadow_settings(ShadowSettings {
+                resolution: config.shadow_resolution.unwrap_or(defaults.resolution),
+                cascades: config.shadow_cascades.unwrap_or(defaults.cascades),
+            });
+        }
+        let filter = config.shadow_filter.unwrap_or(ShadowFilter::Pcf);
+        if config.shadow_filter != old.shadow_filter && filter != self.shadow_filter {
+            self.on_action(Action::ShadowFilter);
+        }
+        if config.bindings != old.bindings && self.rebinder.is_none() {
+            self.bindings.reload(config.bindings.as_ref());
+        }
+        if config.camera != old.camera {
+            let (settings, motion) = (&config.camera, &mut self.camera_motion);
+            motion.move_speed = settings.move_speed;
+            motion.sprint_speed = settings.sprint_speed;
+            motion.mouse_sensitivity = settings.mouse_sensitivity;
+            motion.acceleration = settings.acceleration;
+            motion.deceleration = settings.deceleration;
+            motion.look_smoothing = settings.look_smoothing;
+        }
+        if config.render_scale != old.render_scale || config.scene != old.scene {
+            log::info!("Render scale and default scene changes apply on the next start");
+        }
+    }
+
+    /// Recreates the cascade maps, and the EVSM maps if they exist, for new settings.
+    fn set_shadow_settings(&mut self, settings: ShadowSettings) {
+        let settings = settings.sanitized(&self.device.limits());
+        if settings == self.shadow_settings {
+            return;
+        }
+        self.shadow_settings = settings;
+        let shadow_texture = settings.create_texture(&self.device);
+        self.shadow_texture_view = shadows::array_view(&shadow_texture);
+        self.garbage.defer(std::mem::replace(&mut self.shadow_texture, shadow_texture));
+        self.rebuild_camera_bind_group();
+        if self.evsm.is_allocated() {
+            for texture in self.evsm.ensure_maps(&self.device, &settings) {
+                self.garbage.defer(texture);
+            }
+        }
+        crash::set_context("shadows", format!("{:?}", settings));
+        log::info!("Shadows: {} cascade(s) at {}²", settings.cascades, settings.resolution);
+    }
+
+    fn save_camera_path(&self) {
+        if let Err(e) = self.camera_path.save(&self.camera_path_file) {
+            log::warn!("{:#}", e);
+        }
+    }
+
+    fn toggle_path_playback(&mut self) {
+        if self.path_playback.take().is_some() {
+            log::info!("Camera path stopped");
+            return;
+        }
+        if self.camera_path.keys.len() < 2 {
+            log::warn!("The camera path needs at least two keys to play");
+            return;
+        }
+        self.camera_flight = None;
+        self.camera_motion.stop();
+        self.path_playback = Some(PathPlayback::default());
+        log::info!(
+            "Playing camera path: {} keys over {:.1} s",
+            self.camera_path.keys.len(),
+            self.camera_path.duration
+        );
+    }
+
+    /// Writes the frame just updated to the capture directory while a captured path plays.
+    pub fn capture_path_frame(&mut self) {
+        let (Some(dir), Some(playback)) = (&self.path_capture, &mut self.path_playback) else {
+            return;
+        };
+        let path = dir.join(format!("frame_{:05}.png", playback.frame));
+        playback.frame += 1;
+        if let Err(e) = self.capture(&path) {
+            log::warn!("{:#}", e);
+            self.path_playback = None;
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let player = &self.animation_player;
+        let flashlight = self.flashlight.and_then(|entity| self.world.get::<Light>(entity)).map(|&Light(id)| id);
+        Snapshot {
+            camera: CameraState {
+                position: self.camera.position,
+                yaw: self.camera.yaw,
+                pitch: self.camera.pitch,
+                fovy: self.fov.base,
+            },
+            instances: self.scene_data.loaded.instances.clone(),
+            directional_lights: self.lights.directional().to_vec(),
+            env_intensity: self.env_intensity,
+            ev100: self.ev100,
+            spot_lights: self
+                .lights
+                .spot_lights()
+                .filter(|(id, _)| Some(*id) != flashlight)
+                .map(|(_, l)| *l)
+                .collect(),
+            flashlight: self.flashlight.is_some(),
+            animation: (!player.is_empty()).then_some(AnimationState {
+                clip: player.current,
+                time: player.time,
+                speed: player.speed,
+                looping: player.looping,
+                playing: player.playing,
+            }),
+            latency: self.latency_settings,
+        }
+    }
+
+    /// Applies a snapshot to the running scene. Model instances are baked into the
+    /// loaded geometry, so a different set of instances only gets a warning; start with
+    /// `--snapshot=` to load them.
+    fn restore(&mut self, snapshot: &Snapshot) {
+        let same_instances = snapshot.instances.len() == self.scene_data.loaded.instances.len()
+            && snapshot
+                .instances
+                .iter()
+                .zip(&self.scene_data.loaded.instances)
+                .all(|(a, b)| a.path == b.path && a.transform == b.transform);
+        if !same_instances {
+            log::warn!("Snapshot was taken with different model instances; restart with --snapshot= to restore them");
+        }
+
+        self.apply_camera(&snapshot.camera);
+
+        self.lights.set_directional(&snapshot.directional_lights);
+        self.sun = Sun::from_light(&self.lights.directional()[0]);
+        self.env_intensity = snapshot.env_intensity;
+        self.ev100 = snapshot.ev100;
+
+        // Every spot light goes, so no entity may keep pointing at one.
+        let lit: Vec<Entity> = self.world.query::<Light>().map(|(entity, _)| entity).collect();
+        for entity in lit {
+            self.world.remove::<Light>(entity);
+        }
+        if let Some(entity) = self.flashlight.take() {
+            self.world.despawn(entity);
+        }
+        self.world.take_despawned();
+        self.lights.clear_spots();
+        for light in &snapshot.spot_lights {
+            self.lights.add_spot(*light);
+        }
+        if snapshot.flashlight {
+            self.toggle_flashlight();
+        }
+
+        let player = &mut self.animation_player;
+        if let Some(a) = snapshot.animation.filter(|a| a.clip < player.clips.len()) {
+            player.current = a.clip;
+            player.time = a.time;
+            player.speed = a.speed;
+            player.looping = a.looping;
+            player.playing = a.playing;
+            player.dirty = true;
+        }
+
+        self.latency_settings = snapshot.latency;
+        self.latency_settings.apply(&mut self.config, &self.surface_caps);
+        self.surface.configure(&self.device, &self.config);
+        log::info!("Restored snapshot");
+    }
+
+    fn apply_camera(&mut self, c: &CameraState) {
+        self.camera.position = c.position;
+        self.camera.yaw = c.yaw;
+        self.camera.pitch = c.pitch;
+        self.camera.fovy = c.fovy;
+        self.fov.reset(c.fovy);
+        self.camera_flight = None;
+        self.camera.target = self.camera.position + self.camera.forward();
+        self.camera_motion.stop();
+        self.velocity.reset();
+        self.taa.reset();
+    }
+
+    fn toggle_flashlight(&mut self) {
+        if let Some(entity) = self.flashlight.take() {
+            self.world.despawn(entity);
+            return;
+        }
+        let mut light = SpotLight::new(self.camera.position, self.camera.forward(), [1.0, 0.95, 0.85], 3000.0, Some(30.0));
+        light.inner_angle = 12f32.to_radians();
+        light.outer_angle = 25f32.to_radians();
+        self.flashlight = self.spawn_spot_light(light, self.camera.world_matrix());
+    }
+
+    /// Spawns an entity with a spot light placed and aimed by `transform`, which moves the
+    /// light whenever the entity's transform changes; None past the spot light limit.
+    pub fn spawn_spot_light(&mut self, light: SpotLight, transform: cgmath::Matrix4<f32>) -> Option<Entity> {
+        let id = self.lights.add_spot(light)?;
+        let entity = self.world.spawn();
+        self.world.insert(entity, Transform(transform));
+        self.world.insert(entity, Light(id));
+        Some(entity)
+    }
+
+    /// The scene's entities: the meshes as loaded, the fly camera and spawned lights.
+    /// Changes to them apply on the next `update`.
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// The entity mirroring the fly camera, with its `Camera` and camera-to-world
+    /// `Transform`. It follows the controls; writing to it does not move the view.
+    pub fn camera_entity(&self) -> Entity {
+        self.camera_entity
+    }
+
+    /// Applies what changed in the world since the last frame. Transforms propagate down
+    /// the scene graph first. The lights of despawned entities are removed, meshes without
+    /// an entity are no longer drawn, moved meshes get their objects rewritten and static
+    /// ones their bounds refit, and moved lights follow their transforms.
+    fn run_scene_systems(&mut self) {
+        let _span = profiler::scope("scene systems");
+        self.world.propagate_transforms();
+        for despawned in self.world.take_despawned() {
+            if let Some(Light(id)) = despawned.light {
+                self.lights.remove_spot(id);
+            }
+        }
+
+        let mut drawn = vec![false; self.meshes.len()];
+        for (_, &MeshHandle(i)) in self.world.query::<MeshHandle>() {
+            if let Some(d) = drawn.get_mut(i) {
+                *d = true;
+            }
+        }
+        for (i, (mesh, drawn)) in self.meshes.iter_mut().zip(drawn).enumerate() {
+            // GPU culling draws every mesh it was built with, so the object of a hidden
+            // mesh collapses to a point.
+            if mesh.drawn && !drawn {
+                self.objects.set_transform(i, cgmath::Matrix4::from_scale(0.0));
+            }
+            mesh.drawn = drawn;
+        }
+
+        let mut moved = self.world.take_changed::<Transform>();
+        // A mesh handed to an entity takes that entity's transform.
+        moved.extend(self.world.take_changed::<MeshHandle>());
+        for &entity in &moved {
+            let Some(&placed) = self.world.get::<Transform>(entity) else {
+                continue;
+            };
+            if let Some(&MeshHandle(i)) = self.world.get::<MeshHandle>(entity) {
+                if let Some(mesh) = self.meshes.get_mut(i) {
+                    self.objects.set_transform(i, placed.0);
+                    // Animated meshes are never culled.
+                    if mesh.bounds.is_finite() {
+                        mesh.rest_bounds = mesh.local_bounds.transformed(placed.0);
+                        mesh.bounds = mesh.rest_bounds;
+                    }
+                }
+            }
+            if let Some(light) = self.world.get::<Light>(entity).and_then(|&Light(id)| self.lights.spot_mut(id)) {
+                light.position = placed.position();
+                light.direction = placed.forward();
+            }
+        }
+        for entity in self.world.take_changed::<MaterialHandle>() {
+            let (Some(&MeshHandle(i)), Some(&MaterialHandle(material))) =
+                (self.world.get::<MeshHandle>(entity), self.world.get::<MaterialHandle>(entity))
+            else {
+                continue;
+            };
+            if let (Some(mesh), true) = (self.meshes.get_mut(i), material < self.materials.len()) {
+                mesh.material_index = material;
+                self.objects.set_material(i, material);
+            }
+        }
+        self.objects.upload(&self.queue);
+    }
+
+    /// Advances the camera, animation, lights and streaming by the time since the last
+    /// frame.
+    pub fn update(&mut self) {
+        self.update_by(self.last_frame.elapsed().as_secs_f32());
+    }
+
+    /// Advances everything by `frame_time` seconds instead of the time since the last
+    /// update, for callers that keep their own clock.
+    pub fn update_by(&mut self, frame_time: f32) {
+        let _span = profiler::scope("update");
+        self.latency.begin_frame();
+        for (reload, path) in self.asset_watcher.poll() {
+            reload(self, &path);
+        }
+        // Captured playback steps exactly one video frame at a time, however long rendering takes.
+        let capturing = self.path_capture.is_some() && self.path_playback.is_some();
+        let frame_time = if capturing { 1.0 / self.path_fps } else { frame_time };
+        let dt = if capturing { frame_time } else { frame_time.min(0.1) };
+        let now = Instant::now();
+        self.last_frame = now;
+        self.hud.push_frame_time(frame_time);
+
+        let (dx, dy) = self.camera_motion.look(self.input.take_mouse_delta(), dt);
+        if self.input.mouse_captured {
+            // Slower while zoomed in, so details stay steady.
+            let sensitivity = self.camera_motion.mouse_sensitivity * (self.camera.fovy / self.fov.base).min(1.0);
+            self.camera.apply_mouse_look(dx, dy, sensitivity);
+        }
+
+        let mut wish = Vector3::new(0.0, 0.0, 0.0);
+        if self.input.forward {
+            wish += self.camera.forward();
+        }
+        if self.input.back {
+            wish -= self.camera.forward();
+        }
+        if self.input.right {
+            wish += self.camera.right();
+        }
+        if self.input.left {
+            wish -= self.camera.right();
+        }
+        if self.input.up {
+            wish += self.camera.up;
+        }
+        if self.input.down {
+            wish -= self.camera.up;
+        }
+        if wish.magnitude2() > 0.0 {
+            wish = wish.normalize();
+        }
+
+        let scroll = self.input.take_scroll();
+        if scroll != 0.0 {
+            self.camera_motion.scale_speed(scroll);
+            let (scale, move_speed) = (self.camera_motion.speed_scale, self.camera_motion.move_speed);
+            let (factor, speed) = (hud::format_significant(scale), hud::format_significant(move_speed * scale));
+            self.hud.notify(format!("FLY SPEED {}X  ({} M/S)", factor, speed));
+        }
+        let motion = &self.camera_motion;
+        let speed = if self.input.sprint { motion.sprint_speed } else { motion.move_speed } * motion.speed_scale;
+        let (steps, step_dt) = match &mut self.fixed_timestep {
+            Some(timestep) => (timestep.advance(frame_time), timestep.step()),
+            None => (1, dt),
+        };
+        // Moving takes the camera back from a focus flight.
+        if wish.magnitude2() > 0.0 {
+            self.camera_flight = None;
+        }
+        if let Some(flight) = &mut self.camera_flight {
+            let (position, done) = flight.advance(dt);
+            self.camera.position = position;
+            self.camera.target = position + self.camera.forward();
+            if done {
+                self.camera_flight = None;
+            }
+        }
+        let sprinting = self.input.sprint && wish.magnitude2() > 0.0;
+        self.camera.fovy = self.fov.update(self.input.zoom, sprinting, dt);
+        for _ in 0..steps {
+            let velocity = self.camera_motion.velocity(wish * speed, step_dt);
+            self.camera.move_fly(velocity, step_dt, 1.0);
+            self.update_animation(step_dt);
+            self.sun.advance(step_dt);
+        }
+        if let Some(playback) = &mut self.path_playback {
+            match self.camera_path.sample(playback.time).filter(|_| playback.time <= self.camera_path.duration) {
+                Some(pose) => {
+                    self.camera.position = pose.position;
+                    self.camera.yaw = pose.yaw;
+                    self.camera.pitch = pose.pitch.clamp(-1.55, 1.55);
+                    self.camera.fovy = pose.fovy;
+                    self.camera.target = pose.position + self.camera.forward();
+                    playback.time += dt;
+                }
+                None => {
+                    match &self.path_capture {
+                        Some(dir) => log::info!("Camera path finished: {} frames in {}", playback.frame, dir.display()),
+                        None => log::info!("Camera path finished"),
+                    }
+                    self.path_playback = None;
+                }
+            }
+        }
+
+        let camera_transform = Transform(self.camera.world_matrix());
+        self.world.insert(self.camera_entity, camera_transform);
+        self.world.insert(self.camera_entity, self.camera.clone());
+        if let Some(flashlight) = self.flashlight {
+            self.world.insert(flashlight, camera_transform);
+        }
+        self.run_scene_systems();
+
+        // How large each material gets on screen, for the texture streamer.
+        let mut texture_demand = vec![0.0f32; self.materials.len()];
+        let screen_height = self.config.height as f32;
+        let mut draws = Vec::with_capacity(self.meshes.len());
+        for (i, mesh) in self.meshes.iter_mut().enumerate().filter(|(_, m)| m.shadow != ShadowRole::Proxy && m.drawn) {
+            let coverage = self.camera.screen_coverage(&mesh.bounds);
+            mesh.lod = if self.lods_enabled { mesh.select_lod(coverage) } else { 0 };
+            if let Some(demand) = texture_demand.get_mut(mesh.material_index) {
+                *demand = demand.max(coverage.min(1.0) * screen_height);
+            }
+            let material = mesh.material_index.min(self.material_meta.len().saturating_sub(1));
+            let center = if mesh.bounds.is_finite() { mesh.bounds.center() } else { mesh.rest_bounds.center() };
+            draws.push(DrawKey {
+                mesh: i,
+                pipeline: self.pipeline_ranks.get(material).copied().unwrap_or(0),
+                material,
+                depth: (center - self.camera.position).magnitude(),
+                blended: self.material_meta.get(material).is_some_and(|m| m.alpha_mode == model::AlphaMode::Blend),
+            });
+        }
+        self.draw_list.prepare(draws);
+        if let Some(streamer) = &mut self.texture_streamer {
+            let _span = profiler::scope("texture streaming");
+            let changed = streamer.update(
+                &self.device,
+                &self.queue,
+                &self.material_layout,
+                &mut self.materials,
+                &texture_demand,
+                &mut self.garbage,
+            );
+            if let Some(bindless) = self.bindless.as_mut().filter(|_| changed) {
+                let materials = self.materials.iter().chain(self.material_debug.materials());
+                bindless.update(&self.device, &self.queue, materials);
+            }
+        }
+
+        if self.sun.is_active() {
+            let mut directional = self.lights.directional().to_vec();
+            directional[0] = DirectionalLight {
+                cookie: directional[0].cookie,
+                cookie_size: directional[0].cookie_size,
+                ..self.sun.light()
+            };
+            self.lights.set_directional(&directional);
+        }
+        let clock = self.sun.clock_label();
+        if clock != self.sun_clock {
+            self.sun_clock = clock;
+            self.refresh_title();
+        }
+        if let Some(stats) = self.luminance.latest().filter(|_| self.auto_exposure.enabled) {
+            self.auto_exposure.adapt(&mut self.ev100, stats, dt);
+        }
+        let exposure = camera::exposure(self.ev100);
+        self.lights.set_exposure(exposure);
+        self.lights.upload(&self.queue);
+
+        let light_dir = self.lights.primary_direction();
+        let (near, far) = self.shadow_range();
+        let cascade_splits = self.shadow_settings.cascade_splits(near, far);
+        let light_view_projs: [cgmath::Matrix4<f32>; 4] = std::array::from_fn(|i| {
+            let slice = (if i == 0 { near } else { cascade_splits[i - 1] }, cascade_splits[i]);
+            let resolution = self.shadow_settings.resolution;
+            compute_cascade_view_proj(light_dir, &self.camera, slice, &self.scene_bounds, resolution)
+        });
+
+        let shapes_span = profiler::scope("debug shapes");
+        if let Some(frozen) = &self.shapes_camera {
+            let cascades = self.shadow_settings.cascades as usize;
+            queue_scene_shapes(
+                &mut self.scene_shapes,
+                frozen,
+                &cascade_splits[..cascades],
+                &light_view_projs[..cascades],
+                &self.lights,
+                &self.scene_bounds,
+            );
+        }
+        if self.show_mesh_bounds {
+            queue_mesh_bounds(&mut self.scene_shapes, &self.meshes);
+        }
+        self.scene_shapes.upload(&self.device, &self.queue);
+        drop(shapes_span);
+
+        self.camera_uniform.update_with_cascades(
+            &self.camera,
+            light_view_projs,
+            cascade_splits,
+            light_dir,
+            self.env_intensity * exposure,
+        );
+        let (render_width, render_height) = self.render_size();
+        let jitter = self.taa.next_jitter(render_width, render_height);
+        self.camera_uniform.apply_jitter(jitter);
+        // Generated skies already darken and redden with the sun.
+        if self.sky.is_some() || self.atmosphere.is_some() {
+            self.camera_uniform.env_intensity[3] = 1.0;
+        } else {
+            let sky_tint = self.sun.sky_tint();
+            for (channel, tint) in self.camera_uniform.env_intensity.iter_mut().zip(sky_tint) {
+                *channel *= tint;
+            }
+        }
+        self.camera_uniform.time = [now.duration_since(self.start_time).as_secs_f32(), dt, 0.0, 0.0];
+        self.camera_uniform.shadow_params = [
+            if self.shadow_filter == ShadowFilter::Evsm { 1.0 } else { 0.0 },
+            0.2,
+            EVSM_EXPONENTS[0],
+            EVSM_EXPONENTS[1],
+        ];
+        self.camera_uniform.shadow_map = [
+            self.shadow_settings.resolution as f32,
+            self.shadow_settings.cascades as f32,
+            self.shadow_bias.normal_offset,
+            0.0,
+        ];
+        self.camera_uniform.screen_space = [
+            if self.ssao.enabled { 1.0 } else { 0.0 },
+            if self.ssgi.enabled { 1.0 } else { 0.0 },
+            if self.rt_shadows.as_ref().is_some_and(|rt| rt.enabled) { 1.0 } else { 0.0 },
+            0.0,
+        ];
+        [self.camera_uniform.voxel_min, self.camera_uniform.voxel_extent] =
+            self.vct.as_ref().map_or([[0.0; 4]; 2], VoxelGi::camera_params);
+        self.camera_uniform.fog = self.fog.camera_params();
+        if let Some(lightmaps) = &mut self.lightmaps {
+            lightmaps.poll(&self.queue);
+        }
+        self.camera_uniform.lightmap = self.lightmaps.as_ref().map_or([0.0; 4], |l| l.camera_params(exposure));
+        let sun = self.lights.exposed_primary();
+        let sun_radiance = sun.color.map(|c| c * sun.intensity);
+        self.camera_uniform.sun = [sun_radiance[0], sun_radiance[1], sun_radiance[2], sun::ANGULAR_RADIUS];
+        self.camera_uniform.debug = [self.debug_view.shader_index(), 0.0, 0.0, 0.0];
+        self.lens_flare.update(
+            &self.queue,
+            self.camera_uniform.view_proj.into(),
+            -sun.direction.normalize(),
+            sun_radiance,
+            self.config.width as f32 / self.config.height as f32,
+        );
+        self.queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[self.camera_uniform]),
+        );
+
+        if let Some((ipd, eyes)) = self.stereo {
+            let tracked = self.begin_xr_frame();
+            for (i, (index, (left, rect))) in eyes.into_iter().zip(EYES).enumerate() {
+                self.viewports[index].viewport = match &tracked {
+                    Some(tracked) => Viewport::tracked_eye(&self.camera, &tracked[i], rect),
+                    None => Viewport::eye(&self.camera, ipd, left, rect),
+                };
+            }
+        }
+        let render_size = self.render_size();
+        for v in &self.viewports {
+            let Some([_, _, width, height]) = v.viewport.pixel_rect(render_size) else {
+                continue;
+            };
+            let mut uniform = self.camera_uniform;
+            v.viewport.update_uniform(&mut uniform, width as f32 / height as f32, self.camera.depth_mode);
+            self.queue.write_buffer(&v.buffer, 0, bytemuck::cast_slice(&[uniform]));
+        }
+
+        for i in 0..self.shadow_settings.cascades as usize {
+            let mut u = self.camera_uniform;
+            u.light_view_proj = light_view_projs[i].into();
+            self.queue.write_buffer(
+                &self.shadow_camera_buffers[i],
+                0,
+                bytemuck::cast_slice(&[u]),
+            );
+        }
+    }
+    
+    /// Begins the headset's next frame, waiting for the runtime, and returns where it
+    /// tracked the eyes; None without a headset or when it shows nothing this frame.
+    fn begin_xr_frame(&mut self) -> Option<[TrackedEye; 2]> {
+        #[cfg(feature = "openxr")]
+        match self.xr.as_mut().map(XrSession::begin_frame) {
+            Some(Ok(tracked)) => return tracked,
+            Some(Err(e)) => self.stop_xr(e),
+            None => {}
+        }
+        None
+    }
+
+    /// Gives up on the headset after the runtime failed, going on in the window alone.
+    #[cfg(feature = "openxr")]
+    fn stop_xr(&mut self, error: anyhow::Error) {
+        log::warn!("Stopped rendering to the headset: {:#}", error);
+        self.xr = None;
+    }
+
+    /// The headset's runtime ended the session, such as when quit from the headset.
+    fn xr_exited(&self) -> bool {
+        #[cfg(feature = "openxr")]
+        if let Some(xr) = &self.xr {
+            return xr.exited();
+        }
+        false
+    }
+
+    /// Draws a frame and presents it.
+    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        self.render_with(|_| {})
+    }
+
+    /// Draws a frame with `extra_passes` recorded over it, before the statistics panel,
+    /// and presents it.
+    pub fn render_with(
+        &mut self,
+        mut extra_passes: impl FnMut(&mut RenderContext<'_>),
+    ) -> Result<(), wgpu::SurfaceError> {
+        let _span = profiler::scope("render");
+        let acquire_span = profiler::scope("acquire");
+        let output = self.surface.get_current_texture()?;
+        drop(acquire_span);
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self.record_frame(&view);
+        let window_target = (&view, (self.config.width, self.config.height));
+        extra_passes(&mut RenderContext {
+            device: &self.device,
+            queue: &self.queue,
+            encoder: &mut encoder,
+            view: &view,
+            format: self.config.format,
+            size: window_target.1,
+        });
+        self.texture_inspector.draw(&self.device, &mut encoder, &self.queue, window_target, &self.materials);
+        if self.hud.is_active() {
+            let _span = profiler::scope("hud");
+            let totals = self.scene_totals();
+            self.hud.draw(&mut encoder, &self.queue, window_target, &self.frame_stats, &totals, &self.gpu_timer);
+        }
+        if self.debug_ui.visible || self.console.open || self.show_mesh_inspector {
+            let _span = profiler::scope("debug ui");
+            let show_settings = self.debug_ui.visible;
+            let mut settings = self.settings();
+            let inspector = if self.show_mesh_inspector { self.inspector_nodes() } else { Vec::new() };
+            let mut selected = self.selected;
+            let mut actions = Vec::new();
+            let mut entered = None;
+            let (console, commands) = (&mut self.console, &self.commands);
+            let (window, device, queue) = (&self.window, &self.device, &self.queue);
+            self.debug_ui.draw(window, device, queue, &mut encoder, window_target, |ctx| {
+                if show_settings {
+                    debug_ui::settings_panel(ctx, &mut settings, &mut actions);
+                }
+                if !inspector.is_empty() {
+                    debug_ui::mesh_panel(ctx, &inspector, &mut selected);
+                }
+                if console.open {
+                    entered = console.show(ctx, commands);
+                }
+            });
+            self.selected = selected;
+            if show_settings {
+                self.apply_settings(settings, actions);
+            }
+            if let Some(line) = entered {
+                self.run_command(&line);
+            }
+        }
+
+        #[cfg(feature = "openxr")]
+        if let Some(Err(e)) = self.xr.as_mut().map(|xr| xr.copy_frame(&self.device, &mut encoder, &output.texture)) {
+            self.stop_xr(e);
+        }
+
+        let submit_span = profiler::scope("submit");
+        let submission = self.queue.submit(std::iter::once(encoder.finish()));
+        drop(submit_span);
+        #[cfg(feature = "openxr")]
+        if let Some(Err(e)) = self.xr.as_mut().map(XrSession::end_frame) {
+            self.stop_xr(e);
+        }
+        self.latency.submitted(&self.queue);
+        self.garbage.submitted(&self.queue);
+        if self.latency_settings.wait_before_present {
+            self.device.poll(wgpu::Maintain::wait_for(submission));
+        }
+        let present_span = profiler::scope("present");
+        output.present();
+        drop(present_span);
+        if self.latency.end_frame(&self.device, self.config.present_mode) {
+            self.refresh_title();
+        }
+        self.luminance.poll(&self.device);
+        if let Some(bounds) = &mut self.depth_bounds {
+            bounds.poll(&self.device);
+        }
+        self.gpu_timer.poll(&self.device);
+        if let Some(crowd) = &mut self.crowd {
+            crowd.end_frame(&self.device);
+        }
+        if std::mem::take(&mut self.bake_probes) {
+            self.bake_light_probes();
+            // Reflections pick up the new indirect light.
+            self.reflections_dirty |= !self.reflections.probes().is_empty();
+        }
+        if std::mem::take(&mut self.reflections_dirty) {
+            self.capture_reflections();
+        }
+        let destroyed = self.garbage.collect(&self.device);
+        if destroyed > 0 {
+            log::debug!("Destroyed {} deferred GPU resources", destroyed);
+        }
+        
+        Ok(())
+    }
+
+    /// The current values of everything the settings panel shows.
+    fn settings(&self) -> Settings {
+        Settings {
+            shadow_filter: self.shadow_filter,
+            shadow_settings: self.shadow_settings,
+            normal_offset: self.shadow_bias.normal_offset,
+            ev100: self.ev100,
+            auto_exposure: self.auto_exposure.enabled,
+            compensation: self.auto_exposure.compensation,
+            env_intensity: self.env_intensity,
+            time_of_day: self.sun.time_of_day,
+            animate_sun: self.sun.animate,
+            debug_view: self.debug_view,
+            effects: vec![
+                ("Ambient occlusion", Action::Ssao, self.ssao.enabled),
+                ("Screen-space GI", Action::Ssgi, self.ssgi.enabled),
+                ("Volumetric fog", Action::Fog, self.fog.enabled),
+                ("Lens flare", Action::LensFlare, self.lens_flare.enabled),
+                ("Motion blur", Action::MotionBlur, self.motion_blur.enabled),
+                ("TAA", Action::Taa, self.taa.enabled),
+                ("FXAA", Action::Fxaa, self.fxaa.enabled),
+            ],
+            animation: self.animation_settings(),
+        }
+    }
+
+    fn animation_settings(&self) -> Option<AnimationSettings> {
+        let player = &self.animation_player;
+        if player.is_empty() {
+            return None;
+        }
+        Some(AnimationSettings {
+            clips: player.clips.iter().map(|&(m, c)| self.animations[m].set.clips[c].name.clone()).collect(),
+            current: player.current,
+            time: player.time,
+            duration: player.duration(&self.animations),
+            speed: player.speed,
+            looping: player.looping,
+            playing: player.playing,
+            show_weights: player.show_weights,
+            weight_joint: player.weight_joint,
+            joints: self.animations.iter().map(|a| a.joint_count()).max().unwrap_or(0),
+        })
+    }
+
+    fn apply_animation_settings(&mut self, a: AnimationSettings) {
+        if Some(&a) == self.animation_settings().as_ref() {
+            return;
+        }
+        let player = &mut self.animation_player;
+        let weights_changed = a.show_weights != player.show_weights || a.weight_joint != player.weight_joint;
+        player.time = if a.current == player.current { a.time } else { 0.0 };
+        player.current = a.current;
+        player.speed = a.speed;
+        player.looping = a.looping;
+        player.playing = a.playing;
+        player.show_weights = a.show_weights;
+        player.weight_joint = a.weight_joint;
+        player.dirty = true;
+        if weights_changed {
+            self.upload_bone_weights();
+        }
+    }
+
+    /// Takes back what the settings panel changed and runs the actions it fired.
+    fn apply_settings(&mut self, settings: Settings, actions: Vec<Action>) {
+        if settings.shadow_filter != self.shadow_filter {
+            self.on_action(Action::ShadowFilter);
+        }
+        self.shadow_bias.normal_offset = settings.normal_offset;
+        self.ev100 = settings.ev100;
+        self.auto_exposure.enabled = settings.auto_exposure;
+        self.auto_exposure.compensation = settings.compensation;
+        self.env_intensity = settings.env_intensity;
+        if settings.time_of_day != self.sun.time_of_day {
+            self.sun.set_time_of_day(settings.time_of_day);
+        }
+        if settings.animate_sun != self.sun.animate {
+            self.sun.toggle_animation();
+        }
+        self.debug_view = settings.debug_view;
+        if let Some(animation) = settings.animation {
+            self.apply_animation_settings(animation);
+        }
+        for action in actions {
+            self.on_action(action);
+        }
+    }
+
+    /// Records every pass of a frame, ending with the tonemap and overlays on `view`.
+    fn record_frame(&mut self, view: &wgpu::TextureView) -> wgpu::CommandEncoder {
+        let _span = profiler::scope("encode");
+        let section_span = profiler::scope("sky");
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
+        self.gpu_timer.begin(&mut encoder);
+
+        if let Some(sky) = &mut self.sky {
+            sky.bake(&mut encoder, &self.queue, self.lights.primary_direction());
+        }
+        if let Some(atmosphere) = &mut self.atmosphere {
+            atmosphere.bake(&mut encoder, &self.queue, self.lights.primary_direction());
+        }
+        self.gpu_timer.end_section(&mut encoder);
+        drop(section_span);
+
+        let section_span = profiler::scope("shadows");
+        let mut stats = FrameStats::default();
+        let bundles = self.record_shadow_bundles();
+        stats.shadow += bundles.count;
+        if self.shadow_filter == ShadowFilter::Evsm {
+            self.evsm.record(&mut encoder, &self.shadow_camera_bind_groups, |pass, cascade| {
+                stats.shadow += draw_cascade_casters(pass, &self.meshes, &self.geometry_pool, cascade);
+            });
+        } else {
+            for (cascade, bundle) in (0..).zip(&bundles.cascades) {
+                let shadow_layer_view = self.shadow_texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some(&format!("Shadow Layer {}", cascade)),
+                    format: Some(wgpu::TextureFormat::Depth32Float),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    aspect: wgpu::TextureAspect::All,
+                    base_mip_level: 0,
+                    mip_level_count: None,
+                    base_array_layer: cascade,
+                    array_layer_count: Some(1),
+                });
+
+                let mut shadow_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some(&format!("Shadow Pass Cascade {}", cascade)),
+                    color_attachments: &[],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: &shadow_layer_view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: wgpu::StoreOp::Store,
+                        }),
+                        stencil_ops: None,
+                    }),
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+
+                shadow_pass.execute_bundles(std::iter::once(bundle));
+            }
+        }
+
+        for (i, (face, bundle)) in self.lights.point_shadow_faces.iter().zip(&bundles.point_faces).enumerate() {
+            let mut shadow_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(&format!("Point Shadow Pass {}", i)),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &face.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            shadow_pass.execute_bundles(std::iter::once(bundle));
+        }
+
+        {
+            let mut shadow_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Spot Shadow Atlas Pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.lights.spot_atlas_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            for (tile, bundle) in self.lights.spot_shadow_tiles().zip(&bundles.spot_tiles) {
+                let (x, y, size) = tile.viewport;
+                shadow_pass.set_viewport(x, y, size, size, 0.0, 1.0);
+                shadow_pass.execute_bundles(std::iter::once(bundle));
+            }
+        }
+
+        self.gpu_timer.end_section(&mut encoder);
+        drop(section_span);
+
+        let section_span = profiler::scope("opaque");
+        if let Some(crowd) = &mut self.crowd {
+            crowd.update(&self.queue, &mut encoder, self.camera_uniform.view_proj, self.camera_uniform.time[0]);
+        }
+        if let Some(bindless) = &self.bindless {
+            let replacement = self.material_debug.apply(&self.materials, Some(bindless)).replacement();
+            bindless.set_replacement(&self.queue, replacement);
+        }
+        if let Some(culling) = &self.gpu_culling {
+            culling.cull(&self.queue, &mut encoder, self.camera_uniform.view_proj);
+        }
+
+        let weights_key = PipelineKey::with_features(ShaderFeatures::BONE_WEIGHTS, true);
+        let weights_pipeline = self
+            .pipeline_cache
+            .get(&weights_key)
+            .filter(|_| self.animation_player.show_weights);
+
+        let rt_shadows = self.rt_shadows.as_ref().filter(|rt| rt.enabled);
+        let prepass = self.depth_prepass || self.ssao.enabled || self.ssgi.enabled || rt_shadows.is_some();
+        if prepass {
+            stats.main += self.record_depth_prepass(&mut encoder);
+            encoder.copy_texture_to_texture(
+                self.depth_texture.as_image_copy(),
+                self.scene_depth_texture.as_image_copy(),
+                self.depth_texture.size(),
+            );
+        }
+        if self.ssao.enabled {
+            self.ssao.record(&mut encoder, &self.queue);
+        }
+        if self.ssgi.enabled {
+            self.ssgi.record(&mut encoder, &self.queue, self.camera_uniform.view_proj);
+        }
+        if let (Some(rt), Some(sun)) = (rt_shadows, self.lights.directional().first()) {
+            rt.record(&mut encoder, &self.queue, sun);
+        }
+        let sun = self.lights.exposed_primary();
+        if let Some(ddgi) = &mut self.ddgi {
+            if ddgi.enabled {
+                ddgi.update(&mut encoder, &self.queue, &sun, self.camera_uniform.env_intensity);
+            }
+        }
+        if self.fog.enabled {
+            self.fog.record(&mut encoder, &self.queue, &sun, self.camera_uniform.env_intensity);
+        }
+        if let Some(vct) = &self.vct {
+            if vct.enabled || vct.debug_view {
+                vct.update(&mut encoder, &self.queue, &sun);
+            }
+        }
+        let far_depth = self.camera.depth_mode.far();
+        let mut depth_load = if prepass { wgpu::LoadOp::Load } else { wgpu::LoadOp::Clear(far_depth) };
+        let mut hdr_load = wgpu::LoadOp::Clear(wgpu::Color {
+            r: 0.1,
+            g: 0.2,
+            b: 0.3,
+            a: 1.0,
+        });
+
+        if let Some(gbuffer) = &self.gbuffer {
+            {
+                let mut gbuffer_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("G-Buffer Pass"),
+                    color_attachments: &gbuffer.color_attachments(),
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: &self.depth_texture_view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: depth_load,
+                            store: wgpu::StoreOp::Store,
+                        }),
+                        stencil_ops: None,
+                    }),
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+                gbuffer_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+                gbuffer_pass.set_bind_group(2, &self.lights.bind_group, &[]);
+                gbuffer_pass.set_bind_group(3, &self.evsm.bind_group, &[]);
+                stats.main += draw_opaque_meshes(
+                    &mut gbuffer_pass,
+                    (&self.meshes, self.draw_list.opaque(), &self.geometry_pool),
+                    self.material_debug.apply(&self.materials, self.bindless.as_ref()),
+                    &self.material_meta,
+                    &self.pipeline_cache,
+                    None,
+                    (ShaderFeatures::GBUFFER, prepass, self.gpu_culling.is_some()),
+                );
+                if let Some(culling) = &self.gpu_culling {
+                    stats.main += draw_gpu_culled(
+                        &mut gbuffer_pass,
+                        culling,
+                        self.material_debug.apply(&self.materials, self.bindless.as_ref()),
+                        &self.material_meta,
+                        &self.pipeline_cache,
+                        (ShaderFeatures::GBUFFER, prepass),
+                    );
+                }
+            }
+            let bind_groups = [&self.camera_bind_group, &self.lights.bind_group, &self.evsm.bind_group];
+            gbuffer.resolve(&mut encoder, &self.hdr_target.view, bind_groups);
+            // The main pass below only adds the sky and forward-only geometry.
+            depth_load = wgpu::LoadOp::Load;
+            hdr_load = wgpu::LoadOp::Load;
+        }
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.hdr_target.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: hdr_load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: depth_load,
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            render_pass.set_bind_group(2, &self.lights.bind_group, &[]);
+            render_pass.set_bind_group(3, &self.evsm.bind_group, &[]);
+
+            render_pass.set_pipeline(&self.sky_pipeline);
+            render_pass.draw(0..3, 0..1);
+
+            if self.gbuffer.is_none() {
+                stats.main += draw_opaque_meshes(
+                    &mut render_pass,
+                    (&self.meshes, self.draw_list.opaque(), &self.geometry_pool),
+                    self.material_debug.apply(&self.materials, self.bindless.as_ref()),
+                    &self.material_meta,
+                    &self.pipeline_cache,
+                    weights_pipeline,
+                    (ShaderFeatures::empty(), prepass, self.gpu_culling.is_some()),
+                );
+                if let Some(culling) = &self.gpu_culling {
+                    stats.main += draw_gpu_culled(
+                        &mut render_pass,
+                        culling,
+                        self.material_debug.apply(&self.materials, self.bindless.as_ref()),
+                        &self.material_meta,
+                        &self.pipeline_cache,
+                        (ShaderFeatures::empty(), prepass),
+                    );
+                }
+            } else if let Some(pipeline) = weights_pipeline {
+                // Drawn over the resolved G-buffer at equal depth.
+                stats.main += draw_weight_meshes(
+                    &mut render_pass,
+                    (&self.meshes, &self.geometry_pool),
+                    self.material_debug.apply(&self.materials, self.bindless.as_ref()),
+                    pipeline,
+                );
+            }
+
+            if let Some(crowd) = &self.crowd {
+                crowd.draw(&mut render_pass);
+                // Culled on the GPU, so only the draw is known here.
+                stats.main.draws += 1;
+            }
+        }
+        self.gpu_timer.end_section(&mut encoder);
+        drop(section_span);
+
+        let section_span = profiler::scope("transparent");
+        encoder.copy_texture_to_texture(
+            self.depth_texture.as_image_copy(),
+            self.scene_depth_texture.as_image_copy(),
+            self.depth_texture.size(),
+        );
+
+        if let Some(atmosphere) = &self.atmosphere {
+            atmosphere.apply_aerial_perspective(&mut encoder, &self.hdr_target.view, &self.camera_bind_group);
+        }
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Transparent Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.hdr_target.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            render_pass.set_bind_group(2, &self.lights.bind_group, &[]);
+            render_pass.set_bind_group(3, &self.evsm.bind_group, &[]);
+
+            let materials = self.material_debug.apply(&self.materials, self.bindless.as_ref());
+            materials.begin(&mut render_pass);
+            let mut binder = GeometryBinder::new(&self.geometry_pool);
+            let (mut bound_key, mut bound_material) = (None, None);
+            for &i in self.draw_list.transparent() {
+                let mesh = &self.meshes[i];
+                let material_index = mesh.material_index.min(self.materials.len().saturating_sub(1));
+                let meta = self
+                    .material_meta
+                    .get(material_index)
+                    .copied()
+                    .unwrap_or(MaterialMeta {
+                        alpha_mode: model::AlphaMode::Opaque,
+                        pipeline_key: PipelineKey::new(model::AlphaMode::Opaque, false),
+                    });
+                if meta.alpha_mode != model::AlphaMode::Blend
+                    || (weights_pipeline.is_some() && mesh.bone_weight_buffer.is_some())
+                {
+                    continue;
+                }
+                let Some(pipeline) = self.pipeline_cache.get(&meta.pipeline_key) else {
+                    continue;
+                };
+                if bound_key != Some(meta.pipeline_key) {
+                    render_pass.set_pipeline(pipeline);
+                    bound_key = Some(meta.pipeline_key);
+                    stats.main.switches += 1;
+                }
+                if bound_material != Some(material_index) {
+                    materials.bind(&mut render_pass, material_index);
+                    bound_material = Some(material_index);
+                    stats.main.switches += 1;
+                }
+                let normal_mapped = meta.pipeline_key.features.contains(ShaderFeatures::NORMAL_MAP);
+                let (indices, base_vertex) = binder.bind(&mut render_pass, mesh.lod_geometry(), normal_mapped);
+                stats.main.add(indices.len() as u32);
+                render_pass.draw_indexed(indices, base_vertex, objects::instance(i));
+            }
+        }
+        self.gpu_timer.end_section(&mut encoder);
+        drop(section_span);
+
+        let section_span = profiler::scope("post");
+        if self.ssgi.enabled {
+            self.ssgi.store_color(&mut encoder, &self.hdr_target.texture);
+        }
+        if let Some(vct) = self.vct.as_ref().filter(|vct| vct.debug_view) {
+            vct.draw_debug(&mut encoder, &self.hdr_target.view);
+        }
+        if self.taa.enabled || self.motion_blur.enabled {
+            self.velocity.record(&mut encoder, &self.queue, self.camera_uniform.view_proj, self.taa.jitter());
+        }
+        // Anti-alias first so the blur spreads clean edges.
+        if self.taa.enabled {
+            self.taa.resolve(&mut encoder, &self.queue, &self.hdr_target);
+        }
+        if self.motion_blur.enabled {
+            self.motion_blur.record(&mut encoder, &self.queue, &self.hdr_target);
+        }
+        // After the temporal passes, whose history and motion vectors are the main view's.
+        stats.main += self.record_viewports(&mut encoder);
+        if let Some(bounds) = &mut self.depth_bounds {
+            bounds.record(&self.queue, &mut encoder, self.camera_uniform.proj_inv);
+        }
+        self.luminance.record(&self.queue, &mut encoder, camera::exposure(self.ev100));
+        // After metering, so looking at the sun does not darken the whole frame further.
+        self.lens_flare.draw(&mut encoder, &self.hdr_target.view);
+        let post_input = self.post_stack.input_view().unwrap_or(view);
+        // The last render-resolution image; the upscaler takes it to the window size.
+        let ldr_view = self.upscaler.as_ref().map_or(post_input, |u| u.input_view());
+        if self.fxaa.enabled {
+            self.tonemapper.draw(&mut encoder, self.fxaa.input_view());
+            self.fxaa.draw(&mut encoder, ldr_view);
+        } else {
+            self.tonemapper.draw(&mut encoder, ldr_view);
+        }
+        if self.debug_view == DebugView::Overdraw {
+            self.overdraw.record(&mut encoder, ldr_view, |pass, pipelines| {
+                let mut binder = GeometryBinder::new(&self.geometry_pool);
+                let drawn = self.meshes.iter().enumerate().filter(|(_, m)| m.shadow != ShadowRole::Proxy && m.drawn);
+                for (i, mesh) in drawn {
+                    let double_sided =
+                        self.material_meta.get(mesh.material_index).is_some_and(|m| m.pipeline_key.double_sided);
+                    pass.set_pipeline(&pipelines[double_sided as usize]);
+                    let (indices, base_vertex) = binder.bind(pass, mesh.lod_geometry(), false);
+                    pass.draw_indexed(indices, base_vertex, objects::instance(i));
+                }
+            });
+        }
+
+        if self.show_mesh_inspector || self.shapes_camera.is_some() || self.show_mesh_bounds {
+            let mut overlay_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Overlay Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: ldr_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            if self.show_mesh_inspector {
+                self.mesh_inspector.draw(&mut overlay_pass);
+            }
+            self.scene_shapes.draw(&mut overlay_pass);
+        }
+        if let Some(upscaler) = &self.upscaler {
+            upscaler.draw(&mut encoder, &self.queue, post_input);
+        }
+        self.post_stack.draw(&mut encoder, &self.queue, view, self.camera_uniform.time[0]);
+        self.gpu_timer.end_section(&mut encoder);
+        drop(section_span);
+        self.frame_stats = stats;
+        encoder
+    }
+
+
+    /// What the HUD reports about the loaded scene.
+    fn scene_totals(&self) -> SceneTotals {
+        let mut buffer_bytes = self.geometry_pool.buffer_bytes();
+        for mesh in &self.meshes {
+            let optional = [&mesh.bone_weight_buffer, &mesh.lightmap_uv_buffer];
+            buffer_bytes += mesh.geometry.buffer_bytes();
+            buffer_bytes += optional.into_iter().flatten().map(wgpu::Buffer::size).sum::<u64>();
+        }
+        SceneTotals {
+            meshes: self.meshes.len(),
+            materials: self.materials.len(),
+            buffer_bytes,
+            texture_bytes: self.materials.iter().map(|m| m.texture_bytes).sum(),
+        }
+    }
+
+    /// Pauses rendering while the window is minimized or fully covered, and restarts the
+    /// frame clock once it shows again so the gap isn't taken for one long frame. Returns
+    /// whether rendering is paused.
+    pub fn update_paused(&mut self) -> bool {
+        let hidden = self.occluded || self.minimized || self.window.is_minimized() == Some(true);
+        if hidden != self.paused {
+            self.paused = hidden;
+            if hidden {
+                log::info!("Window hidden; rendering paused");
+            } else {
+                self.last_frame = Instant::now();
+                log::info!("Window visible; rendering resumed");
+            }
+        }
+        hidden
+    }
+
+    /// Records whether the platform reports the window fully covered by others, which
+    /// [`Renderer::update_paused`] pauses for.
+    pub fn set_occluded(&mut self, occluded: bool) {
+        self.occluded = occluded;
+    }
+
+    /// Whether no frames are drawn because the window can't be seen.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Sleeps until the next frame is due under `--fps-cap`; returns at once without one.
+    pub fn wait_for_frame(&mut self) {
+        if let Some(limiter) = &mut self.frame_limiter {
+            limiter.wait();
+        }
+    }
+
+    /// Whether the run ended on its own: the crowd benchmark measured all its frames or
+    /// the headset's session ended.
+    pub fn finished(&self) -> bool {
+        self.crowd.as_ref().is_some_and(|c| c.benchmark_finished()) || self.xr_exited()
+    }
+
+    /// Camera distances the shadow cascades split: what the last reduced frame showed, with
+    /// slack for surfaces coming into view, or else the whole view range up to the far side
+    /// of the scene.
+    fn shadow_range(&self) -> (f32, f32) {
+        let (znear, zfar) = (self.camera.znear, self.camera.zfar);
+        let far = if self.scene_bounds.is_finite() {
+            let (p, b) = (self.camera.position, &self.scene_bounds);
+            let reach = |p: f32, min: f32, max: f32| (p - min).abs().max((max - p).abs());
+            let farthest = Vector3::new(
+                reach(p.x, b.min.x, b.max.x),
+                reach(p.y, b.min.y, b.max.y),
+                reach(p.z, b.min.z, b.max.z),
+            );
+            farthest.magnitude().clamp(znear + 1.0, zfar)
+        } else {
+            zfar
+        };
+        match self.depth_bounds.as_ref().and_then(DepthBounds::latest) {
+            Some((nearest, farthest)) => {
+                let near = (nearest * 0.8).clamp(znear, far);
+                (near, (farthest * 1.1).min(far).max(near + 1.0))
+            }
+            None => (znear, far),
+        }
+    }
+
+    /// The size everything up to the tonemap renders at.
+    fn render_size(&self) -> (u32, u32) {
+        upscale::scaled_size(self.config.width, self.config.height, self.render_scale)
+    }
+
+    /// Lays down depth for fully opaque meshes so SSAO and SSGI can run before shading; masked
+    /// and blended surfaces only appear in the main pass.
+    /// Culls and records the casters of every shadow view on the job threads, a bundle per
+    /// view, for the shadow passes to replay.
+    fn record_shadow_bundles(&self) -> ShadowBundles {
+        let cascades = match self.shadow_filter {
+            ShadowFilter::Evsm => 0,
+            _ => self.shadow_settings.cascades,
+        };
+        let mut views: Vec<(&wgpu::BindGroup, ShadowCasters)> = (0..cascades)
+            .map(|c| (&self.shadow_camera_bind_groups[c as usize], ShadowCasters::Cascade(c)))
+            .collect();
+        let faces = &self.lights.point_shadow_faces;
+        views.extend(faces.iter().map(|f| (&f.bind_group, ShadowCasters::Sphere(f.light_position, f.range))));
+        views.extend(
+            self.lights
+                .spot_shadow_tiles()
+                .map(|t| (t.bind_group, ShadowCasters::Sphere(t.light_position, t.range))),
+        );
+
+        let mut recorded: Vec<Option<(wgpu::RenderBundle, DrawCount)>> = Vec::new();
+        recorded.resize_with(views.len(), || None);
+        let (device, pipeline) = (&self.device, &self.shadow_pipeline);
+        let scene = (&self.meshes[..], &self.geometry_pool);
+        jobs::scope("shadow jobs", |scope| {
+            for (slot, &(bind_group, casters)) in recorded.iter_mut().zip(&views) {
+                scope.spawn(move |_| *slot = Some(record_caster_bundle(device, pipeline, bind_group, scene, casters)));
+            }
+        });
+
+        let mut count = DrawCount::default();
+        let mut bundles = recorded.into_iter().flatten().map(|(bundle, drawn)| {
+            count += drawn;
+            bundle
+        });
+        let cascades = bundles.by_ref().take(cascades as usize).collect();
+        let point_faces = bundles.by_ref().take(faces.len()).collect();
+        let spot_tiles = bundles.collect();
+        ShadowBundles {
+            cascades,
+            point_faces,
+            spot_tiles,
+            count,
+        }
+    }
+
+    fn record_depth_prepass(&self, encoder: &mut wgpu::CommandEncoder) -> DrawCount {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Depth Prepass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_texture_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.camera.depth_mode.far()),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.depth_prepass_pipeline);
+        pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        let mut count = DrawCount::default();
+        let mut binder = GeometryBinder::new(&self.geometry_pool);
+        for &i in self.draw_list.opaque() {
+            let mesh = &self.meshes[i];
+            if mesh.gpu_culled {
+                continue;
+            }
+            let material_index = mesh.material_index.min(self.materials.len().saturating_sub(1));
+            let alpha_mode = self.material_meta.get(material_index).map(|meta| meta.alpha_mode);
+            if alpha_mode.is_some_and(|mode| mode != model::AlphaMode::Opaque) {
+                continue;
+            }
+            let (indices, base_vertex) = binder.bind(&mut pass, mesh.lod_geometry(), false);
+            count.add(indices.len() as u32);
+            pass.draw_indexed(indices, base_vertex, objects::instance(i));
+        }
+        if let Some(culling) = &self.gpu_culling {
+            for group in culling.groups() {
+                let alpha_mode = self.material_meta.get(group.material_index).map(|meta| meta.alpha_mode);
+                if alpha_mode == Some(model::AlphaMode::Opaque) {
+                    culling.draw_group(&mut pass, group, false);
+                    count.draws += 1;
+                }
+            }
+        }
+        count
+    }
+
+    /// Renders one frame offscreen at the window size and writes it to a PNG, and with
+    /// `--exr` the scene before tonemapping to an .exr beside it.
+    pub fn capture(&mut self, path: &Path) -> Result<()> {
+        self.read_frame()?.save(path)
+    }
+
+    /// Captures the scene like [`Self::capture`], rendered at `supersample` times the
+    /// window size and averaged back down, to a new file in the screenshot folder.
+    pub fn screenshot(&mut self, supersample: u32) -> Result<PathBuf> {
+        let window_size = self.size;
+        let max = self.device.limits().max_texture_dimension_2d;
+        let largest = window_size.width.max(window_size.height).max(1);
+        let factor = supersample.clamp(1, (max / largest).max(1));
+        if factor < supersample {
+            log::warn!("{}x supersampling exceeds the {} px texture limit; using {}x", supersample, max, factor);
+        }
+        // The supersampled targets may not fit in GPU memory.
+        self.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        if factor > 1 {
+            self.resize(winit::dpi::PhysicalSize::new(window_size.width * factor, window_size.height * factor));
+        }
+        let frame = self.read_frame();
+        if factor > 1 {
+            self.resize(window_size);
+        }
+        if let Some(error) = pollster::block_on(self.device.pop_error_scope()) {
+            anyhow::bail!("Not enough GPU memory for a {}x screenshot: {}", factor, error);
+        }
+        let path = screenshot::next_path();
+        frame?.downsample(factor).save(&path)?;
+        Ok(path)
+    }
+
+    /// Renders a frame offscreen at the surface size and reads it back, along with the HDR
+    /// target when `--exr` asks for it.
+    fn read_frame(&mut self) -> Result<Frame> {
+        let _span = profiler::scope("capture");
+        let (width, height) = (self.config.width, self.config.height);
+        let target = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Capture Target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self.record_frame(&view);
+        let readback = Readback::new(&self.device, &mut encoder, &target);
+        let hdr_readback = self.write_exr.then(|| Readback::new(&self.device, &mut encoder, &self.hdr_target.texture));
+        let submission = self.queue.submit(std::iter::once(encoder.finish()));
+        self.garbage.submitted(&self.queue);
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        for r in std::iter::once(&readback).chain(&hdr_readback) {
+            r.map(sender.clone());
+        }
+        self.device.poll(wgpu::Maintain::wait_for(submission));
+        for _ in 0..1 + hdr_readback.is_some() as usize {
+            receiver.recv()?.context("mapping capture readback")?;
+        }
+        self.luminance.poll(&self.device);
+        if let Some(bounds) = &mut self.depth_bounds {
+            bounds.poll(&self.device);
+        }
+        self.gpu_timer.poll(&self.device);
+        self.garbage.collect(&self.device);
+
+        let bgra = matches!(
+            self.config.format,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        );
+        Ok(Frame {
+            width,
+            height,
+            pixels: readback.rgba8(bgra),
+            hdr: hdr_readback.map(|r| r.hdr()),
+        })
+    }
+
+    fn set_environment(&mut self, hdr_path: &Path) {
+        if !hdr_path.exists() {
+            log::warn!("Environment map {} not found", hdr_path.display());
+        }
+        let env_texture = self.assets.reload_environment(&self.device, &self.queue, hdr_path);
+        self.garbage.defer(std::mem::replace(&mut self.env_texture, env_texture));
+        if hdr_path.exists() {
+            if let Some(sky) = self.sky.take() {
+                self.garbage.defer(sky.texture);
+            }
+            if let Some(atmosphere) = self.atmosphere.take() {
+                self.garbage.defer(atmosphere.texture);
+            }
+        }
+        self.env_texture_view = self.env_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.rebuild_camera_bind_group();
+        let generated_sky = self.sky.is_some() || self.atmosphere.is_some();
+        self.scene_data.environment = (!generated_sky).then(|| hdr_path.to_path_buf());
+        self.asset_watcher.unwatch(AssetKind::Environment);
+        if hdr_path.exists() {
+            self.asset_watcher.watch(AssetKind::Environment, hdr_path, Renderer::set_environment);
+        }
+    }
+
+    /// Registers the files loaded at startup with the asset watcher.
+    fn watch_assets(&mut self) {
+        let watcher = &mut self.asset_watcher;
+        watcher.watch(AssetKind::Config, &self.options.config, |renderer, path| {
+            renderer.reload_config(EngineConfig::load(path));
+        });
+        let shader_path = Path::new(SHADER_SOURCE_PATH);
+        if shader_path.exists() {
+            watcher.watch(AssetKind::Shader, shader_path, Renderer::reload_shader);
+        }
+        if let Some(hdr_path) = &self.scene_data.environment {
+            watcher.watch(AssetKind::Environment, hdr_path, Renderer::set_environment);
+        }
+        for path in self.texture_files.keys() {
+            watcher.watch(AssetKind::Texture, path, Renderer::reload_texture);
+        }
+        for entry in &self.scene_data.scene.entries {
+            watcher.watch(AssetKind::Model, &entry.path, Renderer::reload_model);
+        }
+    }
+
+    /// Loads the model at `path` again in place of the one held in memory, then swaps the
+    /// scene's meshes and materials for ones built with it on the running device; see
+    /// [`Renderer::reload_scene_meshes`]. A model that fails to load or to upload is
+    /// logged and leaves the running scene as it is.
+    fn reload_model(&mut self, path: &Path) {
+        let Some(index) = self.scene_data.scene.entries.iter().position(|e| e.path == path) else {
+            return;
+        };
+        let mut model = match self.assets.load_model(&self.scene_data.scene.entries[index]) {
+            Ok(model) => model,
+            Err(e) => {
+                log::warn!("Keeping the running scene: {:#}", e);
+                return;
+            }
+        };
+        let (kept, placement) = &mut self.scene_data.loaded.models[index];
+        if *placement != cgmath::Matrix4::from_scale(1.0) {
+            model.transform(*placement);
+        }
+        let previous = std::mem::replace(kept, model);
+        match self.reload_scene_meshes() {
+            Ok(()) => log::info!("Reloaded {}", path.display()),
+            Err(e) => {
+                log::warn!("Keeping the running scene: {:#}", e);
+                self.scene_data.loaded.models[index].0 = previous;
+            }
+        }
+    }
+
+    /// Builds the scene's meshes and materials again from the models held in memory,
+    /// with the traced GI and GPU culling made from them, and swaps them in on the
+    /// running device, rebinding the object table wherever it is bound. The camera,
+    /// lights, probes and every other pass stay as they are; so does the scene when this
+    /// fails.
+    fn reload_scene_meshes(&mut self) -> Result<()> {
+        let _span = profiler::scope("reload scene meshes");
+        let models = &self.scene_data.loaded.models;
+        // Scene materials, then the material debug view's checker and clay.
+        let material_count = models.iter().map(|(m, _)| m.materials.len()).sum::<usize>() + 2;
+        if let Some(bindless) = self.bindless.as_ref().filter(|b| material_count > b.capacity()) {
+            anyhow::bail!(
+                "the scene now has {} materials, the bindless table was made for {}",
+                material_count,
+                bindless.capacity()
+            );
+        }
+        let mut model_bounds = Aabb::empty();
+        for (model, _) in models {
+            model_bounds.union(&model.bounds());
+        }
+        let mut scene_bounds = model_bounds;
+        if let Some(count) = self.options.crowd {
+            scene_bounds.union(&CrowdScene::bounds(count));
+        }
+
+        self.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let device = &self.device;
+        let mut objects = SceneObjects::new(device, models.iter().map(|(m, _)| m.meshes.len()).sum());
+        let default_textures = DefaultTextures::new(device, &self.queue);
+        let mut texture_streamer = self.options.texture_budget.map(TextureStreamer::new);
+        let mut scene_meshes = SceneMeshes::load(
+            (device, &self.queue),
+            (&self.material_layout, &default_textures),
+            (&mut self.pipeline_cache, &mut texture_streamer),
+            (&mut self.world, &mut objects),
+            models,
+            (&self.scene_data.loaded.instances, &scene_bounds),
+            &self.options,
+        );
+        let traced_gi = scene_meshes.trace_gi(
+            (device, &self.queue),
+            &mut self.pipeline_cache,
+            (
+                &self.camera_buffer,
+                &self.scene_depth_view,
+                (self.depth_texture.width(), self.depth_texture.height()),
+                self.camera.depth_mode,
+            ),
+            (
+                self.probes.grid,
+                environment_view(&self.env_texture_view, self.sky.as_ref(), self.atmosphere.as_ref()),
+                self.lights.directional().first(),
+                self.env_intensity,
+            ),
+            &scene_bounds,
+            &self.options,
+        );
+        let gpu_culling = scene_meshes.build_gpu_culling(device, self.bindless.is_some());
+        self.pipeline_cache.prepare_depth_equal_variants(device);
+        if self.gbuffer.is_some() {
+            self.pipeline_cache.prepare_gbuffer_variants(device);
+        }
+        for filter in ["Validation", "Out of memory"] {
+            if let Some(error) = pollster::block_on(self.device.pop_error_scope()) {
+                despawn_scene_meshes(&mut self.world, &scene_meshes.model_roots, &scene_meshes.mesh_entities);
+                anyhow::bail!("Creating GPU resources failed ({}): {}", filter.to_lowercase(), error);
+            }
+        }
+
+        despawn_scene_meshes(&mut self.world, &self.model_roots, &self.mesh_entities);
+        let SceneMeshes {
+            meshes,
+            mesh_entities,
+            model_roots,
+            materials,
+            material_meta,
+            texture_files,
+            geometry_pool,
+            picker,
+            animations,
+            inspect_lines,
+            ..
+        } = scene_meshes;
+        let TracedGi {
+            vct,
+            ddgi,
+            rt_shadows,
+            lightmaps,
+        } = traced_gi;
+        self.scene_data.loaded.bounds = model_bounds;
+        self.scene_bounds = scene_bounds;
+        self.pipeline_ranks = material_meta
+            .iter()
+            .map(|meta| material_meta.iter().position(|m| m.pipeline_key == meta.pipeline_key).unwrap_or(0))
+            .collect();
+        (self.meshes, self.mesh_entities, self.model_roots) = (meshes, mesh_entities, model_roots);
+        (self.materials, self.material_meta) = (materials, material_meta);
+        (self.geometry_pool, self.picker, self.selected) = (geometry_pool, picker, None);
+        self.animation_player = AnimationPlayer::new(&animations);
+        self.animations = animations;
+        self.mesh_inspector.set_static_lines(&self.device, &inspect_lines);
+        (self.texture_streamer, self.gpu_culling) = (texture_streamer, gpu_culling);
+        (self.vct, self.ddgi, self.rt_shadows, self.lightmaps) = (vct, ddgi, rt_shadows, lightmaps);
+        if let Some(bindless) = &mut self.bindless {
+            bindless.update(&self.device, &self.queue, self.materials.iter().chain(self.material_debug.materials()));
+        }
+
+        self.objects = objects;
+        let shadow_cameras = &self.shadow_camera_buffers;
+        self.shadow_camera_bind_groups =
+            create_shadow_camera_bind_groups(&self.device, &self.caster_layout, shadow_cameras, &self.objects);
+        self.lights.bind_objects(&self.device, &self.caster_layout, &self.objects);
+        self.overdraw.bind_objects(&self.device, &self.caster_layout, (&self.camera_buffer, &self.objects));
+        self.rebuild_camera_bind_group();
+        self.reflections_dirty = !self.reflections.probes().is_empty();
+        self.bake_probes |= self.options.probes;
+
+        self.asset_watcher.unwatch(AssetKind::Texture);
+        for path in texture_files.keys() {
+            self.asset_watcher.watch(AssetKind::Texture, path, Renderer::reload_texture);
+        }
+        self.texture_files = texture_files;
+        Ok(())
+    }
+
+    /// Recompiles the material pipelines from the shader at `path`, keeping the running
+    /// ones if it does not compile.
+    fn reload_shader(&mut self, path: &Path) {
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                log::warn!("Failed to read {}: {}", path.display(), e);
+                return;
+            }
+        };
+        match self.pipeline_cache.set_source(&self.device, &source) {
+            Ok(()) => log::info!("Recompiled {} material pipelines", self.pipeline_cache.len()),
+            Err(e) => log::warn!("Keeping the previous shader, {} does not compile: {}", path.display(), e),
+        }
+    }
+
+    /// Uploads the texture file at `path` again into every material slot sampling it.
+    fn reload_texture(&mut self, path: &Path) {
+        let Some(uses) = self.texture_files.get(path) else {
+            return;
+        };
+        let mut texture = match ModelTexture::load(path) {
+            Ok(texture) => texture,
+            Err(e) => {
+                log::warn!("{:#}", e);
+                return;
+            }
+        };
+        // The models held in memory get it too, for a rebuild after a device loss.
+        for (model, _) in &mut self.scene_data.loaded.models {
+            let files = model.textures.iter_mut().zip(&model.texture_paths);
+            for (kept, _) in files.filter(|(_, file)| file.as_deref() == Some(path)) {
+                *kept = ModelTexture {
+                    format: kept.format,
+                    ..texture.clone()
+                };
+            }
+        }
+        for &(index, slot) in uses {
+            let material = &mut self.materials[index];
+            if let Some((width, height, format)) = material.textures[slot].source {
+                texture.format = format;
+                material.texture_bytes = material.texture_bytes.saturating_sub(width as u64 * height as u64 * 4);
+            }
+            material.texture_bytes += texture.data.len() as u64;
+            let uploaded = MaterialTexture::upload(&self.device, &self.queue, TEXTURE_LABELS[slot], &texture);
+            material.set_texture(&self.device, &self.material_layout, slot, uploaded);
+        }
+        if let Some(bindless) = &mut self.bindless {
+            let materials = self.materials.iter().chain(self.material_debug.materials());
+            bindless.update(&self.device, &self.queue, materials);
+        }
+    }
+
+    /// Camera bind groups for the faces of a cube capture.
+    fn capture_bind_groups(&self, capture: &CubeCapture) -> Vec<wgpu::BindGroup> {
+        capture
+            .face_buffers
+            .iter()
+            .map(|buffer| {
+                create_camera_bind_group(
+                    &self.device,
+                    &self.camera_bind_group_layout,
+                    (buffer, &self.objects),
+                    (&self.shadow_texture_view, &self.shadow_sampler),
+                    (
+                        environment_view(&self.env_texture_view, self.sky.as_ref(), self.atmosphere.as_ref()),
+                        &self.env_sampler,
+                    ),
+                    (&self.scene_depth_view, &self.ssao.view, self.ssgi.view(), self.fog.view(), self.rt_shadow_view()),
+                    (&self.brdf_lut_view, &self.probes, &self.reflections, self.gi_views()),
+                )
+            })
+            .collect()
+    }
+
+    /// Draws the sky and opaque scene into each face of `capture`, whose cameras must
+    /// already be written.
+    fn capture_cube(&self, encoder: &mut wgpu::CommandEncoder, capture: &CubeCapture, bind_groups: &[wgpu::BindGroup]) {
+        for (view, bind_group) in capture.face_views.iter().zip(bind_groups) {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Cube Capture Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &capture.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(capture.depth_mode.far()),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.set_bind_group(2, &self.lights.bind_group, &[]);
+            pass.set_bind_group(3, &self.evsm.bind_group, &[]);
+            pass.set_pipeline(&self.sky_pipeline);
+            pass.draw(0..3, 0..1);
+            draw_opaque_meshes(
+                &mut pass,
+                (&self.meshes, self.draw_list.opaque(), &self.geometry_pool),
+                DrawMaterials::new(&self.materials, None, self.bindless.as_ref()),
+                &self.material_meta,
+                &self.pipeline_cache,
+                None,
+                (ShaderFeatures::empty(), false, false),
+            );
+        }
+    }
+
+    /// Captures every probe's surroundings with the current lighting and shadows and
+    /// projects them to SH. Baking again while probes are in use adds a light bounce.
+    fn bake_light_probes(&mut self) {
+        let _span = profiler::scope("bake probes");
+        let started = Instant::now();
+        let grid = self.probes.grid;
+        let bind_groups = self.capture_bind_groups(&self.probes.capture);
+        let zfar = self.scene_bounds.radius() * 2.0 + 1.0;
+
+        // Probes are submitted one at a time since each rewrites the face cameras.
+        for probe in 0..grid.probe_count() {
+            self.probes.write_face_cameras(&self.queue, &self.camera_uniform, probe, zfar);
+            let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Probe Bake Encoder"),
+            });
+            self.capture_cube(&mut encoder, &self.probes.capture, &bind_groups);
+            self.probes.project(&mut encoder, &self.queue, probe);
+            self.queue.submit(std::iter::once(encoder.finish()));
+        }
+        self.probes.finish_bake(&self.queue);
+        self.device.poll(wgpu::Maintain::Wait);
+        log::info!(
+            "Baked {} light probes ({}x{}x{}) in {:.2?}",
+            grid.probe_count(),
+            grid.counts[0],
+            grid.counts[1],
+            grid.counts[2],
+            started.elapsed()
+        );
+    }
+
+    /// Re-renders every reflection probe's cube map and its roughness mips.
+    fn capture_reflections(&mut self) {
+        let _span = profiler::scope("capture reflections");
+        let started = Instant::now();
+        let bind_groups = self.capture_bind_groups(&self.reflections.capture);
+        let zfar = self.scene_bounds.radius() * 2.0 + 1.0;
+        for index in 0..self.reflections.probes().len() {
+            let position = self.reflections.probes()[index].position;
+            self.reflections.capture.write_cameras(&self.queue, &self.camera_uniform, position, self.camera.znear, zfar);
+            let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Reflection Capture Encoder"),
+            });
+            self.capture_cube(&mut encoder, &self.reflections.capture, &bind_groups);
+            self.reflections.store_capture(&self.device, &mut encoder, index);
+            self.queue.submit(std::iter::once(encoder.finish()));
+        }
+        self.reflections.upload(&self.queue);
+        log::info!(
+            "Captured {} reflection probes in {:.2?}",
+            self.reflections.probes().len(),
+            started.elapsed()
+        );
+    }
+
+    fn rt_shadow_view(&self) -> &wgpu::TextureView {
+        self.rt_shadows.as_ref().map_or(&self.rt_shadow_placeholder, RtShadows::view)
+    }
+
+    fn gi_views(&self) -> [&wgpu::TextureView; 4] {
+        let [irradiance, visibility] = match &self.ddgi {
+            Some(ddgi) => [ddgi.irradiance_view(), ddgi.visibility_view()],
+            None => [&self.ddgi_placeholder; 2],
+        };
+        let voxels = self.vct.as_ref().map_or(&self.vct_placeholder, |vct| vct.radiance_view());
+        let lightmap = self.lightmaps.as_ref().map_or(&self.lightmap_placeholder, Lightmaps::view);
+        [irradiance, visibility, voxels, lightmap]
+    }
+
+    fn rebuild_camera_bind_group(&mut self) {
+        self.fog
+            .set_shadow_map(&self.device, &self.camera_buffer, (&self.shadow_texture_view, &self.shadow_sampler));
+        if let Some(ddgi) = &mut self.ddgi {
+            ddgi.set_environment(
+                &self.device,
+                environment_view(&self.env_texture_view, self.sky.as_ref(), self.atmosphere.as_ref()),
+            );
+        }
+        self.camera_bind_group = self.camera_bind_group_for(&self.camera_buffer);
+        let viewport_groups: Vec<_> = self.viewports.iter().map(|v| self.camera_bind_group_for(&v.buffer)).collect();
+        for (viewport, bind_group) in self.viewports.iter_mut().zip(viewport_groups) {
+            viewport.bind_group = bind_group;
+        }
+    }
+
+    /// The camera bind group with `camera_buffer` for the uniform and the renderer's
+    /// shadow, environment, screen-space and indirect lighting resources.
+    fn camera_bind_group_for(&self, camera_buffer: &wgpu::Buffer) -> wgpu::BindGroup {
+        create_camera_bind_group(
+            &self.device,
+            &self.camera_bind_group_layout,
+            (camera_buffer, &self.objects),
+            (&self.shadow_texture_view, &self.shadow_sampler),
+            (
+                environment_view(&self.env_texture_view, self.sky.as_ref(), self.atmosphere.as_ref()),
+                &self.env_sampler,
+            ),
+            (&self.scene_depth_view, &self.ssao.view, self.ssgi.view(), self.fog.view(), self.rt_shadow_view()),
+            (&self.brdf_lut_view, &self.probes, &self.reflections, self.gi_views()),
+        )
+    }
+
+    /// Adds a camera drawn over its region of the main view, returning its index.
+    pub fn add_viewport(&mut self, viewport: Viewport) -> usize {
+        let buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Viewport Camera Buffer"),
+            contents: bytemuck::cast_slice(&[self.camera_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = self.camera_bind_group_for(&buffer);
+        if self.viewport_depth.is_none() {
+            self.viewport_depth = Some(self.create_viewport_depth());
+        }
+        self.viewports.push(SceneViewport {
+            viewport,
+            buffer,
+            bind_group,
+        });
+        self.viewports.len() - 1
+    }
+
+    pub fn viewport_mut(&mut self, index: usize) -> Option<&mut Viewport> {
+        self.viewports.get_mut(index).map(|v| &mut v.viewport)
+    }
+
+    /// Removes every viewport, leaving the main view alone.
+    pub fn clear_viewports(&mut self) {
+        self.viewports.clear();
+        self.stereo = None;
+    }
+
+    fn create_viewport_depth(&self) -> (wgpu::Texture, wgpu::TextureView) {
+        let (width, height) = self.render_size();
+        let texture =
+            create_depth_texture(&self.device, width, height, "Viewport Depth", wgpu::TextureUsages::RENDER_ATTACHMENT);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view)
+    }
+
+    /// Draws each viewport's camera into its region of the HDR target: the sky and the
+    /// opaque meshes, over a depth buffer of their own.
+    fn record_viewports(&self, encoder: &mut wgpu::CommandEncoder) -> DrawCount {
+        let mut count = DrawCount::default();
+        let Some((_, depth_view)) = &self.viewport_depth else {
+            return count;
+        };
+        let drawn = |(_, m): &(usize, &SceneMesh)| m.shadow != ShadowRole::Proxy && m.drawn;
+        let order: Vec<usize> = self.meshes.iter().enumerate().filter(drawn).map(|(i, _)| i).collect();
+        for v in &self.viewports {
+            let Some([x, y, width, height]) = v.viewport.pixel_rect(self.render_size()) else {
+                continue;
+            };
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Viewport Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.hdr_target.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.camera.depth_mode.far()),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            pass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+            pass.set_scissor_rect(x, y, width, height);
+            pass.set_bind_group(0, &v.bind_group, &[]);
+            pass.set_bind_group(2, &self.lights.bind_group, &[]);
+            pass.set_bind_group(3, &self.evsm.bind_group, &[]);
+            pass.set_pipeline(&self.sky_pipeline);
+            pass.draw(0..3, 0..1);
+            count += draw_opaque_meshes(
+                &mut pass,
+                (&self.meshes, &order, &self.geometry_pool),
+                self.material_debug.apply(&self.materials, self.bindless.as_ref()),
+                &self.material_meta,
+                &self.pipeline_cache,
+                None,
+                (ShaderFeatures::empty(), false, self.gpu_culling.is_some()),
+            );
+        }
+        count
+    }
+
+    /// Renders the shot matrix of `--batch=` offscreen, with the crash dialog off since
+    /// nobody is watching. False when there is none to render.
+    pub fn run_batch(&mut self) -> Result<bool> {
+        let Some(matrix) = self.batch.take() else {
+            return Ok(false);
+        };
+        crash::set_dialog_enabled(false);
+        self.shoot_matrix(&matrix)?;
+        Ok(true)
+    }
+
+    /// Renders every camera bookmark under every variant. The scene is frozen while
+    /// shooting so the images only differ by camera and variant.
+    fn shoot_matrix(&mut self, matrix: &ShotMatrix) -> Result<()> {
+        self.animation_player.playing = false;
+        self.sun.animate = false;
+        // Each shot is rendered once, so exposure would not settle; variants set it instead.
+        self.auto_exposure.enabled = false;
+        let cameras: Vec<(String, CameraState)> = if matrix.cameras.is_empty() {
+            vec![("default".to_string(), self.snapshot().camera)]
+        } else {
+            matrix.cameras.iter().map(|b| (b.name.clone(), b.camera)).collect()
+        };
+        let default_variant = batch::Variant {
+            name: "default".to_string(),
+            hdr: None,
+            env_intensity: None,
+            ev100: None,
+        };
+        let variants = if matrix.variants.is_empty() {
+            std::slice::from_ref(&default_variant)
+        } else {
+            &matrix.variants[..]
+        };
+
+        let (env_intensity, ev100) = (self.env_intensity, self.ev100);
+        for variant in variants {
+            if let Some(hdr) = &variant.hdr {
+                self.set_environment(hdr);
+            }
+            self.env_intensity = variant.env_intensity.unwrap_or(env_intensity);
+            self.ev100 = variant.ev100.unwrap_or(ev100);
+            for (name, camera) in &cameras {
+                self.apply_camera(camera);
+                self.update();
+                let path = matrix
+                    .output
+                    .join(batch::file_stem(&variant.name))
+                    .join(format!("{}.png", batch::file_stem(name)));
+                self.capture(&path)?;
+                log::info!("Wrote {}", path.display());
+            }
+        }
+        log::info!(
+            "Batch finished: {} images in {}",
+            cameras.len() * variants.len(),
+            matrix.output.display()
+        );
+        Ok(())
+    }
+}
+
@@ -0,0 +1,317 @@
+use crate::bvh::Bvh;
+use crate::camera::DepthMode;
+use crate::lights::DirectionalLight;
+use crate::pipelines::depth_shader;
+
+const SHADOW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+/// Angular radius of the sun's disk in radians; the real sun is about 0.0047.
+pub const DEFAULT_SUN_ANGLE: f32 = 0.01;
+/// Ray origins move off the surface by this much per unit of view distance.
+const NORMAL_OFFSET: f32 = 0.002;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct RtParams {
+    sun_direction: [f32; 4],
+    settings: [f32; 4],
+}
+
+struct RtTargets {
+    raw: wgpu::Texture,
+    raw_view: wgpu::TextureView,
+    output: wgpu::Texture,
+    output_view: wgpu::TextureView,
+}
+
+impl RtTargets {
+    fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let texture = |label: &str| {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: width.div_ceil(2),
+                    height: height.div_ceil(2),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: SHADOW_FORMAT,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            (texture, view)
+        };
+        let (raw, raw_view) = texture("RT Shadows Raw");
+        let (output, output_view) = texture("RT Shadows");
+        Self {
+            raw,
+            raw_view,
+            output,
+            output_view,
+        }
+    }
+}
+
+struct RtBuffers {
+    params: wgpu::Buffer,
+    nodes: wgpu::Buffer,
+    triangles: wgpu::Buffer,
+}
+
+/// Sun shadows traced per half-resolution pixel against the static scene BVH and
+/// blurred into `view`, which the main pass uses instead of the cascaded shadow maps.
+pub struct RtShadows {
+    pub enabled: bool,
+    pub sun_angle: f32,
+    targets: RtTargets,
+    node_count: u32,
+    buffers: RtBuffers,
+    /// Trace, then denoise.
+    layouts: [wgpu::BindGroupLayout; 2],
+    bind_groups: [wgpu::BindGroup; 2],
+    trace_pipeline: wgpu::ComputePipeline,
+    denoise_pipeline: wgpu::ComputePipeline,
+}
+
+impl RtShadows {
+    pub fn new(
+        device: &wgpu::Device,
+        camera_buffer: &wgpu::Buffer,
+        depth_view: &wgpu::TextureView,
+        bvh: &Bvh,
+        size: (u32, u32),
+        depth_mode: DepthMode,
+    ) -> Self {
+        let targets = RtTargets::new(device, size.0, size.1);
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("RT Shadows Params Buffer"),
+            size: std::mem::size_of::<RtParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let (node_buffer, triangle_buffer) = bvh.create_buffers(device, "RT Shadows");
+
+        let entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty,
+            count: None,
+        };
+        let uniform = wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        };
+        let storage = wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        };
+        let depth = wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            sample_type: wgpu::TextureSampleType::Depth,
+        };
+        let output = wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::WriteOnly,
+            format: SHADOW_FORMAT,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        };
+        let trace_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("rt_shadows_trace_bind_group_layout"),
+            entries: &[
+                entry(0, uniform),
+                entry(1, depth),
+                entry(2, uniform),
+                entry(3, storage),
+                entry(4, storage),
+                entry(5, output),
+            ],
+        });
+        let denoise_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("rt_shadows_denoise_bind_group_layout"),
+            entries: &[
+                entry(0, uniform),
+                entry(1, depth),
+                entry(
+                    6,
+                    wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                ),
+                entry(7, output),
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("RT Shadows Shader"),
+            source: wgpu::ShaderSource::Wgsl(depth_shader(include_str!("rt_shadows.wgsl"), depth_mode).into()),
+        });
+        let pipeline = |label: &str, layout: &wgpu::BindGroupLayout, entry_point: &str| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("RT Shadows Pipeline Layout"),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point,
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        let trace_pipeline = pipeline("RT Shadows Trace Pipeline", &trace_layout, "cs_trace");
+        let denoise_pipeline = pipeline("RT Shadows Denoise Pipeline", &denoise_layout, "cs_denoise");
+
+        let buffers = RtBuffers {
+            params: params_buffer,
+            nodes: node_buffer,
+            triangles: triangle_buffer,
+        };
+        let layouts = [trace_layout, denoise_layout];
+        let bind_groups = create_bind_groups(device, &layouts, camera_buffer, depth_view, &buffers, &targets);
+        Self {
+            enabled: true,
+            sun_angle: DEFAULT_SUN_ANGLE,
+            targets,
+            node_count: bvh.nodes.len() as u32,
+            buffers,
+            layouts,
+            bind_groups,
+            trace_pipeline,
+            denoise_pipeline,
+        }
+    }
+
+    /// Reallocates both targets for a new size, returning the old textures so they can
+    /// be destroyed once the GPU is done with them.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        camera_buffer: &wgpu::Buffer,
+        depth_view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+    ) -> [wgpu::Texture; 2] {
+        let old = std::mem::replace(&mut self.targets, RtTargets::new(device, width, height));
+        self.bind_groups =
+            create_bind_groups(device, &self.layouts, camera_buffer, depth_view, &self.buffers, &self.targets);
+        [old.raw, old.output]
+    }
+
+    /// Visibility of the sun per half-resolution pixel.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.targets.output_view
+    }
+
+    /// Traces and blurs this frame's shadows; the depth view must hold this frame's
+    /// prepass.
+    pub fn record(&self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, sun: &DirectionalLight) {
+        let params = RtParams {
+            sun_direction: [sun.direction.x, sun.direction.y, sun.direction.z, self.sun_angle.tan()],
+            settings: [self.node_count as f32, NORMAL_OFFSET, 0.0, 0.0],
+        };
+        queue.write_buffer(&self.buffers.params, 0, bytemuck::cast_slice(&[params]));
+
+        let size = self.targets.output.size();
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("RT Shadows"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.trace_pipeline);
+        pass.set_bind_group(0, &self.bind_groups[0], &[]);
+        pass.dispatch_workgroups(size.width.div_ceil(8), size.height.div_ceil(8), 1);
+        pass.set_pipeline(&self.denoise_pipeline);
+        pass.set_bind_group(0, &self.bind_groups[1], &[]);
+        pass.dispatch_workgroups(size.width.div_ceil(8), size.height.div_ceil(8), 1);
+    }
+}
+
+fn create_bind_groups(
+    device: &wgpu::Device,
+    layouts: &[wgpu::BindGroupLayout; 2],
+    camera_buffer: &wgpu::Buffer,
+    depth_view: &wgpu::TextureView,
+    buffers: &RtBuffers,
+    targets: &RtTargets,
+) -> [wgpu::BindGroup; 2] {
+    let trace = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("rt_shadows_trace_bind_group"),
+        layout: &layouts[0],
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(depth_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: buffers.params.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: buffers.nodes.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: buffers.triangles.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: wgpu::BindingResource::TextureView(&targets.raw_view),
+            },
+        ],
+    });
+    let denoise = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("rt_shadows_denoise_bind_group"),
+        layout: &layouts[1],
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(depth_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: wgpu::BindingResource::TextureView(&targets.raw_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 7,
+                resource: wgpu::BindingResource::TextureView(&targets.output_view),
+            },
+        ],
+    });
+    [trace, denoise]
+}
+
+/// A 1x1 fully lit stand-in bound while ray-traced shadows are off.
+pub fn placeholder_view(device: &wgpu::Device) -> wgpu::TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some("RT Shadows Placeholder"),
+            size: wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SHADOW_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
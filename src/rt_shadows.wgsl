@@ -0,0 +1,197 @@
+// Ray-traced sun shadows: one shadow ray per half-resolution pixel of the depth prepass,
+// traced against the scene BVH towards a jittered point on the sun's disk, followed by a
+// depth-aware blur that turns the jitter into soft penumbrae.
+
+// Prefix of CameraUniform in shader.wgsl.
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    view_inv: mat4x4<f32>,
+    proj_inv: mat4x4<f32>,
+    position: vec4<f32>,
+};
+
+struct RtParams {
+    // xyz: direction the sun's light travels, w: tangent of the sun's angular radius.
+    sun_direction: vec4<f32>,
+    // x: BVH node count, y: ray origin offset per unit of view distance.
+    settings: vec4<f32>,
+};
+
+struct BvhNode {
+    min: vec3<f32>,
+    first: u32,
+    max: vec3<f32>,
+    count: u32,
+};
+
+struct Triangle {
+    a: vec4<f32>,
+    b: vec4<f32>,
+    c: vec4<f32>,
+    albedo: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(0) @binding(1)
+var depth_map: texture_depth_2d;
+
+@group(0) @binding(2)
+var<uniform> params: RtParams;
+
+@group(0) @binding(3)
+var<storage, read> nodes: array<BvhNode>;
+
+@group(0) @binding(4)
+var<storage, read> triangles: array<Triangle>;
+
+@group(0) @binding(5)
+var raw_out: texture_storage_2d<rgba8unorm, write>;
+
+@group(0) @binding(6)
+var raw_in: texture_2d<f32>;
+
+@group(0) @binding(7)
+var shadow_out: texture_storage_2d<rgba8unorm, write>;
+
+const STACK_SIZE: u32 = 32u;
+const NO_HIT: f32 = 3.4e38;
+
+fn intersect_box(origin: vec3<f32>, inv_dir: vec3<f32>, node: BvhNode) -> bool {
+    let t0 = (node.min - origin) * inv_dir;
+    let t1 = (node.max - origin) * inv_dir;
+    let near = max(max(min(t0.x, t1.x), min(t0.y, t1.y)), min(t0.z, t1.z));
+    let far = min(min(max(t0.x, t1.x), max(t0.y, t1.y)), max(t0.z, t1.z));
+    return far >= max(near, 0.0);
+}
+
+// Möller-Trumbore, two-sided; returns NO_HIT on a miss.
+fn intersect_triangle(origin: vec3<f32>, dir: vec3<f32>, tri: Triangle) -> f32 {
+    let e1 = tri.b.xyz - tri.a.xyz;
+    let e2 = tri.c.xyz - tri.a.xyz;
+    let p = cross(dir, e2);
+    let det = dot(e1, p);
+    if abs(det) < 1e-10 {
+        return NO_HIT;
+    }
+    let inv_det = 1.0 / det;
+    let s = origin - tri.a.xyz;
+    let u = dot(s, p) * inv_det;
+    if u < 0.0 || u > 1.0 {
+        return NO_HIT;
+    }
+    let q = cross(s, e1);
+    let v = dot(dir, q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return NO_HIT;
+    }
+    let t = dot(e2, q) * inv_det;
+    return select(NO_HIT, t, t > 1e-4);
+}
+
+// Any hit along the ray.
+fn occluded(origin: vec3<f32>, dir: vec3<f32>) -> bool {
+    if params.settings.x < 1.0 {
+        return false;
+    }
+    let inv_dir = 1.0 / select(dir, vec3<f32>(1e-8), abs(dir) < vec3<f32>(1e-8));
+    var stack: array<u32, STACK_SIZE>;
+    var top = 1u;
+    stack[0] = 0u;
+    while top > 0u {
+        top -= 1u;
+        let node = nodes[stack[top]];
+        if !intersect_box(origin, inv_dir, node) {
+            continue;
+        }
+        if node.count > 0u {
+            for (var i = node.first; i < node.first + node.count; i++) {
+                if intersect_triangle(origin, dir, triangles[i]) < NO_HIT {
+                    return true;
+                }
+            }
+        } else if top + 2u <= STACK_SIZE {
+            stack[top] = node.first;
+            stack[top + 1u] = node.first + 1u;
+            top += 2u;
+        }
+    }
+    return false;
+}
+
+fn view_position(pixel: vec2<i32>) -> vec3<f32> {
+    let size = vec2<i32>(textureDimensions(depth_map));
+    let p = clamp(pixel, vec2<i32>(0), size - 1);
+    let depth = textureLoad(depth_map, p, 0);
+    let uv = (vec2<f32>(p) + 0.5) / vec2<f32>(size);
+    let view = camera.proj_inv * vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    return view.xyz / view.w;
+}
+
+fn interleaved_gradient_noise(p: vec2<f32>) -> f32 {
+    return fract(52.9829189 * fract(dot(p, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn cs_trace(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(raw_out);
+    if any(id.xy >= size) {
+        return;
+    }
+    let pixel = vec2<i32>(id.xy) * 2;
+    if textureLoad(depth_map, pixel, 0) == FAR_DEPTH {
+        textureStore(raw_out, id.xy, vec4<f32>(1.0));
+        return;
+    }
+    let P = view_position(pixel);
+    // Take each derivative from the neighbour on the same surface.
+    let l = view_position(pixel - vec2<i32>(1, 0));
+    let r = view_position(pixel + vec2<i32>(1, 0));
+    let u = view_position(pixel - vec2<i32>(0, 1));
+    let d = view_position(pixel + vec2<i32>(0, 1));
+    let dx = select(P - l, r - P, abs(r.z - P.z) < abs(P.z - l.z));
+    let dy = select(P - u, d - P, abs(d.z - P.z) < abs(P.z - u.z));
+    let N = normalize((camera.view_inv * vec4<f32>(normalize(cross(dy, dx)), 0.0)).xyz);
+    let world = (camera.view_inv * vec4<f32>(P, 1.0)).xyz;
+
+    let L = -normalize(params.sun_direction.xyz);
+    if dot(N, L) <= 0.0 {
+        textureStore(raw_out, id.xy, vec4<f32>(0.0));
+        return;
+    }
+    // Jitter the ray over the sun's disk; the blur averages neighbouring rays.
+    let tangent = normalize(cross(L, select(vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(1.0, 0.0, 0.0), abs(L.y) > 0.9)));
+    let bitangent = cross(L, tangent);
+    let noise = interleaved_gradient_noise(vec2<f32>(id.xy));
+    let angle = fract(noise * 7.0 + 0.618034) * 6.28318530718;
+    let disk = sqrt(noise) * params.sun_direction.w * vec2<f32>(cos(angle), sin(angle));
+    let dir = normalize(L + tangent * disk.x + bitangent * disk.y);
+
+    let origin = world + N * (-P.z * params.settings.y);
+    let visible = select(1.0, 0.0, occluded(origin, dir));
+    textureStore(raw_out, id.xy, vec4<f32>(visible));
+}
+
+// 5x5 blur of the half-resolution rays that skips samples across depth discontinuities.
+@compute @workgroup_size(8, 8, 1)
+fn cs_denoise(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = vec2<i32>(textureDimensions(raw_in));
+    if any(vec2<i32>(id.xy) >= size) {
+        return;
+    }
+    let pixel = vec2<i32>(id.xy);
+    let center = view_position(pixel * 2).z;
+    var sum = 0.0;
+    var total = 0.0;
+    for (var y = -2; y <= 2; y++) {
+        for (var x = -2; x <= 2; x++) {
+            let p = clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            let z = view_position(p * 2).z;
+            let w = max(0.0, 1.0 - abs(z - center) / (abs(center) * 0.05 + 1e-3));
+            sum += textureLoad(raw_in, p, 0).r * w;
+            total += w;
+        }
+    }
+    textureStore(shadow_out, id.xy, vec4<f32>(sum / max(total, 1e-4)));
+}
@@ -0,0 +1,162 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use cgmath::Matrix4;
+
+use crate::aabb::Aabb;
+use crate::assets::AssetServer;
+use crate::jobs;
+use crate::layout::{self, Layout, SceneCamera, SceneEntry, SceneSun};
+use crate::lights::LightSet;
+use crate::model::Model;
+use crate::profiler;
+use crate::reflections::ReflectionProbe;
+use crate::snapshot::{InstanceState, Snapshot};
+
+/// Shown when nothing else is.
+pub const DEFAULT_MODEL: &str = "assets/models/environment/IntelSponza/NewSponza_Main_glTF_003.gltf";
+
+/// A model to show, or a scene file listing several.
+#[derive(Clone, Debug)]
+pub enum SceneSource {
+    Model(SceneEntry),
+    File(PathBuf),
+}
+
+impl SceneSource {
+    /// Reads a model argument (see [`SceneEntry::from_arg`]), or a scene file by its
+    /// `.json` or `.toml` extension.
+    pub fn from_arg(arg: &str) -> Self {
+        if layout::is_scene_file(arg) {
+            Self::File(PathBuf::from(arg))
+        } else {
+            Self::Model(SceneEntry::from_arg(arg))
+        }
+    }
+}
+
+/// What scene files set besides the models, lights, layout and environment.
+#[derive(Default)]
+pub struct SceneSettings {
+    pub sun: Option<SceneSun>,
+    pub camera: Option<SceneCamera>,
+    pub reflection_probes: Vec<ReflectionProbe>,
+    pub ev100: Option<f32>,
+    pub env_nits: Option<f32>,
+}
+
+/// What to show: the models and where they go, plus lights placed on their own.
+pub struct Scene {
+    pub entries: Vec<SceneEntry>,
+    /// How entries without a transform of their own are arranged.
+    pub layout: Layout,
+    pub lights: LightSet,
+    /// Environment HDR; None looks for one next to the models.
+    pub environment: Option<PathBuf>,
+}
+
+/// A scene with its models loaded and moved into place.
+pub struct LoadedScene {
+    /// Each model, already transformed, with the placement applied to it.
+    pub models: Vec<(Model, Matrix4<f32>)>,
+    /// Where each model came from, as snapshots record it.
+    pub instances: Vec<InstanceState>,
+    pub bounds: Aabb,
+    /// The scene's own lights plus those of every model.
+    pub lights: LightSet,
+}
+
+impl Scene {
+    /// Puts the models of `sources` together in order, reading the scene files among them.
+    /// The first scene file with a layout decides it, otherwise placed models put the rest
+    /// at their origins; the last file to give an environment, sun, camera or exposure
+    /// wins, and lights and reflection probes add up.
+    pub fn from_sources(sources: &[SceneSource]) -> Result<(Self, SceneSettings)> {
+        let mut entries = Vec::new();
+        let mut layout = None;
+        let mut lights = LightSet::default();
+        let mut environment = None;
+        let mut settings = SceneSettings::default();
+        for source in sources {
+            let path = match source {
+                SceneSource::Model(entry) => {
+                    entries.push(entry.clone());
+                    continue;
+                }
+                SceneSource::File(path) => path,
+            };
+            let file = layout::load_scene_file(path)?;
+            layout = layout.or(file.layout);
+            environment = file.environment.or(environment);
+            entries.extend(file.entries);
+            lights.extend(&file.lights);
+            settings.sun = file.sun.or(settings.sun);
+            settings.camera = file.camera.or(settings.camera);
+            settings.reflection_probes.extend(file.reflection_probes);
+            settings.ev100 = file.ev100.or(settings.ev100);
+            settings.env_nits = file.env_nits.or(settings.env_nits);
+        }
+        let placed = entries.iter().any(|e| e.transform.is_some());
+        let layout = layout.unwrap_or(if placed { Layout::Explicit } else { Layout::Row });
+        let scene = Self {
+            entries,
+            layout,
+            lights,
+            environment,
+        };
+        Ok((scene, settings))
+    }
+
+    /// Adds the models of `snapshot` where it placed them.
+    pub fn add_snapshot_models(&mut self, snapshot: &Snapshot) {
+        for instance in &snapshot.instances {
+            let mut entry = SceneEntry::new(&instance.path);
+            entry.transform = Some(instance.transform);
+            self.entries.push(entry);
+        }
+        self.layout = Layout::Explicit;
+    }
+
+    /// Loads every entry through `assets`, the models side by side on the job threads, and
+    /// places them.
+    pub fn load(&self, assets: &AssetServer) -> Result<LoadedScene> {
+        let load_span = profiler::scope("load scene");
+        let models = jobs::map(&self.entries, |entry| assets.load_model(entry));
+        let models = models.into_iter().collect::<Result<Vec<_>>>()?;
+        drop(load_span);
+        let bounds: Vec<Aabb> = models.iter().map(Model::bounds).collect();
+        let explicit: Vec<_> = self.entries.iter().map(|e| e.transform).collect();
+        let placements = layout::arrange(self.layout, &bounds, &explicit);
+        if models.len() > 1 {
+            log::info!("Placed {} models with the {:?} layout", models.len(), self.layout);
+        }
+
+        let instances = self
+            .entries
+            .iter()
+            .zip(&placements)
+            .map(|(e, &transform)| InstanceState {
+                path: e.path.clone(),
+                transform,
+            })
+            .collect();
+
+        let mut scene_bounds = Aabb::empty();
+        let mut lights = self.lights.clone();
+        let mut loaded_models = Vec::new();
+        for (mut m, placement) in models.into_iter().zip(placements) {
+            if placement != Matrix4::from_scale(1.0) {
+                m.transform(placement);
+            }
+            scene_bounds.union(&m.bounds());
+            lights.extend(&m.lights);
+            loaded_models.push((m, placement));
+        }
+        Ok(LoadedScene {
+            models: loaded_models,
+            instances,
+            bounds: scene_bounds,
+            lights,
+        })
+    }
+}
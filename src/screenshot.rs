@@ -0,0 +1,223 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use half::f16;
+
+use crate::exr;
+
+/// Folder the screenshot key writes to.
+pub const SCREENSHOT_DIR: &str = "screenshots";
+/// Supersampling factors a screenshot can be rendered at.
+pub const SUPERSAMPLE_FACTORS: [u32; 3] = [1, 2, 4];
+
+/// A new file in [`SCREENSHOT_DIR`] named after the current time.
+pub fn next_path() -> PathBuf {
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+    let dir = Path::new(SCREENSHOT_DIR);
+    let mut path = dir.join(format!("dusk-{}.png", stamp));
+    let mut n = 1;
+    while path.exists() {
+        path = dir.join(format!("dusk-{}-{}.png", stamp, n));
+        n += 1;
+    }
+    path
+}
+
+/// A frame read back from the GPU.
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    /// The presented image, RGBA8 in sRGB.
+    pub pixels: Vec<u8>,
+    /// The linear scene before tonemapping, at the render size, when it was asked for.
+    pub hdr: Option<HdrImage>,
+}
+
+pub struct HdrImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[f32; 3]>,
+}
+
+impl Frame {
+    /// Shrinks the frame by `factor` in each axis; see [`downsample`].
+    pub fn downsample(self, factor: u32) -> Self {
+        let (width, height, pixels) = downsample(&self.pixels, self.width, self.height, factor);
+        let hdr = self.hdr.map(|hdr| hdr.downsample(factor));
+        Self { width, height, pixels, hdr }
+    }
+
+    /// Writes the image to `path` as a PNG, and the HDR one beside it as an .exr.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        image::save_buffer(path, &self.pixels, self.width, self.height, image::ExtendedColorType::Rgba8)
+            .with_context(|| format!("writing {}", path.display()))?;
+        if let Some(hdr) = &self.hdr {
+            exr::write_rgb(&path.with_extension("exr"), hdr.width, hdr.height, &hdr.pixels)?;
+        }
+        Ok(())
+    }
+}
+
+impl HdrImage {
+    fn downsample(self, factor: u32) -> Self {
+        if factor <= 1 {
+            return self;
+        }
+        let (width, height) = (self.width / factor, self.height / factor);
+        let samples = (factor * factor) as f32;
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0.0f32; 3];
+                for sy in y * factor..(y + 1) * factor {
+                    for sx in x * factor..(x + 1) * factor {
+                        let p = self.pixels[(sy * self.width + sx) as usize];
+                        for (s, c) in sum.iter_mut().zip(p) {
+                            *s += c;
+                        }
+                    }
+                }
+                pixels.push(sum.map(|s| s / samples));
+            }
+        }
+        Self { width, height, pixels }
+    }
+}
+
+/// A texture copied into a buffer the CPU can map once the copy has run.
+pub struct Readback {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_row: u32,
+    row_bytes: u32,
+}
+
+impl Readback {
+    /// Records a copy of all of `texture` into a new buffer.
+    pub fn new(device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) -> Self {
+        let size = texture.size();
+        let row_bytes = size.width * texture.format().block_copy_size(None).unwrap_or(4);
+        let padded_row = row_bytes.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Readback"),
+            size: (padded_row * size.height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(size.height),
+                },
+            },
+            size,
+        );
+        Self {
+            buffer,
+            width: size.width,
+            height: size.height,
+            padded_row,
+            row_bytes,
+        }
+    }
+
+    /// Starts mapping the buffer; `sender` gets the outcome.
+    pub fn map(&self, sender: Sender<Result<(), wgpu::BufferAsyncError>>) {
+        self.buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+    }
+
+    /// The texture's bytes without row padding, once mapped.
+    pub fn bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity((self.row_bytes * self.height) as usize);
+        {
+            let data = self.buffer.slice(..).get_mapped_range();
+            for row in data.chunks(self.padded_row as usize) {
+                bytes.extend_from_slice(&row[..self.row_bytes as usize]);
+            }
+        }
+        self.buffer.unmap();
+        bytes
+    }
+
+    /// RGBA8 pixels, swapping red and blue back for BGRA formats.
+    pub fn rgba8(&self, bgra: bool) -> Vec<u8> {
+        let mut pixels = self.bytes();
+        for p in pixels.chunks_exact_mut(4) {
+            if bgra {
+                p.swap(0, 2);
+            }
+            p[3] = 255;
+        }
+        pixels
+    }
+
+    /// The RGB of an RGBA16F texture.
+    pub fn hdr(&self) -> HdrImage {
+        let channel = |p: &[u8], i: usize| f16::from_le_bytes([p[i * 2], p[i * 2 + 1]]).to_f32();
+        let pixels = self.bytes().chunks_exact(8).map(|p| [channel(p, 0), channel(p, 1), channel(p, 2)]).collect();
+        HdrImage {
+            width: self.width,
+            height: self.height,
+            pixels,
+        }
+    }
+}
+
+fn to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Shrinks RGBA8 sRGB pixels by `factor` in each axis, averaging each block in linear
+/// light so edges do not come out darker than they should.
+fn downsample(pixels: &[u8], width: u32, height: u32, factor: u32) -> (u32, u32, Vec<u8>) {
+    if factor <= 1 {
+        return (width, height, pixels.to_vec());
+    }
+    let linear: Vec<f32> = (0..=255u8).map(|c| to_linear(c as f32 / 255.0)).collect();
+    let (out_width, out_height) = (width / factor, height / factor);
+    let samples = (factor * factor) as f32;
+    let mut out = Vec::with_capacity((out_width * out_height * 4) as usize);
+    for y in 0..out_height {
+        for x in 0..out_width {
+            let mut sum = [0.0f32; 3];
+            for sy in y * factor..(y + 1) * factor {
+                let row = (sy * width) as usize;
+                for sx in x * factor..(x + 1) * factor {
+                    let p = &pixels[(row + sx as usize) * 4..][..3];
+                    for (s, &c) in sum.iter_mut().zip(p) {
+                        *s += linear[c as usize];
+                    }
+                }
+            }
+            for s in sum {
+                out.push((to_srgb(s / samples) * 255.0).round().clamp(0.0, 255.0) as u8);
+            }
+            out.push(255);
+        }
+    }
+    (out_width, out_height, out)
+}
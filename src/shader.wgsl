@@ -1,9 +1,22 @@
 struct VertexOutput {
-    @builtin(position) clip_position: vec4<f32>,
+    // Invariant so the main pass matches the depth prepass exactly.
+    @builtin(position) @invariant clip_position: vec4<f32>,
     @location(0) world_position: vec3<f32>,
     @location(1) normal: vec3<f32>,
     @location(2) tex_coords: vec2<f32>,
     @location(3) view_depth: f32,
+#ifdef BONE_WEIGHTS
+    @location(4) bone_weight: f32,
+#endif
+#ifdef NORMAL_MAP
+    @location(5) tangent: vec4<f32>,
+#endif
+#ifdef LIGHTMAP
+    @location(6) lightmap_uv: vec2<f32>,
+#endif
+#ifdef BINDLESS
+    @location(7) @interpolate(flat) material_id: u32,
+#endif
 };
 
 struct SkyOut {
@@ -23,12 +36,32 @@ struct CameraUniform {
     light_view_proj_cascade1: mat4x4<f32>,
     light_view_proj_cascade2: mat4x4<f32>,
     light_view_proj_cascade3: mat4x4<f32>,
+    time: vec4<f32>,
+    shadow_params: vec4<f32>,
+    // x: cascade map resolution, y: cascade count, z: shadow normal offset.
+    shadow_map: vec4<f32>,
+    // x: 1 when ssao_map applies, y: 1 when ssgi_map applies, z: 1 when rt_shadow_map applies.
+    screen_space: vec4<f32>,
+    // xyz: voxel grid minimum corner, w: 1 when voxel_radiance applies.
+    voxel_min: vec4<f32>,
+    // xyz: voxel grid size, w: voxel size.
+    voxel_extent: vec4<f32>,
+    // x: 1 when fog_volume applies, y: first froxel slice distance, z: last slice distance.
+    fog: vec4<f32>,
+    // x: 1 when lightmap applies, y: exposure to apply to it.
+    lightmap: vec4<f32>,
+    // rgb: pre-exposed sun color and intensity, w: angular radius of the sun disk.
+    sun: vec4<f32>,
+    // x: debug view, 0 for regular shading.
+    debug: vec4<f32>,
 };
 
 struct Material {
     base_color: vec4<f32>,
     metallic_roughness: vec4<f32>,
     alpha_cutoff_flags: vec4<f32>,
+    // x: shadow normal offset override, negative to use camera.shadow_map.z.
+    shadow_bias: vec4<f32>,
 };
 
 @group(0) @binding(0)
@@ -46,6 +79,121 @@ var env_map: texture_2d<f32>;
 @group(0) @binding(4)
 var env_sampler: sampler;
 
+@group(0) @binding(5)
+var scene_depth: texture_depth_2d;
+
+@group(0) @binding(6)
+var brdf_lut: texture_2d<f32>;
+
+struct ProbeVolume {
+    // xyz: first probe, w: 1 when the probes are baked and enabled, or dynamic.
+    origin: vec4<f32>,
+    // w: 1 when the DDGI atlases replace the SH coefficients.
+    spacing: vec4<f32>,
+    counts: vec4<u32>,
+    // Nine L2 SH radiance coefficients per probe, x-fastest.
+    coefficients: array<vec4<f32>>,
+};
+
+@group(0) @binding(7)
+var<storage, read> probes: ProbeVolume;
+
+struct ReflectionProbe {
+    position: vec4<f32>,
+    box_min: vec4<f32>,
+    // w: distance inside the box over which the probe fades in.
+    box_max: vec4<f32>,
+};
+
+struct ReflectionProbes {
+    count: vec4<u32>,
+    probes: array<ReflectionProbe>,
+};
+
+@group(0) @binding(8)
+var reflection_cubes: texture_cube_array<f32>;
+
+@group(0) @binding(9)
+var<storage, read> reflection_probes: ReflectionProbes;
+
+@group(0) @binding(10)
+var reflection_sampler: sampler;
+
+@group(0) @binding(11)
+var ssao_map: texture_2d<f32>;
+
+// Half resolution diffuse bounce light.
+@group(0) @binding(12)
+var ssgi_map: texture_2d<f32>;
+
+// Octahedral irradiance and distance moment tiles of the dynamic probes.
+@group(0) @binding(13)
+var ddgi_irradiance: texture_2d<f32>;
+
+@group(0) @binding(14)
+var ddgi_visibility: texture_2d<f32>;
+
+// Sunlit scene voxels, premultiplied by coverage in alpha, with a mip chain for cones.
+@group(0) @binding(15)
+var voxel_radiance: texture_3d<f32>;
+
+// Volumetric fog froxels: in-scattered light from the camera in rgb, transmittance in alpha.
+@group(0) @binding(16)
+var fog_volume: texture_3d<f32>;
+
+// Baked sky and bounce light of static meshes, addressed by their lightmap UVs.
+@group(0) @binding(17)
+var lightmap: texture_2d<f32>;
+
+// Half resolution sun visibility traced against the scene BVH.
+@group(0) @binding(18)
+var rt_shadow_map: texture_2d<f32>;
+
+// Must match ObjectUniform in objects.rs.
+struct Object {
+    model: mat4x4<f32>,
+    normal: mat3x3<f32>,
+    // x: material id for bindless materials.
+    material: vec4<u32>,
+};
+
+// One per scene mesh, indexed by the instance index; also bound in the shadow caster group.
+@group(0) @binding(19)
+var<storage, read> objects: array<Object>;
+
+#ifdef BINDLESS
+// Every material at once, indexed by the object's material id; the fragment entry
+// points copy theirs into `material` before anything reads it.
+@group(1) @binding(0)
+var<storage, read> materials: array<Material>;
+
+// x: material id replacing every object's plus one, or 0.
+@group(1) @binding(2)
+var<uniform> material_replacement: vec4<u32>;
+
+// Base color, metallic-roughness and normal texture of each material, in that order.
+@group(1) @binding(1)
+var material_textures: binding_array<texture_2d<f32>>;
+
+@group(1) @binding(3)
+var material_sampler: sampler;
+
+// The deferred resolve never loads one and keeps the global shadow normal offset.
+var<private> material: Material = Material(vec4<f32>(1.0), vec4<f32>(1.0), vec4<f32>(0.0), vec4<f32>(-1.0));
+var<private> material_id: u32;
+
+fn sample_base_color(uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(material_textures[material_id * 3u], material_sampler, uv);
+}
+
+fn sample_metallic_roughness(uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(material_textures[material_id * 3u + 1u], material_sampler, uv);
+}
+
+fn sample_normal(uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(material_textures[material_id * 3u + 2u], material_sampler, uv);
+}
+#else
 @group(1) @binding(0)
 var<uniform> material: Material;
 
@@ -58,8 +206,130 @@ var metallic_roughness_texture: texture_2d<f32>;
 @group(1) @binding(3)
 var material_sampler: sampler;
 
+@group(1) @binding(4)
+var normal_texture: texture_2d<f32>;
+
+fn sample_base_color(uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(base_color_texture, material_sampler, uv);
+}
+
+fn sample_metallic_roughness(uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(metallic_roughness_texture, material_sampler, uv);
+}
+
+fn sample_normal(uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(normal_texture, material_sampler, uv);
+}
+#endif
+
+// The deferred resolve binds the G-buffer in place of the material.
+@group(1) @binding(5)
+var gbuffer_albedo: texture_2d<f32>;
+
+// xyz: world-space normal.
+@group(1) @binding(6)
+var gbuffer_normal: texture_2d<f32>;
+
+// x: unused, y: roughness, z: metallic.
+@group(1) @binding(7)
+var gbuffer_orm: texture_2d<f32>;
+
+// rgb: emissive, plus baked diffuse for lightmapped surfaces; a: 1 when lightmapped.
+@group(1) @binding(8)
+var gbuffer_emissive: texture_2d<f32>;
+
+@group(1) @binding(9)
+var gbuffer_depth: texture_depth_2d;
+
+struct PointLight {
+    position: vec3<f32>,
+    range: f32,
+    color: vec3<f32>,
+    intensity: f32,
+    shadow_layer: i32,
+};
+
+struct PointLights {
+    count: vec4<u32>,
+    lights: array<PointLight>,
+};
+
+@group(2) @binding(0)
+var<storage, read> point_lights: PointLights;
+
+@group(2) @binding(1)
+var<storage, read> point_shadow_matrices: array<mat4x4<f32>>;
+
+@group(2) @binding(2)
+var point_shadow_map: texture_depth_2d_array;
+
+@group(2) @binding(3)
+var point_shadow_sampler: sampler_comparison;
+
+struct SpotLight {
+    position: vec3<f32>,
+    range: f32,
+    direction: vec3<f32>,
+    intensity: f32,
+    color: vec3<f32>,
+    cos_outer: f32,
+    cos_inner: f32,
+    shadow_tile: i32,
+    // Layer in light_cookies, or -1.
+    cookie: i32,
+    view_proj: mat4x4<f32>,
+};
+
+struct SpotLights {
+    count: vec4<u32>,
+    lights: array<SpotLight>,
+};
+
+@group(2) @binding(4)
+var<storage, read> spot_lights: SpotLights;
+
+// 4x4 grid of tiles, one per shadowed spot light.
+@group(2) @binding(5)
+var spot_shadow_atlas: texture_depth_2d;
+
+struct DirectionalLight {
+    direction: vec3<f32>,
+    intensity: f32,
+    color: vec3<f32>,
+    // Layer in light_cookies, or -1.
+    cookie: i32,
+    // World units one repeat of the cookie covers.
+    cookie_size: f32,
+};
+
+// Light 0 is the primary light; its direction is camera.light_dir and it owns the cascades.
+struct DirectionalLights {
+    count: vec4<u32>,
+    lights: array<DirectionalLight>,
+};
+
+@group(2) @binding(6)
+var<storage, read> directional_lights: DirectionalLights;
+
+// Projected light patterns, one layer per cookie.
+@group(2) @binding(7)
+var light_cookies: texture_2d_array<f32>;
+
+@group(2) @binding(8)
+var cookie_sampler: sampler;
+
+// Blurred EVSM moments of the cascades, used when camera.shadow_params.x is set.
+@group(3) @binding(0)
+var evsm_moments: texture_2d_array<f32>;
+
+@group(3) @binding(1)
+var evsm_sampler: sampler;
+
 const PI: f32 = 3.14159265359;
-const SHADOW_MAP_SIZE: f32 = 4096.0;
+// Share of the centre's brightness the sun loses at its limb.
+const SUN_LIMB_DARKENING: f32 = 0.6;
+// Below the half-float maximum, so the sun disk cannot overflow the HDR targets.
+const MAX_HDR: f32 = 60000.0;
 
 const POISSON_DISK: array<vec2<f32>, 16> = array<vec2<f32>, 16>(
     vec2<f32>(-0.94201624, -0.39906216),
@@ -80,15 +350,20 @@ const POISSON_DISK: array<vec2<f32>, 16> = array<vec2<f32>, 16>(
     vec2<f32>(0.14383161, -0.14100790)
 );
 
+fn last_cascade() -> i32 {
+    return i32(camera.shadow_map.y) - 1;
+}
+
 fn select_cascade(view_depth: f32) -> i32 {
+    var cascade = 3;
     if view_depth < camera.cascade_splits.x {
-        return 0;
+        cascade = 0;
     } else if view_depth < camera.cascade_splits.y {
-        return 1;
+        cascade = 1;
     } else if view_depth < camera.cascade_splits.z {
-        return 2;
+        cascade = 2;
     }
-    return 3;
+    return min(cascade, last_cascade());
 }
 
 struct CascadeBlend {
@@ -106,17 +381,17 @@ fn cascade_blend(d: f32) -> CascadeBlend {
     let w1 = max(1.0, 0.1 * (s1 - s0));
     let w2 = max(1.0, 0.1 * (s2 - s1));
 
+    // Splits past the last cascade repeat the far plane, so never blend into them.
+    let last = last_cascade();
+    var blend = CascadeBlend(3, 3, 0.0);
     if d < s0 {
-        let t = smoothstep(s0 - w0, s0, d);
-        return CascadeBlend(0, 1, t);
+        blend = CascadeBlend(0, 1, smoothstep(s0 - w0, s0, d));
     } else if d < s1 {
-        let t = smoothstep(s1 - w1, s1, d);
-        return CascadeBlend(1, 2, t);
+        blend = CascadeBlend(1, 2, smoothstep(s1 - w1, s1, d));
     } else if d < s2 {
-        let t = smoothstep(s2 - w2, s2, d);
-        return CascadeBlend(2, 3, t);
+        blend = CascadeBlend(2, 3, smoothstep(s2 - w2, s2, d));
     }
-    return CascadeBlend(3, 3, 0.0);
+    return CascadeBlend(min(blend.c0, last), min(blend.c1, last), blend.t);
 }
 
 fn get_light_view_proj(cascade: i32) -> mat4x4<f32> {
@@ -130,12 +405,19 @@ fn get_light_view_proj(cascade: i32) -> mat4x4<f32> {
     return camera.light_view_proj_cascade3;
 }
 
+fn shadow_normal_offset() -> f32 {
+    if material.shadow_bias.x >= 0.0 {
+        return material.shadow_bias.x;
+    }
+    return camera.shadow_map.z;
+}
+
 fn shadow_pcf_cascade(world_pos: vec3<f32>, N: vec3<f32>, L: vec3<f32>, cascade: i32) -> f32 {
     let light_vp = get_light_view_proj(cascade);
     
     let NdotL = max(dot(N, L), 0.0);
     let slope_bias = 0.0002 * sqrt(1.0 - NdotL * NdotL) / max(NdotL, 0.001);
-    let normal_offset = 0.004 * (1.0 - NdotL);
+    let normal_offset = shadow_normal_offset() * (1.0 - NdotL);
     let offset_pos = world_pos + N * normal_offset;
     
     let light_clip = light_vp * vec4<f32>(offset_pos, 1.0);
@@ -153,7 +435,7 @@ fn shadow_pcf_cascade(world_pos: vec3<f32>, N: vec3<f32>, L: vec3<f32>, cascade:
     
     let depth = ndc.z - slope_bias;
     
-    let texel_size = 1.2 / SHADOW_MAP_SIZE;
+    let texel_size = 1.2 / camera.shadow_map.x;
     var shadow_sum = 0.0;
     
     shadow_sum += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + POISSON_DISK[0] * texel_size, cascade, depth);
@@ -176,29 +458,115 @@ fn shadow_pcf_cascade(world_pos: vec3<f32>, N: vec3<f32>, L: vec3<f32>, cascade:
     return shadow_sum / 16.0;
 }
 
+fn chebyshev_upper_bound(moments: vec2<f32>, t: f32, min_variance: f32, bleed_reduction: f32) -> f32 {
+    if t <= moments.x {
+        return 1.0;
+    }
+    let variance = max(moments.y - moments.x * moments.x, min_variance);
+    let d = t - moments.x;
+    let p_max = variance / (variance + d * d);
+    // Cut off the tail that causes light bleeding between overlapping occluders.
+    return clamp((p_max - bleed_reduction) / (1.0 - bleed_reduction), 0.0, 1.0);
+}
+
+fn shadow_evsm_cascade(world_pos: vec3<f32>, N: vec3<f32>, L: vec3<f32>, cascade: i32) -> f32 {
+    let NdotL = max(dot(N, L), 0.0);
+    let offset_pos = world_pos + N * (shadow_normal_offset() * (1.0 - NdotL));
+    let light_clip = get_light_view_proj(cascade) * vec4<f32>(offset_pos, 1.0);
+    if light_clip.w <= 0.0 {
+        return 1.0;
+    }
+    let ndc = light_clip.xyz / light_clip.w;
+    let uv = ndc.xy * 0.5 + vec2<f32>(0.5, 0.5);
+    if uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0 {
+        return 1.0;
+    }
+
+    let exponents = camera.shadow_params.zw;
+    let d = clamp(ndc.z, 0.0, 1.0) * 2.0 - 1.0;
+    let warped = vec2<f32>(exp(exponents.x * d), -exp(-exponents.y * d));
+    let moments = textureSampleLevel(evsm_moments, evsm_sampler, uv, cascade, 0.0);
+    // Depth-derivative-free minimum variance, scaled into each warped space.
+    let min_variance = 0.0001 * exponents * exponents * warped * warped;
+    let bleed = camera.shadow_params.y;
+    let positive = chebyshev_upper_bound(moments.xy, warped.x, min_variance.x, bleed);
+    let negative = chebyshev_upper_bound(moments.zw, warped.y, min_variance.y, bleed);
+    return min(positive, negative);
+}
+
+fn shadow_cascade(world_pos: vec3<f32>, N: vec3<f32>, L: vec3<f32>, cascade: i32) -> f32 {
+    if camera.shadow_params.x > 0.5 {
+        return shadow_evsm_cascade(world_pos, N, L, cascade);
+    }
+    return shadow_pcf_cascade(world_pos, N, L, cascade);
+}
+
+#ifdef COMPACT_VERTICES
+// Inverse of the octahedral encoding of compact vertex normals.
+fn oct_decode(e: vec2<f32>) -> vec3<f32> {
+    var n = vec3<f32>(e, 1.0 - abs(e.x) - abs(e.y));
+    let t = max(-n.z, 0.0);
+    n.x += select(t, -t, n.x >= 0.0);
+    n.y += select(t, -t, n.y >= 0.0);
+    return normalize(n);
+}
+#endif
+
 @vertex
 fn vs_main(
     @location(0) position: vec3<f32>,
+#ifdef COMPACT_VERTICES
+    @location(1) packed_normal: vec2<f32>,
+#else
     @location(1) normal: vec3<f32>,
+#endif
     @location(2) tex_coords: vec2<f32>,
+#ifdef BONE_WEIGHTS
+    @location(3) bone_weight: f32,
+#endif
+#ifdef NORMAL_MAP
+    @location(5) tangent: vec4<f32>,
+#endif
+#ifdef LIGHTMAP
+    @location(6) lightmap_uv: vec2<f32>,
+#endif
+    @builtin(instance_index) instance: u32,
 ) -> VertexOutput {
+    let object = objects[instance];
+#ifdef COMPACT_VERTICES
+    let normal = oct_decode(packed_normal);
+#endif
     var out: VertexOutput;
-    out.world_position = position;
-    out.normal = normal;
+#ifdef BINDLESS
+    out.material_id = object.material.x;
+#endif
+#ifdef BONE_WEIGHTS
+    out.bone_weight = bone_weight;
+#endif
+#ifdef NORMAL_MAP
+    let world_tangent = (object.model * vec4<f32>(tangent.xyz, 0.0)).xyz;
+    let tangent_length = length(world_tangent);
+    out.tangent = vec4<f32>(world_tangent / max(tangent_length, 1e-8), tangent.w);
+#endif
+#ifdef LIGHTMAP
+    out.lightmap_uv = lightmap_uv;
+#endif
+    let world_position = (object.model * vec4<f32>(position, 1.0)).xyz;
+    out.world_position = world_position;
+    out.normal = normalize(object.normal * normal);
     out.tex_coords = tex_coords;
-    let clip_pos = camera.view_proj * vec4<f32>(position, 1.0);
+    let clip_pos = camera.view_proj * vec4<f32>(world_position, 1.0);
     out.clip_position = clip_pos;
-    out.view_depth = distance(position, camera.position.xyz);
+    out.view_depth = distance(world_position, camera.position.xyz);
     return out;
 }
 
 @vertex
 fn vs_shadow(
     @location(0) position: vec3<f32>,
-    @location(1) normal: vec3<f32>,
-    @location(2) tex_coords: vec2<f32>,
+    @builtin(instance_index) instance: u32,
 ) -> @builtin(position) vec4<f32> {
-    return camera.light_view_proj * vec4<f32>(position, 1.0);
+    return camera.light_view_proj * objects[instance].model * vec4<f32>(position, 1.0);
 }
 
 @vertex
@@ -209,14 +577,15 @@ fn vs_sky(@builtin(vertex_index) vid: u32) -> SkyOut {
         vec2<f32>(-1.0,  1.0),
     );
 
-    let clip = vec4<f32>(p[vid], 1.0, 1.0);
+    // Unprojected between the planes, since an infinite far plane has no finite point.
+    let clip = vec4<f32>(p[vid], 0.5, 1.0);
 
     let view_h = camera.proj_inv * clip;
     let view_dir = normalize(view_h.xyz / view_h.w);
     let world_dir = normalize((camera.view_inv * vec4<f32>(view_dir, 0.0)).xyz);
 
     var o: SkyOut;
-    o.pos = vec4<f32>(p[vid], 1.0, 1.0);
+    o.pos = vec4<f32>(p[vid], FAR_DEPTH, 1.0);
     o.dir = world_dir;
     return o;
 }
@@ -264,72 +633,695 @@ fn fresnel_schlick(cosTheta: f32, F0: vec3<f32>) -> vec3<f32> {
     return F0 + (1.0 - F0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
 }
 
-@fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let base_sample = textureSample(base_color_texture, material_sampler, in.tex_coords);
-    let albedo = base_sample.rgb * material.base_color.rgb;
-    let alpha = base_sample.a * material.base_color.a;
+fn weight_heat(w: f32) -> vec3<f32> {
+    let t = clamp(w, 0.0, 1.0);
+    let cold = vec3<f32>(0.05, 0.05, 0.4);
+    let mid = vec3<f32>(0.1, 0.9, 0.2);
+    let hot = vec3<f32>(1.0, 0.1, 0.05);
+    if t < 0.5 {
+        return mix(cold, mid, t * 2.0);
+    }
+    return mix(mid, hot, (t - 0.5) * 2.0);
+}
+
+#ifdef ALPHA_DITHER
+// Interleaved gradient noise: a cheap per-pixel threshold with a blue-noise-like spectrum.
+fn dither_threshold(pixel: vec2<f32>) -> f32 {
+    return fract(52.9829189 * fract(dot(floor(pixel), vec2<f32>(0.06711056, 0.00583715))));
+}
+#endif
+
+#ifdef ALPHA_BLEND
+fn linear_depth(depth: f32) -> f32 {
+    let v = camera.proj_inv * vec4<f32>(0.0, 0.0, depth, 1.0);
+    return -v.z / v.w;
+}
+
+// Fades blended fragments out as they approach the opaque surface behind them.
+fn soft_depth_fade(frag: vec4<f32>, distance: f32) -> f32 {
+    let opaque = textureLoad(scene_depth, vec2<i32>(frag.xy), 0);
+    return clamp((linear_depth(opaque) - linear_depth(frag.z)) / distance, 0.0, 1.0);
+}
+#endif
+
+// metallic_roughness.z is the normal map scale, .w flips the green channel
+// (DirectX-style maps).
+fn apply_normal_map(normal: vec3<f32>, tangent: vec4<f32>, uv: vec2<f32>) -> vec3<f32> {
+    var m = sample_normal(uv).xyz * 2.0 - 1.0;
+    if material.metallic_roughness.w > 0.5 {
+        m.y = -m.y;
+    }
+    m = vec3<f32>(m.xy * material.metallic_roughness.z, m.z);
+
+    let N = normalize(normal);
+    let t = tangent.xyz - N * dot(N, tangent.xyz);
+    if dot(t, t) < 1e-8 {
+        return N;
+    }
+    let T = normalize(t);
+    let B = cross(N, T) * select(1.0, -1.0, tangent.w < 0.0);
+    return normalize(T * m.x + B * m.y + N * m.z);
+}
+
+struct SurfaceInput {
+    world_position: vec3<f32>,
+    normal: vec3<f32>,
+    tangent: vec4<f32>,
+    uv: vec2<f32>,
+    view_depth: f32,
+    time: f32,
+};
+
+struct Surface {
+    albedo: vec3<f32>,
+    alpha: f32,
+    metallic: f32,
+    roughness: f32,
+    normal: vec3<f32>,
+    emissive: vec3<f32>,
+};
+
+fn default_surface(in: SurfaceInput) -> Surface {
+    let base_sample = sample_base_color(in.uv);
+    let mr_sample = sample_metallic_roughness(in.uv).rgb;
+
+    var s: Surface;
+    s.albedo = base_sample.rgb * material.base_color.rgb;
+    s.alpha = base_sample.a * material.base_color.a;
+    s.metallic = clamp(mr_sample.b * material.metallic_roughness.r, 0.0, 1.0);
+    s.roughness = clamp(mr_sample.g * material.metallic_roughness.g, 0.04, 1.0);
+#ifdef NORMAL_MAP
+    s.normal = apply_normal_map(in.normal, in.tangent, in.uv);
+#else
+    s.normal = normalize(in.normal);
+#endif
+    s.emissive = vec3<f32>(0.0);
+    return s;
+}
+
+// Cook-Torrance GGX with Lambert diffuse, times N.L.
+fn brdf(
+    N: vec3<f32>,
+    V: vec3<f32>,
+    L: vec3<f32>,
+    albedo: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+    F0: vec3<f32>,
+    energy_compensation: vec3<f32>,
+) -> vec3<f32> {
+    let H = normalize(V + L);
+    let NDF = distribution_ggx(N, H, roughness);
+    let G = geometry_smith(N, V, L, roughness);
+    let F = fresnel_schlick(max(dot(H, V), 0.0), F0);
+
+    var kD = vec3<f32>(1.0) - F;
+    kD = kD * (1.0 - metallic);
+
+    let numerator = NDF * G * F;
+    let denominator = 4.0 * max(dot(N, V), 0.0) * max(dot(N, L), 0.0) + 0.0001;
+    let specular = numerator / denominator * energy_compensation;
+
+    let NdotL = max(dot(N, L), 0.0);
+    return (kD * albedo / PI + specular) * NdotL;
+}
+
+// Inverse-square falloff windowed to reach zero at `range` (Karis 2013).
+fn point_falloff(dist2: f32, range: f32) -> f32 {
+    let r = dist2 / (range * range);
+    let window = clamp(1.0 - r * r, 0.0, 1.0);
+    return window * window / max(dist2, 1e-4);
+}
+
+fn point_light_shadow(light: PointLight, world_pos: vec3<f32>, N: vec3<f32>, L: vec3<f32>) -> f32 {
+    if light.shadow_layer < 0 {
+        return 1.0;
+    }
+    let d = world_pos - light.position;
+    let a = abs(d);
+    var face = 0u;
+    if a.x >= a.y && a.x >= a.z {
+        face = select(1u, 0u, d.x > 0.0);
+    } else if a.y >= a.z {
+        face = select(3u, 2u, d.y > 0.0);
+    } else {
+        face = select(5u, 4u, d.z > 0.0);
+    }
+    let layer = u32(light.shadow_layer) + face;
+
+    let NdotL = max(dot(N, L), 0.0);
+    let offset_pos = world_pos + N * (0.01 + 0.02 * (1.0 - NdotL));
+    let clip = point_shadow_matrices[layer] * vec4<f32>(offset_pos, 1.0);
+    if clip.w <= 0.0 {
+        return 1.0;
+    }
+    let ndc = clip.xyz / clip.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5);
+    return textureSampleCompareLevel(point_shadow_map, point_shadow_sampler, uv, layer, ndc.z);
+}
+
+fn spot_light_shadow(light: SpotLight, world_pos: vec3<f32>, N: vec3<f32>, L: vec3<f32>) -> f32 {
+    if light.shadow_tile < 0 {
+        return 1.0;
+    }
+    let NdotL = max(dot(N, L), 0.0);
+    let offset_pos = world_pos + N * (0.01 + 0.02 * (1.0 - NdotL));
+    let clip = light.view_proj * vec4<f32>(offset_pos, 1.0);
+    if clip.w <= 0.0 {
+        return 1.0;
+    }
+    let ndc = clip.xyz / clip.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5);
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) {
+        return 1.0;
+    }
+    let tile = vec2<f32>(f32(u32(light.shadow_tile) % 4u), f32(u32(light.shadow_tile) / 4u));
+    // Keep half a texel from the tile edge so filtering never reads a neighbour.
+    let texel = 1.0 / f32(textureDimensions(spot_shadow_atlas).x);
+    let atlas_uv = clamp((tile + uv) * 0.25, tile * 0.25 + texel * 0.5, (tile + 1.0) * 0.25 - texel * 0.5);
+    return textureSampleCompareLevel(spot_shadow_atlas, point_shadow_sampler, atlas_uv, ndc.z);
+}
+
+// The cookie projected through the spot's frustum; nothing outside it is lit.
+fn spot_light_cookie(light: SpotLight, world_pos: vec3<f32>) -> vec3<f32> {
+    if light.cookie < 0 {
+        return vec3<f32>(1.0);
+    }
+    let clip = light.view_proj * vec4<f32>(world_pos, 1.0);
+    if clip.w <= 0.0 {
+        return vec3<f32>(0.0);
+    }
+    let uv = clip.xy / clip.w * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5);
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) {
+        return vec3<f32>(0.0);
+    }
+    return textureSampleLevel(light_cookies, cookie_sampler, uv, light.cookie, 0.0).rgb;
+}
+
+// The cookie tiled over a plane perpendicular to the light, anchored at the world origin.
+fn directional_light_cookie(light: DirectionalLight, world_pos: vec3<f32>) -> vec3<f32> {
+    if light.cookie < 0 {
+        return vec3<f32>(1.0);
+    }
+    let dir = normalize(light.direction);
+    let up = select(vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(0.0, 0.0, 1.0), abs(dir.y) > 0.99);
+    let right = normalize(cross(dir, up));
+    let down = cross(dir, right);
+    let uv = vec2<f32>(dot(world_pos, right), dot(world_pos, down)) / light.cookie_size;
+    return textureSampleLevel(light_cookies, cookie_sampler, uv, light.cookie, 0.0).rgb;
+}
+
+// Irradiance over pi, so it stands in for the environment sample, using the cosine
+// lobe convolution of Ramamoorthi and Hanrahan 2001.
+fn sh_irradiance(probe: u32, n: vec3<f32>) -> vec3<f32> {
+    let b = probe * 9u;
+    var e = probes.coefficients[b].xyz * 0.886227;
+    e += (probes.coefficients[b + 1u].xyz * n.y
+        + probes.coefficients[b + 2u].xyz * n.z
+        + probes.coefficients[b + 3u].xyz * n.x) * 1.023328;
+    e += (probes.coefficients[b + 4u].xyz * (n.x * n.y)
+        + probes.coefficients[b + 5u].xyz * (n.y * n.z)
+        + probes.coefficients[b + 7u].xyz * (n.x * n.z)) * 0.858086;
+    e += probes.coefficients[b + 6u].xyz * (0.743125 * n.z * n.z - 0.247708);
+    e += probes.coefficients[b + 8u].xyz * (0.429043 * (n.x * n.x - n.y * n.y));
+    return max(e, vec3<f32>(0.0)) / PI;
+}
+
+fn oct_encode(n: vec3<f32>) -> vec2<f32> {
+    let p = n.xy / (abs(n.x) + abs(n.y) + abs(n.z));
+    if n.z < 0.0 {
+        return (1.0 - abs(p.yx)) * select(vec2<f32>(-1.0), vec2<f32>(1.0), p >= vec2<f32>(0.0));
+    }
+    return p;
+}
+
+// UV of `dir` in a probe's tile of a DDGI atlas with `texels` interior texels per side.
+fn ddgi_uv(cell: vec3<u32>, dir: vec3<f32>, texels: i32, atlas: vec2<f32>) -> vec2<f32> {
+    let tile = vec2<i32>(i32(cell.x + cell.y * probes.counts.x), i32(cell.z)) * (texels + 2) + 1;
+    return (vec2<f32>(tile) + (oct_encode(dir) * 0.5 + 0.5) * f32(texels)) / atlas;
+}
+
+// Trilinear blend of the eight surrounding probes in rgb, weighted towards those the
+// surface faces to limit leaking through walls. Alpha fades the volume out over one
+// cell beyond the outer probes. Dynamic (DDGI) probes also drop probes the surface is
+// hidden from, using their distance moments.
+fn probe_irradiance(world_pos: vec3<f32>, N: vec3<f32>, V: vec3<f32>) -> vec4<f32> {
+    if probes.origin.w < 0.5 {
+        return vec4<f32>(0.0);
+    }
+    let dynamic = probes.spacing.w > 0.5;
+    let spacing = probes.spacing.xyz;
+    let bias = (N * 0.2 + V * 0.8) * (0.3 * min(spacing.x, min(spacing.y, spacing.z)));
+    let p = select(world_pos, world_pos + bias, dynamic);
+    let counts = vec3<i32>(probes.counts.xyz);
+    let local = (p - probes.origin.xyz) / spacing;
+    let outside = max(-local, local - vec3<f32>(counts - 1));
+    let coverage = 1.0 - clamp(length(max(outside, vec3<f32>(0.0))), 0.0, 1.0);
+    if coverage <= 0.0 {
+        return vec4<f32>(0.0);
+    }
+    let base = clamp(vec3<i32>(floor(local)), vec3<i32>(0), counts - 2);
+    let t = clamp(local - vec3<f32>(base), vec3<f32>(0.0), vec3<f32>(1.0));
+    let irradiance_size = vec2<f32>(textureDimensions(ddgi_irradiance));
+    let visibility_size = vec2<f32>(textureDimensions(ddgi_visibility));
+
+    var sum = vec3<f32>(0.0);
+    var total = 0.0;
+    for (var i = 0u; i < 8u; i++) {
+        let offset = vec3<u32>(i & 1u, (i >> 1u) & 1u, (i >> 2u) & 1u);
+        let cell = vec3<u32>(base) + offset;
+        let tri = mix(1.0 - t, t, vec3<f32>(offset));
+        let probe_pos = probes.origin.xyz + vec3<f32>(cell) * spacing;
+        if dynamic {
+            let to_probe = probe_pos - p;
+            let distance = length(to_probe);
+            let dir = to_probe / max(distance, 1e-4);
+            let wrap = dot(dir, N) * 0.5 + 0.5;
+            var w = tri.x * tri.y * tri.z * (wrap * wrap + 0.2);
+            let moments = textureSampleLevel(
+                ddgi_visibility, reflection_sampler, ddgi_uv(cell, -dir, 14, visibility_size), 0.0
+            ).xy;
+            if distance > moments.x {
+                let variance = abs(moments.x * moments.x - moments.y);
+                let d = distance - moments.x;
+                let chebyshev = variance / (variance + d * d);
+                w *= max(chebyshev * chebyshev * chebyshev, 0.05);
+            }
+            let irradiance = textureSampleLevel(
+                ddgi_irradiance, reflection_sampler, ddgi_uv(cell, N, 6, irradiance_size), 0.0
+            ).rgb;
+            sum += irradiance * w;
+            total += w;
+        } else {
+            let facing = dot(normalize(probe_pos - world_pos + N * 1e-3), N) * 0.5 + 0.5;
+            let w = tri.x * tri.y * tri.z * (facing * facing + 0.05);
+            let index = (cell.z * probes.counts.y + cell.y) * probes.counts.x + cell.x;
+            sum += sh_irradiance(index, N) * w;
+            total += w;
+        }
+    }
+    return vec4<f32>(sum / max(total, 1e-6), coverage);
+}
+
+// Attenuates `color` by the fog between the camera and `distance` along the pixel's ray
+// and adds the light the fog scatters towards the camera.
+fn apply_fog(color: vec3<f32>, frag_xy: vec2<f32>, distance: f32) -> vec3<f32> {
+    if camera.fog.x < 0.5 {
+        return color;
+    }
+    let uv = frag_xy / vec2<f32>(textureDimensions(scene_depth));
+    let slices = f32(textureDimensions(fog_volume).z);
+    // Each froxel holds the fog integrated up to its far side, so look half a slice back.
+    let slice = log(max(distance, camera.fog.y) / camera.fog.y) / log(camera.fog.z / camera.fog.y) - 0.5 / slices;
+    let fog = textureSampleLevel(fog_volume, reflection_sampler, vec3<f32>(uv, slice), 0.0);
+    return color * fog.a + fog.rgb;
+}
+
+// Front-to-back accumulation of voxel radiance (rgb) and occlusion (alpha) along a cone
+// whose radius grows by `aperture` per unit distance, stepping by half its diameter.
+fn voxel_cone(origin: vec3<f32>, dir: vec3<f32>, aperture: f32) -> vec4<f32> {
+    let voxel = camera.voxel_extent.w;
+    let max_lod = f32(textureNumLevels(voxel_radiance) - 1u);
+    var result = vec4<f32>(0.0);
+    var distance = voxel;
+    for (var i = 0; i < 64 && result.a < 0.95; i++) {
+        let uvw = (origin + dir * distance - camera.voxel_min.xyz) / camera.voxel_extent.xyz;
+        if any(uvw < vec3<f32>(0.0)) || any(uvw > vec3<f32>(1.0)) {
+            break;
+        }
+        let diameter = max(voxel, 2.0 * aperture * distance);
+        let lod = min(log2(diameter / voxel), max_lod);
+        let s = textureSampleLevel(voxel_radiance, reflection_sampler, uvw, lod);
+        result += (1.0 - result.a) * s;
+        distance += diameter * 0.5;
+    }
+    return result;
+}
+
+// Diffuse radiance from six 60 degree cones over the hemisphere around N, with the
+// occlusion they found in alpha.
+fn voxel_diffuse(world_pos: vec3<f32>, N: vec3<f32>) -> vec4<f32> {
+    let up = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), abs(N.y) < 0.99);
+    let T = normalize(cross(up, N));
+    let B = cross(N, T);
+    // Start a voxel out so the surface does not occlude itself.
+    let origin = world_pos + N * camera.voxel_extent.w;
+    var sum = voxel_cone(origin, N, 0.577) * 0.25;
+    for (var i = 0; i < 5; i++) {
+        let angle = f32(i) * (2.0 * PI / 5.0);
+        let side = T * cos(angle) + B * sin(angle);
+        sum += voxel_cone(origin, normalize(N * 0.5 + side * 0.866), 0.577) * 0.15;
+    }
+    return sum;
+}
+
+// Prefiltered radiance along R from the smallest reflection probe box containing the
+// fragment, in rgb, with its edge fade in alpha. R is corrected for parallax by looking
+// up where it leaves the box as seen from the capture point.
+fn reflection_probe(world_pos: vec3<f32>, R: vec3<f32>, roughness: f32) -> vec4<f32> {
+    var best = -1;
+    var best_volume = 3.4e38;
+    var weight = 0.0;
+    for (var i = 0u; i < reflection_probes.count.x; i++) {
+        let p = reflection_probes.probes[i];
+        let inside = min(world_pos - p.box_min.xyz, p.box_max.xyz - world_pos);
+        let edge = min(inside.x, min(inside.y, inside.z));
+        let size = p.box_max.xyz - p.box_min.xyz;
+        let volume = size.x * size.y * size.z;
+        if edge >= 0.0 && volume < best_volume {
+            best = i32(i);
+            best_volume = volume;
+            weight = clamp(edge / max(p.box_max.w, 1e-4), 0.0, 1.0);
+        }
+    }
+    if best < 0 {
+        return vec4<f32>(0.0);
+    }
+    let p = reflection_probes.probes[best];
+    let exits = max((p.box_max.xyz - world_pos) / R, (p.box_min.xyz - world_pos) / R);
+    let hit = world_pos + R * min(exits.x, min(exits.y, exits.z));
+    let lod = roughness * f32(textureNumLevels(reflection_cubes) - 1u);
+    let radiance = textureSampleLevel(reflection_cubes, reflection_sampler, hit - p.position.xyz, best, lod).rgb;
+    return vec4<f32>(radiance, weight);
+}
+
+#ifndef CUSTOM_SURFACE
+fn surface(in: SurfaceInput) -> Surface {
+    return default_surface(in);
+}
+#endif
+
+fn evaluate_surface(in: VertexOutput) -> Surface {
+#ifdef BINDLESS
+    material_id = select(in.material_id, material_replacement.x - 1u, material_replacement.x > 0u);
+    material = materials[material_id];
+#endif
+#ifdef NORMAL_MAP
+    let tangent = in.tangent;
+#else
+    let tangent = vec4<f32>(0.0);
+#endif
+    let surf = surface(SurfaceInput(in.world_position, in.normal, tangent, in.tex_coords, in.view_depth, camera.time.x));
+
+#ifdef ALPHA_MASK
+    if surf.alpha < material.alpha_cutoff_flags.x {
+        discard;
+    }
+#endif
+#ifdef ALPHA_DITHER
+    if surf.alpha <= dither_threshold(in.clip_position.xy) {
+        discard;
+    }
+#endif
+    return surf;
+}
 
-    if material.alpha_cutoff_flags.y >= 0.5 && material.alpha_cutoff_flags.y < 1.5 {
-        if alpha < material.alpha_cutoff_flags.x {
-            discard;
+// rgb: baked irradiance replacing the rest of the diffuse indirect light, a: 1 when it applies.
+fn baked_irradiance(in: VertexOutput) -> vec4<f32> {
+#ifdef LIGHTMAP
+    if camera.lightmap.x > 0.5 {
+        // The bake already holds the sky and every bounce, so it replaces the rest.
+        let baked = textureSampleLevel(lightmap, reflection_sampler, in.lightmap_uv, 0.0).rgb;
+        return vec4<f32>(baked * camera.lightmap.y, 1.0);
+    }
+#endif
+    return vec4<f32>(0.0);
+}
+
+// Only surfaces that made it into the depth prepass have screen-space results.
+fn in_depth_prepass(frag: vec4<f32>) -> bool {
+#ifdef REVERSED_Z
+    return frag.z <= textureLoad(scene_depth, vec2<i32>(frag.xy), 0);
+#else
+    return frag.z >= textureLoad(scene_depth, vec2<i32>(frag.xy), 0);
+#endif
+}
+
+fn screen_ao(frag: vec4<f32>) -> f32 {
+    return select(1.0, mix(1.0, textureLoad(ssao_map, vec2<i32>(frag.xy), 0).r, camera.screen_space.x), in_depth_prepass(frag));
+}
+
+fn sun_shadow(world_position: vec3<f32>, N: vec3<f32>, L: vec3<f32>, view_depth: f32, frag: vec4<f32>) -> f32 {
+    if camera.screen_space.z > 0.5 && in_depth_prepass(frag) {
+        return textureLoad(rt_shadow_map, vec2<i32>(frag.xy) / 2, 0).r;
+    }
+    let cb = cascade_blend(view_depth);
+    let s0 = shadow_cascade(world_position, N, L, cb.c0);
+    let s1 = shadow_cascade(world_position, N, L, cb.c1);
+    return s0 * (1.0 - cb.t) + s1 * cb.t;
+}
+
+// Unlit views of one shading input, selected by camera.debug.x.
+fn debug_view(surf: Surface, world_position: vec3<f32>, view_depth: f32, frag: vec4<f32>, uv: vec2<f32>) -> vec3<f32> {
+    switch i32(camera.debug.x + 0.5) {
+        case 1: {
+            return surf.albedo;
+        }
+        case 2: {
+            return normalize(surf.normal) * 0.5 + 0.5;
+        }
+        case 3: {
+            return vec3<f32>(surf.roughness);
+        }
+        case 4: {
+            return vec3<f32>(surf.metallic);
+        }
+        case 5: {
+            return vec3<f32>(screen_ao(frag));
+        }
+        case 6: {
+            // Logarithmic so near and far detail both show, white at the camera.
+            return vec3<f32>(1.0 - log2(1.0 + view_depth) / log2(1.0 + camera.cascade_splits.w));
+        }
+        case 7: {
+            var colors = array<vec3<f32>, 4>(
+                vec3<f32>(1.0, 0.2, 0.2),
+                vec3<f32>(0.2, 1.0, 0.2),
+                vec3<f32>(0.2, 0.2, 1.0),
+                vec3<f32>(1.0, 1.0, 0.2),
+            );
+            let cb = cascade_blend(view_depth);
+            return mix(colors[cb.c0], colors[cb.c1], cb.t);
+        }
+        case 8: {
+            return vec3<f32>(sun_shadow(world_position, normalize(surf.normal), normalize(-camera.light_dir.xyz), view_depth, frag));
+        }
+        case 9: {
+            return vec3<f32>(fract(uv), 0.0);
+        }
+        default: {
+            return vec3<f32>(1.0, 0.0, 1.0);
         }
     }
+}
 
-    let mr_sample = textureSample(metallic_roughness_texture, material_sampler, in.tex_coords).rgb;
-    let metallic = clamp(mr_sample.b * material.metallic_roughness.r, 0.0, 1.0);
-    let roughness = clamp(mr_sample.g * material.metallic_roughness.g, 0.04, 1.0);
+// Lights a surface at `frag` (framebuffer position and depth), fogged. Shared by the
+// forward pass and the deferred resolve.
+fn shade(surf: Surface, world_position: vec3<f32>, view_depth: f32, frag: vec4<f32>, baked: vec4<f32>) -> vec3<f32> {
+    let albedo = surf.albedo;
+    let metallic = clamp(surf.metallic, 0.0, 1.0);
+    let roughness = clamp(surf.roughness, 0.04, 1.0);
     
-    let N = normalize(in.normal);
-    let V = normalize(camera.position.xyz - in.world_position);
+    let N = normalize(surf.normal);
+    let V = normalize(camera.position.xyz - world_position);
     let L = normalize(-camera.light_dir.xyz);
 
-    let cb = cascade_blend(in.view_depth);
-    let s0 = shadow_pcf_cascade(in.world_position, N, L, cb.c0);
-    let s1 = shadow_pcf_cascade(in.world_position, N, L, cb.c1);
-    let shadow = s0 * (1.0 - cb.t) + s1 * cb.t;
+    let pixel = vec2<i32>(frag.xy);
+    let in_prepass = in_depth_prepass(frag);
+
+    let shadow = sun_shadow(world_position, N, L, view_depth, frag);
     
     var F0 = vec3<f32>(0.04);
     F0 = mix(F0, albedo, metallic);
     
     var Lo = vec3<f32>(0.0);
 
-    let H = normalize(V + L);
-    let radiance = vec3<f32>(6.0, 6.0, 6.0);
+    let primary = directional_lights.lights[0];
+    let radiance = primary.color * primary.intensity * directional_light_cookie(primary, world_position);
 
-    let NDF = distribution_ggx(N, H, roughness);
-    let G = geometry_smith(N, V, L, roughness);
-    let F = fresnel_schlick(max(dot(H, V), 0.0), F0);
+    // Multiple-scattering compensation (Fdez-Aguera 2019): single-scatter GGX only
+    // reflects E(mu) of the energy, so scale by the missing fraction tinted by F0.
+    let dfg = textureSample(brdf_lut, env_sampler, vec2<f32>(max(dot(N, V), 0.0), roughness)).rg;
+    let energy_compensation = 1.0 + F0 * (1.0 / max(dfg.x + dfg.y, 1e-3) - 1.0);
 
-    let kS = F;
-    var kD = vec3<f32>(1.0) - kS;
-    kD = kD * (1.0 - metallic);
+    Lo = brdf(N, V, L, albedo, metallic, roughness, F0, energy_compensation) * radiance * shadow;
 
-    let numerator = NDF * G * F;
-    let denominator = 4.0 * max(dot(N, V), 0.0) * max(dot(N, L), 0.0) + 0.0001;
-    let specular = numerator / denominator;
+    for (var i = 1u; i < directional_lights.count.x; i = i + 1u) {
+        let light = directional_lights.lights[i];
+        let DL = normalize(-light.direction);
+        Lo += brdf(N, V, DL, albedo, metallic, roughness, F0, energy_compensation) * light.color * light.intensity
+            * directional_light_cookie(light, world_position);
+    }
 
-    let NdotL = max(dot(N, L), 0.0);
-    Lo = (kD * albedo / PI + specular) * radiance * NdotL * shadow;
+    for (var i = 0u; i < point_lights.count.x; i = i + 1u) {
+        let light = point_lights.lights[i];
+        let to_light = light.position - world_position;
+        let dist2 = dot(to_light, to_light);
+        let falloff = point_falloff(dist2, light.range);
+        if falloff <= 0.0 {
+            continue;
+        }
+        let PL = to_light * inverseSqrt(max(dist2, 1e-8));
+        let point_radiance = light.color * light.intensity * falloff;
+        let point_shadow = point_light_shadow(light, world_position, N, PL);
+        Lo += brdf(N, V, PL, albedo, metallic, roughness, F0, energy_compensation) * point_radiance * point_shadow;
+    }
+
+    for (var i = 0u; i < spot_lights.count.x; i = i + 1u) {
+        let light = spot_lights.lights[i];
+        let to_light = light.position - world_position;
+        let dist2 = dot(to_light, to_light);
+        let SL = to_light * inverseSqrt(max(dist2, 1e-8));
+        let cone = smoothstep(light.cos_outer, light.cos_inner, dot(-SL, light.direction));
+        let falloff = point_falloff(dist2, light.range) * cone;
+        if falloff <= 0.0 {
+            continue;
+        }
+        let spot_radiance = light.color * light.intensity * falloff * spot_light_cookie(light, world_position);
+        let spot_shadow = spot_light_shadow(light, world_position, N, SL);
+        Lo += brdf(N, V, SL, albedo, metallic, roughness, F0, energy_compensation) * spot_radiance * spot_shadow;
+    }
     
     let env_uv = dir_to_equirect_uv(N);
     let env_col = textureSample(env_map, env_sampler, env_uv).rgb;
-    let ambient = env_col * albedo * camera.env_intensity.rgb;
-    var color = ambient + Lo;
-    
-    color = color / (color + vec3<f32>(1.0));
+    let probe = probe_irradiance(world_position, N, V);
+    let ao = screen_ao(frag);
+    let bounce = select(vec3<f32>(0.0), textureLoad(ssgi_map, pixel / 2, 0).rgb * camera.screen_space.y, in_prepass);
+    var indirect = mix(env_col * camera.env_intensity.rgb, probe.rgb, probe.a);
+    let R = reflect(-V, N);
+    let reflection = reflection_probe(world_position, R, roughness);
+    var specular = reflection.rgb * reflection.a;
+    if camera.voxel_min.w > 0.5 {
+        // Cones cover what the voxels occlude; the rest still sees the sky or probes.
+        let cones = voxel_diffuse(world_position, N);
+        indirect = cones.rgb + indirect * (1.0 - cones.a);
+        // Glossy cones get too thin for the voxels, so only rough surfaces use them.
+        let glossy = smoothstep(0.15, 0.35, roughness);
+        if glossy > 0.0 {
+            let cone = voxel_cone(world_position + N * camera.voxel_extent.w, R, roughness);
+            specular = mix(specular, cone.rgb + specular * (1.0 - cone.a), glossy);
+        }
+    }
+    if baked.a > 0.5 {
+        indirect = baked.rgb;
+    }
+    let ambient = (indirect * ao + bounce) * albedo;
+    let specular_ambient = specular * (F0 * dfg.x + dfg.y) * energy_compensation * ao;
+    return apply_fog(ambient + specular_ambient + Lo + surf.emissive, frag.xy, view_depth);
+}
 
-    if material.alpha_cutoff_flags.y >= 1.5 {
-        return vec4<f32>(color, alpha);
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+#ifdef BONE_WEIGHTS
+    let shade = 0.35 + 0.65 * max(dot(normalize(in.normal), normalize(-camera.light_dir.xyz)), 0.0);
+    return vec4<f32>(weight_heat(in.bone_weight) * shade, 1.0);
+#else
+    let surf = evaluate_surface(in);
+    if camera.debug.x > 0.5 {
+        return vec4<f32>(debug_view(surf, in.world_position, in.view_depth, in.clip_position, in.tex_coords), 1.0);
     }
+    let color = shade(surf, in.world_position, in.view_depth, in.clip_position, baked_irradiance(in));
 
+#ifdef ALPHA_BLEND
+    var fade = 1.0;
+    if material.alpha_cutoff_flags.w > 0.0 {
+        fade = soft_depth_fade(in.clip_position, material.alpha_cutoff_flags.w);
+    }
+    return vec4<f32>(color, surf.alpha * fade);
+#else
     return vec4<f32>(color, 1.0);
+#endif
+#endif
 }
 
+#ifdef GBUFFER
+struct GBufferOut {
+    @location(0) albedo: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+    @location(2) orm: vec4<f32>,
+    @location(3) emissive: vec4<f32>,
+};
+
+@fragment
+fn fs_gbuffer(in: VertexOutput) -> GBufferOut {
+    let surf = evaluate_surface(in);
+    let baked = baked_irradiance(in);
+    var out: GBufferOut;
+    out.albedo = vec4<f32>(surf.albedo, 1.0);
+    out.normal = vec4<f32>(normalize(surf.normal), 0.0);
+    out.orm = vec4<f32>(1.0, clamp(surf.roughness, 0.04, 1.0), clamp(surf.metallic, 0.0, 1.0), 0.0);
+    // Lightmaps are sampled here since the resolve has no lightmap UVs; shade() then
+    // leaves the diffuse indirect term out for these pixels.
+    out.emissive = vec4<f32>(surf.emissive + baked.rgb * surf.albedo * screen_ao(in.clip_position), baked.a);
+    return out;
+}
+#endif
+
+@vertex
+fn vs_deferred(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+    var p = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -3.0),
+        vec2<f32>( 3.0,  1.0),
+        vec2<f32>(-1.0,  1.0),
+    );
+    return vec4<f32>(p[vid], 0.0, 1.0);
+}
+
+// Lighting resolve for the deferred path: rebuilds each pixel's surface from the
+// G-buffer and shades it once, whatever the overdraw. The sky fills the rest afterwards.
+@fragment
+fn fs_deferred(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(pos.xy);
+    let depth = textureLoad(gbuffer_depth, pixel, 0);
+    if depth == FAR_DEPTH {
+        discard;
+    }
+    let uv = pos.xy / vec2<f32>(textureDimensions(gbuffer_depth));
+    let view = camera.proj_inv * vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let world_position = (camera.view_inv * vec4<f32>(view.xyz / view.w, 1.0)).xyz;
+
+    let orm = textureLoad(gbuffer_orm, pixel, 0);
+    let emissive = textureLoad(gbuffer_emissive, pixel, 0);
+    var surf: Surface;
+    surf.albedo = textureLoad(gbuffer_albedo, pixel, 0).rgb;
+    surf.alpha = 1.0;
+    surf.metallic = orm.z;
+    surf.roughness = orm.y;
+    surf.normal = textureLoad(gbuffer_normal, pixel, 0).xyz;
+    surf.emissive = emissive.rgb;
+    let view_depth = distance(world_position, camera.position.xyz);
+    if camera.debug.x > 0.5 {
+        // UVs are not in the G-buffer and show as black.
+        let frag = vec4<f32>(pos.xy, depth, 1.0);
+        return vec4<f32>(debug_view(surf, world_position, view_depth, frag, vec2<f32>(0.0)), 1.0);
+    }
+    let color = shade(surf, world_position, view_depth, vec4<f32>(pos.xy, depth, 1.0), vec4<f32>(0.0, 0.0, 0.0, emissive.a));
+    return vec4<f32>(color, 1.0);
+}
+
+
 @fragment
 fn fs_sky(in: SkyOut) -> @location(0) vec4<f32> {
     let uv = dir_to_equirect_uv(in.dir);
     var col = textureSample(env_map, env_sampler, uv).rgb * camera.env_intensity.rgb;
-    col = col / (col + vec3<f32>(1.0));
-    return vec4<f32>(col, 1.0);
+    // Generated skies are too coarse for the sun itself, so draw its disk here, tinted
+    // like the sky around it so it reddens at sunset.
+    let to_sun = -normalize(camera.light_dir.xyz);
+    let dir = normalize(in.dir);
+    // Distance from the disk centre in disk radii; the cross product keeps precision
+    // at the sun's tiny angles where acos would not.
+    let r = select(2.0, length(cross(dir, to_sun)) / camera.sun.w, dot(dir, to_sun) > 0.0);
+    let coverage = clamp((1.0 - r) / max(fwidth(r), 1e-4), 0.0, 1.0);
+    if camera.env_intensity.w > 0.5 && coverage > 0.0 {
+        let glow = textureSampleLevel(env_map, env_sampler, dir_to_equirect_uv(to_sun), 0.0).rgb;
+        let tint = glow / max(max(glow.r, max(glow.g, glow.b)), 1e-4);
+        // Linear limb darkening, normalized so the disk still delivers the light's
+        // illuminance over its solid angle.
+        let mu = sqrt(max(1.0 - r * r, 0.0));
+        let limb = (1.0 - SUN_LIMB_DARKENING * (1.0 - mu)) / (1.0 - SUN_LIMB_DARKENING / 3.0);
+        let solid_angle = PI * camera.sun.w * camera.sun.w;
+        let radiance = camera.sun.rgb * tint * (limb / solid_angle);
+        col += radiance * coverage * smoothstep(-0.02, 0.05, to_sun.y);
+    }
+    return vec4<f32>(min(apply_fog(col, in.pos.xy, camera.fog.z), vec3<f32>(MAX_HDR)), 1.0);
 }
@@ -0,0 +1,116 @@
+/// Cascades the uniforms and shaders have room for.
+pub const MAX_CASCADES: u32 = 4;
+const MIN_RESOLUTION: u32 = 256;
+
+/// Split distances as fractions of the near-far range, per cascade count.
+const SPLIT_FRACTIONS: [&[f32]; MAX_CASCADES as usize] = [
+    &[1.0],
+    &[0.15, 1.0],
+    &[0.08, 0.3, 1.0],
+    &[0.05, 0.15, 0.40, 1.0],
+];
+
+/// Presets cycled at runtime, from the default down to what low-end GPUs can afford.
+const PRESETS: [ShadowSettings; 4] = [
+    ShadowSettings { resolution: 4096, cascades: 4 },
+    ShadowSettings { resolution: 2048, cascades: 3 },
+    ShadowSettings { resolution: 1024, cascades: 2 },
+    ShadowSettings { resolution: 1024, cascades: 1 },
+];
+
+/// Biasing of the shadow maps against acne. `constant` and `slope_scale` are applied by
+/// the rasterizer when casters are drawn; `normal_offset` pushes receivers along their
+/// normal (in world units, scaled up at grazing angles) before the lookup.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShadowBias {
+    pub constant: i32,
+    pub slope_scale: f32,
+    pub normal_offset: f32,
+}
+
+impl Default for ShadowBias {
+    fn default() -> Self {
+        Self {
+            constant: 1,
+            slope_scale: 1.0,
+            normal_offset: 0.004,
+        }
+    }
+}
+
+impl ShadowBias {
+    pub fn depth_bias_state(&self) -> wgpu::DepthBiasState {
+        wgpu::DepthBiasState {
+            constant: self.constant,
+            slope_scale: self.slope_scale,
+            clamp: 0.0,
+        }
+    }
+}
+
+/// Size and number of the directional shadow cascades.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ShadowSettings {
+    /// Width and height of each cascade map in texels.
+    pub resolution: u32,
+    pub cascades: u32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        PRESETS[0]
+    }
+}
+
+impl ShadowSettings {
+    /// Clamps to what the device and the shaders support.
+    pub fn sanitized(self, limits: &wgpu::Limits) -> Self {
+        Self {
+            resolution: self.resolution.clamp(MIN_RESOLUTION, limits.max_texture_dimension_2d),
+            cascades: self.cascades.clamp(1, MAX_CASCADES),
+        }
+    }
+
+    /// The preset after the one matching these settings, wrapping around.
+    pub fn next_preset(&self) -> Self {
+        let current = PRESETS.iter().position(|p| p == self);
+        PRESETS[current.map_or(0, |i| (i + 1) % PRESETS.len())]
+    }
+
+    /// Far distance of every cascade; unused entries repeat `zfar`.
+    pub fn cascade_splits(&self, znear: f32, zfar: f32) -> [f32; 4] {
+        let fractions = SPLIT_FRACTIONS[self.cascades.clamp(1, MAX_CASCADES) as usize - 1];
+        std::array::from_fn(|i| fractions.get(i).map_or(zfar, |f| znear + f * (zfar - znear)))
+    }
+
+    /// Depth array holding one layer per cascade.
+    pub fn create_texture(&self, device: &wgpu::Device) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow Texture Array"),
+            size: wgpu::Extent3d {
+                width: self.resolution,
+                height: self.resolution,
+                depth_or_array_layers: self.cascades,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+    }
+}
+
+pub fn array_view(texture: &wgpu::Texture) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("Shadow Texture View"),
+        format: Some(wgpu::TextureFormat::Depth32Float),
+        dimension: Some(wgpu::TextureViewDimension::D2Array),
+        aspect: wgpu::TextureAspect::All,
+        base_mip_level: 0,
+        mip_level_count: None,
+        base_array_layer: 0,
+        array_layer_count: Some(texture.depth_or_array_layers()),
+    })
+}
@@ -0,0 +1,153 @@
+use cgmath::{InnerSpace, Vector3};
+
+const SKY_WIDTH: u32 = 512;
+const SKY_HEIGHT: u32 = 256;
+const SKY_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// Converts the model's kcd/m² into roughly the range of the HDR environments.
+const RADIANCE_SCALE: f32 = 0.15;
+pub const DEFAULT_TURBIDITY: f32 = 2.5;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SkyParams {
+    sun: [f32; 4],
+    bake: [f32; 4],
+}
+
+/// Analytic daylight sky used as the environment map when no HDR is available. The sky
+/// is baked into a small equirectangular texture, so the sky pass and image-based
+/// lighting sample it like an HDR; it is re-baked only when the sun moves.
+pub struct ProceduralSky {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub turbidity: f32,
+    pipeline: wgpu::RenderPipeline,
+    params_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    baked: Option<(Vector3<f32>, f32)>,
+}
+
+impl ProceduralSky {
+    pub fn new(device: &wgpu::Device, turbidity: f32) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Procedural Sky"),
+            size: wgpu::Extent3d {
+                width: SKY_WIDTH,
+                height: SKY_HEIGHT,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SKY_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Sky Params Buffer"),
+            size: std::mem::size_of::<SkyParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("sky_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("sky_bind_group"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            }],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Procedural Sky Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("sky.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Procedural Sky Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Procedural Sky Pipeline"),
+            layout: Some(&pipeline_layout),
+            cache: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_fullscreen",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_bake",
+                targets: &[Some(SKY_FORMAT.into())],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            texture,
+            view,
+            turbidity,
+            pipeline,
+            params_buffer,
+            bind_group,
+            baked: None,
+        }
+    }
+
+    /// Re-renders the sky if the sun (given as the direction its light travels) or the
+    /// turbidity changed since the last bake.
+    pub fn bake(&mut self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, light_dir: Vector3<f32>) {
+        let to_sun = -light_dir.normalize();
+        if self
+            .baked
+            .is_some_and(|(sun, turbidity)| sun.dot(to_sun) > 0.99999 && turbidity == self.turbidity)
+        {
+            return;
+        }
+        self.baked = Some((to_sun, self.turbidity));
+
+        let params = SkyParams {
+            sun: [to_sun.x, to_sun.y, to_sun.z, self.turbidity],
+            bake: [SKY_WIDTH as f32, SKY_HEIGHT as f32, RADIANCE_SCALE, 0.0],
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Procedural Sky Bake"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,96 @@
+// Preetham et al. 1999 analytic daylight sky, baked into an equirectangular map that
+// replaces the HDR environment for both the sky pass and image-based lighting.
+
+struct SkyParams {
+    // xyz: direction towards the sun, w: turbidity.
+    sun: vec4<f32>,
+    // xy: target size in texels, z: radiance scale.
+    bake: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> params: SkyParams;
+
+const PI: f32 = 3.14159265359;
+const NIGHT: vec3<f32> = vec3<f32>(0.002, 0.003, 0.008);
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vid << 1u) & 2u), f32(vid & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// Inverse of dir_to_equirect_uv in shader.wgsl.
+fn equirect_dir(uv: vec2<f32>) -> vec3<f32> {
+    let phi = (uv.x - 0.5) * 2.0 * PI;
+    let theta = uv.y * PI;
+    return vec3<f32>(sin(theta) * cos(phi), cos(theta), sin(theta) * sin(phi));
+}
+
+fn perez(theta: f32, gamma: f32, a: f32, b: f32, c: f32, d: f32, e: f32) -> f32 {
+    let cos_gamma = cos(gamma);
+    return (1.0 + a * exp(b / max(cos(theta), 0.01))) * (1.0 + c * exp(d * gamma) + e * cos_gamma * cos_gamma);
+}
+
+fn zenith_chromaticity(t: f32, theta_s: f32, c0: vec4<f32>, c1: vec4<f32>, c2: vec4<f32>) -> f32 {
+    let th = vec4<f32>(theta_s * theta_s * theta_s, theta_s * theta_s, theta_s, 1.0);
+    return t * t * dot(c0, th) + t * dot(c1, th) + dot(c2, th);
+}
+
+fn preetham(dir: vec3<f32>, sun: vec3<f32>, t: f32) -> vec3<f32> {
+    // The model is only defined above the horizon.
+    let theta = acos(clamp(dir.y, 0.001, 1.0));
+    let theta_s = acos(clamp(sun.y, 0.001, 1.0));
+    let gamma = acos(clamp(dot(normalize(vec3<f32>(dir.x, max(dir.y, 0.001), dir.z)), sun), -1.0, 1.0));
+
+    let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+    let zenith_y = (4.0453 * t - 4.9710) * tan(chi) - 0.2155 * t + 2.4192;
+    let zenith_x = zenith_chromaticity(t, theta_s,
+        vec4<f32>(0.00166, -0.00375, 0.00209, 0.0),
+        vec4<f32>(-0.02903, 0.06377, -0.03202, 0.00394),
+        vec4<f32>(0.11693, -0.21196, 0.06052, 0.25886));
+    let zenith_yc = zenith_chromaticity(t, theta_s,
+        vec4<f32>(0.00275, -0.00610, 0.00317, 0.0),
+        vec4<f32>(-0.04214, 0.08970, -0.04153, 0.00516),
+        vec4<f32>(0.15346, -0.26756, 0.06670, 0.26688));
+
+    let ay = 0.1787 * t - 1.4630;
+    let by = -0.3554 * t + 0.4275;
+    let cy = -0.0227 * t + 5.3251;
+    let dy = 0.1206 * t - 2.5771;
+    let ey = -0.0670 * t + 0.3703;
+    let ax = -0.0193 * t - 0.2592;
+    let bx = -0.0665 * t + 0.0008;
+    let cx = -0.0004 * t + 0.2125;
+    let dx = -0.0641 * t - 0.8989;
+    let ex = -0.0033 * t + 0.0452;
+    let ayc = -0.0167 * t - 0.2608;
+    let byc = -0.0950 * t + 0.0092;
+    let cyc = -0.0079 * t + 0.2102;
+    let dyc = -0.0441 * t - 1.6537;
+    let eyc = -0.0109 * t + 0.0529;
+
+    let lum = zenith_y * perez(theta, gamma, ay, by, cy, dy, ey) / perez(0.0, theta_s, ay, by, cy, dy, ey);
+    let x = zenith_x * perez(theta, gamma, ax, bx, cx, dx, ex) / perez(0.0, theta_s, ax, bx, cx, dx, ex);
+    let y = zenith_yc * perez(theta, gamma, ayc, byc, cyc, dyc, eyc) / perez(0.0, theta_s, ayc, byc, cyc, dyc, eyc);
+
+    let xyz = vec3<f32>(x / y * lum, lum, (1.0 - x - y) / y * lum);
+    let rgb = vec3<f32>(
+        3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+        0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z,
+    );
+    return max(rgb, vec3<f32>(0.0));
+}
+
+@fragment
+fn fs_bake(@builtin(position) frag: vec4<f32>) -> @location(0) vec4<f32> {
+    let dir = equirect_dir(frag.xy / params.bake.xy);
+    let sun = normalize(params.sun.xyz);
+    var sky = preetham(dir, sun, params.sun.w) * params.bake.z;
+    // Below the horizon, darken towards a neutral ground.
+    sky = mix(sky * 0.3, sky, smoothstep(-0.1, 0.02, dir.y));
+    // Fade to night as the sun sets, where the model stops being valid.
+    sky = mix(NIGHT, sky, smoothstep(-0.1, 0.05, sun.y));
+    return vec4<f32>(sky, 1.0);
+}
@@ -0,0 +1,250 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use cgmath::{Matrix4, Point3, Vector3};
+use serde_json::{json, Value};
+
+use crate::camera::{DEFAULT_ENV_NITS, DEFAULT_EV100};
+use crate::latency::{self, LatencySettings};
+use crate::lights::{DirectionalLight, SpotLight, DEFAULT_SUN_LUX};
+
+pub const DEFAULT_SNAPSHOT_PATH: &str = "dusk_snapshot.json";
+/// Version 2 stores light intensities in lux and candela and the environment in nits.
+const VERSION: u64 = 2;
+
+#[derive(Copy, Clone, Debug)]
+pub struct CameraState {
+    pub position: Point3<f32>,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub fovy: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct InstanceState {
+    pub path: PathBuf,
+    pub transform: Matrix4<f32>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct AnimationState {
+    pub clip: usize,
+    pub time: f32,
+    pub speed: f32,
+    pub looping: bool,
+    pub playing: bool,
+}
+
+/// Everything needed to come back to the same view of the same scene later: the camera,
+/// where each model was placed, runtime lights and presentation settings.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub camera: CameraState,
+    pub instances: Vec<InstanceState>,
+    pub directional_lights: Vec<DirectionalLight>,
+    pub env_intensity: f32,
+    pub ev100: f32,
+    /// Runtime spot lights, not counting the flashlight.
+    pub spot_lights: Vec<SpotLight>,
+    pub flashlight: bool,
+    pub animation: Option<AnimationState>,
+    pub latency: LatencySettings,
+}
+
+impl Snapshot {
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let c = &self.camera;
+        let value = json!({
+            "version": VERSION,
+            "camera": {
+                "position": [c.position.x, c.position.y, c.position.z],
+                "yaw": c.yaw,
+                "pitch": c.pitch,
+                "fovy": c.fovy,
+            },
+            "models": self.instances.iter().map(|i| json!({
+                "path": i.path.to_string_lossy(),
+                "matrix": matrix_to_json(&i.transform),
+            })).collect::<Vec<_>>(),
+            "directional_lights": self.directional_lights.iter().map(|l| json!({
+                "direction": [l.direction.x, l.direction.y, l.direction.z],
+                "color": l.color,
+                "intensity": l.intensity,
+                "cookie": l.cookie,
+                "cookie_size": l.cookie_size,
+            })).collect::<Vec<_>>(),
+            "env_intensity": self.env_intensity,
+            "ev100": self.ev100,
+            "spot_lights": self.spot_lights.iter().map(spot_to_json).collect::<Vec<_>>(),
+            "flashlight": self.flashlight,
+            "animation": self.animation.map(|a| json!({
+                "clip": a.clip,
+                "time": a.time,
+                "speed": a.speed,
+                "looping": a.looping,
+                "playing": a.playing,
+            })),
+            "latency": {
+                "max_frame_latency": self.latency.max_frame_latency,
+                "wait_before_present": self.latency.wait_before_present,
+                "prefer_mailbox": self.latency.prefer_mailbox,
+                "present_mode": self.latency.present_mode.and_then(latency::present_mode_name),
+            },
+        });
+        let text = serde_json::to_string_pretty(&value)?;
+        std::fs::write(path, text).with_context(|| format!("writing snapshot {}", path.display()))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("reading snapshot {}", path.display()))?;
+        let value: Value =
+            serde_json::from_str(&text).with_context(|| format!("parsing snapshot {}", path.display()))?;
+        let version = value.get("version").and_then(Value::as_u64).unwrap_or(0);
+        if version == 0 || version > VERSION {
+            anyhow::bail!("Snapshot {} has unsupported version {}", path.display(), version);
+        }
+        // Version 1 intensities were used unexposed, which an EV100 of log2(1 / 1.2)
+        // reproduces.
+        let (sun_lux, env_nits, ev100) = if version == 1 {
+            (6.0, 1.0, (1.0f32 / 1.2).log2())
+        } else {
+            (DEFAULT_SUN_LUX, DEFAULT_ENV_NITS, DEFAULT_EV100)
+        };
+
+        let camera = value.get("camera").context("snapshot has no camera")?;
+        let camera = CameraState {
+            position: Point3::from(
+                camera.get("position").and_then(floats::<3>).context("snapshot camera has no position")?,
+            ),
+            yaw: float(camera, "yaw").unwrap_or(0.0),
+            pitch: float(camera, "pitch").unwrap_or(0.0),
+            fovy: float(camera, "fovy").unwrap_or(45.0),
+        };
+
+        let instances = array(&value, "models")
+            .filter_map(|m| {
+                Some(InstanceState {
+                    path: PathBuf::from(m.get("path")?.as_str()?),
+                    transform: matrix_from_json(m.get("matrix")?)?,
+                })
+            })
+            .collect();
+
+        let latency = value.get("latency");
+        let defaults = LatencySettings::default();
+        let latency = LatencySettings {
+            max_frame_latency: latency
+                .and_then(|l| l.get("max_frame_latency"))
+                .and_then(Value::as_u64)
+                .map_or(defaults.max_frame_latency, |v| v as u32),
+            wait_before_present: latency
+                .and_then(|l| l.get("wait_before_present"))
+                .and_then(Value::as_bool)
+                .unwrap_or(defaults.wait_before_present),
+            prefer_mailbox: latency
+                .and_then(|l| l.get("prefer_mailbox"))
+                .and_then(Value::as_bool)
+                .unwrap_or(defaults.prefer_mailbox),
+            present_mode: latency
+                .and_then(|l| l.get("present_mode"))
+                .and_then(Value::as_str)
+                .and_then(latency::parse_present_mode),
+        };
+
+        Ok(Self {
+            camera,
+            instances,
+            directional_lights: array(&value, "directional_lights")
+                .filter_map(|l| {
+                    let mut light = DirectionalLight::new(
+                        Vector3::from(l.get("direction").and_then(floats::<3>)?),
+                        l.get("color").and_then(floats::<3>).unwrap_or([1.0; 3]),
+                        float(l, "intensity").unwrap_or(sun_lux),
+                    );
+                    light.cookie = cookie_from_json(l);
+                    light.cookie_size = float(l, "cookie_size").unwrap_or(light.cookie_size);
+                    Some(light)
+                })
+                .collect(),
+            env_intensity: float(&value, "env_intensity").unwrap_or(env_nits),
+            ev100: float(&value, "ev100").unwrap_or(ev100),
+            spot_lights: array(&value, "spot_lights").filter_map(spot_from_json).collect(),
+            flashlight: value.get("flashlight").and_then(Value::as_bool).unwrap_or(false),
+            animation: value.get("animation").filter(|a| !a.is_null()).map(|a| AnimationState {
+                clip: a.get("clip").and_then(Value::as_u64).unwrap_or(0) as usize,
+                time: float(a, "time").unwrap_or(0.0),
+                speed: float(a, "speed").unwrap_or(1.0),
+                looping: a.get("looping").and_then(Value::as_bool).unwrap_or(true),
+                playing: a.get("playing").and_then(Value::as_bool).unwrap_or(true),
+            }),
+            latency,
+        })
+    }
+}
+
+fn float(value: &Value, key: &str) -> Option<f32> {
+    value.get(key).and_then(Value::as_f64).map(|v| v as f32)
+}
+
+fn floats<const N: usize>(value: &Value) -> Option<[f32; N]> {
+    let array = value.as_array()?;
+    if array.len() != N {
+        return None;
+    }
+    let mut out = [0.0; N];
+    for (o, v) in out.iter_mut().zip(array) {
+        *o = v.as_f64()? as f32;
+    }
+    Some(out)
+}
+
+fn array<'a>(value: &'a Value, key: &str) -> impl Iterator<Item = &'a Value> {
+    value.get(key).and_then(Value::as_array).into_iter().flatten()
+}
+
+/// Column-major, like glTF node matrices.
+fn matrix_to_json(m: &Matrix4<f32>) -> Value {
+    let cols: &[f32; 16] = m.as_ref();
+    json!(cols)
+}
+
+fn matrix_from_json(value: &Value) -> Option<Matrix4<f32>> {
+    let m = floats::<16>(value)?;
+    let m: &Matrix4<f32> = (&m).into();
+    Some(*m)
+}
+
+fn spot_to_json(light: &SpotLight) -> Value {
+    json!({
+        "position": [light.position.x, light.position.y, light.position.z],
+        "direction": [light.direction.x, light.direction.y, light.direction.z],
+        "color": light.color,
+        "intensity": light.intensity,
+        "range": light.range,
+        "inner_angle": light.inner_angle,
+        "outer_angle": light.outer_angle,
+        "shadows": light.cast_shadows,
+        "cookie": light.cookie,
+    })
+}
+
+fn spot_from_json(value: &Value) -> Option<SpotLight> {
+    let mut light = SpotLight::new(
+        Point3::from(value.get("position").and_then(floats::<3>)?),
+        Vector3::from(value.get("direction").and_then(floats::<3>)?),
+        value.get("color").and_then(floats::<3>).unwrap_or([1.0; 3]),
+        float(value, "intensity").unwrap_or(10.0),
+        float(value, "range"),
+    );
+    light.inner_angle = float(value, "inner_angle").unwrap_or(light.inner_angle);
+    light.outer_angle = float(value, "outer_angle").unwrap_or(light.outer_angle);
+    light.cast_shadows = value.get("shadows").and_then(Value::as_bool).unwrap_or(true);
+    light.cookie = cookie_from_json(value);
+    Some(light)
+}
+
+/// Cookies are saved as indices into the scene's cookie list, so they only restore
+/// against the scene the snapshot was taken in.
+fn cookie_from_json(value: &Value) -> Option<usize> {
+    value.get("cookie").and_then(Value::as_u64).map(|c| c as usize)
+}
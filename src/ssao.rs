@@ -0,0 +1,246 @@
+use crate::camera::DepthMode;
+use crate::pipelines::depth_shader;
+
+const AO_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+/// Occluders within a metre darken by default.
+pub const DEFAULT_RADIUS: f32 = 1.0;
+pub const DEFAULT_INTENSITY: f32 = 1.5;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct AoParams {
+    params: [f32; 4],
+}
+
+fn ao_texture(device: &wgpu::Device, label: &str, width: u32, height: u32) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: AO_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    (texture, view)
+}
+
+fn fullscreen_pass<'e>(encoder: &'e mut wgpu::CommandEncoder, label: &str, view: &wgpu::TextureView) -> wgpu::RenderPass<'e> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        occlusion_query_set: None,
+        timestamp_writes: None,
+    })
+}
+
+/// Screen-space ambient occlusion from a depth prepass: GTAO at full resolution, then a
+/// depth-aware blur into `view`, which the main pass multiplies into ambient light.
+pub struct Ssao {
+    pub enabled: bool,
+    pub radius: f32,
+    pub intensity: f32,
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    raw_texture: wgpu::Texture,
+    raw_view: wgpu::TextureView,
+    params_buffer: wgpu::Buffer,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    gtao_pipeline: wgpu::RenderPipeline,
+    blur_pipeline: wgpu::RenderPipeline,
+}
+
+impl Ssao {
+    pub fn new(
+        device: &wgpu::Device,
+        camera_buffer: &wgpu::Buffer,
+        depth_view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+        depth_mode: DepthMode,
+    ) -> Self {
+        let (raw_texture, raw_view) = ao_texture(device, "SSAO Raw", width, height);
+        let (texture, view) = ao_texture(device, "SSAO", width, height);
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("SSAO Params Buffer"),
+            size: std::mem::size_of::<AoParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let uniform = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("ssao_bind_group_layout"),
+            entries: &[
+                uniform(0),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                uniform(2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = Self::create_bind_group(device, &layout, camera_buffer, depth_view, &params_buffer, &raw_view);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("SSAO Shader"),
+            source: wgpu::ShaderSource::Wgsl(depth_shader(include_str!("ssao.wgsl"), depth_mode).into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("SSAO Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |label: &str, entry_point: &str| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                cache: None,
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_fullscreen",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point,
+                    targets: &[Some(AO_FORMAT.into())],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        let gtao_pipeline = pipeline("GTAO Pipeline", "fs_gtao");
+        let blur_pipeline = pipeline("SSAO Blur Pipeline", "fs_blur");
+
+        Self {
+            enabled: true,
+            radius: DEFAULT_RADIUS,
+            intensity: DEFAULT_INTENSITY,
+            texture,
+            view,
+            raw_texture,
+            raw_view,
+            params_buffer,
+            layout,
+            bind_group,
+            gtao_pipeline,
+            blur_pipeline,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        camera_buffer: &wgpu::Buffer,
+        depth_view: &wgpu::TextureView,
+        params_buffer: &wgpu::Buffer,
+        raw_view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("ssao_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(raw_view),
+                },
+            ],
+        })
+    }
+
+    /// Reallocates both AO targets for a new size, returning the old textures so they
+    /// can be destroyed once the GPU is done with them.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        camera_buffer: &wgpu::Buffer,
+        depth_view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+    ) -> [wgpu::Texture; 2] {
+        let (raw_texture, raw_view) = ao_texture(device, "SSAO Raw", width, height);
+        let (texture, view) = ao_texture(device, "SSAO", width, height);
+        self.raw_view = raw_view;
+        self.view = view;
+        self.bind_group =
+            Self::create_bind_group(device, &self.layout, camera_buffer, depth_view, &self.params_buffer, &self.raw_view);
+        [
+            std::mem::replace(&mut self.raw_texture, raw_texture),
+            std::mem::replace(&mut self.texture, texture),
+        ]
+    }
+
+    /// Computes and blurs the occlusion; the depth view must hold this frame's prepass.
+    pub fn record(&self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue) {
+        let params = AoParams {
+            params: [self.radius, self.intensity, 0.0, 0.0],
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+        {
+            let mut pass = fullscreen_pass(encoder, "GTAO", &self.raw_view);
+            pass.set_pipeline(&self.gtao_pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        let mut pass = fullscreen_pass(encoder, "SSAO Blur", &self.view);
+        pass.set_pipeline(&self.blur_pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
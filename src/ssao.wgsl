@@ -0,0 +1,134 @@
+// Ground-truth ambient occlusion (Jimenez et al. 2016, after XeGTAO) from the depth
+// prepass, with normals reconstructed from depth, followed by a depth-aware blur.
+
+// Prefix of CameraUniform in shader.wgsl.
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    view_inv: mat4x4<f32>,
+    proj_inv: mat4x4<f32>,
+};
+
+struct AoParams {
+    // x: radius in world units, y: intensity exponent.
+    params: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(0) @binding(1)
+var depth_map: texture_depth_2d;
+
+@group(0) @binding(2)
+var<uniform> ao: AoParams;
+
+@group(0) @binding(3)
+var raw_ao: texture_2d<f32>;
+
+const PI: f32 = 3.14159265359;
+const HALF_PI: f32 = 1.57079632679;
+const SLICES: i32 = 2;
+const STEPS: i32 = 4;
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vid << 1u) & 2u), f32(vid & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+fn view_position(pixel: vec2<i32>) -> vec3<f32> {
+    let size = vec2<i32>(textureDimensions(depth_map));
+    let p = clamp(pixel, vec2<i32>(0), size - 1);
+    let depth = textureLoad(depth_map, p, 0);
+    let uv = (vec2<f32>(p) + 0.5) / vec2<f32>(size);
+    let view = camera.proj_inv * vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    return view.xyz / view.w;
+}
+
+fn interleaved_gradient_noise(p: vec2<f32>) -> f32 {
+    return fract(52.9829189 * fract(dot(p, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+@fragment
+fn fs_gtao(@builtin(position) frag: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(frag.xy);
+    if textureLoad(depth_map, pixel, 0) == FAR_DEPTH {
+        return vec4<f32>(1.0);
+    }
+    let P = view_position(pixel);
+
+    // Take each derivative from the neighbour on the same surface.
+    let l = view_position(pixel - vec2<i32>(1, 0));
+    let r = view_position(pixel + vec2<i32>(1, 0));
+    let u = view_position(pixel - vec2<i32>(0, 1));
+    let d = view_position(pixel + vec2<i32>(0, 1));
+    let dx = select(P - l, r - P, abs(r.z - P.z) < abs(P.z - l.z));
+    let dy = select(P - u, d - P, abs(d.z - P.z) < abs(P.z - u.z));
+    let N = normalize(cross(dy, dx));
+    let V = normalize(-P);
+
+    let radius = ao.params.x;
+    let size = vec2<f32>(textureDimensions(depth_map));
+    // proj_inv[1][1] is tan(fovy / 2).
+    let radius_px = radius * 0.5 * size.y / (camera.proj_inv[1][1] * -P.z);
+    if radius_px < 1.0 {
+        return vec4<f32>(1.0);
+    }
+    let noise = interleaved_gradient_noise(frag.xy);
+    let jitter = fract(noise + 0.618034);
+
+    var visibility = 0.0;
+    for (var slice = 0; slice < SLICES; slice++) {
+        let phi = (f32(slice) + noise) * PI / f32(SLICES);
+        // Screen space points down, view space up.
+        let omega = vec2<f32>(cos(phi), -sin(phi));
+        let direction = vec3<f32>(cos(phi), sin(phi), 0.0);
+        let ortho = direction - dot(direction, V) * V;
+        let axis = normalize(cross(ortho, V));
+        let projected = N - axis * dot(N, axis);
+        let projected_len = length(projected);
+        let cos_n = clamp(dot(projected, V) / max(projected_len, 1e-4), -1.0, 1.0);
+        let n = sign(dot(ortho, projected)) * acos(cos_n);
+
+        // Horizons along +omega and -omega, starting at the tangent plane.
+        var horizon = vec2<f32>(cos(n + HALF_PI), cos(n - HALF_PI));
+        let low = horizon;
+        for (var step = 0; step < STEPS; step++) {
+            let t = (f32(step) + jitter) / f32(STEPS);
+            let offset = omega * (t * radius_px + 1.0);
+            let d0 = view_position(vec2<i32>(frag.xy + offset)) - P;
+            let d1 = view_position(vec2<i32>(frag.xy - offset)) - P;
+            let len = vec2<f32>(length(d0), length(d1));
+            let cosines = vec2<f32>(dot(d0, V), dot(d1, V)) / max(len, vec2<f32>(1e-4));
+            // Fade out occluders towards the radius so distant geometry does not darken.
+            let falloff = clamp(2.0 - 2.0 * len / radius, vec2<f32>(0.0), vec2<f32>(1.0));
+            horizon = max(horizon, mix(low, cosines, falloff));
+        }
+        let h0 = n + max(-acos(horizon.y) - n, -HALF_PI);
+        let h1 = n + min(acos(horizon.x) - n, HALF_PI);
+        let arc0 = cos_n + 2.0 * h0 * sin(n) - cos(2.0 * h0 - n);
+        let arc1 = cos_n + 2.0 * h1 * sin(n) - cos(2.0 * h1 - n);
+        visibility += projected_len * 0.25 * (arc0 + arc1);
+    }
+    let occlusion = clamp(visibility / f32(SLICES), 0.0, 1.0);
+    return vec4<f32>(pow(occlusion, ao.params.y));
+}
+
+// 5x5 blur that skips samples across depth discontinuities.
+@fragment
+fn fs_blur(@builtin(position) frag: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(frag.xy);
+    let center = view_position(pixel).z;
+    var sum = 0.0;
+    var total = 0.0;
+    for (var y = -2; y <= 2; y++) {
+        for (var x = -2; x <= 2; x++) {
+            let p = clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), vec2<i32>(textureDimensions(raw_ao)) - 1);
+            let z = view_position(p).z;
+            let w = max(0.0, 1.0 - abs(z - center) / (abs(center) * 0.05 + 1e-3));
+            sum += textureLoad(raw_ao, p, 0).r * w;
+            total += w;
+        }
+    }
+    return vec4<f32>(sum / max(total, 1e-4));
+}
@@ -0,0 +1,319 @@
+use crate::camera::DepthMode;
+use crate::pipelines::depth_shader;
+use crate::post::HDR_FORMAT;
+
+const GI_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+pub const DEFAULT_RADIUS: f32 = 2.0;
+pub const DEFAULT_INTENSITY: f32 = 1.0;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct GiParams {
+    prev_view_proj: [[f32; 4]; 4],
+    settings: [f32; 4],
+}
+
+struct GiTargets {
+    output: wgpu::Texture,
+    output_view: wgpu::TextureView,
+    history: wgpu::Texture,
+    history_view: wgpu::TextureView,
+    color: wgpu::Texture,
+    color_view: wgpu::TextureView,
+}
+
+impl GiTargets {
+    fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let texture = |label: &str, size: (u32, u32), format, usage| {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: size.0,
+                    height: size.1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | usage,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            (texture, view)
+        };
+        let half = (width.div_ceil(2), height.div_ceil(2));
+        let (output, output_view) = texture(
+            "SSGI",
+            half,
+            GI_FORMAT,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        );
+        let (history, history_view) = texture("SSGI History", half, GI_FORMAT, wgpu::TextureUsages::COPY_DST);
+        let (color, color_view) = texture("SSGI Color History", (width, height), HDR_FORMAT, wgpu::TextureUsages::COPY_DST);
+        Self {
+            output,
+            output_view,
+            history,
+            history_view,
+            color,
+            color_view,
+        }
+    }
+}
+
+/// Half-resolution screen-space diffuse GI traced against the depth prepass, lighting
+/// hits with the previous frame's HDR color and accumulating over frames into `view`.
+pub struct Ssgi {
+    pub enabled: bool,
+    pub radius: f32,
+    pub intensity: f32,
+    targets: GiTargets,
+    params_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    prev_view_proj: [[f32; 4]; 4],
+    frame: u32,
+    history_valid: bool,
+}
+
+impl Ssgi {
+    pub fn new(
+        device: &wgpu::Device,
+        camera_buffer: &wgpu::Buffer,
+        depth_view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+        depth_mode: DepthMode,
+    ) -> Self {
+        let targets = GiTargets::new(device, width, height);
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("SSGI Params Buffer"),
+            size: std::mem::size_of::<GiParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("SSGI Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let uniform = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let texture = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type,
+            },
+            count: None,
+        };
+        let float = wgpu::TextureSampleType::Float { filterable: true };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("ssgi_bind_group_layout"),
+            entries: &[
+                uniform(0),
+                texture(1, wgpu::TextureSampleType::Depth),
+                uniform(2),
+                texture(3, float),
+                texture(4, float),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = Self::create_bind_group(
+            device,
+            &layout,
+            camera_buffer,
+            depth_view,
+            (&params_buffer, &sampler),
+            &targets,
+        );
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("SSGI Shader"),
+            source: wgpu::ShaderSource::Wgsl(depth_shader(include_str!("ssgi.wgsl"), depth_mode).into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("SSGI Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("SSGI Pipeline"),
+            layout: Some(&pipeline_layout),
+            cache: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_fullscreen",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_trace",
+                targets: &[Some(GI_FORMAT.into())],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            enabled: false,
+            radius: DEFAULT_RADIUS,
+            intensity: DEFAULT_INTENSITY,
+            targets,
+            params_buffer,
+            sampler,
+            layout,
+            bind_group,
+            pipeline,
+            prev_view_proj: [[0.0; 4]; 4],
+            frame: 0,
+            history_valid: false,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        camera_buffer: &wgpu::Buffer,
+        depth_view: &wgpu::TextureView,
+        params: (&wgpu::Buffer, &wgpu::Sampler),
+        targets: &GiTargets,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("ssgi_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params.0.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&targets.color_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&targets.history_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::Sampler(params.1),
+                },
+            ],
+        })
+    }
+
+    /// The accumulated GI at half resolution; alpha is the view depth it was traced at.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.targets.output_view
+    }
+
+    /// Turning GI back on starts accumulation afresh rather than from stale history.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.history_valid = false;
+    }
+
+    /// Reallocates the targets for a new size, returning the old textures so they can be
+    /// destroyed once the GPU is done with them.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        camera_buffer: &wgpu::Buffer,
+        depth_view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+    ) -> [wgpu::Texture; 3] {
+        let old = std::mem::replace(&mut self.targets, GiTargets::new(device, width, height));
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.layout,
+            camera_buffer,
+            depth_view,
+            (&self.params_buffer, &self.sampler),
+            &self.targets,
+        );
+        self.history_valid = false;
+        [old.output, old.history, old.color]
+    }
+
+    /// Traces and accumulates this frame's GI; the depth view must hold this frame's
+    /// prepass and `view_proj` must match the camera buffer.
+    pub fn record(&mut self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, view_proj: [[f32; 4]; 4]) {
+        let params = GiParams {
+            prev_view_proj: self.prev_view_proj,
+            settings: [
+                self.radius,
+                self.intensity,
+                self.frame as f32,
+                if self.history_valid { 1.0 } else { 0.0 },
+            ],
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("SSGI"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.targets.output_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        encoder.copy_texture_to_texture(
+            self.targets.output.as_image_copy(),
+            self.targets.history.as_image_copy(),
+            self.targets.output.size(),
+        );
+        self.prev_view_proj = view_proj;
+        self.frame = (self.frame + 1) % 1024;
+        self.history_valid = true;
+    }
+
+    /// Keeps this frame's lit HDR color for the next frame's rays to pick up.
+    pub fn store_color(&self, encoder: &mut wgpu::CommandEncoder, hdr: &wgpu::Texture) {
+        encoder.copy_texture_to_texture(hdr.as_image_copy(), self.targets.color.as_image_copy(), hdr.size());
+    }
+}
@@ -0,0 +1,150 @@
+// Half-resolution screen-space diffuse GI: short cosine-distributed rays marched against
+// the depth prepass pick up last frame's lit color, then blend with reprojected history.
+
+// Prefix of CameraUniform in shader.wgsl.
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    view_inv: mat4x4<f32>,
+    proj_inv: mat4x4<f32>,
+};
+
+struct GiParams {
+    prev_view_proj: mat4x4<f32>,
+    // x: ray length in world units, y: intensity, z: frame index, w: 1 when history is valid.
+    settings: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(0) @binding(1)
+var depth_map: texture_depth_2d;
+
+@group(0) @binding(2)
+var<uniform> gi: GiParams;
+
+// Last frame's HDR color, before tonemapping.
+@group(0) @binding(3)
+var color_history: texture_2d<f32>;
+
+// Last frame's accumulated GI; alpha holds the view depth it was traced at.
+@group(0) @binding(4)
+var gi_history: texture_2d<f32>;
+
+@group(0) @binding(5)
+var linear_sampler: sampler;
+
+const PI: f32 = 3.14159265359;
+const RAYS: i32 = 2;
+const STEPS: i32 = 8;
+const HISTORY_WEIGHT: f32 = 0.9;
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vid << 1u) & 2u), f32(vid & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+fn view_position(pixel: vec2<i32>) -> vec3<f32> {
+    let size = vec2<i32>(textureDimensions(depth_map));
+    let p = clamp(pixel, vec2<i32>(0), size - 1);
+    let depth = textureLoad(depth_map, p, 0);
+    let uv = (vec2<f32>(p) + 0.5) / vec2<f32>(size);
+    let view = camera.proj_inv * vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    return view.xyz / view.w;
+}
+
+fn to_world(view: vec3<f32>) -> vec3<f32> {
+    return (camera.view_inv * vec4<f32>(view, 1.0)).xyz;
+}
+
+// Screen uv of a world position under `view_proj`; z is negative behind the camera.
+fn project(view_proj: mat4x4<f32>, world: vec3<f32>) -> vec3<f32> {
+    let clip = view_proj * vec4<f32>(world, 1.0);
+    let ndc = clip.xy / clip.w;
+    return vec3<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5, clip.w);
+}
+
+fn on_screen(uv: vec3<f32>) -> bool {
+    return uv.z > 0.0 && all(uv.xy >= vec2<f32>(0.0)) && all(uv.xy <= vec2<f32>(1.0));
+}
+
+fn hash(p: vec2<f32>, seed: f32) -> f32 {
+    return fract(sin(dot(p + seed, vec2<f32>(12.9898, 78.233))) * 43758.5453);
+}
+
+fn cosine_direction(n: vec3<f32>, u: f32, v: f32) -> vec3<f32> {
+    let up = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), abs(n.y) < 0.99);
+    let t = normalize(cross(up, n));
+    let b = cross(n, t);
+    let r = sqrt(u);
+    let phi = 2.0 * PI * v;
+    return normalize(t * (r * cos(phi)) + b * (r * sin(phi)) + n * sqrt(max(1.0 - u, 0.0)));
+}
+
+// Radiance along one view-space ray, or zero if it leaves the screen or hits nothing
+// within the ray length.
+fn trace(origin: vec3<f32>, dir: vec3<f32>, jitter: f32) -> vec3<f32> {
+    let ray_length = gi.settings.x;
+    let size = vec2<f32>(textureDimensions(depth_map));
+    let thickness = ray_length * 0.5;
+    for (var step = 0; step < STEPS; step++) {
+        let s = origin + dir * ((f32(step) + jitter) / f32(STEPS) * ray_length);
+        let uv = project(camera.view_proj, to_world(s));
+        if !on_screen(uv) {
+            break;
+        }
+        let scene = view_position(vec2<i32>(uv.xy * size));
+        let behind = scene.z - s.z;
+        if behind > 0.0 && behind < thickness {
+            let prev = project(gi.prev_view_proj, to_world(scene));
+            if !on_screen(prev) {
+                break;
+            }
+            let radiance = textureSampleLevel(color_history, linear_sampler, prev.xy, 0.0).rgb;
+            return min(radiance, vec3<f32>(16.0));
+        }
+    }
+    return vec3<f32>(0.0);
+}
+
+@fragment
+fn fs_trace(@builtin(position) frag: vec4<f32>) -> @location(0) vec4<f32> {
+    let frame = gi.settings.z;
+    // Each half-res texel traces from a different full-res pixel of its 2x2 block per frame.
+    let offset = vec2<i32>(i32(frame) & 1, (i32(frame) >> 1u) & 1);
+    let pixel = vec2<i32>(frag.xy) * 2 + offset;
+    let clamped = clamp(pixel, vec2<i32>(0), vec2<i32>(textureDimensions(depth_map)) - 1);
+    if textureLoad(depth_map, clamped, 0) == FAR_DEPTH {
+        return vec4<f32>(0.0);
+    }
+    let P = view_position(pixel);
+    let l = view_position(pixel - vec2<i32>(1, 0));
+    let r = view_position(pixel + vec2<i32>(1, 0));
+    let u = view_position(pixel - vec2<i32>(0, 1));
+    let d = view_position(pixel + vec2<i32>(0, 1));
+    let dx = select(P - l, r - P, abs(r.z - P.z) < abs(P.z - l.z));
+    let dy = select(P - u, d - P, abs(d.z - P.z) < abs(P.z - u.z));
+    let N = normalize(cross(dy, dx));
+    let origin = P + N * (0.01 * -P.z);
+
+    var radiance = vec3<f32>(0.0);
+    for (var ray = 0; ray < RAYS; ray++) {
+        let seed = frame * 0.618034 + f32(ray) * 7.0;
+        let dir = cosine_direction(N, hash(frag.xy, seed), hash(frag.yx, seed + 3.0));
+        radiance += trace(origin, dir, hash(frag.xy, seed + 5.0));
+    }
+    // Cosine-weighted rays make the mean radiance the diffuse irradiance over pi.
+    let current = radiance / f32(RAYS) * gi.settings.y;
+
+    let prev = project(gi.prev_view_proj, to_world(P));
+    var weight = 0.0;
+    var history = vec4<f32>(0.0);
+    if gi.settings.w > 0.5 && on_screen(prev) {
+        history = textureSampleLevel(gi_history, linear_sampler, prev.xy, 0.0);
+        // Reject history traced at a noticeably different depth (disocclusion).
+        let expected = prev.z;
+        weight = select(0.0, HISTORY_WEIGHT, abs(history.a - expected) < 0.1 * expected);
+    }
+    return vec4<f32>(mix(current, history.rgb, weight), -P.z);
+}
@@ -0,0 +1,130 @@
+use cgmath::{InnerSpace, Vector3};
+
+use crate::lights::DirectionalLight;
+
+/// Elevation at noon in time-of-day mode, in degrees.
+const NOON_ELEVATION: f32 = 70.0;
+/// Seconds for a full simulated day.
+pub const DEFAULT_DAY_LENGTH: f32 = 120.0;
+
+const NIGHT_SKY: [f32; 3] = [0.04, 0.06, 0.14];
+const DUSK_SKY: [f32; 3] = [0.9, 0.55, 0.45];
+const HORIZON_SUN: [f32; 3] = [1.0, 0.5, 0.25];
+/// Angular radius of the drawn sun disk in radians.
+pub const ANGULAR_RADIUS: f32 = 0.00465;
+
+pub(crate) fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn mix(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    std::array::from_fn(|i| a[i] + (b[i] - a[i]) * t)
+}
+
+/// Drives the primary directional light from an azimuth and elevation (degrees; azimuth
+/// from +X towards +Z), set by hand or following an animated time of day. It stays
+/// inactive, leaving the scene's light alone, until one of them is first changed.
+pub struct Sun {
+    pub azimuth: f32,
+    pub elevation: f32,
+    /// Hours in `0..24`; 6 is sunrise in the east (+X), 18 sunset in the west.
+    pub time_of_day: f32,
+    pub animate: bool,
+    pub day_length: f32,
+    /// The scene's light, whose color and intensity apply at full daylight.
+    base: DirectionalLight,
+    active: bool,
+}
+
+impl Sun {
+    pub fn from_light(light: &DirectionalLight) -> Self {
+        let to_sun = -light.direction.normalize();
+        Self {
+            azimuth: to_sun.z.atan2(to_sun.x).to_degrees().rem_euclid(360.0),
+            elevation: to_sun.y.clamp(-1.0, 1.0).asin().to_degrees(),
+            time_of_day: 12.0,
+            animate: false,
+            day_length: DEFAULT_DAY_LENGTH,
+            base: *light,
+            active: false,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Moves the sun by hand, which stops the time-of-day animation.
+    pub fn nudge(&mut self, azimuth: f32, elevation: f32) {
+        self.active = true;
+        self.animate = false;
+        self.azimuth = (self.azimuth + azimuth).rem_euclid(360.0);
+        self.elevation = (self.elevation + elevation).clamp(-10.0, 90.0);
+    }
+
+    /// Puts the sun at `azimuth` and `elevation` by hand, like `nudge`.
+    pub fn set_angles(&mut self, azimuth: f32, elevation: f32) {
+        self.nudge(azimuth - self.azimuth, elevation - self.elevation);
+    }
+
+    pub fn set_time_of_day(&mut self, hours: f32) {
+        self.active = true;
+        self.time_of_day = hours.rem_euclid(24.0);
+        self.follow_time();
+    }
+
+    pub fn toggle_animation(&mut self) {
+        self.animate = !self.animate;
+        if self.animate {
+            self.set_time_of_day(self.time_of_day);
+        }
+    }
+
+    pub fn advance(&mut self, dt: f32) {
+        if self.animate {
+            self.set_time_of_day(self.time_of_day + dt * 24.0 / self.day_length);
+        }
+    }
+
+    /// A simple east-to-west arc peaking at noon; at night the sun is below the horizon.
+    fn follow_time(&mut self) {
+        let t = (self.time_of_day - 6.0) / 12.0;
+        self.elevation = NOON_ELEVATION * (t * std::f32::consts::PI).sin();
+        self.azimuth = (180.0 * t).rem_euclid(360.0);
+    }
+
+    /// Direction the light travels in.
+    pub fn direction(&self) -> Vector3<f32> {
+        let (azimuth, elevation) = (self.azimuth.to_radians(), self.elevation.to_radians());
+        -Vector3::new(elevation.cos() * azimuth.cos(), elevation.sin(), elevation.cos() * azimuth.sin())
+    }
+
+    /// The primary light for the current position: warmer near the horizon and fading
+    /// out just below it.
+    pub fn light(&self) -> DirectionalLight {
+        let tint = mix(HORIZON_SUN, [1.0; 3], smoothstep(0.0, 30.0, self.elevation));
+        DirectionalLight::new(
+            self.direction(),
+            std::array::from_fn(|i| self.base.color[i] * tint[i]),
+            self.base.intensity * smoothstep(-4.0, 8.0, self.elevation),
+        )
+    }
+
+    /// Multiplier for the environment lighting and sky, from night blue through dusk.
+    pub fn sky_tint(&self) -> [f32; 3] {
+        if !self.active {
+            return [1.0; 3];
+        }
+        let dusk = mix(NIGHT_SKY, DUSK_SKY, smoothstep(-12.0, 2.0, self.elevation));
+        mix(dusk, [1.0; 3], smoothstep(2.0, 25.0, self.elevation))
+    }
+
+    /// `HH:MM` to the quarter hour while animating, for the window title.
+    pub fn clock_label(&self) -> Option<String> {
+        self.animate.then(|| {
+            let minutes = (self.time_of_day * 4.0) as u32 * 15;
+            format!("{:02}:{:02}", minutes / 60, minutes % 60)
+        })
+    }
+}
@@ -0,0 +1,304 @@
+use crate::camera::DepthMode;
+use crate::pipelines::depth_shader;
+use crate::post::{HdrTarget, HDR_FORMAT};
+
+/// Share of the reprojected history kept each frame.
+pub const DEFAULT_HISTORY_WEIGHT: f32 = 0.9;
+/// Length of the Halton(2, 3) jitter sequence.
+const JITTER_SAMPLES: u32 = 8;
+
+fn halton(mut index: u32, base: u32) -> f32 {
+    let (mut f, mut r) = (1.0, 0.0);
+    while index > 0 {
+        f /= base as f32;
+        r += f * (index % base) as f32;
+        index /= base;
+    }
+    r
+}
+
+struct TaaTargets {
+    current: wgpu::Texture,
+    current_view: wgpu::TextureView,
+    history: wgpu::Texture,
+    history_view: wgpu::TextureView,
+}
+
+impl TaaTargets {
+    fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let texture = |label: &str| {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: HDR_FORMAT,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            (texture, view)
+        };
+        let (current, current_view) = texture("TAA Current");
+        let (history, history_view) = texture("TAA History");
+        Self {
+            current,
+            current_view,
+            history,
+            history_view,
+        }
+    }
+}
+
+/// Temporal anti-aliasing: the projection is jittered by a sub-pixel Halton offset every
+/// frame and the HDR frame is resolved against the reprojected, neighbourhood-clipped
+/// history of earlier frames.
+pub struct Taa {
+    pub enabled: bool,
+    pub history_weight: f32,
+    targets: TaaTargets,
+    params_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    /// This frame's projection offset in NDC.
+    jitter: [f32; 2],
+    frame: u32,
+    history_valid: bool,
+}
+
+impl Taa {
+    pub fn new(
+        device: &wgpu::Device,
+        depth_view: &wgpu::TextureView,
+        velocity_view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+        depth_mode: DepthMode,
+    ) -> Self {
+        let targets = TaaTargets::new(device, width, height);
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("TAA Params Buffer"),
+            size: std::mem::size_of::<[f32; 4]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("TAA Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let texture = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("taa_bind_group_layout"),
+            entries: &[
+                texture(0, wgpu::TextureSampleType::Depth),
+                texture(1, wgpu::TextureSampleType::Float { filterable: false }),
+                texture(2, wgpu::TextureSampleType::Float { filterable: true }),
+                texture(3, wgpu::TextureSampleType::Float { filterable: false }),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = Self::create_bind_group(
+            device,
+            &layout,
+            (depth_view, velocity_view),
+            (&params_buffer, &sampler),
+            &targets,
+        );
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("TAA Shader"),
+            source: wgpu::ShaderSource::Wgsl(depth_shader(include_str!("taa.wgsl"), depth_mode).into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("TAA Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("TAA Pipeline"),
+            layout: Some(&pipeline_layout),
+            cache: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_fullscreen",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_resolve",
+                targets: &[Some(HDR_FORMAT.into())],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            enabled: false,
+            history_weight: DEFAULT_HISTORY_WEIGHT,
+            targets,
+            params_buffer,
+            sampler,
+            layout,
+            bind_group,
+            pipeline,
+            jitter: [0.0; 2],
+            frame: 0,
+            history_valid: false,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        (depth_view, velocity_view): (&wgpu::TextureView, &wgpu::TextureView),
+        params: (&wgpu::Buffer, &wgpu::Sampler),
+        targets: &TaaTargets,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("taa_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&targets.current_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&targets.history_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(velocity_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::Sampler(params.1),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: params.0.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// Advances the jitter sequence for a frame of `width`×`height` pixels and returns the
+    /// projection offset in NDC, zero while TAA is off.
+    pub fn next_jitter(&mut self, width: u32, height: u32) -> [f32; 2] {
+        self.jitter = if self.enabled {
+            self.frame = (self.frame + 1) % JITTER_SAMPLES;
+            let index = self.frame + 1;
+            [
+                (halton(index, 2) - 0.5) * 2.0 / width as f32,
+                (halton(index, 3) - 0.5) * 2.0 / height as f32,
+            ]
+        } else {
+            [0.0; 2]
+        };
+        self.jitter
+    }
+
+    pub fn jitter(&self) -> [f32; 2] {
+        self.jitter
+    }
+
+    /// Drops the history, for camera cuts and after toggling.
+    pub fn reset(&mut self) {
+        self.history_valid = false;
+    }
+
+    /// Reallocates the targets for a new size and rebinds the resized depth and velocity
+    /// buffers, returning the old textures so they can be destroyed once the GPU is done.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        views: (&wgpu::TextureView, &wgpu::TextureView),
+        width: u32,
+        height: u32,
+    ) -> [wgpu::Texture; 2] {
+        let old = std::mem::replace(&mut self.targets, TaaTargets::new(device, width, height));
+        self.bind_group =
+            Self::create_bind_group(device, &self.layout, views, (&self.params_buffer, &self.sampler), &self.targets);
+        self.history_valid = false;
+        [old.current, old.history]
+    }
+
+    /// Resolves `hdr` in place against the history and keeps the result as the next
+    /// frame's history. The velocity buffer must already hold this frame's motion.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, hdr: &HdrTarget) {
+        let settings = [
+            self.history_weight,
+            if self.history_valid { 1.0 } else { 0.0 },
+            0.0,
+            0.0,
+        ];
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&settings));
+
+        let size = hdr.texture.size();
+        encoder.copy_texture_to_texture(hdr.texture.as_image_copy(), self.targets.current.as_image_copy(), size);
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("TAA Resolve"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &hdr.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        encoder.copy_texture_to_texture(hdr.texture.as_image_copy(), self.targets.history.as_image_copy(), size);
+        self.history_valid = true;
+    }
+}
@@ -0,0 +1,149 @@
+// Temporal anti-aliasing resolve. The jittered frame is blended into a history that is
+// reprojected along the velocity buffer (taking the nearest surface's motion around each
+// pixel, so edges reproject with the foreground) and clipped to the variance of the current
+// 3×3 neighbourhood to reject stale history.
+
+struct TaaParams {
+    // x: history weight, y: 1 when history is valid.
+    settings: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var depth_map: texture_depth_2d;
+
+// Copy of this frame's jittered HDR color.
+@group(0) @binding(1)
+var color_map: texture_2d<f32>;
+
+// Last frame's resolved color.
+@group(0) @binding(2)
+var history_map: texture_2d<f32>;
+
+@group(0) @binding(3)
+var velocity_map: texture_2d<f32>;
+
+@group(0) @binding(4)
+var linear_sampler: sampler;
+
+@group(0) @binding(5)
+var<uniform> params: TaaParams;
+
+// Width of the neighbourhood box in standard deviations.
+const VARIANCE_GAMMA: f32 = 1.25;
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vid << 1u) & 2u), f32(vid & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+fn luma(c: vec3<f32>) -> f32 {
+    return dot(c, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// Blending in a compressed range keeps single bright pixels from dominating (Karis 2014).
+fn compress(c: vec3<f32>) -> vec3<f32> {
+    return c / (1.0 + luma(c));
+}
+
+fn uncompress(c: vec3<f32>) -> vec3<f32> {
+    return c / max(1.0 - luma(c), 1e-4);
+}
+
+fn rgb_to_ycocg(c: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        0.25 * c.r + 0.5 * c.g + 0.25 * c.b,
+        0.5 * c.r - 0.5 * c.b,
+        -0.25 * c.r + 0.5 * c.g - 0.25 * c.b,
+    );
+}
+
+fn ycocg_to_rgb(c: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(c.x + c.y - c.z, c.x + c.z, c.x - c.y - c.z);
+}
+
+// Catmull-Rom history filter from nine bilinear taps; plain bilinear blurs the image a
+// little more every frame.
+fn sample_history(uv: vec2<f32>) -> vec3<f32> {
+    let size = vec2<f32>(textureDimensions(history_map));
+    let pos = uv * size;
+    let center = floor(pos - 0.5) + 0.5;
+    let f = pos - center;
+    let w0 = f * (-0.5 + f * (1.0 - 0.5 * f));
+    let w1 = 1.0 + f * f * (-2.5 + 1.5 * f);
+    let w2 = f * (0.5 + f * (2.0 - 1.5 * f));
+    let w3 = f * f * (-0.5 + 0.5 * f);
+    let w12 = w1 + w2;
+    let tc0 = (center - 1.0) / size;
+    let tc12 = (center + w2 / w12) / size;
+    let tc3 = (center + 2.0) / size;
+    var c = vec3<f32>(0.0);
+    c += textureSampleLevel(history_map, linear_sampler, vec2<f32>(tc0.x, tc0.y), 0.0).rgb * w0.x * w0.y;
+    c += textureSampleLevel(history_map, linear_sampler, vec2<f32>(tc12.x, tc0.y), 0.0).rgb * w12.x * w0.y;
+    c += textureSampleLevel(history_map, linear_sampler, vec2<f32>(tc3.x, tc0.y), 0.0).rgb * w3.x * w0.y;
+    c += textureSampleLevel(history_map, linear_sampler, vec2<f32>(tc0.x, tc12.y), 0.0).rgb * w0.x * w12.y;
+    c += textureSampleLevel(history_map, linear_sampler, vec2<f32>(tc12.x, tc12.y), 0.0).rgb * w12.x * w12.y;
+    c += textureSampleLevel(history_map, linear_sampler, vec2<f32>(tc3.x, tc12.y), 0.0).rgb * w3.x * w12.y;
+    c += textureSampleLevel(history_map, linear_sampler, vec2<f32>(tc0.x, tc3.y), 0.0).rgb * w0.x * w3.y;
+    c += textureSampleLevel(history_map, linear_sampler, vec2<f32>(tc12.x, tc3.y), 0.0).rgb * w12.x * w3.y;
+    c += textureSampleLevel(history_map, linear_sampler, vec2<f32>(tc3.x, tc3.y), 0.0).rgb * w3.x * w3.y;
+    // The negative lobes can overshoot below zero next to bright pixels.
+    return max(c, vec3<f32>(0.0));
+}
+
+// Moves `history` towards the box centre until it lies inside the box.
+fn clip_to_box(history: vec3<f32>, lo: vec3<f32>, hi: vec3<f32>) -> vec3<f32> {
+    let center = 0.5 * (hi + lo);
+    let extent = 0.5 * (hi - lo) + 1e-5;
+    let offset = history - center;
+    let units = abs(offset / extent);
+    let furthest = max(units.x, max(units.y, units.z));
+    if furthest > 1.0 {
+        return center + offset / furthest;
+    }
+    return history;
+}
+
+@fragment
+fn fs_resolve(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(pos.xy);
+    let size = vec2<i32>(textureDimensions(color_map));
+    let current = textureLoad(color_map, pixel, 0);
+    if params.settings.y < 0.5 {
+        return current;
+    }
+
+    var m1 = vec3<f32>(0.0);
+    var m2 = vec3<f32>(0.0);
+    var closest = 2.0;
+    var closest_pixel = pixel;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let p = clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            let c = rgb_to_ycocg(compress(textureLoad(color_map, p, 0).rgb));
+            m1 += c;
+            m2 += c * c;
+            let stored = textureLoad(depth_map, p, 0);
+            // Distance from the near plane in depth units, whichever end that is.
+            let depth = 1.0 - abs(FAR_DEPTH - stored);
+            if depth < closest {
+                closest = depth;
+                closest_pixel = p;
+            }
+        }
+    }
+    let center = rgb_to_ycocg(compress(current.rgb));
+
+    let motion = textureLoad(velocity_map, closest_pixel, 0).xy;
+    let uv = (pos.xy - motion) / vec2<f32>(size);
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) {
+        return current;
+    }
+
+    let mean = m1 / 9.0;
+    let sigma = sqrt(max(m2 / 9.0 - mean * mean, vec3<f32>(0.0)));
+    let history = rgb_to_ycocg(compress(sample_history(uv)));
+    let clipped = clip_to_box(history, mean - VARIANCE_GAMMA * sigma, mean + VARIANCE_GAMMA * sigma);
+    let resolved = mix(center, clipped, params.settings.x);
+    return vec4<f32>(uncompress(ycocg_to_rgb(resolved)), current.a);
+}
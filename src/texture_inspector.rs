@@ -0,0 +1,248 @@
+use crate::material::Material;
+
+/// Names of the textures in [`Material::textures`], in order.
+const SLOTS: [&str; 3] = ["base color", "metallic-roughness", "normal"];
+/// Height of the inset as a fraction of the window.
+const INSET_FRACTION: f32 = 0.35;
+const MARGIN: f32 = 8.0;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum InspectorMode {
+    #[default]
+    Off,
+    /// A picture-in-picture quad in the bottom-right corner.
+    Inset,
+    /// The texture fitted to the window on black.
+    Fullscreen,
+}
+
+/// What the inspector shows of the texture: all of it over a checkerboard that shows
+/// through where alpha is low, or one channel in grey.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Channel {
+    #[default]
+    Rgba,
+    Red,
+    Green,
+    Blue,
+    Alpha,
+}
+
+impl Channel {
+    const ALL: [Self; 5] = [Self::Rgba, Self::Red, Self::Green, Self::Blue, Self::Alpha];
+}
+
+type InspectorKey = (usize, usize, Option<(u32, u32, wgpu::TextureFormat)>);
+
+/// Shows one texture of one material as it was uploaded, to check what the GPU actually
+/// samples for base color, metallic-roughness and normal maps.
+pub struct TextureInspector {
+    pub mode: InspectorMode,
+    material: usize,
+    slot: usize,
+    channel: Channel,
+    params_buffer: wgpu::Buffer,
+    layout: wgpu::BindGroupLayout,
+    /// Bind group for the material, slot and texture size it was created for; streaming
+    /// replaces the texture when its size changes.
+    bind_group: Option<(InspectorKey, wgpu::BindGroup)>,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl TextureInspector {
+    pub fn new(device: &wgpu::Device, output_format: wgpu::TextureFormat) -> Self {
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Texture Inspector Params Buffer"),
+            size: std::mem::size_of::<[f32; 8]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("texture_inspector_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Texture Inspector Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("texture_inspector.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Texture Inspector Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Texture Inspector Pipeline"),
+            layout: Some(&pipeline_layout),
+            cache: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_quad",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_inspect",
+                targets: &[Some(output_format.into())],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            mode: InspectorMode::Off,
+            material: 0,
+            slot: 0,
+            channel: Channel::Rgba,
+            params_buffer,
+            layout,
+            bind_group: None,
+            pipeline,
+        }
+    }
+
+    /// Off, inset, fullscreen and back.
+    pub fn cycle_mode(&mut self) {
+        self.mode = match self.mode {
+            InspectorMode::Off => InspectorMode::Inset,
+            InspectorMode::Inset => InspectorMode::Fullscreen,
+            InspectorMode::Fullscreen => InspectorMode::Off,
+        };
+    }
+
+    /// Moves the selection `delta` materials on, wrapping around `count`.
+    pub fn step_material(&mut self, delta: isize, count: usize) {
+        if count > 0 {
+            self.material = (self.material as isize + delta).rem_euclid(count as isize) as usize;
+        }
+    }
+
+    pub fn next_slot(&mut self) {
+        self.slot = (self.slot + 1) % SLOTS.len();
+    }
+
+    pub fn next_channel(&mut self) {
+        self.channel = Channel::ALL[(self.channel as usize + 1) % Channel::ALL.len()];
+    }
+
+    /// The selection, for the log.
+    pub fn describe(&self, materials: &[Material]) -> String {
+        let Some(material) = materials.get(self.material) else {
+            return "no materials".to_string();
+        };
+        let source = match material.textures[self.slot].source {
+            Some((width, height, format)) => format!("{}x{} {:?}", width, height, format),
+            None => "default".to_string(),
+        };
+        format!(
+            "{:?}: material {}/{}, {} texture ({}), {:?}",
+            self.mode,
+            self.material,
+            materials.len(),
+            SLOTS[self.slot],
+            source,
+            self.channel
+        )
+    }
+
+    /// Draws the selected texture over `target`, which is `size` pixels.
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        (target, size): (&wgpu::TextureView, (u32, u32)),
+        materials: &[Material],
+    ) {
+        if self.mode == InspectorMode::Off {
+            return;
+        }
+        let Some(material) = materials.get(self.material) else {
+            return;
+        };
+        let texture = &material.textures[self.slot];
+        let key = (self.material, self.slot, texture.source);
+        if self.bind_group.as_ref().map(|(k, _)| *k) != Some(key) {
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("texture_inspector_bind_group"),
+                layout: &self.layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: self.params_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&texture.view),
+                    },
+                ],
+            });
+            self.bind_group = Some((key, bind_group));
+        }
+
+        let (width, height) = (size.0 as f32, size.1 as f32);
+        let (texture_width, texture_height) = texture.source.map_or((1, 1), |(w, h, _)| (w, h));
+        let aspect = texture_width as f32 / texture_height as f32;
+        let (area_width, area_height) = match self.mode {
+            InspectorMode::Inset => (width - MARGIN * 2.0, height * INSET_FRACTION),
+            _ => (width - MARGIN * 2.0, height - MARGIN * 2.0),
+        };
+        let rect_height = area_height.min(area_width / aspect);
+        let rect_width = rect_height * aspect;
+        let rect = match self.mode {
+            InspectorMode::Inset => [width - MARGIN - rect_width, height - MARGIN - rect_height],
+            _ => [(width - rect_width) * 0.5, (height - rect_height) * 0.5],
+        };
+        let params = [rect[0], rect[1], rect_width, rect_height, width, height, self.channel as usize as f32, 0.0];
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&params));
+
+        let load = match self.mode {
+            InspectorMode::Fullscreen => wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+            _ => wgpu::LoadOp::Load,
+        };
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Texture Inspector Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        if let Some((_, bind_group)) = &self.bind_group {
+            pass.set_bind_group(0, bind_group, &[]);
+        }
+        pass.draw(0..6, 0..1);
+    }
+}
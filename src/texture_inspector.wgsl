@@ -0,0 +1,53 @@
+// Texture inspector: one material texture drawn into a screen rectangle, texel for texel,
+// either as stored or as a single channel in grey.
+
+struct InspectorParams {
+    // Top-left corner and size of the rectangle, in pixels.
+    rect: vec4<f32>,
+    // xy: target size in pixels, z: 0 for RGBA over a checkerboard, 1-4 for R, G, B or A.
+    screen_channel: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> params: InspectorParams;
+
+@group(0) @binding(1)
+var inspected: texture_2d<f32>;
+
+struct QuadOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_quad(@builtin(vertex_index) vid: u32) -> QuadOut {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let c = corners[vid];
+    let pixel = params.rect.xy + c * params.rect.zw;
+    var o: QuadOut;
+    o.pos = vec4<f32>(pixel / params.screen_channel.xy * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    o.uv = c;
+    return o;
+}
+
+@fragment
+fn fs_inspect(in: QuadOut) -> @location(0) vec4<f32> {
+    // Unfiltered, so what is shown is exactly what was uploaded.
+    let size = textureDimensions(inspected);
+    let texel = min(vec2<u32>(in.uv * vec2<f32>(size)), size - 1u);
+    let value = textureLoad(inspected, texel, 0);
+    let channel = u32(params.screen_channel.z);
+    if channel == 0u {
+        let cell = vec2<u32>(in.pos.xy / 8.0);
+        let checker = select(0.2, 0.4, (cell.x + cell.y) % 2u == 0u);
+        return vec4<f32>(mix(vec3<f32>(checker), value.rgb, value.a), 1.0);
+    }
+    return vec4<f32>(vec3<f32>(value[channel - 1u]), 1.0);
+}
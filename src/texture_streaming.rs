@@ -0,0 +1,256 @@
+use crate::garbage::GpuGarbage;
+use crate::material::{DefaultTextures, Material, MaterialTexture, TEXTURE_LABELS};
+use crate::model::{Material as ModelMaterial, Texture as ModelTexture};
+
+/// Largest mip uploaded at load; the finer ones stream in once the camera needs them.
+const START_SIZE: u32 = 64;
+/// Promotions per frame, so a burst of them spreads over frames instead of stalling one.
+const PROMOTIONS_PER_FRAME: usize = 4;
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Box-filtered mip chain of an RGBA8 texture, level 0 first; sRGB textures are averaged
+/// in linear space.
+fn build_mips(texture: &ModelTexture) -> Vec<Vec<u8>> {
+    let (mut width, mut height) = (texture.width, texture.height);
+    let mut mips = vec![texture.data.clone()];
+    if texture.data.len() != (width * height * 4) as usize {
+        return mips;
+    }
+    let srgb = texture.format.is_srgb();
+    let to_linear: Vec<f32> = (0..256).map(|v| srgb_to_linear(v as f32 / 255.0)).collect();
+    while width > 1 || height > 1 {
+        let (next_width, next_height) = ((width / 2).max(1), (height / 2).max(1));
+        let src = mips.last().unwrap();
+        let mut dst = Vec::with_capacity((next_width * next_height * 4) as usize);
+        for y in 0..next_height {
+            for x in 0..next_width {
+                for c in 0..4 {
+                    let color = srgb && c < 3;
+                    let mut sum = 0.0;
+                    for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        let (sx, sy) = ((x * 2 + dx).min(width - 1), (y * 2 + dy).min(height - 1));
+                        let v = src[((sy * width + sx) * 4 + c) as usize];
+                        sum += if color { to_linear[v as usize] } else { v as f32 / 255.0 };
+                    }
+                    let average = if color { linear_to_srgb(sum / 4.0) } else { sum / 4.0 };
+                    dst.push((average * 255.0 + 0.5) as u8);
+                }
+            }
+        }
+        mips.push(dst);
+        (width, height) = (next_width, next_height);
+    }
+    mips
+}
+
+/// A texture's whole chain in system memory.
+struct MipChain {
+    slot: usize,
+    format: wgpu::TextureFormat,
+    size: (u32, u32),
+    /// Level 0 first.
+    mips: Vec<Vec<u8>>,
+}
+
+impl MipChain {
+    fn level_size(&self, level: usize) -> (u32, u32) {
+        ((self.size.0 >> level).max(1), (self.size.1 >> level).max(1))
+    }
+
+    /// GPU memory with `level` as the finest mip.
+    fn bytes_from(&self, level: usize) -> u64 {
+        self.mips[level..].iter().map(|m| m.len() as u64).sum()
+    }
+
+    /// Uploads the chain from `level` down into a new texture.
+    fn upload(&self, device: &wgpu::Device, queue: &wgpu::Queue, level: usize) -> (wgpu::Texture, MaterialTexture) {
+        let (width, height) = self.level_size(level);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(TEXTURE_LABELS[self.slot]),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: (self.mips.len() - level) as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        for (mip, data) in self.mips[level..].iter().enumerate() {
+            let (width, height) = self.level_size(level + mip);
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: mip as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * width),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        let material_texture = MaterialTexture {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            source: Some((width, height, self.format)),
+        };
+        (texture, material_texture)
+    }
+}
+
+struct StreamedTexture {
+    material: usize,
+    chain: MipChain,
+    /// Finest level on the GPU.
+    resident: usize,
+    /// Coarsest level the texture ever drops to: the one uploaded at load.
+    floor: usize,
+    texture: wgpu::Texture,
+}
+
+/// Keeps material textures in system memory and only their coarse mips on the GPU at
+/// first. Each frame the finest useful mip of every texture follows how large its
+/// material's meshes are on screen, and mips are promoted and demoted so the resident
+/// set fits the budget, nearest materials first.
+pub struct TextureStreamer {
+    budget: u64,
+    textures: Vec<StreamedTexture>,
+}
+
+impl TextureStreamer {
+    pub fn new(budget: u64) -> Self {
+        Self {
+            budget,
+            textures: Vec::new(),
+        }
+    }
+
+    /// Like `Material::from_model_material`, with the textures streamed. `index` is where
+    /// the material goes in the scene's materials.
+    pub fn load_material(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        (index, material): (usize, &ModelMaterial),
+        textures: &[ModelTexture],
+        defaults: &DefaultTextures,
+    ) -> Material {
+        let images = [material.base_color_image, material.metallic_roughness_image, material.normal_image];
+        let mut texture_bytes = 0;
+        let views = std::array::from_fn(|slot| {
+            let Some(source) = images[slot].and_then(|i| textures.get(i)) else {
+                return defaults.for_slot(slot);
+            };
+            let chain = MipChain {
+                slot,
+                format: source.format,
+                size: (source.width, source.height),
+                mips: build_mips(source),
+            };
+            let floor = (0..chain.mips.len())
+                .find(|&level| (source.width.max(source.height) >> level) <= START_SIZE)
+                .unwrap_or(chain.mips.len() - 1);
+            let (texture, view) = chain.upload(device, queue, floor);
+            texture_bytes += chain.bytes_from(floor);
+            self.textures.push(StreamedTexture {
+                material: index,
+                chain,
+                resident: floor,
+                floor,
+                texture,
+            });
+            view
+        });
+        Material::new(device, layout, material, views, texture_bytes)
+    }
+
+    /// GPU memory of the resident mips.
+    pub fn resident_bytes(&self) -> u64 {
+        self.textures.iter().map(|t| t.chain.bytes_from(t.resident)).sum()
+    }
+
+    /// Moves every texture towards the mip its material needs. `demand` holds, per
+    /// material, the most pixels of screen height any of its meshes spans; a texture is
+    /// assumed to span its mesh once, so it wants about that many texels across. Returns
+    /// whether any material's textures changed.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        materials: &mut [Material],
+        demand: &[f32],
+        garbage: &mut GpuGarbage,
+    ) -> bool {
+        let demand_of = |t: &StreamedTexture| demand.get(t.material).copied().unwrap_or(0.0);
+        let mut order: Vec<usize> = (0..self.textures.len()).collect();
+        order.sort_by(|&a, &b| demand_of(&self.textures[b]).total_cmp(&demand_of(&self.textures[a])));
+
+        // The load-time mips always stay; the budget decides what goes on top, nearest first.
+        let mut used: u64 = self.textures.iter().map(|t| t.chain.bytes_from(t.floor)).sum();
+        let mut granted = vec![0; self.textures.len()];
+        for &i in &order {
+            let t = &self.textures[i];
+            let texels = t.chain.size.0.max(t.chain.size.1) as f32;
+            let wanted = (texels / demand_of(t).max(1.0)).log2().floor().max(0.0) as usize;
+            let extra = |level| t.chain.bytes_from(level) - t.chain.bytes_from(t.floor);
+            let mut level = wanted.min(t.floor);
+            while level < t.floor && used + extra(level) > self.budget {
+                level += 1;
+            }
+            used += extra(level);
+            granted[i] = level;
+        }
+
+        let over_budget = self.resident_bytes() > self.budget;
+        let mut promotions = 0;
+        let mut changed = false;
+        for i in order {
+            let t = &mut self.textures[i];
+            let level = granted[i];
+            let promote = level < t.resident && promotions < PROMOTIONS_PER_FRAME;
+            // One level of slack before dropping detail, so a mesh on the edge doesn't flip.
+            let demote = level > t.resident && (level > t.resident + 1 || over_budget);
+            if !promote && !demote {
+                continue;
+            }
+            promotions += promote as usize;
+            let (texture, view) = t.chain.upload(device, queue, level);
+            garbage.defer(std::mem::replace(&mut t.texture, texture));
+            let material = &mut materials[t.material];
+            material.texture_bytes -= t.chain.bytes_from(t.resident);
+            material.texture_bytes += t.chain.bytes_from(level);
+            material.set_texture(device, layout, t.chain.slot, view);
+            t.resident = level;
+            changed = true;
+        }
+        changed
+    }
+}
@@ -0,0 +1,25 @@
+@group(0) @binding(0)
+var hdr_texture: texture_2d<f32>;
+
+struct FullscreenOut {
+    @builtin(position) pos: vec4<f32>,
+};
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> FullscreenOut {
+    var p = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -3.0),
+        vec2<f32>( 3.0,  1.0),
+        vec2<f32>(-1.0,  1.0),
+    );
+    var o: FullscreenOut;
+    o.pos = vec4<f32>(p[vid], 0.0, 1.0);
+    return o;
+}
+
+@fragment
+fn fs_tonemap(in: FullscreenOut) -> @location(0) vec4<f32> {
+    let hdr = textureLoad(hdr_texture, vec2<i32>(in.pos.xy), 0).rgb;
+    let color = hdr / (hdr + vec3<f32>(1.0));
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,221 @@
+/// RCAS sharpening in stops; 0 is the strongest, each stop halves it.
+pub const DEFAULT_SHARPNESS: f32 = 0.2;
+
+/// The internal render size for a `width`×`height` window at `scale`.
+pub fn scaled_size(width: u32, height: u32, scale: f32) -> (u32, u32) {
+    let scaled = |v: u32| ((v as f32 * scale).round() as u32).clamp(1, v.max(1));
+    (scaled(width), scaled(height))
+}
+
+fn create_target(
+    device: &wgpu::Device,
+    label: &str,
+    format: wgpu::TextureFormat,
+    (width, height): (u32, u32),
+) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    (texture, view)
+}
+
+struct UpscaleTargets {
+    input: wgpu::Texture,
+    input_view: wgpu::TextureView,
+    upscaled: wgpu::Texture,
+    upscaled_view: wgpu::TextureView,
+    display_size: (u32, u32),
+}
+
+impl UpscaleTargets {
+    fn new(device: &wgpu::Device, format: wgpu::TextureFormat, render: (u32, u32), display: (u32, u32)) -> Self {
+        let (input, input_view) = create_target(device, "Upscale Input", format, render);
+        let (upscaled, upscaled_view) = create_target(device, "Upscaled", format, display);
+        Self {
+            input,
+            input_view,
+            upscaled,
+            upscaled_view,
+            display_size: display,
+        }
+    }
+}
+
+/// FSR1-style spatial upscaler for render scales below 100%: the tonemapped
+/// render-resolution image is resampled to the window size with EASU and sharpened with
+/// RCAS, instead of being stretched bilinearly.
+pub struct Upscaler {
+    /// RCAS sharpening in stops, 0 being the strongest.
+    pub sharpness: f32,
+    format: wgpu::TextureFormat,
+    targets: UpscaleTargets,
+    params_buffer: wgpu::Buffer,
+    layout: wgpu::BindGroupLayout,
+    /// EASU reads the input, RCAS the upscaled image.
+    bind_groups: [wgpu::BindGroup; 2],
+    easu_pipeline: wgpu::RenderPipeline,
+    rcas_pipeline: wgpu::RenderPipeline,
+}
+
+impl Upscaler {
+    pub fn new(device: &wgpu::Device, output_format: wgpu::TextureFormat, render: (u32, u32), display: (u32, u32)) -> Self {
+        let targets = UpscaleTargets::new(device, output_format, render, display);
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Upscale Params Buffer"),
+            size: std::mem::size_of::<[f32; 4]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("upscale_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_groups = Self::create_bind_groups(device, &layout, &targets, &params_buffer);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Upscale Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("upscale.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Upscale Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |label, entry_point| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                cache: None,
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_fullscreen",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point,
+                    targets: &[Some(output_format.into())],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        let easu_pipeline = pipeline("EASU Pipeline", "fs_easu");
+        let rcas_pipeline = pipeline("RCAS Pipeline", "fs_rcas");
+
+        Self {
+            sharpness: DEFAULT_SHARPNESS,
+            format: output_format,
+            targets,
+            params_buffer,
+            layout,
+            bind_groups,
+            easu_pipeline,
+            rcas_pipeline,
+        }
+    }
+
+    fn create_bind_groups(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        targets: &UpscaleTargets,
+        params_buffer: &wgpu::Buffer,
+    ) -> [wgpu::BindGroup; 2] {
+        [&targets.input_view, &targets.upscaled_view].map(|view| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("upscale_bind_group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: params_buffer.as_entire_binding(),
+                    },
+                ],
+            })
+        })
+    }
+
+    /// Where the tonemapped render-resolution image should go.
+    pub fn input_view(&self) -> &wgpu::TextureView {
+        &self.targets.input_view
+    }
+
+    /// Reallocates the targets for new render and window sizes, returning the old
+    /// textures so they can be destroyed once the GPU is done with them.
+    pub fn resize(&mut self, device: &wgpu::Device, render: (u32, u32), display: (u32, u32)) -> [wgpu::Texture; 2] {
+        let old = std::mem::replace(&mut self.targets, UpscaleTargets::new(device, self.format, render, display));
+        self.bind_groups = Self::create_bind_groups(device, &self.layout, &self.targets, &self.params_buffer);
+        [old.input, old.upscaled]
+    }
+
+    /// Upscales the input to `target`, which must be window-sized.
+    pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, target: &wgpu::TextureView) {
+        let (width, height) = self.targets.display_size;
+        let params = [(-self.sharpness).exp2(), 0.0, width as f32, height as f32];
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&params));
+        let passes = [
+            ("EASU Pass", &self.easu_pipeline, &self.targets.upscaled_view),
+            ("RCAS Pass", &self.rcas_pipeline, target),
+        ];
+        for ((label, pipeline, view), bind_group) in passes.into_iter().zip(&self.bind_groups) {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(label),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+// Spatial upscaling after AMD FidelityFX Super Resolution 1.0: EASU (edge-adaptive
+// spatial upsampling) resamples the render-resolution image with a 12-tap Lanczos-like
+// kernel stretched along the local edge, then RCAS (robust contrast-adaptive sharpening)
+// restores the detail the resampling softened. Both run on the tonemapped image, in an
+// approximately perceptual space (square root of the linear values).
+
+struct UpscaleParams {
+    // x: RCAS sharpness as a linear scale (2^-stops), zw: output size in pixels.
+    values: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var input_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var<uniform> params: UpscaleParams;
+
+// RCAS never sharpens beyond this lobe weight, which keeps it from ringing.
+const RCAS_LIMIT: f32 = 0.1875;
+
+struct FullscreenOut {
+    @builtin(position) pos: vec4<f32>,
+};
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> FullscreenOut {
+    var p = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -3.0),
+        vec2<f32>( 3.0,  1.0),
+        vec2<f32>(-1.0,  1.0),
+    );
+    var o: FullscreenOut;
+    o.pos = vec4<f32>(p[vid], 0.0, 1.0);
+    return o;
+}
+
+fn load(p: vec2<i32>) -> vec3<f32> {
+    let size = vec2<i32>(textureDimensions(input_texture));
+    return sqrt(max(textureLoad(input_texture, clamp(p, vec2<i32>(0), size - 1), 0).rgb, vec3<f32>(0.0)));
+}
+
+// Cheap luma for edge detection, as in EASU.
+fn easu_luma(c: vec3<f32>) -> f32 {
+    return c.b * 0.5 + (c.r * 0.5 + c.g);
+}
+
+struct EdgeAccum {
+    dir: vec2<f32>,
+    len: f32,
+};
+
+// Gradient of one of the four texels around the sample point from its '+' neighbours:
+//    a
+//  b c d
+//    e
+fn easu_set(acc: ptr<function, EdgeAccum>, w: f32, a: f32, b: f32, c: f32, d: f32, e: f32) {
+    let dir_x = d - b;
+    let len_x = clamp(abs(dir_x) / max(max(abs(d - c), abs(c - b)), 1e-5), 0.0, 1.0);
+    let dir_y = e - a;
+    let len_y = clamp(abs(dir_y) / max(max(abs(e - c), abs(c - a)), 1e-5), 0.0, 1.0);
+    (*acc).dir += vec2<f32>(dir_x, dir_y) * w;
+    (*acc).len += (len_x * len_x + len_y * len_y) * w;
+}
+
+struct TapAccum {
+    color: vec3<f32>,
+    weight: f32,
+};
+
+// One kernel tap at `offset` texels from the sample point, rotated into the edge frame
+// and scaled so the kernel stretches along the edge.
+fn easu_tap(
+    acc: ptr<function, TapAccum>,
+    offset: vec2<f32>,
+    dir: vec2<f32>,
+    len2: vec2<f32>,
+    lobe: f32,
+    clip: f32,
+    color: vec3<f32>,
+) {
+    var v = vec2<f32>(offset.x * dir.x + offset.y * dir.y, offset.x * -dir.y + offset.y * dir.x);
+    v *= len2;
+    let d2 = min(dot(v, v), clip);
+    // Polynomial stand-in for windowed Lanczos 2: (25/16 (2/5 x^2 - 1)^2 - 9/16) (lobe x^2 - 1)^2.
+    var wb = 0.4 * d2 - 1.0;
+    var wa = lobe * d2 - 1.0;
+    wb *= wb;
+    wa *= wa;
+    wb = 1.5625 * wb - 0.5625;
+    let w = wb * wa;
+    (*acc).color += color * w;
+    (*acc).weight += w;
+}
+
+@fragment
+fn fs_easu(in: FullscreenOut) -> @location(0) vec4<f32> {
+    let input_size = vec2<f32>(textureDimensions(input_texture));
+    // Output pixels are drawn at the display size; map them back into the input.
+    let scale = input_size / params.values.zw;
+    let pp_full = in.pos.xy * scale - 0.5;
+    let fp = floor(pp_full);
+    let pp = pp_full - fp;
+    let o = vec2<i32>(fp);
+
+    // 12-tap footprint:
+    //    b c
+    //  e f g h
+    //  i j k l
+    //    n o
+    let b = load(o + vec2<i32>(0, -1));
+    let c = load(o + vec2<i32>(1, -1));
+    let e = load(o + vec2<i32>(-1, 0));
+    let f = load(o);
+    let g = load(o + vec2<i32>(1, 0));
+    let h = load(o + vec2<i32>(2, 0));
+    let i = load(o + vec2<i32>(-1, 1));
+    let j = load(o + vec2<i32>(0, 1));
+    let k = load(o + vec2<i32>(1, 1));
+    let l = load(o + vec2<i32>(2, 1));
+    let n = load(o + vec2<i32>(0, 2));
+    let oo = load(o + vec2<i32>(1, 2));
+
+    let lb = easu_luma(b);
+    let lc = easu_luma(c);
+    let le = easu_luma(e);
+    let lf = easu_luma(f);
+    let lg = easu_luma(g);
+    let lh = easu_luma(h);
+    let li = easu_luma(i);
+    let lj = easu_luma(j);
+    let lk = easu_luma(k);
+    let ll = easu_luma(l);
+    let ln = easu_luma(n);
+    let lo = easu_luma(oo);
+
+    // Edge direction and strength, bilinearly weighted over f, g, j and k.
+    var edge: EdgeAccum;
+    easu_set(&edge, (1.0 - pp.x) * (1.0 - pp.y), lb, le, lf, lg, lj);
+    easu_set(&edge, pp.x * (1.0 - pp.y), lc, lf, lg, lh, lk);
+    easu_set(&edge, (1.0 - pp.x) * pp.y, lf, li, lj, lk, ln);
+    easu_set(&edge, pp.x * pp.y, lg, lj, lk, ll, lo);
+
+    var dir = edge.dir;
+    let dir_len2 = dot(dir, dir);
+    if dir_len2 < 1.0 / 32768.0 {
+        dir = vec2<f32>(1.0, 0.0);
+    } else {
+        dir *= inverseSqrt(dir_len2);
+    }
+    var len = edge.len * 0.5;
+    len *= len;
+    // Diagonal edges stretch further so the kernel keeps covering the footprint.
+    let stretch = dot(dir, dir) / max(abs(dir.x), abs(dir.y));
+    let len2 = vec2<f32>(1.0 + (stretch - 1.0) * len, 1.0 - 0.5 * len);
+    // Sharper lobe along strong edges, softer in flat areas.
+    let lobe = 0.5 + (0.21 - 0.5) * len;
+    let clip = 1.0 / lobe;
+
+    var acc: TapAccum;
+    easu_tap(&acc, vec2<f32>(0.0, -1.0) - pp, dir, len2, lobe, clip, b);
+    easu_tap(&acc, vec2<f32>(1.0, -1.0) - pp, dir, len2, lobe, clip, c);
+    easu_tap(&acc, vec2<f32>(-1.0, 1.0) - pp, dir, len2, lobe, clip, i);
+    easu_tap(&acc, vec2<f32>(0.0, 1.0) - pp, dir, len2, lobe, clip, j);
+    easu_tap(&acc, vec2<f32>(0.0, 0.0) - pp, dir, len2, lobe, clip, f);
+    easu_tap(&acc, vec2<f32>(-1.0, 0.0) - pp, dir, len2, lobe, clip, e);
+    easu_tap(&acc, vec2<f32>(1.0, 1.0) - pp, dir, len2, lobe, clip, k);
+    easu_tap(&acc, vec2<f32>(2.0, 1.0) - pp, dir, len2, lobe, clip, l);
+    easu_tap(&acc, vec2<f32>(2.0, 0.0) - pp, dir, len2, lobe, clip, h);
+    easu_tap(&acc, vec2<f32>(1.0, 0.0) - pp, dir, len2, lobe, clip, g);
+    easu_tap(&acc, vec2<f32>(1.0, 2.0) - pp, dir, len2, lobe, clip, oo);
+    easu_tap(&acc, vec2<f32>(0.0, 2.0) - pp, dir, len2, lobe, clip, n);
+
+    // The negative lobes can overshoot; clamp to the four nearest texels.
+    let lo4 = min(min(f, g), min(j, k));
+    let hi4 = max(max(f, g), max(j, k));
+    let color = clamp(acc.color / max(acc.weight, 1e-5), lo4, hi4);
+    return vec4<f32>(color * color, 1.0);
+}
+
+// RCAS on the upscaled image: a '+' shaped sharpening filter whose negative lobe is the
+// largest that cannot push the centre outside the neighbourhood's range.
+@fragment
+fn fs_rcas(in: FullscreenOut) -> @location(0) vec4<f32> {
+    let p = vec2<i32>(in.pos.xy);
+    //    b
+    //  d e f
+    //    h
+    let b = load(p + vec2<i32>(0, -1));
+    let d = load(p + vec2<i32>(-1, 0));
+    let e = load(p);
+    let f = load(p + vec2<i32>(1, 0));
+    let h = load(p + vec2<i32>(0, 1));
+
+    let mn4 = min(min(b, d), min(f, h));
+    let mx4 = max(max(b, d), max(f, h));
+    let hit_min = mn4 / max(4.0 * mx4, vec3<f32>(1e-5));
+    let hit_max = (1.0 - mx4) / min(4.0 * mn4 - 4.0, vec3<f32>(-1e-5));
+    let lobe_rgb = max(-hit_min, hit_max);
+    let lobe = max(-RCAS_LIMIT, min(max(lobe_rgb.r, max(lobe_rgb.g, lobe_rgb.b)), 0.0)) * params.values.x;
+    let color = (lobe * (b + d + f + h) + e) / (4.0 * lobe + 1.0);
+    return vec4<f32>(color * color, 1.0);
+}
@@ -0,0 +1,459 @@
+use std::collections::HashMap;
+
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
+use wgpu::util::DeviceExt;
+
+use crate::aabb::Aabb;
+use crate::bvh::BvhTriangle;
+use crate::lights::DirectionalLight;
+use crate::post::HDR_FORMAT;
+
+/// Voxels along the longest side of the scene.
+pub const DEFAULT_RESOLUTION: u32 = 128;
+const RADIANCE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct VoxelParams {
+    grid_min: [f32; 4],
+    grid_counts: [u32; 4],
+    sun_direction: [f32; 4],
+    sun_radiance: [f32; 4],
+}
+
+/// Cubic voxels covering the scene bounds, `resolution` of them along the longest side.
+#[derive(Copy, Clone, Debug)]
+pub struct VoxelGrid {
+    pub min: Point3<f32>,
+    pub voxel_size: f32,
+    pub counts: [u32; 3],
+}
+
+impl VoxelGrid {
+    pub fn fit(bounds: &Aabb, resolution: u32) -> Self {
+        let extent = bounds.extent();
+        let voxel_size = (extent.x.max(extent.y).max(extent.z) / resolution as f32).max(1e-3);
+        // One voxel of margin so surfaces on the bounds are not clipped.
+        let count = |e: f32| ((e / voxel_size).ceil() as u32 + 2).min(resolution + 2);
+        Self {
+            min: bounds.min - Vector3::new(1.0, 1.0, 1.0) * voxel_size,
+            voxel_size,
+            counts: [count(extent.x), count(extent.y), count(extent.z)],
+        }
+    }
+
+    fn index(&self, p: Vector3<f32>) -> Option<usize> {
+        let local = (Point3::from_vec(p) - self.min) / self.voxel_size;
+        let [nx, ny, nz] = self.counts;
+        let (x, y, z) = (local.x.floor(), local.y.floor(), local.z.floor());
+        if x < 0.0 || y < 0.0 || z < 0.0 || x >= nx as f32 || y >= ny as f32 || z >= nz as f32 {
+            return None;
+        }
+        Some(((z as u32 * ny + y as u32) * nx + x as u32) as usize)
+    }
+
+    fn extent(&self) -> wgpu::Extent3d {
+        wgpu::Extent3d {
+            width: self.counts[0],
+            height: self.counts[1],
+            depth_or_array_layers: self.counts[2],
+        }
+    }
+}
+
+/// Rasterizes the triangles into albedo (alpha marks occupied voxels) and average
+/// normal volumes by sampling each triangle at half the voxel size.
+fn voxelize(grid: &VoxelGrid, triangles: &[BvhTriangle]) -> (Vec<[u8; 4]>, Vec<[i8; 4]>) {
+    let mut voxels: HashMap<usize, ([f32; 3], Vector3<f32>, u32)> = HashMap::new();
+    let step = grid.voxel_size * 0.5;
+    for tri in triangles {
+        let [a, b, c] = [tri.a, tri.b, tri.c].map(|p| Vector3::new(p[0], p[1], p[2]));
+        let normal = (b - a).cross(c - a);
+        let normal = if normal.magnitude2() > 0.0 { normal.normalize() } else { normal };
+        let longest = (b - a).magnitude().max((c - a).magnitude()).max((c - b).magnitude());
+        let n = ((longest / step).ceil() as u32).clamp(1, 512);
+        for i in 0..=n {
+            for j in 0..=n - i {
+                let p = a + (b - a) * (i as f32 / n as f32) + (c - a) * (j as f32 / n as f32);
+                let Some(index) = grid.index(p) else {
+                    continue;
+                };
+                let entry = voxels.entry(index).or_insert(([0.0; 3], Vector3::new(0.0, 0.0, 0.0), 0));
+                for (sum, albedo) in entry.0.iter_mut().zip(tri.albedo) {
+                    *sum += albedo;
+                }
+                entry.1 += normal;
+                entry.2 += 1;
+            }
+        }
+    }
+
+    let count = grid.counts.iter().product::<u32>() as usize;
+    let mut albedo = vec![[0u8; 4]; count];
+    let mut normals = vec![[0i8; 4]; count];
+    let unorm = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let snorm = |v: f32| (v.clamp(-1.0, 1.0) * 127.0).round() as i8;
+    for (index, (sum, normal, samples)) in voxels {
+        let n = samples as f32;
+        albedo[index] = [unorm(sum[0] / n), unorm(sum[1] / n), unorm(sum[2] / n), 255];
+        let normal = normal / n;
+        normals[index] = [snorm(normal.x), snorm(normal.y), snorm(normal.z), 0];
+    }
+    (albedo, normals)
+}
+
+fn volume_texture(
+    device: &wgpu::Device,
+    label: &str,
+    grid: &VoxelGrid,
+    format: wgpu::TextureFormat,
+    mip_level_count: u32,
+    usage: wgpu::TextureUsages,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: grid.extent(),
+        mip_level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D3,
+        format,
+        usage,
+        view_formats: &[],
+    })
+}
+
+fn mip_view(texture: &wgpu::Texture, level: u32) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        base_mip_level: level,
+        mip_level_count: Some(1),
+        ..Default::default()
+    })
+}
+
+/// Voxel cone tracing GI: static geometry is voxelized once on load, the sun is injected
+/// into a mipmapped radiance volume every frame, and the main shader cone-traces that
+/// volume for diffuse and rough specular indirect light.
+pub struct VoxelGi {
+    pub enabled: bool,
+    /// Draw the voxels instead of the scene.
+    pub debug_view: bool,
+    grid: VoxelGrid,
+    _albedo: wgpu::Texture,
+    _normals: wgpu::Texture,
+    _radiance: wgpu::Texture,
+    radiance_view: wgpu::TextureView,
+    params_buffer: wgpu::Buffer,
+    inject_pipeline: wgpu::ComputePipeline,
+    inject_bind_group: wgpu::BindGroup,
+    downsample_pipeline: wgpu::ComputePipeline,
+    /// One per mip level after the first, with that level's size.
+    downsample_bind_groups: Vec<(wgpu::BindGroup, [u32; 3])>,
+    debug_pipeline: wgpu::RenderPipeline,
+    debug_bind_group: wgpu::BindGroup,
+}
+
+impl VoxelGi {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        camera_buffer: &wgpu::Buffer,
+        grid: VoxelGrid,
+        triangles: &[BvhTriangle],
+    ) -> Self {
+        let (albedo_data, normal_data) = voxelize(&grid, triangles);
+        let upload = |label, format, data: &[u8]| {
+            device.create_texture_with_data(
+                queue,
+                &wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: grid.extent(),
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D3,
+                    format,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                },
+                wgpu::util::TextureDataOrder::LayerMajor,
+                data,
+            )
+        };
+        let albedo = upload(
+            "Voxel Albedo",
+            wgpu::TextureFormat::Rgba8Unorm,
+            bytemuck::cast_slice(&albedo_data),
+        );
+        let normals = upload(
+            "Voxel Normals",
+            wgpu::TextureFormat::Rgba8Snorm,
+            bytemuck::cast_slice(&normal_data),
+        );
+        let albedo_view = albedo.create_view(&wgpu::TextureViewDescriptor::default());
+        let normal_view = normals.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mip_levels = 32 - grid.counts.iter().max().copied().unwrap_or(1).leading_zeros();
+        let radiance = volume_texture(
+            device,
+            "Voxel Radiance",
+            &grid,
+            RADIANCE_FORMAT,
+            mip_levels,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+        );
+        let radiance_view = radiance.create_view(&wgpu::TextureViewDescriptor::default());
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Voxel Params Buffer"),
+            size: std::mem::size_of::<VoxelParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let stages = wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT;
+        let uniform = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: stages,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let texture = |binding, filterable| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: stages,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D3,
+                sample_type: wgpu::TextureSampleType::Float { filterable },
+            },
+            count: None,
+        };
+        let storage = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: RADIANCE_FORMAT,
+                view_dimension: wgpu::TextureViewDimension::D3,
+            },
+            count: None,
+        };
+        let layout = |label, entries: &[wgpu::BindGroupLayoutEntry]| {
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some(label),
+                entries,
+            })
+        };
+        let inject_layout = layout(
+            "voxel_inject_bind_group_layout",
+            &[uniform(0), texture(1, true), texture(2, true), storage(3)],
+        );
+        let downsample_layout = layout("voxel_downsample_bind_group_layout", &[texture(4, false), storage(5)]);
+        let debug_layout = layout(
+            "voxel_debug_bind_group_layout",
+            &[uniform(0), texture(1, true), uniform(6), texture(7, true)],
+        );
+
+        let inject_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("voxel_inject_bind_group"),
+            layout: &inject_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&albedo_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&normal_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&mip_view(&radiance, 0)),
+                },
+            ],
+        });
+        let downsample_bind_groups = (1..mip_levels)
+            .map(|level| {
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("voxel_downsample_bind_group"),
+                    layout: &downsample_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 4,
+                            resource: wgpu::BindingResource::TextureView(&mip_view(&radiance, level - 1)),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 5,
+                            resource: wgpu::BindingResource::TextureView(&mip_view(&radiance, level)),
+                        },
+                    ],
+                });
+                (bind_group, grid.counts.map(|n| (n >> level).max(1)))
+            })
+            .collect();
+        let debug_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("voxel_debug_bind_group"),
+            layout: &debug_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&albedo_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: wgpu::BindingResource::TextureView(&radiance_view),
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Voxel GI Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("vct.wgsl").into()),
+        });
+        let pipeline_layout = |layout: &wgpu::BindGroupLayout| {
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Voxel GI Pipeline Layout"),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            })
+        };
+        let compute = |label: &str, layout: &wgpu::BindGroupLayout, entry_point: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout(layout)),
+                module: &shader,
+                entry_point,
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        let inject_pipeline = compute("Voxel Inject Pipeline", &inject_layout, "cs_inject");
+        let downsample_pipeline = compute("Voxel Downsample Pipeline", &downsample_layout, "cs_downsample");
+        let debug_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Voxel Debug Pipeline"),
+            layout: Some(&pipeline_layout(&debug_layout)),
+            cache: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_fullscreen",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_debug",
+                targets: &[Some(HDR_FORMAT.into())],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            enabled: true,
+            debug_view: false,
+            grid,
+            _albedo: albedo,
+            _normals: normals,
+            _radiance: radiance,
+            radiance_view,
+            params_buffer,
+            inject_pipeline,
+            inject_bind_group,
+            downsample_pipeline,
+            downsample_bind_groups,
+            debug_pipeline,
+            debug_bind_group,
+        }
+    }
+
+    pub fn radiance_view(&self) -> &wgpu::TextureView {
+        &self.radiance_view
+    }
+
+    /// Grid minimum corner (w: 1 when enabled) and size (w: voxel size) for the camera uniform.
+    pub fn camera_params(&self) -> [[f32; 4]; 2] {
+        let g = &self.grid;
+        let size = g.counts.map(|n| n as f32 * g.voxel_size);
+        [
+            [g.min.x, g.min.y, g.min.z, if self.enabled { 1.0 } else { 0.0 }],
+            [size[0], size[1], size[2], g.voxel_size],
+        ]
+    }
+
+    /// Relights the voxels with the sun and rebuilds the radiance mips.
+    pub fn update(&self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue, sun: &DirectionalLight) {
+        let g = &self.grid;
+        let radiance = sun.color.map(|c| c * sun.intensity);
+        let params = VoxelParams {
+            grid_min: [g.min.x, g.min.y, g.min.z, g.voxel_size],
+            grid_counts: [g.counts[0], g.counts[1], g.counts[2], 0],
+            sun_direction: [sun.direction.x, sun.direction.y, sun.direction.z, 0.0],
+            sun_radiance: [radiance[0], radiance[1], radiance[2], 0.0],
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Voxel Inject"),
+            timestamp_writes: None,
+        });
+        let dispatch = |pass: &mut wgpu::ComputePass, size: [u32; 3]| {
+            pass.dispatch_workgroups(size[0].div_ceil(4), size[1].div_ceil(4), size[2].div_ceil(4));
+        };
+        pass.set_pipeline(&self.inject_pipeline);
+        pass.set_bind_group(0, &self.inject_bind_group, &[]);
+        dispatch(&mut pass, g.counts);
+        pass.set_pipeline(&self.downsample_pipeline);
+        for (bind_group, size) in &self.downsample_bind_groups {
+            pass.set_bind_group(0, bind_group, &[]);
+            dispatch(&mut pass, *size);
+        }
+    }
+
+    /// Draws the first voxel along each view ray over `target`.
+    pub fn draw_debug(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Voxel Debug"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.debug_pipeline);
+        pass.set_bind_group(0, &self.debug_bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+/// Stand-in for the radiance volume in bind groups while voxel GI is off.
+pub fn placeholder_view(device: &wgpu::Device) -> wgpu::TextureView {
+    let grid = VoxelGrid {
+        min: Point3::new(0.0, 0.0, 0.0),
+        voxel_size: 1.0,
+        counts: [1, 1, 1],
+    };
+    volume_texture(device, "Voxel Placeholder", &grid, RADIANCE_FORMAT, 1, wgpu::TextureUsages::TEXTURE_BINDING)
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
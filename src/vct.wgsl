@@ -0,0 +1,156 @@
+// Voxel cone tracing: the static scene voxelized on load is lit by the sun each frame
+// into a mipmapped radiance volume that shader.wgsl cone-traces for indirect light.
+
+struct VoxelParams {
+    // xyz: grid minimum corner, w: voxel size.
+    grid_min: vec4<f32>,
+    grid_counts: vec4<u32>,
+    // Direction the sun light travels in.
+    sun_direction: vec4<f32>,
+    sun_radiance: vec4<f32>,
+};
+
+// Prefix of CameraUniform in shader.wgsl.
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    view_inv: mat4x4<f32>,
+    proj_inv: mat4x4<f32>,
+    position: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> params: VoxelParams;
+
+// rgb: linear albedo, a: 1 where geometry was voxelized.
+@group(0) @binding(1)
+var albedo_volume: texture_3d<f32>;
+
+// Average surface normal; near zero where opposite faces share a voxel.
+@group(0) @binding(2)
+var normal_volume: texture_3d<f32>;
+
+@group(0) @binding(3)
+var radiance_out: texture_storage_3d<rgba16float, write>;
+
+@group(0) @binding(4)
+var mip_source: texture_3d<f32>;
+
+@group(0) @binding(5)
+var mip_out: texture_storage_3d<rgba16float, write>;
+
+@group(0) @binding(6)
+var<uniform> camera: CameraUniform;
+
+@group(0) @binding(7)
+var radiance_volume: texture_3d<f32>;
+
+const PI: f32 = 3.14159265359;
+const SHADOW_STEPS: i32 = 128;
+const DEBUG_STEPS: i32 = 512;
+
+fn occupied(p: vec3<i32>) -> bool {
+    let counts = vec3<i32>(params.grid_counts.xyz);
+    if any(p < vec3<i32>(0)) || any(p >= counts) {
+        return false;
+    }
+    return textureLoad(albedo_volume, p, 0).a > 0.5;
+}
+
+// Marches the occupancy towards the sun one voxel at a time.
+fn sun_visibility(voxel: vec3<i32>, L: vec3<f32>) -> f32 {
+    let start = vec3<f32>(voxel) + 0.5 + L * 1.5;
+    for (var i = 0; i < SHADOW_STEPS; i++) {
+        let p = vec3<i32>(floor(start + L * f32(i)));
+        if any(p < vec3<i32>(0)) || any(p >= vec3<i32>(params.grid_counts.xyz)) {
+            return 1.0;
+        }
+        if occupied(p) {
+            return 0.0;
+        }
+    }
+    return 1.0;
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn cs_inject(@builtin(global_invocation_id) id: vec3<u32>) {
+    if any(id >= params.grid_counts.xyz) {
+        return;
+    }
+    let voxel = vec3<i32>(id);
+    let albedo = textureLoad(albedo_volume, voxel, 0);
+    if albedo.a < 0.5 {
+        textureStore(radiance_out, voxel, vec4<f32>(0.0));
+        return;
+    }
+    let L = -normalize(params.sun_direction.xyz);
+    let n = textureLoad(normal_volume, voxel, 0).xyz;
+    // Voxels holding both sides of a thin wall have no clear facing, so take half.
+    let NdotL = select(0.5, max(dot(normalize(n), L), 0.0), length(n) > 0.3);
+    var radiance = vec3<f32>(0.0);
+    if NdotL > 0.0 {
+        radiance = albedo.rgb / PI * params.sun_radiance.rgb * NdotL * sun_visibility(voxel, L);
+    }
+    textureStore(radiance_out, voxel, vec4<f32>(radiance, 1.0));
+}
+
+// Averages 2x2x2 texels of the previous level; radiance is premultiplied by opacity,
+// so a plain average keeps partially covered voxels correct.
+@compute @workgroup_size(4, 4, 4)
+fn cs_downsample(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(mip_out);
+    if any(id >= size) {
+        return;
+    }
+    let source_max = vec3<i32>(textureDimensions(mip_source)) - 1;
+    var sum = vec4<f32>(0.0);
+    for (var i = 0; i < 8; i++) {
+        let offset = vec3<i32>(i & 1, (i >> 1u) & 1, (i >> 2u) & 1);
+        sum += textureLoad(mip_source, min(vec3<i32>(id) * 2 + offset, source_max), 0);
+    }
+    textureStore(mip_out, id, sum / 8.0);
+}
+
+struct DebugOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> DebugOut {
+    let uv = vec2<f32>(f32((vid << 1u) & 2u), f32(vid & 2u));
+    var out: DebugOut;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+// Shows the first voxel along each view ray: its injected radiance over a dim copy of
+// its albedo, so shadowed voxels stay visible.
+@fragment
+fn fs_debug(in: DebugOut) -> @location(0) vec4<f32> {
+    let ndc = vec2<f32>(in.uv.x * 2.0 - 1.0, 1.0 - in.uv.y * 2.0);
+    let view = camera.proj_inv * vec4<f32>(ndc, 1.0, 1.0);
+    let dir = normalize((camera.view_inv * vec4<f32>(normalize(view.xyz / view.w), 0.0)).xyz);
+
+    // March in voxel units from where the ray enters the grid.
+    let counts = vec3<f32>(params.grid_counts.xyz);
+    let origin = (camera.position.xyz - params.grid_min.xyz) / params.grid_min.w;
+    let inv = 1.0 / dir;
+    let t0 = (vec3<f32>(0.0) - origin) * inv;
+    let t1 = (counts - origin) * inv;
+    let near = max(max(min(t0.x, t1.x), min(t0.y, t1.y)), max(min(t0.z, t1.z), 0.0));
+    let far = min(min(max(t0.x, t1.x), max(t0.y, t1.y)), max(t0.z, t1.z));
+    for (var i = 0; i < DEBUG_STEPS; i++) {
+        let t = near + f32(i) * 0.5;
+        if t > far {
+            break;
+        }
+        let p = vec3<i32>(floor(origin + dir * t));
+        if occupied(p) {
+            let albedo = textureLoad(albedo_volume, p, 0).rgb;
+            let radiance = textureLoad(radiance_volume, p, 0).rgb;
+            return vec4<f32>(radiance + albedo * 0.05, 1.0);
+        }
+    }
+    return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+}
@@ -0,0 +1,217 @@
+use cgmath::{Matrix4, Vector3};
+
+pub const VELOCITY_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct VelocityParams {
+    prev_view_proj: [[f32; 4]; 4],
+    jitter: [f32; 4],
+}
+
+fn create_target(device: &wgpu::Device, width: u32, height: u32) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Velocity"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: VELOCITY_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    (texture, view)
+}
+
+/// Screen-space motion of every pixel since the previous recorded frame, in pixels, from
+/// reprojecting the depth buffer with the camera. Used by TAA and motion blur.
+pub struct VelocityBuffer {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    params_buffer: wgpu::Buffer,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    /// Last frame's view-projection without jitter, or None when the camera jumped since.
+    prev_view_proj: Option<Matrix4<f32>>,
+}
+
+impl VelocityBuffer {
+    pub fn new(
+        device: &wgpu::Device,
+        camera_buffer: &wgpu::Buffer,
+        depth_view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let (texture, view) = create_target(device, width, height);
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Velocity Params Buffer"),
+            size: std::mem::size_of::<VelocityParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let uniform = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("velocity_bind_group_layout"),
+            entries: &[
+                uniform(0),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                uniform(2),
+            ],
+        });
+        let bind_group = Self::create_bind_group(device, &layout, camera_buffer, depth_view, &params_buffer);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Velocity Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("velocity.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Velocity Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Velocity Pipeline"),
+            layout: Some(&pipeline_layout),
+            cache: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_fullscreen",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_velocity",
+                targets: &[Some(VELOCITY_FORMAT.into())],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            texture,
+            view,
+            params_buffer,
+            layout,
+            bind_group,
+            pipeline,
+            prev_view_proj: None,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        camera_buffer: &wgpu::Buffer,
+        depth_view: &wgpu::TextureView,
+        params_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("velocity_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Forgets the previous camera, for cuts where motion across the jump would be wrong.
+    pub fn reset(&mut self) {
+        self.prev_view_proj = None;
+    }
+
+    /// Reallocates the target for a new size, returning the old texture so it can be
+    /// destroyed once the GPU is done with it. Users of `view` must rebind afterwards.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        camera_buffer: &wgpu::Buffer,
+        depth_view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+    ) -> wgpu::Texture {
+        let (texture, view) = create_target(device, width, height);
+        self.view = view;
+        self.bind_group = Self::create_bind_group(device, &self.layout, camera_buffer, depth_view, &self.params_buffer);
+        std::mem::replace(&mut self.texture, texture)
+    }
+
+    /// Writes this frame's motion. The depth view must hold this frame's opaque depth, and
+    /// `view_proj` and `jitter` (in NDC) must match the camera buffer.
+    pub fn record(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        view_proj: [[f32; 4]; 4],
+        jitter: [f32; 2],
+    ) {
+        let unjittered = Matrix4::from_translation(Vector3::new(-jitter[0], -jitter[1], 0.0)) * Matrix4::from(view_proj);
+        let prev_view_proj = self.prev_view_proj.replace(unjittered).unwrap_or(unjittered);
+        let params = VelocityParams {
+            prev_view_proj: prev_view_proj.into(),
+            jitter: [jitter[0], jitter[1], 0.0, 0.0],
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Velocity"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
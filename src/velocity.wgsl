@@ -0,0 +1,52 @@
+// Per-pixel camera motion: the depth buffer is reprojected with last frame's
+// view-projection. Both frames are compared without their TAA jitter, so a still camera
+// gives zero motion.
+
+// Prefix of CameraUniform in shader.wgsl.
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    view_inv: mat4x4<f32>,
+    proj_inv: mat4x4<f32>,
+};
+
+struct VelocityParams {
+    // Without jitter.
+    prev_view_proj: mat4x4<f32>,
+    // xy: this frame's projection jitter in NDC.
+    jitter: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(0) @binding(1)
+var depth_map: texture_depth_2d;
+
+@group(0) @binding(2)
+var<uniform> params: VelocityParams;
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vid << 1u) & 2u), f32(vid & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_velocity(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(pos.xy);
+    let size = vec2<f32>(textureDimensions(depth_map));
+    let uv = pos.xy / size;
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, textureLoad(depth_map, pixel, 0), 1.0);
+    // The camera matrices carry the jitter, so they reconstruct the point the depth came from.
+    let view = camera.proj_inv * ndc;
+    // Kept homogeneous so the sky still moves with rotation, whether it sits on the far
+    // plane or, with an infinite far plane, at w = 0.
+    let world = camera.view_inv * view;
+    let prev = params.prev_view_proj * world;
+    if prev.w <= 0.0 {
+        return vec4<f32>(0.0);
+    }
+    let prev_ndc = prev.xy / prev.w;
+    let motion = (ndc.xy - params.jitter.xy - prev_ndc) * vec2<f32>(0.5, -0.5) * size;
+    return vec4<f32>(motion, 0.0, 0.0);
+}
@@ -0,0 +1,68 @@
+use std::borrow::Cow;
+
+use half::f16;
+
+use crate::model::Vertex;
+
+/// A `Vertex` in 20 bytes instead of 32: the normal octahedral-encoded into two snorm16s
+/// and the UVs as two float16s. Positions keep full floats.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CompactVertex {
+    position: [f32; 3],
+    normal: [i16; 2],
+    tex_coords: [u16; 2],
+}
+
+/// Folds a unit vector onto the [-1, 1] square: the upper hemisphere of the octahedron
+/// fills the inner diamond, the lower one the corners.
+fn oct_encode([x, y, z]: [f32; 3]) -> [f32; 2] {
+    let sum = x.abs() + y.abs() + z.abs();
+    if sum == 0.0 {
+        return [0.0, 0.0];
+    }
+    let (u, v) = (x / sum, y / sum);
+    if z >= 0.0 {
+        return [u, v];
+    }
+    let sign = |a: f32| if a >= 0.0 { 1.0 } else { -1.0 };
+    [(1.0 - v.abs()) * sign(u), (1.0 - u.abs()) * sign(v)]
+}
+
+fn snorm16(a: f32) -> i16 {
+    (a.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16
+}
+
+impl From<&Vertex> for CompactVertex {
+    fn from(v: &Vertex) -> Self {
+        let normal = oct_encode(v.normal);
+        Self {
+            position: v.position,
+            normal: normal.map(snorm16),
+            tex_coords: v.tex_coords.map(|t| f16::from_f32(t).to_bits()),
+        }
+    }
+}
+
+/// How vertices are laid out in GPU vertex buffers.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum VertexPacking {
+    /// `Vertex` as is.
+    #[default]
+    Full,
+    /// `CompactVertex`; the shaders decode it with `COMPACT_VERTICES` defined.
+    Compact,
+}
+
+impl VertexPacking {
+    /// Bytes of the vertex buffer holding `vertices`.
+    pub fn encode(self, vertices: &[Vertex]) -> Cow<'_, [u8]> {
+        match self {
+            Self::Full => Cow::Borrowed(bytemuck::cast_slice(vertices)),
+            Self::Compact => {
+                let compact: Vec<CompactVertex> = vertices.iter().map(CompactVertex::from).collect();
+                Cow::Owned(bytemuck::cast_slice(&compact).to_vec())
+            }
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Extra cameras drawn into regions of the window over the main view.
+
+use std::f32::consts::FRAC_PI_2;
+
+use cgmath::{InnerSpace, Matrix4, Point3, Quaternion, Rad, Rotation, Rotation3, SquareMatrix, Vector3};
+
+use crate::aabb::Aabb;
+use crate::camera::{Camera, CameraUniform, DepthMode};
+
+/// Distance between the eyes of `--stereo` when it gives none, in metres.
+pub const DEFAULT_IPD: f32 = 0.064;
+
+/// How a viewport's camera projects the scene.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    /// Perspective with a vertical field of view in degrees.
+    Perspective { fovy: f32 },
+    /// Parallel projection showing `height` world units from top to bottom.
+    Orthographic { height: f32 },
+    /// Off-axis perspective from the tangents of the angles to the left, right, top and
+    /// bottom edges, left and bottom negative: an XR eye's field of view.
+    OffAxis { tangents: [f32; 4] },
+}
+
+/// An eye as an XR runtime tracks it: its position and orientation in the runtime's
+/// space, in metres with Y up and -Z ahead, and the tangents of its field of view as
+/// [`Projection::OffAxis`] takes them.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TrackedEye {
+    pub position: Vector3<f32>,
+    pub orientation: Quaternion<f32>,
+    pub tangents: [f32; 4],
+}
+
+/// A camera drawn into part of the window, after the main view and before tonemapping.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Viewport {
+    pub position: Point3<f32>,
+    pub target: Point3<f32>,
+    pub up: Vector3<f32>,
+    pub projection: Projection,
+    pub znear: f32,
+    pub zfar: f32,
+    /// x, y, width and height as fractions of the window, from its top-left corner.
+    pub rect: [f32; 4],
+}
+
+impl Viewport {
+    /// An orthographic camera looking straight down on `bounds`, with all of it in view
+    /// and -Z towards the top.
+    pub fn top_down(bounds: &Aabb, rect: [f32; 4]) -> Self {
+        let (center, extent) = if bounds.is_finite() && !bounds.is_empty() {
+            (bounds.center(), bounds.extent())
+        } else {
+            (Point3::new(0.0, 0.0, 0.0), Vector3::new(20.0, 20.0, 20.0))
+        };
+        let aspect = (rect[2] / rect[3].max(1e-3)).max(1e-3);
+        let height = extent.z.max(extent.x / aspect).max(1.0) * 1.1;
+        let above = extent.y * 0.5 + 1.0;
+        Self {
+            position: center + Vector3::unit_y() * above,
+            target: center,
+            up: -Vector3::unit_z(),
+            projection: Projection::Orthographic { height },
+            znear: 0.1,
+            zfar: above + extent.y * 0.5 + 1.0,
+            rect,
+        }
+    }
+
+    /// One eye of a stereo pair looking where `head` looks, `ipd` metres apart, drawn
+    /// into `rect`. The left eye is offset to the head's left.
+    pub fn eye(head: &Camera, ipd: f32, left: bool, rect: [f32; 4]) -> Self {
+        let offset = head.right() * (if left { -0.5 } else { 0.5 } * ipd);
+        let forward = head.forward();
+        Self {
+            position: head.position + offset,
+            target: head.position + offset + forward,
+            up: head.right().cross(forward).normalize(),
+            projection: Projection::Perspective { fovy: head.fovy },
+            znear: head.znear,
+            zfar: head.zfar,
+            rect,
+        }
+    }
+
+    /// A tracked eye drawn into `rect`. The runtime's space is carried by `origin`: it
+    /// starts at the camera's position and turns with its heading, so flying the camera
+    /// moves the tracked head through the scene while the headset looks around.
+    pub fn tracked_eye(origin: &Camera, eye: &TrackedEye, rect: [f32; 4]) -> Self {
+        // The turn that takes the runtime's -Z to the camera's heading.
+        let heading = Quaternion::from_angle_y(Rad(-(origin.yaw + FRAC_PI_2)));
+        let orientation = heading * eye.orientation;
+        let position = origin.position + heading.rotate_vector(eye.position);
+        Self {
+            position,
+            target: position + orientation.rotate_vector(-Vector3::unit_z()),
+            up: orientation.rotate_vector(Vector3::unit_y()),
+            projection: Projection::OffAxis { tangents: eye.tangents },
+            znear: origin.znear,
+            zfar: origin.zfar,
+            rect,
+        }
+    }
+
+    /// The region in a target of `size` pixels as x, y, width and height; None when it
+    /// covers no pixels.
+    pub fn pixel_rect(&self, (width, height): (u32, u32)) -> Option<[u32; 4]> {
+        let [x, y, w, h] = self.rect;
+        let left = (x.clamp(0.0, 1.0) * width as f32).round() as u32;
+        let top = (y.clamp(0.0, 1.0) * height as f32).round() as u32;
+        let right = ((x + w).clamp(0.0, 1.0) * width as f32).round() as u32;
+        let bottom = ((y + h).clamp(0.0, 1.0) * height as f32).round() as u32;
+        (right > left && bottom > top).then_some([left, top, right - left, bottom - top])
+    }
+
+    /// Points `uniform`, a copy of the main camera's, at this camera for a region of
+    /// `aspect`. Screen-space effects and fog were computed for the main view, so they
+    /// are switched off.
+    pub fn update_uniform(&self, uniform: &mut CameraUniform, aspect: f32, depth_mode: DepthMode) {
+        let view = Matrix4::look_at_rh(self.position, self.target, self.up);
+        let proj = match self.projection {
+            Projection::Perspective { fovy } => depth_mode.perspective(fovy, aspect, self.znear, self.zfar),
+            Projection::Orthographic { height } => depth_mode.orthographic(height, aspect, self.znear, self.zfar),
+            Projection::OffAxis { tangents } => depth_mode.off_axis(tangents, self.znear, self.zfar),
+        };
+        let identity = Matrix4::identity();
+        uniform.view_proj = (proj * view).into();
+        uniform.view_inv = view.invert().unwrap_or(identity).into();
+        uniform.proj_inv = proj.invert().unwrap_or(identity).into();
+        uniform.position = [self.position.x, self.position.y, self.position.z, 1.0];
+        uniform.screen_space = [0.0; 4];
+        uniform.fog[0] = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{assert_abs_diff_eq, Deg};
+
+    use super::*;
+    use crate::camera::CameraSettings;
+
+    fn eye(position: Vector3<f32>, orientation: Quaternion<f32>) -> TrackedEye {
+        TrackedEye { position, orientation, tangents: [-1.0, 1.0, 1.0, -1.0] }
+    }
+
+    #[test]
+    fn tracked_eyes_turn_with_the_camera_heading() {
+        let mut camera = Camera::new(100, 100, &CameraSettings::default());
+        camera.set_look_at(Point3::new(1.0, 2.0, 3.0), Point3::new(11.0, 2.0, 3.0));
+        let still = Quaternion::new(1.0, 0.0, 0.0, 0.0);
+        let ahead = Viewport::tracked_eye(&camera, &eye(Vector3::new(0.1, 0.0, -1.0), still), [0.0; 4]);
+        assert_abs_diff_eq!(ahead.position, Point3::new(2.0, 2.0, 3.1), epsilon = 1e-5);
+        assert_abs_diff_eq!(ahead.target - ahead.position, Vector3::unit_x(), epsilon = 1e-5);
+        assert_abs_diff_eq!(ahead.up, Vector3::unit_y(), epsilon = 1e-5);
+
+        let left = Quaternion::from_angle_y(Deg(90.0));
+        let turned = Viewport::tracked_eye(&camera, &eye(Vector3::new(0.0, 0.0, 0.0), left), [0.0; 4]);
+        assert_abs_diff_eq!(turned.target - turned.position, -Vector3::unit_z(), epsilon = 1e-5);
+        assert_eq!(turned.projection, Projection::OffAxis { tangents: [-1.0, 1.0, 1.0, -1.0] });
+    }
+}
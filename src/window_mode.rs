@@ -0,0 +1,135 @@
+use winit::dpi::PhysicalSize;
+use winit::monitor::{MonitorHandle, VideoModeHandle};
+use winit::window::{Fullscreen, Window};
+
+/// How fullscreen covers the monitor.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum FullscreenMode {
+    /// A borderless window the size of the monitor, which keeps the desktop's video mode.
+    #[default]
+    Borderless,
+    /// The monitor switched to a video mode of the window's own.
+    Exclusive,
+}
+
+impl FullscreenMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "borderless" => Some(Self::Borderless),
+            "exclusive" => Some(Self::Exclusive),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the window is fullscreen, how, on which monitor, and the windowed size to go
+/// back to.
+#[derive(Debug)]
+pub struct WindowMode {
+    pub mode: FullscreenMode,
+    /// Index into the available monitors; None for the one the window is on.
+    pub monitor: Option<usize>,
+    /// Inner size before going fullscreen; Some while fullscreen.
+    windowed_size: Option<PhysicalSize<u32>>,
+}
+
+impl WindowMode {
+    pub fn new(mode: FullscreenMode, monitor: Option<usize>) -> Self {
+        Self {
+            mode,
+            monitor,
+            windowed_size: None,
+        }
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        self.windowed_size.is_some()
+    }
+
+    /// Goes fullscreen, or back to a window of the size it had before.
+    pub fn toggle(&mut self, window: &Window) {
+        if let Some(size) = self.windowed_size.take() {
+            window.set_fullscreen(None);
+            let _ = window.request_inner_size(size);
+            log::info!("Windowed at {}x{}", size.width, size.height);
+        } else {
+            self.enter(window);
+        }
+    }
+
+    /// Applies a new mode or monitor, going fullscreen again if the window already is.
+    pub fn set(&mut self, window: &Window, mode: FullscreenMode, monitor: Option<usize>) {
+        if (mode, monitor) == (self.mode, self.monitor) {
+            return;
+        }
+        self.mode = mode;
+        self.monitor = monitor;
+        if self.is_fullscreen() {
+            self.enter(window);
+        }
+    }
+
+    /// Remembers a size for when the window leaves fullscreen; false if it is windowed
+    /// and the size should apply now.
+    pub fn defer_windowed_size(&mut self, size: PhysicalSize<u32>) -> bool {
+        match &mut self.windowed_size {
+            Some(windowed) => {
+                *windowed = size;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn enter(&mut self, window: &Window) {
+        let Some(monitor) = self.pick_monitor(window) else {
+            log::warn!("No monitor to go fullscreen on");
+            return;
+        };
+        let name = monitor.name().unwrap_or_else(|| "monitor".to_string());
+        let fullscreen = match self.mode {
+            FullscreenMode::Exclusive => match best_video_mode(&monitor) {
+                Some(video_mode) => {
+                    let (size, hz) = (video_mode.size(), video_mode.refresh_rate_millihertz() as f32 / 1000.0);
+                    log::info!("Exclusive fullscreen on {} at {}x{} {:.0} Hz", name, size.width, size.height, hz);
+                    Fullscreen::Exclusive(video_mode)
+                }
+                None => {
+                    log::warn!("{} offers no video modes; using borderless fullscreen", name);
+                    Fullscreen::Borderless(Some(monitor))
+                }
+            },
+            FullscreenMode::Borderless => {
+                log::info!("Borderless fullscreen on {}", name);
+                Fullscreen::Borderless(Some(monitor))
+            }
+        };
+        self.windowed_size.get_or_insert(window.inner_size());
+        window.set_fullscreen(Some(fullscreen));
+    }
+
+    fn pick_monitor(&self, window: &Window) -> Option<MonitorHandle> {
+        if let Some(index) = self.monitor {
+            if let Some(monitor) = window.available_monitors().nth(index) {
+                return Some(monitor);
+            }
+            let names: Vec<_> = window
+                .available_monitors()
+                .enumerate()
+                .map(|(i, m)| format!("{} {}", i, m.name().unwrap_or_default()))
+                .collect();
+            log::warn!("No monitor {}; the monitors are: {}", index, names.join(", "));
+        }
+        window.current_monitor().or_else(|| window.primary_monitor())
+    }
+}
+
+/// The monitor's own resolution at its highest refresh rate, or failing that its
+/// largest mode.
+fn best_video_mode(monitor: &MonitorHandle) -> Option<VideoModeHandle> {
+    let native = monitor.size();
+    monitor.video_modes().max_by_key(|m| {
+        let size = m.size();
+        (size == native, size.width * size.height, m.bit_depth(), m.refresh_rate_millihertz())
+    })
+}
@@ -0,0 +1,326 @@
+use std::collections::{HashMap, HashSet};
+
+use cgmath::{InnerSpace, Matrix4, Point3, Vector3};
+
+use crate::camera::Camera;
+use crate::lights::SpotLightId;
+
+/// A thing in the scene, as an index into the component storages. The generation tells
+/// a despawned entity from a later one reusing its index.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+/// Object-to-world transform.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform(pub Matrix4<f32>);
+
+impl Transform {
+    pub fn position(&self) -> Point3<f32> {
+        Point3::new(self.0.w.x, self.0.w.y, self.0.w.z)
+    }
+
+    /// Where the local -Z axis points, the way cameras and lights look.
+    pub fn forward(&self) -> Vector3<f32> {
+        -self.0.z.truncate().normalize()
+    }
+}
+
+/// Transform relative to the `Parent`, or to the world without one. The world
+/// `Transform` of an entity with one is derived from it by `propagate_transforms`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LocalTransform(pub Matrix4<f32>);
+
+/// The entity this one hangs from in the scene graph.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Parent(pub Entity);
+
+/// A glTF node or model name, for finding entities with `World::find`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Name(pub String);
+
+/// A scene mesh of the renderer: its geometry, LODs and the object it is drawn with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MeshHandle(pub usize);
+
+/// Index into the renderer's materials.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MaterialHandle(pub usize);
+
+/// A spot light, placed and aimed by the entity's transform.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Light(pub SpotLightId);
+
+/// A component type and where the world keeps it.
+pub trait Component: Sized + 'static {
+    fn storage(world: &World) -> &Components<Self>;
+    fn storage_mut(world: &mut World) -> &mut Components<Self>;
+}
+
+macro_rules! component {
+    ($ty:ty, $field:ident) => {
+        impl Component for $ty {
+            fn storage(world: &World) -> &Components<Self> {
+                &world.$field
+            }
+
+            fn storage_mut(world: &mut World) -> &mut Components<Self> {
+                &mut world.$field
+            }
+        }
+    };
+}
+
+component!(Transform, transforms);
+component!(LocalTransform, locals);
+component!(Parent, parents);
+component!(Name, names);
+component!(MeshHandle, meshes);
+component!(MaterialHandle, materials);
+component!(Light, lights);
+component!(Camera, cameras);
+
+/// One component type of every entity, by entity index, and which changed since the
+/// systems last looked.
+pub struct Components<T> {
+    slots: Vec<Option<T>>,
+    changed: Vec<u32>,
+}
+
+impl<T> Default for Components<T> {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            changed: Vec::new(),
+        }
+    }
+}
+
+impl<T> Components<T> {
+    fn insert(&mut self, index: u32, value: T) -> Option<T> {
+        let i = index as usize;
+        if self.slots.len() <= i {
+            self.slots.resize_with(i + 1, || None);
+        }
+        self.changed.push(index);
+        self.slots[i].replace(value)
+    }
+
+    fn remove(&mut self, index: u32) -> Option<T> {
+        self.slots.get_mut(index as usize)?.take()
+    }
+
+    fn get(&self, index: u32) -> Option<&T> {
+        self.slots.get(index as usize)?.as_ref()
+    }
+
+    fn get_mut(&mut self, index: u32) -> Option<&mut T> {
+        let value = self.slots.get_mut(index as usize)?.as_mut()?;
+        self.changed.push(index);
+        Some(value)
+    }
+}
+
+/// What a despawned entity pointed at that systems have to release.
+pub struct Despawned {
+    pub light: Option<Light>,
+}
+
+/// Every entity of the scene and its components. Entities are spawned empty and get
+/// components inserted; systems walk one component type with `query` and look up the
+/// others with `get`. Inserting a component or borrowing it mutably marks it changed
+/// until `take_changed`.
+#[derive(Default)]
+pub struct World {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<u32>,
+    transforms: Components<Transform>,
+    locals: Components<LocalTransform>,
+    parents: Components<Parent>,
+    names: Components<Name>,
+    meshes: Components<MeshHandle>,
+    materials: Components<MaterialHandle>,
+    lights: Components<Light>,
+    cameras: Components<Camera>,
+    despawned: Vec<Despawned>,
+}
+
+impl World {
+    pub fn spawn(&mut self) -> Entity {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.generations.push(0);
+                self.alive.push(false);
+                (self.generations.len() - 1) as u32
+            }
+        };
+        self.alive[index as usize] = true;
+        Entity {
+            index,
+            generation: self.generations[index as usize],
+        }
+    }
+
+    /// Removes `entity` and all its components; false if it was already gone.
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.contains(entity) {
+            return false;
+        }
+        let index = entity.index;
+        self.alive[index as usize] = false;
+        self.generations[index as usize] += 1;
+        self.free.push(index);
+        self.transforms.remove(index);
+        self.locals.remove(index);
+        self.parents.remove(index);
+        self.names.remove(index);
+        self.meshes.remove(index);
+        self.materials.remove(index);
+        self.cameras.remove(index);
+        self.despawned.push(Despawned {
+            light: self.lights.remove(index),
+        });
+        true
+    }
+
+    /// Despawns `entity` and everything below it in the scene graph.
+    pub fn despawn_recursive(&mut self, entity: Entity) {
+        let children = self.children();
+        let mut stack = vec![entity];
+        while let Some(entity) = stack.pop() {
+            if self.despawn(entity) {
+                stack.extend(children.get(&entity).into_iter().flatten());
+            }
+        }
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        let i = entity.index as usize;
+        self.alive.get(i).copied().unwrap_or(false) && self.generations[i] == entity.generation
+    }
+
+    pub fn len(&self) -> usize {
+        self.alive.iter().filter(|&&alive| alive).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gives `entity` a component, returning the one it replaces; does nothing to a
+    /// despawned entity.
+    pub fn insert<T: Component>(&mut self, entity: Entity, component: T) -> Option<T> {
+        if !self.contains(entity) {
+            return None;
+        }
+        T::storage_mut(self).insert(entity.index, component)
+    }
+
+    pub fn remove<T: Component>(&mut self, entity: Entity) -> Option<T> {
+        if !self.contains(entity) {
+            return None;
+        }
+        T::storage_mut(self).remove(entity.index)
+    }
+
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
+        self.contains(entity).then(|| T::storage(self).get(entity.index)).flatten()
+    }
+
+    pub fn get_mut<T: Component>(&mut self, entity: Entity) -> Option<&mut T> {
+        if !self.contains(entity) {
+            return None;
+        }
+        T::storage_mut(self).get_mut(entity.index)
+    }
+
+    /// Every entity with a `T`, in spawn order of their indices.
+    pub fn query<T: Component>(&self) -> impl Iterator<Item = (Entity, &T)> {
+        T::storage(self).slots.iter().enumerate().filter_map(|(i, slot)| {
+            let entity = Entity {
+                index: i as u32,
+                generation: self.generations[i],
+            };
+            slot.as_ref().map(|component| (entity, component))
+        })
+    }
+
+    /// Entities whose `T` was inserted or borrowed mutably since the last call, once
+    /// each; despawned ones are left out.
+    pub fn take_changed<T: Component>(&mut self) -> Vec<Entity> {
+        let mut indices = std::mem::take(&mut T::storage_mut(self).changed);
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+            .into_iter()
+            .filter(|&index| T::storage(self).get(index).is_some())
+            .map(|index| Entity {
+                index,
+                generation: self.generations[index as usize],
+            })
+            .collect()
+    }
+
+    /// The first entity named `name`.
+    pub fn find(&self, name: &str) -> Option<Entity> {
+        self.query::<Name>().find(|(_, n)| n.0 == name).map(|(entity, _)| entity)
+    }
+
+    /// The children of every entity with any.
+    pub(crate) fn children(&self) -> HashMap<Entity, Vec<Entity>> {
+        let mut children: HashMap<Entity, Vec<Entity>> = HashMap::new();
+        for (entity, &Parent(parent)) in self.query::<Parent>() {
+            children.entry(parent).or_default().push(entity);
+        }
+        children
+    }
+
+    /// Updates the world `Transform` of the entities whose `LocalTransform` or `Parent`
+    /// changed since the last call, and of everything below them. A missing or despawned
+    /// parent counts as the world origin.
+    pub fn propagate_transforms(&mut self) {
+        let mut dirty = self.take_changed::<LocalTransform>();
+        dirty.extend(self.take_changed::<Parent>());
+        if dirty.is_empty() {
+            return;
+        }
+        let dirty_set: HashSet<Entity> = dirty.iter().copied().collect();
+        let children = self.children();
+        let mut visited = HashSet::new();
+        for root in dirty {
+            // Entities below another dirty one are updated with it.
+            let mut ancestor = self.get::<Parent>(root).map(|p| p.0);
+            let mut depth = 0;
+            while let Some(a) = ancestor.filter(|_| depth < self.generations.len()) {
+                if dirty_set.contains(&a) {
+                    break;
+                }
+                ancestor = self.get::<Parent>(a).map(|p| p.0);
+                depth += 1;
+            }
+            if ancestor.is_some_and(|a| dirty_set.contains(&a)) {
+                continue;
+            }
+            let mut stack = vec![root];
+            while let Some(entity) = stack.pop() {
+                if !visited.insert(entity) {
+                    continue;
+                }
+                let parent = self.get::<Parent>(entity).and_then(|&Parent(p)| self.get::<Transform>(p));
+                let parent = parent.map_or(Matrix4::from_scale(1.0), |t| t.0);
+                let local = self.get::<LocalTransform>(entity).map_or(Matrix4::from_scale(1.0), |l| l.0);
+                self.insert(entity, Transform(parent * local));
+                stack.extend(children.get(&entity).into_iter().flatten());
+            }
+        }
+    }
+
+    /// Components of the entities despawned since the last call.
+    pub fn take_despawned(&mut self) -> Vec<Despawned> {
+        std::mem::take(&mut self.despawned)
+    }
+}
@@ -0,0 +1,435 @@
+//! Rendering into an OpenXR headset, behind the `openxr` feature. The runtime creates the
+//! Vulkan instance and device, which are wrapped for wgpu; each frame the runtime's eye
+//! poses place the two stereo viewports, and the side-by-side frame they draw is copied
+//! into a swapchain image whose left and right halves are the eyes' views.
+
+use std::ffi::c_char;
+
+use anyhow::{Context, Result};
+use ash::vk::{self, Handle};
+use cgmath::{Quaternion, Vector3};
+use openxr as xr;
+use wgpu::hal::{self, api::Vulkan};
+
+use crate::gpu::{self, Gpu};
+use crate::viewport::TrackedEye;
+
+const VIEW_TYPE: xr::ViewConfigurationType = xr::ViewConfigurationType::PRIMARY_STEREO;
+
+struct Swapchain {
+    // The textures go before the swapchain that owns their images.
+    textures: Vec<wgpu::Texture>,
+    handle: xr::Swapchain<xr::Vulkan>,
+    size: (u32, u32),
+    format: wgpu::TextureFormat,
+    /// An image is acquired and waits to be released by [`XrSession::end_frame`].
+    acquired: bool,
+}
+
+/// A session with the runtime's headset, and the frame in progress.
+pub struct XrSession {
+    swapchain: Option<Swapchain>,
+    space: xr::Space,
+    frame_stream: xr::FrameStream<xr::Vulkan>,
+    frame_waiter: xr::FrameWaiter,
+    session: xr::Session<xr::Vulkan>,
+    events: xr::EventDataBuffer,
+    instance: xr::Instance,
+    system: xr::SystemId,
+    blend_mode: xr::EnvironmentBlendMode,
+    running: bool,
+    exited: bool,
+    /// The display time of the frame [`Self::begin_frame`] began, and the views located for
+    /// it when the runtime wants it drawn.
+    frame: Option<(xr::Time, Option<[xr::View; 2]>)>,
+}
+
+impl XrSession {
+    /// Starts a session with the runtime's headset on a Vulkan device the runtime opens,
+    /// with the features and limits [`gpu::request_device`] asks for.
+    pub fn new() -> Result<(Self, Gpu)> {
+        // SAFETY: the OpenXR loader is a system library meant to be loaded this way.
+        let entry = unsafe { xr::Entry::load() }.context("loading the OpenXR loader")?;
+        anyhow::ensure!(
+            entry.enumerate_extensions()?.khr_vulkan_enable2,
+            "the OpenXR runtime cannot render with Vulkan"
+        );
+        let mut extensions = xr::ExtensionSet::default();
+        extensions.khr_vulkan_enable2 = true;
+        let app = xr::ApplicationInfo {
+            application_name: "Dusk",
+            application_version: 0,
+            engine_name: "Dusk",
+            engine_version: 0,
+            api_version: xr::Version::new(1, 0, 0),
+        };
+        let instance = entry.create_instance(&app, &extensions, &[]).context("creating the OpenXR instance")?;
+        let system = instance.system(xr::FormFactor::HEAD_MOUNTED_DISPLAY).context("finding a headset")?;
+        let blend_modes = instance.enumerate_environment_blend_modes(system, VIEW_TYPE)?;
+        let blend_mode = blend_modes.first().copied().context("the runtime offers no blend mode")?;
+        let (gpu, session_info) = open_vulkan(&instance, system)?;
+        // SAFETY: the handles in `session_info` are the ones `open_vulkan` created through
+        // this instance, and they outlive the session.
+        let started = unsafe { instance.create_session::<xr::Vulkan>(system, &session_info) };
+        let (session, frame_waiter, frame_stream) = started.context("starting the session")?;
+        let space = session.create_reference_space(xr::ReferenceSpaceType::LOCAL, xr::Posef::IDENTITY)?;
+        let (runtime, headset) = (instance.properties()?, instance.system_properties(system)?);
+        log::info!("Rendering to {} through {} {}", headset.system_name, runtime.runtime_name, runtime.runtime_version);
+        let xr = Self {
+            swapchain: None,
+            space,
+            frame_stream,
+            frame_waiter,
+            session,
+            events: xr::EventDataBuffer::new(),
+            instance,
+            system,
+            blend_mode,
+            running: false,
+            exited: false,
+            frame: None,
+        };
+        Ok((xr, gpu))
+    }
+
+    /// The window size that gives each eye the runtime's recommended resolution, side by
+    /// side.
+    pub fn recommended_size(&self) -> Result<(u32, u32)> {
+        let views = self.instance.enumerate_view_configuration_views(self.system, VIEW_TYPE)?;
+        let eye = views.first().context("the runtime has no views")?;
+        Ok((eye.recommended_image_rect_width * 2, eye.recommended_image_rect_height))
+    }
+
+    /// The runtime ended the session, such as when the program was quit from the headset.
+    pub fn exited(&self) -> bool {
+        self.exited
+    }
+
+    /// Handles the runtime's events and begins its next frame, waiting until the runtime
+    /// is ready for it. The eyes tracked for the frame, or None when it shows nothing.
+    pub fn begin_frame(&mut self) -> Result<Option<[TrackedEye; 2]>> {
+        // A frame whose render failed still has to be ended before the next one.
+        self.end_frame()?;
+        while let Some(event) = self.instance.poll_event(&mut self.events)? {
+            match event {
+                xr::Event::SessionStateChanged(change) => match change.state() {
+                    xr::SessionState::READY => {
+                        self.session.begin(VIEW_TYPE)?;
+                        self.running = true;
+                    }
+                    xr::SessionState::STOPPING => {
+                        self.session.end()?;
+                        self.running = false;
+                    }
+                    xr::SessionState::EXITING | xr::SessionState::LOSS_PENDING => self.exited = true,
+                    _ => {}
+                },
+                xr::Event::InstanceLossPending(_) => self.exited = true,
+                _ => {}
+            }
+        }
+        if !self.running || self.exited {
+            return Ok(None);
+        }
+        let state = self.frame_waiter.wait()?;
+        self.frame_stream.begin()?;
+        let views = if state.should_render {
+            let (_, views) = self.session.locate_views(VIEW_TYPE, state.predicted_display_time, &self.space)?;
+            <[xr::View; 2]>::try_from(views).ok()
+        } else {
+            None
+        };
+        self.frame = Some((state.predicted_display_time, views));
+        Ok(views.map(|views| views.map(tracked_eye)))
+    }
+
+    /// Records copying `frame`, the side-by-side stereo frame, into a swapchain image for
+    /// the frame in progress, recreating the swapchain when the frame's size changed.
+    pub fn copy_frame(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        frame: &wgpu::Texture,
+    ) -> Result<()> {
+        if !matches!(self.frame, Some((_, Some(_)))) {
+            return Ok(());
+        }
+        let size = (frame.width(), frame.height());
+        if self.swapchain.as_ref().map(|s| (s.size, s.format)) != Some((size, frame.format())) {
+            self.swapchain = None;
+            self.swapchain = Some(self.create_swapchain(device, size, frame.format())?);
+        }
+        let Some(swapchain) = &mut self.swapchain else {
+            return Ok(());
+        };
+        let index = swapchain.handle.acquire_image()?;
+        swapchain.handle.wait_image(xr::Duration::INFINITE)?;
+        swapchain.acquired = true;
+        let target = &swapchain.textures[index as usize];
+        encoder.copy_texture_to_texture(frame.as_image_copy(), target.as_image_copy(), frame.size());
+        // The runtime takes the image back as a colour attachment; an empty pass leaves it
+        // in that layout.
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("XR Swapchain Layout"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+        Ok(())
+    }
+
+    /// Hands the copied image back and ends the frame in progress, showing each half of
+    /// the image to its eye. Called once the frame's commands are submitted.
+    pub fn end_frame(&mut self) -> Result<()> {
+        let Some((time, views)) = self.frame.take() else {
+            return Ok(());
+        };
+        let shown = match (&mut self.swapchain, views) {
+            (Some(swapchain), Some(views)) if swapchain.acquired => {
+                swapchain.handle.release_image()?;
+                swapchain.acquired = false;
+                Some((&*swapchain, views))
+            }
+            _ => None,
+        };
+        let Some((swapchain, views)) = shown else {
+            self.frame_stream.end(time, self.blend_mode, &[])?;
+            return Ok(());
+        };
+        let (width, height) = ((swapchain.size.0 / 2) as i32, swapchain.size.1 as i32);
+        let eye = |index: usize| {
+            let rect = xr::Rect2Di {
+                offset: xr::Offset2Di { x: index as i32 * width, y: 0 },
+                extent: xr::Extent2Di { width, height },
+            };
+            xr::CompositionLayerProjectionView::new()
+                .pose(views[index].pose)
+                .fov(views[index].fov)
+                .sub_image(xr::SwapchainSubImage::new().swapchain(&swapchain.handle).image_rect(rect))
+        };
+        let eyes = [eye(0), eye(1)];
+        let layer = xr::CompositionLayerProjection::new().space(&self.space).views(&eyes);
+        self.frame_stream.end(time, self.blend_mode, &[&layer])?;
+        Ok(())
+    }
+
+    fn create_swapchain(
+        &self,
+        device: &wgpu::Device,
+        (width, height): (u32, u32),
+        format: wgpu::TextureFormat,
+    ) -> Result<Swapchain> {
+        let vk_format = vk_format(format).with_context(|| format!("no OpenXR swapchain format for {:?}", format))?;
+        anyhow::ensure!(
+            self.session.enumerate_swapchain_formats()?.contains(&(vk_format.as_raw() as _)),
+            "the OpenXR runtime has no {:?} swapchains",
+            format
+        );
+        let handle = self.session.create_swapchain(&xr::SwapchainCreateInfo {
+            create_flags: xr::SwapchainCreateFlags::EMPTY,
+            usage_flags: xr::SwapchainUsageFlags::COLOR_ATTACHMENT | xr::SwapchainUsageFlags::TRANSFER_DST,
+            format: vk_format.as_raw() as _,
+            sample_count: 1,
+            width,
+            height,
+            face_count: 1,
+            array_size: 1,
+            mip_count: 1,
+        })?;
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let textures = handle
+            .enumerate_images()?
+            .into_iter()
+            .map(|image| {
+                let hal_descriptor = hal::TextureDescriptor {
+                    label: Some("XR Swapchain"),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: hal::TextureUses::COLOR_TARGET | hal::TextureUses::COPY_DST,
+                    memory_flags: hal::MemoryFlags::empty(),
+                    view_formats: Vec::new(),
+                };
+                // SAFETY: the runtime made the image on this device with this size, format
+                // and usage, and destroys it with the swapchain, so wgpu only gets a guard.
+                unsafe {
+                    let texture = hal::vulkan::Device::texture_from_raw(
+                        vk::Image::from_raw(image),
+                        &hal_descriptor,
+                        Some(Box::new(())),
+                    );
+                    device.create_texture_from_hal::<Vulkan>(
+                        texture,
+                        &wgpu::TextureDescriptor {
+                            label: Some("XR Swapchain"),
+                            size,
+                            mip_level_count: 1,
+                            sample_count: 1,
+                            dimension: wgpu::TextureDimension::D2,
+                            format,
+                            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_DST,
+                            view_formats: &[],
+                        },
+                    )
+                }
+            })
+            .collect();
+        log::info!("Created a {}x{} OpenXR swapchain", width, height);
+        Ok(Swapchain {
+            textures,
+            handle,
+            size: (width, height),
+            format,
+            acquired: false,
+        })
+    }
+}
+
+fn tracked_eye(view: xr::View) -> TrackedEye {
+    let (position, orientation, fov) = (view.pose.position, view.pose.orientation, view.fov);
+    TrackedEye {
+        position: Vector3::new(position.x, position.y, position.z),
+        orientation: Quaternion::new(orientation.w, orientation.x, orientation.y, orientation.z),
+        tangents: [fov.angle_left.tan(), fov.angle_right.tan(), fov.angle_up.tan(), fov.angle_down.tan()],
+    }
+}
+
+/// The Vulkan format of the surface formats wgpu picks.
+fn vk_format(format: wgpu::TextureFormat) -> Option<vk::Format> {
+    match format {
+        wgpu::TextureFormat::Bgra8UnormSrgb => Some(vk::Format::B8G8R8A8_SRGB),
+        wgpu::TextureFormat::Bgra8Unorm => Some(vk::Format::B8G8R8A8_UNORM),
+        wgpu::TextureFormat::Rgba8UnormSrgb => Some(vk::Format::R8G8B8A8_SRGB),
+        wgpu::TextureFormat::Rgba8Unorm => Some(vk::Format::R8G8B8A8_UNORM),
+        wgpu::TextureFormat::Rgba16Float => Some(vk::Format::R16G16B16A16_SFLOAT),
+        _ => None,
+    }
+}
+
+/// Has the runtime create a Vulkan instance and device with the extensions and features
+/// wgpu wants, and wraps them for wgpu. The runtime owns them, so wgpu never destroys them.
+fn open_vulkan(instance: &xr::Instance, system: xr::SystemId) -> Result<(Gpu, xr::vulkan::SessionCreateInfo)> {
+    let requirements = instance.graphics_requirements::<xr::Vulkan>(system)?;
+    // SAFETY: as for the OpenXR loader.
+    let entry = unsafe { ash::Entry::load() }.context("loading the Vulkan loader")?;
+    // SAFETY: a plain query of the loader.
+    let loader_version = unsafe { entry.try_enumerate_instance_version() }?.unwrap_or(vk::API_VERSION_1_0);
+    let api_version = loader_version.min(vk::API_VERSION_1_3);
+    let (major, minor) = (vk::api_version_major(api_version), vk::api_version_minor(api_version));
+    let version = xr::Version::new(major as u16, minor as u16, 0);
+    anyhow::ensure!(
+        version >= requirements.min_api_version_supported,
+        "the OpenXR runtime needs Vulkan {}, the loader has {}",
+        requirements.min_api_version_supported,
+        version
+    );
+    let get_instance_proc_addr = entry.static_fn().get_instance_proc_addr;
+    // SAFETY: the two are the same C function pointer type.
+    let get_instance_proc_addr = unsafe {
+        std::mem::transmute::<vk::PFN_vkGetInstanceProcAddr, xr::sys::platform::VkGetInstanceProcAddr>(
+            get_instance_proc_addr,
+        )
+    };
+
+    let flags = wgpu::InstanceFlags::from_build_config();
+    let instance_extensions = hal::vulkan::Instance::desired_extensions(&entry, api_version, flags)?;
+    let names: Vec<*const c_char> = instance_extensions.iter().map(|e| e.as_ptr()).collect();
+    let app = vk::ApplicationInfo::default().application_name(c"Dusk").engine_name(c"Dusk").api_version(api_version);
+    let create_info = vk::InstanceCreateInfo::default().application_info(&app).enabled_extension_names(&names);
+    // SAFETY: `create_info` and everything it points to outlive the call.
+    let raw_instance = unsafe {
+        instance.create_vulkan_instance(system, get_instance_proc_addr, &create_info as *const _ as *const _)
+    }?
+    .map_err(vk::Result::from_raw)
+    .context("creating the Vulkan instance")?;
+    // SAFETY: the runtime just created the instance with this loader.
+    let raw_instance = unsafe { ash::Instance::load(entry.static_fn(), vk::Instance::from_raw(raw_instance as _)) };
+    // SAFETY: `raw_instance` was created through this OpenXR instance.
+    let physical_device = unsafe { instance.vulkan_graphics_device(system, raw_instance.handle().as_raw() as _) }?;
+    let physical_device = vk::PhysicalDevice::from_raw(physical_device as _);
+
+    // SAFETY: the instance was created with the extensions given, and is never destroyed
+    // by wgpu, which holds a guard in its place.
+    let hal_instance = unsafe {
+        hal::vulkan::Instance::from_raw(
+            entry.clone(),
+            raw_instance.clone(),
+            api_version,
+            0,
+            None,
+            instance_extensions,
+            flags,
+            false,
+            Some(Box::new(())),
+        )
+    }?;
+    let exposed = hal_instance.expose_adapter(physical_device).context("wgpu cannot use the headset's GPU")?;
+    // SAFETY: the adapter was exposed by this instance.
+    let wgpu_instance = unsafe { wgpu::Instance::from_hal::<Vulkan>(hal_instance) };
+    let adapter = unsafe { wgpu_instance.create_adapter_from_hal(exposed) };
+    let descriptor = gpu::device_descriptor(&adapter);
+
+    let family = unsafe { raw_instance.get_physical_device_queue_family_properties(physical_device) }
+        .iter()
+        .position(|family| family.queue_flags.contains(vk::QueueFlags::GRAPHICS))
+        .context("the headset's GPU has no graphics queue")? as u32;
+    // SAFETY: the device is made on the adapter's physical device with the extensions and
+    // features the adapter lists, and handed to wgpu without ownership.
+    let (open_device, raw_device) = unsafe {
+        adapter.as_hal::<Vulkan, _, _>(|hal_adapter| -> Result<_> {
+            let hal_adapter = hal_adapter.context("the adapter is not a Vulkan one")?;
+            let features = descriptor.required_features;
+            let device_extensions = hal_adapter.required_device_extensions(features);
+            let mut physical_features = hal_adapter.physical_device_features(&device_extensions, features);
+            let names: Vec<*const c_char> = device_extensions.iter().map(|e| e.as_ptr()).collect();
+            let queues = [vk::DeviceQueueCreateInfo::default().queue_family_index(family).queue_priorities(&[1.0])];
+            let create_info = physical_features
+                .add_to_device_create(vk::DeviceCreateInfo::default().queue_create_infos(&queues))
+                .enabled_extension_names(&names);
+            let raw_device = instance
+                .create_vulkan_device(
+                    system,
+                    get_instance_proc_addr,
+                    physical_device.as_raw() as _,
+                    &create_info as *const _ as *const _,
+                )?
+                .map_err(vk::Result::from_raw)
+                .context("creating the Vulkan device")?;
+            let raw_device = ash::Device::load(raw_instance.fp_v1_0(), vk::Device::from_raw(raw_device as _));
+            let handle = raw_device.handle();
+            let open_device = hal_adapter.device_from_raw(
+                raw_device,
+                false,
+                &device_extensions,
+                features,
+                &descriptor.memory_hints,
+                family,
+                0,
+            )?;
+            Ok((open_device, handle))
+        })
+    }?;
+    // SAFETY: the device was opened on this adapter with at least the descriptor's features.
+    let (device, queue) = unsafe { adapter.create_device_from_hal(open_device, &descriptor, None) }?;
+    let session_info = xr::vulkan::SessionCreateInfo {
+        instance: raw_instance.handle().as_raw() as _,
+        physical_device: physical_device.as_raw() as _,
+        device: raw_device.as_raw() as _,
+        queue_family_index: family,
+        queue_index: 0,
+    };
+    Ok(((wgpu_instance, adapter, device, queue), session_info))
+}